@@ -2,11 +2,67 @@
 // SPDX-License-Identifier: GPL-3.0
 
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 
 const PROTO_DIR: &str = "./proto/";
 
+/// The message payload fields generated as `bytes::Bytes` rather than `Vec<u8>`, so that
+/// payloads are shared rather than copied as they are queued, leased and delivered.
+const PAYLOAD_FIELDS: [&str; 3] = [
+    ".pubsub.Message.data",
+    ".pubsub.ClaimResponse.data",
+    ".deliver.DeliverRequest.data",
+];
+
+/// Run the supplied command returning its trimmed stdout, or "unknown" if it fails.
+fn output(cmd: &str, args: &[&str]) -> String {
+    Command::new(cmd)
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|output| output.trim().to_string())
+        .unwrap_or_else(|| String::from("unknown"))
+}
+
+/// Expose the build information consumed by the `rift_build_info` metric.
+fn build_info() {
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| String::from("rustc"));
+    let profile = env::var("PROFILE").unwrap_or_else(|_| String::from("unknown"));
+
+    println!(
+        "cargo:rustc-env=RIFT_GIT_SHA={}",
+        output("git", &["rev-parse", "--short", "HEAD"])
+    );
+    println!(
+        "cargo:rustc-env=RIFT_RUSTC_VERSION={}",
+        output(&rustc, &["--version"])
+    );
+    println!("cargo:rustc-env=RIFT_BUILD_PROFILE={}", profile);
+
+    // Only watch the git metadata when it exists, as cargo treats missing paths as always
+    // changed, which would rebuild the crate on every invocation.
+    for path in [".git/HEAD", ".git/refs/heads"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed={}", PROTO_DIR);
+}
+
+/// Create the prost configuration shared by every proto file.
+fn config() -> prost_build::Config {
+    let mut config = prost_build::Config::new();
+    config.bytes(PAYLOAD_FIELDS);
+    config
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    build_info();
+
     let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
 
     let files = std::fs::read_dir(PROTO_DIR).expect("failed to list proto files.");
@@ -27,7 +83,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             .build_server(true)
             .file_descriptor_set_path(out_dir.join(descriptor_name))
             .format(true)
-            .compile(&[file], &[PROTO_DIR])?;
+            .compile_with_config(config(), &[file], &[PROTO_DIR])?;
     }
 
     Ok(())
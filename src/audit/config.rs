@@ -0,0 +1,32 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::path::PathBuf;
+
+// extern usings
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, StructOpt)]
+/// Rift audit configuration.
+pub struct Config {
+    #[structopt(
+        long = "audit-log",
+        env = "RIFT_AUDIT_LOG",
+        help = "The file to append control-plane audit events to.",
+        long_help = "Sets the file to append JSON formatted audit events to for every control-plane operation, use '-' to write them to stdout. Audit logging is disabled when no file is supplied.",
+        takes_value = true
+    )]
+    /// Define the file to append audit events to, if any.
+    pub path: Option<PathBuf>,
+
+    #[structopt(
+        long = "audit-topic",
+        env = "RIFT_AUDIT_TOPIC",
+        help = "Whether or not to publish audit events to the __audit topic.",
+        long_help = "Sets whether or not to publish every control-plane audit event as a message to the __audit topic, so that they can be consumed like any other message.",
+        takes_value = false
+    )]
+    /// Define whether or not to publish audit events to the audit topic.
+    pub topic: bool,
+}
@@ -0,0 +1,27 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::io;
+use std::path::PathBuf;
+use std::result;
+
+// extern usings
+use thiserror::Error;
+
+/// Custom Result wrapper to simplify usage.
+pub type Result<T> = result::Result<T, Error>;
+
+/// Represents audit errors based on user configuration or OS errors while
+/// attempting to open the audit log.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// Handles failures opening the configured audit log.
+    #[error("failed to open audit log '{}': {source}", path.display())]
+    Open {
+        /// path represents the audit log that failed to open.
+        path: PathBuf,
+        /// The initial error cause.
+        source: io::Error,
+    },
+}
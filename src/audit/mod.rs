@@ -0,0 +1,387 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// extern usings
+use bytes::Bytes;
+use prost_types::Timestamp;
+use slog::Drain;
+use slog_async::{AsyncGuard, OverflowStrategy};
+use tonic::{Request, Status};
+
+// crate usings
+use crate::grpc::interceptor::{IdentityExt, RequestIdExt};
+use crate::grpc::pubsub::Message;
+use crate::pubsub::Registry;
+
+mod config;
+mod error;
+
+pub use self::config::Config;
+pub use self::error::{Error, Result};
+
+/// The topic that audit events are published to, when enabled.
+pub const AUDIT_TOPIC: &str = "__audit";
+
+/// The identity recorded for callers which cannot be identified.
+const UNKNOWN: &str = "unknown";
+
+/// The identity recorded for operations riftd performs on its own.
+const SYSTEM: &str = "system";
+
+/// The control-plane operation performed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// A resource was created.
+    Create,
+    /// A resource was updated.
+    Update,
+    /// A resource was deleted.
+    Delete,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = match self {
+            Action::Create => "create",
+            Action::Update => "update",
+            Action::Delete => "delete",
+        };
+        f.write_str(action)
+    }
+}
+
+/// The kind of resource an operation was performed on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    /// A topic.
+    Topic,
+    /// A subscription, named as `topic/subscription`.
+    Subscription,
+}
+
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let resource = match self {
+            Resource::Topic => "topic",
+            Resource::Subscription => "subscription",
+        };
+        f.write_str(resource)
+    }
+}
+
+/// A single audited control-plane operation.
+#[derive(Debug, Clone)]
+pub struct Event {
+    /// The operation performed.
+    pub action: Action,
+    /// The kind of resource the operation was performed on.
+    pub resource: Resource,
+    /// The name of the resource the operation was performed on.
+    pub name: String,
+    /// The authenticated identity of the caller, or their remote address if they are
+    /// anonymous.
+    pub identity: String,
+    /// The ID of the request which performed the operation.
+    pub request_id: String,
+    /// When the operation was requested.
+    pub timestamp: SystemTime,
+}
+
+impl Event {
+    /// Create a new event for the supplied request.
+    pub fn new<T>(request: &Request<T>, action: Action, resource: Resource, name: String) -> Self {
+        let identity = match request.extensions().get::<IdentityExt>() {
+            Some(ext) => ext.identity.clone(),
+            None => request
+                .remote_addr()
+                .map(|addr| addr.to_string())
+                .unwrap_or_else(|| String::from(UNKNOWN)),
+        };
+        let request_id = request
+            .extensions()
+            .get::<RequestIdExt>()
+            .map(|ext| ext.id.clone())
+            .unwrap_or_else(|| String::from(UNKNOWN));
+        Self {
+            action,
+            resource,
+            name,
+            identity,
+            request_id,
+            timestamp: SystemTime::now(),
+        }
+    }
+
+    /// Create a new event for an operation riftd performed on its own, rather than on request.
+    pub fn system(action: Action, resource: Resource, name: String) -> Self {
+        Self {
+            action,
+            resource,
+            name,
+            identity: String::from(SYSTEM),
+            request_id: String::from(UNKNOWN),
+            timestamp: SystemTime::now(),
+        }
+    }
+
+    fn timestamp_ms(&self) -> u64 {
+        self.timestamp
+            .duration_since(UNIX_EPOCH)
+            .map(|ts| ts.as_millis() as u64)
+            .unwrap_or_default()
+    }
+
+    fn to_message(&self, code: &str) -> Message {
+        let mut attributes = HashMap::with_capacity(6);
+        attributes.insert(String::from("action"), self.action.to_string());
+        attributes.insert(String::from("resource"), self.resource.to_string());
+        attributes.insert(String::from("name"), self.name.clone());
+        attributes.insert(String::from("identity"), self.identity.clone());
+        attributes.insert(String::from("reqID"), self.request_id.clone());
+        attributes.insert(String::from("code"), code.to_string());
+        Message {
+            topic: String::from(AUDIT_TOPIC),
+            attributes,
+            published: Some(Timestamp::from(self.timestamp)),
+            data: Bytes::new(),
+            checksum: None,
+            content_type: String::new(),
+        }
+    }
+}
+
+/// The Auditor records control-plane operations to a dedicated JSON log, and optionally
+/// to the [AUDIT_TOPIC] topic. The default auditor records nothing.
+#[derive(Debug, Clone, Default)]
+pub struct Auditor {
+    logger: Option<slog::Logger>,
+    registry: Option<Registry<Message>>,
+    guard: Arc<Guard>,
+}
+
+/// Holds the guard of the audit log until it is flushed, after which the events still queued
+/// for it have been written.
+#[derive(Default)]
+struct Guard(Mutex<Option<AsyncGuard>>);
+
+impl fmt::Debug for Guard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flushed = self.0.lock().unwrap().is_none();
+        f.debug_struct("Guard").field("flushed", &flushed).finish()
+    }
+}
+
+impl Auditor {
+    /// Create a new auditor based on the supplied configuration, creating the audit topic
+    /// within the supplied registry if enabled.
+    pub fn new(cfg: &Config, registry: &Registry<Message>) -> Result<Self> {
+        let logger = match &cfg.path {
+            Some(path) if path.as_os_str() == "-" => Some(Self::logger(io::stdout())),
+            Some(path) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|source| Error::Open {
+                        path: path.clone(),
+                        source,
+                    })?;
+                Some(Self::logger(file))
+            }
+            None => None,
+        };
+        let (logger, guard) = match logger {
+            Some((logger, guard)) => (Some(logger), Some(guard)),
+            None => (None, None),
+        };
+        let registry = if cfg.topic {
+            registry.create(String::from(AUDIT_TOPIC));
+            Some(registry.clone())
+        } else {
+            None
+        };
+        Ok(Self {
+            logger,
+            registry,
+            guard: Arc::new(Guard(Mutex::new(guard))),
+        })
+    }
+
+    /// Create a new auditor which records events to the supplied logger.
+    pub fn with_logger(logger: slog::Logger) -> Self {
+        Self {
+            logger: Some(logger),
+            registry: None,
+            guard: Arc::default(),
+        }
+    }
+
+    /// Build the audit log writing to the supplied writer. Recording blocks while the queue of
+    /// events waiting to be written is full, rather than dropping audit events.
+    fn logger<W>(w: W) -> (slog::Logger, AsyncGuard)
+    where
+        W: io::Write + Send + 'static,
+    {
+        let drain = slog_json::Json::new(w).add_default_keys().build().fuse();
+        let (drain, guard) = slog_async::Async::new(drain)
+            .overflow_strategy(OverflowStrategy::Block)
+            .build_with_guard();
+        // Events recorded once the log is flushed are discarded, rather than panicking.
+        (slog::Logger::root(drain.ignore_res(), o!()), guard)
+    }
+
+    /// Wait for every event recorded to the audit log so far to be written, such as when
+    /// shutting down. Events recorded to the log afterwards are discarded.
+    pub fn flush(&self) {
+        self.guard.0.lock().unwrap().take();
+    }
+
+    /// Execute the supplied operation, recording its outcome alongside the supplied event.
+    pub async fn audit<R>(
+        &self,
+        event: Event,
+        op: impl std::future::Future<Output = std::result::Result<R, Status>>,
+    ) -> std::result::Result<R, Status> {
+        let res = op.await;
+        self.record(&event, res.as_ref().err());
+        res
+    }
+
+    /// Record the supplied event alongside the status it failed with, if any.
+    pub fn record(&self, event: &Event, status: Option<&Status>) {
+        let code = format!(
+            "{:?}",
+            status
+                .map(|status| status.code())
+                .unwrap_or(tonic::Code::Ok)
+        );
+        if let Some(logger) = &self.logger {
+            info!(logger, "Audited control-plane operation.";
+                "action" => event.action.to_string(),
+                "resource" => event.resource.to_string(),
+                "name" => &event.name,
+                "identity" => &event.identity,
+                "reqID" => &event.request_id,
+                "timestamp" => event.timestamp_ms(),
+                "code" => &code,
+            );
+        }
+        if let Some(topic) = self
+            .registry
+            .as_ref()
+            .and_then(|registry| registry.get(AUDIT_TOPIC))
+        {
+            // Publishing only fails when the audit topic has no subscriptions, in which case
+            // there is nobody to deliver the event to.
+            let _ = topic.push(event.to_message(&code));
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event() {
+        let mut request = Request::new(());
+        request.extensions_mut().insert(RequestIdExt {
+            id: String::from("woot"),
+        });
+        let event = Event::new(
+            &request,
+            Action::Create,
+            Resource::Topic,
+            String::from("topic"),
+        );
+        assert_eq!(event.identity, UNKNOWN);
+        assert_eq!(event.request_id, "woot");
+
+        request.extensions_mut().insert(IdentityExt {
+            identity: String::from("alice"),
+        });
+        let event = Event::new(&request, Action::Create, Resource::Topic, String::from("t"));
+        assert_eq!(event.identity, "alice");
+
+        let msg = event.to_message("Ok");
+        assert_eq!(msg.topic, AUDIT_TOPIC);
+        assert_eq!(msg.attributes["action"], "create");
+        assert_eq!(msg.attributes["resource"], "topic");
+        assert_eq!(msg.attributes["reqID"], "woot");
+
+        let event = Event::system(
+            Action::Delete,
+            Resource::Subscription,
+            String::from("topic/sub"),
+        );
+        assert_eq!(event.identity, SYSTEM);
+        assert_eq!(event.request_id, UNKNOWN);
+    }
+
+    #[test]
+    fn test_auditor() {
+        let registry = Registry::default();
+        let cfg = Config {
+            path: None,
+            topic: true,
+        };
+        let auditor = Auditor::new(&cfg, &registry).unwrap();
+        let audit = registry.get(AUDIT_TOPIC).unwrap();
+        let sub = audit.create(String::from("sub"));
+
+        let event = Event::new(
+            &Request::new(()),
+            Action::Delete,
+            Resource::Subscription,
+            String::from("topic/sub"),
+        );
+        auditor.record(&event, Some(&Status::not_found("nope")));
+
+        let (_, _, msg) = sub.queue.next().unwrap();
+        assert_eq!(msg.attributes["action"], "delete");
+        assert_eq!(msg.attributes["code"], "NotFound");
+
+        let cfg = Config {
+            path: Some(std::path::PathBuf::from("/nonexistent/audit.log")),
+            topic: false,
+        };
+        assert!(matches!(
+            Auditor::new(&cfg, &registry),
+            Err(Error::Open { .. })
+        ));
+
+        let auditor = Auditor::with_logger(slog::Logger::root(slog::Discard, o!()));
+        auditor.record(&event, None);
+    }
+
+    #[test]
+    fn test_auditor_flush() {
+        let path = std::env::temp_dir().join(format!("rift-audit-{}.log", uuid::Uuid::new_v4()));
+        let cfg = Config {
+            path: Some(path.clone()),
+            topic: false,
+        };
+        let auditor = Auditor::new(&cfg, &Registry::default()).unwrap();
+        let event = Event::system(Action::Create, Resource::Topic, String::from("orders"));
+        for _ in 0..100 {
+            auditor.clone().record(&event, None);
+        }
+
+        // Flushing writes every queued event, and events recorded afterwards are discarded.
+        auditor.flush();
+        auditor.record(&event, None);
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(written.lines().count(), 100);
+        assert!(written.lines().all(|line| line.contains("\"orders\"")));
+    }
+}
@@ -0,0 +1,22 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::path::PathBuf;
+
+// extern usings
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, StructOpt)]
+/// Rift authentication and authorization configuration.
+pub struct Config {
+    #[structopt(
+        long = "auth-file",
+        env = "RIFT_AUTH_FILE",
+        help = "The policy file of the identities allowed to call riftd.",
+        long_help = "Sets the JSON policy file declaring each identity, the bearer tokens and API keys it authenticates with, and the actions it is allowed to perform, over both gRPC and HTTP. Cluster members call each other without credentials, so clustered deployments must grant the `cluster` action to anonymous callers. Every caller is allowed every action when no file is supplied.",
+        takes_value = true
+    )]
+    /// Define the policy file of the identities allowed to call riftd, if any.
+    pub auth_file: Option<PathBuf>,
+}
@@ -0,0 +1,53 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::io;
+use std::result;
+
+// crate usings
+use super::Action;
+
+// extern usings
+use thiserror::Error;
+
+/// Custom Result wrapper to simplify usage.
+pub type Result<T> = result::Result<T, Error>;
+
+/// Represents errors loading an auth policy, and authenticating or authorizing callers.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// Handles failures reading the policy file.
+    #[error("failed to read '{path}': {source}")]
+    Io {
+        /// The path that failed to be read.
+        path: String,
+        /// The initial error cause.
+        source: io::Error,
+    },
+    /// Handles policies which are not valid JSON.
+    #[error("failed to parse the auth policy: {0}")]
+    Parse(#[from] serde_json::Error),
+    /// Handles policies which do not describe a valid set of identities.
+    #[error("invalid auth policy at '{location}': {reason}")]
+    Invalid {
+        /// The location of the invalid entry within the policy.
+        location: String,
+        /// The reason the entry is invalid.
+        reason: String,
+    },
+    /// Handles callers supplying missing, malformed, or unknown credentials.
+    #[error("the caller is not authenticated: {reason}")]
+    Unauthenticated {
+        /// The reason the caller could not be authenticated.
+        reason: &'static str,
+    },
+    /// Handles callers which are not allowed to perform an action.
+    #[error("the caller '{identity}' is not granted the '{action}' action")]
+    PermissionDenied {
+        /// The identity of the caller, or `anonymous` for callers without credentials.
+        identity: String,
+        /// The action the caller attempted.
+        action: Action,
+    },
+}
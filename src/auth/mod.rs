@@ -0,0 +1,136 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+mod config;
+mod error;
+mod policy;
+
+use std::sync::Arc;
+
+pub use config::Config;
+pub use error::{Error, Result};
+pub use policy::{Action, Policy, ANONYMOUS};
+
+/// The header bearer tokens are supplied under, as `Bearer <token>`.
+pub const AUTHORIZATION_HEADER: &str = "authorization";
+/// The header API keys are supplied under.
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Auth makes the authentication and authorization decisions for every request, regardless
+/// of whether it arrived over gRPC or HTTP. Without a [Policy] every caller is anonymous and
+/// allowed every action. Clones share the same policy.
+#[derive(Debug, Clone, Default)]
+pub struct Auth {
+    policy: Option<Arc<Policy>>,
+}
+
+impl Auth {
+    /// Create a new auth enforcing the policy file of the supplied configuration, if any.
+    pub fn new(cfg: &Config) -> Result<Self> {
+        let policy = match &cfg.auth_file {
+            Some(path) => Some(Policy::load(path)?),
+            None => None,
+        };
+        Ok(Self {
+            policy: policy.map(Arc::new),
+        })
+    }
+
+    /// Create a new auth enforcing the supplied policy.
+    pub fn with_policy(policy: Policy) -> Self {
+        Self {
+            policy: Some(Arc::new(policy)),
+        }
+    }
+
+    /// Return whether a policy is enforced.
+    pub fn is_enabled(&self) -> bool {
+        self.policy.is_some()
+    }
+
+    /// Authenticate the caller of a request from its headers, looked up with the supplied
+    /// function, and authorize it to perform the supplied action. Returns the identity of the
+    /// caller, or [None] if it is anonymous. Requests performing no action are not checked.
+    pub fn check<'a, F>(&self, header: F, action: Option<Action>) -> Result<Option<String>>
+    where
+        F: Fn(&str) -> Option<&'a str>,
+    {
+        let (policy, action) = match (&self.policy, action) {
+            (Some(policy), Some(action)) => (policy, action),
+            _ => return Ok(None),
+        };
+        let unauthenticated = |reason| Error::Unauthenticated { reason };
+        let identity = match (header(AUTHORIZATION_HEADER), header(API_KEY_HEADER)) {
+            (Some(authorization), _) => {
+                let token = authorization.strip_prefix("Bearer ").ok_or_else(|| {
+                    unauthenticated("the authorization header must be a bearer token")
+                })?;
+                let identity = policy.token(token.trim());
+                Some(identity.ok_or_else(|| unauthenticated("unknown bearer token"))?)
+            }
+            (None, Some(key)) => {
+                let identity = policy.api_key(key);
+                Some(identity.ok_or_else(|| unauthenticated("unknown API key"))?)
+            }
+            (None, None) => None,
+        };
+        policy.authorize(identity, action)?;
+        Ok(identity.map(str::to_owned))
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_check() {
+        let policy = Policy::parse(
+            br#"{"identities": [{"name": "alice", "tokens": ["t"], "api_keys": ["k"], "actions": ["consume"]}]}"#,
+        )
+        .unwrap();
+        let auth = Auth::with_policy(policy);
+        let check = |headers: &[(&str, &str)], action| {
+            let headers: HashMap<_, _> = headers.iter().copied().collect();
+            auth.check(|name| headers.get(name).copied(), action)
+        };
+
+        assert_eq!(
+            check(&[("authorization", "Bearer t")], Some(Action::Consume)).unwrap(),
+            Some(String::from("alice"))
+        );
+        assert_eq!(
+            check(&[("x-api-key", "k")], Some(Action::Consume)).unwrap(),
+            Some(String::from("alice"))
+        );
+        assert!(matches!(
+            check(&[("authorization", "Basic t")], Some(Action::Consume)),
+            Err(Error::Unauthenticated { .. })
+        ));
+        assert!(matches!(
+            check(&[("x-api-key", "nope")], Some(Action::Consume)),
+            Err(Error::Unauthenticated { .. })
+        ));
+        assert!(matches!(
+            check(&[("x-api-key", "k")], Some(Action::Publish)),
+            Err(Error::PermissionDenied { .. })
+        ));
+        assert!(matches!(
+            check(&[], Some(Action::Consume)),
+            Err(Error::PermissionDenied { .. })
+        ));
+        assert_eq!(check(&[("x-api-key", "nope")], None).unwrap(), None);
+
+        let disabled = Auth::default();
+        assert!(!disabled.is_enabled());
+        assert_eq!(
+            disabled
+                .check(|_| Some("Basic nope"), Some(Action::Admin))
+                .unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,338 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+// crate usings
+use super::{Error, Result};
+
+// extern usings
+use serde_json::{Map, Value};
+
+/// The identity reported for callers which supplied no credentials.
+pub const ANONYMOUS: &str = "anonymous";
+
+/// A class of operations which callers are granted as a whole.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    /// Publishing messages.
+    Publish,
+    /// Subscribing to, settling, peeking and redriving messages.
+    Consume,
+    /// Managing topics and subscriptions.
+    Admin,
+    /// Operating riftd itself over HTTP, such as changing its log level or inspecting queues.
+    Operate,
+    /// Calls made between cluster members.
+    Cluster,
+}
+
+impl Action {
+    /// Every action, in the order they are documented.
+    pub const ALL: [Action; 5] = [
+        Action::Publish,
+        Action::Consume,
+        Action::Admin,
+        Action::Operate,
+        Action::Cluster,
+    ];
+
+    /// Return the stable string representation of this action.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Action::Publish => "publish",
+            Action::Consume => "consume",
+            Action::Admin => "admin",
+            Action::Operate => "operate",
+            Action::Cluster => "cluster",
+        }
+    }
+
+    /// Return the action the gRPC method with the supplied path performs, or [None] for
+    /// methods every caller may call, such as health checks and reflection. Unknown methods
+    /// require [Action::Admin].
+    ///
+    /// ```
+    /// use librift::auth::Action;
+    ///
+    /// assert_eq!(
+    ///     Action::of_grpc("/pubsub.PubSubService/Publish"),
+    ///     Some(Action::Publish)
+    /// );
+    /// assert_eq!(Action::of_grpc("/grpc.health.v1.Health/Check"), None);
+    /// ```
+    pub fn of_grpc(path: &str) -> Option<Action> {
+        let (service, method) = path
+            .trim_start_matches('/')
+            .split_once('/')
+            .unwrap_or((path, ""));
+        match service {
+            "grpc.health.v1.Health" | "grpc.reflection.v1alpha.ServerReflection" => None,
+            "pubsub.PubSubService" if method == "Publish" => Some(Action::Publish),
+            "pubsub.PubSubService" => Some(Action::Consume),
+            "cluster.ClusterService" => Some(Action::Cluster),
+            _ => Some(Action::Admin),
+        }
+    }
+
+    /// Return the action the HTTP request to the supplied path performs, or [None] for the
+    /// probe and metric endpoints every caller may request.
+    pub fn of_http(path: &str) -> Option<Action> {
+        match path {
+            "/metrics" | "/live" | "/ready" => None,
+            _ => Some(Action::Operate),
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Action {
+    type Err = ();
+
+    fn from_str(action: &str) -> std::result::Result<Action, ()> {
+        Action::ALL
+            .into_iter()
+            .find(|candidate| candidate.as_str() == action)
+            .ok_or(())
+    }
+}
+
+/// An authentication and authorization policy, mapping credentials to the identities they
+/// authenticate, and identities to the actions they are granted.
+///
+/// Policies are JSON documents of the form:
+///
+/// ```json
+/// {
+///   "identities": [
+///     {"name": "billing", "tokens": ["s3cr3t"], "api_keys": [], "actions": ["publish", "consume"]},
+///     {"name": "ops", "tokens": ["0p5"], "actions": ["*"]}
+///   ],
+///   "anonymous": ["cluster"]
+/// }
+/// ```
+///
+/// Tokens are supplied as `authorization: Bearer <token>`, and API keys as `x-api-key: <key>`.
+/// The `*` action grants every action. Callers supplying no credentials are granted only the
+/// actions listed under `anonymous`, which defaults to none.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Policy {
+    tokens: HashMap<String, String>,
+    api_keys: HashMap<String, String>,
+    grants: HashMap<String, HashSet<Action>>,
+    anonymous: HashSet<Action>,
+}
+
+impl Policy {
+    /// Load the policy at the supplied path.
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path).map_err(|source| Error::Io {
+            path: path.display().to_string(),
+            source,
+        })?;
+        Self::parse(&data)
+    }
+
+    /// Parse the supplied policy.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let root: Value = serde_json::from_slice(data)?;
+        let root = match root {
+            Value::Object(root) => root,
+            _ => return Err(invalid("", "expected an object")),
+        };
+
+        let mut policy = Policy {
+            anonymous: actions(&root, "anonymous", "")?,
+            ..Default::default()
+        };
+        for (idx, identity) in array(&root, "identities", "")?.iter().enumerate() {
+            let location = format!("identities[{}]", idx);
+            let identity = match identity {
+                Value::Object(identity) => identity,
+                _ => return Err(invalid(&location, "expected an object")),
+            };
+            let name = match identity.get("name") {
+                Some(Value::String(name)) if !name.is_empty() => name.clone(),
+                _ => return Err(invalid(&location, "'name' must be a non-empty string")),
+            };
+            if name == ANONYMOUS || policy.grants.contains_key(&name) {
+                return Err(invalid(&location, "duplicate or reserved name"));
+            }
+            for (field, credentials) in [
+                ("tokens", &mut policy.tokens),
+                ("api_keys", &mut policy.api_keys),
+            ] {
+                for (idx, credential) in array(identity, field, &location)?.iter().enumerate() {
+                    let location = format!("{}.{}[{}]", location, field, idx);
+                    let credential = match credential {
+                        Value::String(credential) if !credential.is_empty() => credential,
+                        _ => return Err(invalid(&location, "expected a non-empty string")),
+                    };
+                    if credentials
+                        .insert(credential.clone(), name.clone())
+                        .is_some()
+                    {
+                        return Err(invalid(&location, "duplicate credential"));
+                    }
+                }
+            }
+            let granted = actions(identity, "actions", &location)?;
+            policy.grants.insert(name, granted);
+        }
+        Ok(policy)
+    }
+
+    /// Return the identity authenticated by the supplied bearer token, if any.
+    pub fn token(&self, token: &str) -> Option<&str> {
+        self.tokens.get(token).map(String::as_str)
+    }
+
+    /// Return the identity authenticated by the supplied API key, if any.
+    pub fn api_key(&self, key: &str) -> Option<&str> {
+        self.api_keys.get(key).map(String::as_str)
+    }
+
+    /// Check that the supplied identity, or an anonymous caller if [None], is granted the
+    /// supplied action.
+    pub fn authorize(&self, identity: Option<&str>, action: Action) -> Result<()> {
+        let granted = match identity {
+            Some(identity) => {
+                matches!(self.grants.get(identity), Some(grants) if grants.contains(&action))
+            }
+            None => self.anonymous.contains(&action),
+        };
+        if !granted {
+            return Err(Error::PermissionDenied {
+                identity: identity.unwrap_or(ANONYMOUS).to_owned(),
+                action,
+            });
+        }
+        Ok(())
+    }
+}
+
+fn invalid(location: &str, reason: &str) -> Error {
+    Error::Invalid {
+        location: location.to_owned(),
+        reason: reason.to_owned(),
+    }
+}
+
+/// Return the array under the supplied key, or an empty array if it is missing.
+fn array<'a>(object: &'a Map<String, Value>, key: &str, location: &str) -> Result<&'a [Value]> {
+    match object.get(key) {
+        Some(Value::Array(values)) => Ok(values),
+        Some(_) => Err(invalid(location, &format!("'{}' must be an array", key))),
+        None => Ok(&[]),
+    }
+}
+
+/// Return the set of actions listed under the supplied key, expanding `*` to every action.
+fn actions(object: &Map<String, Value>, key: &str, location: &str) -> Result<HashSet<Action>> {
+    let mut actions = HashSet::new();
+    for (idx, action) in array(object, key, location)?.iter().enumerate() {
+        match action.as_str() {
+            Some("*") => actions.extend(Action::ALL),
+            Some(action) if action.parse::<Action>().is_ok() => {
+                actions.insert(action.parse().unwrap());
+            }
+            _ => {
+                let location = format!("{}.{}[{}]", location, key, idx);
+                let location = location.trim_start_matches('.');
+                return Err(invalid(location, "unknown action"));
+            }
+        }
+    }
+    Ok(actions)
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    const POLICY: &str = r#"{
+        "identities": [
+            {"name": "billing", "tokens": ["t1"], "api_keys": ["k1"], "actions": ["publish"]},
+            {"name": "ops", "tokens": ["t2"], "actions": ["*"]}
+        ],
+        "anonymous": ["cluster"]
+    }"#;
+
+    #[test]
+    fn test_parse() {
+        let policy = Policy::parse(POLICY.as_bytes()).unwrap();
+        assert_eq!(policy.token("t1"), Some("billing"));
+        assert_eq!(policy.api_key("k1"), Some("billing"));
+        assert_eq!(policy.token("k1"), None);
+
+        assert!(policy.authorize(Some("billing"), Action::Publish).is_ok());
+        assert!(matches!(
+            policy.authorize(Some("billing"), Action::Consume),
+            Err(Error::PermissionDenied { identity, action: Action::Consume }) if identity == "billing"
+        ));
+        for action in Action::ALL {
+            assert!(policy.authorize(Some("ops"), action).is_ok());
+        }
+        assert!(policy.authorize(None, Action::Cluster).is_ok());
+        assert!(matches!(
+            policy.authorize(None, Action::Publish),
+            Err(Error::PermissionDenied { identity, .. }) if identity == ANONYMOUS
+        ));
+        assert!(policy.authorize(Some("nobody"), Action::Publish).is_err());
+
+        let invalid = [
+            (r#"[]"#, ""),
+            (r#"{"anonymous": ["nope"]}"#, "anonymous[0]"),
+            (r#"{"identities": [{"tokens": ["t"]}]}"#, "identities[0]"),
+            (
+                r#"{"identities": [{"name": "a", "tokens": ["t", "t"]}]}"#,
+                "identities[0].tokens[1]",
+            ),
+            (
+                r#"{"identities": [{"name": "a"}, {"name": "a"}]}"#,
+                "identities[1]",
+            ),
+            (
+                r#"{"identities": [{"name": "a", "actions": "publish"}]}"#,
+                "identities[0]",
+            ),
+        ];
+        for (src, expected) in invalid {
+            match Policy::parse(src.as_bytes()) {
+                Err(Error::Invalid { location, .. }) => assert_eq!(location, expected, "{}", src),
+                res => panic!("expected '{}' to be invalid, got {:?}", src, res),
+            }
+        }
+    }
+
+    #[test]
+    fn test_classify() {
+        let cases = [
+            ("/pubsub.PubSubService/Publish", Some(Action::Publish)),
+            ("/pubsub.PubSubService/Subscribe", Some(Action::Consume)),
+            ("/topic.TopicService/Create", Some(Action::Admin)),
+            ("/subscription.SubscriptionService/Get", Some(Action::Admin)),
+            ("/tenant.TenantService/ListUsage", Some(Action::Admin)),
+            ("/backup.BackupService/Restore", Some(Action::Admin)),
+            ("/cluster.ClusterService/Heartbeat", Some(Action::Cluster)),
+            ("/grpc.health.v1.Health/Watch", None),
+            ("/unknown.Service/Method", Some(Action::Admin)),
+        ];
+        for (path, action) in cases {
+            assert_eq!(Action::of_grpc(path), action, "{}", path);
+        }
+        assert_eq!(Action::of_http("/ready"), None);
+        assert_eq!(Action::of_http("/log/level"), Some(Action::Operate));
+        assert_eq!(Action::of_http("/debug/queues/a/b"), Some(Action::Operate));
+    }
+}
@@ -1,8 +1,7 @@
 // (c) Copyright 2021-2022 Christian Saide
 // SPDX-License-Identifier: GPL-3.0
 
-#[tokio::main]
-async fn main() {
-    let code = librift::riftd::run().await;
+fn main() {
+    let code = librift::riftd::run();
     std::process::exit(code)
 }
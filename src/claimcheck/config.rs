@@ -0,0 +1,44 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::path::PathBuf;
+
+// extern usings
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, StructOpt)]
+/// Rift claim check configuration.
+pub struct Config {
+    #[structopt(
+        long = "claim-check-dir",
+        env = "RIFT_CLAIM_CHECK_DIR",
+        help = "The directory to store oversized message payloads in.",
+        long_help = "Sets the directory that the payloads of published messages exceeding the claim check threshold are stored in, leaving only a pointer to the payload in the queue. Payloads are always queued in memory when no directory is supplied.",
+        takes_value = true
+    )]
+    /// Define the directory to store oversized message payloads in, if any.
+    pub dir: Option<PathBuf>,
+
+    #[structopt(
+        long = "claim-check-threshold-bytes",
+        env = "RIFT_CLAIM_CHECK_THRESHOLD_BYTES",
+        help = "The payload size above which payloads are stored in the claim check directory.",
+        long_help = "Sets the size in bytes above which the payload of a published message is moved to the claim check directory.",
+        default_value = "1048576",
+        takes_value = true
+    )]
+    /// Define the payload size above which payloads are claim checked.
+    pub threshold_bytes: usize,
+
+    #[structopt(
+        long = "claim-check-retention-ms",
+        env = "RIFT_CLAIM_CHECK_RETENTION_MS",
+        help = "The time claim checked payloads are retained for.",
+        long_help = "Sets the time in milliseconds that claim checked payloads are retained for after being published, after which they are deleted and can no longer be claimed.",
+        default_value = "604800000",
+        takes_value = true
+    )]
+    /// Define the time claim checked payloads are retained for.
+    pub retention_ms: u64,
+}
@@ -0,0 +1,32 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::io;
+use std::result;
+
+// extern usings
+use thiserror::Error;
+
+/// Custom Result wrapper to simplify usage.
+pub type Result<T> = result::Result<T, Error>;
+
+/// Represents claim check errors based on missing payloads or failures accessing the store.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// Handles claims for payloads which do not exist, or are past their retention.
+    #[error("the claim checked payload '{key}' does not exist")]
+    NotFound {
+        /// The key of the missing payload.
+        key: String,
+    },
+    /// Handles payloads which no longer match the checksum they were stored with.
+    #[error("the claim checked payload '{key}' is corrupted")]
+    Corrupted {
+        /// The key of the corrupted payload.
+        key: String,
+    },
+    /// Handles failures reading or writing payloads.
+    #[error("failed to access the claim check store: {0}")]
+    Io(#[from] io::Error),
+}
@@ -0,0 +1,14 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+mod config;
+mod error;
+mod store;
+
+pub use config::Config;
+pub use error::{Error, Result};
+pub use store::Store;
+
+/// The attribute carrying the claim check key of a message whose payload was moved to the
+/// claim check store. The data of such a message is the key itself.
+pub const CLAIM_CHECK_ATTRIBUTE: &str = "x-rift-claim-check";
@@ -0,0 +1,295 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+// crate usings
+use super::{Config, Error, Result};
+
+// extern usings
+use prometheus::IntCounter;
+use uuid::Uuid;
+
+/// How often payloads past their retention are deleted.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The length of the CRC32C checksum each stored payload is prefixed with.
+const CHECKSUM_LEN: usize = 4;
+
+/// Stores oversized message payloads in a local directory, keyed by a random identifier, so
+/// that only a pointer to each payload needs to be queued. Each payload is stored alongside
+/// its checksum, which is verified as it is read back.
+///
+/// The store counts the queued messages holding each payload stored since it was created, so
+/// that a payload is deleted as soon as the last message holding it is acked. Payloads which
+/// are not counted, such as those stored before a restart or held by a deleted subscription,
+/// are left to the retention instead.
+#[derive(Debug, Clone)]
+pub struct Store {
+    dir: PathBuf,
+    threshold: usize,
+    retention_ms: Arc<AtomicU64>,
+    holds: Arc<Mutex<HashMap<String, usize>>>,
+    corrupted: Option<IntCounter>,
+}
+
+impl Store {
+    /// Create a new store based on the supplied configuration, creating its directory if
+    /// needed. [None] is returned if no directory is configured.
+    pub fn new(cfg: &Config) -> Result<Option<Self>> {
+        let dir = match &cfg.dir {
+            Some(dir) => dir.clone(),
+            None => return Ok(None),
+        };
+        std::fs::create_dir_all(&dir)?;
+        Ok(Some(Self {
+            dir,
+            threshold: cfg.threshold_bytes,
+            retention_ms: Arc::new(AtomicU64::new(cfg.retention_ms)),
+            holds: Arc::new(Mutex::new(HashMap::new())),
+            corrupted: None,
+        }))
+    }
+
+    /// Count the payloads which fail verification as they are read with the supplied counter.
+    pub fn with_corruption_counter(mut self, counter: IntCounter) -> Self {
+        self.corrupted = Some(counter);
+        self
+    }
+
+    /// Return the time payloads are retained for.
+    pub fn retention(&self) -> Duration {
+        Duration::from_millis(self.retention_ms.load(Ordering::Relaxed))
+    }
+
+    /// Switch to the supplied retention, returning the previous one. The new retention
+    /// applies to every stored payload from the next sweep on.
+    pub fn set_retention(&self, retention: Duration) -> Duration {
+        let prev = self
+            .retention_ms
+            .swap(retention.as_millis() as u64, Ordering::Relaxed);
+        Duration::from_millis(prev)
+    }
+
+    /// Return whether a payload of the supplied size should be claim checked.
+    pub fn exceeds(&self, size: usize) -> bool {
+        size > self.threshold
+    }
+
+    /// Store the supplied payload, returning the key it can be claimed with.
+    pub fn put(&self, data: &[u8]) -> Result<String> {
+        let key = Uuid::new_v4().to_string();
+        let path = self.dir.join(&key);
+        let tmp = path.with_extension("tmp");
+        let mut contents = Vec::with_capacity(CHECKSUM_LEN + data.len());
+        contents.extend_from_slice(&crc32c::crc32c(data).to_le_bytes());
+        contents.extend_from_slice(data);
+        std::fs::write(&tmp, contents)?;
+        std::fs::rename(tmp, path)?;
+        Ok(key)
+    }
+
+    /// Return the payload stored under the supplied key, failing if it no longer matches the
+    /// checksum it was stored with.
+    pub fn get(&self, key: &str) -> Result<Vec<u8>> {
+        let not_found = || Error::NotFound {
+            key: key.to_owned(),
+        };
+        // Only keys generated by this store are valid, which also keeps reads within its directory.
+        let key = Uuid::parse_str(key).map_err(|_| not_found())?;
+        let mut data = match std::fs::read(self.dir.join(key.to_string())) {
+            Ok(data) => data,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Err(not_found()),
+            Err(err) => return Err(err.into()),
+        };
+        let verified = data.len() >= CHECKSUM_LEN && {
+            let (checksum, payload) = data.split_at(CHECKSUM_LEN);
+            checksum == crc32c::crc32c(payload).to_le_bytes()
+        };
+        if !verified {
+            if let Some(corrupted) = &self.corrupted {
+                corrupted.inc();
+            }
+            return Err(Error::Corrupted {
+                key: key.to_string(),
+            });
+        }
+        data.drain(..CHECKSUM_LEN);
+        Ok(data)
+    }
+
+    /// Delete the payload stored under the supplied key, if it still exists.
+    pub fn delete(&self, key: &str) -> Result<()> {
+        self.holds.lock().unwrap().remove(key);
+        // Only keys generated by this store are valid, which also keeps deletes within its directory.
+        let key = match Uuid::parse_str(key) {
+            Ok(key) => key,
+            Err(_) => return Ok(()),
+        };
+        match std::fs::remove_file(self.dir.join(key.to_string())) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Count the supplied number of queued messages as holding the payload stored under the
+    /// supplied key, which was just stored.
+    pub fn track(&self, key: &str, count: usize) {
+        if count > 0 {
+            *self
+                .holds
+                .lock()
+                .unwrap()
+                .entry(key.to_owned())
+                .or_default() += count;
+        }
+    }
+
+    /// Count the supplied number of further queued messages as holding the payload stored
+    /// under the supplied key, such as copies dead-lettered or forwarded to another topic.
+    /// Payloads which are not counted are left uncounted.
+    pub fn hold(&self, key: &str, count: usize) {
+        if let Some(holds) = self.holds.lock().unwrap().get_mut(key) {
+            *holds += count;
+        }
+    }
+
+    /// Release a single hold on the payload stored under the supplied key, as a message
+    /// holding it is acked, deleting the payload once no message holds it any longer. Returns
+    /// whether the payload was deleted.
+    pub fn release(&self, key: &str) -> Result<bool> {
+        {
+            let mut holds = self.holds.lock().unwrap();
+            match holds.get_mut(key) {
+                Some(count) if *count > 1 => {
+                    *count -= 1;
+                    return Ok(false);
+                }
+                Some(_) => {}
+                None => return Ok(false),
+            }
+        }
+        self.delete(key)?;
+        Ok(true)
+    }
+
+    /// Delete every payload stored longer than the retention, returning the count deleted.
+    pub fn sweep(&self) -> Result<usize> {
+        let now = SystemTime::now();
+        let retention = self.retention();
+        let mut deleted = 0;
+        for entry in std::fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let modified = entry.metadata()?.modified()?;
+            let age = now.duration_since(modified).unwrap_or_default();
+            if age >= retention {
+                std::fs::remove_file(entry.path())?;
+                if let Some(key) = entry.file_name().to_str() {
+                    self.holds.lock().unwrap().remove(key);
+                }
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+
+    /// Delete payloads past their retention on an interval, forever.
+    pub async fn run(self, logger: slog::Logger) {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            let store = self.clone();
+            match tokio::task::spawn_blocking(move || store.sweep()).await {
+                Ok(Ok(deleted)) if deleted > 0 => {
+                    debug!(&logger, "Deleted expired claim checked payloads."; "count" => deleted)
+                }
+                Ok(Ok(_)) => {}
+                Ok(Err(err)) => {
+                    warn!(&logger, "Failed to delete expired claim checked payloads."; "error" => err.to_string())
+                }
+                Err(err) => {
+                    warn!(&logger, "Claim check sweep panicked."; "error" => err.to_string())
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store() {
+        let dir = std::env::temp_dir().join(format!("rift-claim-{}", Uuid::new_v4()));
+        let mut cfg = Config {
+            dir: None,
+            threshold_bytes: 4,
+            retention_ms: 60000,
+        };
+        assert!(Store::new(&cfg).unwrap().is_none());
+        cfg.dir = Some(dir.clone());
+        let store = Store::new(&cfg).unwrap().unwrap();
+
+        assert!(!store.exceeds(4));
+        assert!(store.exceeds(5));
+
+        let key = store.put(b"hello world").unwrap();
+        assert_eq!(store.get(&key).unwrap(), b"hello world");
+        let corrupted = IntCounter::new("corrupted", "corrupted").unwrap();
+        let store = store.with_corruption_counter(corrupted.clone());
+        let path = dir.join(&key);
+        let mut contents = std::fs::read(&path).unwrap();
+        contents[CHECKSUM_LEN] ^= 1;
+        std::fs::write(&path, &contents).unwrap();
+        assert!(matches!(store.get(&key), Err(Error::Corrupted { .. })));
+        std::fs::write(&path, b"he").unwrap();
+        assert!(matches!(store.get(&key), Err(Error::Corrupted { .. })));
+        assert_eq!(corrupted.get(), 2);
+
+        assert!(matches!(
+            store.get("../etc/passwd"),
+            Err(Error::NotFound { .. })
+        ));
+        assert!(matches!(
+            store.get(&Uuid::new_v4().to_string()),
+            Err(Error::NotFound { .. })
+        ));
+
+        // Payloads are deleted once the last message holding them is acked.
+        let held = store.put(b"held").unwrap();
+        store.track(&held, 2);
+        store.hold(&held, 1);
+        store.hold(&Uuid::new_v4().to_string(), 1);
+        assert!(!store.release(&held).unwrap());
+        assert!(!store.release(&held).unwrap());
+        assert_eq!(store.get(&held).unwrap(), b"held");
+        assert!(store.release(&held).unwrap());
+        assert!(matches!(store.get(&held), Err(Error::NotFound { .. })));
+        // Payloads which are not counted are left to the retention.
+        let untracked = store.put(b"untracked").unwrap();
+        assert!(!store.release(&untracked).unwrap());
+        store.delete(&untracked).unwrap();
+        store.delete(&untracked).unwrap();
+        store.delete("../etc/passwd").unwrap();
+        assert!(store.get(&untracked).is_err());
+
+        assert_eq!(store.sweep().unwrap(), 0);
+        assert_eq!(
+            store.clone().set_retention(Duration::ZERO),
+            Duration::from_millis(60000)
+        );
+        assert_eq!(store.retention(), Duration::ZERO);
+        assert_eq!(store.sweep().unwrap(), 1);
+        assert!(store.get(&key).is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
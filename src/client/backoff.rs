@@ -0,0 +1,78 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::time::Duration;
+
+// extern usings
+use rand::Rng;
+
+/// The default initial delay after a failure.
+pub const DEFAULT_BACKOFF_MIN: Duration = Duration::from_millis(100);
+/// The default maximum delay between failures.
+pub const DEFAULT_BACKOFF_MAX: Duration = Duration::from_secs(30);
+
+/// An exponential backoff with jitter, doubling its ceiling on each consecutive failure.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    min: Duration,
+    max: Duration,
+    attempt: u32,
+}
+
+impl Backoff {
+    /// Create a new backoff starting at the supplied minimum and capped at the supplied maximum.
+    pub fn new(min: Duration, max: Duration) -> Self {
+        Self {
+            min,
+            max: std::cmp::max(min, max),
+            attempt: 0,
+        }
+    }
+
+    /// Return the jittered delay to wait before the next attempt, and advance the backoff.
+    pub fn delay(&mut self) -> Duration {
+        let ceiling = self
+            .min
+            .checked_mul(1u32.checked_shl(self.attempt).unwrap_or(u32::MAX))
+            .unwrap_or(self.max)
+            .min(self.max);
+        self.attempt = self.attempt.saturating_add(1);
+        let ceiling = ceiling.as_millis() as u64;
+        Duration::from_millis(rand::thread_rng().gen_range(0..=ceiling))
+    }
+
+    /// Reset the backoff after a success.
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+
+    /// Return the count of consecutive attempts since the last reset.
+    pub fn attempts(&self) -> u32 {
+        self.attempt
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(DEFAULT_BACKOFF_MIN, DEFAULT_BACKOFF_MAX)
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let mut backoff = Backoff::new(Duration::from_millis(1), Duration::from_millis(4));
+        for _ in 0..64 {
+            assert!(backoff.delay() <= Duration::from_millis(4));
+        }
+        assert_eq!(backoff.attempts(), 64);
+        backoff.reset();
+        assert_eq!(backoff.attempts(), 0);
+        assert!(backoff.delay() <= Duration::from_millis(1));
+    }
+}
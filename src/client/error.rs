@@ -0,0 +1,27 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::result;
+
+// extern usings
+use thiserror::Error;
+
+/// Custom Result wrapper to simplify usage.
+pub type Result<T> = result::Result<T, Error>;
+
+/// Represents client errors which could not be recovered from by retrying.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// Handles endpoints which can not be connected to.
+    #[error("the supplied endpoint '{endpoint}' is invalid: {reason}")]
+    InvalidEndpoint {
+        /// The invalid endpoint.
+        endpoint: String,
+        /// The reason the endpoint is invalid.
+        reason: String,
+    },
+    /// Handles calls which failed permanently, or exhausted their retries.
+    #[error("the call failed: {0}")]
+    Status(#[from] tonic::Status),
+}
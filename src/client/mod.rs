@@ -0,0 +1,42 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+mod backoff;
+mod error;
+mod publisher;
+mod subscriber;
+
+pub use backoff::Backoff;
+pub use error::{Error, Result};
+pub use publisher::Publisher;
+pub use subscriber::{Outcome, Subscriber};
+
+// extern usings
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Code, Status};
+
+/// Create a lazily connected channel to the supplied endpoint, which transparently reconnects
+/// after the connection is lost.
+fn channel(endpoint: &str) -> Result<Channel> {
+    Endpoint::from_shared(endpoint.to_owned())
+        .map(|endpoint| endpoint.connect_lazy())
+        .map_err(|err| Error::InvalidEndpoint {
+            endpoint: endpoint.to_owned(),
+            reason: err.to_string(),
+        })
+}
+
+/// Return whether the supplied status represents a transient failure, such as a lost
+/// connection, which is worth retrying.
+fn transient(status: &Status) -> bool {
+    matches!(
+        status.code(),
+        Code::Unavailable
+            | Code::Unknown
+            | Code::Cancelled
+            | Code::DeadlineExceeded
+            | Code::Aborted
+            | Code::Internal
+            | Code::ResourceExhausted
+    )
+}
@@ -0,0 +1,157 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::collections::HashMap;
+
+// crate usings
+use super::{channel, transient, Backoff, Result};
+use crate::grpc::pubsub::{self, Message, PubSubServiceClient};
+
+// extern usings
+use tonic::transport::Channel;
+
+/// The default number of times a failed publish is retried.
+pub const DEFAULT_RETRIES: u32 = 5;
+/// The default size above which payloads are split into chunks, which keeps each publish
+/// within the default 4MiB gRPC message limit.
+pub const DEFAULT_CHUNK_SIZE: usize = 3 * 1024 * 1024;
+
+/// Publishes messages to rift, transparently reconnecting and retrying publishes which fail
+/// with transient errors.
+///
+/// Payloads larger than the chunk size are published as a sequence of chunks, which riftd
+/// reassembles into the original message before delivering it.
+#[derive(Debug, Clone)]
+pub struct Publisher {
+    client: PubSubServiceClient<Channel>,
+    backoff: Backoff,
+    retries: u32,
+    chunk_size: usize,
+    logger: slog::Logger,
+}
+
+impl Publisher {
+    /// Create a new publisher lazily connected to the supplied endpoint, including its scheme.
+    pub fn connect(endpoint: &str) -> Result<Self> {
+        channel(endpoint).map(Self::with_channel)
+    }
+
+    /// Create a new publisher over the supplied channel.
+    pub fn with_channel(channel: Channel) -> Self {
+        Self {
+            client: PubSubServiceClient::new(channel),
+            backoff: Backoff::default(),
+            retries: DEFAULT_RETRIES,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            logger: slog::Logger::root(slog::Discard, o!()),
+        }
+    }
+
+    /// Use the supplied backoff between retried publishes.
+    pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Retry failed publishes the supplied number of times, a value of zero disables retries.
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Split payloads larger than the supplied size into chunks.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Log retried failures to the supplied logger.
+    pub fn with_logger(mut self, logger: slog::Logger) -> Self {
+        self.logger = logger;
+        self
+    }
+
+    /// Publish the supplied data to the supplied topic.
+    pub async fn publish(&self, topic: &str, data: Vec<u8>) -> Result<()> {
+        self.publish_with_attributes(topic, data, HashMap::new())
+            .await
+    }
+
+    /// Publish the supplied data and attributes to the supplied topic.
+    pub async fn publish_with_attributes(
+        &self,
+        topic: &str,
+        data: Vec<u8>,
+        attributes: HashMap<String, String>,
+    ) -> Result<()> {
+        let msg = Message {
+            topic: topic.to_owned(),
+            attributes,
+            published: None,
+            data: data.into(),
+            checksum: None,
+            content_type: String::new(),
+        };
+        for chunk in pubsub::split(msg, self.chunk_size) {
+            self.send(chunk).await?;
+        }
+        Ok(())
+    }
+
+    /// Publish the supplied message, retrying transient failures.
+    async fn send(&self, msg: Message) -> Result<()> {
+        let mut client = self.client.clone();
+        let mut backoff = self.backoff.clone();
+        loop {
+            let status = match client.publish(msg.clone()).await {
+                Ok(_) => return Ok(()),
+                Err(status) => status,
+            };
+            if !transient(&status) || backoff.attempts() >= self.retries {
+                return Err(status.into());
+            }
+            let delay = backoff.delay();
+            warn!(&self.logger, "Publish failed with a transient error, retrying.";
+                "topic" => &msg.topic,
+                "attempt" => backoff.attempts(),
+                "backoff_ms" => delay.as_millis() as u64,
+                "error" => status.message(),
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    use crate::testing::Fixture;
+
+    #[test]
+    fn test_publish_chunked() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let fixture = Fixture::start().await.unwrap();
+            let topic = fixture.registry.create(String::from("topic"));
+            let sub = topic.create(String::from("sub"));
+
+            let publisher =
+                Publisher::with_channel(fixture.channel().await.unwrap()).with_chunk_size(2);
+            let mut attributes = HashMap::new();
+            attributes.insert(String::from("key"), String::from("value"));
+            publisher
+                .publish_with_attributes("topic", vec![1, 2, 3, 4, 5], attributes.clone())
+                .await
+                .unwrap();
+
+            // The chunks are only delivered once reassembled.
+            let (_, _, msg) = sub.queue.next().unwrap();
+            assert_eq!(msg.data, vec![1, 2, 3, 4, 5]);
+            assert_eq!(msg.attributes, attributes);
+            assert!(sub.queue.next().is_none());
+        });
+    }
+}
@@ -0,0 +1,538 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::future::Future;
+use std::task::Poll;
+use std::time::{Duration, SystemTime};
+
+// crate usings
+use super::{channel, transient, Backoff, Result};
+use crate::claimcheck::CLAIM_CHECK_ATTRIBUTE;
+use crate::grpc::pubsub::{
+    ClaimRequest, Lease, LeasedMessage, Message, NackReason, PubSubServiceClient, SettleRequest,
+    StartPosition, Subscription,
+};
+
+// extern usings
+use futures::future::{self, Either, FutureExt};
+use futures::stream::{FuturesUnordered, StreamExt};
+use tokio::time::Instant;
+use tonic::transport::Channel;
+use tonic::{Status, Streaming};
+
+/// The shortest interval leases are renewed on, guarding against renewing in a tight loop.
+const MIN_RENEW_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The default maximum number of messages leased but not yet settled.
+pub const DEFAULT_MAX_OUTSTANDING_MESSAGES: usize = 1000;
+/// The default maximum number of data bytes leased but not yet settled.
+pub const DEFAULT_MAX_OUTSTANDING_BYTES: usize = 64 * 1024 * 1024;
+/// The default interval handled messages are acked or nacked in batches on.
+pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The outcome of handling a message, deciding whether it is acked or redelivered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The message was handled, and is removed from the subscription.
+    Ack,
+    /// The message was not handled, and is redelivered.
+    Nack,
+    /// The message was not handled, and is redelivered once the supplied delay elapses.
+    Retry(Duration),
+    /// The message will never be handled, and is moved to the dead-letter topic.
+    Permanent,
+    /// The message could not be parsed, and is moved to the dead-letter topic.
+    Malformed,
+}
+
+/// The handled messages waiting to be settled by the next flush.
+#[derive(Debug, Default)]
+struct Batch {
+    acks: Vec<Lease>,
+    nacks: Vec<Lease>,
+    bytes: usize,
+}
+
+impl Batch {
+    fn push(&mut self, mut lease: Lease, bytes: usize, outcome: Outcome) {
+        match outcome {
+            Outcome::Ack => self.acks.push(lease),
+            Outcome::Nack => self.nacks.push(lease),
+            Outcome::Retry(delay) => {
+                lease.delay_ms = delay.as_millis() as u64;
+                self.nacks.push(lease)
+            }
+            Outcome::Permanent => {
+                lease.set_reason(NackReason::Permanent);
+                self.nacks.push(lease)
+            }
+            Outcome::Malformed => {
+                lease.set_reason(NackReason::Malformed);
+                self.nacks.push(lease)
+            }
+        }
+        self.bytes += bytes;
+    }
+}
+
+/// An event driving the consumption of a subscription stream.
+enum Event {
+    /// The next message leased from the stream, if the stream is still open.
+    Leased(Option<std::result::Result<LeasedMessage, Status>>),
+    /// A handler completed, along with the lease and size of its message.
+    Handled((Lease, usize, Outcome)),
+    /// The batch of handled messages is due to be settled.
+    Flush,
+}
+
+/// Consumes the messages of a subscription, transparently reconnecting after failures and
+/// renewing the lease of each message until its handler completes.
+///
+/// Messages are handled concurrently, up to a limit on the messages and bytes which have been
+/// leased but not yet settled. Once either limit is reached, no more messages are read from
+/// the subscription until handled messages are settled, which happens in batches on an interval.
+#[derive(Debug, Clone)]
+pub struct Subscriber {
+    client: PubSubServiceClient<Channel>,
+    topic: String,
+    subscription: String,
+    backoff: Backoff,
+    max_messages: usize,
+    max_bytes: usize,
+    flush_interval: Duration,
+    max_batch: u32,
+    max_wait: Duration,
+    start: StartPosition,
+    start_time: Option<SystemTime>,
+    ack_deadline: Duration,
+    content_type: String,
+    logger: slog::Logger,
+}
+
+impl Subscriber {
+    /// Create a new subscriber to the supplied subscription, lazily connected to the supplied
+    /// endpoint, including its scheme.
+    pub fn connect(endpoint: &str, topic: &str, subscription: &str) -> Result<Self> {
+        channel(endpoint).map(|channel| Self::with_channel(channel, topic, subscription))
+    }
+
+    /// Create a new subscriber to the supplied subscription over the supplied channel.
+    pub fn with_channel(channel: Channel, topic: &str, subscription: &str) -> Self {
+        Self {
+            client: PubSubServiceClient::new(channel),
+            topic: topic.to_owned(),
+            subscription: subscription.to_owned(),
+            backoff: Backoff::default(),
+            max_messages: DEFAULT_MAX_OUTSTANDING_MESSAGES,
+            max_bytes: DEFAULT_MAX_OUTSTANDING_BYTES,
+            flush_interval: DEFAULT_FLUSH_INTERVAL,
+            max_batch: 0,
+            max_wait: Duration::ZERO,
+            start: StartPosition::Beginning,
+            start_time: None,
+            ack_deadline: Duration::ZERO,
+            content_type: String::new(),
+            logger: slog::Logger::root(slog::Discard, o!()),
+        }
+    }
+
+    /// Use the supplied backoff between reconnection attempts.
+    pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Limit the messages leased but not yet settled to the supplied count, a value of one
+    /// handles messages one at a time.
+    pub fn with_max_outstanding_messages(mut self, max: usize) -> Self {
+        self.max_messages = std::cmp::max(max, 1);
+        self
+    }
+
+    /// Limit the data bytes leased but not yet settled to the supplied size. A single message
+    /// larger than the limit is still handled, once nothing else is outstanding.
+    pub fn with_max_outstanding_bytes(mut self, max: usize) -> Self {
+        self.max_bytes = std::cmp::max(max, 1);
+        self
+    }
+
+    /// Settle handled messages in batches on the supplied interval.
+    pub fn with_flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = interval;
+        self
+    }
+
+    /// Ask the server to coalesce up to the supplied count of leased messages into each
+    /// streamed response, waiting up to the supplied duration for a partial batch to fill.
+    /// Whole batches are read at once, so the outstanding limits may be exceeded by up to a
+    /// batch.
+    pub fn with_delivery_batch(mut self, max: u32, wait: Duration) -> Self {
+        self.max_batch = max;
+        self.max_wait = wait;
+        self
+    }
+
+    /// Start from the supplied position within the messages retained by the subscription,
+    /// along with the time to start from when starting from a time. Reconnections resume
+    /// from the time the subscriber first started from, rather than from their own end.
+    pub fn with_start(mut self, start: StartPosition, time: Option<SystemTime>) -> Self {
+        self.start = start;
+        self.start_time = time;
+        self
+    }
+
+    /// Lease messages for the supplied deadline rather than the ttl of the subscription, where
+    /// zero uses the ttl of the subscription. Leases are still renewed until handlers complete.
+    pub fn with_ack_deadline(mut self, deadline: Duration) -> Self {
+        self.ack_deadline = deadline;
+        self
+    }
+
+    /// Ask the server to deliver message data transcoded into the supplied content type, such
+    /// as `application/json`, using the schema of the topic. An empty content type delivers
+    /// data as it was published.
+    pub fn with_content_type(mut self, content_type: &str) -> Self {
+        self.content_type = content_type.to_owned();
+        self
+    }
+
+    /// Log reconnections and failed lease operations to the supplied logger.
+    pub fn with_logger(mut self, logger: slog::Logger) -> Self {
+        self.logger = logger;
+        self
+    }
+
+    /// Handle every message of the subscription with the supplied handler, acking or nacking
+    /// each based on the returned [Outcome].
+    ///
+    /// This only returns once the subscription fails with a permanent error, such as the
+    /// subscription not existing. Transient failures are retried with backoff.
+    pub async fn run<F, Fut>(&self, mut handler: F) -> Result<()>
+    where
+        F: FnMut(Message) -> Fut,
+        Fut: Future<Output = Outcome>,
+    {
+        let mut client = self.client.clone();
+        let mut backoff = self.backoff.clone();
+        let (start, start_time) = match self.start {
+            StartPosition::End => (StartPosition::Time, Some(SystemTime::now())),
+            start => (start, self.start_time),
+        };
+        let request = Subscription {
+            name: self.subscription.clone(),
+            topic: self.topic.clone(),
+            max_batch: self.max_batch,
+            max_wait_ms: self.max_wait.as_millis() as u64,
+            start: start as i32,
+            start_time: start_time.map(Into::into),
+            ack_deadline_ms: self.ack_deadline.as_millis() as u64,
+            content_type: self.content_type.clone(),
+        };
+        loop {
+            let status = match client.subscribe(request.clone()).await {
+                Ok(response) => {
+                    let stream = response.into_inner();
+                    self.consume(&mut client, stream, &mut handler, &mut backoff)
+                        .await
+                }
+                Err(status) => Some(status),
+            };
+            if let Some(status) = status {
+                if !transient(&status) {
+                    return Err(status.into());
+                }
+                let delay = backoff.delay();
+                warn!(&self.logger, "Subscription failed with a transient error, reconnecting.";
+                    "topic" => &self.topic,
+                    "subscription" => &self.subscription,
+                    "backoff_ms" => delay.as_millis() as u64,
+                    "error" => status.message(),
+                );
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+
+    /// Handle the messages of the supplied stream until it ends, returning the status it
+    /// failed with if any. Every message leased from the stream is settled before returning.
+    async fn consume<F, Fut>(
+        &self,
+        client: &mut PubSubServiceClient<Channel>,
+        mut stream: Streaming<LeasedMessage>,
+        handler: &mut F,
+        backoff: &mut Backoff,
+    ) -> Option<Status>
+    where
+        F: FnMut(Message) -> Fut,
+        Fut: Future<Output = Outcome>,
+    {
+        let mut in_flight = FuturesUnordered::new();
+        let mut batch = Batch::default();
+        let (mut messages, mut bytes) = (0, 0);
+        let mut flush = Box::pin(tokio::time::sleep(self.flush_interval));
+        let status = loop {
+            let open = messages < self.max_messages && bytes < self.max_bytes;
+            let event = future::poll_fn(|cx| {
+                if let Poll::Ready(Some(handled)) = in_flight.poll_next_unpin(cx) {
+                    return Poll::Ready(Event::Handled(handled));
+                }
+                if flush.poll_unpin(cx).is_ready() {
+                    return Poll::Ready(Event::Flush);
+                }
+                if open {
+                    if let Poll::Ready(next) = stream.poll_next_unpin(cx) {
+                        return Poll::Ready(Event::Leased(next));
+                    }
+                }
+                Poll::Pending
+            })
+            .await;
+
+            match event {
+                Event::Leased(Some(Ok(leased))) => {
+                    backoff.reset();
+                    for leased in leased.unbatch() {
+                        let (lease, msg) = match (leased.lease, leased.message) {
+                            (Some(lease), Some(msg)) => (lease, msg),
+                            _ => continue,
+                        };
+                        let resolved = self.resolve(client, msg).await;
+                        let size = match &resolved {
+                            Ok(msg) => msg.data.len(),
+                            Err(_) => 0,
+                        };
+                        messages += 1;
+                        bytes += size;
+                        match resolved {
+                            Ok(msg) => in_flight.push(self.handle(
+                                client.clone(),
+                                lease,
+                                size,
+                                handler(msg),
+                            )),
+                            // Nack messages whose payload can't be claimed, to retry them later.
+                            Err(status) => {
+                                warn!(&self.logger, "Failed to claim message payload, nacking.";
+                                    "error" => status.message(),
+                                );
+                                batch.push(lease, size, Outcome::Nack);
+                            }
+                        }
+                    }
+                }
+                Event::Leased(Some(Err(status))) => break Some(status),
+                Event::Leased(None) => break None,
+                Event::Handled((lease, size, outcome)) => batch.push(lease, size, outcome),
+                Event::Flush => {
+                    flush.as_mut().reset(Instant::now() + self.flush_interval);
+                    let (settled, size) = self.flush(client, &mut batch).await;
+                    messages -= settled;
+                    bytes -= size;
+                }
+            }
+        };
+
+        // Let the in-flight handlers complete before reconnecting, so their messages are
+        // settled rather than left to expire.
+        while let Some((lease, size, outcome)) = in_flight.next().await {
+            batch.push(lease, size, outcome);
+        }
+        self.flush(client, &mut batch).await;
+        status
+    }
+
+    /// Replace the data of the supplied message with its claim checked payload, if it was
+    /// claim checked when published.
+    async fn resolve(
+        &self,
+        client: &mut PubSubServiceClient<Channel>,
+        mut msg: Message,
+    ) -> std::result::Result<Message, Status> {
+        if let Some(key) = msg.attributes.remove(CLAIM_CHECK_ATTRIBUTE) {
+            let request = ClaimRequest {
+                topic: msg.topic.clone(),
+                key,
+            };
+            msg.data = client.claim(request).await?.into_inner().data;
+        }
+        if !msg.verify_checksum() {
+            return Err(Status::data_loss(
+                "the message data does not match its checksum",
+            ));
+        }
+        Ok(msg)
+    }
+
+    /// Handle the supplied message, renewing its lease at half its ttl until the handler
+    /// completes, returning the lease and size of the message along with the outcome.
+    async fn handle<Fut>(
+        &self,
+        mut client: PubSubServiceClient<Channel>,
+        lease: Lease,
+        size: usize,
+        handled: Fut,
+    ) -> (Lease, usize, Outcome)
+    where
+        Fut: Future<Output = Outcome>,
+    {
+        let interval = std::cmp::max(Duration::from_millis(lease.ttl_ms / 2), MIN_RENEW_INTERVAL);
+        let mut handled = Box::pin(handled);
+        loop {
+            let tick = Box::pin(tokio::time::sleep(interval));
+            match future::select(handled, tick).await {
+                Either::Left((outcome, _)) => return (lease, size, outcome),
+                Either::Right((_, pending)) => {
+                    handled = pending;
+                    if let Err(status) = client.renew(lease.clone()).await {
+                        warn!(&self.logger, "Failed to renew lease, the message may be redelivered.";
+                            "error" => status.message(),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Settle the supplied batch, returning the count and size of the settled messages.
+    async fn flush(
+        &self,
+        client: &mut PubSubServiceClient<Channel>,
+        batch: &mut Batch,
+    ) -> (usize, usize) {
+        let batch = std::mem::take(batch);
+        let settled = batch.acks.len() + batch.nacks.len();
+        if settled == 0 {
+            return (0, 0);
+        }
+        let request = SettleRequest {
+            topic: self.topic.clone(),
+            acks: batch.acks,
+            nacks: batch.nacks,
+        };
+        // Leases which can not be settled expire, and their messages are redelivered.
+        match client.settle(request).await {
+            Ok(response) if !response.get_ref().failed.is_empty() => {
+                warn!(&self.logger, "Failed to settle some leases, their messages may be redelivered.";
+                    "failed" => response.get_ref().failed.len(),
+                );
+            }
+            Ok(_) => {}
+            Err(status) => {
+                warn!(&self.logger, "Failed to settle leases, their messages may be redelivered.";
+                    "count" => settled,
+                    "error" => status.message(),
+                );
+            }
+        }
+        (settled, batch.bytes)
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    use crate::client::{Error, Publisher};
+    use crate::testing::Fixture;
+
+    #[test]
+    fn test_publish_subscribe() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let fixture = Fixture::start().await.unwrap();
+            let topic = fixture.registry.create(String::from("topic"));
+            topic.create(String::from("sub"));
+            let channel = fixture.channel().await.unwrap();
+
+            let publisher = Publisher::with_channel(channel.clone());
+            publisher.publish("topic", vec![1]).await.unwrap();
+            publisher.publish("topic", vec![2]).await.unwrap();
+            assert!(publisher.publish("missing", vec![3]).await.is_err());
+
+            // The first delivery is nacked, so is redelivered.
+            let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+            let subscriber = Subscriber::with_channel(channel.clone(), "topic", "sub")
+                .with_flush_interval(Duration::from_millis(10));
+            tokio::spawn(async move {
+                let nacked = Arc::new(AtomicBool::new(false));
+                subscriber
+                    .run(|msg| {
+                        let tx = tx.clone();
+                        let nacked = nacked.clone();
+                        async move {
+                            let _ = tx.send(msg.data);
+                            match nacked.swap(true, Ordering::SeqCst) {
+                                false => Outcome::Nack,
+                                true => Outcome::Ack,
+                            }
+                        }
+                    })
+                    .await
+            });
+            let mut received = Vec::new();
+            for _ in 0..3 {
+                received.push(rx.recv().await.unwrap());
+            }
+            // The stream may lease the next message before the first is nacked.
+            received.sort();
+            assert_eq!(received, vec![vec![1], vec![1], vec![2]]);
+
+            let subscriber = Subscriber::with_channel(channel, "topic", "missing");
+            let res = subscriber.run(|_| async { Outcome::Ack }).await;
+            assert!(
+                matches!(res, Err(Error::Status(status)) if status.code() == tonic::Code::NotFound)
+            );
+        });
+    }
+
+    #[test]
+    fn test_flow_control() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let fixture = Fixture::start().await.unwrap();
+            let topic = fixture.registry.create(String::from("topic"));
+            topic.create(String::from("sub"));
+            let channel = fixture.channel().await.unwrap();
+
+            let publisher = Publisher::with_channel(channel.clone());
+            for data in 0..3 {
+                publisher.publish("topic", vec![data]).await.unwrap();
+            }
+
+            let gate = Arc::new(tokio::sync::Semaphore::new(0));
+            let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+            let subscriber = Subscriber::with_channel(channel, "topic", "sub")
+                .with_max_outstanding_messages(2)
+                .with_flush_interval(Duration::from_millis(10));
+            let handler_gate = gate.clone();
+            tokio::spawn(async move {
+                subscriber
+                    .run(|msg| {
+                        let tx = tx.clone();
+                        let gate = handler_gate.clone();
+                        async move {
+                            let _ = tx.send(msg.data);
+                            gate.acquire().await.unwrap().forget();
+                            Outcome::Ack
+                        }
+                    })
+                    .await
+            });
+
+            // Only two messages are handled while the handlers are blocked.
+            rx.recv().await.unwrap();
+            rx.recv().await.unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            assert!(rx.try_recv().is_err());
+
+            // Settling the blocked messages lets the last one through.
+            gate.add_permits(3);
+            rx.recv().await.unwrap();
+        });
+    }
+}
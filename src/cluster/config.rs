@@ -0,0 +1,114 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// extern usings
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, StructOpt)]
+/// Rift cluster configuration.
+pub struct Config {
+    #[structopt(
+        long = "node-id",
+        env = "RIFT_NODE_ID",
+        help = "The unique ID of this node within the cluster.",
+        long_help = "Sets the unique ID of this node within the cluster. Defaults to the advertised address of this node.",
+        takes_value = true
+    )]
+    /// Define the unique ID of this node, if any.
+    pub node_id: Option<String>,
+
+    #[structopt(
+        long = "advertise-addr",
+        env = "RIFT_ADVERTISE_ADDR",
+        help = "The gRPC address other cluster members can reach this node on.",
+        long_help = "Sets the gRPC address, without a scheme, that other cluster members send heartbeats to. Defaults to the gRPC listen address.",
+        takes_value = true
+    )]
+    /// Define the address other members can reach this node on, if any.
+    pub advertise_addr: Option<String>,
+
+    #[structopt(
+        long = "join",
+        env = "RIFT_JOIN",
+        help = "The addresses of existing cluster members to join.",
+        long_help = "Sets the comma separated list of existing cluster members to heartbeat with in order to join the cluster. Each entry is either a `host:port` gRPC address, a `dns://host:port` name resolving to a member for every A/AAAA record, or a `dns+srv://name` fully qualified name resolving to a member for every SRV record.",
+        use_delimiter = true,
+        takes_value = true
+    )]
+    /// Define the addresses of the cluster members to join.
+    pub join: Vec<String>,
+
+    #[structopt(
+        long = "cluster-secret",
+        env = "RIFT_CLUSTER_SECRET",
+        help = "The secret cluster members authenticate the requests they forward with.",
+        long_help = "Sets the secret shared by every cluster member, which members attach to the requests they forward to each other. Forwarded requests carrying the secret are handled by the receiving member as is, without forwarding them again or counting them against its per caller limits. Without a secret no forwarded request is trusted, so a request forwarded to a member which disagrees on the owner of its topic is rejected with the owner instead. Every member of the cluster must use the same value.",
+        hide_env_values = true,
+        takes_value = true
+    )]
+    /// Define the secret authenticating the requests forwarded between members, if any.
+    pub secret: Option<String>,
+
+    #[structopt(
+        long = "discovery-interval-ms",
+        env = "RIFT_DISCOVERY_INTERVAL_MS",
+        help = "The interval in milliseconds between DNS resolutions of the join addresses.",
+        default_value = "30000",
+        takes_value = true
+    )]
+    /// Define the interval in milliseconds between DNS resolutions of the join addresses.
+    pub discovery_interval_ms: u64,
+
+    #[structopt(
+        long = "heartbeat-interval-ms",
+        env = "RIFT_HEARTBEAT_INTERVAL_MS",
+        help = "The interval in milliseconds between heartbeats.",
+        long_help = "Sets the interval in milliseconds between each round of heartbeats sent to every known cluster member.",
+        default_value = "1000",
+        takes_value = true
+    )]
+    /// Define the interval in milliseconds between heartbeats.
+    pub heartbeat_interval_ms: u64,
+
+    #[structopt(
+        long = "suspect-timeout-ms",
+        env = "RIFT_SUSPECT_TIMEOUT_MS",
+        help = "The time in milliseconds without a heartbeat before a member is suspected.",
+        default_value = "5000",
+        takes_value = true
+    )]
+    /// Define the time in milliseconds without a heartbeat before a member is suspected.
+    pub suspect_timeout_ms: u64,
+
+    #[structopt(
+        long = "dead-timeout-ms",
+        env = "RIFT_DEAD_TIMEOUT_MS",
+        help = "The time in milliseconds without a heartbeat before a member is declared dead.",
+        default_value = "30000",
+        takes_value = true
+    )]
+    /// Define the time in milliseconds without a heartbeat before a member is declared dead.
+    pub dead_timeout_ms: u64,
+
+    #[structopt(
+        long = "evict-timeout-ms",
+        env = "RIFT_EVICT_TIMEOUT_MS",
+        help = "The time in milliseconds without a heartbeat before a dead member is forgotten.",
+        long_help = "Sets the time in milliseconds without a heartbeat before a member declared dead is evicted from the cluster and forgotten. Evicted members are ignored for as long again, unless they restart, so that the stale views of other members don't bring them back.",
+        default_value = "300000",
+        takes_value = true
+    )]
+    /// Define the time in milliseconds without a heartbeat before a dead member is forgotten.
+    pub evict_timeout_ms: u64,
+
+    #[structopt(
+        long = "vnodes",
+        env = "RIFT_VNODES",
+        help = "The number of virtual nodes to place each member on the hash ring with.",
+        long_help = "Sets the number of virtual nodes each cluster member is placed on the consistent hash ring with, which assigns topics to members. Every member of the cluster must use the same value.",
+        default_value = "64",
+        takes_value = true
+    )]
+    /// Define the number of virtual nodes to place each member on the hash ring with.
+    pub vnodes: usize,
+}
@@ -0,0 +1,229 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use super::dns;
+use super::{Config, Error, Result};
+
+const DNS_SCHEME: &str = "dns://";
+const SRV_SCHEME: &str = "dns+srv://";
+
+/// A single `--join` entry, describing how to find one or more cluster members.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Seed {
+    /// A fixed `host:port` address.
+    Static(String),
+    /// A `dns://host:port` name, resolving to a member for every A and AAAA record.
+    Dns {
+        /// The name to resolve.
+        host: String,
+        /// The port every member listens on.
+        port: u16,
+    },
+    /// A `dns+srv://name` fully qualified name, resolving to a member for every SRV record.
+    Srv(String),
+}
+
+impl FromStr for Seed {
+    type Err = Error;
+
+    /// Parse a `--join` entry.
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// use librift::cluster::Seed;
+    ///
+    /// let seed = Seed::from_str("dns://riftd-headless:8081").unwrap();
+    /// assert_eq!(seed, Seed::Dns { host: String::from("riftd-headless"), port: 8081 });
+    /// ```
+    fn from_str(seed: &str) -> Result<Seed> {
+        let invalid = |reason: &str| Error::InvalidSeed {
+            seed: seed.to_string(),
+            reason: reason.to_string(),
+        };
+        if let Some(name) = seed.strip_prefix(SRV_SCHEME) {
+            if name.is_empty() {
+                return Err(invalid("missing SRV name"));
+            }
+            return Ok(Seed::Srv(name.to_string()));
+        }
+
+        let (dns, addr) = match seed.strip_prefix(DNS_SCHEME) {
+            Some(addr) => (true, addr),
+            None => (false, seed),
+        };
+        let (host, port) = addr
+            .rsplit_once(':')
+            .ok_or_else(|| invalid("missing port"))?;
+        if host.is_empty() {
+            return Err(invalid("missing host"));
+        }
+        let port = port
+            .parse::<u16>()
+            .map_err(|err| invalid(&err.to_string()))?;
+        if dns {
+            Ok(Seed::Dns {
+                host: host.to_string(),
+                port,
+            })
+        } else {
+            Ok(Seed::Static(seed.to_string()))
+        }
+    }
+}
+
+impl Seed {
+    /// Resolve this seed into the addresses of the members it describes.
+    pub async fn resolve(&self) -> Result<Vec<String>> {
+        match self {
+            Seed::Static(addr) => Ok(vec![addr.clone()]),
+            Seed::Dns { host, port } => lookup_host(host, *port).await,
+            Seed::Srv(name) => {
+                let mut addrs = Vec::new();
+                for record in dns::lookup_srv(name).await? {
+                    addrs.extend(lookup_host(&record.target, record.port).await?);
+                }
+                Ok(addrs)
+            }
+        }
+    }
+}
+
+async fn lookup_host(host: &str, port: u16) -> Result<Vec<String>> {
+    let addrs = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|source| Error::Resolve {
+            name: host.to_string(),
+            source,
+        })?;
+    Ok(addrs.map(|addr| addr.to_string()).collect())
+}
+
+/// Discovery periodically resolves the configured `--join` entries into the addresses of
+/// cluster members to heartbeat with, so that members can be found through DNS, such as a
+/// Kubernetes headless service, rather than a hand maintained list of addresses.
+#[derive(Debug, Clone)]
+pub struct Discovery {
+    seeds: Vec<Seed>,
+    resolved: Arc<RwLock<Vec<Vec<String>>>>,
+    interval: Duration,
+}
+
+impl Discovery {
+    /// Create a new discovery based on the supplied configuration. Static addresses are
+    /// available immediately, while DNS entries are only available once resolved.
+    pub fn new(cfg: &Config) -> Result<Self> {
+        let seeds = cfg
+            .join
+            .iter()
+            .map(|seed| Seed::from_str(seed))
+            .collect::<Result<Vec<Seed>>>()?;
+        let resolved = seeds
+            .iter()
+            .map(|seed| match seed {
+                Seed::Static(addr) => vec![addr.clone()],
+                _ => Vec::new(),
+            })
+            .collect();
+        Ok(Self {
+            seeds,
+            resolved: Arc::new(RwLock::new(resolved)),
+            interval: Duration::from_millis(cfg.discovery_interval_ms),
+        })
+    }
+
+    /// Return the addresses resolved from every seed.
+    pub fn peers(&self) -> Vec<String> {
+        let resolved = self.resolved.read().unwrap();
+        resolved.iter().flatten().cloned().collect()
+    }
+
+    /// Resolve every seed, keeping the previously resolved addresses of any seed which fails
+    /// to resolve.
+    pub async fn refresh(&self, logger: &slog::Logger) {
+        for (idx, seed) in self.seeds.iter().enumerate() {
+            match seed.resolve().await {
+                Ok(addrs) => self.resolved.write().unwrap()[idx] = addrs,
+                Err(err) => {
+                    warn!(logger, "Failed to resolve cluster members."; "seed" => format!("{:?}", seed), "error" => err.to_string())
+                }
+            }
+        }
+    }
+
+    /// Resolve every seed on every configured interval, forever.
+    pub async fn run(self, logger: slog::Logger) {
+        if self
+            .seeds
+            .iter()
+            .all(|seed| matches!(seed, Seed::Static(..)))
+        {
+            return;
+        }
+        let mut interval = tokio::time::interval(self.interval);
+        loop {
+            interval.tick().await;
+            self.refresh(&logger).await;
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn test_seed() {
+        assert_eq!(
+            Seed::from_str("10.0.0.1:8081").unwrap(),
+            Seed::Static(String::from("10.0.0.1:8081"))
+        );
+        assert_eq!(
+            Seed::from_str("dns+srv://_grpc._tcp.riftd.default.svc.cluster.local").unwrap(),
+            Seed::Srv(String::from("_grpc._tcp.riftd.default.svc.cluster.local"))
+        );
+        for invalid in ["riftd", ":8081", "dns://riftd:port", "dns+srv://"] {
+            assert!(matches!(
+                Seed::from_str(invalid),
+                Err(Error::InvalidSeed { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_discovery() {
+        let logger = slog::Logger::root(slog::Discard, o!());
+        let cfg = Config {
+            node_id: None,
+            advertise_addr: None,
+            join: vec![
+                String::from("10.0.0.1:8081"),
+                String::from("dns://localhost:8081"),
+            ],
+            secret: None,
+            discovery_interval_ms: 30000,
+            heartbeat_interval_ms: 1000,
+            suspect_timeout_ms: 5000,
+            dead_timeout_ms: 30000,
+            evict_timeout_ms: 300000,
+            vnodes: 64,
+        };
+        let discovery = Discovery::new(&cfg).unwrap();
+        assert_eq!(discovery.peers(), vec![String::from("10.0.0.1:8081")]);
+
+        aw!(discovery.refresh(&logger));
+        let peers = discovery.peers();
+        assert_eq!(peers[0], "10.0.0.1:8081");
+        assert!(peers[1..].iter().all(|peer| peer.ends_with(":8081")));
+    }
+}
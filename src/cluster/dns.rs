@@ -0,0 +1,342 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! A minimal DNS client, which only supports the SRV lookups used for peer discovery.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+
+use super::{Error, Result};
+
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+const DEFAULT_NAMESERVER: &str = "127.0.0.1:53";
+/// How long a single nameserver has to respond to a query.
+const TIMEOUT: Duration = Duration::from_secs(2);
+/// How many times every nameserver is queried in turn before a lookup fails.
+const ATTEMPTS: usize = 2;
+/// The largest UDP response advertised through EDNS0, larger responses are truncated and
+/// retried over TCP.
+const UDP_PAYLOAD_SIZE: u16 = 4096;
+const TYPE_SRV: u16 = 33;
+const TYPE_OPT: u16 = 41;
+const CLASS_IN: u16 = 1;
+const FLAG_TRUNCATED: u16 = 0x0200;
+
+/// A single SRV record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Srv {
+    /// The port the target listens on.
+    pub port: u16,
+    /// The host name of the target.
+    pub target: String,
+}
+
+/// Return every nameserver configured in the supplied resolv.conf contents, in order.
+fn nameservers(conf: &str) -> Vec<SocketAddr> {
+    let servers: Vec<SocketAddr> = conf
+        .lines()
+        .filter_map(|line| line.strip_prefix("nameserver"))
+        .filter_map(|addr| addr.trim().parse::<std::net::IpAddr>().ok())
+        .map(|addr| SocketAddr::new(addr, 53))
+        .collect();
+    if servers.is_empty() {
+        return vec![DEFAULT_NAMESERVER.parse().unwrap()];
+    }
+    servers
+}
+
+/// Encode a query for the SRV records of the supplied name, advertising EDNS0 support so that
+/// large record sets fit within a single UDP response.
+fn query(id: u16, name: &str) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(name.len() + 29);
+    buf.extend_from_slice(&id.to_be_bytes());
+    // Recursion desired, one question, and one additional record for EDNS0.
+    buf.extend_from_slice(&[0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01]);
+    for label in name.trim_end_matches('.').split('.') {
+        buf.push(label.len() as u8);
+        buf.extend_from_slice(label.as_bytes());
+    }
+    buf.push(0);
+    buf.extend_from_slice(&TYPE_SRV.to_be_bytes());
+    buf.extend_from_slice(&CLASS_IN.to_be_bytes());
+    // The OPT record, owned by the root name, carrying the UDP payload size as its class and
+    // neither an extended rcode, flags, nor any options.
+    buf.push(0);
+    buf.extend_from_slice(&TYPE_OPT.to_be_bytes());
+    buf.extend_from_slice(&UDP_PAYLOAD_SIZE.to_be_bytes());
+    buf.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+    buf
+}
+
+/// Return whether the supplied response was truncated to fit within a UDP response.
+fn truncated(buf: &[u8]) -> bool {
+    matches!(buf.get(2..4), Some(flags) if u16::from_be_bytes([flags[0], flags[1]]) & FLAG_TRUNCATED != 0)
+}
+
+/// A cursor over a DNS message.
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn u8(&mut self) -> Option<u8> {
+        let value = *self.buf.get(self.pos)?;
+        self.pos += 1;
+        Some(value)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_be_bytes([self.u8()?, self.u8()?]))
+    }
+
+    fn skip(&mut self, len: usize) -> Option<()> {
+        if self.pos + len > self.buf.len() {
+            return None;
+        }
+        self.pos += len;
+        Some(())
+    }
+
+    /// Read a possibly compressed name, leaving the cursor after the name.
+    fn name(&mut self) -> Option<String> {
+        let mut labels = Vec::new();
+        let mut pos = self.pos;
+        let mut end = None;
+        // Bound the number of pointers followed to guard against pointer loops.
+        for _ in 0..128 {
+            let len = *self.buf.get(pos)? as usize;
+            if len == 0 {
+                self.pos = end.unwrap_or(pos + 1);
+                return Some(labels.join("."));
+            }
+            if len & 0xc0 == 0xc0 {
+                let offset = ((len & 0x3f) << 8) | *self.buf.get(pos + 1)? as usize;
+                end.get_or_insert(pos + 2);
+                pos = offset;
+                continue;
+            }
+            let label = self.buf.get(pos + 1..pos + 1 + len)?;
+            labels.push(String::from_utf8_lossy(label).into_owned());
+            pos += 1 + len;
+        }
+        None
+    }
+}
+
+/// Parse the SRV records out of the supplied response to the query with the supplied ID.
+fn parse(id: u16, buf: &[u8]) -> std::result::Result<Vec<Srv>, String> {
+    let malformed = || String::from("malformed response");
+    let mut reader = Reader { buf, pos: 0 };
+    if reader.u16().ok_or_else(malformed)? != id {
+        return Err(String::from("mismatched response ID"));
+    }
+    let flags = reader.u16().ok_or_else(malformed)?;
+    match flags & 0x000f {
+        0 => {}
+        3 => return Ok(Vec::new()),
+        rcode => return Err(format!("server responded with rcode {}", rcode)),
+    }
+    let questions = reader.u16().ok_or_else(malformed)?;
+    let answers = reader.u16().ok_or_else(malformed)?;
+    reader.skip(4).ok_or_else(malformed)?;
+
+    for _ in 0..questions {
+        reader.name().ok_or_else(malformed)?;
+        reader.skip(4).ok_or_else(malformed)?;
+    }
+
+    let mut records = Vec::with_capacity(answers as usize);
+    for _ in 0..answers {
+        reader.name().ok_or_else(malformed)?;
+        let kind = reader.u16().ok_or_else(malformed)?;
+        reader.skip(6).ok_or_else(malformed)?;
+        let len = reader.u16().ok_or_else(malformed)? as usize;
+        let next = reader.pos + len;
+        if kind == TYPE_SRV {
+            // Skip the priority and weight, as every target is heartbeated regardless.
+            reader.skip(4).ok_or_else(malformed)?;
+            let port = reader.u16().ok_or_else(malformed)?;
+            let target = reader.name().ok_or_else(malformed)?;
+            records.push(Srv { port, target });
+        }
+        reader.pos = next;
+    }
+    Ok(records)
+}
+
+/// Look up the SRV records of the supplied fully qualified name, using the nameservers
+/// configured in the system resolv.conf. Every nameserver is queried in turn until one
+/// responds, for up to [ATTEMPTS] rounds, and the last failure is returned if none do.
+pub async fn lookup_srv(name: &str) -> Result<Vec<Srv>> {
+    let conf = std::fs::read_to_string(RESOLV_CONF).unwrap_or_default();
+    let servers = nameservers(&conf);
+    let mut failure = None;
+    for _ in 0..ATTEMPTS {
+        for server in servers.iter() {
+            match lookup_srv_with(*server, name).await {
+                Ok(records) => return Ok(records),
+                Err(err) => failure = Some(err),
+            }
+        }
+    }
+    Err(failure.unwrap())
+}
+
+/// Look up the SRV records of the supplied name with the supplied nameserver, repeating the
+/// query over TCP if the UDP response was truncated.
+async fn lookup_srv_with(server: SocketAddr, name: &str) -> Result<Vec<Srv>> {
+    let resolve = |source| Error::Resolve {
+        name: name.to_string(),
+        source,
+    };
+    let timed_out = |_| resolve(std::io::ErrorKind::TimedOut.into());
+    let bind = if server.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(bind).await.map_err(resolve)?;
+    socket.connect(server).await.map_err(resolve)?;
+
+    let id = rand::random::<u16>();
+    let query = query(id, name);
+    socket.send(&query).await.map_err(resolve)?;
+
+    let mut buf = vec![0u8; UDP_PAYLOAD_SIZE as usize];
+    let len = tokio::time::timeout(TIMEOUT, socket.recv(&mut buf))
+        .await
+        .map_err(timed_out)?
+        .map_err(resolve)?;
+    buf.truncate(len);
+    if truncated(&buf) {
+        buf = tokio::time::timeout(TIMEOUT, exchange_tcp(server, &query))
+            .await
+            .map_err(timed_out)?
+            .map_err(resolve)?;
+    }
+    parse(id, &buf).map_err(|reason| Error::Dns {
+        name: name.to_string(),
+        reason,
+    })
+}
+
+/// Send the supplied query to the supplied nameserver over TCP, returning its response.
+async fn exchange_tcp(server: SocketAddr, query: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut stream = TcpStream::connect(server).await?;
+    // Messages sent over TCP are prefixed by their length.
+    let mut framed = Vec::with_capacity(query.len() + 2);
+    framed.extend_from_slice(&(query.len() as u16).to_be_bytes());
+    framed.extend_from_slice(query);
+    stream.write_all(&framed).await?;
+
+    let len = stream.read_u16().await? as usize;
+    let mut buf = vec![0u8; len];
+    stream.read_exact(&mut buf).await?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nameservers() {
+        let conf = "search default.svc\nnameserver 10.96.0.10\nnameserver 8.8.8.8\n";
+        let expected: Vec<SocketAddr> = vec![
+            "10.96.0.10:53".parse().unwrap(),
+            "8.8.8.8:53".parse().unwrap(),
+        ];
+        assert_eq!(nameservers(conf), expected);
+        assert_eq!(nameservers(""), vec![DEFAULT_NAMESERVER.parse().unwrap()]);
+    }
+
+    /// Build a response to the query with the supplied ID, with a single SRV answer.
+    fn response(id: u16, name: &str) -> Vec<u8> {
+        let mut buf = query(id, name);
+        // Drop the OPT record, and flip the header into a response with a single answer.
+        buf.truncate(buf.len() - 11);
+        buf[2] = 0x81;
+        buf[3] = 0x80;
+        buf[7] = 1;
+        buf[11] = 0;
+        // A compressed pointer to the question name.
+        buf.extend_from_slice(&[0xc0, 0x0c]);
+        buf.extend_from_slice(&TYPE_SRV.to_be_bytes());
+        buf.extend_from_slice(&CLASS_IN.to_be_bytes());
+        buf.extend_from_slice(&[0, 0, 0, 30]);
+        let mut rdata = vec![0, 10, 0, 5, 0x1f, 0x91];
+        rdata.extend_from_slice(&[7]);
+        rdata.extend_from_slice(b"riftd-0");
+        // Point back into the question name for the `riftd` suffix.
+        rdata.extend_from_slice(&[0xc0, 0x0c + 1 + 5 + 1 + 4]);
+        buf.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        buf.extend_from_slice(&rdata);
+        buf
+    }
+
+    #[test]
+    fn test_query() {
+        let buf = query(7, "riftd");
+        // The question is followed by an OPT record advertising the UDP payload size.
+        assert_eq!(&buf[10..12], &[0, 1]);
+        assert_eq!(
+            &buf[buf.len() - 11..],
+            &[0, 0, 41, 0x10, 0, 0, 0, 0, 0, 0, 0]
+        );
+        assert!(!truncated(&buf));
+    }
+
+    #[test]
+    fn test_parse() {
+        let buf = response(7, "_grpc._tcp.riftd");
+        let records = parse(7, &buf).unwrap();
+        assert_eq!(
+            records,
+            vec![Srv {
+                port: 8081,
+                target: String::from("riftd-0.riftd"),
+            }]
+        );
+
+        assert!(parse(8, &buf).is_err());
+        assert!(parse(7, &buf[..20]).is_err());
+    }
+
+    #[test]
+    fn test_lookup_truncated() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let name = "_grpc._tcp.riftd";
+        let server = runtime.block_on(async {
+            let udp = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let addr = udp.local_addr().unwrap();
+            let tcp = tokio::net::TcpListener::bind(addr).await.unwrap();
+            // Answer over UDP with a truncated response, and over TCP with the full one.
+            tokio::spawn(async move {
+                let mut buf = [0u8; 512];
+                let (len, peer) = udp.recv_from(&mut buf).await.unwrap();
+                let mut reply = buf[..len].to_vec();
+                reply[2] |= 0x82;
+                udp.send_to(&reply, peer).await.unwrap();
+            });
+            tokio::spawn(async move {
+                let (mut stream, _) = tcp.accept().await.unwrap();
+                let len = stream.read_u16().await.unwrap() as usize;
+                let mut buf = vec![0u8; len];
+                stream.read_exact(&mut buf).await.unwrap();
+                let reply = response(u16::from_be_bytes([buf[0], buf[1]]), name);
+                stream.write_u16(reply.len() as u16).await.unwrap();
+                stream.write_all(&reply).await.unwrap();
+            });
+            addr
+        });
+
+        let records = runtime.block_on(lookup_srv_with(server, name)).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].target, "riftd-0.riftd");
+    }
+}
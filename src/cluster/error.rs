@@ -0,0 +1,47 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::io;
+use std::result;
+
+// extern usings
+use thiserror::Error;
+
+/// Custom Result wrapper to simplify usage.
+pub type Result<T> = result::Result<T, Error>;
+
+/// Represents cluster errors based on user configuration or failures discovering peers.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// Handles invalid `--join` entries.
+    #[error("the supplied join address '{seed}' is invalid: {reason}")]
+    InvalidSeed {
+        /// The invalid join address.
+        seed: String,
+        /// The reason the address is invalid.
+        reason: String,
+    },
+    /// Handles advertised addresses other members can not reach this node on.
+    #[error("the advertised address '{addr}' is unspecified, set --advertise-addr to an address other members can reach this node on")]
+    UnspecifiedAddr {
+        /// The unspecified address.
+        addr: String,
+    },
+    /// Handles failures resolving a DNS name.
+    #[error("failed to resolve '{name}': {source}")]
+    Resolve {
+        /// The name that failed to resolve.
+        name: String,
+        /// The initial error cause.
+        source: io::Error,
+    },
+    /// Handles malformed or failed DNS responses.
+    #[error("invalid DNS response for '{name}': {reason}")]
+    Dns {
+        /// The name that was queried.
+        name: String,
+        /// The reason the response is invalid.
+        reason: String,
+    },
+}
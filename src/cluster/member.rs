@@ -0,0 +1,80 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::fmt;
+use std::time::Instant;
+
+/// The health of a cluster member, as determined by the local node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum State {
+    /// The member has heartbeated recently.
+    Alive,
+    /// The member has not heartbeated within the suspect timeout.
+    Suspect,
+    /// The member has not heartbeated within the dead timeout.
+    Dead,
+}
+
+impl State {
+    /// All of the possible member states.
+    pub const ALL: [State; 3] = [State::Alive, State::Suspect, State::Dead];
+
+    /// Return the lower case name of this state.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            State::Alive => "alive",
+            State::Suspect => "suspect",
+            State::Dead => "dead",
+        }
+    }
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A single riftd node within the cluster.
+#[derive(Debug, Clone)]
+pub struct Member {
+    /// The unique ID of this node.
+    pub id: String,
+    /// The gRPC address other nodes can reach this node on.
+    pub addr: String,
+    /// The monotonically increasing heartbeat counter of this node, which is only ever
+    /// incremented by the node itself.
+    pub heartbeat: u64,
+    /// The incarnation of this node, which increases every time the node restarts. Views of a
+    /// node are ordered by incarnation first and heartbeat counter second.
+    pub incarnation: u64,
+    /// The state of this node as seen by the local node.
+    pub state: State,
+    /// When the heartbeat counter of this node last increased, as seen by the local node.
+    pub last_seen: Instant,
+}
+
+impl Member {
+    /// Create a new alive member.
+    pub fn new(id: String, addr: String, heartbeat: u64) -> Self {
+        Self {
+            id,
+            addr,
+            heartbeat,
+            incarnation: 0,
+            state: State::Alive,
+            last_seen: Instant::now(),
+        }
+    }
+
+    /// Set the incarnation of this member.
+    pub fn with_incarnation(mut self, incarnation: u64) -> Self {
+        self.incarnation = incarnation;
+        self
+    }
+
+    /// Return the version of this view of the member, where later views compare greater.
+    pub fn version(&self) -> (u64, u64) {
+        (self.incarnation, self.heartbeat)
+    }
+}
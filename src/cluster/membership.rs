@@ -0,0 +1,771 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::collections::HashMap;
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use prometheus::{IntCounter, IntGaugeVec};
+use tokio::sync::watch;
+use tonic::codegen::http::uri::InvalidUri;
+use tonic::transport::Channel;
+
+use crate::grpc::cluster::{ClusterServiceClient, HeartbeatRequest};
+use crate::metric::{self, Manager, Opt};
+
+use super::{Config, Discovery, Error, Member, Pool, Result, Ring, State};
+
+/// The topics held by the local node. Topics stay on the member holding them when the ring
+/// changes, so the topics held by each member are consulted before the ring to resolve their
+/// owner.
+pub trait Topics: fmt::Debug + Send + Sync {
+    /// Return whether the local node holds the supplied topic.
+    fn contains(&self, topic: &str) -> bool;
+
+    /// Return the names of every topic held by the local node.
+    fn names(&self) -> Vec<Arc<str>>;
+}
+
+/// The version of every evicted member when it was evicted, along with when it was evicted.
+type Evicted = HashMap<String, ((u64, u64), Instant)>;
+
+#[derive(Debug, Clone)]
+struct MembershipMetrics {
+    members: IntGaugeVec,
+    heartbeat_failures: IntCounter,
+}
+
+/// Membership tracks the members of the cluster and their health using a gossip style
+/// heartbeat protocol. Every node periodically increments its own heartbeat counter and
+/// exchanges its view of the cluster with every known member, a member whose counter stops
+/// increasing is first suspected and eventually declared dead. Topics are assigned to every
+/// member not yet declared dead using a consistent hash [Ring].
+#[derive(Debug, Clone)]
+pub struct Membership {
+    local: String,
+    members: Arc<RwLock<HashMap<String, Member>>>,
+    // The version of every evicted member when it was evicted, and when, so that stale views
+    // of it are ignored until they are evicted by every other member too.
+    evicted: Arc<RwLock<Evicted>>,
+    // The member holding each topic the ring assigns to another member, as last reported by
+    // the holder.
+    placements: Arc<RwLock<HashMap<String, String>>>,
+    topics: Option<Arc<dyn Topics>>,
+    ring: Arc<RwLock<Ring>>,
+    version: Arc<watch::Sender<u64>>,
+    // Held so that the version channel is never closed, even without any watchers.
+    watcher: watch::Receiver<u64>,
+    discovery: Discovery,
+    pool: Pool,
+    secret: Option<Arc<str>>,
+    interval: Duration,
+    suspect_timeout: Duration,
+    dead_timeout: Duration,
+    evict_timeout: Duration,
+    metrics: Option<MembershipMetrics>,
+}
+
+impl Membership {
+    /// Create a new membership based on the supplied configuration, containing only the local
+    /// node. The supplied listen address is advertised when no advertise address is configured.
+    /// Unspecified addresses, such as `[::]:8081`, can't be reached by other members, so they
+    /// are refused whenever they would be advertised to another member.
+    pub fn new(cfg: &Config, listen_addr: &SocketAddr) -> Result<Self> {
+        let addr = cfg
+            .advertise_addr
+            .clone()
+            .unwrap_or_else(|| listen_addr.to_string());
+        let unspecified = addr
+            .parse::<SocketAddr>()
+            .map(|addr| addr.ip().is_unspecified())
+            .unwrap_or(false);
+        if unspecified && (cfg.advertise_addr.is_some() || !cfg.join.is_empty()) {
+            return Err(Error::UnspecifiedAddr { addr });
+        }
+        let local = cfg.node_id.clone().unwrap_or_else(|| addr.clone());
+
+        // Every restart starts a new incarnation, so that other members don't mistake the
+        // restarted heartbeat counter for a stale view of this node.
+        let incarnation = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_millis() as u64)
+            .unwrap_or_default();
+        let mut members = HashMap::new();
+        let member = Member::new(local.clone(), addr, 0).with_incarnation(incarnation);
+        members.insert(local.clone(), member);
+        let mut ring = Ring::new(cfg.vnodes);
+        ring.add(&local);
+        let (version, watcher) = watch::channel(0);
+        Ok(Self {
+            local,
+            members: Arc::new(RwLock::new(members)),
+            evicted: Arc::default(),
+            placements: Arc::default(),
+            topics: None,
+            ring: Arc::new(RwLock::new(ring)),
+            version: Arc::new(version),
+            watcher,
+            discovery: Discovery::new(cfg)?,
+            pool: Pool::new(Duration::from_millis(cfg.heartbeat_interval_ms)),
+            secret: cfg.secret.as_deref().map(Arc::from),
+            interval: Duration::from_millis(cfg.heartbeat_interval_ms),
+            suspect_timeout: Duration::from_millis(cfg.suspect_timeout_ms),
+            dead_timeout: Duration::from_millis(cfg.dead_timeout_ms),
+            evict_timeout: Duration::from_millis(cfg.evict_timeout_ms),
+            metrics: None,
+        })
+    }
+
+    /// Return the channel to the member at the supplied address.
+    pub fn channel(&self, addr: &str) -> std::result::Result<Channel, InvalidUri> {
+        self.pool.channel(addr)
+    }
+
+    /// Return the secret members attach to the requests they forward to each other, if any.
+    pub fn secret(&self) -> Option<&str> {
+        self.secret.as_deref()
+    }
+
+    /// Return whether the supplied secret matches the secret shared by the members of the
+    /// cluster. Nothing matches when no secret is configured. The secrets are compared in
+    /// constant time, so that the comparison doesn't reveal how much of the secret matched.
+    pub fn is_secret(&self, secret: &[u8]) -> bool {
+        match &self.secret {
+            Some(expected) if expected.len() == secret.len() => {
+                expected
+                    .bytes()
+                    .zip(secret)
+                    .fold(0, |acc, (a, b)| acc | (a ^ b))
+                    == 0
+            }
+            _ => false,
+        }
+    }
+
+    /// Return the discovery used to find the members to join.
+    pub fn discovery(&self) -> Discovery {
+        self.discovery.clone()
+    }
+
+    /// Report the number of members in each state, and the number of failed heartbeats, to
+    /// metrics registered with the supplied manager.
+    pub fn with_metrics(mut self, mm: &Manager) -> metric::Result<Self> {
+        self.metrics = Some(MembershipMetrics {
+            members: mm.register_int_gauge_vec(
+                "members",
+                "The current count of cluster members by state.",
+                Some(vec![Opt::Labels(vec![String::from("state")])]),
+            )?,
+            heartbeat_failures: mm.register_int_counter(
+                "heartbeat_failures_total",
+                "The total count of heartbeats which failed to reach another cluster member.",
+                None,
+            )?,
+        });
+        self.report();
+        Ok(self)
+    }
+
+    /// Resolve the owner of the topics held by the local node from the supplied topics,
+    /// rather than only from the ring.
+    pub fn with_topics(mut self, topics: impl Topics + 'static) -> Self {
+        self.topics = Some(Arc::new(topics));
+        self
+    }
+
+    /// Return the local member.
+    pub fn local(&self) -> Member {
+        let members = self.members.read().unwrap();
+        members[&self.local].clone()
+    }
+
+    /// Return every known member, including the local member, sorted by ID.
+    pub fn members(&self) -> Vec<Member> {
+        let members = self.members.read().unwrap();
+        let mut members = members.values().cloned().collect::<Vec<Member>>();
+        members.sort_by(|a, b| a.id.cmp(&b.id));
+        members
+    }
+
+    /// Return the member owning the supplied topic.
+    pub fn owner(&self, topic: &str) -> Member {
+        let owner = self.owner_id(topic);
+        let members = self.members.read().unwrap();
+        members[&owner].clone()
+    }
+
+    /// Return whether or not the supplied topic is owned by the local member.
+    pub fn is_local(&self, topic: &str) -> bool {
+        self.owner_id(topic) == self.local
+    }
+
+    /// Return the ID of the member owning the supplied topic, which is the member holding it
+    /// if any member not yet declared dead does, and otherwise the member the ring assigns it
+    /// to.
+    fn owner_id(&self, topic: &str) -> String {
+        if let Some(topics) = &self.topics {
+            if topics.contains(topic) {
+                return self.local.clone();
+            }
+        }
+        if let Some(holder) = self.placements.read().unwrap().get(topic) {
+            let members = self.members.read().unwrap();
+            if matches!(members.get(holder), Some(member) if member.state != State::Dead) {
+                return holder.clone();
+            }
+        }
+        let ring = self.ring.read().unwrap();
+        // The local member is always on the ring, so it is never empty.
+        ring.owner(topic).unwrap_or(&self.local).to_owned()
+    }
+
+    /// Return the topics held by the local node which the ring assigns to other members, which
+    /// are reported to every other member with each heartbeat.
+    pub fn misplaced(&self) -> Vec<String> {
+        let topics = match &self.topics {
+            Some(topics) => topics.names(),
+            None => return Vec::new(),
+        };
+        let ring = self.ring.read().unwrap();
+        topics
+            .into_iter()
+            .filter(|topic| ring.owner(topic).unwrap_or(&self.local) != self.local)
+            .map(|topic| topic.to_string())
+            .collect()
+    }
+
+    /// Replace the topics the supplied member reported holding, while the ring assigns them
+    /// to other members.
+    pub fn place(&self, holder: &str, topics: Vec<String>) {
+        if holder == self.local {
+            return;
+        }
+        let mut placements = self.placements.write().unwrap();
+        placements.retain(|_, known| known != holder);
+        for topic in topics {
+            placements.insert(topic, holder.to_owned());
+        }
+    }
+
+    /// Return the number of times every member is placed on the ring.
+    pub fn vnodes(&self) -> usize {
+        self.ring.read().unwrap().vnodes()
+    }
+
+    /// Return the members placed on the ring, which is every member not yet declared dead.
+    pub fn ring_members(&self) -> Vec<Member> {
+        let ring = self.ring.read().unwrap();
+        let mut members = self.members();
+        members.retain(|member| ring.contains(&member.id));
+        members
+    }
+
+    /// Return the current topology version, which increases whenever the ring changes.
+    pub fn version(&self) -> u64 {
+        *self.watcher.borrow()
+    }
+
+    /// Return a receiver which is notified of the new topology version whenever the ring
+    /// changes.
+    pub fn watch(&self) -> watch::Receiver<u64> {
+        self.watcher.clone()
+    }
+
+    /// Synchronize the ring with the current members, placing every member not yet declared
+    /// dead on the ring.
+    fn rebalance(&self) {
+        let members = self.members.read().unwrap();
+        let mut ring = self.ring.write().unwrap();
+        let mut changed = false;
+        for member in members.values() {
+            let placed = ring.contains(&member.id);
+            if member.state == State::Dead && placed {
+                ring.remove(&member.id);
+                changed = true;
+            } else if member.state != State::Dead && !placed {
+                ring.add(&member.id);
+                changed = true;
+            }
+        }
+        if changed {
+            // This can't fail, as the membership always holds a receiver.
+            let _ = self.version.send(self.version() + 1);
+        }
+    }
+
+    /// Return the addresses to send heartbeats to, which are the discovered addresses to join
+    /// and the addresses of every member not yet declared dead.
+    pub fn peers(&self) -> Vec<String> {
+        let members = self.members.read().unwrap();
+        let local_addr = &members[&self.local].addr;
+        let mut peers = members
+            .values()
+            .filter(|member| member.id != self.local && member.state != State::Dead)
+            .map(|member| member.addr.clone())
+            .chain(self.discovery.peers())
+            .filter(|addr| addr != local_addr)
+            .collect::<Vec<String>>();
+        peers.sort();
+        peers.dedup();
+        peers
+    }
+
+    /// Merge the supplied view of the cluster into the local view. Members which are unknown,
+    /// or whose view is later than the local view, are considered alive. Views of evicted
+    /// members are ignored unless they are later than the view the member was evicted with.
+    pub fn merge(&self, remote: impl IntoIterator<Item = Member>) {
+        let mut members = self.members.write().unwrap();
+        let mut evicted = self.evicted.write().unwrap();
+        for member in remote {
+            if member.id == self.local {
+                // Another member may remember a previous incarnation of the local node which
+                // is later than the current one, such as after the clock moved backwards.
+                let local = members.get_mut(&self.local).unwrap();
+                if member.version() > local.version() {
+                    local.incarnation = member.incarnation + 1;
+                }
+                continue;
+            }
+            match members.get_mut(&member.id) {
+                Some(known) if known.version() >= member.version() => {}
+                Some(known) => {
+                    known.addr = member.addr;
+                    known.heartbeat = member.heartbeat;
+                    known.incarnation = member.incarnation;
+                    known.state = State::Alive;
+                    known.last_seen = Instant::now();
+                }
+                None => {
+                    match evicted.get(&member.id) {
+                        Some((version, _)) if *version >= member.version() => continue,
+                        Some(_) => {
+                            evicted.remove(&member.id);
+                        }
+                        None => {}
+                    }
+                    let member = Member::new(member.id, member.addr, member.heartbeat)
+                        .with_incarnation(member.incarnation);
+                    members.insert(member.id.clone(), member);
+                }
+            }
+        }
+        drop(evicted);
+        drop(members);
+        self.rebalance();
+        self.report();
+    }
+
+    /// Increment the local heartbeat counter and update the state of every remote member,
+    /// returning the members whose state changed along with their previous state.
+    pub fn tick(&self) -> Vec<(Member, State)> {
+        let mut members = self.members.write().unwrap();
+        let mut changed = Vec::new();
+        for member in members.values_mut() {
+            if member.id == self.local {
+                member.heartbeat += 1;
+                member.last_seen = Instant::now();
+                continue;
+            }
+
+            let elapsed = member.last_seen.elapsed();
+            let state = if elapsed >= self.dead_timeout {
+                State::Dead
+            } else if elapsed >= self.suspect_timeout {
+                State::Suspect
+            } else {
+                State::Alive
+            };
+            if state != member.state {
+                let previous = member.state;
+                member.state = state;
+                changed.push((member.clone(), previous));
+            }
+        }
+        drop(members);
+        if !changed.is_empty() {
+            self.rebalance();
+        }
+        self.report();
+        changed
+    }
+
+    /// Evict every member which has been dead for longer than the evict timeout, returning the
+    /// evicted members. Evicted members are remembered for as long again, so that stale views
+    /// of them from members which have yet to evict them are ignored.
+    pub fn evict(&self) -> Vec<Member> {
+        let mut members = self.members.write().unwrap();
+        let mut evicted = self.evicted.write().unwrap();
+        evicted.retain(|_, (_, at)| at.elapsed() < self.evict_timeout);
+        let expired = members
+            .values()
+            .filter(|member| {
+                member.state == State::Dead && member.last_seen.elapsed() >= self.evict_timeout
+            })
+            .map(|member| member.id.clone())
+            .collect::<Vec<String>>();
+        let mut removed = Vec::with_capacity(expired.len());
+        if !expired.is_empty() {
+            let mut placements = self.placements.write().unwrap();
+            placements.retain(|_, holder| !expired.contains(holder));
+        }
+        for id in expired {
+            if let Some(member) = members.remove(&id) {
+                evicted.insert(id, (member.version(), Instant::now()));
+                removed.push(member);
+            }
+        }
+        drop(evicted);
+        drop(members);
+        if !removed.is_empty() {
+            self.report();
+        }
+        removed
+    }
+
+    fn report(&self) {
+        let metrics = match &self.metrics {
+            Some(metrics) => metrics,
+            None => return,
+        };
+        let members = self.members.read().unwrap();
+        for state in State::ALL {
+            let count = members.values().filter(|m| m.state == state).count();
+            metrics
+                .members
+                .with_label_values(&[state.as_str()])
+                .set(count as i64);
+        }
+    }
+
+    async fn heartbeat(&self, channel: Channel) -> std::result::Result<(), tonic::Status> {
+        let request = HeartbeatRequest {
+            sender: Some(self.local().into()),
+            members: self.members().into_iter().map(Into::into).collect(),
+            topics: self.misplaced(),
+        };
+        let mut client = ClusterServiceClient::new(channel);
+        let response = tokio::time::timeout(self.interval, client.heartbeat(request))
+            .await
+            .map_err(|_| tonic::Status::deadline_exceeded("heartbeat timed out"))??
+            .into_inner();
+        self.merge(response.members.into_iter().map(Into::into));
+        Ok(())
+    }
+
+    /// Heartbeat with every peer on every configured interval, forever.
+    pub async fn run(self, logger: slog::Logger) {
+        let mut interval = tokio::time::interval(self.interval);
+        loop {
+            interval.tick().await;
+            for (member, previous) in self.tick() {
+                info!(logger, "Cluster member changed state.";
+                    "member" => &member.id,
+                    "addr" => &member.addr,
+                    "from" => previous.as_str(),
+                    "to" => member.state.as_str(),
+                );
+            }
+            for member in self.evict() {
+                info!(logger, "Evicted dead cluster member.";
+                    "member" => &member.id,
+                    "addr" => &member.addr,
+                );
+            }
+
+            let this = &self;
+            let mut heartbeats = Vec::new();
+            for peer in self.peers() {
+                let channel = match self.channel(&peer) {
+                    Ok(channel) => channel,
+                    Err(err) => {
+                        warn!(logger, "Invalid cluster member address."; "addr" => &peer, "error" => err.to_string());
+                        continue;
+                    }
+                };
+                heartbeats.push(async move { (peer, this.heartbeat(channel).await) });
+            }
+
+            for (peer, result) in futures::future::join_all(heartbeats).await {
+                if let Err(err) = result {
+                    if let Some(metrics) = &self.metrics {
+                        metrics.heartbeat_failures.inc();
+                    }
+                    debug!(logger, "Failed to heartbeat cluster member."; "addr" => peer, "error" => err.message());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        Config {
+            node_id: Some(String::from("one")),
+            advertise_addr: None,
+            join: vec![String::from("10.0.0.2:8081"), String::from("10.0.0.1:8081")],
+            secret: None,
+            discovery_interval_ms: 30000,
+            heartbeat_interval_ms: 1000,
+            suspect_timeout_ms: 5000,
+            dead_timeout_ms: 30000,
+            evict_timeout_ms: 300000,
+            vnodes: 64,
+        }
+    }
+
+    #[test]
+    fn test_membership() {
+        let addr = "10.0.0.1:8081".parse().unwrap();
+        let membership = Membership::new(&config(), &addr).unwrap();
+
+        let local = membership.local();
+        assert_eq!(local.id, "one");
+        assert_eq!(local.addr, "10.0.0.1:8081");
+        assert_eq!(membership.peers(), vec![String::from("10.0.0.2:8081")]);
+
+        membership.tick();
+        assert_eq!(membership.local().heartbeat, 1);
+
+        let two = Member::new(String::from("two"), String::from("10.0.0.3:8081"), 4);
+        let stale_local = Member::new(String::from("one"), String::from("nope"), 100);
+        membership.merge(vec![two, stale_local]);
+        assert_eq!(membership.local().heartbeat, 1);
+        assert_eq!(membership.members().len(), 2);
+        assert_eq!(
+            membership.peers(),
+            vec![String::from("10.0.0.2:8081"), String::from("10.0.0.3:8081")]
+        );
+
+        // An older heartbeat must not refresh the member.
+        let stale = Member::new(String::from("two"), String::from("nope"), 3);
+        membership.merge(vec![stale]);
+        assert_eq!(membership.members()[1].addr, "10.0.0.3:8081");
+    }
+
+    #[test]
+    fn test_failure_detection() {
+        let mut cfg = config();
+        cfg.suspect_timeout_ms = 0;
+        cfg.dead_timeout_ms = 60000;
+        let addr = "10.0.0.1:8081".parse().unwrap();
+        let mm = Manager::new(
+            String::from("test"),
+            String::from("cluster"),
+            String::from("test"),
+        );
+        let membership = Membership::new(&cfg, &addr)
+            .unwrap()
+            .with_metrics(&mm)
+            .unwrap();
+
+        let two = Member::new(String::from("two"), String::from("10.0.0.3:8081"), 1);
+        membership.merge(vec![two]);
+
+        let changed = membership.tick();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].0.state, State::Suspect);
+        assert_eq!(changed[0].1, State::Alive);
+        let metrics = membership.metrics.as_ref().unwrap();
+        assert_eq!(metrics.members.with_label_values(&["suspect"]).get(), 1);
+        assert_eq!(metrics.members.with_label_values(&["alive"]).get(), 1);
+
+        let two = Member::new(String::from("two"), String::from("10.0.0.3:8081"), 2);
+        membership.merge(vec![two]);
+        assert_eq!(membership.members()[1].state, State::Alive);
+    }
+
+    #[test]
+    fn test_ownership() {
+        let mut cfg = config();
+        cfg.dead_timeout_ms = 0;
+        let addr = "10.0.0.1:8081".parse().unwrap();
+        let membership = Membership::new(&cfg, &addr).unwrap();
+        assert!(membership.is_local("woot"));
+        assert_eq!(membership.owner("woot").id, "one");
+
+        let two = Member::new(String::from("two"), String::from("10.0.0.3:8081"), 1);
+        membership.merge(vec![two]);
+        let topics = (0..100)
+            .map(|topic| format!("topic-{}", topic))
+            .collect::<Vec<String>>();
+        let remote = topics
+            .iter()
+            .filter(|topic| !membership.is_local(topic))
+            .collect::<Vec<&String>>();
+        assert!(!remote.is_empty() && remote.len() < topics.len());
+        assert_eq!(membership.owner(remote[0]).id, "two");
+
+        assert_eq!(membership.version(), 1);
+        assert_eq!(membership.ring_members().len(), 2);
+
+        // Dead members must not own any topics.
+        let mut watch = membership.watch();
+        membership.tick();
+        assert!(topics.iter().all(|topic| membership.is_local(topic)));
+        assert!(watch.has_changed().unwrap());
+        assert_eq!(*watch.borrow_and_update(), 2);
+        assert_eq!(membership.ring_members().len(), 1);
+    }
+
+    #[test]
+    fn test_unspecified_addr() {
+        let unspecified = "[::]:8081".parse().unwrap();
+        assert!(matches!(
+            Membership::new(&config(), &unspecified),
+            Err(Error::UnspecifiedAddr { .. })
+        ));
+        let mut cfg = config();
+        cfg.advertise_addr = Some(String::from("10.0.0.1:8081"));
+        assert!(Membership::new(&cfg, &unspecified).is_ok());
+        cfg.advertise_addr = Some(String::from("0.0.0.0:8081"));
+        assert!(Membership::new(&cfg, &unspecified).is_err());
+
+        // Lone nodes never advertise their address.
+        let mut cfg = config();
+        cfg.join = Vec::new();
+        assert!(Membership::new(&cfg, &unspecified).is_ok());
+    }
+
+    #[test]
+    fn test_incarnation() {
+        let addr = "10.0.0.1:8081".parse().unwrap();
+        let membership = Membership::new(&config(), &addr).unwrap();
+        assert!(membership.local().incarnation > 0);
+
+        let two = |incarnation, heartbeat| {
+            Member::new(
+                String::from("two"),
+                String::from("10.0.0.3:8081"),
+                heartbeat,
+            )
+            .with_incarnation(incarnation)
+        };
+        membership.merge(vec![two(5, 100)]);
+        // A restarted member restarts its heartbeat counter within a later incarnation.
+        membership.merge(vec![two(6, 1)]);
+        let known = membership.members()[1].clone();
+        assert_eq!(known.version(), (6, 1));
+        membership.merge(vec![two(5, 200)]);
+        assert_eq!(membership.members()[1].version(), (6, 1));
+
+        // The local node outlives any later incarnation of itself it is told about.
+        let local = membership.local();
+        let stale = Member::new(local.id.clone(), local.addr.clone(), 0)
+            .with_incarnation(local.incarnation + 10);
+        membership.merge(vec![stale]);
+        assert_eq!(membership.local().incarnation, local.incarnation + 11);
+    }
+
+    #[test]
+    fn test_evict() {
+        let mut cfg = config();
+        cfg.suspect_timeout_ms = 0;
+        cfg.dead_timeout_ms = 0;
+        cfg.evict_timeout_ms = 0;
+        let addr = "10.0.0.1:8081".parse().unwrap();
+        let membership = Membership::new(&cfg, &addr).unwrap();
+        let two = Member::new(String::from("two"), String::from("10.0.0.3:8081"), 4);
+        membership.merge(vec![two.clone()]);
+        assert!(membership.evict().is_empty());
+
+        membership.tick();
+        let evicted = membership.evict();
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].id, "two");
+        assert_eq!(membership.members().len(), 1);
+
+        // Stale views of an evicted member are ignored, while later ones bring it back.
+        membership.merge(vec![two.clone()]);
+        assert_eq!(membership.members().len(), 1);
+        let mut later = two;
+        later.heartbeat += 1;
+        membership.merge(vec![later]);
+        assert_eq!(membership.members().len(), 2);
+        assert!(membership.evicted.read().unwrap().is_empty());
+    }
+
+    #[derive(Debug)]
+    struct Held(Vec<Arc<str>>);
+
+    impl Topics for Held {
+        fn contains(&self, topic: &str) -> bool {
+            self.0.iter().any(|held| held.as_ref() == topic)
+        }
+
+        fn names(&self) -> Vec<Arc<str>> {
+            self.0.clone()
+        }
+    }
+
+    #[test]
+    fn test_placement() {
+        let mut cfg = config();
+        cfg.dead_timeout_ms = 0;
+        let addr = "10.0.0.1:8081".parse().unwrap();
+        let topics = (0..100)
+            .map(|topic| Arc::from(format!("topic-{}", topic)))
+            .collect::<Vec<Arc<str>>>();
+        let membership = Membership::new(&cfg, &addr)
+            .unwrap()
+            .with_topics(Held(topics[..50].to_vec()));
+        assert!(membership.misplaced().is_empty());
+
+        // Topics held by the local node stay local as the ring changes, and are reported to
+        // the other members, while the rest move to their new owner.
+        let two = Member::new(String::from("two"), String::from("10.0.0.3:8081"), 1);
+        membership.merge(vec![two]);
+        let moved = topics
+            .iter()
+            .filter(|topic| membership.ring.read().unwrap().owner(topic).unwrap() == "two")
+            .collect::<Vec<_>>();
+        let (held, unheld) = moved
+            .into_iter()
+            .partition::<Vec<&Arc<str>>, _>(|topic| topics[..50].contains(topic));
+        assert!(!held.is_empty() && !unheld.is_empty());
+        assert!(held.iter().all(|topic| membership.is_local(topic)));
+        assert!(unheld.iter().all(|topic| !membership.is_local(topic)));
+        let mut misplaced = membership.misplaced();
+        misplaced.sort();
+        let mut expected = held
+            .iter()
+            .map(|topic| topic.to_string())
+            .collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(misplaced, expected);
+
+        // Topics reported held by another member are owned by it, until it is declared dead.
+        let local = topics[50..]
+            .iter()
+            .find(|topic| membership.is_local(topic))
+            .unwrap();
+        membership.place("two", vec![local.to_string()]);
+        assert_eq!(membership.owner(local).id, "two");
+        membership.place("two", Vec::new());
+        assert!(membership.is_local(local));
+        membership.place("two", vec![local.to_string()]);
+        membership.tick();
+        assert!(membership.is_local(local));
+    }
+
+    #[test]
+    fn test_secret() {
+        let addr = "10.0.0.1:8081".parse().unwrap();
+        let membership = Membership::new(&config(), &addr).unwrap();
+        assert!(membership.secret().is_none());
+        assert!(!membership.is_secret(b""));
+
+        let mut cfg = config();
+        cfg.secret = Some(String::from("woot"));
+        let membership = Membership::new(&cfg, &addr).unwrap();
+        assert_eq!(membership.secret(), Some("woot"));
+        assert!(membership.is_secret(b"woot"));
+        assert!(!membership.is_secret(b"woo"));
+        assert!(!membership.is_secret(b"wook"));
+    }
+}
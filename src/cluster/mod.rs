@@ -0,0 +1,19 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+mod config;
+mod discovery;
+mod dns;
+mod error;
+mod member;
+mod membership;
+mod pool;
+mod ring;
+
+pub use config::Config;
+pub use discovery::{Discovery, Seed};
+pub use error::{Error, Result};
+pub use member::{Member, State};
+pub use membership::{Membership, Topics};
+pub use pool::Pool;
+pub use ring::Ring;
@@ -0,0 +1,58 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tonic::codegen::http::uri::InvalidUri;
+use tonic::transport::{Channel, Endpoint};
+
+/// A Pool of lazily connected gRPC channels to other cluster members, keyed by address, so
+/// that every member is only ever connected to once.
+#[derive(Debug, Clone)]
+pub struct Pool {
+    channels: Arc<Mutex<HashMap<String, Channel>>>,
+    connect_timeout: Duration,
+}
+
+impl Pool {
+    /// Create a new empty pool, whose channels time out connecting after the supplied duration.
+    pub fn new(connect_timeout: Duration) -> Self {
+        Self {
+            channels: Arc::default(),
+            connect_timeout,
+        }
+    }
+
+    /// Return the channel to the member at the supplied address, without a scheme.
+    pub fn channel(&self, addr: &str) -> Result<Channel, InvalidUri> {
+        let mut channels = self.channels.lock().unwrap();
+        if let Some(channel) = channels.get(addr) {
+            return Ok(channel.clone());
+        }
+        let channel = Endpoint::from_shared(format!("http://{}", addr))?
+            .connect_timeout(self.connect_timeout)
+            .connect_lazy();
+        channels.insert(addr.to_string(), channel.clone());
+        Ok(channel)
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool() {
+        // Lazily connected channels must be created within a runtime.
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+        let pool = Pool::new(Duration::from_secs(1));
+        assert!(pool.channel("127.0.0.1:8081").is_ok());
+        assert!(pool.channel("127.0.0.1:8081").is_ok());
+        assert_eq!(pool.channels.lock().unwrap().len(), 1);
+        assert!(pool.channel("not a uri").is_err());
+    }
+}
@@ -0,0 +1,123 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::collections::BTreeMap;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Hash the supplied key with FNV-1a, which unlike the std hasher is stable across builds and
+/// platforms, so every node in the cluster agrees on the placement of every key.
+fn hash(key: &str) -> u64 {
+    let mut hash = FNV_OFFSET;
+    for byte in key.as_bytes() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    // Finalize the hash to spread the otherwise poorly distributed high bits of short keys.
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash
+}
+
+/// A consistent hash ring assigning keys to nodes. Every node is placed on the ring a fixed
+/// number of times as virtual nodes, so that adding or removing a node only moves roughly
+/// `1/n` of the keys, spread evenly over the remaining nodes.
+#[derive(Debug, Clone, Default)]
+pub struct Ring {
+    vnodes: usize,
+    ring: BTreeMap<u64, String>,
+}
+
+impl Ring {
+    /// Create a new empty ring, placing every node on the ring the supplied number of times.
+    pub fn new(vnodes: usize) -> Self {
+        Self {
+            vnodes: vnodes.max(1),
+            ring: BTreeMap::new(),
+        }
+    }
+
+    /// Return the number of times every node is placed on the ring.
+    pub fn vnodes(&self) -> usize {
+        self.vnodes
+    }
+
+    /// Add the supplied node to the ring.
+    pub fn add(&mut self, node: &str) {
+        for vnode in 0..self.vnodes {
+            self.ring
+                .insert(hash(&format!("{}#{}", node, vnode)), node.to_string());
+        }
+    }
+
+    /// Remove the supplied node from the ring.
+    pub fn remove(&mut self, node: &str) {
+        self.ring.retain(|_, owner| owner != node);
+    }
+
+    /// Return whether or not the supplied node is on the ring.
+    pub fn contains(&self, node: &str) -> bool {
+        self.ring.values().any(|owner| owner == node)
+    }
+
+    /// Return the node owning the supplied key, which is the first node at or after the hash of
+    /// the key, or [None] if the ring is empty.
+    pub fn owner(&self, key: &str) -> Option<&str> {
+        let hash = hash(key);
+        self.ring
+            .range(hash..)
+            .next()
+            .or_else(|| self.ring.iter().next())
+            .map(|(_, node)| node.as_str())
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring() {
+        let mut ring = Ring::new(64);
+        assert!(ring.owner("woot").is_none());
+
+        for node in ["one", "two", "three"] {
+            ring.add(node);
+        }
+        assert!(ring.contains("two"));
+
+        let keys = (0..1000)
+            .map(|key| format!("topic-{}", key))
+            .collect::<Vec<String>>();
+        let before = keys
+            .iter()
+            .map(|key| ring.owner(key).unwrap().to_string())
+            .collect::<Vec<String>>();
+        for node in ["one", "two", "three"] {
+            let owned = before.iter().filter(|owner| *owner == node).count();
+            assert!(owned > 200, "{} owns only {} keys", node, owned);
+        }
+
+        // Adding a node must only move keys to the new node.
+        ring.add("four");
+        let mut moved = 0;
+        for (key, owner) in keys.iter().zip(before.iter()) {
+            let actual = ring.owner(key).unwrap();
+            if actual != owner {
+                assert_eq!(actual, "four");
+                moved += 1;
+            }
+        }
+        assert!(moved > 100 && moved < 400, "moved {} keys", moved);
+
+        // Removing it again must restore the original assignment.
+        ring.remove("four");
+        assert!(!ring.contains("four"));
+        for (key, owner) in keys.iter().zip(before.iter()) {
+            assert_eq!(ring.owner(key).unwrap(), owner);
+        }
+    }
+}
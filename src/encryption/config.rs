@@ -0,0 +1,44 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::path::PathBuf;
+
+// extern usings
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, StructOpt)]
+/// Rift payload encryption configuration.
+pub struct Config {
+    #[structopt(
+        long = "encryption-master-key-file",
+        env = "RIFT_ENCRYPTION_MASTER_KEY_FILE",
+        help = "The file containing the master key wrapping topic data keys.",
+        long_help = "Sets the file containing the 256 bit master key that the data keys of encrypted topics are wrapped with, either as 32 raw bytes or base64 encoded. Topics can only be encrypted when a master key is supplied.",
+        conflicts_with = "encryption-master-key",
+        takes_value = true
+    )]
+    /// Define the file containing the master key, if any.
+    pub master_key_file: Option<PathBuf>,
+
+    #[structopt(
+        long = "encryption-master-key",
+        env = "RIFT_ENCRYPTION_MASTER_KEY",
+        help = "The base64 encoded master key wrapping topic data keys.",
+        long_help = "Sets the base64 encoded 256 bit master key that the data keys of encrypted topics are wrapped with. Prefer supplying the key via the environment over the command line.",
+        hide_env_values = true,
+        takes_value = true
+    )]
+    /// Define the base64 encoded master key, if any.
+    pub master_key: Option<String>,
+
+    #[structopt(
+        long = "encryption-key-dir",
+        env = "RIFT_ENCRYPTION_KEY_DIR",
+        help = "The directory to store wrapped topic data keys in.",
+        long_help = "Sets the directory that the wrapped data keys of encrypted topics are stored in, so that payloads encrypted at rest remain readable across restarts. Data keys are only held in memory when no directory is supplied.",
+        takes_value = true
+    )]
+    /// Define the directory to store wrapped data keys in, if any.
+    pub key_dir: Option<PathBuf>,
+}
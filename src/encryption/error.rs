@@ -0,0 +1,36 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::io;
+use std::result;
+
+// extern usings
+use thiserror::Error;
+
+/// Custom Result wrapper to simplify usage.
+pub type Result<T> = result::Result<T, Error>;
+
+/// Represents failures loading keys, and encrypting or decrypting data with them.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// Handles master keys which are malformed or can not be used.
+    #[error("the master key is invalid: {reason}")]
+    InvalidMasterKey {
+        /// The reason the master key is invalid.
+        reason: String,
+    },
+    /// Handles operations on topics which have no data key.
+    #[error("the topic '{topic}' has no data key")]
+    NoDataKey {
+        /// The name of the topic.
+        topic: String,
+    },
+    /// Handles ciphertext which is malformed or fails authentication, generally as it was
+    /// tampered with or encrypted under another key.
+    #[error("failed to decrypt data, it is malformed or was encrypted with another key")]
+    Decrypt,
+    /// Handles failures reading or writing wrapped data keys.
+    #[error("failed to access the data key store: {0}")]
+    Io(#[from] io::Error),
+}
@@ -0,0 +1,152 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::fmt;
+use std::path::Path;
+
+// crate usings
+use super::{Error, Result};
+
+// extern usings
+use aes_gcm::aead::{Aead, NewAead};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+
+/// The length in bytes of master and data keys.
+pub(super) const KEY_LEN: usize = 32;
+
+/// The length in bytes of the random nonce prefixing every ciphertext.
+const NONCE_LEN: usize = 12;
+
+/// Wraps and unwraps topic data keys, so that data keys are never stored in the clear.
+///
+/// Implement this to delegate wrapping to an external key management service, such that the
+/// master key itself never enters riftd's memory.
+pub trait MasterKey: Send + Sync {
+    /// Encrypt the supplied data key.
+    fn wrap(&self, key: &[u8]) -> Result<Vec<u8>>;
+    /// Decrypt the supplied wrapped data key.
+    fn unwrap(&self, wrapped: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// A 256 bit AES key, encrypting data as a random nonce followed by the AES-GCM ciphertext.
+#[derive(Clone)]
+pub(super) struct Cipher(Aes256Gcm);
+
+impl Cipher {
+    /// Create a new cipher from the supplied 256 bit key.
+    pub(super) fn new(key: &[u8]) -> Option<Self> {
+        let key = <[u8; KEY_LEN]>::try_from(key).ok()?;
+        Some(Self(Aes256Gcm::new(&Key::from(key))))
+    }
+
+    /// Encrypt the supplied plaintext under a fresh random nonce.
+    pub(super) fn seal(&self, plaintext: &[u8]) -> Vec<u8> {
+        let nonce = rand::random::<[u8; NONCE_LEN]>();
+        let ciphertext = self
+            .0
+            .encrypt(&Nonce::from(nonce), plaintext)
+            .expect("AES-GCM encryption can not fail for in-memory payloads");
+        let mut sealed = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        sealed
+    }
+
+    /// Decrypt and authenticate the supplied sealed data.
+    pub(super) fn open(&self, sealed: &[u8]) -> Result<Vec<u8>> {
+        if sealed.len() < NONCE_LEN {
+            return Err(Error::Decrypt);
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let nonce = <[u8; NONCE_LEN]>::try_from(nonce).map_err(|_| Error::Decrypt)?;
+        self.0
+            .decrypt(&Nonce::from(nonce), ciphertext)
+            .map_err(|_| Error::Decrypt)
+    }
+}
+
+impl fmt::Debug for Cipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Cipher(..)")
+    }
+}
+
+/// A master key held locally, loaded from a file or the environment.
+#[derive(Debug, Clone)]
+pub struct LocalKey {
+    cipher: Cipher,
+}
+
+impl LocalKey {
+    /// Create a new master key from the supplied 256 bit key.
+    pub fn new(key: &[u8]) -> Result<Self> {
+        let cipher = Cipher::new(key).ok_or_else(|| Error::InvalidMasterKey {
+            reason: format!("expected {} bytes but found {}", KEY_LEN, key.len()),
+        })?;
+        Ok(Self { cipher })
+    }
+
+    /// Create a new master key from the supplied base64 encoded 256 bit key.
+    ///
+    /// ```
+    /// let key = base64::encode([7; 32]);
+    /// assert!(librift::encryption::LocalKey::from_base64(&key).is_ok());
+    /// assert!(librift::encryption::LocalKey::from_base64("c2hvcnQ=").is_err());
+    /// ```
+    pub fn from_base64(key: &str) -> Result<Self> {
+        let key = base64::decode(key.trim()).map_err(|err| Error::InvalidMasterKey {
+            reason: err.to_string(),
+        })?;
+        Self::new(&key)
+    }
+
+    /// Load the master key from the supplied file, containing either the raw or base64
+    /// encoded key.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let key = std::fs::read(path)?;
+        if key.len() == KEY_LEN {
+            return Self::new(&key);
+        }
+        let key = String::from_utf8(key).map_err(|_| Error::InvalidMasterKey {
+            reason: format!("'{}' is neither a raw nor base64 key", path.display()),
+        })?;
+        Self::from_base64(&key)
+    }
+}
+
+impl MasterKey for LocalKey {
+    fn wrap(&self, key: &[u8]) -> Result<Vec<u8>> {
+        Ok(self.cipher.seal(key))
+    }
+
+    fn unwrap(&self, wrapped: &[u8]) -> Result<Vec<u8>> {
+        self.cipher.open(wrapped)
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_key() {
+        assert!(LocalKey::new(&[0; 16]).is_err());
+
+        let key = LocalKey::new(&[1; KEY_LEN]).unwrap();
+        let wrapped = key.wrap(b"data key").unwrap();
+        assert_ne!(&wrapped[NONCE_LEN..], b"data key");
+        assert_eq!(key.unwrap(&wrapped).unwrap(), b"data key");
+        // Every wrap uses a fresh nonce.
+        assert_ne!(key.wrap(b"data key").unwrap(), wrapped);
+
+        let mut tampered = wrapped.clone();
+        tampered[NONCE_LEN] ^= 1;
+        assert!(matches!(key.unwrap(&tampered), Err(Error::Decrypt)));
+        assert!(matches!(key.unwrap(&[0; 4]), Err(Error::Decrypt)));
+
+        let other = LocalKey::new(&[2; KEY_LEN]).unwrap();
+        assert!(other.unwrap(&wrapped).is_err());
+    }
+}
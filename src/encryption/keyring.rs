@@ -0,0 +1,212 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+// crate usings
+use super::key::{Cipher, KEY_LEN};
+use super::{Config, Error, LocalKey, MasterKey, Result, ALGORITHM, ENCRYPTION_ATTRIBUTE};
+use crate::claimcheck::CLAIM_CHECK_ATTRIBUTE;
+use crate::grpc::pubsub::Message;
+
+/// Holds the data key of every encrypted topic, generating data keys as topics are encrypted
+/// and wrapping them with a master key before they are stored.
+#[derive(Clone)]
+pub struct Keyring {
+    master: Arc<dyn MasterKey>,
+    dir: Option<PathBuf>,
+    keys: Arc<RwLock<HashMap<String, Cipher>>>,
+}
+
+impl Keyring {
+    /// Create a new keyring based on the supplied configuration, ensuring its key directory
+    /// exists. [None] is returned if no master key is configured.
+    pub fn new(cfg: &Config) -> Result<Option<Self>> {
+        let master = match (&cfg.master_key_file, &cfg.master_key) {
+            (Some(path), _) => LocalKey::from_file(path)?,
+            (None, Some(key)) => LocalKey::from_base64(key)?,
+            (None, None) => return Ok(None),
+        };
+        if let Some(dir) = &cfg.key_dir {
+            std::fs::create_dir_all(dir)?;
+        }
+        Ok(Some(Self::with_master_key(
+            Arc::new(master),
+            cfg.key_dir.clone(),
+        )))
+    }
+
+    /// Create a new keyring wrapping data keys with the supplied master key, and storing the
+    /// wrapped keys within the supplied directory if any.
+    pub fn with_master_key(master: Arc<dyn MasterKey>, dir: Option<PathBuf>) -> Self {
+        Self {
+            master,
+            dir,
+            keys: Arc::default(),
+        }
+    }
+
+    /// Return the path the wrapped data key of the supplied topic is stored at, if any. Topic
+    /// names are hex encoded as they may contain characters which are invalid in file names.
+    fn path(&self, topic: &str) -> Option<PathBuf> {
+        let name: String = topic.bytes().map(|b| format!("{:02x}", b)).collect();
+        self.dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}.key", name)))
+    }
+
+    /// Encrypt the supplied topic, loading its stored data key or generating a new one.
+    pub fn enable(&self, topic: &str) -> Result<()> {
+        let mut keys = self.keys.write().unwrap();
+        if keys.contains_key(topic) {
+            return Ok(());
+        }
+
+        let key = match self.path(topic) {
+            Some(path) if path.exists() => self.master.unwrap(&std::fs::read(path)?)?,
+            path => {
+                let key = rand::random::<[u8; KEY_LEN]>().to_vec();
+                if let Some(path) = path {
+                    let tmp = path.with_extension("tmp");
+                    std::fs::write(&tmp, self.master.wrap(&key)?)?;
+                    std::fs::rename(tmp, path)?;
+                }
+                key
+            }
+        };
+        let cipher = Cipher::new(&key).ok_or(Error::Decrypt)?;
+        keys.insert(topic.to_owned(), cipher);
+        Ok(())
+    }
+
+    /// Remove the data key of the supplied topic, destroying its stored copy so that any
+    /// remaining data encrypted with it can no longer be read.
+    pub fn remove(&self, topic: &str) -> Result<()> {
+        self.keys.write().unwrap().remove(topic);
+        match self.path(topic) {
+            Some(path) if path.exists() => Ok(std::fs::remove_file(path)?),
+            _ => Ok(()),
+        }
+    }
+
+    /// Return whether the supplied topic is encrypted.
+    pub fn is_encrypted(&self, topic: &str) -> bool {
+        self.keys.read().unwrap().contains_key(topic)
+    }
+
+    /// Encrypt the supplied data with the data key of the supplied topic, returning [None] if
+    /// the topic is not encrypted.
+    pub fn encrypt(&self, topic: &str, data: &[u8]) -> Option<Vec<u8>> {
+        let keys = self.keys.read().unwrap();
+        keys.get(topic).map(|cipher| cipher.seal(data))
+    }
+
+    /// Decrypt the supplied data with the data key of the supplied topic.
+    pub fn decrypt(&self, topic: &str, data: &[u8]) -> Result<Vec<u8>> {
+        let keys = self.keys.read().unwrap();
+        match keys.get(topic) {
+            Some(cipher) => cipher.open(data),
+            None => Err(Error::NoDataKey {
+                topic: topic.to_owned(),
+            }),
+        }
+    }
+
+    /// Encrypt the data of the supplied message if its topic is encrypted, marking it as
+    /// such. Any marker supplied by the publisher is discarded.
+    pub fn seal(&self, msg: &mut Message) {
+        msg.attributes.remove(ENCRYPTION_ATTRIBUTE);
+        if let Some(data) = self.encrypt(&msg.topic, &msg.data) {
+            msg.data = data.into();
+            msg.attributes
+                .insert(String::from(ENCRYPTION_ATTRIBUTE), String::from(ALGORITHM));
+        }
+    }
+
+    /// Decrypt the data of the supplied message if it was sealed, removing its marker. The
+    /// data of claim checked messages is only a pointer, and is left to be decrypted as the
+    /// payload is claimed.
+    pub fn open(&self, msg: &mut Message) -> Result<()> {
+        if msg.attributes.remove(ENCRYPTION_ATTRIBUTE).is_none()
+            || msg.attributes.contains_key(CLAIM_CHECK_ATTRIBUTE)
+        {
+            return Ok(());
+        }
+        msg.data = self.decrypt(&msg.topic, &msg.data)?.into();
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Keyring {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Keyring")
+            .field("dir", &self.dir)
+            .field("topics", &self.keys.read().unwrap().len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    use bytes::Bytes;
+
+    #[test]
+    fn test_keyring() {
+        let dir = std::env::temp_dir().join(format!("rift-keys-{}", uuid::Uuid::new_v4()));
+        let cfg = Config {
+            master_key_file: None,
+            master_key: Some(base64::encode([3; KEY_LEN])),
+            key_dir: Some(dir.clone()),
+        };
+        let keyring = Keyring::new(&cfg).unwrap().unwrap();
+
+        assert!(keyring.encrypt("orders", b"hello").is_none());
+        assert!(keyring.decrypt("orders", b"hello").is_err());
+
+        keyring.enable("orders").unwrap();
+        assert!(keyring.is_encrypted("orders"));
+        let sealed = keyring.encrypt("orders", b"hello").unwrap();
+        assert_eq!(keyring.decrypt("orders", &sealed).unwrap(), b"hello");
+
+        let mut msg = Message {
+            topic: String::from("orders"),
+            data: Bytes::from_static(b"hello"),
+            ..Default::default()
+        };
+        keyring.seal(&mut msg);
+        assert_ne!(msg.data, &b"hello"[..]);
+        assert_eq!(msg.attributes[ENCRYPTION_ATTRIBUTE], ALGORITHM);
+        keyring.open(&mut msg).unwrap();
+        assert_eq!(msg.data, &b"hello"[..]);
+        assert!(msg.attributes.is_empty());
+        assert!(keyring.path("orders").unwrap().exists());
+
+        // A new keyring resumes with the stored data key.
+        let restarted = Keyring::new(&cfg).unwrap().unwrap();
+        assert!(!restarted.is_encrypted("orders"));
+        restarted.enable("orders").unwrap();
+        assert_eq!(restarted.decrypt("orders", &sealed).unwrap(), b"hello");
+
+        // Removing a topic destroys its data key.
+        restarted.remove("orders").unwrap();
+        assert!(!keyring.path("orders").unwrap().exists());
+        restarted.enable("orders").unwrap();
+        assert!(restarted.decrypt("orders", &sealed).is_err());
+
+        let cfg = Config {
+            master_key_file: None,
+            master_key: None,
+            key_dir: None,
+        };
+        assert!(Keyring::new(&cfg).unwrap().is_none());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,19 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+mod config;
+mod error;
+mod key;
+mod keyring;
+
+pub use config::Config;
+pub use error::{Error, Result};
+pub use key::{LocalKey, MasterKey};
+pub use keyring::Keyring;
+
+/// The attribute marking a queued message whose data is encrypted with its topic's data key.
+/// Messages are decrypted, and the attribute removed, when they are delivered.
+pub const ENCRYPTION_ATTRIBUTE: &str = "x-rift-encryption";
+
+/// The algorithm that message data and data keys are encrypted with.
+pub const ALGORITHM: &str = "aes-256-gcm";
@@ -0,0 +1,501 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::fmt;
+use std::result;
+use std::str::FromStr;
+
+// extern usings
+use thiserror::Error;
+use tonic::metadata::MetadataMap;
+use tonic::Status;
+
+// crate usings
+use crate::{
+    audit, auth, claimcheck, cluster, encryption, log, metric, pubsub, schema, tenant, trace,
+};
+
+/// The gRPC metadata key that the stable [Code] of an error is returned to clients under.
+pub const CODE_METADATA_KEY: &str = "x-rift-error-code";
+
+/// The gRPC metadata key that the address of the member owning a topic is returned to clients
+/// under, when the request was sent to a member which doesn't own the topic.
+pub const OWNER_METADATA_KEY: &str = "x-rift-owner";
+
+/// The gRPC metadata key that the path of the field violating a topic's schema is returned to
+/// clients under, when a published message does not conform to the schema.
+pub const SCHEMA_PATH_METADATA_KEY: &str = "x-rift-schema-path";
+
+/// The gRPC metadata key that the key of the attribute violating the configured attribute limits
+/// is returned to clients under, when a published message's attributes are rejected.
+pub const ATTRIBUTE_METADATA_KEY: &str = "x-rift-attribute";
+
+/// Custom Result wrapper to simplify usage.
+pub type Result<T> = result::Result<T, Error>;
+
+/// A stable machine-readable error code, which clients and tests can rely on regardless of
+/// changes to error messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Code {
+    /// The referenced topic does not exist.
+    TopicNotFound,
+    /// The referenced subscription does not exist within its topic.
+    SubscriptionNotFound,
+    /// The topic has no subscriptions to deliver a message to.
+    NoSubscriptions,
+    /// The published message exceeds the configured size limit.
+    MessageTooLarge,
+    /// The supplied request is invalid.
+    InvalidArgument,
+    /// The referenced topic is owned by another cluster member.
+    NotOwner,
+    /// The published message does not conform to the topic's schema.
+    SchemaViolation,
+    /// The referenced claim checked payload does not exist.
+    ClaimCheckNotFound,
+    /// The claim check store failed to store or read a payload.
+    ClaimCheck,
+    /// A stored payload no longer matches the checksum it was stored with.
+    DataCorrupted,
+    /// A payload or data key failed to be encrypted or decrypted.
+    Encryption,
+    /// The queue is unable to accept new messages.
+    QueueFull,
+    /// The caller already has the maximum count of open subscribe streams.
+    StreamLimitExceeded,
+    /// A configured resource limit, such as the count of topics or queued bytes, was reached.
+    LimitExceeded,
+    /// A quota of the tenant owning the topic, such as its publish rate, was reached.
+    QuotaExceeded,
+    /// The server is shutting down, and the request should be retried against another.
+    ShuttingDown,
+    /// A publish with the same idempotency key is still in progress.
+    PublishInProgress,
+    /// The referenced lease is either invalid, missing, or expired.
+    InvalidLease,
+    /// The referenced message index is out of range.
+    IndexOutOfRange,
+    /// The referenced message is not in a valid state for the operation.
+    InvalidState,
+    /// A metric failed to register.
+    Metric,
+    /// Logging failed to initialize.
+    Log,
+    /// Tracing failed to initialize.
+    Trace,
+    /// Audit logging failed to initialize.
+    Audit,
+    /// Cluster discovery failed.
+    Cluster,
+    /// The caller supplied missing, malformed, or unknown credentials.
+    Unauthenticated,
+    /// The caller is not allowed to perform the operation.
+    PermissionDenied,
+    /// The auth policy failed to load.
+    Auth,
+    /// The tenant quotas failed to load.
+    Tenant,
+    /// The server failed unexpectedly while handling the request.
+    Internal,
+}
+
+impl Code {
+    /// Return the stable string representation of this code.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Code::TopicNotFound => "TOPIC_NOT_FOUND",
+            Code::SubscriptionNotFound => "SUBSCRIPTION_NOT_FOUND",
+            Code::NoSubscriptions => "NO_SUBSCRIPTIONS",
+            Code::MessageTooLarge => "MESSAGE_TOO_LARGE",
+            Code::InvalidArgument => "INVALID_ARGUMENT",
+            Code::NotOwner => "NOT_OWNER",
+            Code::SchemaViolation => "SCHEMA_VIOLATION",
+            Code::ClaimCheckNotFound => "CLAIM_CHECK_NOT_FOUND",
+            Code::ClaimCheck => "CLAIM_CHECK",
+            Code::DataCorrupted => "DATA_CORRUPTED",
+            Code::Encryption => "ENCRYPTION",
+            Code::QueueFull => "QUEUE_FULL",
+            Code::StreamLimitExceeded => "STREAM_LIMIT_EXCEEDED",
+            Code::LimitExceeded => "LIMIT_EXCEEDED",
+            Code::QuotaExceeded => "QUOTA_EXCEEDED",
+            Code::ShuttingDown => "SHUTTING_DOWN",
+            Code::PublishInProgress => "PUBLISH_IN_PROGRESS",
+            Code::InvalidLease => "INVALID_LEASE",
+            Code::IndexOutOfRange => "INDEX_OUT_OF_RANGE",
+            Code::InvalidState => "INVALID_STATE",
+            Code::Metric => "METRIC",
+            Code::Log => "LOG",
+            Code::Trace => "TRACE",
+            Code::Audit => "AUDIT",
+            Code::Cluster => "CLUSTER",
+            Code::Unauthenticated => "UNAUTHENTICATED",
+            Code::PermissionDenied => "PERMISSION_DENIED",
+            Code::Auth => "AUTH",
+            Code::Tenant => "TENANT",
+            Code::Internal => "INTERNAL",
+        }
+    }
+
+    /// Return the gRPC status code that this code is surfaced to clients as.
+    pub fn to_grpc(&self) -> tonic::Code {
+        match self {
+            Code::TopicNotFound | Code::SubscriptionNotFound | Code::ClaimCheckNotFound => {
+                tonic::Code::NotFound
+            }
+            Code::InvalidArgument | Code::SchemaViolation | Code::MessageTooLarge => {
+                tonic::Code::InvalidArgument
+            }
+            Code::QueueFull
+            | Code::StreamLimitExceeded
+            | Code::LimitExceeded
+            | Code::QuotaExceeded => tonic::Code::ResourceExhausted,
+            Code::NoSubscriptions | Code::NotOwner | Code::InvalidLease | Code::InvalidState => {
+                tonic::Code::FailedPrecondition
+            }
+            Code::IndexOutOfRange => tonic::Code::OutOfRange,
+            Code::DataCorrupted => tonic::Code::DataLoss,
+            Code::ShuttingDown => tonic::Code::Unavailable,
+            Code::PublishInProgress => tonic::Code::Aborted,
+            Code::Unauthenticated => tonic::Code::Unauthenticated,
+            Code::PermissionDenied => tonic::Code::PermissionDenied,
+            Code::ClaimCheck
+            | Code::Encryption
+            | Code::Metric
+            | Code::Log
+            | Code::Trace
+            | Code::Audit
+            | Code::Cluster
+            | Code::Auth
+            | Code::Tenant
+            | Code::Internal => tonic::Code::Internal,
+        }
+    }
+
+    /// Return the code attached to the supplied gRPC status, if any.
+    ///
+    /// ```
+    /// let status: tonic::Status = librift::Error::TopicNotFound {
+    ///     topic: String::from("woot"),
+    /// }
+    /// .into();
+    /// assert_eq!(
+    ///     librift::error::Code::from_status(&status),
+    ///     Some(librift::error::Code::TopicNotFound)
+    /// );
+    /// ```
+    pub fn from_status(status: &Status) -> Option<Code> {
+        status
+            .metadata()
+            .get(CODE_METADATA_KEY)
+            .and_then(|code| code.to_str().ok())
+            .and_then(|code| Code::from_str(code).ok())
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Code {
+    type Err = ();
+
+    fn from_str(code: &str) -> result::Result<Code, ()> {
+        let code = match code {
+            "TOPIC_NOT_FOUND" => Code::TopicNotFound,
+            "SUBSCRIPTION_NOT_FOUND" => Code::SubscriptionNotFound,
+            "NO_SUBSCRIPTIONS" => Code::NoSubscriptions,
+            "MESSAGE_TOO_LARGE" => Code::MessageTooLarge,
+            "INVALID_ARGUMENT" => Code::InvalidArgument,
+            "NOT_OWNER" => Code::NotOwner,
+            "SCHEMA_VIOLATION" => Code::SchemaViolation,
+            "CLAIM_CHECK_NOT_FOUND" => Code::ClaimCheckNotFound,
+            "CLAIM_CHECK" => Code::ClaimCheck,
+            "DATA_CORRUPTED" => Code::DataCorrupted,
+            "ENCRYPTION" => Code::Encryption,
+            "QUEUE_FULL" => Code::QueueFull,
+            "STREAM_LIMIT_EXCEEDED" => Code::StreamLimitExceeded,
+            "LIMIT_EXCEEDED" => Code::LimitExceeded,
+            "QUOTA_EXCEEDED" => Code::QuotaExceeded,
+            "SHUTTING_DOWN" => Code::ShuttingDown,
+            "PUBLISH_IN_PROGRESS" => Code::PublishInProgress,
+            "INVALID_LEASE" => Code::InvalidLease,
+            "INDEX_OUT_OF_RANGE" => Code::IndexOutOfRange,
+            "INVALID_STATE" => Code::InvalidState,
+            "METRIC" => Code::Metric,
+            "LOG" => Code::Log,
+            "TRACE" => Code::Trace,
+            "AUDIT" => Code::Audit,
+            "CLUSTER" => Code::Cluster,
+            "UNAUTHENTICATED" => Code::Unauthenticated,
+            "PERMISSION_DENIED" => Code::PermissionDenied,
+            "AUTH" => Code::Auth,
+            "TENANT" => Code::Tenant,
+            "INTERNAL" => Code::Internal,
+            _ => return Err(()),
+        };
+        Ok(code)
+    }
+}
+
+/// Represents all errors surfaced by librift, each of which maps to a stable [Code].
+#[derive(Error, Debug)]
+pub enum Error {
+    /// Handles references to topics which do not exist.
+    #[error("the supplied topic '{topic}' does not exist")]
+    TopicNotFound {
+        /// The name of the missing topic.
+        topic: String,
+    },
+    /// Handles references to subscriptions which do not exist.
+    #[error("the supplied subscription '{subscription}' is not assoicated with the given topic '{topic}'")]
+    SubscriptionNotFound {
+        /// The name of the missing subscription.
+        subscription: String,
+        /// The name of the topic the subscription was expected within.
+        topic: String,
+    },
+    /// Handles invalid requests.
+    #[error("{reason}")]
+    InvalidArgument {
+        /// The reason the request is invalid.
+        reason: String,
+    },
+    /// Handles published attributes violating the configured attribute limits.
+    #[error("{reason}")]
+    InvalidAttribute {
+        /// The key of the offending attribute, if the violation concerns a single attribute.
+        key: Option<String>,
+        /// The reason the attributes are invalid.
+        reason: String,
+    },
+    /// Handles requests for topics owned by another cluster member.
+    #[error("the supplied topic '{topic}' is owned by cluster member '{owner}' at '{addr}'")]
+    NotOwner {
+        /// The name of the topic.
+        topic: String,
+        /// The ID of the member owning the topic.
+        owner: String,
+        /// The address of the member owning the topic.
+        addr: String,
+    },
+    /// Handles subscribe streams exceeding the per caller limit.
+    #[error("the caller '{caller}' already has the maximum of {limit} open subscribe streams")]
+    StreamLimitExceeded {
+        /// The identity or IP the caller is limited under.
+        caller: String,
+        /// The maximum count of open streams per caller.
+        limit: usize,
+    },
+    /// Handles chunks which would exceed the bytes held for incomplete chunked messages.
+    #[error("the chunks of incomplete messages held for {held_for} would exceed the limit of {limit} bytes")]
+    ChunkLimitExceeded {
+        /// Who the limit applies to, either a single caller or every caller.
+        held_for: String,
+        /// The maximum count of bytes held.
+        limit: usize,
+    },
+    /// Handles restores of messages into subscriptions which already hold messages.
+    #[error("the subscription '{subscription}' of topic '{topic}' already holds messages")]
+    SubscriptionNotEmpty {
+        /// The name of the subscription.
+        subscription: String,
+        /// The name of the topic of the subscription.
+        topic: String,
+    },
+    /// Handles requests arriving while the server is shutting down.
+    #[error("the server is shutting down")]
+    ShuttingDown,
+    /// Handles publishes racing another with the same idempotency key.
+    #[error("a publish with the idempotency key '{key}' is still in progress")]
+    PublishInProgress {
+        /// The idempotency key of the publish.
+        key: String,
+    },
+    /// Handles invalid schemas and messages violating them.
+    #[error(transparent)]
+    Schema(#[from] schema::Error),
+    /// Handles claim check store errors.
+    #[error(transparent)]
+    ClaimCheck(#[from] claimcheck::Error),
+    /// Handles payload encryption errors.
+    #[error(transparent)]
+    Encryption(#[from] encryption::Error),
+    /// Handles queue and slot errors.
+    #[error(transparent)]
+    Pubsub(#[from] pubsub::Error),
+    /// Handles metric registration errors.
+    #[error(transparent)]
+    Metric(#[from] metric::Error),
+    /// Handles logging errors.
+    #[error(transparent)]
+    Log(#[from] log::Error),
+    /// Handles tracing errors.
+    #[error(transparent)]
+    Trace(#[from] trace::Error),
+    /// Handles audit errors.
+    #[error(transparent)]
+    Audit(#[from] audit::Error),
+    /// Handles cluster errors.
+    #[error(transparent)]
+    Cluster(#[from] cluster::Error),
+    /// Handles authentication and authorization errors.
+    #[error(transparent)]
+    Auth(#[from] auth::Error),
+    /// Handles tenant quota errors.
+    #[error(transparent)]
+    Tenant(#[from] tenant::Error),
+}
+
+impl Error {
+    /// Return the stable code of this error.
+    pub fn code(&self) -> Code {
+        match self {
+            Error::TopicNotFound { .. } => Code::TopicNotFound,
+            Error::SubscriptionNotFound { .. } => Code::SubscriptionNotFound,
+            Error::InvalidArgument { .. } => Code::InvalidArgument,
+            Error::InvalidAttribute { .. } => Code::InvalidArgument,
+            Error::NotOwner { .. } => Code::NotOwner,
+            Error::StreamLimitExceeded { .. } => Code::StreamLimitExceeded,
+            Error::ChunkLimitExceeded { .. } => Code::LimitExceeded,
+            Error::SubscriptionNotEmpty { .. } => Code::InvalidState,
+            Error::ShuttingDown => Code::ShuttingDown,
+            Error::PublishInProgress { .. } => Code::PublishInProgress,
+            Error::Schema(schema::Error::Invalid { .. }) => Code::InvalidArgument,
+            Error::Schema(schema::Error::Violation { .. }) => Code::SchemaViolation,
+            Error::Schema(schema::Error::Unsupported { .. }) => Code::InvalidArgument,
+            Error::ClaimCheck(claimcheck::Error::NotFound { .. }) => Code::ClaimCheckNotFound,
+            Error::ClaimCheck(claimcheck::Error::Corrupted { .. }) => Code::DataCorrupted,
+            Error::ClaimCheck(claimcheck::Error::Io(..)) => Code::ClaimCheck,
+            Error::Encryption(..) => Code::Encryption,
+            Error::Pubsub(err) => match err {
+                pubsub::Error::MustBeLocked
+                | pubsub::Error::MustBeFilled
+                | pubsub::Error::MustBeEmpty
+                | pubsub::Error::ReplayInProgress => Code::InvalidState,
+                pubsub::Error::InvalidOrExpiredLease => Code::InvalidLease,
+                pubsub::Error::QueueFull => Code::QueueFull,
+                pubsub::Error::IndexOutOfRange => Code::IndexOutOfRange,
+                pubsub::Error::NoSubscriptions => Code::NoSubscriptions,
+                pubsub::Error::MessageTooLarge { .. } => Code::MessageTooLarge,
+                pubsub::Error::LimitExceeded { .. } => Code::LimitExceeded,
+                pubsub::Error::InvalidName { .. } => Code::InvalidArgument,
+                pubsub::Error::FanOut { .. } => Code::Internal,
+            },
+            Error::Metric(..) => Code::Metric,
+            Error::Log(..) => Code::Log,
+            Error::Trace(..) => Code::Trace,
+            Error::Audit(..) => Code::Audit,
+            Error::Cluster(..) => Code::Cluster,
+            Error::Auth(err) => match err {
+                auth::Error::Unauthenticated { .. } => Code::Unauthenticated,
+                auth::Error::PermissionDenied { .. } => Code::PermissionDenied,
+                auth::Error::Io { .. } | auth::Error::Parse(..) | auth::Error::Invalid { .. } => {
+                    Code::Auth
+                }
+            },
+            Error::Tenant(err) => match err {
+                tenant::Error::QuotaExceeded { .. } => Code::QuotaExceeded,
+                tenant::Error::Io { .. }
+                | tenant::Error::Parse(..)
+                | tenant::Error::Invalid { .. } => Code::Tenant,
+            },
+        }
+    }
+}
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Status {
+        let code = err.code();
+        let mut metadata = MetadataMap::new();
+        metadata.insert(CODE_METADATA_KEY, code.as_str().parse().unwrap());
+        if let Error::NotOwner { addr, .. } = &err {
+            if let Ok(addr) = addr.parse() {
+                metadata.insert(OWNER_METADATA_KEY, addr);
+            }
+        }
+        if let Error::InvalidAttribute { key: Some(key), .. } = &err {
+            if let Ok(key) = key.parse() {
+                metadata.insert(ATTRIBUTE_METADATA_KEY, key);
+            }
+        }
+        if let Error::Schema(schema::Error::Violation { path, .. }) = &err {
+            if let Ok(path) = path.parse() {
+                metadata.insert(SCHEMA_PATH_METADATA_KEY, path);
+            }
+        }
+        Status::with_metadata(code.to_grpc(), err.to_string(), metadata)
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_round_trip() {
+        let codes = [
+            Code::TopicNotFound,
+            Code::SubscriptionNotFound,
+            Code::NoSubscriptions,
+            Code::MessageTooLarge,
+            Code::InvalidArgument,
+            Code::NotOwner,
+            Code::SchemaViolation,
+            Code::ClaimCheckNotFound,
+            Code::ClaimCheck,
+            Code::DataCorrupted,
+            Code::Encryption,
+            Code::QueueFull,
+            Code::StreamLimitExceeded,
+            Code::LimitExceeded,
+            Code::QuotaExceeded,
+            Code::ShuttingDown,
+            Code::PublishInProgress,
+            Code::InvalidLease,
+            Code::IndexOutOfRange,
+            Code::InvalidState,
+            Code::Metric,
+            Code::Log,
+            Code::Trace,
+            Code::Audit,
+            Code::Cluster,
+            Code::Unauthenticated,
+            Code::PermissionDenied,
+            Code::Auth,
+            Code::Tenant,
+            Code::Internal,
+        ];
+        for code in codes {
+            assert_eq!(Ok(code), Code::from_str(code.as_str()));
+        }
+        assert!(Code::from_str("nope").is_err());
+    }
+
+    #[test]
+    fn test_into_status() {
+        let err = Error::from(pubsub::Error::InvalidOrExpiredLease);
+        assert_eq!(err.code(), Code::InvalidLease);
+
+        let status = Status::from(err);
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+        assert_eq!(Code::from_status(&status), Some(Code::InvalidLease));
+
+        let status = Status::from(Error::NotOwner {
+            topic: String::from("woot"),
+            owner: String::from("two"),
+            addr: String::from("10.0.0.2:8081"),
+        });
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+        assert_eq!(
+            status.metadata().get(OWNER_METADATA_KEY).unwrap(),
+            "10.0.0.2:8081"
+        );
+
+        let status = Status::internal("nope");
+        assert_eq!(Code::from_status(&status), None);
+    }
+}
@@ -0,0 +1,19 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// extern usings
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, StructOpt)]
+/// Rift system events configuration.
+pub struct Config {
+    #[structopt(
+        long = "system-events",
+        env = "RIFT_SYSTEM_EVENTS",
+        help = "Whether or not to publish lifecycle events to the __system.events topic.",
+        long_help = "Sets whether or not to publish an event to the __system.events topic whenever a topic or subscription is created, updated or deleted, or a cluster member joins or leaves the ring, so that tooling can react to them without polling.",
+        takes_value = false
+    )]
+    /// Define whether or not to publish lifecycle events to the system events topic.
+    pub system_events: bool,
+}
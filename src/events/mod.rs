@@ -0,0 +1,315 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// extern usings
+use bytes::Bytes;
+use prost_types::Timestamp;
+use tokio::sync::broadcast;
+use tonic::Request;
+
+// crate usings
+use crate::cluster::Membership;
+use crate::grpc::cluster::{ClusterServiceClient, Event as ForwardedEvent};
+use crate::grpc::forward::SECRET_METADATA_KEY;
+use crate::grpc::pubsub::Message;
+use crate::pubsub::Registry;
+
+mod config;
+
+pub use self::config::Config;
+
+/// The topic that lifecycle events are published to, when enabled.
+pub const SYSTEM_EVENTS_TOPIC: &str = "__system.events";
+
+/// The count of events a watcher may fall behind by before it misses events.
+pub const WATCH_CAPACITY: usize = 1024;
+
+/// The lifecycle change an event notifies of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// A topic was created.
+    TopicCreated,
+    /// A topic was updated.
+    TopicUpdated,
+    /// A topic was deleted.
+    TopicDeleted,
+    /// A subscription, named as `topic/subscription`, was created.
+    SubscriptionCreated,
+    /// A subscription, named as `topic/subscription`, was updated.
+    SubscriptionUpdated,
+    /// A subscription, named as `topic/subscription`, was deleted.
+    SubscriptionDeleted,
+    /// A cluster member, named by its ID, was placed on the ring.
+    MemberJoined,
+    /// A cluster member, named by its ID, was removed from the ring.
+    MemberLeft,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            Kind::TopicCreated => "topic.created",
+            Kind::TopicUpdated => "topic.updated",
+            Kind::TopicDeleted => "topic.deleted",
+            Kind::SubscriptionCreated => "subscription.created",
+            Kind::SubscriptionUpdated => "subscription.updated",
+            Kind::SubscriptionDeleted => "subscription.deleted",
+            Kind::MemberJoined => "member.joined",
+            Kind::MemberLeft => "member.left",
+        };
+        f.write_str(kind)
+    }
+}
+
+/// A lifecycle event, as delivered to watchers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    /// The lifecycle change this event notifies of.
+    pub kind: Kind,
+    /// The name of the changed resource.
+    pub name: String,
+    /// When the change was made.
+    pub time: SystemTime,
+}
+
+/// Publishes lifecycle events to the [SYSTEM_EVENTS_TOPIC] topic, and to every watcher. The
+/// default emitter only delivers events to watchers. Clones deliver to the same watchers.
+#[derive(Debug, Clone)]
+pub struct Emitter {
+    registry: Option<Registry<Message>>,
+    membership: Option<Membership>,
+    watchers: broadcast::Sender<Event>,
+}
+
+impl Emitter {
+    /// Create a new emitter based on the supplied configuration, creating the system events
+    /// topic within the supplied registry if enabled.
+    pub fn new(cfg: &Config, registry: &Registry<Message>) -> Self {
+        if !cfg.system_events {
+            return Self::default();
+        }
+        registry.create(String::from(SYSTEM_EVENTS_TOPIC));
+        Self {
+            registry: Some(registry.clone()),
+            ..Self::default()
+        }
+    }
+
+    /// Send the events to the cluster member owning the system events topic when it is owned
+    /// by another member of the supplied membership, so that they are all published where its
+    /// subscribers are served.
+    pub fn with_membership(mut self, membership: Membership) -> Self {
+        self.membership = Some(membership);
+        self
+    }
+
+    /// Watch every event emitted from now on. Watchers falling more than [WATCH_CAPACITY]
+    /// events behind miss the oldest events, and are told so as they next receive.
+    pub fn watch(&self) -> broadcast::Receiver<Event> {
+        self.watchers.subscribe()
+    }
+
+    /// Publish an event of the supplied kind for the supplied resource.
+    pub fn emit(&self, kind: Kind, name: &str) {
+        let time = SystemTime::now();
+        // Sending only fails when nobody is watching.
+        let _ = self.watchers.send(Event {
+            kind,
+            name: name.to_owned(),
+            time,
+        });
+        let time_ms = time
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_millis() as u64)
+            .unwrap_or_default();
+        match &self.membership {
+            Some(membership) if self.registry.is_some() => {
+                if membership.is_local(SYSTEM_EVENTS_TOPIC) {
+                    self.publish(&kind.to_string(), name, time_ms);
+                } else {
+                    let event = ForwardedEvent {
+                        kind: kind.to_string(),
+                        name: name.to_owned(),
+                        time_ms,
+                    };
+                    tokio::spawn(forward(membership.clone(), event));
+                }
+            }
+            _ => self.publish(&kind.to_string(), name, time_ms),
+        }
+    }
+
+    /// Publish an event of the supplied kind, for the supplied resource changed at the supplied
+    /// time in milliseconds since the unix epoch, to the local system events topic only. Events
+    /// published this way aren't delivered to watchers.
+    pub fn publish(&self, kind: &str, name: &str, time_ms: u64) {
+        let topic = match self
+            .registry
+            .as_ref()
+            .and_then(|registry| registry.get(SYSTEM_EVENTS_TOPIC))
+        {
+            Some(topic) => topic,
+            None => return,
+        };
+        let mut attributes = HashMap::with_capacity(2);
+        attributes.insert(String::from("event"), kind.to_owned());
+        attributes.insert(String::from("name"), name.to_owned());
+        let msg = Message {
+            topic: String::from(SYSTEM_EVENTS_TOPIC),
+            attributes,
+            published: Some(Timestamp::from(UNIX_EPOCH + Duration::from_millis(time_ms))),
+            data: Bytes::new(),
+            checksum: None,
+            content_type: String::new(),
+        };
+        // Publishing only fails when the events topic has no subscriptions, in which case
+        // there is nobody to deliver the event to.
+        let _ = topic.push(msg);
+    }
+
+    /// Publish an event for every member placed on or removed from the ring of the supplied
+    /// membership, forever.
+    pub async fn watch_membership(self, membership: Membership) {
+        let ids = |membership: &Membership| {
+            membership
+                .ring_members()
+                .into_iter()
+                .map(|member| member.id)
+                .collect::<HashSet<_>>()
+        };
+        let mut watcher = membership.watch();
+        let mut previous = ids(&membership);
+        while watcher.changed().await.is_ok() {
+            let current = ids(&membership);
+            for id in current.difference(&previous) {
+                self.emit(Kind::MemberJoined, id);
+            }
+            for id in previous.difference(&current) {
+                self.emit(Kind::MemberLeft, id);
+            }
+            previous = current;
+        }
+    }
+}
+
+/// Send the supplied event to the member of the supplied membership owning the system events
+/// topic. Events are best effort, so an event the owner can't be reached for is dropped.
+async fn forward(membership: Membership, event: ForwardedEvent) {
+    let owner = membership.owner(SYSTEM_EVENTS_TOPIC);
+    let channel = match membership.channel(&owner.addr) {
+        Ok(channel) => channel,
+        Err(_) => return,
+    };
+    let mut request = Request::new(event);
+    if let Some(secret) = membership.secret().and_then(|secret| secret.parse().ok()) {
+        request.metadata_mut().insert(SECRET_METADATA_KEY, secret);
+    }
+    let _ = ClusterServiceClient::new(channel)
+        .publish_event(request)
+        .await;
+}
+
+impl Default for Emitter {
+    fn default() -> Self {
+        Self {
+            registry: None,
+            membership: None,
+            watchers: broadcast::channel(WATCH_CAPACITY).0,
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_emitter() {
+        let registry = Registry::default();
+        let emitter = Emitter::new(
+            &Config {
+                system_events: false,
+            },
+            &registry,
+        );
+        assert!(registry.get(SYSTEM_EVENTS_TOPIC).is_none());
+        let mut watcher = emitter.clone().watch();
+        emitter.emit(Kind::TopicCreated, "orders");
+        let event = watcher.try_recv().unwrap();
+        assert_eq!(
+            (event.kind, event.name.as_str()),
+            (Kind::TopicCreated, "orders")
+        );
+        assert!(watcher.try_recv().is_err());
+
+        let emitter = Emitter::new(
+            &Config {
+                system_events: true,
+            },
+            &registry,
+        );
+        let sub = registry
+            .get(SYSTEM_EVENTS_TOPIC)
+            .unwrap()
+            .create(String::from("sub"));
+        emitter.emit(Kind::SubscriptionDeleted, "orders/sub");
+
+        let (_, _, msg) = sub.queue.next().unwrap();
+        assert_eq!(msg.attributes["event"], "subscription.deleted");
+        assert_eq!(msg.attributes["name"], "orders/sub");
+        assert!(sub.queue.next().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_emitter_membership() {
+        let cfg = crate::cluster::Config {
+            node_id: Some(String::from("one")),
+            advertise_addr: None,
+            join: Vec::new(),
+            secret: None,
+            discovery_interval_ms: 30000,
+            heartbeat_interval_ms: 1000,
+            suspect_timeout_ms: 5000,
+            dead_timeout_ms: 30000,
+            evict_timeout_ms: 300000,
+            vnodes: 64,
+        };
+        let membership = Membership::new(&cfg, &"127.0.0.1:8081".parse().unwrap()).unwrap();
+        let registry = Registry::default();
+        let membership = membership.with_topics(registry.clone());
+        let emitter = Emitter::new(
+            &Config {
+                system_events: true,
+            },
+            &registry,
+        )
+        .with_membership(membership.clone());
+        let sub = registry
+            .get(SYSTEM_EVENTS_TOPIC)
+            .unwrap()
+            .create(String::from("sub"));
+
+        // Alone, the local member owns the events topic and publishes to it.
+        emitter.emit(Kind::TopicCreated, "orders");
+        let (_, _, msg) = sub.queue.next().unwrap();
+        assert_eq!(msg.attributes["name"], "orders");
+
+        // Holding the events topic doesn't make the local member its owner, so once the ring
+        // assigns it elsewhere the events are sent there instead, while still being delivered to
+        // local watchers.
+        membership.merge((0..16).map(|idx| {
+            crate::cluster::Member::new(format!("peer-{}", idx), String::from("127.0.0.1:1"), 1)
+        }));
+        assert!(!membership.is_local(SYSTEM_EVENTS_TOPIC));
+        let mut watcher = emitter.watch();
+        emitter.emit(Kind::TopicDeleted, "orders");
+        assert_eq!(watcher.try_recv().unwrap().kind, Kind::TopicDeleted);
+        assert!(sub.queue.next().is_none());
+    }
+}
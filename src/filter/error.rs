@@ -0,0 +1,42 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::result;
+
+// extern usings
+use thiserror::Error;
+
+/// Custom Result wrapper to simplify usage.
+pub type Result<T> = result::Result<T, Error>;
+
+/// Represents filter expressions which can not be evaluated.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// Handles expressions which fail to parse, or are not boolean.
+    #[error("the filter is invalid at position {position}: {reason}")]
+    Invalid {
+        /// The byte offset into the expression the error was found at.
+        position: usize,
+        /// The reason the expression is invalid.
+        reason: String,
+    },
+    /// Handles expressions longer than the maximum supported length.
+    #[error("the filter is {len} bytes long, exceeding the maximum of {max} bytes")]
+    TooLong {
+        /// The length of the expression in bytes.
+        len: usize,
+        /// The maximum length of an expression in bytes.
+        max: usize,
+    },
+}
+
+impl Error {
+    /// Create an invalid expression error at the supplied position.
+    pub(super) fn invalid(position: usize, reason: impl Into<String>) -> Self {
+        Error::Invalid {
+            position,
+            reason: reason.into(),
+        }
+    }
+}
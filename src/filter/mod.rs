@@ -0,0 +1,241 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+mod error;
+mod parser;
+
+pub use error::{Error, Result};
+
+/// The longest filter expression accepted, in bytes.
+pub const MAX_FILTER_LEN: usize = 1024;
+
+/// A comparison between two operands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compare {
+    /// The operands are equal.
+    Eq,
+    /// The operands are not equal.
+    Ne,
+    /// The left operand is less than the right.
+    Lt,
+    /// The left operand is less than or equal to the right.
+    Le,
+    /// The left operand is greater than the right.
+    Gt,
+    /// The left operand is greater than or equal to the right.
+    Ge,
+}
+
+impl Compare {
+    fn holds(&self, ordering: Ordering) -> bool {
+        match self {
+            Compare::Eq => ordering == Ordering::Equal,
+            Compare::Ne => ordering != Ordering::Equal,
+            Compare::Lt => ordering == Ordering::Less,
+            Compare::Le => ordering != Ordering::Greater,
+            Compare::Gt => ordering == Ordering::Greater,
+            Compare::Ge => ordering != Ordering::Less,
+        }
+    }
+}
+
+impl fmt::Display for Compare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Compare::Eq => "==",
+            Compare::Ne => "!=",
+            Compare::Lt => "<",
+            Compare::Le => "<=",
+            Compare::Gt => ">",
+            Compare::Ge => ">=",
+        })
+    }
+}
+
+/// A value compared by a filter.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    /// The value of the named message attribute.
+    Attribute(String),
+    /// A string literal.
+    Str(String),
+    /// A number literal.
+    Num(f64),
+}
+
+impl Operand {
+    fn resolve<'a>(&'a self, attributes: &'a HashMap<String, String>) -> Option<&'a str> {
+        match self {
+            Operand::Attribute(key) => attributes.get(key).map(String::as_str),
+            Operand::Str(value) => Some(value),
+            Operand::Num(_) => None,
+        }
+    }
+
+    fn number(&self, attributes: &HashMap<String, String>) -> Option<f64> {
+        match self {
+            Operand::Num(value) => Some(*value),
+            operand => operand.resolve(attributes)?.parse().ok(),
+        }
+    }
+}
+
+/// A parsed boolean filter expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// A boolean literal.
+    Bool(bool),
+    /// Negates the inner expression.
+    Not(Box<Expr>),
+    /// Holds when both expressions hold.
+    And(Box<Expr>, Box<Expr>),
+    /// Holds when either expression holds.
+    Or(Box<Expr>, Box<Expr>),
+    /// Compares two operands, numerically if either is a number literal and otherwise as
+    /// strings. Comparisons involving a missing attribute, or an attribute which is not a
+    /// number when compared to one, never hold.
+    Compare(Operand, Compare, Operand),
+    /// Holds when the message carries the named attribute.
+    HasAttr(String),
+    /// Holds when the named attribute starts with the supplied prefix.
+    HasPrefix(String, String),
+}
+
+impl Expr {
+    /// Evaluate this expression against the supplied message attributes.
+    pub fn eval(&self, attributes: &HashMap<String, String>) -> bool {
+        match self {
+            Expr::Bool(value) => *value,
+            Expr::Not(expr) => !expr.eval(attributes),
+            Expr::And(lhs, rhs) => lhs.eval(attributes) && rhs.eval(attributes),
+            Expr::Or(lhs, rhs) => lhs.eval(attributes) || rhs.eval(attributes),
+            Expr::Compare(lhs, compare, rhs) => {
+                let ordering = match (lhs, rhs) {
+                    (Operand::Num(_), _) | (_, Operand::Num(_)) => lhs
+                        .number(attributes)
+                        .zip(rhs.number(attributes))
+                        .and_then(|(lhs, rhs)| lhs.partial_cmp(&rhs)),
+                    _ => lhs
+                        .resolve(attributes)
+                        .zip(rhs.resolve(attributes))
+                        .map(|(lhs, rhs)| lhs.cmp(rhs)),
+                };
+                matches!(ordering, Some(ordering) if compare.holds(ordering))
+            }
+            Expr::HasAttr(key) => attributes.contains_key(key),
+            Expr::HasPrefix(key, prefix) => {
+                matches!(attributes.get(key), Some(value) if value.starts_with(prefix.as_str()))
+            }
+        }
+    }
+}
+
+/// A filter selecting messages by their attributes, with a small expression language.
+///
+/// Attributes are accessed as `attributes.name`, or `attributes["name"]` for names which
+/// are not identifiers, and compared to strings, numbers, or other attributes with `==`,
+/// `!=`, `<`, `<=`, `>` and `>=`. `hasattr("name")` checks that an attribute is present, and
+/// `hasprefix(attributes.name, "prefix")` that it starts with a prefix. Expressions are
+/// combined with `&&`, `||`, `!` and parentheses.
+///
+/// ```
+/// use std::collections::HashMap;
+/// use librift::filter::Filter;
+///
+/// let filter = Filter::parse(r#"attributes.kind == "order" && attributes.total >= 100"#).unwrap();
+/// let mut attributes = HashMap::new();
+/// attributes.insert(String::from("kind"), String::from("order"));
+/// attributes.insert(String::from("total"), String::from("250"));
+/// assert!(filter.matches(&attributes));
+///
+/// assert!(Filter::parse("attributes.kind =").is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    source: String,
+    expr: Expr,
+}
+
+impl Filter {
+    /// Parse the supplied expression, failing if it is not a valid boolean expression.
+    pub fn parse(source: &str) -> Result<Self> {
+        if source.len() > MAX_FILTER_LEN {
+            return Err(Error::TooLong {
+                len: source.len(),
+                max: MAX_FILTER_LEN,
+            });
+        }
+        Ok(Self {
+            source: source.to_owned(),
+            expr: parser::parse(source)?,
+        })
+    }
+
+    /// Return the expression this filter was parsed from.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Return the parsed expression of this filter.
+    pub fn expr(&self) -> &Expr {
+        &self.expr
+    }
+
+    /// Return whether a message carrying the supplied attributes matches this filter.
+    pub fn matches(&self, attributes: &HashMap<String, String>) -> bool {
+        self.expr.eval(attributes)
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let attributes = [("kind", "order"), ("total", "250"), ("region", "eu-west")]
+            .into_iter()
+            .map(|(key, value)| (String::from(key), String::from(value)))
+            .collect::<HashMap<_, _>>();
+        let cases = [
+            ("true", true),
+            ("attributes.kind == 'order'", true),
+            ("attributes.kind != 'order'", false),
+            ("attributes.kind < 'p'", true),
+            ("attributes.total > 99", true),
+            ("attributes.total > '99'", false),
+            ("attributes.total <= 250.0", true),
+            ("attributes.kind >= 1", false),
+            ("attributes.missing != 'order'", false),
+            ("!(attributes.missing == 'order')", true),
+            ("attributes.kind == attributes.total", false),
+            ("hasattr('region') && !hasattr('missing')", true),
+            ("hasprefix(attributes.region, 'eu-')", true),
+            ("hasprefix(attributes.missing, '')", false),
+            (
+                "attributes.kind == 'refund' || attributes.total >= 250",
+                true,
+            ),
+        ];
+        for (src, expected) in cases {
+            let filter = Filter::parse(src).unwrap();
+            assert_eq!(filter.matches(&attributes), expected, "{}", src);
+            assert_eq!(filter.to_string(), src);
+        }
+
+        let long = format!("'{}' == 'a'", "a".repeat(MAX_FILTER_LEN));
+        assert!(matches!(Filter::parse(&long), Err(Error::TooLong { .. })));
+    }
+}
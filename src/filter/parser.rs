@@ -0,0 +1,378 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// crate usings
+use super::{Compare, Error, Expr, Operand, Result};
+
+/// The deepest nesting of parentheses and negations an expression may contain, bounding the
+/// recursion needed to parse and evaluate it.
+const MAX_DEPTH: usize = 32;
+
+/// The name every attribute is accessed through.
+const ATTRIBUTES: &str = "attributes";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Num(f64),
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Dot,
+    Comma,
+    Not,
+    And,
+    Or,
+    Compare(Compare),
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Ident(ident) => format!("'{}'", ident),
+            Token::Str(value) => format!("the string {:?}", value),
+            Token::Num(value) => format!("the number {}", value),
+            Token::LParen => String::from("'('"),
+            Token::RParen => String::from("')'"),
+            Token::LBracket => String::from("'['"),
+            Token::RBracket => String::from("']'"),
+            Token::Dot => String::from("'.'"),
+            Token::Comma => String::from("','"),
+            Token::Not => String::from("'!'"),
+            Token::And => String::from("'&&'"),
+            Token::Or => String::from("'||'"),
+            Token::Compare(compare) => format!("'{}'", compare),
+        }
+    }
+}
+
+/// Split the supplied expression into its tokens, each paired with its byte offset.
+fn tokenize(src: &str) -> Result<Vec<(usize, Token)>> {
+    let mut tokens = Vec::new();
+    let mut chars = src.char_indices().peekable();
+    while let Some((pos, c)) = chars.next() {
+        let mut next_is = |expected: char| chars.next_if(|(_, c)| *c == expected).is_some();
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '[' => Token::LBracket,
+            ']' => Token::RBracket,
+            '.' => Token::Dot,
+            ',' => Token::Comma,
+            '&' if next_is('&') => Token::And,
+            '|' if next_is('|') => Token::Or,
+            '=' if next_is('=') => Token::Compare(Compare::Eq),
+            '!' if next_is('=') => Token::Compare(Compare::Ne),
+            '!' => Token::Not,
+            '<' if next_is('=') => Token::Compare(Compare::Le),
+            '<' => Token::Compare(Compare::Lt),
+            '>' if next_is('=') => Token::Compare(Compare::Ge),
+            '>' => Token::Compare(Compare::Gt),
+            '"' | '\'' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, end)) if end == c => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, escaped)) => value.push(escaped),
+                            None => return Err(Error::invalid(pos, "unterminated string")),
+                        },
+                        Some((_, other)) => value.push(other),
+                        None => return Err(Error::invalid(pos, "unterminated string")),
+                    }
+                }
+                Token::Str(value)
+            }
+            c if c.is_ascii_digit() || c == '-' => {
+                let mut end = pos + c.len_utf8();
+                while let Some((idx, _)) = chars.next_if(|(_, c)| c.is_ascii_digit() || *c == '.') {
+                    end = idx + 1;
+                }
+                match src[pos..end].parse() {
+                    Ok(value) => Token::Num(value),
+                    Err(_) => {
+                        let reason = format!("'{}' is not a number", &src[pos..end]);
+                        return Err(Error::invalid(pos, reason));
+                    }
+                }
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut end = pos + 1;
+                while let Some((idx, c)) =
+                    chars.next_if(|(_, c)| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+                {
+                    end = idx + c.len_utf8();
+                }
+                Token::Ident(src[pos..end].to_owned())
+            }
+            c => return Err(Error::invalid(pos, format!("unexpected '{}'", c))),
+        };
+        tokens.push((pos, token));
+    }
+    Ok(tokens)
+}
+
+/// A recursive descent parser over the tokens of an expression.
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+    len: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn position(&self) -> usize {
+        self.tokens.get(self.next).map_or(self.len, |(pos, _)| *pos)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(_, token)| token)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.next).map(|(_, token)| token.clone());
+        self.next += 1;
+        token
+    }
+
+    fn unexpected(&self, expected: &str) -> Error {
+        let found = match self.peek() {
+            Some(token) => token.describe(),
+            None => String::from("the end of the filter"),
+        };
+        Error::invalid(
+            self.position(),
+            format!("expected {} but found {}", expected, found),
+        )
+    }
+
+    fn expect(&mut self, token: Token) -> Result<()> {
+        if self.peek() != Some(&token) {
+            return Err(self.unexpected(&token.describe()));
+        }
+        self.next += 1;
+        Ok(())
+    }
+
+    fn nest(&mut self) -> Result<()> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            let reason = format!("the filter is nested more than {} levels deep", MAX_DEPTH);
+            return Err(Error::invalid(self.position(), reason));
+        }
+        Ok(())
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut expr = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.next += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        match self.peek() {
+            Some(Token::Not) => {
+                self.next += 1;
+                self.nest()?;
+                let expr = Expr::Not(Box::new(self.unary()?));
+                self.depth -= 1;
+                Ok(expr)
+            }
+            Some(Token::LParen) => {
+                self.next += 1;
+                self.nest()?;
+                let expr = self.or()?;
+                self.expect(Token::RParen)?;
+                self.depth -= 1;
+                Ok(expr)
+            }
+            Some(Token::Ident(ident)) if ident == "true" || ident == "false" => {
+                let value = ident == "true";
+                self.next += 1;
+                Ok(Expr::Bool(value))
+            }
+            Some(Token::Ident(ident)) if ident != ATTRIBUTES => {
+                let ident = ident.clone();
+                self.call(ident)
+            }
+            _ => self.comparison(),
+        }
+    }
+
+    fn call(&mut self, function: String) -> Result<Expr> {
+        let position = self.position();
+        self.next += 1;
+        self.expect(Token::LParen)?;
+        let expr = match function.as_str() {
+            "hasattr" => Expr::HasAttr(self.string()?),
+            "hasprefix" => {
+                let key = self.attribute()?;
+                self.expect(Token::Comma)?;
+                Expr::HasPrefix(key, self.string()?)
+            }
+            _ => {
+                let reason = format!("unknown function '{}'", function);
+                return Err(Error::invalid(position, reason));
+            }
+        };
+        self.expect(Token::RParen)?;
+        Ok(expr)
+    }
+
+    fn comparison(&mut self) -> Result<Expr> {
+        let lhs = self.operand()?;
+        let compare = match self.peek() {
+            Some(Token::Compare(compare)) => *compare,
+            _ => return Err(self.unexpected("a comparison")),
+        };
+        self.next += 1;
+        Ok(Expr::Compare(lhs, compare, self.operand()?))
+    }
+
+    fn operand(&mut self) -> Result<Operand> {
+        match self.peek() {
+            Some(Token::Str(_)) => self.string().map(Operand::Str),
+            Some(Token::Num(value)) => {
+                let value = *value;
+                self.next += 1;
+                Ok(Operand::Num(value))
+            }
+            Some(Token::Ident(ident)) if ident == ATTRIBUTES => {
+                self.attribute().map(Operand::Attribute)
+            }
+            _ => Err(self.unexpected("an attribute, string or number")),
+        }
+    }
+
+    fn attribute(&mut self) -> Result<String> {
+        match self.peek() {
+            Some(Token::Ident(ident)) if ident == ATTRIBUTES => self.next += 1,
+            _ => return Err(self.unexpected("an attribute")),
+        }
+        match self.advance() {
+            Some(Token::Dot) => match self.advance() {
+                Some(Token::Ident(key)) => Ok(key),
+                _ => {
+                    self.next -= 1;
+                    Err(self.unexpected("an attribute name"))
+                }
+            },
+            Some(Token::LBracket) => {
+                let key = self.string()?;
+                self.expect(Token::RBracket)?;
+                Ok(key)
+            }
+            _ => {
+                self.next -= 1;
+                Err(self.unexpected("'.' or '['"))
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        match self.peek() {
+            Some(Token::Str(value)) => {
+                let value = value.clone();
+                self.next += 1;
+                Ok(value)
+            }
+            _ => Err(self.unexpected("a string")),
+        }
+    }
+}
+
+/// Parse the supplied expression.
+pub(super) fn parse(src: &str) -> Result<Expr> {
+    let mut parser = Parser {
+        tokens: tokenize(src)?,
+        next: 0,
+        len: src.len(),
+        depth: 0,
+    };
+    let expr = parser.or()?;
+    if parser.peek().is_some() {
+        return Err(parser.unexpected("'&&', '||' or the end of the filter"));
+    }
+    Ok(expr)
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let attr = |key: &str| Operand::Attribute(String::from(key));
+        assert_eq!(
+            parse(r#"attributes.kind == "order" && !(attributes["x.y"] < 2.5)"#).unwrap(),
+            Expr::And(
+                Box::new(Expr::Compare(
+                    attr("kind"),
+                    Compare::Eq,
+                    Operand::Str(String::from("order"))
+                )),
+                Box::new(Expr::Not(Box::new(Expr::Compare(
+                    attr("x.y"),
+                    Compare::Lt,
+                    Operand::Num(2.5)
+                )))),
+            )
+        );
+        assert_eq!(
+            parse("hasattr('a') || hasprefix(attributes.b-c, 'x') && true").unwrap(),
+            Expr::Or(
+                Box::new(Expr::HasAttr(String::from("a"))),
+                Box::new(Expr::And(
+                    Box::new(Expr::HasPrefix(String::from("b-c"), String::from("x"))),
+                    Box::new(Expr::Bool(true)),
+                )),
+            )
+        );
+
+        let invalid = [
+            ("", 0),
+            ("attributes.kind", 15),
+            ("attributes.kind == ", 19),
+            ("attributes.kind = 'a'", 16),
+            ("attributes == 'a'", 11),
+            ("'a' == 'b' 'c'", 11),
+            ("(true", 5),
+            ("hasattr(attributes.a)", 8),
+            ("hasprefix('a', 'b')", 10),
+            ("contains(attributes.a, 'b')", 0),
+            ("attributes.a == 'unterminated", 16),
+            ("attributes.a == 1.2.3", 16),
+            ("attributes.a == #", 16),
+        ];
+        for (src, position) in invalid {
+            match parse(src) {
+                Err(Error::Invalid {
+                    position: actual, ..
+                }) => {
+                    assert_eq!(actual, position, "{}", src)
+                }
+                res => panic!("expected '{}' to be invalid, got {:?}", src, res),
+            }
+        }
+
+        let nested = format!("{}true{}", "(".repeat(MAX_DEPTH), ")".repeat(MAX_DEPTH));
+        assert!(parse(&nested).is_ok());
+        assert!(parse(&format!("!{}", nested)).is_err());
+    }
+}
@@ -0,0 +1,424 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::collections::HashSet;
+use std::future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use futures::{stream, Stream, StreamExt};
+use tonic::{Code, Request, Response, Status, Streaming};
+
+use crate::grpc::interceptor::{IdentityExt, RequestIdExt};
+use crate::grpc::pubsub::Message;
+use crate::grpc::subscription::{self, SubscriptionService};
+use crate::grpc::topic::{self, TopicService};
+use crate::pubsub::{Registry, Sub};
+
+use super::proto::backup_service_server::BackupService;
+use super::{BackupMessage, BackupRecord, BackupRequest, Record, RestoreResponse};
+
+/// Streams the records of a backup, one topic at a time, from a sorted snapshot of the
+/// topic names. Topics deleted since the snapshot was taken are skipped.
+pub type BackupStream = Pin<Box<dyn Stream<Item = Result<BackupRecord, Status>> + Send>>;
+
+/// The count of messages copied out of a subscription at a time as it is backed up.
+const BACKUP_CHUNK: usize = 1000;
+
+/// The Backup service implementation.
+///
+/// Topics and subscriptions are described and restored through the topic and subscription
+/// handlers, so that restoring them is validated, audited and announced exactly as if they
+/// were created through their own services. Clones share the same registry.
+#[derive(Debug, Clone)]
+pub struct Handler {
+    topic_registry: Registry<Message>,
+    topics: topic::Handler,
+    subscriptions: subscription::Handler,
+}
+
+impl Handler {
+    /// Create a new handler backing up and restoring the supplied registry, through the
+    /// supplied topic and subscription handlers sharing it.
+    pub fn new(
+        topic_registry: Registry<Message>,
+        topics: topic::Handler,
+        subscriptions: subscription::Handler,
+    ) -> Self {
+        Self {
+            topic_registry,
+            topics,
+            subscriptions,
+        }
+    }
+
+    /// Record the supplied topic, followed by each of its subscriptions and, unless only
+    /// metadata is requested, their messages. Topics deleted since the backup began record
+    /// nothing. Messages are only copied as the stream is polled, a chunk at a time.
+    async fn records(&self, name: Arc<str>, metadata_only: bool) -> BackupStream {
+        let request = Request::new(topic::GetRequest {
+            name: name.to_string(),
+        });
+        let described = match TopicService::get(&self.topics, request).await {
+            Ok(described) => described.into_inner(),
+            Err(status) if status.code() == Code::NotFound => return Box::pin(stream::empty()),
+            Err(status) => return Box::pin(stream::once(future::ready(Err(status)))),
+        };
+        let topic = match self.topic_registry.get(&name) {
+            Some(topic) => topic,
+            None => return Box::pin(stream::empty()),
+        };
+
+        let subs = topic.iter(|subs| subs.cloned().collect::<Vec<_>>());
+        let subs = stream::iter(subs).flat_map(move |(sub_name, sub)| {
+            let described = subscription::Subscription::from_inner(
+                sub_name.to_string(),
+                name.to_string(),
+                sub.clone(),
+            );
+            let described =
+                stream::once(future::ready(Ok(record(Record::Subscription(described)))));
+            let messages = match metadata_only {
+                true => None,
+                false => Some(messages(sub_name, sub)),
+            };
+            described.chain(stream::iter(messages).flatten())
+        });
+        let described = stream::once(future::ready(Ok(record(Record::Topic(described)))));
+        Box::pin(described.chain(subs))
+    }
+
+    /// Restore the supplied record on behalf of the caller of the restore, counting it among
+    /// what has been restored. Messages are refused by subscriptions already holding messages
+    /// before the first of them is restored, as restoring them would duplicate those held.
+    async fn restore_record(
+        &self,
+        caller: &Caller,
+        record: BackupRecord,
+        restored: &mut RestoreResponse,
+        restoring: &mut HashSet<(String, String)>,
+    ) -> Result<(), Status> {
+        match record.record {
+            Some(Record::Topic(described)) => {
+                let create = topic::CreateRequest {
+                    name: described.name.clone(),
+                    schema: described.schema,
+                    encrypted: described.encrypted,
+                    default_subscription: false,
+                };
+                TopicService::create(&self.topics, caller.request(create)).await?;
+                if !described.routes.is_empty() || !described.forwards.is_empty() {
+                    let update = topic::UpdateRequest {
+                        name: described.name,
+                        routes: described.routes,
+                        forwards: described.forwards,
+                    };
+                    TopicService::update(&self.topics, caller.request(update)).await?;
+                }
+                restored.topics += 1;
+            }
+            Some(Record::Subscription(described)) => {
+                let create = subscription::CreateRequest {
+                    name: described.name,
+                    topic: described.topic,
+                    push: described.push,
+                    quota: described.quota,
+                    filter: described.filter,
+                    max_outstanding_messages: described.max_outstanding_messages,
+                    ordered: described.ordered,
+                };
+                SubscriptionService::create(&self.subscriptions, caller.request(create)).await?;
+                restored.subscriptions += 1;
+            }
+            Some(Record::Message(backed_up)) => {
+                let message = backed_up
+                    .message
+                    .ok_or_else(|| invalid("every backed up message must hold a message"))?;
+                let topic = self.topic_registry.get(&message.topic).ok_or_else(|| {
+                    crate::Error::TopicNotFound {
+                        topic: message.topic.clone(),
+                    }
+                })?;
+                let sub = topic.get(&backed_up.subscription).ok_or_else(|| {
+                    crate::Error::SubscriptionNotFound {
+                        subscription: backed_up.subscription.clone(),
+                        topic: message.topic.clone(),
+                    }
+                })?;
+                let restoring_into = (message.topic.clone(), backed_up.subscription);
+                if !restoring.contains(&restoring_into) {
+                    if !sub.queue.is_empty() {
+                        let (topic, subscription) = restoring_into;
+                        return Err(crate::Error::SubscriptionNotEmpty {
+                            subscription,
+                            topic,
+                        }
+                        .into());
+                    }
+                    restoring.insert(restoring_into);
+                }
+                sub.queue.push(message).map_err(crate::Error::from)?;
+                restored.messages += 1;
+            }
+            None => {
+                return Err(invalid(
+                    "every backup record must hold a topic, subscription or message",
+                )
+                .into())
+            }
+        }
+        Ok(())
+    }
+
+    async fn _backup(
+        &self,
+        request: Request<BackupRequest>,
+    ) -> Result<Response<BackupStream>, Status> {
+        let metadata_only = request.into_inner().metadata_only;
+        let names = self.topic_registry.names();
+        let handler = self.clone();
+        let stream = stream::iter(names)
+            .then(move |name| {
+                let handler = handler.clone();
+                async move { handler.records(name, metadata_only).await }
+            })
+            .flatten();
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn _restore(
+        &self,
+        request: Request<Streaming<BackupRecord>>,
+    ) -> Result<Response<RestoreResponse>, Status> {
+        let caller = Caller::of(&request);
+        let mut records = request.into_inner();
+        let (mut restored, mut restoring) = (RestoreResponse::default(), HashSet::new());
+        while let Some(record) = records.message().await? {
+            self.restore_record(&caller, record, &mut restored, &mut restoring)
+                .await?;
+        }
+        Ok(Response::new(restored))
+    }
+}
+
+/// The caller of a restore, on whose behalf every topic and subscription is restored.
+#[derive(Debug, Default)]
+struct Caller {
+    identity: Option<String>,
+    request_id: Option<String>,
+}
+
+impl Caller {
+    fn of<T>(request: &Request<T>) -> Self {
+        let extensions = request.extensions();
+        Self {
+            identity: extensions
+                .get::<IdentityExt>()
+                .map(|ext| ext.identity.clone()),
+            request_id: extensions.get::<RequestIdExt>().map(|ext| ext.id.clone()),
+        }
+    }
+
+    /// Wrap the supplied message in a request carrying the identity and request ID of the
+    /// caller, so that it is audited as theirs.
+    fn request<T>(&self, message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        if let Some(identity) = &self.identity {
+            request.extensions_mut().insert(IdentityExt {
+                identity: identity.clone(),
+            });
+        }
+        if let Some(id) = &self.request_id {
+            request
+                .extensions_mut()
+                .insert(RequestIdExt { id: id.clone() });
+        }
+        request
+    }
+}
+
+/// Record the messages of the supplied subscription, copying them out of it a chunk at a
+/// time as the records are polled.
+fn messages(
+    sub_name: Arc<str>,
+    sub: Sub<Message>,
+) -> impl Stream<Item = Result<BackupRecord, Status>> {
+    stream::unfold(None, move |after| {
+        let chunk = sub.queue.snapshot_after(after, BACKUP_CHUNK);
+        let next = chunk.last().map(|(sequence, _)| Some(*sequence));
+        let records = chunk
+            .into_iter()
+            .map(|(_, message)| {
+                Ok(record(Record::Message(BackupMessage {
+                    subscription: sub_name.to_string(),
+                    message: Some(message),
+                })))
+            })
+            .collect::<Vec<_>>();
+        future::ready(next.map(|next| (stream::iter(records), next)))
+    })
+    .flatten()
+}
+
+fn invalid(reason: &str) -> crate::Error {
+    crate::Error::InvalidArgument {
+        reason: reason.to_owned(),
+    }
+}
+
+fn record(record: Record) -> BackupRecord {
+    BackupRecord {
+        record: Some(record),
+    }
+}
+
+#[tonic::async_trait]
+impl BackupService for Handler {
+    type BackupStream = BackupStream;
+
+    #[inline]
+    async fn backup(
+        &self,
+        request: Request<BackupRequest>,
+    ) -> Result<Response<Self::BackupStream>, Status> {
+        self._backup(request).await
+    }
+
+    #[inline]
+    async fn restore(
+        &self,
+        request: Request<Streaming<BackupRecord>>,
+    ) -> Result<Response<RestoreResponse>, Status> {
+        self._restore(request).await
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    fn handler() -> Handler {
+        let registry = Registry::default();
+        Handler::new(
+            registry.clone(),
+            topic::Handler::with_registry(registry.clone()),
+            subscription::Handler::with_registry(registry),
+        )
+    }
+
+    fn message(topic: &str, data: &[u8]) -> Message {
+        Message {
+            topic: topic.to_owned(),
+            data: data.to_vec().into(),
+            ..Message::default()
+        }
+    }
+
+    fn backup(handler: &Handler, metadata_only: bool) -> Vec<BackupRecord> {
+        let request = Request::new(BackupRequest { metadata_only });
+        let stream = aw!(handler.backup(request)).unwrap().into_inner();
+        aw!(stream.map(Result::unwrap).collect())
+    }
+
+    #[test]
+    fn test_backup_restore() {
+        let source = handler();
+        for name in ["orders", "billing/invoices"] {
+            let topic = source.topic_registry.create(name.to_owned());
+            let sub = topic.create(String::from("audit"));
+            sub.queue.push(message(name, b"first")).unwrap();
+            sub.queue.push(message(name, b"second")).unwrap();
+        }
+
+        let records = backup(&source, false);
+        let kinds: Vec<_> = records
+            .iter()
+            .map(|record| match &record.record {
+                Some(Record::Topic(topic)) => format!("topic {}", topic.name),
+                Some(Record::Subscription(sub)) => format!("subscription {}", sub.name),
+                Some(Record::Message(msg)) => {
+                    let data = msg.message.as_ref().unwrap().data.clone();
+                    format!("message {}", String::from_utf8(data.to_vec()).unwrap())
+                }
+                None => panic!("expected every record to be set"),
+            })
+            .collect();
+        let expected = [
+            "topic billing/invoices",
+            "subscription audit",
+            "message first",
+            "message second",
+            "topic orders",
+            "subscription audit",
+            "message first",
+            "message second",
+        ];
+        assert_eq!(kinds, expected);
+        assert_eq!(backup(&source, true).len(), 4);
+
+        let target = handler();
+        let caller = Caller::default();
+        let (mut restored, mut restoring) = (RestoreResponse::default(), HashSet::new());
+        for record in records.clone() {
+            aw!(target.restore_record(&caller, record, &mut restored, &mut restoring)).unwrap();
+        }
+        assert_eq!(
+            restored,
+            RestoreResponse {
+                topics: 2,
+                subscriptions: 2,
+                messages: 4,
+            }
+        );
+        let sub = target.topic_registry.get("orders").unwrap().get("audit");
+        let data: Vec<_> = sub
+            .unwrap()
+            .queue
+            .snapshot()
+            .into_iter()
+            .map(|msg| msg.data.to_vec())
+            .collect();
+        assert_eq!(data, vec![b"first".to_vec(), b"second".to_vec()]);
+
+        let orphan = record(Record::Message(BackupMessage {
+            subscription: String::from("nope"),
+            message: Some(message("orders", b"lost")),
+        }));
+        let res = aw!(target.restore_record(&caller, orphan, &mut restored, &mut restoring));
+        assert_eq!(res.unwrap_err().code(), Code::NotFound);
+        let res = aw!(target.restore_record(
+            &caller,
+            BackupRecord::default(),
+            &mut restored,
+            &mut restoring
+        ));
+        assert_eq!(res.unwrap_err().code(), Code::InvalidArgument);
+        assert_eq!(restored.messages, 4);
+
+        // Restoring the messages again would duplicate those already held.
+        let mut restoring = HashSet::new();
+        let res =
+            aw!(target.restore_record(&caller, records[2].clone(), &mut restored, &mut restoring));
+        assert_eq!(res.unwrap_err().code(), Code::FailedPrecondition);
+        assert_eq!(restored.messages, 4);
+    }
+
+    #[test]
+    fn test_backup_chunks() {
+        let source = handler();
+        let topic = source.topic_registry.create(String::from("orders"));
+        let sub = topic.create(String::from("audit"));
+        for _ in 0..BACKUP_CHUNK * 2 + 1 {
+            sub.queue.push(message("orders", b"data")).unwrap();
+        }
+        // Messages are copied a chunk at a time without skipping or repeating any.
+        assert_eq!(backup(&source, false).len(), BACKUP_CHUNK * 2 + 3);
+    }
+}
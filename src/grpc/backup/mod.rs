@@ -0,0 +1,18 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+use crate::grpc::{pubsub, subscription, topic};
+
+mod proto {
+    tonic::include_proto!("backup");
+}
+mod handler;
+
+pub(crate) const FILE_DESCRIPTOR_SET: &[u8] =
+    tonic::include_file_descriptor_set!("backup_descriptor");
+
+pub use handler::{BackupStream, Handler};
+pub(crate) use proto::backup_record::Record;
+pub use proto::backup_service_client::BackupServiceClient;
+pub use proto::backup_service_server::BackupServiceServer;
+pub use proto::{BackupMessage, BackupRecord, BackupRequest, RestoreResponse};
@@ -0,0 +1,373 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::{Stream, StreamExt};
+use tonic::{Request, Response, Status};
+
+use crate::cluster::Membership;
+use crate::events::Emitter;
+use crate::grpc::forward::SECRET_METADATA_KEY;
+
+use super::proto::cluster_service_server::ClusterService;
+use super::{
+    Event, GetTopologyRequest, HeartbeatRequest, HeartbeatResponse, ListMembersRequest, Member,
+    PublishEventResponse, TopicOwner, Topology, WatchTopologyRequest,
+};
+
+pub type TopologyStream = Pin<Box<dyn Stream<Item = Result<Topology, Status>> + Send>>;
+
+/// Build the current topology of the supplied membership, including the owners of the
+/// supplied topics.
+fn topology(membership: &Membership, topics: &[String]) -> Topology {
+    let topics = topics
+        .iter()
+        .map(|topic| TopicOwner {
+            topic: topic.clone(),
+            owner: Some(membership.owner(topic).into()),
+        })
+        .collect();
+    Topology {
+        version: membership.version(),
+        vnodes: membership.vnodes() as u64,
+        members: membership
+            .ring_members()
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+        topics,
+    }
+}
+
+pub struct MemberStream(Vec<Member>);
+
+impl Stream for MemberStream {
+    type Item = Result<Member, Status>;
+    fn poll_next(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let item = self.0.pop().map(Ok);
+        Poll::Ready(item)
+    }
+}
+
+/// The Cluster service implementation.
+#[derive(Debug)]
+pub struct Handler {
+    membership: Membership,
+    events: Emitter,
+}
+
+impl Handler {
+    /// Create a new handler exposing the supplied membership.
+    pub fn with_membership(membership: Membership) -> Self {
+        Self {
+            membership,
+            events: Emitter::default(),
+        }
+    }
+
+    /// Publish the lifecycle events other members send to this member with the supplied
+    /// emitter.
+    pub fn with_events(mut self, events: Emitter) -> Self {
+        self.events = events;
+        self
+    }
+
+    async fn _heartbeat(
+        &self,
+        request: Request<HeartbeatRequest>,
+    ) -> Result<Response<HeartbeatResponse>, Status> {
+        let request = request.into_inner();
+
+        let holder = request.sender.as_ref().map(|sender| sender.id.clone());
+        let sender = request.sender.into_iter();
+        self.membership
+            .merge(sender.chain(request.members).map(Into::into));
+        if let Some(holder) = holder {
+            self.membership.place(&holder, request.topics);
+        }
+
+        let members = self.membership.members().into_iter().map(Into::into);
+        Ok(Response::new(HeartbeatResponse {
+            members: members.collect(),
+        }))
+    }
+
+    async fn _list_members(
+        &self,
+        _request: Request<ListMembersRequest>,
+    ) -> Result<Response<MemberStream>, Status> {
+        // Reverse the sorted members so that popping off the stream yields them in order.
+        let members = self
+            .membership
+            .members()
+            .into_iter()
+            .rev()
+            .map(Into::into)
+            .collect();
+        Ok(Response::new(MemberStream(members)))
+    }
+
+    async fn _get_topology(
+        &self,
+        request: Request<GetTopologyRequest>,
+    ) -> Result<Response<Topology>, Status> {
+        let request = request.into_inner();
+        Ok(Response::new(topology(&self.membership, &request.topics)))
+    }
+
+    async fn _watch_topology(
+        &self,
+        request: Request<WatchTopologyRequest>,
+    ) -> Result<Response<TopologyStream>, Status> {
+        let topics = request.into_inner().topics;
+        let membership = self.membership.clone();
+        let mut watch = membership.watch();
+        watch.borrow_and_update();
+
+        let current = topology(&membership, &topics);
+        let updates = futures::stream::unfold(
+            (membership, watch, topics),
+            |(membership, mut watch, topics)| async move {
+                // The membership always holds a receiver, so the channel never closes.
+                watch.changed().await.ok()?;
+                watch.borrow_and_update();
+                let update = topology(&membership, &topics);
+                Some((Ok(update), (membership, watch, topics)))
+            },
+        );
+        let stream = futures::stream::once(async move { Ok(current) }).chain(updates);
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn _publish_event(
+        &self,
+        request: Request<Event>,
+    ) -> Result<Response<PublishEventResponse>, Status> {
+        // Only other members may publish events when the cluster shares a secret.
+        if self.membership.secret().is_some() {
+            let secret = request.metadata().get(SECRET_METADATA_KEY);
+            if !matches!(secret, Some(secret) if self.membership.is_secret(secret.as_bytes())) {
+                return Err(Status::permission_denied(
+                    "only cluster members may publish events",
+                ));
+            }
+        }
+        let event = request.into_inner();
+        self.events.publish(&event.kind, &event.name, event.time_ms);
+        Ok(Response::new(PublishEventResponse {}))
+    }
+}
+
+#[tonic::async_trait]
+impl ClusterService for Handler {
+    #[inline]
+    async fn heartbeat(
+        &self,
+        request: Request<HeartbeatRequest>,
+    ) -> Result<Response<HeartbeatResponse>, Status> {
+        self._heartbeat(request).await
+    }
+
+    type ListMembersStream = MemberStream;
+
+    #[inline]
+    async fn list_members(
+        &self,
+        request: Request<ListMembersRequest>,
+    ) -> Result<Response<Self::ListMembersStream>, Status> {
+        self._list_members(request).await
+    }
+
+    #[inline]
+    async fn get_topology(
+        &self,
+        request: Request<GetTopologyRequest>,
+    ) -> Result<Response<Topology>, Status> {
+        self._get_topology(request).await
+    }
+
+    type WatchTopologyStream = TopologyStream;
+
+    #[inline]
+    async fn watch_topology(
+        &self,
+        request: Request<WatchTopologyRequest>,
+    ) -> Result<Response<Self::WatchTopologyStream>, Status> {
+        self._watch_topology(request).await
+    }
+
+    #[inline]
+    async fn publish_event(
+        &self,
+        request: Request<Event>,
+    ) -> Result<Response<PublishEventResponse>, Status> {
+        self._publish_event(request).await
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    use crate::cluster::Config;
+    use crate::grpc::cluster::MemberState;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn test_heartbeat() {
+        let cfg = Config {
+            node_id: Some(String::from("one")),
+            advertise_addr: None,
+            join: Vec::new(),
+            secret: None,
+            discovery_interval_ms: 30000,
+            heartbeat_interval_ms: 1000,
+            suspect_timeout_ms: 5000,
+            dead_timeout_ms: 30000,
+            evict_timeout_ms: 300000,
+            vnodes: 64,
+        };
+        let membership = Membership::new(&cfg, &"127.0.0.1:8081".parse().unwrap()).unwrap();
+        let handler = Handler::with_membership(membership.clone());
+
+        let sender = Member {
+            id: String::from("two"),
+            addr: String::from("127.0.0.1:8082"),
+            heartbeat: 3,
+            state: MemberState::Alive as i32,
+            incarnation: 1,
+        };
+        let req = Request::new(HeartbeatRequest {
+            sender: Some(sender),
+            members: Vec::new(),
+            topics: vec![String::from("woot")],
+        });
+        let res = aw!(handler.heartbeat(req)).unwrap().into_inner();
+        let ids = res
+            .members
+            .iter()
+            .map(|member| member.id.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(ids, vec!["one", "two"]);
+        // Topics held by the sender are owned by it, wherever the ring assigns them.
+        assert_eq!(membership.owner("woot").id, "two");
+
+        let mut stream = aw!(handler.list_members(Request::new(ListMembersRequest {})))
+            .unwrap()
+            .into_inner();
+        let waker = futures::task::noop_waker();
+        let mut cx = std::task::Context::from_waker(&waker);
+        for expected in ["one", "two"] {
+            let actual = match Pin::new(&mut stream).poll_next(&mut cx) {
+                Poll::Ready(Some(Ok(actual))) => actual,
+                _ => unimplemented!(),
+            };
+            assert_eq!(actual.id, expected);
+        }
+    }
+
+    #[test]
+    fn test_topology() {
+        let cfg = Config {
+            node_id: Some(String::from("one")),
+            advertise_addr: None,
+            join: Vec::new(),
+            secret: None,
+            discovery_interval_ms: 30000,
+            heartbeat_interval_ms: 1000,
+            suspect_timeout_ms: 5000,
+            dead_timeout_ms: 30000,
+            evict_timeout_ms: 300000,
+            vnodes: 64,
+        };
+        let membership = Membership::new(&cfg, &"127.0.0.1:8081".parse().unwrap()).unwrap();
+        let handler = Handler::with_membership(membership.clone());
+
+        let req = GetTopologyRequest {
+            topics: vec![String::from("woot")],
+        };
+        let res = aw!(handler.get_topology(Request::new(req)))
+            .unwrap()
+            .into_inner();
+        assert_eq!(res.version, 0);
+        assert_eq!(res.vnodes, 64);
+        assert_eq!(res.members.len(), 1);
+        assert_eq!(res.topics[0].owner.as_ref().unwrap().id, "one");
+
+        let req = WatchTopologyRequest {
+            topics: vec![String::from("woot")],
+        };
+        let mut stream = aw!(handler.watch_topology(Request::new(req)))
+            .unwrap()
+            .into_inner();
+        let current = aw!(stream.next()).unwrap().unwrap();
+        assert_eq!(current.version, 0);
+
+        membership.merge(vec![crate::cluster::Member::new(
+            String::from("two"),
+            String::from("127.0.0.1:8082"),
+            1,
+        )]);
+        let update = aw!(stream.next()).unwrap().unwrap();
+        assert_eq!(update.version, 1);
+        assert_eq!(update.members.len(), 2);
+    }
+
+    #[test]
+    fn test_publish_event() {
+        let cfg = Config {
+            node_id: Some(String::from("one")),
+            advertise_addr: None,
+            join: Vec::new(),
+            secret: Some(String::from("s3cret")),
+            discovery_interval_ms: 30000,
+            heartbeat_interval_ms: 1000,
+            suspect_timeout_ms: 5000,
+            dead_timeout_ms: 30000,
+            evict_timeout_ms: 300000,
+            vnodes: 64,
+        };
+        let membership = Membership::new(&cfg, &"127.0.0.1:8081".parse().unwrap()).unwrap();
+        let registry = crate::pubsub::Registry::default();
+        let events = Emitter::new(
+            &crate::events::Config {
+                system_events: true,
+            },
+            &registry,
+        );
+        let mut watcher = events.watch();
+        let handler = Handler::with_membership(membership).with_events(events);
+        let sub = registry
+            .get(crate::events::SYSTEM_EVENTS_TOPIC)
+            .unwrap()
+            .create(String::from("sub"));
+
+        let event = || Event {
+            kind: String::from("topic.created"),
+            name: String::from("orders"),
+            time_ms: 1000,
+        };
+        let err = aw!(handler.publish_event(Request::new(event()))).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::PermissionDenied);
+        assert!(sub.queue.next().is_none());
+
+        let mut req = Request::new(event());
+        req.metadata_mut()
+            .insert(SECRET_METADATA_KEY, "s3cret".parse().unwrap());
+        aw!(handler.publish_event(req)).unwrap();
+        let (_, _, msg) = sub.queue.next().unwrap();
+        assert_eq!(msg.attributes["event"], "topic.created");
+        assert_eq!(msg.attributes["name"], "orders");
+        assert_eq!(msg.published.as_ref().unwrap().seconds, 1);
+        // Forwarded events were already delivered to the watchers of the emitting member.
+        assert!(watcher.try_recv().is_err());
+    }
+}
@@ -0,0 +1,59 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+mod proto {
+    use std::time::Instant;
+
+    use crate::cluster;
+
+    tonic::include_proto!("cluster");
+
+    impl From<cluster::State> for MemberState {
+        fn from(state: cluster::State) -> Self {
+            match state {
+                cluster::State::Alive => MemberState::Alive,
+                cluster::State::Suspect => MemberState::Suspect,
+                cluster::State::Dead => MemberState::Dead,
+            }
+        }
+    }
+
+    impl From<cluster::Member> for Member {
+        fn from(member: cluster::Member) -> Self {
+            Self {
+                id: member.id,
+                addr: member.addr,
+                heartbeat: member.heartbeat,
+                state: MemberState::from(member.state) as i32,
+                incarnation: member.incarnation,
+            }
+        }
+    }
+
+    impl From<Member> for cluster::Member {
+        /// Convert a remote view of a member into a local one. The remote state is discarded,
+        /// as the local node determines the state of every member itself.
+        fn from(member: Member) -> Self {
+            Self {
+                id: member.id,
+                addr: member.addr,
+                heartbeat: member.heartbeat,
+                incarnation: member.incarnation,
+                state: cluster::State::Alive,
+                last_seen: Instant::now(),
+            }
+        }
+    }
+}
+mod handler;
+
+pub(crate) const FILE_DESCRIPTOR_SET: &[u8] =
+    tonic::include_file_descriptor_set!("cluster_descriptor");
+
+pub use handler::Handler;
+pub use proto::cluster_service_client::ClusterServiceClient;
+pub use proto::cluster_service_server::ClusterServiceServer;
+pub use proto::{
+    Event, GetTopologyRequest, HeartbeatRequest, HeartbeatResponse, ListMembersRequest, Member,
+    MemberState, PublishEventResponse, TopicOwner, Topology, WatchTopologyRequest,
+};
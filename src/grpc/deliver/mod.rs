@@ -0,0 +1,25 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+mod proto {
+    use crate::grpc::pubsub::Message;
+
+    tonic::include_proto!("deliver");
+
+    impl DeliverRequest {
+        /// Create a delivery of the supplied message on behalf of the supplied subscription.
+        pub fn from_message(subscription: String, msg: &Message) -> Self {
+            Self {
+                topic: msg.topic.clone(),
+                subscription,
+                attributes: msg.attributes.clone(),
+                published: msg.published.clone(),
+                data: msg.data.clone(),
+            }
+        }
+    }
+}
+
+pub use proto::deliver_service_client::DeliverServiceClient;
+pub use proto::deliver_service_server::{DeliverService, DeliverServiceServer};
+pub use proto::{DeliverRequest, DeliverResponse};
@@ -3,20 +3,42 @@
 
 use tonic::{Response, Status};
 
+use crate::cluster::Member;
+use crate::Error;
+
 /// Create and return a topic not found error.
 pub fn topic_not_found<T>(topic: &str) -> Result<Response<T>, Status> {
-    return Err(Status::not_found(format!(
-        "the supplied topic '{}' does not exist",
-        topic
-    )));
+    Err(Error::TopicNotFound {
+        topic: topic.to_string(),
+    }
+    .into())
 }
 
 /// Create and return a subscription not found error.
 pub fn sub_not_found<T>(subscription: &str, topic: &str) -> Result<Response<T>, Status> {
-    return Err(Status::not_found(format!(
-        "the supplied subscription '{}' is not assoicated with the given topic '{}'",
-        subscription, topic
-    )));
+    Err(Error::SubscriptionNotFound {
+        subscription: subscription.to_string(),
+        topic: topic.to_string(),
+    }
+    .into())
+}
+
+/// Create and return an invalid argument error.
+pub fn invalid_argument<T>(reason: &str) -> Result<Response<T>, Status> {
+    Err(Error::InvalidArgument {
+        reason: reason.to_string(),
+    }
+    .into())
+}
+
+/// Create and return an error redirecting the caller to the member owning the topic.
+pub fn not_owner<T>(topic: &str, owner: Member) -> Result<Response<T>, Status> {
+    Err(Error::NotOwner {
+        topic: topic.to_string(),
+        owner: owner.id,
+        addr: owner.addr,
+    }
+    .into())
 }
 
 #[cfg(test)]
@@ -25,6 +47,7 @@ mod tests {
     use tonic::Code;
 
     use super::*;
+    use crate::error;
 
     #[test]
     fn test_topic_not_found() {
@@ -33,6 +56,10 @@ mod tests {
         let err = err.unwrap_err();
         assert_eq!(err.message(), "the supplied topic 'woot' does not exist");
         assert_eq!(err.code(), Code::NotFound);
+        assert_eq!(
+            error::Code::from_status(&err),
+            Some(error::Code::TopicNotFound)
+        );
     }
 
     #[test]
@@ -45,5 +72,16 @@ mod tests {
             "the supplied subscription 'woot' is not assoicated with the given topic 'testing'"
         );
         assert_eq!(err.code(), Code::NotFound);
+        assert_eq!(
+            error::Code::from_status(&err),
+            Some(error::Code::SubscriptionNotFound)
+        );
+    }
+
+    #[test]
+    fn test_invalid_argument() {
+        let err = invalid_argument::<usize>("nope").unwrap_err();
+        assert_eq!(err.message(), "nope");
+        assert_eq!(err.code(), Code::InvalidArgument);
     }
 }
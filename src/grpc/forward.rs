@@ -0,0 +1,228 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+use tonic::transport::Channel;
+use tonic::{Request, Response, Status};
+
+use crate::auth::{API_KEY_HEADER, AUTHORIZATION_HEADER};
+use crate::cluster::{Member, Membership};
+use crate::error::OWNER_METADATA_KEY;
+use crate::grpc::interceptor::TraceExt;
+
+/// The gRPC metadata key marking a request as forwarded by another cluster member, holding the
+/// ID of the forwarding member. Forwarded requests are never forwarded again, so that members
+/// with diverging views of the ring never forward a request back and forth.
+pub const FORWARDED_METADATA_KEY: &str = "x-rift-forwarded";
+
+/// The gRPC metadata key that the secret shared by the members of the cluster is attached to
+/// forwarded requests under, authenticating the [FORWARDED_METADATA_KEY] they carry.
+pub const SECRET_METADATA_KEY: &str = "x-rift-cluster-secret";
+
+/// The metadata copied from the original request onto the forwarded request.
+const COPIED_METADATA: [&str; 3] = ["x-request-id", AUTHORIZATION_HEADER, API_KEY_HEADER];
+
+/// A Forward describes a request which must be forwarded to the cluster member owning its
+/// topic, because the topic is owned by another member and the request wasn't already
+/// forwarded.
+#[derive(Debug, Clone)]
+pub struct Forward {
+    /// The member owning the topic.
+    pub owner: Member,
+    /// The channel to the member owning the topic.
+    pub channel: Channel,
+    local: String,
+    secret: Option<String>,
+}
+
+/// Return whether the supplied request was forwarded by another member of the supplied
+/// membership. Clients can set any metadata, so the request must carry the secret shared by
+/// the members of the cluster, and no request is trusted without a membership or secret.
+pub fn is_forwarded<T>(membership: Option<&Membership>, request: &Request<T>) -> bool {
+    let metadata = request.metadata();
+    match (membership, metadata.get(SECRET_METADATA_KEY)) {
+        (Some(membership), Some(secret)) => {
+            metadata.contains_key(FORWARDED_METADATA_KEY) && membership.is_secret(secret.as_bytes())
+        }
+        _ => false,
+    }
+}
+
+impl Forward {
+    /// Return how to forward the supplied request for the supplied topic, or [None] if it must
+    /// be handled locally. Requests forwarded by another member with the cluster's secret are
+    /// always handled locally, while requests merely claiming to be forwarded are refused with
+    /// the owner unless the topic is local, so that clients can't force a member to serve a
+    /// topic it doesn't own.
+    pub fn new<T>(
+        membership: Option<&Membership>,
+        request: &Request<T>,
+        topic: &str,
+    ) -> Result<Option<Forward>, Status> {
+        let membership = match membership {
+            Some(membership) => membership,
+            None => return Ok(None),
+        };
+        if membership.is_local(topic) || is_forwarded(Some(membership), request) {
+            return Ok(None);
+        }
+
+        let owner = membership.owner(topic);
+        if request.metadata().contains_key(FORWARDED_METADATA_KEY) {
+            return Err(crate::Error::NotOwner {
+                topic: topic.to_string(),
+                owner: owner.id,
+                addr: owner.addr,
+            }
+            .into());
+        }
+        let channel = membership.channel(&owner.addr).map_err(|err| {
+            Status::unavailable(format!(
+                "invalid address '{}' for cluster member '{}': {}",
+                owner.addr, owner.id, err
+            ))
+        })?;
+        Ok(Some(Forward {
+            owner,
+            channel,
+            local: membership.local().id,
+            secret: membership.secret().map(String::from),
+        }))
+    }
+
+    /// Build the request forwarded to the owner from the supplied original request, continuing
+    /// its request ID and trace.
+    pub fn request<T>(&self, request: Request<T>) -> Request<T> {
+        let metadata = request.metadata().clone();
+        let trace = request
+            .extensions()
+            .get::<TraceExt>()
+            .map(|ext| ext.context.clone());
+
+        let mut forwarded = Request::new(request.into_inner());
+        for key in COPIED_METADATA {
+            if let Some(value) = metadata.get(key) {
+                forwarded.metadata_mut().insert(key, value.clone());
+            }
+        }
+        if let Ok(local) = self.local.parse() {
+            forwarded
+                .metadata_mut()
+                .insert(FORWARDED_METADATA_KEY, local);
+        }
+        if let Some(Ok(secret)) = self.secret.as_deref().map(str::parse) {
+            forwarded.metadata_mut().insert(SECRET_METADATA_KEY, secret);
+        }
+        if let Some(context) = trace {
+            TraceExt { context }.inject(forwarded.metadata_mut());
+        }
+        forwarded
+    }
+
+    /// Attach a hint to the supplied response redirecting the caller to the owner.
+    pub fn response<T>(&self, mut response: Response<T>) -> Response<T> {
+        if let Ok(addr) = self.owner.addr.parse() {
+            response.metadata_mut().insert(OWNER_METADATA_KEY, addr);
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    use crate::cluster::Config;
+
+    #[test]
+    fn test_forward() {
+        // Lazily connected channels must be created within a runtime.
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+        let cfg = Config {
+            node_id: Some(String::from("one")),
+            advertise_addr: None,
+            join: Vec::new(),
+            secret: None,
+            discovery_interval_ms: 30000,
+            heartbeat_interval_ms: 1000,
+            suspect_timeout_ms: 5000,
+            dead_timeout_ms: 30000,
+            evict_timeout_ms: 300000,
+            vnodes: 64,
+        };
+        let membership = Membership::new(&cfg, &"127.0.0.1:8081".parse().unwrap()).unwrap();
+        membership.merge(vec![Member::new(
+            String::from("two"),
+            String::from("127.0.0.1:8082"),
+            1,
+        )]);
+        let topic = (0..100)
+            .map(|topic| format!("topic-{}", topic))
+            .find(|topic| !membership.is_local(topic))
+            .unwrap();
+
+        let mut req = Request::new(());
+        req.metadata_mut()
+            .insert("x-request-id", "abc".parse().unwrap());
+        assert!(Forward::new(None, &req, &topic).unwrap().is_none());
+        let forward = Forward::new(Some(&membership), &req, &topic)
+            .unwrap()
+            .unwrap();
+        assert_eq!(forward.owner.id, "two");
+
+        let forwarded = forward.request(req);
+        assert_eq!(forwarded.metadata().get("x-request-id").unwrap(), "abc");
+        assert_eq!(
+            forwarded.metadata().get(FORWARDED_METADATA_KEY).unwrap(),
+            "one"
+        );
+        // Without a secret no forwarded request is trusted, nor forwarded again.
+        assert!(!is_forwarded(Some(&membership), &forwarded));
+        let status = Forward::new(Some(&membership), &forwarded, &topic).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+        let local = (0..100)
+            .map(|topic| format!("topic-{}", topic))
+            .find(|topic| membership.is_local(topic))
+            .unwrap();
+        assert!(Forward::new(Some(&membership), &forwarded, &local)
+            .unwrap()
+            .is_none());
+
+        // Requests forwarded with the cluster's secret are handled locally, never forwarded
+        // again, while those with any other secret are refused.
+        let cfg = Config {
+            secret: Some(String::from("secret")),
+            ..cfg
+        };
+        let membership = Membership::new(&cfg, &"127.0.0.1:8081".parse().unwrap()).unwrap();
+        membership.merge(vec![Member::new(
+            String::from("two"),
+            String::from("127.0.0.1:8082"),
+            1,
+        )]);
+        let forward = Forward::new(Some(&membership), &Request::new(()), &topic)
+            .unwrap()
+            .unwrap();
+        let mut forwarded = forward.request(Request::new(()));
+        assert_eq!(
+            forwarded.metadata().get(SECRET_METADATA_KEY).unwrap(),
+            "secret"
+        );
+        assert!(is_forwarded(Some(&membership), &forwarded));
+        assert!(Forward::new(Some(&membership), &forwarded, &topic)
+            .unwrap()
+            .is_none());
+        forwarded
+            .metadata_mut()
+            .insert(SECRET_METADATA_KEY, "nope".parse().unwrap());
+        assert!(!is_forwarded(Some(&membership), &forwarded));
+        assert!(Forward::new(Some(&membership), &forwarded, &topic).is_err());
+
+        let res = forward.response(Response::new(()));
+        assert_eq!(
+            res.metadata().get(OWNER_METADATA_KEY).unwrap(),
+            "127.0.0.1:8082"
+        );
+    }
+}
@@ -1,13 +1,15 @@
 // (c) Copyright 2021-2022 Christian Saide
 // SPDX-License-Identifier: GPL-3.0-only
 
-use std::time::Instant;
-
-use prometheus::{Histogram, IntCounter};
+use opentelemetry::global;
+use opentelemetry::trace::TraceContextExt;
+use prometheus::IntCounter;
+use tonic::metadata::MetadataMap;
 use tonic::service::Interceptor;
 use tonic::{Request, Status};
 
 use crate::metric::Manager;
+use crate::trace::{MetadataExtractor, MetadataInjector};
 
 /// The LoggerExt handles injecting a request specific logger into the gRPC execution
 /// chain.
@@ -16,20 +18,35 @@ pub struct LoggerExt {
     pub logger: slog::Logger,
 }
 
-/// The ResponseTimeExt handles injecting a
-pub struct ResponseTimeExt {
-    /// The response time histogram to use for observing measurements for this gRPC
-    /// request.
-    pub histogram: Histogram,
-    /// The start instant for this request to measure execution time against.
-    pub start: Instant,
+/// The RequestIdExt handles injecting the ID of a request into the gRPC execution chain.
+pub struct RequestIdExt {
+    /// The ID of this request, either supplied by the caller via `x-request-id` or generated.
+    pub id: String,
+}
+
+/// The IdentityExt carries the authenticated identity of the caller through the gRPC
+/// execution chain. It is only present on requests which have been authenticated.
+#[derive(Debug, Clone)]
+pub struct IdentityExt {
+    /// The authenticated identity of the caller.
+    pub identity: String,
+}
+
+/// The TraceExt handles injecting the trace context of a request into the gRPC execution
+/// chain.
+pub struct TraceExt {
+    /// The trace context of this request, which is either the current server span or the
+    /// remote span context propagated by the caller.
+    pub context: opentelemetry::Context,
 }
 
-impl ResponseTimeExt {
-    /// Observe the total response time generally used within a defer statement.
-    pub fn observe(&self) {
-        self.histogram
-            .observe(self.start.elapsed().as_millis() as f64)
+impl TraceExt {
+    /// Inject this trace context into the metadata of an outbound gRPC call, so that the
+    /// callee continues this trace.
+    pub fn inject(&self, metadata: &mut MetadataMap) {
+        global::get_text_map_propagator(|propagator| {
+            propagator.inject_context(&self.context, &mut MetadataInjector(metadata))
+        })
     }
 }
 
@@ -39,7 +56,6 @@ pub struct RiftInterceptor {
     logger: slog::Logger,
 
     total_requests: IntCounter,
-    response_time: Histogram,
 }
 
 impl RiftInterceptor {
@@ -54,13 +70,6 @@ impl RiftInterceptor {
                     None,
                 )
                 .unwrap(),
-            response_time: mm
-                .register_histogram(
-                    "response_time",
-                    "The response time over all received gRPC requests seen by this server.",
-                    None,
-                )
-                .unwrap(),
         }
     }
 }
@@ -75,13 +84,29 @@ impl Interceptor for RiftInterceptor {
             uuid::Uuid::new_v4().to_string()
         };
 
-        req.extensions_mut().insert(LoggerExt {
-            logger: self.logger.new(o!("reqID" => req_id)),
-        });
-        req.extensions_mut().insert(ResponseTimeExt {
-            histogram: self.response_time.clone(),
-            start: Instant::now(),
-        });
+        // Prefer the server span started by the trace layer, falling back to the propagated
+        // remote context when the interceptor is used on its own.
+        let current = opentelemetry::Context::current();
+        let context = if current.span().span_context().is_valid() {
+            current
+        } else {
+            global::get_text_map_propagator(|propagator| {
+                propagator.extract(&MetadataExtractor(req.metadata()))
+            })
+        };
+
+        let mut logger = self.logger.new(o!("reqID" => req_id.clone()));
+        let span_context = context.span().span_context().clone();
+        if span_context.is_valid() {
+            logger = logger.new(o!(
+                "traceID" => span_context.trace_id().to_string(),
+                "spanID" => span_context.span_id().to_string(),
+            ));
+        }
+
+        req.extensions_mut().insert(LoggerExt { logger });
+        req.extensions_mut().insert(RequestIdExt { id: req_id });
+        req.extensions_mut().insert(TraceExt { context });
 
         Ok(req)
     }
@@ -109,9 +134,44 @@ mod tests {
         let res = res.unwrap();
         let ext = res.extensions().get::<LoggerExt>();
         assert!(ext.is_some());
-        let ext = res.extensions().get::<ResponseTimeExt>();
+        let ext = res.extensions().get::<RequestIdExt>();
+        assert!(ext.is_some());
+        assert_eq!(interceptor.total_requests.get(), 1);
+    }
+
+    #[test]
+    fn test_interceptor_trace_context() {
+        global::set_text_map_propagator(
+            opentelemetry::sdk::propagation::TraceContextPropagator::new(),
+        );
+
+        let logger = slog::Logger::root(slog::Discard {}, o!());
+        let mm = Manager::new(
+            String::from("test"),
+            String::from("trace"),
+            String::from("test"),
+        );
+        let mut interceptor = RiftInterceptor::new(&logger, mm);
+
+        let traceparent = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+        let mut req = Request::new(());
+        req.metadata_mut()
+            .insert("traceparent", traceparent.parse().unwrap());
+        let res = interceptor.call(req).unwrap();
+
+        let ext = res.extensions().get::<TraceExt>();
         assert!(ext.is_some());
         let ext = ext.unwrap();
-        ext.observe();
+        let span = ext.context.span();
+        let span_context = span.span_context();
+        assert!(span_context.is_remote());
+        assert_eq!(
+            span_context.trace_id().to_string(),
+            "0af7651916cd43dd8448eb211c80319c"
+        );
+
+        let mut metadata = MetadataMap::new();
+        ext.inject(&mut metadata);
+        assert_eq!(metadata.get("traceparent").unwrap(), traceparent);
     }
 }
@@ -0,0 +1,136 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::task::{Context, Poll};
+
+use futures::future::BoxFuture;
+use tonic::body::BoxBody;
+use tonic::codegen::http::{Request, Response};
+use tonic::Status;
+use tower::{Layer, Service};
+
+use crate::auth::{Action, Auth};
+use crate::grpc::interceptor::IdentityExt;
+
+/// The AuthLayer wraps the gRPC server authenticating the caller of every request and
+/// authorizing it to perform the action of the requested method, rejecting it with
+/// `UNAUTHENTICATED` or `PERMISSION_DENIED` before it reaches its handler. The identity of
+/// authenticated callers is attached to the request as an [IdentityExt]. Unlike the
+/// interceptor, this layer sees the requested method, which authorization depends on.
+#[derive(Debug, Clone)]
+pub struct AuthLayer {
+    auth: Auth,
+}
+
+impl AuthLayer {
+    /// Create a new auth layer making its decisions with the supplied auth.
+    pub fn new(auth: Auth) -> Self {
+        Self { auth }
+    }
+}
+
+impl<S> Layer<S> for AuthLayer {
+    type Service = AuthService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuthService {
+            inner,
+            auth: self.auth.clone(),
+        }
+    }
+}
+
+/// The service produced by an [AuthLayer].
+#[derive(Debug, Clone)]
+pub struct AuthService<S> {
+    inner: S,
+    auth: Auth,
+}
+
+impl<S, ReqBody> Service<Request<ReqBody>> for AuthService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<BoxBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
+        let action = Action::of_grpc(req.uri().path());
+        let headers = req.headers();
+        let checked = self.auth.check(
+            |name| headers.get(name).and_then(|value| value.to_str().ok()),
+            action,
+        );
+        match checked {
+            Ok(Some(identity)) => {
+                req.extensions_mut().insert(IdentityExt { identity });
+            }
+            Ok(None) => {}
+            Err(err) => {
+                let res = Status::from(crate::Error::from(err)).to_http();
+                return Box::pin(async move { Ok(res) });
+            }
+        }
+        Box::pin(self.inner.call(req))
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    use std::convert::Infallible;
+
+    use crate::auth::Policy;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn test_auth_service() {
+        let policy = Policy::parse(
+            br#"{"identities": [{"name": "alice", "tokens": ["t"], "actions": ["publish"]}]}"#,
+        )
+        .unwrap();
+        let inner = tower::service_fn(|req: Request<()>| async move {
+            let identity = req.extensions().get::<IdentityExt>().unwrap();
+            assert_eq!(identity.identity, "alice");
+            Ok::<_, Infallible>(Response::new(BoxBody::default()))
+        });
+        let mut svc = AuthLayer::new(Auth::with_policy(policy)).layer(inner);
+
+        let req = |path: &str, token: Option<&str>| {
+            let mut req = Request::builder().uri(path);
+            if let Some(token) = token {
+                req = req.header("authorization", format!("Bearer {}", token));
+            }
+            req.body(()).unwrap()
+        };
+        let status = |res: Response<BoxBody>| {
+            res.headers()
+                .get("grpc-status")
+                .map(|code| code.to_str().unwrap().to_owned())
+        };
+
+        let res = aw!(svc.call(req("/pubsub.PubSubService/Publish", Some("t")))).unwrap();
+        assert_eq!(status(res), None);
+
+        let res = aw!(svc.call(req("/pubsub.PubSubService/Publish", Some("nope")))).unwrap();
+        let unauthenticated = (tonic::Code::Unauthenticated as i32).to_string();
+        assert_eq!(status(res), Some(unauthenticated));
+
+        let res = aw!(svc.call(req("/topic.TopicService/Create", Some("t")))).unwrap();
+        let denied = (tonic::Code::PermissionDenied as i32).to_string();
+        assert_eq!(status(res), Some(denied));
+    }
+}
@@ -0,0 +1,209 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use futures::future::BoxFuture;
+use prometheus::{Histogram, HistogramVec, IntCounterVec};
+use tonic::codegen::http::{Request, Response};
+use tonic::Code;
+use tower::{Layer, Service};
+
+use crate::metric::{self, Manager, Opt};
+
+/// The MetricsLayer wraps the gRPC server recording the count and latency of every completed
+/// request, labeled by the fully qualified gRPC method and its final status code. Requests
+/// dropped before completing, generally due to the client cancelling them, are recorded with
+/// the `Cancelled` code. Like the [super::TraceLayer] the status is read from the response
+/// headers, so errors raised mid-stream by streaming responses are recorded as `Ok`.
+///
+/// The overall response time is observed separately once the response future completes, so
+/// it covers the whole handler rather than only the time taken to decode the request.
+#[derive(Debug, Clone)]
+pub struct MetricsLayer {
+    completed: IntCounterVec,
+    latency: HistogramVec,
+    response_time: Histogram,
+}
+
+impl MetricsLayer {
+    /// Register the request outcome metrics using the supplied manager, observing latencies
+    /// into the supplied histogram buckets.
+    pub fn new(mm: &Manager, buckets: &[f64]) -> metric::Result<Self> {
+        let labels = || {
+            Some(vec![Opt::Labels(vec![
+                String::from("method"),
+                String::from("code"),
+            ])])
+        };
+        Ok(Self {
+            completed: mm.register_int_counter_vec(
+                "completed_requests",
+                "The total count of completed gRPC requests by method and status code.",
+                labels(),
+            )?,
+            latency: mm.register_histogram_vec(
+                "request_duration_seconds",
+                "The time in seconds taken to complete gRPC requests by method and status code.",
+                labels().map(|mut opts| {
+                    opts.push(Opt::Buckets(buckets.to_vec()));
+                    opts
+                }),
+            )?,
+            response_time: mm.register_histogram(
+                "response_time",
+                "The response time in seconds over all received gRPC requests seen by this server.",
+                Some(vec![Opt::Buckets(buckets.to_vec())]),
+            )?,
+        })
+    }
+}
+
+impl<S> Layer<S> for MetricsLayer {
+    type Service = MetricsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        MetricsService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// The service produced by a [MetricsLayer].
+#[derive(Debug, Clone)]
+pub struct MetricsService<S> {
+    inner: S,
+    layer: MetricsLayer,
+}
+
+/// Records the outcome of a single request, falling back to `Cancelled` if it is dropped
+/// before an outcome is recorded.
+struct Outcome {
+    layer: MetricsLayer,
+    method: String,
+    start: Instant,
+    recorded: bool,
+}
+
+impl Outcome {
+    fn record(&mut self, code: Code) {
+        let code = format!("{:?}", code);
+        let labels = &[self.method.as_str(), code.as_str()];
+        self.layer.completed.with_label_values(labels).inc();
+        metric::exemplar::observe(
+            &self.layer.latency.with_label_values(labels),
+            self.start.elapsed().as_secs_f64(),
+        );
+        self.recorded = true;
+    }
+}
+
+impl Drop for Outcome {
+    fn drop(&mut self) {
+        if !self.recorded {
+            self.record(Code::Cancelled)
+        }
+    }
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for MetricsService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        let mut outcome = Outcome {
+            layer: self.layer.clone(),
+            method: req.uri().path().to_string(),
+            start: Instant::now(),
+            recorded: false,
+        };
+        let fut = Manager::time(&self.layer.response_time, self.inner.call(req));
+
+        Box::pin(async move {
+            let res = fut.await;
+            let code = match &res {
+                Ok(res) => res
+                    .headers()
+                    .get("grpc-status")
+                    .and_then(|code| code.to_str().ok())
+                    .and_then(|code| code.parse::<i32>().ok())
+                    .map(Code::from_i32)
+                    .unwrap_or(Code::Ok),
+                Err(_) => Code::Unavailable,
+            };
+            outcome.record(code);
+            res
+        })
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    use std::convert::Infallible;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn test_metrics_service() {
+        let mm = Manager::new(
+            String::from("testing"),
+            String::from("grpc_layer_metrics"),
+            String::from("0.1.0"),
+        );
+        let layer = MetricsLayer::new(&mm, metric::RESPONSE_TIME_BUCKETS).unwrap();
+        assert!(MetricsLayer::new(&mm, metric::RESPONSE_TIME_BUCKETS).is_err());
+
+        let inner = tower::service_fn(|req: Request<()>| async move {
+            let status = if req.uri().path() == "/fail" {
+                "5"
+            } else {
+                "0"
+            };
+            Ok::<_, Infallible>(
+                Response::builder()
+                    .header("grpc-status", status)
+                    .body(())
+                    .unwrap(),
+            )
+        });
+        let mut svc = layer.layer(inner);
+
+        let req = Request::builder().uri("/ok").body(()).unwrap();
+        aw!(svc.call(req)).unwrap();
+        let req = Request::builder().uri("/fail").body(()).unwrap();
+        aw!(svc.call(req)).unwrap();
+        let req = Request::builder().uri("/cancel").body(()).unwrap();
+        drop(svc.call(req));
+
+        let completed = |method, code| layer.completed.with_label_values(&[method, code]).get();
+        assert_eq!(completed("/ok", "Ok"), 1);
+        assert_eq!(completed("/fail", "NotFound"), 1);
+        assert_eq!(completed("/cancel", "Cancelled"), 1);
+        assert_eq!(
+            layer
+                .latency
+                .with_label_values(&["/fail", "NotFound"])
+                .get_sample_count(),
+            1
+        );
+        assert_eq!(layer.response_time.get_sample_count(), 2);
+    }
+}
@@ -0,0 +1,12 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+mod auth;
+mod metrics;
+mod panic;
+mod trace;
+
+pub use auth::{AuthLayer, AuthService};
+pub use metrics::{MetricsLayer, MetricsService};
+pub use panic::{PanicLayer, PanicService};
+pub use trace::{TraceLayer, TraceService};
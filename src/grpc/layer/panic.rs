@@ -0,0 +1,212 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Once;
+use std::task::{Context, Poll};
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use prometheus::IntCounterVec;
+use tonic::body::BoxBody;
+use tonic::codegen::http::{HeaderValue, Request, Response};
+use tonic::Status;
+use tower::{Layer, Service};
+
+use crate::metric::{self, Manager, Opt};
+
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+static INSTALL_HOOK: Once = Once::new();
+
+thread_local! {
+    /// The backtrace of the most recent panic on this thread, captured by the panic hook
+    /// installed by [PanicLayer::new].
+    static BACKTRACE: RefCell<Option<backtrace::Backtrace>> = const { RefCell::new(None) };
+}
+
+/// Install a panic hook capturing the backtrace of every panic, before delegating to the
+/// previously installed hook.
+fn install_hook() {
+    INSTALL_HOOK.call_once(|| {
+        let prev = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            BACKTRACE.with(|bt| *bt.borrow_mut() = Some(backtrace::Backtrace::new()));
+            prev(info)
+        }));
+    })
+}
+
+/// Extract the message of a panic from its payload.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        String::from("unknown panic")
+    }
+}
+
+/// The PanicLayer wraps the gRPC server catching any panics raised while handling a request,
+/// converting them into an internal status rather than silently killing the connection task.
+/// Each panic is logged alongside its request ID and backtrace, and counted by method. This
+/// layer also ensures every request carries an `x-request-id` header, so that the ID logged
+/// here matches the one used by the interceptor. Panics raised while streaming response
+/// messages, after the handler has returned, are not caught.
+#[derive(Debug, Clone)]
+pub struct PanicLayer {
+    logger: slog::Logger,
+    panics: IntCounterVec,
+}
+
+impl PanicLayer {
+    /// Create a new panic layer, registering its metrics using the supplied manager.
+    pub fn new(logger: &slog::Logger, mm: &Manager) -> metric::Result<Self> {
+        install_hook();
+        Ok(Self {
+            logger: logger.clone(),
+            panics: mm.register_int_counter_vec(
+                "panics_total",
+                "The total count of panics caught while handling gRPC requests by method.",
+                Some(vec![Opt::Labels(vec![String::from("method")])]),
+            )?,
+        })
+    }
+
+    fn recover(
+        &self,
+        method: &str,
+        req_id: &str,
+        payload: Box<dyn Any + Send>,
+    ) -> Response<BoxBody> {
+        let backtrace = BACKTRACE
+            .with(|bt| bt.borrow_mut().take())
+            .map(|bt| format!("{:?}", bt))
+            .unwrap_or_default();
+        crit!(&self.logger, "Caught panic while handling request.";
+            "reqID" => req_id,
+            "method" => method,
+            "panic" => panic_message(payload.as_ref()),
+            "backtrace" => backtrace,
+        );
+        self.panics.with_label_values(&[method]).inc();
+        Status::internal("an internal error occurred while handling the request").to_http()
+    }
+}
+
+impl<S> Layer<S> for PanicLayer {
+    type Service = PanicService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        PanicService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// The service produced by a [PanicLayer].
+#[derive(Debug, Clone)]
+pub struct PanicService<S> {
+    inner: S,
+    layer: PanicLayer,
+}
+
+impl<S, ReqBody> Service<Request<ReqBody>> for PanicService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<BoxBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
+        let req_id = match req
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|req_id| req_id.to_str().ok())
+        {
+            Some(req_id) => req_id.to_string(),
+            None => {
+                let req_id = uuid::Uuid::new_v4().to_string();
+                req.headers_mut()
+                    .insert(REQUEST_ID_HEADER, HeaderValue::from_str(&req_id).unwrap());
+                req_id
+            }
+        };
+        let method = req.uri().path().to_string();
+        let layer = self.layer.clone();
+
+        let fut = match panic::catch_unwind(AssertUnwindSafe(|| self.inner.call(req))) {
+            Ok(fut) => fut,
+            Err(payload) => {
+                let res = layer.recover(&method, &req_id, payload);
+                return Box::pin(async move { Ok(res) });
+            }
+        };
+        Box::pin(async move {
+            match AssertUnwindSafe(fut).catch_unwind().await {
+                Ok(res) => res,
+                Err(payload) => Ok(layer.recover(&method, &req_id, payload)),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    use std::convert::Infallible;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn test_panic_service() {
+        let logger = slog::Logger::root(slog::Discard, o!());
+        let mm = Manager::new(
+            String::from("testing"),
+            String::from("grpc_layer_panic"),
+            String::from("0.1.0"),
+        );
+        let layer = PanicLayer::new(&logger, &mm).unwrap();
+
+        let inner = tower::service_fn(|req: Request<()>| async move {
+            assert!(req.headers().contains_key(REQUEST_ID_HEADER));
+            if req.uri().path() == "/panic" {
+                panic!("woot");
+            }
+            Ok::<_, Infallible>(Response::new(BoxBody::default()))
+        });
+        let mut svc = layer.layer(inner);
+
+        let req = Request::builder().uri("/ok").body(()).unwrap();
+        let res = aw!(svc.call(req)).unwrap();
+        assert!(res.headers().get("grpc-status").is_none());
+
+        let req = Request::builder().uri("/panic").body(()).unwrap();
+        let res = aw!(svc.call(req)).unwrap();
+        assert_eq!(res.headers().get("grpc-status").unwrap(), "13");
+        assert_eq!(layer.panics.with_label_values(&["/panic"]).get(), 1);
+    }
+
+    #[test]
+    fn test_panic_message() {
+        assert_eq!(panic_message(&"woot"), "woot");
+        assert_eq!(panic_message(&String::from("woot")), "woot");
+        assert_eq!(panic_message(&1), "unknown panic");
+    }
+}
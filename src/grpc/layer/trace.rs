@@ -0,0 +1,165 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::task::{Context, Poll};
+
+use futures::future::BoxFuture;
+use opentelemetry::propagation::Extractor;
+use opentelemetry::trace::{FutureExt, SpanKind, StatusCode, TraceContextExt, Tracer};
+use opentelemetry::{global, KeyValue};
+use tonic::codegen::http::{HeaderMap, Request, Response};
+use tower::{Layer, Service};
+
+use crate::trace;
+
+/// Extracts propagated trace context from a set of HTTP headers.
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl<'a> Extractor for HeaderExtractor<'a> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+/// The TraceLayer wraps the gRPC server creating a server span for every request, named after
+/// the fully qualified gRPC method. The span is current while the handler executes so that any
+/// queue operations are recorded as its children. Note that for streaming responses the span
+/// ends once the response headers are returned.
+#[derive(Debug, Clone, Default)]
+pub struct TraceLayer;
+
+impl<S> Layer<S> for TraceLayer {
+    type Service = TraceService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TraceService { inner }
+    }
+}
+
+/// The service produced by a [TraceLayer].
+#[derive(Debug, Clone)]
+pub struct TraceService<S> {
+    inner: S,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for TraceService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        let parent = global::get_text_map_propagator(|propagator| {
+            propagator.extract(&HeaderExtractor(req.headers()))
+        });
+
+        let method = req.uri().path().to_string();
+        let tracer = trace::tracer();
+        let span = tracer
+            .span_builder(method.clone())
+            .with_kind(SpanKind::Server)
+            .with_attributes(vec![
+                KeyValue::new("rpc.system", "grpc"),
+                KeyValue::new("rpc.method", method),
+            ])
+            .start_with_context(&tracer, &parent);
+        let cx = parent.with_span(span);
+
+        // Attach the context while calling the inner service, so that any synchronous
+        // interceptors observe this span as current.
+        let fut = {
+            let _guard = cx.clone().attach();
+            self.inner.call(req)
+        };
+
+        Box::pin(async move {
+            let res = fut.with_context(cx.clone()).await;
+
+            let span = cx.span();
+            match &res {
+                Ok(res) => {
+                    // The grpc-status header is only present in the initial headers for
+                    // trailers-only responses, which is the case for all handler errors.
+                    let code = res
+                        .headers()
+                        .get("grpc-status")
+                        .and_then(|code| code.to_str().ok())
+                        .and_then(|code| code.parse::<i64>().ok())
+                        .unwrap_or(0);
+                    span.set_attribute(KeyValue::new("rpc.grpc.status_code", code));
+                    if code != 0 {
+                        span.set_status(StatusCode::Error, String::new());
+                    }
+                }
+                Err(_) => span.set_status(StatusCode::Error, String::from("transport error")),
+            }
+            span.end();
+            res
+        })
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    use std::convert::Infallible;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn test_trace_service() {
+        let inner = tower::service_fn(|req: Request<()>| async move {
+            let status = if req.uri().path() == "/fail" {
+                "5"
+            } else {
+                "0"
+            };
+            Ok::<_, Infallible>(
+                Response::builder()
+                    .header("grpc-status", status)
+                    .body(())
+                    .unwrap(),
+            )
+        });
+        let mut svc = TraceLayer.layer(inner);
+
+        let req = Request::builder()
+            .uri("/pubsub.PubSubService/Publish")
+            .header("traceparent", "nope")
+            .body(())
+            .unwrap();
+        let res = aw!(svc.call(req));
+        assert!(res.is_ok());
+
+        let req = Request::builder().uri("/fail").body(()).unwrap();
+        let res = aw!(svc.call(req)).unwrap();
+        assert_eq!(res.headers().get("grpc-status").unwrap(), "5");
+    }
+
+    #[test]
+    fn test_header_extractor() {
+        let mut headers = HeaderMap::new();
+        headers.insert("traceparent", "woot".parse().unwrap());
+        let extractor = HeaderExtractor(&headers);
+        assert_eq!(extractor.get("traceparent"), Some("woot"));
+        assert_eq!(extractor.get("nope"), None);
+        assert_eq!(extractor.keys(), vec!["traceparent"]);
+    }
+}
@@ -0,0 +1,31 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// extern usings
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, StructOpt)]
+/// Rift gRPC connection and stream limit configuration.
+pub struct Config {
+    #[structopt(
+        long = "max-connections-per-ip",
+        env = "RIFT_MAX_CONNECTIONS_PER_IP",
+        help = "The maximum count of concurrent gRPC connections from a single IP.",
+        long_help = "Sets the maximum count of concurrent gRPC connections accepted from a single IP address, closing any further connections as soon as they are accepted. Zero disables the limit.",
+        default_value = "0",
+        takes_value = true
+    )]
+    /// Define the maximum count of concurrent connections from a single IP.
+    pub max_connections_per_ip: usize,
+
+    #[structopt(
+        long = "max-streams-per-caller",
+        env = "RIFT_MAX_STREAMS_PER_CALLER",
+        help = "The maximum count of concurrently open subscribe streams per caller.",
+        long_help = "Sets the maximum count of concurrently open subscribe streams per authenticated identity, or per IP for unauthenticated callers, rejecting further streams with RESOURCE_EXHAUSTED. Zero disables the limit.",
+        default_value = "0",
+        takes_value = true
+    )]
+    /// Define the maximum count of concurrently open subscribe streams per caller.
+    pub max_streams_per_caller: usize,
+}
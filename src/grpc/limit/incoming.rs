@@ -0,0 +1,141 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+// crate usings
+use super::{Limiter, Permit};
+
+// extern usings
+use futures::Stream;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tonic::transport::server::{Connected, TcpConnectInfo};
+
+/// How long to wait before accepting again after failing to accept a connection, generally as
+/// the process is out of file descriptors.
+const ACCEPT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// An accepted connection, holding a permit against the connection limit of its IP until it
+/// is closed.
+#[derive(Debug)]
+pub struct LimitedStream {
+    stream: TcpStream,
+    _permit: Permit,
+}
+
+impl Connected for LimitedStream {
+    type ConnectInfo = TcpConnectInfo;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        self.stream.connect_info()
+    }
+}
+
+impl AsyncRead for LimitedStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for LimitedStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+/// Accept connections from the supplied listener, immediately closing connections from IPs
+/// which already hold the maximum count of connections of the supplied limiter.
+pub fn incoming(
+    listener: TcpListener,
+    limiter: Limiter,
+    logger: slog::Logger,
+) -> impl Stream<Item = io::Result<LimitedStream>> {
+    futures::stream::unfold(listener, move |listener| {
+        let limiter = limiter.clone();
+        let logger = logger.clone();
+        async move {
+            loop {
+                let (stream, addr) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(err) => {
+                        warn!(&logger, "Failed to accept gRPC connection."; "error" => err.to_string());
+                        tokio::time::sleep(ACCEPT_BACKOFF).await;
+                        continue;
+                    }
+                };
+                match limiter.acquire(&addr.ip().to_string()) {
+                    Some(permit) => {
+                        let stream = LimitedStream {
+                            stream,
+                            _permit: permit,
+                        };
+                        return Some((Ok(stream), listener));
+                    }
+                    None => {
+                        debug!(&logger, "Closing connection exceeding the per IP limit."; "addr" => addr.to_string());
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    use futures::StreamExt;
+
+    #[test]
+    fn test_incoming() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let limiter = Limiter::new(1);
+            let logger = slog::Logger::root(slog::Discard, o!());
+            let mut incoming = Box::pin(incoming(listener, limiter.clone(), logger));
+
+            let _first = TcpStream::connect(addr).await.unwrap();
+            let accepted = incoming.next().await.unwrap().unwrap();
+            assert_eq!(
+                accepted.connect_info().remote_addr().unwrap().ip(),
+                addr.ip()
+            );
+            assert_eq!(limiter.count("127.0.0.1"), 1);
+
+            // Further connections are closed until the first is.
+            let _second = TcpStream::connect(addr).await.unwrap();
+            let next = tokio::time::timeout(Duration::from_millis(100), incoming.next()).await;
+            assert!(next.is_err());
+            assert_eq!(limiter.count("127.0.0.1"), 1);
+
+            drop(accepted);
+            let _third = TcpStream::connect(addr).await.unwrap();
+            let _accepted = incoming.next().await.unwrap().unwrap();
+            assert_eq!(limiter.count("127.0.0.1"), 1);
+        });
+    }
+}
@@ -0,0 +1,149 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+// crate usings
+use crate::grpc::interceptor::IdentityExt;
+
+// extern usings
+use tonic::Request;
+
+/// Return the key the supplied request is limited under, being the authenticated identity of
+/// its caller, or the IP of its caller when unauthenticated.
+pub fn caller<T>(request: &Request<T>) -> String {
+    if let Some(identity) = request.extensions().get::<IdentityExt>() {
+        return format!("identity:{}", identity.identity);
+    }
+    match request.remote_addr() {
+        Some(addr) => format!("ip:{}", addr.ip()),
+        None => String::from("unknown"),
+    }
+}
+
+/// The count of permits held per key.
+type Counts = Arc<Mutex<HashMap<String, usize>>>;
+
+/// Limits the count of concurrently held permits per key.
+#[derive(Debug, Clone, Default)]
+pub struct Limiter {
+    max: Arc<AtomicUsize>,
+    counts: Counts,
+}
+
+impl Limiter {
+    /// Create a new limiter allowing up to the supplied count of concurrent permits per key,
+    /// where zero is unlimited.
+    pub fn new(max: usize) -> Self {
+        Self {
+            max: Arc::new(AtomicUsize::new(max)),
+            counts: Arc::default(),
+        }
+    }
+
+    /// Return the maximum count of concurrent permits per key, where zero is unlimited.
+    pub fn max(&self) -> usize {
+        self.max.load(Ordering::Relaxed)
+    }
+
+    /// Switch to the supplied maximum, returning the previous one. Permits already held in
+    /// excess of a lowered maximum are kept until released.
+    pub fn set_max(&self, max: usize) -> usize {
+        self.max.swap(max, Ordering::Relaxed)
+    }
+
+    /// Acquire a permit for the supplied key, which is released when dropped. [None] is
+    /// returned if the key already holds the maximum count of permits.
+    pub fn acquire(&self, key: &str) -> Option<Permit> {
+        let max = self.max();
+        if max == 0 {
+            return Some(Permit { inner: None });
+        }
+        let mut counts = self.counts.lock().unwrap();
+        let count = counts.entry(key.to_owned()).or_insert(0);
+        if *count >= max {
+            return None;
+        }
+        *count += 1;
+        Some(Permit {
+            inner: Some((key.to_owned(), self.counts.clone())),
+        })
+    }
+
+    /// Return the count of permits currently held for the supplied key.
+    pub fn count(&self, key: &str) -> usize {
+        self.counts.lock().unwrap().get(key).copied().unwrap_or(0)
+    }
+}
+
+/// A permit held against a [Limiter], released when dropped.
+#[derive(Debug)]
+pub struct Permit {
+    inner: Option<(String, Counts)>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some((key, counts)) = self.inner.take() {
+            let mut counts = counts.lock().unwrap();
+            if let Some(count) = counts.get_mut(&key) {
+                *count -= 1;
+                // Forget idle keys, so that the map only grows with concurrent callers.
+                if *count == 0 {
+                    counts.remove(&key);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limiter() {
+        let limiter = Limiter::new(2);
+        let first = limiter.acquire("a").unwrap();
+        let second = limiter.acquire("a").unwrap();
+        assert!(limiter.acquire("a").is_none());
+        assert!(limiter.acquire("b").is_some());
+        assert_eq!(limiter.count("a"), 2);
+
+        drop(first);
+        assert_eq!(limiter.count("a"), 1);
+        let third = limiter.acquire("a").unwrap();
+        drop(second);
+        drop(third);
+        assert_eq!(limiter.count("a"), 0);
+        assert!(limiter.counts.lock().unwrap().is_empty());
+
+        let unlimited = Limiter::default();
+        let permits: Vec<Permit> = (0..100).filter_map(|_| unlimited.acquire("a")).collect();
+        assert_eq!(permits.len(), 100);
+
+        // Clones share their maximum, so it can be switched while in use.
+        let clone = limiter.clone();
+        assert_eq!(clone.set_max(1), 2);
+        assert_eq!(limiter.max(), 1);
+        let only = limiter.acquire("a").unwrap();
+        assert!(limiter.acquire("a").is_none());
+        drop(only);
+    }
+
+    #[test]
+    fn test_caller() {
+        let request = Request::new(());
+        assert_eq!(caller(&request), "unknown");
+
+        let mut request = Request::new(());
+        request.extensions_mut().insert(IdentityExt {
+            identity: String::from("alice"),
+        });
+        assert_eq!(caller(&request), "identity:alice");
+    }
+}
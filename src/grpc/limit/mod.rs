@@ -0,0 +1,10 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+mod config;
+mod incoming;
+mod limiter;
+
+pub use config::Config;
+pub use incoming::{incoming, LimitedStream};
+pub use limiter::{caller, Limiter, Permit};
@@ -1,13 +1,29 @@
 // (c) Copyright 2021-2022 Christian Saide
 // SPDX-License-Identifier: GPL-3.0
 
+/// The backup service gRPC implementation.
+pub mod backup;
+/// The cluster service gRPC implementation.
+pub mod cluster;
+/// The deliver service implemented by subscribers that have messages pushed to them.
+pub mod deliver;
 /// A handful of error helpers for gRPC error conditions.
 pub mod error;
+/// Forwarding of requests to the cluster member owning their topic.
+pub mod forward;
 /// A set of gRPC interceptors to use.
 pub mod interceptor;
+/// A set of tower layers to wrap the gRPC server with.
+pub mod layer;
+/// Per caller limits on concurrent connections and subscribe streams.
+pub mod limit;
 /// The pub/sub service gRPC implementation.
 pub mod pubsub;
 /// The subscription service gRPC implementation.
 pub mod subscription;
+/// The tenant service gRPC implementation.
+pub mod tenant;
 /// The topic service gRPC implementation.
 pub mod topic;
+/// Streaming of resource changes to the watch RPCs of the topic and subscription services.
+pub mod watch;
@@ -0,0 +1,205 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::collections::HashMap;
+
+use crate::claimcheck::CLAIM_CHECK_ATTRIBUTE;
+use crate::pubsub::Config;
+
+use super::{
+    CHUNK_COUNT_ATTRIBUTE, CHUNK_GROUP_ATTRIBUTE, CHUNK_INDEX_ATTRIBUTE, IDEMPOTENCY_KEY_ATTRIBUTE,
+};
+
+/// The prefix of the attributes reserved by rift itself, such as those of chunks and
+/// idempotency keys.
+pub const RESERVED_ATTRIBUTE_PREFIX: &str = "x-rift-";
+
+/// The reserved attributes publishers set themselves, which are exempt from the configured
+/// attribute limits, alongside the fixed maximum count of bytes of each of their values.
+const EXEMPT_ATTRIBUTES: &[(&str, usize)] = &[
+    (CHUNK_GROUP_ATTRIBUTE, 128),
+    (CHUNK_INDEX_ATTRIBUTE, 20),
+    (CHUNK_COUNT_ATTRIBUTE, 20),
+    (IDEMPOTENCY_KEY_ATTRIBUTE, 256),
+    (CLAIM_CHECK_ATTRIBUTE, 64),
+];
+
+/// Caps on the attributes of a published message, where zero is unlimited. The reserved
+/// attributes of chunks, idempotency keys and claim checks are not subject to them, and are
+/// held to fixed limits instead.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AttributeLimits {
+    /// The maximum count of attributes.
+    pub count: usize,
+    /// The maximum count of bytes of each attribute key.
+    pub key_bytes: usize,
+    /// The maximum count of bytes of each attribute value.
+    pub value_bytes: usize,
+}
+
+impl From<&Config> for AttributeLimits {
+    fn from(cfg: &Config) -> Self {
+        Self {
+            count: cfg.max_attributes,
+            key_bytes: cfg.max_attribute_key_bytes,
+            value_bytes: cfg.max_attribute_value_bytes,
+        }
+    }
+}
+
+impl AttributeLimits {
+    /// Check that the supplied attributes fit within these limits, failing with the first
+    /// violation in key order.
+    pub fn check(&self, attributes: &HashMap<String, String>) -> crate::Result<()> {
+        for (key, max) in EXEMPT_ATTRIBUTES {
+            match attributes.get(*key) {
+                Some(value) if value.len() > *max => {
+                    return Err(crate::Error::InvalidAttribute {
+                        key: Some(key.to_string()),
+                        reason: format!(
+                            "the value of attribute '{}' of {} bytes exceeds the limit of {} bytes",
+                            key,
+                            value.len(),
+                            max
+                        ),
+                    })
+                }
+                _ => {}
+            }
+        }
+        let mut keys: Vec<_> = attributes
+            .keys()
+            .filter(|key| !EXEMPT_ATTRIBUTES.iter().any(|(exempt, _)| key == exempt))
+            .collect();
+        if self.count > 0 && keys.len() > self.count {
+            return Err(crate::Error::InvalidAttribute {
+                key: None,
+                reason: format!(
+                    "the message has {} attributes, exceeding the limit of {}",
+                    keys.len(),
+                    self.count
+                ),
+            });
+        }
+        keys.sort_unstable();
+        for key in keys {
+            if self.key_bytes > 0 && key.len() > self.key_bytes {
+                // The key is truncated rather than echoed back whole, as it may be huge.
+                return Err(crate::Error::InvalidAttribute {
+                    key: None,
+                    reason: format!(
+                        "the attribute key '{}...' of {} bytes exceeds the limit of {} bytes",
+                        truncate(key, self.key_bytes),
+                        key.len(),
+                        self.key_bytes
+                    ),
+                });
+            }
+            let value = &attributes[key];
+            if self.value_bytes > 0 && value.len() > self.value_bytes {
+                return Err(crate::Error::InvalidAttribute {
+                    key: Some(key.clone()),
+                    reason: format!(
+                        "the value of attribute '{}' of {} bytes exceeds the limit of {} bytes",
+                        key,
+                        value.len(),
+                        self.value_bytes
+                    ),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Return the longest prefix of the supplied string of at most the supplied count of bytes.
+fn truncate(value: &str, max: usize) -> &str {
+    let mut end = max.min(value.len());
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    &value[..end]
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    fn attributes(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_check() {
+        let limits = AttributeLimits {
+            count: 2,
+            key_bytes: 4,
+            value_bytes: 3,
+        };
+        assert!(limits.check(&HashMap::new()).is_ok());
+        assert!(limits
+            .check(&attributes(&[
+                ("a", "abc"),
+                ("bcde", "b"),
+                ("x-rift-chunk-index", "10000"),
+                ("x-rift-chunk-count", "10000")
+            ]))
+            .is_ok());
+
+        match limits.check(&attributes(&[("a", "1"), ("b", "2"), ("c", "3")])) {
+            Err(crate::Error::InvalidAttribute { key: None, reason }) => {
+                assert_eq!(
+                    reason,
+                    "the message has 3 attributes, exceeding the limit of 2"
+                )
+            }
+            res => panic!("unexpected result: {:?}", res),
+        }
+        match limits.check(&attributes(&[("a", "1"), ("hélloo", "2")])) {
+            Err(crate::Error::InvalidAttribute { key: None, reason }) => assert_eq!(
+                reason,
+                "the attribute key 'hél...' of 7 bytes exceeds the limit of 4 bytes"
+            ),
+            res => panic!("unexpected result: {:?}", res),
+        }
+        match limits.check(&attributes(&[("b", "long"), ("a", "longer")])) {
+            Err(crate::Error::InvalidAttribute {
+                key: Some(key),
+                reason,
+            }) => {
+                assert_eq!(key, "a");
+                assert_eq!(
+                    reason,
+                    "the value of attribute 'a' of 6 bytes exceeds the limit of 3 bytes"
+                );
+            }
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        let many = attributes(&[("a", "1"), ("b", "2"), ("c", "3")]);
+        assert!(AttributeLimits::default().check(&many).is_ok());
+
+        // Only the reserved attributes publishers set are exempt, and only up to their own limit.
+        let reserved = attributes(&[("a", "1"), ("b", "2"), ("x-rift-other", "3")]);
+        assert!(limits.check(&reserved).is_err());
+        let long = "x".repeat(257);
+        let reserved = attributes(&[(IDEMPOTENCY_KEY_ATTRIBUTE, &long)]);
+        match AttributeLimits::default().check(&reserved) {
+            Err(crate::Error::InvalidAttribute {
+                key: Some(key),
+                reason,
+            }) => {
+                assert_eq!(key, IDEMPOTENCY_KEY_ATTRIBUTE);
+                assert_eq!(
+                    reason,
+                    "the value of attribute 'x-rift-idempotency-key' of 257 bytes exceeds the limit of 256 bytes"
+                );
+            }
+            res => panic!("unexpected result: {:?}", res),
+        }
+    }
+}
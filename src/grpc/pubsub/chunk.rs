@@ -0,0 +1,397 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+
+use crate::pubsub::Config;
+use crate::{Error, Result};
+
+use super::Message;
+
+/// The attribute carrying the identifier shared by every chunk of a chunked message.
+pub const CHUNK_GROUP_ATTRIBUTE: &str = "x-rift-chunk-group";
+/// The attribute carrying the zero based position of a chunk within its message.
+pub const CHUNK_INDEX_ATTRIBUTE: &str = "x-rift-chunk-index";
+/// The attribute carrying the total count of chunks in a chunked message.
+pub const CHUNK_COUNT_ATTRIBUTE: &str = "x-rift-chunk-count";
+
+/// The maximum count of chunks a single message can be split into.
+pub const MAX_CHUNKS: usize = 1024;
+/// The default time allowed between the first and last chunk of a message arriving.
+pub const DEFAULT_CHUNK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The chunks of a message received so far.
+#[derive(Debug)]
+struct Group {
+    started: Instant,
+    parts: Vec<Option<Bytes>>,
+    received: usize,
+    bytes: usize,
+    attributes: HashMap<String, String>,
+    content_type: String,
+}
+
+/// The groups of an [Assembler], keyed by the caller publishing them, their topic and their
+/// identifier, alongside the bytes of chunks they hold per caller and in total.
+#[derive(Debug, Default)]
+struct Groups {
+    groups: HashMap<(String, String, String), Group>,
+    callers: HashMap<String, usize>,
+    bytes: usize,
+}
+
+impl Groups {
+    /// Remove the group with the supplied key, releasing the bytes of its chunks.
+    fn remove(&mut self, key: &(String, String, String)) -> Option<Group> {
+        let group = self.groups.remove(key)?;
+        self.bytes -= group.bytes;
+        if let Some(held) = self.callers.get_mut(&key.0) {
+            *held -= group.bytes;
+            if *held == 0 {
+                self.callers.remove(&key.0);
+            }
+        }
+        Some(group)
+    }
+}
+
+/// Reassembles chunked messages, published as a sequence of parts sharing a group identifier,
+/// back into the original message once every part has arrived.
+///
+/// Groups which are not completed within the timeout are discarded by [Assembler::prune], and
+/// the bytes of chunks held may be capped per caller and in total.
+#[derive(Debug, Clone)]
+pub struct Assembler {
+    groups: Arc<Mutex<Groups>>,
+    timeout: Duration,
+    max_bytes: usize,
+    max_caller_bytes: usize,
+}
+
+impl From<&Config> for Assembler {
+    fn from(cfg: &Config) -> Self {
+        Self::default()
+            .with_max_bytes(cfg.max_chunk_bytes)
+            .with_max_caller_bytes(cfg.max_chunk_bytes_per_caller)
+    }
+}
+
+impl Assembler {
+    /// Create a new assembler discarding groups not completed within the supplied timeout.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            groups: Arc::default(),
+            timeout,
+            max_bytes: 0,
+            max_caller_bytes: 0,
+        }
+    }
+
+    /// Cap the bytes of chunks held for incomplete messages across every caller, where zero
+    /// is unlimited.
+    pub fn with_max_bytes(mut self, max: usize) -> Self {
+        self.max_bytes = max;
+        self
+    }
+
+    /// Cap the bytes of chunks held for the incomplete messages of each caller, where zero is
+    /// unlimited.
+    pub fn with_max_caller_bytes(mut self, max: usize) -> Self {
+        self.max_caller_bytes = max;
+        self
+    }
+
+    /// Discard every group not completed within the timeout, returning the count discarded.
+    pub fn prune(&self) -> usize {
+        let mut groups = self.groups.lock().unwrap();
+        let stale: Vec<_> = groups
+            .groups
+            .iter()
+            .filter(|(_, group)| group.started.elapsed() >= self.timeout)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in stale.iter() {
+            groups.remove(key);
+        }
+        stale.len()
+    }
+
+    /// Prune this assembler on every interval, forever.
+    pub async fn run(self, interval: Duration) {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            self.prune();
+        }
+    }
+
+    /// Add the supplied chunk, published by the supplied caller, returning the reassembled
+    /// message once it completes its group. Messages which are not chunks are returned as is.
+    pub fn add(&self, caller: &str, mut msg: Message) -> Result<Option<Message>> {
+        let group = match msg.attributes.remove(CHUNK_GROUP_ATTRIBUTE) {
+            Some(group) => group,
+            None => return Ok(Some(msg)),
+        };
+        let index = parse(&mut msg, CHUNK_INDEX_ATTRIBUTE)?;
+        let count = parse(&mut msg, CHUNK_COUNT_ATTRIBUTE)?;
+        if count == 0 || count > MAX_CHUNKS {
+            return Err(invalid(&format!(
+                "chunk count must be between 1 and {}",
+                MAX_CHUNKS
+            )));
+        }
+        if index >= count {
+            return Err(invalid("chunk index must be less than the chunk count"));
+        }
+
+        let mut groups = self.groups.lock().unwrap();
+        let key = (caller.to_owned(), msg.topic.clone(), group);
+        // Groups are only pruned periodically, so one which already timed out starts over.
+        if matches!(groups.groups.get(&key), Some(group) if group.started.elapsed() >= self.timeout)
+        {
+            groups.remove(&key);
+        }
+        match groups.groups.get(&key) {
+            Some(group) if group.parts.len() != count => {
+                return Err(invalid(
+                    "chunk count must match the other chunks of its group",
+                ))
+            }
+            // Duplicate chunks are ignored.
+            Some(group) if group.parts[index].is_some() => return Ok(None),
+            _ => {}
+        }
+
+        let bytes = msg.data.len();
+        if self.max_bytes > 0 && groups.bytes + bytes > self.max_bytes {
+            return Err(Error::ChunkLimitExceeded {
+                held_for: String::from("every caller"),
+                limit: self.max_bytes,
+            });
+        }
+        let held = groups.callers.get(caller).copied().unwrap_or_default();
+        if self.max_caller_bytes > 0 && held + bytes > self.max_caller_bytes {
+            return Err(Error::ChunkLimitExceeded {
+                held_for: format!("the caller '{}'", caller),
+                limit: self.max_caller_bytes,
+            });
+        }
+        groups.bytes += bytes;
+        *groups.callers.entry(caller.to_owned()).or_default() += bytes;
+
+        let group = groups.groups.entry(key.clone()).or_insert_with(|| Group {
+            started: Instant::now(),
+            parts: vec![None; count],
+            received: 0,
+            bytes: 0,
+            attributes: HashMap::new(),
+            content_type: String::new(),
+        });
+        if index == 0 {
+            group.attributes = std::mem::take(&mut msg.attributes);
+            group.content_type = std::mem::take(&mut msg.content_type);
+        }
+        group.parts[index] = Some(msg.data);
+        group.received += 1;
+        group.bytes += bytes;
+        if group.received < count {
+            return Ok(None);
+        }
+
+        let group = groups.remove(&key).unwrap();
+        let data = group
+            .parts
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .concat();
+        Ok(Some(Message {
+            topic: key.1,
+            attributes: group.attributes,
+            published: None,
+            data: data.into(),
+            checksum: None,
+            content_type: group.content_type,
+        }))
+    }
+}
+
+impl Default for Assembler {
+    fn default() -> Self {
+        Self::new(DEFAULT_CHUNK_TIMEOUT)
+    }
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::InvalidArgument {
+        reason: reason.to_owned(),
+    }
+}
+
+/// Remove and parse the supplied numeric chunk attribute from the supplied message.
+fn parse(msg: &mut Message, attribute: &str) -> Result<usize> {
+    msg.attributes
+        .remove(attribute)
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| {
+            invalid(&format!(
+                "chunks require a numeric '{}' attribute",
+                attribute
+            ))
+        })
+}
+
+/// Split the supplied message into chunks of at most the supplied size, which an [Assembler]
+/// reassembles into the original message. Messages within the size are returned as is, and
+/// chunks share the data of the original message rather than copying it.
+pub fn split(msg: Message, size: usize) -> Vec<Message> {
+    let size = std::cmp::max(size, 1);
+    if msg.data.len() <= size {
+        return vec![msg];
+    }
+    let group = uuid::Uuid::new_v4().to_string();
+    let count = msg.data.len().div_ceil(size);
+    (0..count)
+        .map(|index| {
+            // Only the first chunk carries the attributes and content type of the original
+            // message.
+            let (mut attributes, content_type) = match index {
+                0 => (msg.attributes.clone(), msg.content_type.clone()),
+                _ => (HashMap::new(), String::new()),
+            };
+            attributes.insert(String::from(CHUNK_GROUP_ATTRIBUTE), group.clone());
+            attributes.insert(String::from(CHUNK_INDEX_ATTRIBUTE), index.to_string());
+            attributes.insert(String::from(CHUNK_COUNT_ATTRIBUTE), count.to_string());
+            Message {
+                topic: msg.topic.clone(),
+                attributes,
+                published: None,
+                data: msg
+                    .data
+                    .slice(index * size..std::cmp::min((index + 1) * size, msg.data.len())),
+                checksum: None,
+                content_type,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    fn message(data: &[u8]) -> Message {
+        let mut msg = Message {
+            topic: String::from("woot"),
+            data: Bytes::copy_from_slice(data),
+            content_type: String::from("text/plain"),
+            ..Default::default()
+        };
+        msg.attributes
+            .insert(String::from("key"), String::from("value"));
+        msg
+    }
+
+    #[test]
+    fn test_split_and_reassemble() {
+        let assembler = Assembler::default();
+        let msg = message(b"hello world");
+        assert_eq!(split(msg.clone(), 11), vec![msg.clone()]);
+
+        let mut chunks = split(msg.clone(), 4);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[2].data, &b"rld"[..]);
+        // Chunks share the data of the original message.
+        assert_eq!(chunks[1].data.as_ptr(), msg.data[4..].as_ptr());
+        // Chunks may arrive in any order, and duplicates are ignored.
+        chunks.swap(0, 2);
+        assert_eq!(assembler.add("a", chunks[0].clone()).unwrap(), None);
+        assert_eq!(assembler.add("a", chunks[0].clone()).unwrap(), None);
+        assert_eq!(assembler.add("a", chunks[1].clone()).unwrap(), None);
+        assert_eq!(
+            assembler.add("a", chunks[2].clone()).unwrap(),
+            Some(msg.clone())
+        );
+
+        // Plain messages pass straight through.
+        assert_eq!(assembler.add("a", msg.clone()).unwrap(), Some(msg));
+    }
+
+    #[test]
+    fn test_invalid_chunks() {
+        let assembler = Assembler::default();
+        let chunks = split(message(b"hello world"), 4);
+
+        let mut chunk = chunks[1].clone();
+        chunk.attributes.remove(CHUNK_INDEX_ATTRIBUTE);
+        assert!(assembler.add("a", chunk).is_err());
+
+        let mut chunk = chunks[1].clone();
+        chunk
+            .attributes
+            .insert(String::from(CHUNK_INDEX_ATTRIBUTE), String::from("3"));
+        assert!(assembler.add("a", chunk).is_err());
+
+        assert_eq!(assembler.add("a", chunks[0].clone()).unwrap(), None);
+        let mut chunk = chunks[1].clone();
+        chunk
+            .attributes
+            .insert(String::from(CHUNK_COUNT_ATTRIBUTE), String::from("4"));
+        assert!(assembler.add("a", chunk).is_err());
+    }
+
+    #[test]
+    fn test_timeout() {
+        let assembler = Assembler::new(Duration::from_millis(10));
+        let chunks = split(message(b"hello world"), 4);
+        assert_eq!(assembler.add("a", chunks[0].clone()).unwrap(), None);
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(assembler.add("a", chunks[1].clone()).unwrap(), None);
+        assert_eq!(assembler.add("a", chunks[2].clone()).unwrap(), None);
+
+        // Pruning discards timed out groups, releasing the bytes of their chunks.
+        assert_eq!(assembler.prune(), 0);
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(assembler.prune(), 1);
+        let groups = assembler.groups.lock().unwrap();
+        assert!(groups.groups.is_empty());
+        assert!(groups.callers.is_empty());
+        assert_eq!(groups.bytes, 0);
+    }
+
+    #[test]
+    fn test_limits() {
+        let assembler = Assembler::default()
+            .with_max_bytes(15)
+            .with_max_caller_bytes(11);
+        let chunks = split(message(b"hello world"), 4);
+
+        // Each caller may hold up to its own limit, and every caller up to the total limit.
+        assert_eq!(assembler.add("a", chunks[0].clone()).unwrap(), None);
+        assert_eq!(assembler.add("a", chunks[1].clone()).unwrap(), None);
+        match assembler.add("a", split(message(b"hello world"), 4)[0].clone()) {
+            Err(Error::ChunkLimitExceeded { held_for, limit }) => {
+                assert_eq!(held_for, "the caller 'a'");
+                assert_eq!(limit, 11);
+            }
+            res => panic!("unexpected result: {:?}", res),
+        }
+        assert_eq!(assembler.add("b", chunks[0].clone()).unwrap(), None);
+        match assembler.add("b", chunks[1].clone()) {
+            Err(Error::ChunkLimitExceeded { held_for, limit }) => {
+                assert_eq!(held_for, "every caller");
+                assert_eq!(limit, 15);
+            }
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        // Completing a message releases the bytes of its chunks.
+        assert!(assembler.add("a", chunks[2].clone()).unwrap().is_some());
+        assert_eq!(assembler.add("b", chunks[1].clone()).unwrap(), None);
+    }
+}
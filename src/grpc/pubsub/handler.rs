@@ -1,38 +1,307 @@
 // (c) Copyright 2021-2022 Christian Saide
 // SPDX-License-Identifier: GPL-3.0
 
+use std::borrow::Cow;
+use std::collections::{HashSet, VecDeque};
+use std::future::Future;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
+use futures::StreamExt;
 use prost_types::Timestamp;
-use tonic::{Request, Response, Status};
-
-use crate::grpc::error::{sub_not_found, topic_not_found};
-use crate::pubsub::{Registry, Stream};
-
+use tokio::time::Sleep;
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::claimcheck::{self, CLAIM_CHECK_ATTRIBUTE};
+use crate::cluster::Membership;
+use crate::encryption::Keyring;
+use crate::events::{Emitter, Kind};
+use crate::grpc::error::{invalid_argument, not_owner, sub_not_found, topic_not_found};
+use crate::grpc::forward::{is_forwarded, Forward};
+use crate::grpc::interceptor::IdentityExt;
+use crate::grpc::limit::{caller, Limiter, Permit};
+use crate::grpc::topic::DEFAULT_SUBSCRIPTION_TEMPLATE;
+use crate::metric::IdentityMetrics;
+use crate::pubsub::{
+    namespace, Entry, Error as PubsubError, ForwardRule, Progress, Queue, Registry, Rejection,
+    Stream, Sub, Throttle, Topic,
+};
+use crate::schema;
+use crate::shutdown::Shutdown;
+use crate::tenant::Tenants;
+
+use super::proto::pub_sub_service_client::PubSubServiceClient;
 use super::proto::pub_sub_service_server::PubSubService;
-use super::{ConfimrationStatus, Confirmation, Lease, LeasedMessage, Message, Subscription};
+use super::redrive::{rekey, Redrive};
+use super::{
+    Assembler, AttributeLimits, ClaimRequest, ClaimResponse, ConfimrationStatus, Confirmation,
+    Deduplicator, Lease, LeasedMessage, Message, NackReason, PeekRequest, PeekedMessage,
+    QuarantineRequest, QuarantineResponse, QuarantinedMessage, RedriveRequest, RedriveResponse,
+    ReleaseRequest, ReleaseResponse, SettleRequest, SettleResponse, StartPosition, Subscription,
+    IDEMPOTENCY_KEY_ATTRIBUTE,
+};
+
+/// The identity metrics of a request paired with the authenticated identity of its caller.
+type Identity = (IdentityMetrics, String);
+
+/// The schema of a topic paired with the content type its messages are delivered as.
+type Transcode = (Arc<schema::Schema>, String);
+
+/// Completes once the server begins shutting down.
+type Draining = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// The source of the messages of a subscribe stream.
+enum Source {
+    /// Messages leased from a local subscription.
+    Local(Box<Stream<Message>>),
+    /// Messages leased from a subscription owned by another cluster member.
+    Forwarded(Streaming<LeasedMessage>),
+}
+
+/// The maximum count of leased messages coalesced into a single subscribe response.
+pub const MAX_DELIVERY_BATCH: usize = 1000;
+/// The longest ack deadline a subscribe stream may lease its messages for.
+pub const MAX_ACK_DEADLINE: Duration = Duration::from_secs(600);
+/// The longest delay a nacked message may be held back from redelivery for.
+pub const MAX_NACK_DELAY: Duration = Duration::from_secs(3600);
+/// The default name of the topic messages nacked as permanent or malformed are moved to,
+/// where `{topic}` is replaced by the name of the topic they were nacked from.
+pub const DEAD_LETTER_TOPIC_TEMPLATE: &str = "{topic}.dead-letter";
 
 pub struct SubscribeStream {
-    inner: Stream<Message>,
+    source: Source,
     subscription: String,
+    identity: Option<Identity>,
+    tenants: Option<Tenants>,
+    throttle: Throttle,
+    throttled: Option<Pin<Box<Sleep>>>,
+    keyring: Option<Keyring>,
+    transcode: Option<Transcode>,
+    dead_letter: Option<(String, Topic<Message>)>,
+    draining: Draining,
+    drained: bool,
+    max_batch: usize,
+    max_wait: Duration,
+    batch: Option<LeasedMessage>,
+    deadline: Option<Pin<Box<Sleep>>>,
+    failed: Option<Status>,
+    _permit: Option<Permit>,
+}
+
+impl SubscribeStream {
+    /// Poll until the throttle of the subscription allows the next delivery.
+    fn poll_throttle(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        while let Some(delay) = self.throttle.delay() {
+            let throttled = self
+                .throttled
+                .get_or_insert_with(|| Box::pin(tokio::time::sleep(delay)));
+            if throttled.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            self.throttled = None;
+        }
+        Poll::Ready(())
+    }
+
+    /// Coalesce leased messages into batches of up to the supplied count, waiting up to the
+    /// supplied duration for a partially filled batch to fill.
+    fn with_batching(mut self, max_batch: u32, max_wait_ms: u64) -> Self {
+        self.max_batch = (max_batch as usize).clamp(1, MAX_DELIVERY_BATCH);
+        self.max_wait = Duration::from_millis(max_wait_ms);
+        self
+    }
+
+    /// Stream the pending batch, if any.
+    fn flush(&mut self) -> Poll<Option<Result<LeasedMessage, Status>>> {
+        self.deadline = None;
+        Poll::Ready(self.batch.take().map(Ok))
+    }
+
+    /// Poll for the next leased message, once the throttle of the subscription allows it.
+    fn poll_leased(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<LeasedMessage, Status>>> {
+        if self.poll_throttle(cx).is_pending() {
+            return Poll::Pending;
+        }
+        let leased_msg = match &mut self.source {
+            // Messages which can not be opened or transcoded are set aside rather than failing
+            // the stream, as they would otherwise fail every stream they are redelivered on.
+            Source::Local(inner) => loop {
+                let (tag, index, mut msg) = match inner.poll_next_unpin(cx) {
+                    Poll::Ready(opt) if opt.is_some() => opt.unwrap(),
+                    _ => return Poll::Pending,
+                };
+                if let Some(keyring) = &self.keyring {
+                    // Messages which can not be opened can not be sealed for a dead-letter
+                    // topic either, so they are held back instead.
+                    if keyring.open(&mut msg).is_err() {
+                        let _ = inner.queue().nack_with_delay(tag.id, index, MAX_NACK_DELAY);
+                        continue;
+                    }
+                }
+                if transcode(&mut msg, self.transcode.as_ref()).is_err() {
+                    let dead_letter = self.dead_letter.as_ref();
+                    set_aside(
+                        inner.queue(),
+                        tag.id,
+                        index,
+                        msg,
+                        self.keyring.as_ref(),
+                        dead_letter,
+                    );
+                    continue;
+                }
+                self.throttle.consume(msg.data.len());
+                if let Some(tenants) = &self.tenants {
+                    tenants.consumed(&msg.topic, msg.data.len());
+                }
+                let lease =
+                    Lease::from_tag(tag, msg.topic.clone(), self.subscription.clone(), index);
+                break LeasedMessage {
+                    lease: Some(lease),
+                    message: Some(msg),
+                    batch: Vec::new(),
+                };
+            },
+            Source::Forwarded(inner) => match inner.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(leased_msg))) => leased_msg,
+                other => return other,
+            },
+        };
+        if let Some((metrics, identity)) = &self.identity {
+            // Forwarded responses may already be batched by the owning member.
+            let batched = leased_msg.batch.iter().map(|leased| &leased.message);
+            for msg in std::iter::once(&leased_msg.message)
+                .chain(batched)
+                .flatten()
+            {
+                metrics.consumed(identity, msg.data.len());
+            }
+        }
+        Poll::Ready(Some(Ok(leased_msg)))
+    }
+}
+
+/// Transcode the data of the supplied message into the requested content type, if any.
+/// Claim checked data is delivered as it was published, as only the reference is streamed.
+fn transcode(msg: &mut Message, transcode: Option<&Transcode>) -> schema::Result<()> {
+    let (schema, content_type) = match transcode {
+        Some(transcode) if !msg.attributes.contains_key(CLAIM_CHECK_ATTRIBUTE) => transcode,
+        _ => return Ok(()),
+    };
+    let data = match schema.transcode(&msg.data, &msg.content_type, content_type)? {
+        Cow::Borrowed(_) => return Ok(()),
+        Cow::Owned(data) => data,
+    };
+    msg.data = data.into();
+    msg.content_type = content_type.clone();
+    if msg.checksum.is_some() {
+        msg.stamp_checksum();
+    }
+    Ok(())
+}
+
+/// Move the supplied opened message, leased from the supplied queue but undeliverable, to the
+/// supplied dead-letter topic as though it was nacked as malformed. Messages are held back for
+/// [MAX_NACK_DELAY] instead when there is no dead-letter topic, or it refuses them.
+fn set_aside(
+    queue: &Queue<Message>,
+    lease_id: u64,
+    index: usize,
+    mut msg: Message,
+    keyring: Option<&Keyring>,
+    dead_letter: Option<&(String, Topic<Message>)>,
+) {
+    let moved = match dead_letter {
+        Some((name, dead)) => rekey(keyring, &mut msg, name) && dead.push(msg).is_ok(),
+        None => false,
+    };
+    let _ = match moved {
+        true => queue
+            .reject(lease_id, index, Rejection::Malformed)
+            .map(|_| ()),
+        false => queue.nack_with_delay(lease_id, index, MAX_NACK_DELAY),
+    };
+}
+
+/// Return whether the supplied message was published before the supplied start.
+fn published_before(msg: &Message, start: SystemTime) -> bool {
+    let published = msg
+        .published
+        .clone()
+        .and_then(|published| SystemTime::try_from(published).ok());
+    matches!(published, Some(published) if published < start)
 }
 
 impl futures::Stream for SubscribeStream {
     type Item = Result<LeasedMessage, Status>;
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let pinned = Pin::new(&mut self.inner);
-        let (tag, index, msg) = match pinned.poll_next(cx) {
-            Poll::Ready(opt) if opt.is_some() => opt.unwrap(),
-            _ => return Poll::Pending,
-        };
-        let lease = Lease::from_tag(tag, msg.topic.clone(), self.subscription.clone(), index);
-        let leased_msg = LeasedMessage {
-            lease: Some(lease),
-            message: Some(msg),
-        };
-        Poll::Ready(Some(Ok(leased_msg)))
+        // Streams end as the server shuts down, before leasing any further messages, so that
+        // subscribers can resume against another member. Every lease the stream handed out is
+        // released, including those of a batch not yet delivered, so that their messages need
+        // not wait for the leases to expire, and the stream fails as shutting down to tell
+        // subscribers to reconnect elsewhere. Forwarded streams release their leases on the
+        // owning member once they are dropped.
+        if self.drained {
+            return Poll::Ready(None);
+        }
+        if self.draining.as_mut().poll(cx).is_ready() {
+            self.drained = true;
+            self.batch = None;
+            self.deadline = None;
+            if let Source::Local(inner) = &mut self.source {
+                inner.release();
+            }
+            return Poll::Ready(Some(Err(crate::Error::ShuttingDown.into())));
+        }
+        loop {
+            if self.batch.is_none() {
+                if let Some(status) = self.failed.take() {
+                    return Poll::Ready(Some(Err(status)));
+                }
+            }
+            let size = self.batch.as_ref().map_or(0, |batch| batch.batch.len() + 1);
+            if size >= self.max_batch {
+                return self.flush();
+            }
+            let (next, batching) = (self.poll_leased(cx), self.max_batch > 1);
+            match (next, &mut self.batch) {
+                (Poll::Ready(Some(Ok(leased))), Some(batch)) => batch.batch.push(leased),
+                (Poll::Ready(Some(Ok(leased))), None) if batching => {
+                    self.batch = Some(leased);
+                    if !self.max_wait.is_zero() {
+                        self.deadline = Some(Box::pin(tokio::time::sleep(self.max_wait)));
+                    }
+                }
+                (Poll::Ready(Some(Err(status))), Some(_)) => {
+                    self.failed = Some(status);
+                    return self.flush();
+                }
+                (Poll::Ready(None), Some(_)) => return self.flush(),
+                (Poll::Pending, Some(_)) => {
+                    let waiting = self
+                        .deadline
+                        .as_mut()
+                        .map(|deadline| deadline.as_mut().poll(cx));
+                    return match waiting {
+                        Some(Poll::Pending) => Poll::Pending,
+                        _ => self.flush(),
+                    };
+                }
+                (next, None) => return next,
+            }
+        }
+    }
+}
+
+pub struct PeekStream(Vec<PeekedMessage>);
+
+impl futures::Stream for PeekStream {
+    type Item = Result<PeekedMessage, Status>;
+    fn poll_next(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let item = self.0.pop().map(Ok);
+        Poll::Ready(item)
     }
 }
 
@@ -40,6 +309,22 @@ impl futures::Stream for SubscribeStream {
 #[derive(Debug)]
 pub struct Handler {
     topic_registry: Registry<Message>,
+    identity_metrics: Option<IdentityMetrics>,
+    tenants: Tenants,
+    membership: Option<Membership>,
+    schemas: schema::Registry,
+    claim_checks: Option<claimcheck::Store>,
+    assembler: Assembler,
+    attribute_limits: AttributeLimits,
+    deduplicator: Deduplicator,
+    keyring: Option<Keyring>,
+    streams: Limiter,
+    shutdown: Shutdown,
+    auto_create_topics: bool,
+    default_subscription: String,
+    dead_letter_topic: String,
+    replay_messages_per_sec: u64,
+    events: Emitter,
 }
 
 impl Handler {
@@ -51,7 +336,177 @@ impl Handler {
 
     /// Create a new handler with the supplied topic registry.
     pub fn with_registry(topic_registry: Registry<Message>) -> Self {
-        Self { topic_registry }
+        Self {
+            topic_registry,
+            identity_metrics: None,
+            tenants: Tenants::default(),
+            membership: None,
+            schemas: schema::Registry::default(),
+            claim_checks: None,
+            assembler: Assembler::default(),
+            attribute_limits: AttributeLimits::default(),
+            deduplicator: Deduplicator::default(),
+            keyring: None,
+            streams: Limiter::default(),
+            shutdown: Shutdown::default(),
+            auto_create_topics: false,
+            default_subscription: String::from(DEFAULT_SUBSCRIPTION_TEMPLATE),
+            dead_letter_topic: String::from(DEAD_LETTER_TOPIC_TEMPLATE),
+            replay_messages_per_sec: 0,
+            events: Emitter::default(),
+        }
+    }
+
+    /// Validate published messages against the schemas bound to their topics within the
+    /// supplied schema registry.
+    pub fn with_schemas(mut self, schemas: schema::Registry) -> Self {
+        self.schemas = schemas;
+        self
+    }
+
+    /// Move the payloads of published messages exceeding the threshold of the supplied store
+    /// into it, queueing a pointer to the payload in their place.
+    pub fn with_claim_checks(mut self, claim_checks: claimcheck::Store) -> Self {
+        self.claim_checks = Some(claim_checks);
+        self
+    }
+
+    /// Encrypt the data of messages published to encrypted topics with their data keys
+    /// within the supplied keyring, decrypting them as they are delivered.
+    pub fn with_keyring(mut self, keyring: Keyring) -> Self {
+        self.keyring = Some(keyring);
+        self
+    }
+
+    /// Create missing topics with the default options as messages are published to them,
+    /// rather than failing with NOT_FOUND. Each is created along with its default
+    /// subscription, so that the message creating it is delivered.
+    pub fn with_auto_create_topics(mut self, auto_create_topics: bool) -> Self {
+        self.auto_create_topics = auto_create_topics;
+        self
+    }
+
+    /// Name the default subscription of topics created as messages are published to them
+    /// with the supplied template, where `{topic}` is replaced by the name of the topic within
+    /// its namespace.
+    pub fn with_default_subscription(mut self, template: String) -> Self {
+        self.default_subscription = template;
+        self
+    }
+
+    /// Move messages nacked as permanent or malformed to the topic named by the supplied
+    /// template, where `{topic}` is replaced by the name of the topic they were nacked from.
+    /// An empty template redelivers them like any other nacked message.
+    pub fn with_dead_letter_topic(mut self, template: String) -> Self {
+        self.dead_letter_topic = template;
+        self
+    }
+
+    /// Limit redrives to moving the supplied count of messages per second, replaying larger
+    /// redrives in the background. Zero moves every message at once.
+    pub fn with_replay_rate(mut self, messages_per_sec: u64) -> Self {
+        self.replay_messages_per_sec = messages_per_sec;
+        self
+    }
+
+    /// Publish the creation of topics created as messages are published to them with the
+    /// supplied emitter.
+    pub fn with_events(mut self, events: Emitter) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Confirm retried publishes carrying the idempotency key of one committed within the
+    /// window of the supplied deduplicator, without enqueuing them again.
+    pub fn with_deduplicator(mut self, deduplicator: Deduplicator) -> Self {
+        self.deduplicator = deduplicator;
+        self
+    }
+
+    /// Limit the count of concurrently open subscribe streams per caller with the supplied
+    /// limiter.
+    pub fn with_stream_limiter(mut self, streams: Limiter) -> Self {
+        self.streams = streams;
+        self
+    }
+
+    /// End subscribe streams, and reject new ones, once the supplied coordinator begins
+    /// shutting down.
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Acquire a permit to open a subscribe stream for the caller of the supplied request.
+    /// Streams forwarded by another cluster member were already limited by that member.
+    fn acquire_stream<T>(&self, request: &Request<T>) -> Result<Option<Permit>, Status> {
+        if is_forwarded(self.membership.as_ref(), request) {
+            return Ok(None);
+        }
+        let caller = caller(request);
+        match self.streams.acquire(&caller) {
+            Some(permit) => Ok(Some(permit)),
+            None => Err(crate::Error::StreamLimitExceeded {
+                caller,
+                limit: self.streams.max(),
+            }
+            .into()),
+        }
+    }
+
+    /// Reassemble chunked messages with the supplied assembler.
+    pub fn with_assembler(mut self, assembler: Assembler) -> Self {
+        self.assembler = assembler;
+        self
+    }
+
+    /// Reject published messages whose attributes exceed the supplied limits.
+    pub fn with_attribute_limits(mut self, attribute_limits: AttributeLimits) -> Self {
+        self.attribute_limits = attribute_limits;
+        self
+    }
+
+    /// Forward requests for topics owned by other members of the supplied membership to
+    /// their owner.
+    pub fn with_membership(mut self, membership: Membership) -> Self {
+        self.membership = Some(membership);
+        self
+    }
+
+    /// Return how to forward the supplied request for the supplied topic, along with a client
+    /// connected to its owner, or [None] if it must be handled locally.
+    fn forward<T>(
+        &self,
+        request: &Request<T>,
+        topic: &str,
+    ) -> Result<Option<(Forward, PubSubServiceClient<tonic::transport::Channel>)>, Status> {
+        let forward = Forward::new(self.membership.as_ref(), request, topic)?;
+        Ok(forward.map(|forward| {
+            let client = PubSubServiceClient::new(forward.channel.clone());
+            (forward, client)
+        }))
+    }
+
+    /// Record per-identity request and throughput metrics for authenticated requests.
+    pub fn with_identity_metrics(mut self, identity_metrics: IdentityMetrics) -> Self {
+        self.identity_metrics = Some(identity_metrics);
+        self
+    }
+
+    /// Account the messages published to, and delivered from, local topics against their
+    /// tenants, rejecting publishes exceeding a quota of their tenant.
+    pub fn with_tenants(mut self, tenants: Tenants) -> Self {
+        self.tenants = tenants;
+        self
+    }
+
+    /// Record the supplied request against the identity of its caller, returning the identity
+    /// for further recording. Unauthenticated requests are not recorded.
+    fn identity<T>(&self, request: &Request<T>, method: &str) -> Option<Identity> {
+        let metrics = self.identity_metrics.as_ref()?;
+        let identity = request.extensions().get::<IdentityExt>()?;
+        metrics.request(&identity.identity, method);
+        Some((metrics.clone(), identity.identity.clone()))
     }
 
     #[cfg(test)]
@@ -60,33 +515,164 @@ impl Handler {
     }
 
     async fn _publish(&self, request: Request<Message>) -> Result<Response<Confirmation>, Status> {
-        let mut msg = request.into_inner();
+        let identity = self.identity(&request, "/pubsub.PubSubService/Publish");
+        if let Some((forward, mut client)) = self.forward(&request, &request.get_ref().topic)? {
+            let bytes = request.get_ref().data.len();
+            let response = client.publish(forward.request(request)).await?;
+            if let Some((metrics, identity)) = identity {
+                metrics.published(&identity, bytes);
+            }
+            return Ok(forward.response(response));
+        }
+        let publisher = caller(&request);
+        let msg = request.into_inner();
         if msg.data.is_empty() {
-            return Err(Status::invalid_argument("data payload must be non-empty."));
+            return invalid_argument("data payload must be non-empty.");
         }
         if msg.topic.is_empty() {
-            return Err(Status::invalid_argument("topic name must be non-empty"));
+            return invalid_argument("topic name must be non-empty");
         }
+        self.attribute_limits.check(&msg.attributes)?;
 
         let topic = match self.topic_registry.get(&msg.topic) {
             Some(topic) => topic,
+            None if self.auto_create_topics => {
+                let topic = self
+                    .topic_registry
+                    .try_create(msg.topic.clone())
+                    .map_err(crate::Error::from)?;
+                self.events.emit(Kind::TopicCreated, &msg.topic);
+                let (_, local) = namespace::split(&msg.topic);
+                let name = self.default_subscription.replace("{topic}", local);
+                let (_, created) = topic
+                    .create_with_push(name.clone(), None)
+                    .map_err(crate::Error::from)?;
+                if created {
+                    let name = format!("{}/{}", msg.topic, name);
+                    self.events.emit(Kind::SubscriptionCreated, &name);
+                }
+                topic
+            }
             None => return topic_not_found(&msg.topic),
         };
+        let bytes = msg.data.len();
+        self.tenants
+            .admit(&msg.topic, bytes)
+            .map_err(crate::Error::from)?;
+        let topic_name = msg.topic.clone();
+        // Chunks are held until their whole message arrives, which is then published as one,
+        // so only the chunk completing a message is confirmed as committed.
+        let mut msg = match self.assembler.add(&publisher, msg) {
+            Ok(Some(msg)) => msg,
+            Ok(None) => {
+                self.tenants.published(&topic_name, bytes);
+                if let Some((metrics, identity)) = identity {
+                    metrics.published(&identity, bytes);
+                }
+                return Ok(Response::new(Confirmation {
+                    status: ConfimrationStatus::Pending as i32,
+                    duplicate: false,
+                }));
+            }
+            Err(err) => return Err(err.into()),
+        };
+        // Released again unless the publish commits, so that failed publishes can be retried.
+        let reservation = match msg.attributes.get(IDEMPOTENCY_KEY_ATTRIBUTE) {
+            Some(key) => match self.deduplicator.reserve(&msg.topic, key)? {
+                Some(reservation) => Some(reservation),
+                None => {
+                    return Ok(Response::new(Confirmation {
+                        status: ConfimrationStatus::Committed as i32,
+                        duplicate: true,
+                    }))
+                }
+            },
+            None => None,
+        };
+        if let Err(err) = self
+            .schemas
+            .validate_as(&msg.topic, &msg.content_type, &msg.data)
+        {
+            return Err(crate::Error::from(err).into());
+        }
 
         msg.published = Some(Timestamp::from(SystemTime::now()));
+        msg.stamp_checksum();
+        // Encrypting first ensures claim checked payloads are also encrypted at rest.
+        if let Some(keyring) = &self.keyring {
+            keyring.seal(&mut msg);
+        }
+        let mut claim_check = None;
+        if let Some(store) = self
+            .claim_checks
+            .clone()
+            .filter(|store| store.exceeds(msg.data.len()))
+        {
+            let data = std::mem::take(&mut msg.data);
+            let key = tokio::task::spawn_blocking(move || store.put(&data))
+                .await
+                .map_err(|err| Status::internal(err.to_string()))?
+                .map_err(crate::Error::from)?;
+            msg.attributes
+                .insert(String::from(CLAIM_CHECK_ATTRIBUTE), key.clone());
+            msg.data = key.clone().into_bytes().into();
+            claim_check = self.claim_checks.clone().map(|store| (store, key));
+        }
 
-        match topic.push(msg) {
-            Ok(()) => Ok(Response::new(Confirmation {
-                status: ConfimrationStatus::Committed as i32,
-            })),
-            Err(err) => Err(Status::internal(format!(
-                "queue is full or otherwise invalid: {}",
-                err
-            ))),
+        let rules = topic.forward_rules();
+        let forwarded = match rules.is_empty() {
+            true => None,
+            false => Some(msg.clone()),
+        };
+        // The messages holding a claim checked payload are counted before any can be acked, so
+        // that the payload is deleted once the last of them is. Forwarding holds the payload
+        // until the forwarded copies are counted in turn.
+        let forwarding = forwarded.is_some() as usize;
+        let mut copies = 0;
+        let published = topic
+            .publish_with(msg, |count| {
+                copies = count + forwarding;
+                if let Some((store, key)) = &claim_check {
+                    store.track(key, copies);
+                }
+            })
+            .await;
+        if let Some((store, key)) = claim_check
+            .clone()
+            .filter(|_| published.is_err() || copies == 0)
+        {
+            let _ = tokio::task::spawn_blocking(move || store.delete(&key)).await;
+        }
+        match published {
+            Ok(()) => {
+                if let Some(reservation) = reservation {
+                    reservation.commit();
+                }
+                if let Some(forwarded) = forwarded {
+                    self.forward_published(forwarded, rules).await;
+                    if let Some((store, key)) = claim_check {
+                        let _ = tokio::task::spawn_blocking(move || store.release(&key)).await;
+                    }
+                }
+                self.tenants.published(&topic_name, bytes);
+                if let Some((metrics, identity)) = identity {
+                    metrics.published(&identity, bytes);
+                }
+                Ok(Response::new(Confirmation {
+                    status: ConfimrationStatus::Committed as i32,
+                    duplicate: false,
+                }))
+            }
+            Err(err) => Err(crate::Error::from(err).into()),
         }
     }
 
     async fn _ack(&self, request: Request<Lease>) -> Result<Response<Confirmation>, Status> {
+        self.identity(&request, "/pubsub.PubSubService/Ack");
+        if let Some((forward, mut client)) = self.forward(&request, &request.get_ref().topic)? {
+            let response = client.ack(forward.request(request)).await?;
+            return Ok(forward.response(response));
+        }
         let lease = request.into_inner();
 
         let topic = match self.topic_registry.get(&lease.topic) {
@@ -98,18 +684,21 @@ impl Handler {
             None => return sub_not_found(&lease.subscription, &lease.topic),
         };
 
-        match sub.queue.ack(lease.id, lease.index as usize) {
+        match self.ack_lease(&sub, &lease) {
             Ok(()) => Ok(Response::new(Confirmation {
                 status: ConfimrationStatus::Committed as i32,
+                duplicate: false,
             })),
-            Err(err) => Err(Status::internal(format!(
-                "queue is full or otherwise invalid: {}",
-                err
-            ))),
+            Err(err) => Err(crate::Error::from(err).into()),
         }
     }
 
     async fn _nack(&self, request: Request<Lease>) -> Result<Response<Confirmation>, Status> {
+        self.identity(&request, "/pubsub.PubSubService/Nack");
+        if let Some((forward, mut client)) = self.forward(&request, &request.get_ref().topic)? {
+            let response = client.nack(forward.request(request)).await?;
+            return Ok(forward.response(response));
+        }
         let lease = request.into_inner();
 
         let topic = match self.topic_registry.get(&lease.topic) {
@@ -121,22 +710,385 @@ impl Handler {
             None => return sub_not_found(&lease.subscription, &lease.topic),
         };
 
-        match sub.queue.nack(lease.id, lease.index as usize) {
+        match self.nack_lease(&sub, &lease) {
             Ok(()) => Ok(Response::new(Confirmation {
                 status: ConfimrationStatus::Committed as i32,
+                duplicate: false,
             })),
-            Err(err) => Err(Status::internal(format!(
-                "queue is full or otherwise invalid: {}",
-                err
+            Err(err) => Err(crate::Error::from(err).into()),
+        }
+    }
+
+    async fn _renew(&self, request: Request<Lease>) -> Result<Response<Lease>, Status> {
+        self.identity(&request, "/pubsub.PubSubService/Renew");
+        if let Some((forward, mut client)) = self.forward(&request, &request.get_ref().topic)? {
+            let response = client.renew(forward.request(request)).await?;
+            return Ok(forward.response(response));
+        }
+        let lease = request.into_inner();
+
+        let topic = match self.topic_registry.get(&lease.topic) {
+            Some(topic) => topic,
+            None => return topic_not_found(&lease.topic),
+        };
+        let sub = match topic.get(&lease.subscription) {
+            Some(sub) => sub,
+            None => return sub_not_found(&lease.subscription, &lease.topic),
+        };
+
+        match sub.queue.renew(lease.id, lease.index as usize) {
+            Ok(tag) => Ok(Response::new(Lease::from_tag(
+                tag,
+                lease.topic,
+                lease.subscription,
+                lease.index as usize,
             ))),
+            Err(err) => Err(crate::Error::from(err).into()),
+        }
+    }
+
+    async fn _settle(
+        &self,
+        request: Request<SettleRequest>,
+    ) -> Result<Response<SettleResponse>, Status> {
+        self.identity(&request, "/pubsub.PubSubService/Settle");
+        if let Some((forward, mut client)) = self.forward(&request, &request.get_ref().topic)? {
+            let response = client.settle(forward.request(request)).await?;
+            return Ok(forward.response(response));
+        }
+        let request = request.into_inner();
+
+        let topic = match self.topic_registry.get(&request.topic) {
+            Some(topic) => topic,
+            None => return topic_not_found(&request.topic),
+        };
+        let leases = request.acks.iter().chain(request.nacks.iter());
+        if leases.clone().any(|lease| lease.topic != request.topic) {
+            return invalid_argument("every lease must belong to the batch topic");
+        }
+
+        let acks = request.acks.iter().map(|lease| (lease, true));
+        let nacks = request.nacks.iter().map(|lease| (lease, false));
+        let failed = acks
+            .chain(nacks)
+            .filter(|(lease, ack)| {
+                let sub = match topic.get(&lease.subscription) {
+                    Some(sub) => sub,
+                    None => return true,
+                };
+                let res = match ack {
+                    true => self.ack_lease(&sub, lease),
+                    false => self.nack_lease(&sub, lease),
+                };
+                res.is_err()
+            })
+            .map(|(lease, _)| lease.id)
+            .collect();
+        Ok(Response::new(SettleResponse { failed }))
+    }
+
+    /// Return the dead-letter topic of the supplied topic, if it exists on this member.
+    fn dead_letter_topic(&self, topic: &str) -> Option<(String, Topic<Message>)> {
+        if self.dead_letter_topic.is_empty() {
+            return None;
+        }
+        let name = self.dead_letter_topic.replace("{topic}", topic);
+        match &self.membership {
+            Some(membership) if !membership.is_local(&name) => None,
+            _ => self.topic_registry.get(&name).map(|dead| (name, dead)),
+        }
+    }
+
+    /// Forward the supplied message, just published to its topic, along the supplied
+    /// forwarding rules of that topic and, in turn, those of each topic it is forwarded to.
+    /// Every topic receives the message at most once, so that rules looping back to a topic
+    /// are harmless. Forwarding is best effort, skipping topics which do not exist, are owned
+    /// by other members or refuse the message.
+    async fn forward_published(&self, msg: Message, rules: Vec<ForwardRule<Message>>) {
+        let mut visited = HashSet::from([msg.topic.clone()]);
+        let mut pending = VecDeque::from([(msg, rules)]);
+        while let Some((msg, rules)) = pending.pop_front() {
+            for rule in rules {
+                if visited.contains(rule.topic()) || !rule.selector().matches(&msg) {
+                    continue;
+                }
+                visited.insert(rule.topic().to_owned());
+                let destination = match &self.membership {
+                    Some(membership) if !membership.is_local(rule.topic()) => None,
+                    _ => self.topic_registry.get(rule.topic()),
+                };
+                let destination = match destination {
+                    Some(destination) => destination,
+                    None => continue,
+                };
+                let mut forwarded = msg.clone();
+                rule.rename(&mut forwarded.attributes);
+                if !rekey(self.keyring.as_ref(), &mut forwarded, rule.topic()) {
+                    continue;
+                }
+                let rules = destination.forward_rules();
+                let next = match rules.is_empty() {
+                    true => None,
+                    false => Some(forwarded.clone()),
+                };
+                let holding = self.holding(&forwarded);
+                let published = destination.publish_with(forwarded, holding).await;
+                if let (Ok(()), Some(next)) = (published, next) {
+                    pending.push_back((next, rules));
+                }
+            }
         }
     }
 
+    /// Nack the supplied lease of the supplied subscription. Messages nacked as permanent or
+    /// malformed are moved to the dead-letter topic of their topic, and are otherwise held
+    /// back for the delay the lease requests. Messages which can not be moved, as their topic
+    /// has no dead-letter topic, their data can not be re-encrypted for it or it refuses them,
+    /// are held back likewise, keeping their attempts and position.
+    fn nack_lease(&self, sub: &Sub<Message>, lease: &Lease) -> crate::pubsub::Result<()> {
+        let index = lease.index as usize;
+        let rejection = match lease.reason() {
+            NackReason::Transient => None,
+            NackReason::Permanent => Some(Rejection::Permanent),
+            NackReason::Malformed => Some(Rejection::Malformed),
+        };
+        let delay = Duration::from_millis(lease.delay_ms).min(MAX_NACK_DELAY);
+        let (rejection, (name, dead)) = match rejection.zip(self.dead_letter_topic(&lease.topic)) {
+            Some(dead_letter) => dead_letter,
+            None => return sub.queue.nack_with_delay(lease.id, index, delay),
+        };
+
+        // The message stays leased until it is dead-lettered, so that messages which can't be
+        // moved, or are refused by the dead-letter topic, are nacked in place rather than lost.
+        let msg = sub.queue.leased(lease.id, index)?;
+        let mut dead_msg = msg.clone();
+        if !rekey(self.keyring.as_ref(), &mut dead_msg, &name) {
+            return sub.queue.nack_with_delay(lease.id, index, delay);
+        }
+        let holding = self.holding(&dead_msg);
+        if let Err(err) = dead.push_with(dead_msg, holding) {
+            sub.queue.nack_with_delay(lease.id, index, delay)?;
+            return Err(err);
+        }
+        sub.queue.reject(lease.id, index, rejection)?;
+        // The dead-lettered copies now hold the claim checked payload in place of the message.
+        self.release_claim_check(&msg);
+        Ok(())
+    }
+
+    /// Return a function counting the supplied number of copies of the supplied message as
+    /// holding its claim checked payload, if any.
+    fn holding(&self, msg: &Message) -> impl FnOnce(usize) {
+        let key = msg.attributes.get(CLAIM_CHECK_ATTRIBUTE).cloned();
+        let claim_check = self.claim_checks.clone().zip(key);
+        move |count| {
+            if let Some((store, key)) = claim_check {
+                store.hold(&key, count);
+            }
+        }
+    }
+
+    /// Release the hold of the supplied message, just acked, on its claim checked payload, if
+    /// any, deleting the payload once no message holds it. The ack stands regardless, leaving
+    /// a payload which fails to be deleted to the retention of the store.
+    fn release_claim_check(&self, msg: &Message) {
+        let key = msg.attributes.get(CLAIM_CHECK_ATTRIBUTE);
+        if let Some((store, key)) = self.claim_checks.as_ref().zip(key) {
+            let _ = store.release(key);
+        }
+    }
+
+    /// Ack the supplied lease of the supplied subscription, releasing the hold of the acked
+    /// message on its claim checked payload, if any.
+    fn ack_lease(&self, sub: &Sub<Message>, lease: &Lease) -> crate::pubsub::Result<()> {
+        let key = sub.queue.ack_with(lease.id, lease.index as usize, |msg| {
+            msg.attributes.get(CLAIM_CHECK_ATTRIBUTE).cloned()
+        })?;
+        if let Some((store, key)) = self.claim_checks.as_ref().zip(key) {
+            let _ = store.release(&key);
+        }
+        Ok(())
+    }
+
+    async fn _redrive(
+        &self,
+        request: Request<RedriveRequest>,
+    ) -> Result<Response<RedriveResponse>, Status> {
+        self.identity(&request, "/pubsub.PubSubService/Redrive");
+        if let Some((forward, mut client)) = self.forward(&request, &request.get_ref().topic)? {
+            let response = client.redrive(forward.request(request)).await?;
+            return Ok(forward.response(response));
+        }
+        let mut request = request.into_inner();
+        if request.destination_topic.is_empty() {
+            request.destination_topic = request.topic.clone();
+        }
+        if request.destination_topic == request.topic
+            && request.destination_subscription == request.subscription
+        {
+            return invalid_argument("messages can not be moved to the subscription they are in.");
+        }
+        if let Some(membership) = &self.membership {
+            if !membership.is_local(&request.destination_topic) {
+                let owner = membership.owner(&request.destination_topic);
+                return not_owner(&request.destination_topic, owner);
+            }
+        }
+
+        let redrive = Redrive {
+            registry: self.topic_registry.clone(),
+            keyring: self.keyring.clone(),
+            claim_checks: self.claim_checks.clone(),
+            topic: request.topic,
+            subscription: request.subscription,
+            destination_topic: request.destination_topic,
+            destination_subscription: request.destination_subscription,
+        };
+        let resolved = redrive.resolve()?;
+        let sub = resolved.0.clone();
+        let in_progress = || Status::from(crate::Error::from(PubsubError::ReplayInProgress));
+
+        let max = match request.max {
+            0 => sub.queue.len(),
+            max => sub.queue.len().min(max as usize),
+        };
+        let rate = self.replay_messages_per_sec as usize;
+        if rate == 0 || max <= rate {
+            if sub.replay.is_active() {
+                return Err(in_progress());
+            }
+            let (moved, failed, res) = redrive.move_pending(&resolved, max);
+            res.map_err(crate::Error::from)?;
+            return Ok(Response::new(RedriveResponse {
+                moved: moved as u64,
+                failed: failed as u64,
+                replaying: 0,
+            }));
+        }
+
+        // Larger redrives move the first second's worth now, and replay the rest in the
+        // background so that the destination is not flooded.
+        let progress = Progress::new(
+            redrive.destination_topic.clone(),
+            redrive.destination_subscription.clone(),
+            max as u64,
+            rate as u64,
+        );
+        if !sub.replay.start(progress) {
+            return Err(in_progress());
+        }
+        let (moved, failed, res) = redrive.move_pending(&resolved, rate);
+        sub.replay.record(moved, failed);
+        if let Err(err) = res {
+            sub.replay.finish();
+            return Err(crate::Error::from(err).into());
+        }
+        let replaying = match moved + failed < rate {
+            true => {
+                sub.replay.finish();
+                0
+            }
+            false => {
+                tokio::spawn(redrive.replay(sub, max - rate, rate));
+                max - rate
+            }
+        };
+        Ok(Response::new(RedriveResponse {
+            moved: moved as u64,
+            failed: failed as u64,
+            replaying: replaying as u64,
+        }))
+    }
+
+    async fn _claim(
+        &self,
+        request: Request<ClaimRequest>,
+    ) -> Result<Response<ClaimResponse>, Status> {
+        self.identity(&request, "/pubsub.PubSubService/Claim");
+        if let Some((forward, mut client)) = self.forward(&request, &request.get_ref().topic)? {
+            let response = client.claim(forward.request(request)).await?;
+            return Ok(forward.response(response));
+        }
+        let request = request.into_inner();
+
+        let store = match &self.claim_checks {
+            Some(store) => store.clone(),
+            None => {
+                let err = claimcheck::Error::NotFound { key: request.key };
+                return Err(crate::Error::from(err).into());
+            }
+        };
+        let key = request.key;
+        let mut data = tokio::task::spawn_blocking(move || store.get(&key))
+            .await
+            .map_err(|err| Status::internal(err.to_string()))?
+            .map_err(crate::Error::from)?;
+        // Only new topics can be encrypted, so every payload of an encrypted topic is sealed.
+        if let Some(keyring) = self
+            .keyring
+            .as_ref()
+            .filter(|keyring| keyring.is_encrypted(&request.topic))
+        {
+            data = keyring
+                .decrypt(&request.topic, &data)
+                .map_err(crate::Error::from)?;
+        }
+        Ok(Response::new(ClaimResponse { data: data.into() }))
+    }
+
     async fn _subscribe(
         &self,
         request: Request<Subscription>,
     ) -> Result<Response<SubscribeStream>, Status> {
+        let identity = self.identity(&request, "/pubsub.PubSubService/Subscribe");
+        if self.shutdown.is_triggered() {
+            return Err(crate::Error::ShuttingDown.into());
+        }
+        let permit = self.acquire_stream(&request)?;
+        if let Some((forward, mut client)) = self.forward(&request, &request.get_ref().topic)? {
+            let subscription = request.get_ref().name.clone();
+            let response = client.subscribe(forward.request(request)).await?;
+            let response = forward.response(response);
+            let metadata = response.metadata().clone();
+            let inner = response.into_inner();
+            let mut response = Response::new(SubscribeStream {
+                source: Source::Forwarded(inner),
+                subscription,
+                identity,
+                // The owning member accounts the messages it streams.
+                tenants: None,
+                throttle: Throttle::default(),
+                throttled: None,
+                keyring: None,
+                // The owning member transcodes the messages it streams.
+                transcode: None,
+                dead_letter: None,
+                draining: Box::pin(self.shutdown.wait()),
+                drained: false,
+                // The owning member batches the messages it streams.
+                max_batch: 1,
+                max_wait: Duration::ZERO,
+                batch: None,
+                deadline: None,
+                failed: None,
+                _permit: permit,
+            });
+            *response.metadata_mut() = metadata;
+            return Ok(response);
+        }
+        let owner = caller(&request);
         let subscription = request.into_inner();
+        let start = match (subscription.start(), subscription.start_time.clone()) {
+            (StartPosition::Beginning, _) => None,
+            (StartPosition::End, _) => Some(SystemTime::now()),
+            (StartPosition::Time, Some(start_time)) => match SystemTime::try_from(start_time) {
+                Ok(start) => Some(start),
+                Err(_) => return invalid_argument("start time must be a valid timestamp."),
+            },
+            (StartPosition::Time, None) => {
+                return invalid_argument("start time must be set when starting from a time.")
+            }
+        };
 
         let topic = match self.topic_registry.get(&subscription.topic) {
             Some(topic) => topic,
@@ -147,12 +1099,204 @@ impl Handler {
             None => return sub_not_found(&subscription.name, &subscription.topic),
         };
 
+        let transcode = match self.schemas.get(&subscription.topic) {
+            Some(schema) if !subscription.content_type.is_empty() => {
+                if !schema.transcodes_to(&subscription.content_type) {
+                    return invalid_argument(&format!(
+                        "messages of the topic can not be delivered as '{}'.",
+                        subscription.content_type
+                    ));
+                }
+                Some((schema, subscription.content_type.clone()))
+            }
+            _ => None,
+        };
+
+        // Ordered subscriptions deliver nothing past a message until it is settled, so a
+        // stream skipping it would never deliver anything.
+        if start.is_some() && sub.queue.is_ordered() {
+            return invalid_argument(
+                "ordered subscriptions can only be consumed from the beginning.",
+            );
+        }
+
+        let mut source = Stream::from(sub.queue);
+        let owner = format!("{} stream {}", owner, source.id());
+        source = source.with_owner(owner);
+        // Messages published before the start are left for the other streams of the
+        // subscription, rather than acked, as they share its messages.
+        if let Some(start) = start {
+            source = source.with_filter(move |msg| !published_before(msg, start));
+        }
+        if subscription.ack_deadline_ms > 0 {
+            let ack_deadline = Duration::from_millis(subscription.ack_deadline_ms);
+            source = source.with_ttl(ack_deadline.min(MAX_ACK_DEADLINE));
+        }
         let stream = SubscribeStream {
-            inner: sub.queue.into(),
+            source: Source::Local(Box::new(source)),
             subscription: subscription.name,
-        };
+            identity,
+            tenants: Some(self.tenants.clone()),
+            throttle: sub.throttle,
+            throttled: None,
+            keyring: self.keyring.clone(),
+            transcode,
+            dead_letter: self.dead_letter_topic(&subscription.topic),
+            draining: Box::pin(self.shutdown.wait()),
+            drained: false,
+            max_batch: 1,
+            max_wait: Duration::ZERO,
+            batch: None,
+            deadline: None,
+            failed: None,
+            _permit: permit,
+        }
+        .with_batching(subscription.max_batch, subscription.max_wait_ms);
         Ok(Response::new(stream))
     }
+
+    async fn _peek(&self, request: Request<PeekRequest>) -> Result<Response<PeekStream>, Status> {
+        self.identity(&request, "/pubsub.PubSubService/Peek");
+        if let Some((forward, mut client)) = self.forward(&request, &request.get_ref().topic)? {
+            let response = client.peek(forward.request(request)).await?;
+            let response = forward.response(response);
+            let metadata = response.metadata().clone();
+            let mut inner = response.into_inner();
+            let mut peeked = Vec::new();
+            while let Some(msg) = inner.message().await? {
+                peeked.push(msg);
+            }
+            // Reverse the peeked messages so that popping off the stream yields the oldest first.
+            peeked.reverse();
+            let mut response = Response::new(PeekStream(peeked));
+            *response.metadata_mut() = metadata;
+            return Ok(response);
+        }
+        let request = request.into_inner();
+
+        let topic = match self.topic_registry.get(&request.topic) {
+            Some(topic) => topic,
+            None => return topic_not_found(&request.topic),
+        };
+        let sub = match topic.get(&request.subscription) {
+            Some(sub) => sub,
+            None => return sub_not_found(&request.subscription, &request.topic),
+        };
+
+        let max = match request.max {
+            0 => usize::MAX,
+            max => max as usize,
+        };
+        // Reverse the peeked messages so that popping off the stream yields the oldest first.
+        let peeked = sub
+            .queue
+            .peek(max)
+            .into_iter()
+            .rev()
+            .map(|(index, mut entry)| {
+                if let Some(keyring) = &self.keyring {
+                    keyring.open(&mut entry.value)?;
+                }
+                Ok(PeekedMessage {
+                    index: index as u64,
+                    attempts: entry.attempts,
+                    message: Some(entry.value),
+                })
+            })
+            .collect::<crate::encryption::Result<_>>()
+            .map_err(crate::Error::from)?;
+        Ok(Response::new(PeekStream(peeked)))
+    }
+
+    /// Return the supplied subscription of the supplied topic owned by this member.
+    fn local_sub(&self, topic: &str, subscription: &str) -> crate::Result<Sub<Message>> {
+        let found = self
+            .topic_registry
+            .get(topic)
+            .ok_or_else(|| crate::Error::TopicNotFound {
+                topic: topic.to_owned(),
+            })?;
+        found
+            .get(subscription)
+            .ok_or_else(|| crate::Error::SubscriptionNotFound {
+                subscription: subscription.to_owned(),
+                topic: topic.to_owned(),
+            })
+    }
+
+    async fn _quarantine(
+        &self,
+        request: Request<QuarantineRequest>,
+    ) -> Result<Response<QuarantineResponse>, Status> {
+        self.identity(&request, "/pubsub.PubSubService/Quarantine");
+        if let Some((forward, mut client)) = self.forward(&request, &request.get_ref().topic)? {
+            let response = client.quarantine(forward.request(request)).await?;
+            return Ok(forward.response(response));
+        }
+        let request = request.into_inner();
+        let sub = self.local_sub(&request.topic, &request.subscription)?;
+
+        let messages = sub
+            .quarantine
+            .list(request.max as usize)
+            .into_iter()
+            .map(|mut quarantined| {
+                if let Some(keyring) = &self.keyring {
+                    keyring.open(&mut quarantined.value)?;
+                }
+                Ok(QuarantinedMessage {
+                    attempts: quarantined.attempts,
+                    expired_by: quarantined
+                        .expired_by
+                        .iter()
+                        .map(|owner| owner.to_string())
+                        .collect(),
+                    quarantined: Some(Timestamp::from(quarantined.quarantined)),
+                    message: Some(quarantined.value),
+                })
+            })
+            .collect::<crate::encryption::Result<_>>()
+            .map_err(crate::Error::from)?;
+        Ok(Response::new(QuarantineResponse {
+            messages,
+            total: sub.quarantine.len() as u64,
+        }))
+    }
+
+    async fn _release(
+        &self,
+        request: Request<ReleaseRequest>,
+    ) -> Result<Response<ReleaseResponse>, Status> {
+        self.identity(&request, "/pubsub.PubSubService/Release");
+        if let Some((forward, mut client)) = self.forward(&request, &request.get_ref().topic)? {
+            let response = client.release(forward.request(request)).await?;
+            return Ok(forward.response(response));
+        }
+        let request = request.into_inner();
+        let sub = self.local_sub(&request.topic, &request.subscription)?;
+
+        let mut released = sub.quarantine.release(request.max as usize);
+        if request.discard {
+            for quarantined in &released {
+                self.release_claim_check(&quarantined.value);
+            }
+            return Ok(Response::new(ReleaseResponse {
+                released: released.len() as u64,
+                failed: 0,
+            }));
+        }
+        // Messages the subscription refuses, such as when it is full, remain quarantined.
+        // Returned messages keep their attempts and the owners whose leases of them expired.
+        let entries = released.iter().cloned().map(Entry::from);
+        let (pushed, _) = sub.queue.push_entries(entries);
+        let failed = released.split_off(pushed);
+        let response = ReleaseResponse {
+            released: pushed as u64,
+            failed: failed.len() as u64,
+        };
+        sub.quarantine.restore(failed);
+        Ok(Response::new(response))
+    }
 }
 
 impl Default for Handler {
@@ -181,6 +1325,51 @@ impl PubSubService for Handler {
         self._nack(request).await
     }
 
+    #[inline]
+    async fn renew(&self, request: Request<Lease>) -> Result<Response<Lease>, Status> {
+        self._renew(request).await
+    }
+
+    #[inline]
+    async fn settle(
+        &self,
+        request: Request<SettleRequest>,
+    ) -> Result<Response<SettleResponse>, Status> {
+        self._settle(request).await
+    }
+
+    #[inline]
+    async fn redrive(
+        &self,
+        request: Request<RedriveRequest>,
+    ) -> Result<Response<RedriveResponse>, Status> {
+        self._redrive(request).await
+    }
+
+    #[inline]
+    async fn quarantine(
+        &self,
+        request: Request<QuarantineRequest>,
+    ) -> Result<Response<QuarantineResponse>, Status> {
+        self._quarantine(request).await
+    }
+
+    #[inline]
+    async fn release(
+        &self,
+        request: Request<ReleaseRequest>,
+    ) -> Result<Response<ReleaseResponse>, Status> {
+        self._release(request).await
+    }
+
+    #[inline]
+    async fn claim(
+        &self,
+        request: Request<ClaimRequest>,
+    ) -> Result<Response<ClaimResponse>, Status> {
+        self._claim(request).await
+    }
+
     #[inline]
     async fn subscribe(
         &self,
@@ -188,6 +1377,16 @@ impl PubSubService for Handler {
     ) -> Result<Response<Self::SubscribeStream>, Status> {
         self._subscribe(request).await
     }
+
+    type PeekStream = PeekStream;
+
+    #[inline]
+    async fn peek(
+        &self,
+        request: Request<PeekRequest>,
+    ) -> Result<Response<Self::PeekStream>, Status> {
+        self._peek(request).await
+    }
 }
 
 #[cfg(test)]
@@ -197,7 +1396,10 @@ mod tests {
 
     use std::collections::HashMap;
 
-    use futures::Stream;
+    use bytes::Bytes;
+    use futures::{Stream, StreamExt};
+
+    use crate::grpc::forward::{FORWARDED_METADATA_KEY, SECRET_METADATA_KEY};
 
     macro_rules! aw {
         ($e:expr) => {
@@ -279,6 +1481,55 @@ mod tests {
         assert!(res.is_err());
     }
 
+    #[test]
+    fn test_settle() {
+        let handler = Handler::default();
+        let topic_name = String::from("woot");
+        let sub_name = String::from("sub");
+
+        let reg = handler.get_registry();
+        let topic = reg.create(topic_name.clone());
+        let sub = topic.create(sub_name.clone());
+        for data in [1, 2] {
+            topic
+                .push(Message {
+                    topic: topic_name.clone(),
+                    data: vec![data].into(),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        let mut leases = (0..2).map(|_| {
+            let (tag, index, _) = sub.queue.next().unwrap();
+            Lease::from_tag(tag, topic_name.clone(), sub_name.clone(), index)
+        });
+        let (first, second) = (leases.next().unwrap(), leases.next().unwrap());
+
+        let mut stale = first.clone();
+        stale.id += 1;
+        let req = SettleRequest {
+            topic: topic_name.clone(),
+            acks: vec![first, stale.clone()],
+            nacks: vec![second],
+        };
+        let res = aw!(handler.settle(Request::new(req))).unwrap().into_inner();
+        assert_eq!(res.failed, vec![stale.id]);
+
+        // Only the nacked message is redelivered.
+        let (_, _, msg) = sub.queue.next().unwrap();
+        assert_eq!(msg.data, vec![2]);
+        assert!(sub.queue.next().is_none());
+
+        let mut mixed = stale;
+        mixed.topic = String::from("nope");
+        let req = SettleRequest {
+            topic: topic_name,
+            acks: vec![mixed],
+            nacks: Vec::new(),
+        };
+        assert!(aw!(handler.settle(Request::new(req))).is_err());
+    }
+
     #[test]
     fn test_subscribe() {
         let handler = Handler::default();
@@ -292,8 +1543,10 @@ mod tests {
 
         let msg = Message {
             attributes: HashMap::new(),
-            data: vec![0x01],
+            data: vec![0x01].into(),
             published: None,
+            checksum: None,
+            content_type: String::new(),
             topic: topic_name.clone(),
         };
         let req = Request::new(msg);
@@ -305,8 +1558,10 @@ mod tests {
 
         let msg = Message {
             attributes: HashMap::new(),
-            data: vec![0x02],
+            data: vec![0x02].into(),
             published: None,
+            checksum: None,
+            content_type: String::new(),
             topic: topic_name.clone(),
         };
         let req = Request::new(msg);
@@ -319,6 +1574,7 @@ mod tests {
         let sub_req = Subscription {
             name: sub_name.clone(),
             topic: String::from("nope"),
+            ..Default::default()
         };
         let req = Request::new(sub_req);
         let stream = aw!(handler.subscribe(req));
@@ -327,6 +1583,7 @@ mod tests {
         let sub_req = Subscription {
             name: String::from("nope"),
             topic: topic_name.clone(),
+            ..Default::default()
         };
         let req = Request::new(sub_req);
         let stream = aw!(handler.subscribe(req));
@@ -335,6 +1592,7 @@ mod tests {
         let sub_req = Subscription {
             name: sub_name.clone(),
             topic: topic_name.clone(),
+            ..Default::default()
         };
         let req = Request::new(sub_req);
         let stream = aw!(handler.subscribe(req));
@@ -410,6 +1668,11 @@ mod tests {
         assert_eq!(msg.data.len(), 1);
         assert_eq!(msg.data[0], 0x02);
 
+        let req = Request::new(lease.clone());
+        let renewed = aw!(handler.renew(req)).unwrap().into_inner();
+        assert_eq!(renewed.id, lease.id);
+        assert_eq!(renewed.index, lease.index);
+
         let req = Request::new(lease);
         let res = aw!(handler.ack(req));
         assert!(res.is_ok());
@@ -421,4 +1684,1290 @@ mod tests {
         let actual = Pin::new(&mut stream).poll_next(&mut cx);
         assert!(matches!(actual, Poll::Pending));
     }
+
+    #[test]
+    fn test_subscribe_batch() {
+        let handler = Handler::default();
+        let topic = handler.get_registry().create(String::from("woot"));
+        topic.create(String::from("sub"));
+        for data in 0..3u8 {
+            let msg = Message {
+                topic: String::from("woot"),
+                data: vec![data].into(),
+                ..Default::default()
+            };
+            assert!(aw!(handler.publish(Request::new(msg))).is_ok());
+        }
+
+        let sub_req = Subscription {
+            name: String::from("sub"),
+            topic: String::from("woot"),
+            max_batch: 2,
+            max_wait_ms: 0,
+            ..Default::default()
+        };
+        let mut stream = aw!(handler.subscribe(Request::new(sub_req)))
+            .unwrap()
+            .into_inner();
+        let waker = futures::task::noop_waker();
+        let mut cx = std::task::Context::from_waker(&waker);
+
+        // Full batches are streamed as one response, and partial batches are streamed as soon
+        // as nothing else is available without a wait.
+        let mut batches = Vec::new();
+        while let Poll::Ready(Some(Ok(leased))) = Pin::new(&mut stream).poll_next(&mut cx) {
+            batches.push(leased.unbatch().collect::<Vec<_>>());
+        }
+        let sizes = batches.iter().map(Vec::len).collect::<Vec<_>>();
+        assert_eq!(sizes, vec![2, 1]);
+        let data = batches
+            .iter()
+            .flatten()
+            .map(|leased| leased.message.as_ref().unwrap().data[0])
+            .collect::<Vec<_>>();
+        assert_eq!(data, vec![0, 1, 2]);
+
+        // Partial batches wait for further messages up to the maximum wait.
+        let msg = Message {
+            topic: String::from("woot"),
+            data: vec![3].into(),
+            ..Default::default()
+        };
+        assert!(aw!(handler.publish(Request::new(msg))).is_ok());
+        let sub_req = Subscription {
+            name: String::from("sub"),
+            topic: String::from("woot"),
+            max_batch: 10,
+            max_wait_ms: 20,
+            ..Default::default()
+        };
+        let leased = aw!(async {
+            let mut stream = handler
+                .subscribe(Request::new(sub_req))
+                .await
+                .unwrap()
+                .into_inner();
+            stream.next().await
+        });
+        let leased = leased.unwrap().unwrap();
+        assert_eq!(leased.unbatch().count(), 1);
+    }
+
+    #[test]
+    fn test_subscribe_ack_deadline() {
+        let handler = Handler::default();
+        let topic = handler.get_registry().create(String::from("woot"));
+        topic.create(String::from("sub"));
+        for data in 0..2u8 {
+            let msg = Message {
+                topic: String::from("woot"),
+                data: vec![data].into(),
+                ..Default::default()
+            };
+            assert!(aw!(handler.publish(Request::new(msg))).is_ok());
+        }
+
+        let waker = futures::task::noop_waker();
+        let mut cx = std::task::Context::from_waker(&waker);
+        let mut lease = |ack_deadline_ms| {
+            let sub_req = Subscription {
+                name: String::from("sub"),
+                topic: String::from("woot"),
+                ack_deadline_ms,
+                ..Default::default()
+            };
+            let mut stream = aw!(handler.subscribe(Request::new(sub_req)))
+                .unwrap()
+                .into_inner();
+            // The stream is returned alongside the lease, as dropping it nacks the lease.
+            match Pin::new(&mut stream).poll_next(&mut cx) {
+                Poll::Ready(Some(Ok(leased))) => (stream, leased.lease.unwrap()),
+                _ => panic!("expected a leased message"),
+            }
+        };
+
+        // Leases last for the requested deadline, capped at the maximum.
+        let (_first, leased) = lease(1000);
+        assert_eq!(leased.ttl_ms, 1000);
+        let (_second, leased) = lease(u64::MAX);
+        assert_eq!(leased.ttl_ms, MAX_ACK_DEADLINE.as_millis() as u64);
+
+        // Renewals keep the deadline of the stream.
+        let renewed = aw!(handler.renew(Request::new(leased))).unwrap();
+        assert_eq!(
+            renewed.get_ref().ttl_ms,
+            MAX_ACK_DEADLINE.as_millis() as u64
+        );
+    }
+
+    #[test]
+    fn test_subscribe_start() {
+        let handler = Handler::default();
+        let topic = handler.get_registry().create(String::from("woot"));
+        let sub = topic.create(String::from("sub"));
+        let publish = |data: u8| {
+            let msg = Message {
+                topic: String::from("woot"),
+                data: vec![data].into(),
+                ..Default::default()
+            };
+            assert!(aw!(handler.publish(Request::new(msg))).is_ok());
+            std::thread::sleep(Duration::from_millis(2));
+        };
+        let subscribe = |start: StartPosition, start_time: Option<SystemTime>| {
+            let sub_req = Subscription {
+                name: String::from("sub"),
+                topic: String::from("woot"),
+                start: start as i32,
+                start_time: start_time.map(Into::into),
+                ..Default::default()
+            };
+            aw!(handler.subscribe(Request::new(sub_req)))
+        };
+        let waker = futures::task::noop_waker();
+        let mut cx = std::task::Context::from_waker(&waker);
+        let mut next = |stream: &mut SubscribeStream| match Pin::new(stream).poll_next(&mut cx) {
+            Poll::Ready(Some(Ok(leased))) => Some(leased.message.unwrap().data[0]),
+            _ => None,
+        };
+
+        let status = subscribe(StartPosition::Time, None).err().unwrap();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        // Messages published before the start are skipped, but left for other streams.
+        publish(0);
+        let start = SystemTime::now();
+        publish(1);
+        let mut stream = subscribe(StartPosition::Time, Some(start))
+            .unwrap()
+            .into_inner();
+        assert_eq!(next(&mut stream), Some(1));
+        assert_eq!(next(&mut stream), None);
+        assert_eq!(sub.queue.len(), 2);
+
+        publish(2);
+        let mut stream = subscribe(StartPosition::End, None).unwrap().into_inner();
+        assert_eq!(next(&mut stream), None);
+        publish(3);
+        assert_eq!(next(&mut stream), Some(3));
+        let mut stream = subscribe(StartPosition::Beginning, None)
+            .unwrap()
+            .into_inner();
+        assert_eq!(next(&mut stream), Some(0));
+
+        sub.queue.set_ordered(true);
+        let status = subscribe(StartPosition::End, None).err().unwrap();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[test]
+    fn test_peek() {
+        let handler = Handler::default();
+
+        let topic_name = String::from("woot");
+        let sub_name = String::from("sub");
+
+        let reg = handler.get_registry();
+        let topic = reg.create(topic_name.clone());
+        topic.create(sub_name.clone());
+
+        for data in [0x01, 0x02, 0x03] {
+            let msg = Message {
+                attributes: HashMap::new(),
+                data: vec![data].into(),
+                published: None,
+                checksum: None,
+                content_type: String::new(),
+                topic: topic_name.clone(),
+            };
+            let res = aw!(handler.publish(Request::new(msg)));
+            assert!(res.is_ok());
+        }
+
+        let req = PeekRequest {
+            topic: String::from("nope"),
+            subscription: sub_name.clone(),
+            max: 0,
+        };
+        let res = aw!(handler.peek(Request::new(req)));
+        assert!(res.is_err());
+
+        let req = PeekRequest {
+            topic: topic_name.clone(),
+            subscription: String::from("nope"),
+            max: 0,
+        };
+        let res = aw!(handler.peek(Request::new(req)));
+        assert!(res.is_err());
+
+        let req = PeekRequest {
+            topic: topic_name.clone(),
+            subscription: sub_name.clone(),
+            max: 2,
+        };
+        let res = aw!(handler.peek(Request::new(req)));
+        assert!(res.is_ok());
+        let mut stream = res.unwrap().into_inner();
+
+        let waker = futures::task::noop_waker();
+        let mut cx = std::task::Context::from_waker(&waker);
+
+        for expected in [0x01, 0x02] {
+            let actual = match Pin::new(&mut stream).poll_next(&mut cx) {
+                Poll::Ready(Some(Ok(actual))) => actual,
+                _ => unimplemented!(),
+            };
+            assert_eq!(actual.attempts, 0);
+            assert_eq!(actual.message.unwrap().data[0], expected);
+        }
+        let actual = Pin::new(&mut stream).poll_next(&mut cx);
+        assert!(matches!(actual, Poll::Ready(None)));
+
+        // Peeking must leave every message available for leasing.
+        let req = PeekRequest {
+            topic: topic_name.clone(),
+            subscription: sub_name.clone(),
+            max: 0,
+        };
+        let res = aw!(handler.peek(Request::new(req)));
+        assert_eq!(res.unwrap().into_inner().0.len(), 3);
+    }
+
+    #[test]
+    fn test_identity_metrics() {
+        let mm = crate::metric::Manager::new(
+            String::from("test"),
+            String::from("handler_identity"),
+            String::from("test"),
+        );
+        let handler =
+            Handler::default().with_identity_metrics(IdentityMetrics::new(&mm, 10).unwrap());
+
+        let topic_name = String::from("woot");
+        let sub_name = String::from("sub");
+
+        let reg = handler.get_registry();
+        let topic = reg.create(topic_name.clone());
+        topic.create(sub_name.clone());
+
+        let msg = Message {
+            attributes: HashMap::new(),
+            data: vec![0x01, 0x02].into(),
+            published: None,
+            checksum: None,
+            content_type: String::new(),
+            topic: topic_name.clone(),
+        };
+        let mut req = Request::new(msg);
+        req.extensions_mut().insert(IdentityExt {
+            identity: String::from("alice"),
+        });
+        assert!(aw!(handler.publish(req)).is_ok());
+
+        let sub_req = Subscription {
+            name: sub_name.clone(),
+            topic: topic_name.clone(),
+            ..Default::default()
+        };
+        let mut req = Request::new(sub_req);
+        req.extensions_mut().insert(IdentityExt {
+            identity: String::from("bob"),
+        });
+        let mut stream = aw!(handler.subscribe(req)).unwrap().into_inner();
+
+        let waker = futures::task::noop_waker();
+        let mut cx = std::task::Context::from_waker(&waker);
+        let actual = Pin::new(&mut stream).poll_next(&mut cx);
+        assert!(matches!(actual, Poll::Ready(Some(Ok(_)))));
+
+        let value = |name: &str, identity: &str| {
+            prometheus::gather()
+                .into_iter()
+                .find(|family| family.get_name() == name)
+                .and_then(|family| {
+                    family
+                        .get_metric()
+                        .iter()
+                        .find(|metric| {
+                            metric
+                                .get_label()
+                                .iter()
+                                .any(|label| label.get_value() == identity)
+                        })
+                        .map(|metric| metric.get_counter().get_value())
+                })
+        };
+        assert_eq!(
+            value(
+                "test_handler_identity_identity_published_bytes_total",
+                "alice"
+            ),
+            Some(2.0)
+        );
+        assert_eq!(
+            value("test_handler_identity_identity_consumed_bytes_total", "bob"),
+            Some(2.0)
+        );
+    }
+
+    #[test]
+    fn test_tenant_quotas() {
+        use crate::tenant::{Quotas, Tenants};
+
+        let quotas =
+            Quotas::parse(br#"{"namespaces": {"billing": {"published_messages_per_sec": 1}}}"#)
+                .unwrap();
+        let tenants = Tenants::with_quotas(quotas);
+        let handler = Handler::default().with_tenants(tenants.clone());
+        let topic = handler
+            .get_registry()
+            .create(String::from("billing/invoices"));
+        topic.create(String::from("sub"));
+
+        let msg = || Message {
+            data: vec![0x01, 0x02].into(),
+            topic: String::from("billing/invoices"),
+            ..Default::default()
+        };
+        assert!(aw!(handler.publish(Request::new(msg()))).is_ok());
+        let status = aw!(handler.publish(Request::new(msg()))).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        assert_eq!(
+            crate::error::Code::from_status(&status),
+            Some(crate::error::Code::QuotaExceeded)
+        );
+
+        let sub_req = Subscription {
+            name: String::from("sub"),
+            topic: String::from("billing/invoices"),
+            ..Default::default()
+        };
+        let mut stream = aw!(handler.subscribe(Request::new(sub_req)))
+            .unwrap()
+            .into_inner();
+        let waker = futures::task::noop_waker();
+        let mut cx = std::task::Context::from_waker(&waker);
+        let actual = Pin::new(&mut stream).poll_next(&mut cx);
+        assert!(matches!(actual, Poll::Ready(Some(Ok(_)))));
+
+        let usage = tenants.usage("billing");
+        assert_eq!(usage.published_messages, 1);
+        assert_eq!(usage.published_bytes, 2);
+        assert_eq!(usage.consumed_messages, 1);
+        assert_eq!(usage.rejected, 1);
+    }
+
+    fn event_schema() -> schema::Schema {
+        use prost::Message as _;
+        use prost_types::field_descriptor_proto::Type;
+        use prost_types::{
+            DescriptorProto, FieldDescriptorProto, FileDescriptorProto, FileDescriptorSet,
+        };
+
+        let set = FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some(String::from("test.proto")),
+                package: Some(String::from("test")),
+                message_type: vec![DescriptorProto {
+                    name: Some(String::from("Event")),
+                    field: vec![FieldDescriptorProto {
+                        name: Some(String::from("name")),
+                        number: Some(1),
+                        r#type: Some(Type::String as i32),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+        let schema =
+            schema::ProtobufSchema::new(set.encode_to_vec(), String::from("test.Event")).unwrap();
+        schema::Schema::Protobuf(schema)
+    }
+
+    #[test]
+    fn test_schema_violation() {
+        let schemas = schema::Registry::default();
+        schemas.bind(String::from("woot"), event_schema());
+        let handler = Handler::default().with_schemas(schemas);
+
+        let topic = handler.get_registry().create(String::from("woot"));
+        topic.create(String::from("sub"));
+
+        let msg = |data| Message {
+            attributes: HashMap::new(),
+            data: Bytes::from(data),
+            published: None,
+            checksum: None,
+            content_type: String::new(),
+            topic: String::from("woot"),
+        };
+        assert!(aw!(handler.publish(Request::new(msg(vec![0x0a, 0x01, b'a'])))).is_ok());
+
+        let status = aw!(handler.publish(Request::new(msg(vec![0x08, 0x01])))).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert_eq!(
+            crate::error::Code::from_status(&status),
+            Some(crate::error::Code::SchemaViolation)
+        );
+        assert_eq!(
+            status
+                .metadata()
+                .get(crate::error::SCHEMA_PATH_METADATA_KEY)
+                .unwrap(),
+            "name"
+        );
+    }
+
+    #[test]
+    fn test_attribute_limits() {
+        let handler = Handler::default().with_attribute_limits(AttributeLimits {
+            count: 1,
+            key_bytes: 0,
+            value_bytes: 4,
+        });
+
+        let topic = handler.get_registry().create(String::from("woot"));
+        topic.create(String::from("sub"));
+
+        let msg = |attributes: &[(&str, &str)]| Message {
+            attributes: attributes
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            data: Bytes::from("woot"),
+            published: None,
+            checksum: None,
+            content_type: String::new(),
+            topic: String::from("woot"),
+        };
+        assert!(aw!(handler.publish(Request::new(msg(&[("kind", "a")])))).is_ok());
+
+        let status = aw!(handler.publish(Request::new(msg(&[("kind", "order")])))).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert_eq!(
+            crate::error::Code::from_status(&status),
+            Some(crate::error::Code::InvalidArgument)
+        );
+        assert_eq!(
+            status
+                .metadata()
+                .get(crate::error::ATTRIBUTE_METADATA_KEY)
+                .unwrap(),
+            "kind"
+        );
+
+        let status =
+            aw!(handler.publish(Request::new(msg(&[("a", "1"), ("b", "2")])))).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert!(status
+            .metadata()
+            .get(crate::error::ATTRIBUTE_METADATA_KEY)
+            .is_none());
+    }
+
+    #[test]
+    fn test_publish_chunks() {
+        let handler = Handler::default();
+        let sub = handler
+            .get_registry()
+            .create(String::from("woot"))
+            .create(String::from("sub"));
+
+        // Only the chunk completing the message confirms it as committed.
+        let msg = Message {
+            topic: String::from("woot"),
+            data: Bytes::from_static(b"hello world"),
+            ..Default::default()
+        };
+        let chunks = super::super::split(msg, 4);
+        let last = chunks.len() - 1;
+        for (index, chunk) in chunks.into_iter().enumerate() {
+            let res = aw!(handler.publish(Request::new(chunk)))
+                .unwrap()
+                .into_inner();
+            let status = match index == last {
+                true => ConfimrationStatus::Committed,
+                false => ConfimrationStatus::Pending,
+            };
+            assert_eq!(res.status, status as i32);
+        }
+        assert_eq!(sub.queue.next().unwrap().2.data, &b"hello world"[..]);
+    }
+
+    #[test]
+    fn test_transcode() {
+        let schemas = schema::Registry::default();
+        schemas.bind(String::from("woot"), event_schema());
+        let handler = Handler::default().with_schemas(schemas);
+
+        let topic = handler.get_registry().create(String::from("woot"));
+        for name in ["json", "proto", "raw"] {
+            topic.create(String::from(name));
+        }
+
+        let msg = |data: &[u8], content_type: &str| Message {
+            data: Bytes::copy_from_slice(data),
+            content_type: content_type.to_owned(),
+            topic: String::from("woot"),
+            ..Default::default()
+        };
+        let proto = [0x0a, 0x01, b'a'];
+        let json = br#"{"name":"a"}"#;
+        assert!(aw!(handler.publish(Request::new(msg(&proto, "")))).is_ok());
+        let published = msg(json, schema::JSON_CONTENT_TYPE);
+        assert!(aw!(handler.publish(Request::new(published))).is_ok());
+
+        // JSON documents must transcode into a valid message, and other content types are
+        // rejected outright.
+        let invalid = msg(br#"{"name":1}"#, schema::JSON_CONTENT_TYPE);
+        let status = aw!(handler.publish(Request::new(invalid))).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        let unsupported = msg(b"a", "text/plain");
+        let status = aw!(handler.publish(Request::new(unsupported))).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        let sub_req = Subscription {
+            name: String::from("raw"),
+            topic: String::from("woot"),
+            content_type: String::from("text/plain"),
+            ..Default::default()
+        };
+        match aw!(handler.subscribe(Request::new(sub_req))) {
+            Err(status) => assert_eq!(status.code(), tonic::Code::InvalidArgument),
+            Ok(_) => panic!("expected an unsupported content type to be rejected"),
+        }
+
+        let waker = futures::task::noop_waker();
+        let mut cx = std::task::Context::from_waker(&waker);
+        let cases = [
+            ("json", schema::JSON_CONTENT_TYPE, [&json[..], json]),
+            ("proto", schema::PROTOBUF_CONTENT_TYPE, [&proto, &proto]),
+            ("raw", "", [&proto, json]),
+        ];
+        for (name, content_type, expected) in cases {
+            let sub_req = Subscription {
+                name: String::from(name),
+                topic: String::from("woot"),
+                content_type: String::from(content_type),
+                ..Default::default()
+            };
+            let mut stream = aw!(handler.subscribe(Request::new(sub_req)))
+                .unwrap()
+                .into_inner();
+            for data in expected {
+                let msg = match Pin::new(&mut stream).poll_next(&mut cx) {
+                    Poll::Ready(Some(Ok(leased))) => leased.message.unwrap(),
+                    other => panic!("expected a message, got {:?}", other),
+                };
+                assert_eq!(msg.data, data, "{}", name);
+                assert!(msg.verify_checksum());
+            }
+        }
+    }
+
+    #[test]
+    fn test_undeliverable() {
+        let schemas = schema::Registry::default();
+        let handler = Handler::default().with_schemas(schemas.clone());
+        let registry = handler.get_registry();
+        for name in ["woot", "plain"] {
+            registry
+                .create(String::from(name))
+                .create(String::from("json"));
+        }
+        let dead = registry
+            .create(String::from("woot.dead-letter"))
+            .create(String::from("dead"));
+
+        // Messages published before the schema was bound can not be transcoded.
+        let msg = |topic: &str, data: &[u8]| Message {
+            data: Bytes::copy_from_slice(data),
+            topic: topic.to_owned(),
+            ..Default::default()
+        };
+        for topic in ["woot", "plain"] {
+            assert!(aw!(handler.publish(Request::new(msg(topic, &[0xff])))).is_ok());
+            schemas.bind(topic.to_owned(), event_schema());
+            assert!(aw!(handler.publish(Request::new(msg(topic, &[0x0a, 0x01, b'a'])))).is_ok());
+        }
+
+        // Undeliverable messages are dead-lettered, or held back without a dead-letter topic,
+        // and the stream carries on with the messages after them.
+        let waker = futures::task::noop_waker();
+        let mut cx = std::task::Context::from_waker(&waker);
+        for topic in ["woot", "plain"] {
+            let sub_req = Subscription {
+                name: String::from("json"),
+                topic: topic.to_owned(),
+                content_type: String::from(schema::JSON_CONTENT_TYPE),
+                ..Default::default()
+            };
+            let mut stream = aw!(handler.subscribe(Request::new(sub_req)))
+                .unwrap()
+                .into_inner();
+            match Pin::new(&mut stream).poll_next(&mut cx) {
+                Poll::Ready(Some(Ok(leased))) => {
+                    assert_eq!(leased.message.unwrap().data, &br#"{"name":"a"}"#[..])
+                }
+                other => panic!("expected a message, got {:?}", other),
+            }
+        }
+        assert_eq!(dead.queue.next().unwrap().2.data, &[0xff][..]);
+        let sub = registry.get("plain").unwrap().get("json").unwrap();
+        // Only the message delivered on the dropped stream is redelivered straight away.
+        assert_eq!(sub.queue.len(), 2);
+        assert_eq!(sub.queue.next().unwrap().2.data, &[0x0a, 0x01, b'a'][..]);
+        assert!(sub.queue.next().is_none());
+    }
+
+    #[test]
+    fn test_forward() {
+        use crate::filter::Filter;
+        use crate::pubsub::Selector;
+
+        let handler = Handler::default();
+        let rule = |topic: &str, filter: &str, renames: &[(&str, &str)]| {
+            let selector = Selector::new(Filter::parse(filter).unwrap(), |msg: &Message| {
+                &msg.attributes
+            });
+            let renames = renames
+                .iter()
+                .map(|(from, to)| (String::from(*from), String::from(*to)))
+                .collect();
+            ForwardRule::new(String::from(topic), selector, renames)
+        };
+        let orders = handler.get_registry().create(String::from("orders"));
+        let orders_sub = orders.create(String::from("sub"));
+        let audit = handler.get_registry().create(String::from("audit"));
+        let audit_sub = audit.create(String::from("sub"));
+        orders.set_forward_rules(vec![
+            rule(
+                "audit",
+                "attributes.region == 'eu'",
+                &[("region", "origin")],
+            ),
+            rule("missing", "true", &[]),
+        ]);
+        // Rules looping back to the original topic never deliver the message twice.
+        audit.set_forward_rules(vec![rule("orders", "true", &[])]);
+
+        for region in ["eu", "us"] {
+            let msg = Message {
+                topic: String::from("orders"),
+                data: region.as_bytes().to_vec().into(),
+                attributes: [(String::from("region"), String::from(region))]
+                    .into_iter()
+                    .collect(),
+                ..Default::default()
+            };
+            aw!(handler.publish(Request::new(msg))).unwrap();
+        }
+        assert_eq!((orders_sub.queue.len(), audit_sub.queue.len()), (2, 1));
+        let (_, _, msg) = audit_sub.queue.next().unwrap();
+        assert_eq!(msg.topic, "audit");
+        assert_eq!(msg.data, &b"eu"[..]);
+        assert_eq!(msg.attributes.get("origin").map(String::as_str), Some("eu"));
+        assert!(!msg.attributes.contains_key("region"));
+    }
+
+    #[test]
+    fn test_quarantine() {
+        let handler = Handler::default();
+        let topic = handler.get_registry().create(String::from("woot"));
+        let sub = topic.create(String::from("sub"));
+        for data in 0..2u8 {
+            sub.queue
+                .push(Message {
+                    topic: String::from("woot"),
+                    data: vec![data].into(),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        for owner in ["first", "second"] {
+            let owner = Arc::from(owner);
+            while sub.queue.next_for(Duration::ZERO, Some(&owner)).is_some() {}
+            handler.get_registry().expire_leases(2);
+        }
+        assert!(sub.queue.is_empty());
+
+        let quarantine = |subscription: &str, max| {
+            let req = QuarantineRequest {
+                topic: String::from("woot"),
+                subscription: String::from(subscription),
+                max,
+            };
+            aw!(handler.quarantine(Request::new(req))).map(Response::into_inner)
+        };
+        let release = |max, discard| {
+            let req = ReleaseRequest {
+                topic: String::from("woot"),
+                subscription: String::from("sub"),
+                max,
+                discard,
+            };
+            aw!(handler.release(Request::new(req)))
+                .unwrap()
+                .into_inner()
+        };
+
+        let status = quarantine("nope", 0).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+        let res = quarantine("sub", 1).unwrap();
+        assert_eq!((res.messages.len(), res.total), (1, 2));
+        let quarantined = &res.messages[0];
+        assert_eq!(quarantined.attempts, 2);
+        assert_eq!(quarantined.expired_by, vec!["first", "second"]);
+        assert_eq!(quarantined.message.as_ref().unwrap().data, &[0u8][..]);
+
+        // Released messages are delivered again, unless discarded, keeping their history so
+        // that they are quarantined again once another lease of them expires.
+        let res = release(1, false);
+        assert_eq!((res.released, res.failed), (1, 0));
+        let (_, entry) = sub.queue.peek(1).remove(0);
+        assert_eq!(entry.attempts, 2);
+        assert_eq!(entry.expired_by.len(), 2);
+        let (_, _, msg) = sub
+            .queue
+            .next_for(Duration::ZERO, Some(&Arc::from("third")))
+            .unwrap();
+        assert_eq!(msg.data, &[0u8][..]);
+        handler.get_registry().expire_leases(2);
+        assert!(sub.queue.is_empty());
+        let res = release(0, true);
+        assert_eq!((res.released, res.failed), (2, 0));
+        assert!(sub.quarantine.is_empty());
+    }
+
+    #[test]
+    fn test_redrive() {
+        let handler = Handler::default();
+        let topic = handler.get_registry().create(String::from("woot"));
+        let dlq = topic.create(String::from("dlq"));
+        let sub = topic.create(String::from("sub"));
+        let other = handler.get_registry().create(String::from("other"));
+        let other_sub = other.create(String::from("sub"));
+        for data in 0..3u8 {
+            dlq.queue
+                .push(Message {
+                    topic: String::from("woot"),
+                    data: vec![data].into(),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        let redrive = |max, destination_topic: &str, destination_subscription: &str| {
+            let req = RedriveRequest {
+                topic: String::from("woot"),
+                subscription: String::from("dlq"),
+                max,
+                destination_topic: String::from(destination_topic),
+                destination_subscription: String::from(destination_subscription),
+            };
+            aw!(handler.redrive(Request::new(req))).map(Response::into_inner)
+        };
+
+        let status = redrive(0, "", "dlq").unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        let status = redrive(0, "other", "nope").unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+
+        let res = redrive(1, "", "sub").unwrap();
+        assert_eq!((res.moved, res.failed), (1, 0));
+        assert_eq!((dlq.queue.len(), sub.queue.len()), (2, 1));
+
+        // Messages moved to another topic are published to it.
+        let res = redrive(0, "other", "").unwrap();
+        assert_eq!((res.moved, res.failed), (2, 0));
+        assert!(dlq.queue.is_empty());
+        let (_, _, msg) = other_sub.queue.next().unwrap();
+        assert_eq!(msg.topic, "other");
+        assert_eq!(msg.data, &[1u8][..]);
+    }
+
+    #[test]
+    fn test_redrive_topic_refused() {
+        let limits = crate::pubsub::Limits {
+            pending_per_subscription: 3,
+            ..Default::default()
+        };
+        let registry = Registry::default().with_limits(limits, |msg: &Message| msg.data.len());
+        let handler = Handler::with_registry(registry.clone());
+        let topic = registry.create(String::from("woot"));
+        let dlq = topic.create(String::from("dlq"));
+        let other = registry.create(String::from("other"));
+        let full = other.create(String::from("full"));
+        let roomy = other.create(String::from("roomy"));
+        let msg = |data: u8| Message {
+            topic: String::from("woot"),
+            data: vec![data].into(),
+            ..Default::default()
+        };
+        for data in 0..3u8 {
+            dlq.queue.push(msg(data)).unwrap();
+        }
+        for data in 0..2u8 {
+            full.queue.push(msg(data)).unwrap();
+        }
+
+        // Only the message every subscription has room for is moved, and the rest are left in
+        // none of them.
+        let req = RedriveRequest {
+            topic: String::from("woot"),
+            subscription: String::from("dlq"),
+            max: 3,
+            destination_topic: String::from("other"),
+            destination_subscription: String::new(),
+        };
+        let res = aw!(handler.redrive(Request::new(req)))
+            .unwrap()
+            .into_inner();
+        assert_eq!((res.moved, res.failed), (1, 2));
+        assert_eq!(
+            (dlq.queue.len(), full.queue.len(), roomy.queue.len()),
+            (2, 3, 1)
+        );
+    }
+
+    #[test]
+    fn test_redrive_ordered() {
+        use crate::encryption::{LocalKey, ENCRYPTION_ATTRIBUTE};
+
+        let keyring =
+            Keyring::with_master_key(std::sync::Arc::new(LocalKey::new(&[1; 32]).unwrap()), None);
+        let handler = Handler::default().with_keyring(keyring);
+        let topic = handler.get_registry().create(String::from("woot"));
+        let dlq = topic.create(String::from("dlq"));
+        dlq.queue.set_ordered(true);
+        let other = handler.get_registry().create(String::from("other"));
+        let other_sub = other.create(String::from("sub"));
+        // Encrypted claim checked payloads can't be moved to another topic.
+        let mut attributes = HashMap::new();
+        attributes.insert(String::from(CLAIM_CHECK_ATTRIBUTE), String::from("key"));
+        attributes.insert(String::from(ENCRYPTION_ATTRIBUTE), String::from("key"));
+        dlq.queue
+            .push(Message {
+                topic: String::from("woot"),
+                attributes,
+                ..Default::default()
+            })
+            .unwrap();
+        for data in 0..3u8 {
+            dlq.queue
+                .push(Message {
+                    topic: String::from("woot"),
+                    data: vec![data].into(),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+
+        // Every message is moved out of an ordered subscription, and the one which can't be is
+        // only counted once.
+        let req = RedriveRequest {
+            topic: String::from("woot"),
+            subscription: String::from("dlq"),
+            max: 10,
+            destination_topic: String::from("other"),
+            destination_subscription: String::new(),
+        };
+        let res = aw!(handler.redrive(Request::new(req)))
+            .unwrap()
+            .into_inner();
+        assert_eq!((res.moved, res.failed), (3, 1));
+        assert_eq!((dlq.queue.len(), other_sub.queue.len()), (1, 3));
+        assert_eq!(dlq.queue.outstanding(), 0);
+    }
+
+    #[test]
+    fn test_redrive_replay() {
+        let handler = Handler::default().with_replay_rate(2);
+        let topic = handler.get_registry().create(String::from("woot"));
+        let dlq = topic.create(String::from("dlq"));
+        let sub = topic.create(String::from("sub"));
+        for data in 0..3u8 {
+            dlq.queue
+                .push(Message {
+                    topic: String::from("woot"),
+                    data: vec![data].into(),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        let req = || {
+            Request::new(RedriveRequest {
+                topic: String::from("woot"),
+                subscription: String::from("dlq"),
+                destination_subscription: String::from("sub"),
+                ..Default::default()
+            })
+        };
+
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let res = rt.block_on(handler.redrive(req())).unwrap().into_inner();
+        assert_eq!((res.moved, res.failed, res.replaying), (2, 0, 1));
+        assert_eq!((dlq.queue.len(), sub.queue.len()), (1, 2));
+        let progress = dlq.replay.progress().unwrap();
+        assert_eq!((progress.total, progress.moved), (3, 2));
+        assert!(progress.is_active());
+
+        let status = rt.block_on(handler.redrive(req())).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+
+        // The rest is moved a second later, by the replay running in the background.
+        rt.block_on(async {
+            while dlq.replay.is_active() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        });
+        let progress = dlq.replay.progress().unwrap();
+        assert_eq!((progress.moved, progress.failed), (3, 0));
+        assert!(progress.finished.is_some());
+        assert_eq!((dlq.queue.len(), sub.queue.len()), (0, 3));
+    }
+
+    #[test]
+    fn test_nack_reason() {
+        let handler = Handler::default();
+        let topic = handler.get_registry().create(String::from("woot"));
+        let sub = topic.create(String::from("sub"));
+        for data in 0..3u8 {
+            topic
+                .push(Message {
+                    topic: String::from("woot"),
+                    data: vec![data].into(),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        let nack = |reason| {
+            let (tag, index, _) = sub.queue.next().unwrap();
+            let mut lease = Lease::from_tag(tag, String::from("woot"), String::from("sub"), index);
+            lease.set_reason(reason);
+            aw!(handler.nack(Request::new(lease)))
+        };
+
+        // Without a dead-letter topic, rejected messages are redelivered.
+        assert!(nack(NackReason::Permanent).is_ok());
+        assert_eq!(sub.queue.len(), 3);
+
+        // Messages the dead-letter topic refuses are nacked in place, keeping their attempts.
+        let dead = handler
+            .get_registry()
+            .create(String::from("woot.dead-letter"));
+        assert!(nack(NackReason::Permanent).is_err());
+        assert_eq!(sub.queue.len(), 3);
+        let (_, entry) = sub.queue.peek(1).remove(0);
+        assert_eq!((entry.value.data.as_ref(), entry.attempts), (&[0u8][..], 2));
+
+        let dead_sub = dead.create(String::from("sub"));
+        assert!(nack(NackReason::Transient).is_ok());
+        assert!(nack(NackReason::Malformed).is_ok());
+        assert_eq!((sub.queue.len(), dead_sub.queue.len()), (2, 1));
+        let (_, _, msg) = dead_sub.queue.next().unwrap();
+        assert_eq!(msg.topic, "woot.dead-letter");
+        assert_eq!(msg.data, &[0u8][..]);
+    }
+
+    #[test]
+    fn test_auto_create_topics() {
+        let msg = || Message {
+            topic: String::from("woot"),
+            data: Bytes::from_static(b"hello"),
+            ..Default::default()
+        };
+
+        let handler = Handler::default();
+        let status = aw!(handler.publish(Request::new(msg()))).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+        assert!(handler.get_registry().get("woot").is_none());
+
+        // Created topics are delivered the message creating them through their default
+        // subscription.
+        let handler = Handler::default()
+            .with_auto_create_topics(true)
+            .with_default_subscription(String::from("{topic}-all"));
+        assert!(aw!(handler.publish(Request::new(msg()))).is_ok());
+        let topic = handler.get_registry().get("woot").unwrap();
+        let sub = topic.get("woot-all").unwrap();
+        assert_eq!(sub.queue.len(), 1);
+        assert!(aw!(handler.publish(Request::new(msg()))).is_ok());
+        assert_eq!(sub.queue.len(), 2);
+    }
+
+    #[test]
+    fn test_idempotent_publish() {
+        let handler = Handler::default();
+        let topic = handler.get_registry().create(String::from("woot"));
+        let sub = topic.create(String::from("sub"));
+
+        let msg = |key: &str| Message {
+            attributes: HashMap::from([(
+                String::from(IDEMPOTENCY_KEY_ATTRIBUTE),
+                String::from(key),
+            )]),
+            data: Bytes::from_static(b"hello"),
+            published: None,
+            checksum: None,
+            content_type: String::new(),
+            topic: String::from("woot"),
+        };
+        let first = aw!(handler.publish(Request::new(msg("one")))).unwrap();
+        assert!(!first.get_ref().duplicate);
+        let retry = aw!(handler.publish(Request::new(msg("one")))).unwrap();
+        assert!(retry.get_ref().duplicate);
+        assert_eq!(retry.get_ref().status, ConfimrationStatus::Committed as i32);
+        assert_eq!(sub.queue.len(), 1);
+
+        let other = aw!(handler.publish(Request::new(msg("two")))).unwrap();
+        assert!(!other.get_ref().duplicate);
+        assert_eq!(sub.queue.len(), 2);
+    }
+
+    #[test]
+    fn test_claim_check() {
+        let dir = std::env::temp_dir().join(format!("rift-claim-{}", uuid::Uuid::new_v4()));
+        let cfg = claimcheck::Config {
+            dir: Some(dir.clone()),
+            threshold_bytes: 4,
+            retention_ms: 60000,
+        };
+        let store = claimcheck::Store::new(&cfg).unwrap().unwrap();
+        let handler = Handler::default().with_claim_checks(store);
+        let topic = handler.get_registry().create(String::from("woot"));
+        let sub = topic.create(String::from("sub"));
+        let other = topic.create(String::from("other"));
+
+        let msg = |data: &[u8]| Message {
+            topic: String::from("woot"),
+            data: Bytes::copy_from_slice(data),
+            ..Default::default()
+        };
+        assert!(aw!(handler.publish(Request::new(msg(b"tiny")))).is_ok());
+        assert!(aw!(handler.publish(Request::new(msg(b"oversized")))).is_ok());
+
+        let (_, _, small) = sub.queue.next().unwrap();
+        assert_eq!(small.data, &b"tiny"[..]);
+        assert!(!small.attributes.contains_key(CLAIM_CHECK_ATTRIBUTE));
+        assert_eq!(small.checksum, Some(crc32c::crc32c(b"tiny")));
+        let (tag, index, mut large) = sub.queue.next().unwrap();
+        let key = large.attributes[CLAIM_CHECK_ATTRIBUTE].clone();
+        assert_eq!(large.data, key.as_bytes());
+
+        let req = ClaimRequest {
+            topic: String::from("woot"),
+            key: key.clone(),
+        };
+        let res = aw!(handler.claim(Request::new(req.clone())))
+            .unwrap()
+            .into_inner();
+        assert_eq!(res.data, &b"oversized"[..]);
+        // The checksum covers the claimed payload rather than its key.
+        assert!(!large.verify_checksum());
+        large.data = res.data;
+        assert!(large.verify_checksum());
+
+        // Corrupted payloads are refused rather than delivered.
+        let path = dir.join(&key);
+        let mut contents = std::fs::read(&path).unwrap();
+        *contents.last_mut().unwrap() ^= 1;
+        std::fs::write(&path, contents).unwrap();
+        let status = aw!(handler.claim(Request::new(req))).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::DataLoss);
+
+        let req = ClaimRequest {
+            topic: String::from("woot"),
+            key: String::from("nope"),
+        };
+        let status = aw!(handler.claim(Request::new(req))).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+
+        // Payloads are deleted once every subscription acks the message holding them.
+        let lease = Lease::from_tag(tag, String::from("woot"), String::from("sub"), index);
+        assert!(aw!(handler.ack(Request::new(lease))).is_ok());
+        assert!(path.exists());
+        other.queue.next().unwrap();
+        let (tag, index, _) = other.queue.next().unwrap();
+        let lease = Lease::from_tag(tag, String::from("woot"), String::from("other"), index);
+        assert!(aw!(handler.ack(Request::new(lease))).is_ok());
+        assert!(!path.exists());
+
+        // Payloads of messages which fail to be published are deleted right away.
+        handler.get_registry().create(String::from("empty"));
+        let mut empty = msg(b"oversized");
+        empty.topic = String::from("empty");
+        assert!(aw!(handler.publish(Request::new(empty))).is_err());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_encryption() {
+        use crate::encryption::{LocalKey, ENCRYPTION_ATTRIBUTE};
+
+        let dir = std::env::temp_dir().join(format!("rift-claim-{}", uuid::Uuid::new_v4()));
+        let cfg = claimcheck::Config {
+            dir: Some(dir.clone()),
+            threshold_bytes: 64,
+            retention_ms: 60000,
+        };
+        let store = claimcheck::Store::new(&cfg).unwrap().unwrap();
+        let keyring =
+            Keyring::with_master_key(std::sync::Arc::new(LocalKey::new(&[1; 32]).unwrap()), None);
+        keyring.enable("secret").unwrap();
+        let handler = Handler::default()
+            .with_claim_checks(store)
+            .with_keyring(keyring);
+        let topic = handler.get_registry().create(String::from("secret"));
+        let sub = topic.create(String::from("sub"));
+
+        let msg = |data: &[u8]| Message {
+            topic: String::from("secret"),
+            data: Bytes::copy_from_slice(data),
+            ..Default::default()
+        };
+        let large = [b'x'; 128];
+        assert!(aw!(handler.publish(Request::new(msg(b"hello")))).is_ok());
+        assert!(aw!(handler.publish(Request::new(msg(&large)))).is_ok());
+
+        // Queued data is encrypted, and decrypted as it is delivered.
+        let peeked = sub.queue.peek(2);
+        assert_ne!(peeked[0].1.value.data, &b"hello"[..]);
+        assert!(peeked[0]
+            .1
+            .value
+            .attributes
+            .contains_key(ENCRYPTION_ATTRIBUTE));
+        let req = PeekRequest {
+            topic: String::from("secret"),
+            subscription: String::from("sub"),
+            max: 1,
+        };
+        let mut stream = aw!(handler.peek(Request::new(req))).unwrap().into_inner();
+        let peeked = stream.0.pop().unwrap().message.unwrap();
+        assert_eq!(peeked.data, &b"hello"[..]);
+        assert!(!peeked.attributes.contains_key(ENCRYPTION_ATTRIBUTE));
+
+        // Claim checked payloads are encrypted at rest, and decrypted as they are claimed.
+        sub.queue.next().unwrap();
+        let (_, _, claimed) = sub.queue.next().unwrap();
+        let key = claimed.attributes[CLAIM_CHECK_ATTRIBUTE].clone();
+        let stored = std::fs::read_dir(&dir).unwrap().next().unwrap().unwrap();
+        assert_ne!(std::fs::read(stored.path()).unwrap(), large);
+        let req = ClaimRequest {
+            topic: String::from("secret"),
+            key,
+        };
+        let res = aw!(handler.claim(Request::new(req))).unwrap().into_inner();
+        assert_eq!(res.data, &large[..]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_stream_limit() {
+        let cfg = crate::cluster::Config {
+            node_id: Some(String::from("one")),
+            advertise_addr: None,
+            join: Vec::new(),
+            secret: Some(String::from("secret")),
+            discovery_interval_ms: 30000,
+            heartbeat_interval_ms: 1000,
+            suspect_timeout_ms: 5000,
+            dead_timeout_ms: 30000,
+            evict_timeout_ms: 300000,
+            vnodes: 64,
+        };
+        let membership = Membership::new(&cfg, &"127.0.0.1:8081".parse().unwrap()).unwrap();
+        let handler = Handler::default()
+            .with_stream_limiter(Limiter::new(1))
+            .with_membership(membership);
+        let topic = handler.get_registry().create(String::from("woot"));
+        topic.create(String::from("sub"));
+
+        let req = || {
+            Request::new(Subscription {
+                name: String::from("sub"),
+                topic: String::from("woot"),
+                ..Default::default()
+            })
+        };
+        let first = aw!(handler.subscribe(req())).unwrap();
+        let status = aw!(handler.subscribe(req())).err().unwrap();
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        assert_eq!(
+            crate::error::Code::from_status(&status),
+            Some(crate::error::Code::StreamLimitExceeded)
+        );
+
+        // Closing a stream releases its permit, and streams forwarded by other members are
+        // never limited, so long as they carry the cluster's secret.
+        drop(first);
+        let _second = aw!(handler.subscribe(req())).unwrap();
+        let forwarded = |secret: &str| {
+            let mut req = req();
+            req.metadata_mut()
+                .insert(FORWARDED_METADATA_KEY, "two".parse().unwrap());
+            req.metadata_mut()
+                .insert(SECRET_METADATA_KEY, secret.parse().unwrap());
+            req
+        };
+        assert!(aw!(handler.subscribe(forwarded("nope"))).is_err());
+        assert!(aw!(handler.subscribe(forwarded("secret"))).is_ok());
+    }
+
+    #[test]
+    fn test_shutdown() {
+        let shutdown = Shutdown::new();
+        let handler = Handler::default().with_shutdown(shutdown.clone());
+        let topic = handler.get_registry().create(String::from("woot"));
+        let sub = topic.create(String::from("sub"));
+
+        let req = || {
+            Request::new(Subscription {
+                name: String::from("sub"),
+                topic: String::from("woot"),
+                ..Default::default()
+            })
+        };
+        let mut stream = aw!(handler.subscribe(req())).unwrap().into_inner();
+        for data in [&b"hello"[..], &b"world"[..]] {
+            aw!(handler.publish(Request::new(Message {
+                topic: String::from("woot"),
+                data: Bytes::from_static(data),
+                ..Default::default()
+            })))
+            .unwrap();
+        }
+        let waker = futures::task::noop_waker();
+        let mut cx = std::task::Context::from_waker(&waker);
+        let actual = Pin::new(&mut stream).poll_next(&mut cx);
+        assert!(matches!(actual, Poll::Ready(Some(Ok(_)))));
+        assert_eq!(sub.queue.outstanding(), 1);
+
+        // Open streams fail as shutting down once shutdown begins, releasing the leases they
+        // handed out without leasing any further messages, and new streams are rejected.
+        shutdown.trigger();
+        let status = match Pin::new(&mut stream).poll_next(&mut cx) {
+            Poll::Ready(Some(Err(status))) => status,
+            _ => unimplemented!(),
+        };
+        assert_eq!(status.code(), tonic::Code::Unavailable);
+        let actual = Pin::new(&mut stream).poll_next(&mut cx);
+        assert!(matches!(actual, Poll::Ready(None)));
+        assert_eq!(sub.queue.outstanding(), 0);
+        assert_eq!(sub.queue.peek(2).len(), 2);
+        let status = aw!(handler.subscribe(req())).err().unwrap();
+        assert_eq!(status.code(), tonic::Code::Unavailable);
+        assert_eq!(
+            crate::error::Code::from_status(&status),
+            Some(crate::error::Code::ShuttingDown)
+        );
+    }
 }
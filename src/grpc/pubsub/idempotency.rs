@@ -0,0 +1,135 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::{Error, Result};
+
+/// The attribute carrying the client supplied key shared by every retry of the same publish.
+pub const IDEMPOTENCY_KEY_ATTRIBUTE: &str = "x-rift-idempotency-key";
+/// The default time a committed idempotency key is remembered for.
+pub const DEFAULT_IDEMPOTENCY_WINDOW: Duration = Duration::from_secs(300);
+
+/// A topic paired with an idempotency key published to it.
+type Key = (String, String);
+
+/// The publishes keyed so far, where [None] marks a publish still in progress.
+#[derive(Debug, Default)]
+struct Keys {
+    states: HashMap<Key, Option<Instant>>,
+    committed: VecDeque<(Instant, Key)>,
+}
+
+impl Keys {
+    /// Forget the keys committed longer ago than the supplied window.
+    fn expire(&mut self, window: Duration) {
+        while let Some((committed, _)) = self.committed.front() {
+            if committed.elapsed() < window {
+                break;
+            }
+            let (committed, key) = self.committed.pop_front().unwrap();
+            // The key may have expired and been committed again since.
+            if self.states.get(&key) == Some(&Some(committed)) {
+                self.states.remove(&key);
+            }
+        }
+    }
+}
+
+/// Remembers the idempotency keys of committed publishes for a window, so that retries of a
+/// publish are confirmed without being enqueued again.
+#[derive(Debug, Clone)]
+pub struct Deduplicator {
+    keys: Arc<Mutex<Keys>>,
+    window: Duration,
+}
+
+impl Deduplicator {
+    /// Create a new deduplicator remembering committed keys for the supplied window.
+    pub fn new(window: Duration) -> Self {
+        Self {
+            keys: Arc::default(),
+            window,
+        }
+    }
+
+    /// Reserve the supplied key for a publish to the supplied topic, returning [None] if a
+    /// publish with the key was already committed within the window. Publishes racing one
+    /// still in progress with the same key are refused, so that they retry once it settles.
+    pub fn reserve(&self, topic: &str, key: &str) -> Result<Option<Reservation>> {
+        let mut keys = self.keys.lock().unwrap();
+        keys.expire(self.window);
+        let key = (topic.to_owned(), key.to_owned());
+        match keys.states.get(&key) {
+            Some(Some(_)) => return Ok(None),
+            Some(None) => return Err(Error::PublishInProgress { key: key.1 }),
+            None => keys.states.insert(key.clone(), None),
+        };
+        Ok(Some(Reservation {
+            keys: self.keys.clone(),
+            key: Some(key),
+        }))
+    }
+}
+
+impl Default for Deduplicator {
+    fn default() -> Self {
+        Self::new(DEFAULT_IDEMPOTENCY_WINDOW)
+    }
+}
+
+/// A key reserved for an in progress publish, which is released unless it is committed.
+#[derive(Debug)]
+pub struct Reservation {
+    keys: Arc<Mutex<Keys>>,
+    key: Option<Key>,
+}
+
+impl Reservation {
+    /// Commit the publish, confirming retries with the same key without enqueuing them.
+    pub fn commit(mut self) {
+        let key = self.key.take().unwrap();
+        let now = Instant::now();
+        let mut keys = self.keys.lock().unwrap();
+        keys.states.insert(key.clone(), Some(now));
+        keys.committed.push_back((now, key));
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.keys.lock().unwrap().states.remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deduplicator() {
+        let dedup = Deduplicator::new(Duration::from_millis(20));
+
+        let reservation = dedup.reserve("woot", "one").unwrap().unwrap();
+        assert!(matches!(
+            dedup.reserve("woot", "one"),
+            Err(Error::PublishInProgress { .. })
+        ));
+        // Keys are scoped to their topic.
+        assert!(dedup.reserve("other", "one").unwrap().is_some());
+        reservation.commit();
+        assert!(dedup.reserve("woot", "one").unwrap().is_none());
+
+        // Failed publishes release their key to be retried.
+        drop(dedup.reserve("woot", "two").unwrap().unwrap());
+        assert!(dedup.reserve("woot", "two").unwrap().is_some());
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(dedup.reserve("woot", "one").unwrap().is_some());
+    }
+}
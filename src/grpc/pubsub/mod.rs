@@ -19,16 +19,60 @@ mod proto {
                 ttl_ms: tag.ttl.as_millis() as u64,
                 deadline: Some(Timestamp::from(tag.deadline)),
                 leased: Some(Timestamp::from(tag.leased_at)),
+                delay_ms: 0,
+                reason: NackReason::Transient as i32,
             }
         }
     }
+
+    impl Message {
+        /// Set the checksum of this message to that of its data.
+        pub fn stamp_checksum(&mut self) {
+            self.checksum = Some(crc32c::crc32c(&self.data));
+        }
+
+        /// Return whether the data of this message matches its checksum. Messages without a
+        /// checksum, such as those emitted by the server itself, always match.
+        pub fn verify_checksum(&self) -> bool {
+            match self.checksum {
+                Some(checksum) => crc32c::crc32c(&self.data) == checksum,
+                None => true,
+            }
+        }
+    }
+
+    impl LeasedMessage {
+        /// Split this response into each of the leased messages coalesced into it, starting
+        /// with its own.
+        pub fn unbatch(mut self) -> impl Iterator<Item = LeasedMessage> {
+            let batch = std::mem::take(&mut self.batch);
+            std::iter::once(self).chain(batch)
+        }
+    }
 }
+mod attributes;
+mod chunk;
 mod handler;
+mod idempotency;
+mod redrive;
 
 pub(crate) const FILE_DESCRIPTOR_SET: &[u8] =
     tonic::include_file_descriptor_set!("pubsub_descriptor");
 
-pub use handler::Handler;
+pub use attributes::{AttributeLimits, RESERVED_ATTRIBUTE_PREFIX};
+pub use chunk::{
+    split, Assembler, CHUNK_COUNT_ATTRIBUTE, CHUNK_GROUP_ATTRIBUTE, CHUNK_INDEX_ATTRIBUTE,
+    DEFAULT_CHUNK_TIMEOUT, MAX_CHUNKS,
+};
+pub use handler::{Handler, DEAD_LETTER_TOPIC_TEMPLATE};
+pub use idempotency::{
+    Deduplicator, Reservation, DEFAULT_IDEMPOTENCY_WINDOW, IDEMPOTENCY_KEY_ATTRIBUTE,
+};
 pub use proto::pub_sub_service_client::PubSubServiceClient;
 pub use proto::pub_sub_service_server::PubSubServiceServer;
-pub use proto::{ConfimrationStatus, Confirmation, Lease, LeasedMessage, Message, Subscription};
+pub use proto::{
+    ClaimRequest, ClaimResponse, ConfimrationStatus, Confirmation, Lease, LeasedMessage, Message,
+    NackReason, PeekRequest, PeekedMessage, QuarantineRequest, QuarantineResponse,
+    QuarantinedMessage, RedriveRequest, RedriveResponse, ReleaseRequest, ReleaseResponse,
+    SettleRequest, SettleResponse, StartPosition, Subscription,
+};
@@ -0,0 +1,198 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0
+
+use std::time::Duration;
+
+use crate::claimcheck::{self, CLAIM_CHECK_ATTRIBUTE};
+use crate::encryption::{Keyring, ENCRYPTION_ATTRIBUTE};
+use crate::pubsub::{Registry, Sub, Topic};
+use crate::Error;
+
+use super::Message;
+
+/// The subscription messages are moved out of, along with the topic and, if any, the
+/// subscription they are moved to.
+pub(super) type Resolved = (Sub<Message>, Topic<Message>, Option<Sub<Message>>);
+
+/// A redrive, moving pending messages out of a subscription into another subscription or
+/// topic.
+pub(super) struct Redrive {
+    pub(super) registry: Registry<Message>,
+    pub(super) keyring: Option<Keyring>,
+    pub(super) claim_checks: Option<claimcheck::Store>,
+    pub(super) topic: String,
+    pub(super) subscription: String,
+    pub(super) destination_topic: String,
+    pub(super) destination_subscription: String,
+}
+
+impl Redrive {
+    /// Look up the subscription messages are moved out of and where they are moved to.
+    pub(super) fn resolve(&self) -> crate::Result<Resolved> {
+        let topic = self
+            .registry
+            .get(&self.topic)
+            .ok_or_else(|| Error::TopicNotFound {
+                topic: self.topic.clone(),
+            })?;
+        let sub = topic
+            .get(&self.subscription)
+            .ok_or_else(|| Error::SubscriptionNotFound {
+                subscription: self.subscription.clone(),
+                topic: self.topic.clone(),
+            })?;
+        let destination =
+            self.registry
+                .get(&self.destination_topic)
+                .ok_or_else(|| Error::TopicNotFound {
+                    topic: self.destination_topic.clone(),
+                })?;
+        let destination_sub = match self.destination_subscription.as_str() {
+            "" => None,
+            name => Some(
+                destination
+                    .get(name)
+                    .ok_or_else(|| Error::SubscriptionNotFound {
+                        subscription: name.to_owned(),
+                        topic: self.destination_topic.clone(),
+                    })?,
+            ),
+        };
+        Ok((sub, destination, destination_sub))
+    }
+
+    /// Move up to the supplied count of pending messages to the destination, returning the
+    /// counts of messages moved and left pending. Refusing every message fails the move, such
+    /// as when the destination topic has no subscriptions.
+    pub(super) fn move_pending(
+        &self,
+        resolved: &Resolved,
+        max: usize,
+    ) -> (usize, usize, crate::pubsub::Result<()>) {
+        let (sub, destination, destination_sub) = resolved;
+
+        // Messages are leased while they are moved, so that they are left pending in the
+        // subscription unless the destination accepts them. Messages which can't be moved stay
+        // leased until the end of the move, so that they aren't leased again by it.
+        let (mut leased, mut failed) = (Vec::with_capacity(max), Vec::new());
+        while leased.len() + failed.len() < max {
+            let batch = sub.queue.lease_pending(max - leased.len() - failed.len());
+            if batch.is_empty() {
+                break;
+            }
+            for (tag, index, mut msg) in batch {
+                match rekey(self.keyring.as_ref(), &mut msg, &self.destination_topic) {
+                    true => leased.push((tag, index, msg)),
+                    false => failed.push((tag, index)),
+                }
+            }
+        }
+        for (tag, index) in &failed {
+            let _ = sub.queue.nack(tag.id, *index);
+        }
+
+        // Messages moved to a topic hold their claim checked payloads on behalf of each of the
+        // subscriptions they are enqueued into, counted before any of them can be acked, in
+        // place of the moved message.
+        let claim_checks = self
+            .claim_checks
+            .as_ref()
+            .filter(|_| destination_sub.is_none());
+        let claim_check = |msg: &Message| {
+            let key = msg.attributes.get(CLAIM_CHECK_ATTRIBUTE).cloned();
+            claim_checks.zip(key)
+        };
+
+        let (moved, res) = match destination_sub {
+            Some(destination_sub) => destination_sub
+                .queue
+                .push_batch(leased.iter().map(|(_, _, msg)| msg.clone())),
+            // Messages are moved to a topic one at a time, so that each is enqueued into either
+            // every subscription it is routed to or none of them.
+            None => {
+                let mut moved = 0;
+                let mut res = Ok(());
+                for (_, _, msg) in &leased {
+                    let hold = |count| {
+                        if let Some((store, key)) = claim_check(msg) {
+                            store.hold(&key, count);
+                        }
+                    };
+                    if let Err(err) = destination.push_with(msg.clone(), hold) {
+                        res = Err(err);
+                        break;
+                    }
+                    moved += 1;
+                }
+                (moved, res)
+            }
+        };
+        for (position, (tag, index, msg)) in leased.iter().enumerate() {
+            let _ = match position < moved {
+                true => sub.queue.ack(tag.id, *index).map(|()| {
+                    if let Some((store, key)) = claim_check(msg) {
+                        let _ = store.release(&key);
+                    }
+                }),
+                false => sub.queue.nack(tag.id, *index),
+            };
+        }
+        let res = match moved == 0 && !leased.is_empty() {
+            true => res,
+            false => Ok(()),
+        };
+        (moved, failed.len() + leased.len() - moved, res)
+    }
+
+    /// Move the supplied count of remaining pending messages out of the supplied subscription
+    /// one second's worth at a time, at up to the supplied rate per second, recording the
+    /// progress on its replay. The first second's worth is expected to be moved already.
+    ///
+    /// The replay ends early once the subscription runs out of pending messages, the
+    /// destination refuses every message of a batch, or either side is deleted.
+    pub(super) async fn replay(self, sub: Sub<Message>, mut remaining: usize, rate: usize) {
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        // The first tick completes immediately, while the first batch was just moved.
+        interval.tick().await;
+        while remaining > 0 {
+            interval.tick().await;
+            let resolved = match self.resolve() {
+                Ok(resolved) => resolved,
+                Err(_) => break,
+            };
+            let batch = remaining.min(rate);
+            let (moved, failed, res) = self.move_pending(&resolved, batch);
+            sub.replay.record(moved, failed);
+            if res.is_err() || moved + failed < batch {
+                break;
+            }
+            remaining -= batch;
+        }
+        sub.replay.finish();
+    }
+}
+
+/// Move the supplied message to the supplied topic, re-encrypting its data with the data
+/// key of that topic within the supplied keyring, if any. Returns false if its data can not be
+/// moved, as claim checked payloads remain encrypted with the data key of the topic they were
+/// published to.
+pub(super) fn rekey(keyring: Option<&Keyring>, msg: &mut Message, topic: &str) -> bool {
+    let keyring = match keyring {
+        Some(keyring) if msg.topic != topic => keyring,
+        _ => {
+            msg.topic = topic.to_owned();
+            return true;
+        }
+    };
+    if msg.attributes.contains_key(CLAIM_CHECK_ATTRIBUTE)
+        && msg.attributes.contains_key(ENCRYPTION_ATTRIBUTE)
+    {
+        return false;
+    }
+    if keyring.open(msg).is_err() {
+        return false;
+    }
+    msg.topic = topic.to_owned();
+    keyring.seal(msg);
+    true
+}
@@ -1,35 +1,96 @@
 // (c) Copyright 2021-2022 Christian Saide
 // SPDX-License-Identifier: GPL-3.0
 
-use crate::grpc::error::{sub_not_found, topic_not_found};
+use crate::audit::{Action, Auditor, Event, Resource};
+use crate::cluster::Membership;
+use crate::events::{Emitter, Kind};
+use crate::filter::Filter;
+use crate::grpc::error::{invalid_argument, sub_not_found, topic_not_found};
+use crate::grpc::forward::Forward;
 use crate::grpc::pubsub::Message;
-use crate::pubsub::Registry;
+use crate::grpc::watch::{watch, WatchStream};
+use crate::pubsub::{Registry, Selector};
+use crate::push::{Endpoint, Pusher};
+use crate::shutdown::Shutdown;
 
+use super::proto::subscription_service_client::SubscriptionServiceClient;
 use super::proto::subscription_service_server::SubscriptionService;
 use super::proto::{
-    CreateRequest, DeleteRequest, GetRequest, ListRequest, Subscription, UpdateRequest,
+    CreateRequest, DeleteRequest, EventKind, GetRequest, Lease, ListLeasesRequest, ListRequest,
+    StatsRequest, Subscription, SubscriptionEvent, SubscriptionStats, UpdateRequest, WatchRequest,
 };
 
 use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 
-use futures::Stream;
-use tonic::{Request, Response, Status};
+use futures::{Stream, StreamExt};
+use prost_types::Timestamp;
+use tonic::transport::Channel;
+use tonic::{Request, Response, Status, Streaming};
 
-pub struct SubscriptionStream(Vec<Subscription>);
+/// The source of the subscriptions of a list stream.
+enum Source {
+    /// The subscriptions of a local topic, from a sorted snapshot of their names.
+    Local {
+        names: Vec<Arc<str>>,
+        topic_name: String,
+        topic: crate::pubsub::Topic<Message>,
+    },
+    /// The subscriptions of a topic owned by another cluster member.
+    Forwarded(Streaming<Subscription>),
+}
+
+/// Streams the subscriptions of a topic from a sorted snapshot of their names, describing
+/// each subscription only as it is polled. Subscriptions removed since the snapshot was taken
+/// are skipped.
+pub struct SubscriptionStream {
+    source: Source,
+}
 
 impl Stream for SubscriptionStream {
     type Item = Result<Subscription, Status>;
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let (names, topic_name, topic) = match &mut self.source {
+            Source::Local {
+                names,
+                topic_name,
+                topic,
+            } => (names, topic_name, topic),
+            Source::Forwarded(inner) => return inner.poll_next_unpin(cx),
+        };
+        while let Some(name) = names.pop() {
+            if let Some(sub) = topic.get(&name) {
+                let sub = Subscription::from_inner(name.to_string(), topic_name.clone(), sub);
+                return Poll::Ready(Some(Ok(sub)));
+            }
+        }
+        Poll::Ready(None)
+    }
+}
+
+/// Streams a snapshot of the leases of a subscription, taken when they were listed.
+pub struct LeaseStream {
+    leases: std::vec::IntoIter<Lease>,
+}
+
+impl Stream for LeaseStream {
+    type Item = Result<Lease, Status>;
     fn poll_next(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let item = self.0.pop().map(Ok);
-        Poll::Ready(item)
+        Poll::Ready(self.leases.next().map(Ok))
     }
 }
 
-/// The Subscription service implementation.
-#[derive(Debug)]
+/// The Subscription service implementation. Clones share the same registry.
+#[derive(Debug, Clone)]
 pub struct Handler {
     topic_registry: Registry<Message>,
+    auditor: Auditor,
+    membership: Option<Membership>,
+    events: Emitter,
+    pusher: Pusher,
+    shutdown: Shutdown,
 }
 
 impl Handler {
@@ -41,7 +102,61 @@ impl Handler {
 
     /// Create a new handler with a predefined registry.
     pub fn with_registry(topic_registry: Registry<Message>) -> Self {
-        Handler { topic_registry }
+        Handler {
+            topic_registry,
+            auditor: Auditor::default(),
+            membership: None,
+            events: Emitter::default(),
+            pusher: Pusher::default(),
+            shutdown: Shutdown::default(),
+        }
+    }
+
+    /// Record all control-plane operations handled by this handler with the supplied auditor.
+    pub fn with_auditor(mut self, auditor: Auditor) -> Self {
+        self.auditor = auditor;
+        self
+    }
+
+    /// Forward requests for the subscriptions of topics owned by other members of the supplied
+    /// membership to their owner.
+    pub fn with_membership(mut self, membership: Membership) -> Self {
+        self.membership = Some(membership);
+        self
+    }
+
+    /// Return how to forward the supplied request for the supplied topic, along with a client
+    /// connected to its owner, or [None] if it must be handled locally.
+    fn forward<T>(
+        &self,
+        request: &Request<T>,
+        topic: &str,
+    ) -> Result<Option<(Forward, SubscriptionServiceClient<Channel>)>, Status> {
+        let forward = Forward::new(self.membership.as_ref(), request, topic)?;
+        Ok(forward.map(|forward| {
+            let client = SubscriptionServiceClient::new(forward.channel.clone());
+            (forward, client)
+        }))
+    }
+
+    /// Publish the creation, update and deletion of subscriptions with the supplied emitter,
+    /// which is also watched by the watch RPC.
+    pub fn with_events(mut self, events: Emitter) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// End open watch streams once the supplied shutdown begins.
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Deliver the messages of push subscriptions created by this handler with the supplied
+    /// pusher.
+    pub fn with_pusher(mut self, pusher: Pusher) -> Self {
+        self.pusher = pusher;
+        self
     }
 
     #[cfg(test)]
@@ -53,17 +168,65 @@ impl Handler {
         &self,
         request: Request<CreateRequest>,
     ) -> Result<Response<Subscription>, Status> {
+        if let Some((forward, mut client)) = self.forward(&request, &request.get_ref().topic)? {
+            let response = client.create(forward.request(request)).await?;
+            return Ok(forward.response(response));
+        }
         let request = request.into_inner();
+        let endpoint = match request
+            .push
+            .as_ref()
+            .map(|push| Endpoint::from_str(&push.endpoint))
+        {
+            Some(Ok(endpoint)) => Some(endpoint),
+            Some(Err(err)) => return invalid_argument(&err.to_string()),
+            None => None,
+        };
+        let filter = match request.filter.as_str() {
+            "" => None,
+            filter => match Filter::parse(filter) {
+                Ok(filter) => Some(Selector::new(filter, |msg: &Message| &msg.attributes)),
+                Err(err) => return invalid_argument(&err.to_string()),
+            },
+        };
         let topic = match self.topic_registry.get(&request.topic) {
             Some(topic) => topic,
             None => return topic_not_found(&request.topic),
         };
-        let sub = topic.create(request.name.clone());
+        let push = endpoint.as_ref().map(ToString::to_string);
+        let (sub, created) = topic
+            .create_with_filter(request.name.clone(), push, filter)
+            .map_err(crate::Error::from)?;
+        if created {
+            let name = format!("{}/{}", request.topic, request.name);
+            self.events.emit(Kind::SubscriptionCreated, &name);
+        }
+        if let (Some(quota), true) = (request.quota, created) {
+            sub.throttle.set(quota.into());
+        }
+        if created {
+            let max = request.max_outstanding_messages as usize;
+            sub.queue.set_max_outstanding(max);
+            sub.queue.set_ordered(request.ordered);
+        }
+        if let (Some(endpoint), true) = (endpoint, created) {
+            self.pusher.spawn(
+                self.topic_registry.clone(),
+                request.topic.clone(),
+                request.name.clone(),
+                sub.clone(),
+                endpoint,
+            );
+        }
         let sub = Subscription::from_inner(request.name, request.topic, sub);
         Ok(Response::new(sub))
     }
 
     async fn _get(&self, request: Request<GetRequest>) -> Result<Response<Subscription>, Status> {
+        if let Some((forward, mut client)) = self.forward(&request, &request.get_ref().topic)? {
+            let response = client.get(forward.request(request)).await?;
+            return Ok(forward.response(response));
+        }
         let request = request.into_inner();
 
         let topic = match self.topic_registry.get(&request.topic) {
@@ -82,6 +245,13 @@ impl Handler {
         &self,
         request: Request<ListRequest>,
     ) -> Result<Response<SubscriptionStream>, Status> {
+        if let Some((forward, mut client)) = self.forward(&request, &request.get_ref().topic)? {
+            let response = client.list(forward.request(request)).await?;
+            let response = response.map(|inner| SubscriptionStream {
+                source: Source::Forwarded(inner),
+            });
+            return Ok(forward.response(response));
+        }
         let request = request.into_inner();
 
         let topic = match self.topic_registry.get(&request.topic) {
@@ -89,35 +259,152 @@ impl Handler {
             None => return topic_not_found(&request.topic),
         };
 
-        let subscriptions = topic.iter(|iter| {
-            let mut subs = iter
-                .map(|(name, subscription)| {
-                    Subscription::from_inner(
-                        name.clone(),
-                        request.topic.clone(),
-                        subscription.clone(),
-                    )
-                })
-                .collect::<Vec<Subscription>>();
-            subs.sort_by_key(|k| k.name.clone());
-            subs
-        });
-
-        let stream = SubscriptionStream(subscriptions);
+        let stream = SubscriptionStream {
+            source: Source::Local {
+                names: topic.names(),
+                topic_name: request.topic,
+                topic,
+            },
+        };
         Ok(Response::new(stream))
     }
 
+    async fn _list_leases(
+        &self,
+        request: Request<ListLeasesRequest>,
+    ) -> Result<Response<LeaseStream>, Status> {
+        if let Some((forward, mut client)) = self.forward(&request, &request.get_ref().topic)? {
+            let mut inner = client
+                .list_leases(forward.request(request))
+                .await?
+                .into_inner();
+            let mut leases = Vec::new();
+            while let Some(lease) = inner.next().await {
+                leases.push(lease?);
+            }
+            let response = Response::new(LeaseStream {
+                leases: leases.into_iter(),
+            });
+            return Ok(forward.response(response));
+        }
+        let request = request.into_inner();
+
+        let topic = match self.topic_registry.get(&request.topic) {
+            Some(topic) => topic,
+            None => return topic_not_found(&request.topic),
+        };
+        let sub = match topic.get(&request.name) {
+            Some(sub) => sub,
+            None => return sub_not_found(&request.name, &request.topic),
+        };
+
+        let mut leases = Vec::new();
+        sub.queue
+            .inspect(|info| leases.extend(Lease::from_slot(info)));
+        leases.sort_unstable_by_key(|lease| lease.index);
+        Ok(Response::new(LeaseStream {
+            leases: leases.into_iter(),
+        }))
+    }
+
+    async fn _stats(
+        &self,
+        request: Request<StatsRequest>,
+    ) -> Result<Response<SubscriptionStats>, Status> {
+        if let Some((forward, mut client)) = self.forward(&request, &request.get_ref().topic)? {
+            let response = client.stats(forward.request(request)).await?;
+            return Ok(forward.response(response));
+        }
+        let request = request.into_inner();
+
+        let topic = match self.topic_registry.get(&request.topic) {
+            Some(topic) => topic,
+            None => return topic_not_found(&request.topic),
+        };
+        let sub = match topic.get(&request.name) {
+            Some(sub) => sub,
+            None => return sub_not_found(&request.name, &request.topic),
+        };
+        Ok(Response::new(SubscriptionStats {
+            name: request.name,
+            topic: request.topic,
+            held: sub.queue.len() as u64,
+            outstanding: sub.queue.outstanding() as u64,
+            replay: sub.replay.progress().map(Into::into),
+            quarantined: sub.quarantine.len() as u64,
+        }))
+    }
+
     async fn _update(
         &self,
-        _request: Request<UpdateRequest>,
+        request: Request<UpdateRequest>,
     ) -> Result<Response<Subscription>, Status> {
-        unimplemented!()
+        if let Some((forward, mut client)) = self.forward(&request, &request.get_ref().topic)? {
+            let response = client.update(forward.request(request)).await?;
+            return Ok(forward.response(response));
+        }
+        let request = request.into_inner();
+
+        let topic = match self.topic_registry.get(&request.topic) {
+            Some(topic) => topic,
+            None => return topic_not_found(&request.topic),
+        };
+        let quota = request.quota.map(Into::into).unwrap_or_default();
+        let sub = match topic.set_quota(&request.name, quota) {
+            Some(sub) => sub,
+            None => return sub_not_found(&request.name, &request.topic),
+        };
+        sub.queue
+            .set_max_outstanding(request.max_outstanding_messages as usize);
+        sub.queue.set_ordered(request.ordered);
+        let name = format!("{}/{}", request.topic, request.name);
+        self.events.emit(Kind::SubscriptionUpdated, &name);
+        let sub = Subscription::from_inner(request.name, request.topic, sub);
+        Ok(Response::new(sub))
+    }
+
+    async fn _watch(
+        &self,
+        request: Request<WatchRequest>,
+    ) -> Result<Response<WatchStream<SubscriptionEvent>>, Status> {
+        let watched = request.into_inner().topic;
+        let topic_registry = self.topic_registry.clone();
+        let stream = watch(&self.events, &self.shutdown, move |event| {
+            let kind = match event.kind {
+                Kind::SubscriptionCreated => EventKind::Created,
+                Kind::SubscriptionUpdated => EventKind::Updated,
+                Kind::SubscriptionDeleted => EventKind::Deleted,
+                _ => return None,
+            };
+            // Subscriptions are named as `topic/subscription`, where only the topic may be
+            // qualified with a namespace.
+            let (topic, name) = event.name.rsplit_once('/')?;
+            if !watched.is_empty() && topic != watched {
+                return None;
+            }
+            let subscription = topic_registry
+                .get(topic)
+                .and_then(|found| found.get(name))
+                .map(|sub| Subscription::from_inner(name.to_owned(), topic.to_owned(), sub));
+            Some(SubscriptionEvent {
+                kind: kind as i32,
+                name: name.to_owned(),
+                topic: topic.to_owned(),
+                time: Some(Timestamp::from(event.time)),
+                subscription,
+            })
+        });
+        Ok(Response::new(stream))
     }
 
     async fn _delete(
         &self,
         request: Request<DeleteRequest>,
     ) -> Result<Response<Subscription>, Status> {
+        if let Some((forward, mut client)) = self.forward(&request, &request.get_ref().topic)? {
+            let response = client.delete(forward.request(request)).await?;
+            return Ok(forward.response(response));
+        }
         let request = request.into_inner();
         let topic = match self.topic_registry.get(&request.topic) {
             Some(topic) => topic,
@@ -125,11 +412,15 @@ impl Handler {
         };
 
         match topic.remove(&request.name) {
-            Some(subscription) => Ok(Response::new(Subscription::from_inner(
-                request.name,
-                request.topic,
-                subscription,
-            ))),
+            Some(subscription) => {
+                let name = format!("{}/{}", request.topic, request.name);
+                self.events.emit(Kind::SubscriptionDeleted, &name);
+                Ok(Response::new(Subscription::from_inner(
+                    request.name,
+                    request.topic,
+                    subscription,
+                )))
+            }
             None => sub_not_found(&request.name, &request.topic),
         }
     }
@@ -148,7 +439,9 @@ impl SubscriptionService for Handler {
         &self,
         request: Request<CreateRequest>,
     ) -> Result<Response<Subscription>, Status> {
-        self._create(request).await
+        let name = format!("{}/{}", request.get_ref().topic, request.get_ref().name);
+        let event = Event::new(&request, Action::Create, Resource::Subscription, name);
+        self.auditor.audit(event, self._create(request)).await
     }
 
     #[inline]
@@ -169,12 +462,24 @@ impl SubscriptionService for Handler {
         self._list(request).await
     }
 
+    type WatchStream = WatchStream<SubscriptionEvent>;
+
+    #[inline]
+    async fn watch(
+        &self,
+        request: Request<WatchRequest>,
+    ) -> Result<Response<Self::WatchStream>, Status> {
+        self._watch(request).await
+    }
+
     #[inline]
     async fn update(
         &self,
         request: Request<UpdateRequest>,
     ) -> Result<Response<Subscription>, Status> {
-        self._update(request).await
+        let name = format!("{}/{}", request.get_ref().topic, request.get_ref().name);
+        let event = Event::new(&request, Action::Update, Resource::Subscription, name);
+        self.auditor.audit(event, self._update(request)).await
     }
 
     #[inline]
@@ -182,7 +487,27 @@ impl SubscriptionService for Handler {
         &self,
         request: Request<DeleteRequest>,
     ) -> Result<Response<Subscription>, Status> {
-        self._delete(request).await
+        let name = format!("{}/{}", request.get_ref().topic, request.get_ref().name);
+        let event = Event::new(&request, Action::Delete, Resource::Subscription, name);
+        self.auditor.audit(event, self._delete(request)).await
+    }
+
+    type ListLeasesStream = LeaseStream;
+
+    #[inline]
+    async fn list_leases(
+        &self,
+        request: Request<ListLeasesRequest>,
+    ) -> Result<Response<Self::ListLeasesStream>, Status> {
+        self._list_leases(request).await
+    }
+
+    #[inline]
+    async fn stats(
+        &self,
+        request: Request<StatsRequest>,
+    ) -> Result<Response<SubscriptionStats>, Status> {
+        self._stats(request).await
     }
 }
 
@@ -190,6 +515,7 @@ impl SubscriptionService for Handler {
 #[cfg(not(tarpaulin_include))]
 mod tests {
     use super::*;
+    use crate::grpc::subscription::{PushConfig, Quota};
 
     macro_rules! aw {
         ($e:expr) => {
@@ -210,6 +536,11 @@ mod tests {
         let create_req = CreateRequest {
             topic: topic_name.clone(),
             name: sub_name.clone(),
+            push: None,
+            quota: None,
+            filter: String::new(),
+            max_outstanding_messages: 0,
+            ordered: false,
         };
         let req = Request::new(create_req);
         let res = aw!(handler.create(req));
@@ -222,6 +553,11 @@ mod tests {
         let create_req = CreateRequest {
             topic: String::from("nope"),
             name: sub_name.clone(),
+            push: None,
+            quota: None,
+            filter: String::new(),
+            max_outstanding_messages: 0,
+            ordered: false,
         };
         let req = Request::new(create_req);
         let res = aw!(handler.create(req));
@@ -230,6 +566,11 @@ mod tests {
         let create_req = CreateRequest {
             topic: topic_name.clone(),
             name: second_sub_name.clone(),
+            push: None,
+            quota: None,
+            filter: String::new(),
+            max_outstanding_messages: 0,
+            ordered: false,
         };
         let req = Request::new(create_req);
         let res = aw!(handler.create(req));
@@ -240,6 +581,188 @@ mod tests {
         assert_eq!(res.topic, topic_name);
     }
 
+    #[test]
+    fn test_create_push() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+        let handler = Handler::default();
+        let topic_name = String::from("topic");
+        handler.get_registry().create(topic_name.clone());
+
+        let create_req = CreateRequest {
+            topic: topic_name.clone(),
+            name: String::from("invalid"),
+            push: Some(PushConfig {
+                endpoint: String::from("ftp://localhost/push"),
+            }),
+            quota: None,
+            filter: String::new(),
+            max_outstanding_messages: 0,
+            ordered: false,
+        };
+        let res = aw!(handler.create(Request::new(create_req)));
+        assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+
+        let endpoint = String::from("http://localhost:8080/push");
+        let create_req = CreateRequest {
+            topic: topic_name.clone(),
+            name: String::from("push"),
+            push: Some(PushConfig {
+                endpoint: endpoint.clone(),
+            }),
+            quota: None,
+            filter: String::new(),
+            max_outstanding_messages: 0,
+            ordered: false,
+        };
+        let res = aw!(handler.create(Request::new(create_req))).unwrap();
+        assert_eq!(res.get_ref().push.as_ref().unwrap().endpoint, endpoint);
+
+        let sub = handler.get_registry().get(&topic_name).unwrap().get("push");
+        assert_eq!(sub.unwrap().push, Some(endpoint));
+    }
+
+    #[test]
+    fn test_watch() {
+        use futures::StreamExt;
+
+        let handler = Handler::default();
+        for topic in ["orders", "billing/invoices"] {
+            handler.get_registry().create(String::from(topic));
+        }
+        let watch_req = WatchRequest {
+            topic: String::from("billing/invoices"),
+        };
+        let mut stream = aw!(handler.watch(Request::new(watch_req)))
+            .unwrap()
+            .into_inner();
+
+        for topic in ["orders", "billing/invoices"] {
+            let create_req = CreateRequest {
+                topic: String::from(topic),
+                name: String::from("audit"),
+                ..Default::default()
+            };
+            aw!(handler.create(Request::new(create_req))).unwrap();
+        }
+        let update_req = UpdateRequest {
+            topic: String::from("billing/invoices"),
+            name: String::from("audit"),
+            max_outstanding_messages: 5,
+            ..Default::default()
+        };
+        aw!(handler.update(Request::new(update_req))).unwrap();
+
+        for kind in [EventKind::Created, EventKind::Updated] {
+            let event = aw!(stream.next()).unwrap().unwrap();
+            assert_eq!(event.kind(), kind);
+            assert_eq!(
+                (event.topic.as_str(), event.name.as_str()),
+                ("billing/invoices", "audit")
+            );
+            let sub = event.subscription.unwrap();
+            assert_eq!(sub.max_outstanding_messages, 5);
+        }
+
+        let delete_req = DeleteRequest {
+            topic: String::from("billing/invoices"),
+            name: String::from("audit"),
+        };
+        aw!(handler.delete(Request::new(delete_req))).unwrap();
+        let event = aw!(stream.next()).unwrap().unwrap();
+        assert_eq!(event.kind(), EventKind::Deleted);
+        assert!(event.subscription.is_none());
+    }
+
+    #[test]
+    fn test_update_quota() {
+        let handler = Handler::default();
+        let topic_name = String::from("topic");
+        let topic = handler.get_registry().create(topic_name.clone());
+        let quota = Quota {
+            messages_per_sec: 10,
+            bytes_per_sec: 1024,
+        };
+
+        let create_req = CreateRequest {
+            topic: topic_name.clone(),
+            name: String::from("sub"),
+            push: None,
+            quota: Some(quota.clone()),
+            filter: String::new(),
+            max_outstanding_messages: 5,
+            ordered: true,
+        };
+        let res = aw!(handler.create(Request::new(create_req))).unwrap();
+        assert_eq!(res.get_ref().quota, Some(quota));
+        assert_eq!(res.get_ref().max_outstanding_messages, 5);
+        assert!(res.get_ref().ordered);
+        assert_eq!(topic.get("sub").unwrap().queue.max_outstanding(), 5);
+        assert_eq!(
+            topic.get("sub").unwrap().throttle.quota().messages_per_sec,
+            10
+        );
+
+        let update_req = UpdateRequest {
+            topic: topic_name.clone(),
+            name: String::from("sub"),
+            quota: None,
+            max_outstanding_messages: 0,
+            ordered: false,
+        };
+        let res = aw!(handler.update(Request::new(update_req))).unwrap();
+        assert_eq!(res.get_ref().quota, None);
+        assert_eq!(res.get_ref().max_outstanding_messages, 0);
+        assert!(!res.get_ref().ordered);
+        assert!(res.get_ref().updated.is_some());
+        assert!(topic.get("sub").unwrap().throttle.quota().is_unlimited());
+        assert_eq!(topic.get("sub").unwrap().queue.max_outstanding(), 0);
+
+        let update_req = UpdateRequest {
+            topic: topic_name,
+            name: String::from("nope"),
+            quota: None,
+            max_outstanding_messages: 0,
+            ordered: false,
+        };
+        let res = aw!(handler.update(Request::new(update_req)));
+        assert_eq!(res.unwrap_err().code(), tonic::Code::NotFound);
+    }
+
+    #[test]
+    fn test_create_filter() {
+        let handler = Handler::default();
+        let topic = handler.get_registry().create(String::from("woot"));
+        let create_req = |name: &str, filter: &str| CreateRequest {
+            topic: String::from("woot"),
+            name: String::from(name),
+            push: None,
+            quota: None,
+            filter: String::from(filter),
+            max_outstanding_messages: 0,
+            ordered: false,
+        };
+
+        let res = aw!(handler.create(Request::new(create_req("sub", "attributes.kind =="))));
+        assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+        assert!(topic.get("sub").is_none());
+
+        let filter = "attributes.kind == 'order'";
+        let res = aw!(handler.create(Request::new(create_req("sub", filter)))).unwrap();
+        assert_eq!(res.get_ref().filter, filter);
+        let all = aw!(handler.create(Request::new(create_req("all", "")))).unwrap();
+        assert!(all.get_ref().filter.is_empty());
+
+        for kind in ["order", "refund"] {
+            let mut msg = Message::default();
+            msg.attributes
+                .insert(String::from("kind"), String::from(kind));
+            topic.push(msg).unwrap();
+        }
+        assert_eq!(topic.get("sub").unwrap().queue.len(), 1);
+        assert_eq!(topic.get("all").unwrap().queue.len(), 2);
+    }
+
     #[test]
     fn test_delete() {
         let topic_name = String::from("topic");
@@ -253,6 +776,11 @@ mod tests {
         let create_req = CreateRequest {
             topic: topic_name.clone(),
             name: sub_name.clone(),
+            push: None,
+            quota: None,
+            filter: String::new(),
+            max_outstanding_messages: 0,
+            ordered: false,
         };
         let req = Request::new(create_req);
         let res = aw!(handler.create(req));
@@ -300,6 +828,11 @@ mod tests {
         let create_req = CreateRequest {
             topic: topic_name.clone(),
             name: sub_name.clone(),
+            push: None,
+            quota: None,
+            filter: String::new(),
+            max_outstanding_messages: 0,
+            ordered: false,
         };
         let req = Request::new(create_req);
         let res = aw!(handler.create(req));
@@ -348,6 +881,11 @@ mod tests {
         let create_req = CreateRequest {
             topic: topic_name.clone(),
             name: sub_name.clone(),
+            push: None,
+            quota: None,
+            filter: String::new(),
+            max_outstanding_messages: 0,
+            ordered: false,
         };
         let req = Request::new(create_req);
         let res = aw!(handler.create(req));
@@ -356,6 +894,11 @@ mod tests {
         let create_req = CreateRequest {
             topic: topic_name.clone(),
             name: second_sub_name.clone(),
+            push: None,
+            quota: None,
+            filter: String::new(),
+            max_outstanding_messages: 0,
+            ordered: false,
         };
         let req = Request::new(create_req);
         let res = aw!(handler.create(req));
@@ -407,5 +950,170 @@ mod tests {
             _ => unimplemented!(),
         };
         assert!(actual.is_none());
+
+        // Subscriptions removed after listing began are skipped.
+        let list_req = ListRequest {
+            topic: topic_name.clone(),
+        };
+        let mut stream = aw!(handler.list(Request::new(list_req))).unwrap();
+        let stream = stream.get_mut();
+        reg.get(&topic_name).unwrap().remove(&second_sub_name);
+
+        let actual = match Pin::new(&mut *stream).poll_next(&mut cx) {
+            Poll::Ready(actual) => actual.unwrap().unwrap(),
+            _ => unimplemented!(),
+        };
+        assert_eq!(actual.name, sub_name);
+        assert!(matches!(
+            Pin::new(&mut *stream).poll_next(&mut cx),
+            Poll::Ready(None)
+        ));
+    }
+
+    #[test]
+    fn test_list_leases() {
+        let handler = Handler::default();
+        let reg = handler.get_registry();
+        let topic = reg.create(String::from("topic"));
+        let sub = topic.create(String::from("sub"));
+        for _ in 0..3 {
+            sub.queue.push(Message::default()).unwrap();
+        }
+        let ttl = std::time::Duration::from_secs(30);
+        let (first, first_idx, _) = sub.queue.next_for(ttl, Some(&Arc::from("alice"))).unwrap();
+        let (second, second_idx, _) = sub.queue.next().unwrap();
+
+        let req = |topic: &str, name: &str| {
+            Request::new(ListLeasesRequest {
+                topic: topic.to_owned(),
+                name: name.to_owned(),
+            })
+        };
+        assert!(aw!(handler.list_leases(req("nope", "sub"))).is_err());
+        assert!(aw!(handler.list_leases(req("topic", "nope"))).is_err());
+
+        let mut stream = aw!(handler.list_leases(req("topic", "sub"))).unwrap();
+        let waker = futures::task::noop_waker();
+        let mut cx = std::task::Context::from_waker(&waker);
+        let mut leases = Vec::new();
+        while let Poll::Ready(Some(lease)) = Pin::new(stream.get_mut()).poll_next(&mut cx) {
+            leases.push(lease.unwrap());
+        }
+        assert_eq!(leases.len(), 2);
+        assert_eq!(leases[0].id, first.id);
+        assert_eq!(leases[0].index, first_idx as u64);
+        assert_eq!(leases[0].owner, "alice");
+        assert_eq!(leases[0].attempt, 1);
+        assert!(leases[0].deadline.is_some());
+        assert!(leases[0].published.is_some());
+        assert!(!leases[0].expired);
+        assert_eq!(leases[1].id, second.id);
+        assert_eq!(leases[1].index, second_idx as u64);
+        assert_eq!(leases[1].owner, "");
+    }
+
+    #[test]
+    fn test_stats() {
+        let handler = Handler::default();
+        let topic = handler.get_registry().create(String::from("topic"));
+        let sub = topic.create(String::from("sub"));
+        for _ in 0..3 {
+            sub.queue.push(Message::default()).unwrap();
+        }
+        sub.queue.next().unwrap();
+
+        let req = |topic: &str, name: &str| {
+            Request::new(StatsRequest {
+                topic: topic.to_owned(),
+                name: name.to_owned(),
+            })
+        };
+        assert!(aw!(handler.stats(req("nope", "sub"))).is_err());
+        assert!(aw!(handler.stats(req("topic", "nope"))).is_err());
+
+        let stats = aw!(handler.stats(req("topic", "sub")))
+            .unwrap()
+            .into_inner();
+        assert_eq!((stats.held, stats.outstanding), (3, 1));
+        assert_eq!(stats.replay, None);
+
+        let progress = crate::pubsub::Progress::new(String::from("other"), String::new(), 3, 1);
+        sub.replay.start(progress);
+        sub.replay.record(1, 0);
+        let stats = aw!(handler.stats(req("topic", "sub")))
+            .unwrap()
+            .into_inner();
+        let replay = stats.replay.unwrap();
+        assert_eq!(replay.destination_topic, "other");
+        assert_eq!((replay.total, replay.moved, replay.failed), (3, 1, 0));
+        assert!(replay.started.is_some());
+        assert!(replay.finished.is_none());
+    }
+
+    #[test]
+    fn test_forward() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let owner = runtime.block_on(crate::testing::Fixture::start()).unwrap();
+        let cfg = crate::cluster::Config {
+            node_id: Some(String::from("one")),
+            advertise_addr: None,
+            join: Vec::new(),
+            secret: None,
+            discovery_interval_ms: 30000,
+            heartbeat_interval_ms: 1000,
+            suspect_timeout_ms: 5000,
+            dead_timeout_ms: 30000,
+            evict_timeout_ms: 300000,
+            vnodes: 64,
+        };
+        let membership = Membership::new(&cfg, &"127.0.0.1:8081".parse().unwrap()).unwrap();
+        membership.merge(vec![crate::cluster::Member::new(
+            String::from("two"),
+            owner.addr.to_string(),
+            1,
+        )]);
+        let handler = Handler::default().with_membership(membership.clone());
+        let topic = (0..100)
+            .map(|topic| format!("topic-{}", topic))
+            .find(|topic| !membership.is_local(topic))
+            .unwrap();
+        let owned = owner.registry.create(topic.clone());
+
+        // Requests for the subscriptions of topics owned by another member are handled by
+        // that member.
+        let res = runtime.block_on(handler.create(Request::new(CreateRequest {
+            name: String::from("sub"),
+            topic: topic.clone(),
+            ..Default::default()
+        })));
+        assert_eq!(res.unwrap().into_inner().name, "sub");
+        let sub = owned.get("sub").unwrap();
+        sub.queue.push(Message::default()).unwrap();
+        sub.queue.next().unwrap();
+
+        let subs = runtime.block_on(async {
+            let req = Request::new(ListRequest {
+                topic: topic.clone(),
+            });
+            let stream = handler.list(req).await.unwrap().into_inner();
+            stream.collect::<Vec<_>>().await
+        });
+        assert_eq!(subs.len(), 1);
+        assert_eq!(subs[0].as_ref().unwrap().name, "sub");
+        let leases = runtime.block_on(async {
+            let req = Request::new(ListLeasesRequest {
+                topic: topic.clone(),
+                name: String::from("sub"),
+            });
+            let stream = handler.list_leases(req).await.unwrap().into_inner();
+            stream.collect::<Vec<_>>().await
+        });
+        assert_eq!(leases.len(), 1);
+        let res = runtime.block_on(handler.stats(Request::new(StatsRequest {
+            topic: topic.clone(),
+            name: String::from("sub"),
+        })));
+        assert_eq!(res.unwrap().into_inner().outstanding, 1);
+        assert!(handler.get_registry().get(&topic).is_none());
     }
 }
@@ -2,6 +2,8 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
 mod proto {
+    use std::time::SystemTime;
+
     use prost_types::Timestamp;
 
     tonic::include_proto!("subscription");
@@ -14,6 +16,70 @@ mod proto {
                 name,
                 topic,
                 updated: i.updated.map(Timestamp::from),
+                push: i.push.map(|endpoint| PushConfig { endpoint }),
+                filter: i
+                    .filter
+                    .map(|filter| filter.filter().source().to_owned())
+                    .unwrap_or_default(),
+                quota: Some(i.throttle.quota())
+                    .filter(|quota| !quota.is_unlimited())
+                    .map(Quota::from),
+                max_outstanding_messages: i.queue.max_outstanding() as u64,
+                ordered: i.queue.is_ordered(),
+            }
+        }
+    }
+
+    impl Lease {
+        /// Describe the lease of the supplied locked slot, or [None] if the slot is not locked.
+        pub fn from_slot(info: crate::pubsub::SlotInfo) -> Option<Self> {
+            let id = info.lease_id?;
+            let now = SystemTime::now();
+            Some(Self {
+                id,
+                index: info.index as u64,
+                deadline: info.lease_deadline.map(Timestamp::from),
+                attempt: info.attempts,
+                owner: info.owner.as_deref().unwrap_or_default().to_owned(),
+                published: info
+                    .age
+                    .and_then(|age| now.checked_sub(age))
+                    .map(Timestamp::from),
+                expired: info.is_expired(),
+                sequence: info.sequence.unwrap_or_default(),
+            })
+        }
+    }
+
+    impl From<crate::pubsub::Progress> for Replay {
+        fn from(progress: crate::pubsub::Progress) -> Self {
+            Self {
+                destination_topic: progress.destination_topic,
+                destination_subscription: progress.destination_subscription,
+                total: progress.total,
+                moved: progress.moved,
+                failed: progress.failed,
+                messages_per_sec: progress.messages_per_sec,
+                started: Some(Timestamp::from(progress.started)),
+                finished: progress.finished.map(Timestamp::from),
+            }
+        }
+    }
+
+    impl From<crate::pubsub::Quota> for Quota {
+        fn from(quota: crate::pubsub::Quota) -> Self {
+            Self {
+                messages_per_sec: quota.messages_per_sec,
+                bytes_per_sec: quota.bytes_per_sec,
+            }
+        }
+    }
+
+    impl From<Quota> for crate::pubsub::Quota {
+        fn from(quota: Quota) -> Self {
+            Self {
+                messages_per_sec: quota.messages_per_sec,
+                bytes_per_sec: quota.bytes_per_sec,
             }
         }
     }
@@ -25,7 +91,9 @@ pub(crate) const FILE_DESCRIPTOR_SET: &[u8] =
 
 pub use handler::Handler;
 pub use proto::subscription_service_client::SubscriptionServiceClient;
-pub use proto::subscription_service_server::SubscriptionServiceServer;
+pub use proto::subscription_service_server::{SubscriptionService, SubscriptionServiceServer};
 pub use proto::{
-    CreateRequest, DeleteRequest, GetRequest, ListRequest, Subscription, UpdateRequest,
+    CreateRequest, DeleteRequest, EventKind, GetRequest, Lease, ListLeasesRequest, ListRequest,
+    PushConfig, Quota, Replay, StatsRequest, Subscription, SubscriptionEvent, SubscriptionStats,
+    UpdateRequest, WatchRequest,
 };
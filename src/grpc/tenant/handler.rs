@@ -0,0 +1,125 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::Stream;
+use tonic::{Request, Response, Status};
+
+use crate::pubsub::namespace;
+use crate::tenant::Tenants;
+
+use super::proto::tenant_service_server::TenantService;
+use super::{GetUsageRequest, ListUsageRequest, Usage};
+
+pub struct UsageStream(Vec<Usage>);
+
+impl Stream for UsageStream {
+    type Item = Result<Usage, Status>;
+    fn poll_next(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let item = self.0.pop().map(Ok);
+        Poll::Ready(item)
+    }
+}
+
+/// The Tenant service implementation.
+#[derive(Debug)]
+pub struct Handler {
+    tenants: Tenants,
+}
+
+impl Handler {
+    /// Create a new handler exposing the usage of the supplied tenants.
+    pub fn with_tenants(tenants: Tenants) -> Self {
+        Self { tenants }
+    }
+
+    async fn _get_usage(
+        &self,
+        request: Request<GetUsageRequest>,
+    ) -> Result<Response<Usage>, Status> {
+        let tenant = match request.get_ref().tenant.as_str() {
+            "" => namespace::DEFAULT_NAMESPACE,
+            tenant => tenant,
+        };
+        Ok(Response::new(self.tenants.usage(tenant).into()))
+    }
+
+    async fn _list_usage(
+        &self,
+        _request: Request<ListUsageRequest>,
+    ) -> Result<Response<UsageStream>, Status> {
+        // Reverse the sorted usage so that popping off the stream yields it in order.
+        let usage = self
+            .tenants
+            .list()
+            .into_iter()
+            .rev()
+            .map(Into::into)
+            .collect();
+        Ok(Response::new(UsageStream(usage)))
+    }
+}
+
+#[tonic::async_trait]
+impl TenantService for Handler {
+    #[inline]
+    async fn get_usage(
+        &self,
+        request: Request<GetUsageRequest>,
+    ) -> Result<Response<Usage>, Status> {
+        self._get_usage(request).await
+    }
+
+    type ListUsageStream = UsageStream;
+
+    #[inline]
+    async fn list_usage(
+        &self,
+        request: Request<ListUsageRequest>,
+    ) -> Result<Response<Self::ListUsageStream>, Status> {
+        self._list_usage(request).await
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    use futures::StreamExt;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn test_usage() {
+        let tenants = Tenants::default();
+        tenants.published("billing/invoices", 3);
+        tenants.published("orders", 5);
+        let handler = Handler::with_tenants(tenants);
+
+        let req = GetUsageRequest {
+            tenant: String::new(),
+        };
+        let usage = aw!(handler.get_usage(Request::new(req)))
+            .unwrap()
+            .into_inner();
+        assert_eq!(usage.tenant, "default");
+        assert_eq!(usage.published_bytes, 5);
+        assert!(usage.quota.is_some());
+
+        let mut stream = aw!(handler.list_usage(Request::new(ListUsageRequest {})))
+            .unwrap()
+            .into_inner();
+        let mut names = Vec::new();
+        while let Some(usage) = aw!(stream.next()) {
+            names.push(usage.unwrap().tenant);
+        }
+        assert_eq!(names, vec!["billing", "default"]);
+    }
+}
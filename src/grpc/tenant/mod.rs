@@ -0,0 +1,44 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+mod proto {
+    use crate::tenant;
+
+    tonic::include_proto!("tenant");
+
+    impl From<tenant::Quota> for Quota {
+        fn from(quota: tenant::Quota) -> Self {
+            Self {
+                published_messages_per_sec: quota.published_messages_per_sec,
+                published_bytes_per_sec: quota.published_bytes_per_sec,
+                stored_messages: quota.stored_messages,
+                stored_bytes: quota.stored_bytes,
+            }
+        }
+    }
+
+    impl From<tenant::Usage> for Usage {
+        fn from(usage: tenant::Usage) -> Self {
+            Self {
+                tenant: usage.tenant,
+                quota: Some(usage.quota.into()),
+                published_messages: usage.published_messages,
+                published_bytes: usage.published_bytes,
+                consumed_messages: usage.consumed_messages,
+                consumed_bytes: usage.consumed_bytes,
+                stored_messages: usage.stored_messages,
+                stored_bytes: usage.stored_bytes,
+                rejected: usage.rejected,
+            }
+        }
+    }
+}
+mod handler;
+
+pub(crate) const FILE_DESCRIPTOR_SET: &[u8] =
+    tonic::include_file_descriptor_set!("tenant_descriptor");
+
+pub use handler::Handler;
+pub use proto::tenant_service_client::TenantServiceClient;
+pub use proto::tenant_service_server::TenantServiceServer;
+pub use proto::{GetUsageRequest, ListUsageRequest, Quota, Usage};
@@ -1,33 +1,101 @@
 // (c) Copyright 2021-2022 Christian Saide
 // SPDX-License-Identifier: GPL-3.0
 
-use crate::grpc::error::topic_not_found;
-use crate::grpc::pubsub::Message;
-use crate::pubsub::Registry;
-
+use crate::audit::{Action, Auditor, Event, Resource};
+use crate::cluster::Membership;
+use crate::encryption::Keyring;
+use crate::events::{Emitter, Kind};
+use crate::filter::Filter;
+use crate::grpc::error::{invalid_argument, topic_not_found};
+use crate::grpc::forward::Forward;
+use crate::grpc::pubsub::{Message, RESERVED_ATTRIBUTE_PREFIX};
+use crate::grpc::watch::{watch, WatchStream};
+use crate::pubsub::{namespace, ForwardRule, Registry, Route, Selector};
+use crate::schema::{self, Schema as RiftSchema};
+use crate::shutdown::Shutdown;
+
+use super::proto::topic_service_client::TopicServiceClient;
 use super::proto::topic_service_server::TopicService;
-use super::proto::{CreateRequest, DeleteRequest, GetRequest, ListRequest, Topic, UpdateRequest};
+use super::proto::{
+    CreateRequest, DeleteRequest, EventKind, GetRequest, ListRequest, Schema, Topic, TopicEvent,
+    UpdateRequest, WatchRequest,
+};
 
+use std::convert::TryFrom;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 
 use futures::Stream;
+use prost_types::Timestamp;
+use tonic::transport::Channel;
 use tonic::{Request, Response, Status};
 
-pub struct TopicStream(Vec<Topic>);
+/// The template default subscriptions are named with, unless configured otherwise.
+pub const DEFAULT_SUBSCRIPTION_TEMPLATE: &str = "default";
+
+/// Streams the topics of a registry from a sorted snapshot of their names, describing each
+/// topic only as it is polled. Topics deleted since the snapshot was taken are skipped.
+pub struct TopicStream {
+    names: Vec<Arc<str>>,
+    topic_registry: Registry<Message>,
+    schemas: schema::Registry,
+    keyring: Option<Keyring>,
+}
 
 impl Stream for TopicStream {
     type Item = Result<Topic, Status>;
     fn poll_next(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let item = self.0.pop().map(Ok);
-        Poll::Ready(item)
+        while let Some(name) = self.names.pop() {
+            if let Some(topic) = self.topic_registry.get(&name) {
+                let topic = describe(&self.schemas, &self.keyring, name.to_string(), topic);
+                return Poll::Ready(Some(Ok(topic)));
+            }
+        }
+        Poll::Ready(None)
     }
 }
 
-/// The Topic service implementation.
-#[derive(Debug)]
+/// Convert the supplied inner topic, including the schema bound to it if any and whether it
+/// is encrypted.
+fn describe(
+    schemas: &schema::Registry,
+    keyring: &Option<Keyring>,
+    name: String,
+    topic: crate::pubsub::Topic<Message>,
+) -> Topic {
+    let schema = schemas.get(&name);
+    let encrypted = keyring
+        .as_ref()
+        .map(|keyring| keyring.is_encrypted(&name))
+        .unwrap_or(false);
+    let mut topic = Topic::from_inner(name, topic);
+    topic.schema = schema.map(|schema| Schema::from(schema.as_ref()));
+    topic.encrypted = encrypted;
+    topic
+}
+
+/// Parse the supplied filter expression of a route or forwarding rule into a selector over
+/// the attributes of messages, where an empty filter matches every message.
+fn selector(filter: &str) -> crate::filter::Result<Selector<Message>> {
+    let filter = match filter {
+        "" => Filter::parse("true"),
+        filter => Filter::parse(filter),
+    }?;
+    Ok(Selector::new(filter, |msg: &Message| &msg.attributes))
+}
+
+/// The Topic service implementation. Clones share the same registries.
+#[derive(Debug, Clone)]
 pub struct Handler {
     topic_registry: Registry<Message>,
+    auditor: Auditor,
+    membership: Option<Membership>,
+    schemas: schema::Registry,
+    keyring: Option<Keyring>,
+    default_subscription: String,
+    events: Emitter,
+    shutdown: Shutdown,
 }
 
 impl Handler {
@@ -39,47 +107,269 @@ impl Handler {
 
     /// Create a new handler with a predefined registry.
     pub fn with_registry(topic_registry: Registry<Message>) -> Self {
-        Handler { topic_registry }
+        Handler {
+            topic_registry,
+            auditor: Auditor::default(),
+            membership: None,
+            schemas: schema::Registry::default(),
+            keyring: None,
+            default_subscription: String::from(DEFAULT_SUBSCRIPTION_TEMPLATE),
+            events: Emitter::default(),
+            shutdown: Shutdown::default(),
+        }
+    }
+
+    /// Record all control-plane operations handled by this handler with the supplied auditor.
+    pub fn with_auditor(mut self, auditor: Auditor) -> Self {
+        self.auditor = auditor;
+        self
+    }
+
+    /// Forward requests for topics owned by other members of the supplied membership to
+    /// their owner.
+    pub fn with_membership(mut self, membership: Membership) -> Self {
+        self.membership = Some(membership);
+        self
+    }
+
+    /// Return how to forward the supplied request for the supplied topic, along with a client
+    /// connected to its owner, or [None] if it must be handled locally.
+    fn forward<T>(
+        &self,
+        request: &Request<T>,
+        topic: &str,
+    ) -> Result<Option<(Forward, TopicServiceClient<Channel>)>, Status> {
+        let forward = Forward::new(self.membership.as_ref(), request, topic)?;
+        Ok(forward.map(|forward| {
+            let client = TopicServiceClient::new(forward.channel.clone());
+            (forward, client)
+        }))
+    }
+
+    /// Bind the schemas of created topics within the supplied schema registry.
+    pub fn with_schemas(mut self, schemas: schema::Registry) -> Self {
+        self.schemas = schemas;
+        self
+    }
+
+    /// Generate the data keys of topics created as encrypted within the supplied keyring.
+    pub fn with_keyring(mut self, keyring: Keyring) -> Self {
+        self.keyring = Some(keyring);
+        self
+    }
+
+    /// Name the default subscriptions of topics with the supplied template, where every
+    /// `{topic}` is replaced by the name of the topic within its namespace.
+    pub fn with_default_subscription(mut self, template: String) -> Self {
+        self.default_subscription = template;
+        self
+    }
+
+    /// Publish the creation, update and deletion of topics, along with the creation of their
+    /// default subscriptions, with the supplied emitter, which is also watched by the watch
+    /// RPC.
+    pub fn with_events(mut self, events: Emitter) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// End open watch streams once the supplied shutdown begins.
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    fn is_encrypted(&self, name: &str) -> bool {
+        self.keyring
+            .as_ref()
+            .map(|keyring| keyring.is_encrypted(name))
+            .unwrap_or(false)
+    }
+
+    /// Convert the supplied inner topic, including the schema bound to it if any.
+    fn topic(&self, name: String, topic: crate::pubsub::Topic<Message>) -> Topic {
+        describe(&self.schemas, &self.keyring, name, topic)
     }
 
     async fn _create(&self, request: Request<CreateRequest>) -> Result<Response<Topic>, Status> {
+        if let Some((forward, mut client)) = self.forward(&request, &request.get_ref().name)? {
+            let response = client.create(forward.request(request)).await?;
+            return Ok(forward.response(response));
+        }
         let request = request.into_inner();
 
-        let topic = self.topic_registry.create(request.name.clone());
-        Ok(Response::new(Topic::from_inner(request.name, topic)))
+        let schema = match request.schema.map(RiftSchema::try_from).transpose() {
+            Ok(schema) => schema,
+            Err(err) => return Err(crate::Error::from(err).into()),
+        };
+
+        if request.encrypted {
+            let keyring = match &self.keyring {
+                Some(keyring) => keyring,
+                None => return invalid_argument("encryption requires a configured master key"),
+            };
+            // Existing messages would remain in the clear, so only new topics can be encrypted.
+            if self.topic_registry.get(&request.name).is_some() && !self.is_encrypted(&request.name)
+            {
+                return invalid_argument("encryption can only be enabled on new topics");
+            }
+            keyring.enable(&request.name).map_err(crate::Error::from)?;
+        }
+
+        let existed = self.topic_registry.get(&request.name).is_some();
+        let topic = match self.topic_registry.try_create(request.name.clone()) {
+            Ok(topic) => topic,
+            Err(err) => {
+                // The topic was refused, so drop the data key generated for it.
+                if let (true, Some(keyring)) = (request.encrypted, &self.keyring) {
+                    keyring.remove(&request.name).map_err(crate::Error::from)?;
+                }
+                return Err(crate::Error::from(err).into());
+            }
+        };
+        if !existed {
+            self.events.emit(Kind::TopicCreated, &request.name);
+        }
+        if let Some(schema) = schema {
+            self.schemas.bind(request.name.clone(), schema);
+        }
+        if request.default_subscription {
+            let (_, local) = namespace::split(&request.name);
+            let name = self.default_subscription.replace("{topic}", local);
+            let (_, created) = topic
+                .create_with_push(name.clone(), None)
+                .map_err(crate::Error::from)?;
+            if created {
+                let name = format!("{}/{}", request.name, name);
+                self.events.emit(Kind::SubscriptionCreated, &name);
+            }
+        }
+        Ok(Response::new(self.topic(request.name, topic)))
     }
 
     async fn _get(&self, request: Request<GetRequest>) -> Result<Response<Topic>, Status> {
+        if let Some((forward, mut client)) = self.forward(&request, &request.get_ref().name)? {
+            let response = client.get(forward.request(request)).await?;
+            return Ok(forward.response(response));
+        }
         let request = request.into_inner();
 
         match self.topic_registry.get(&request.name) {
-            Some(topic) => Ok(Response::new(Topic::from_inner(request.name, topic))),
+            Some(topic) => Ok(Response::new(self.topic(request.name, topic))),
             None => topic_not_found(&request.name),
         }
     }
 
-    async fn _list(&self, _request: Request<ListRequest>) -> Result<Response<TopicStream>, Status> {
-        let topics = self.topic_registry.iter(|iter| {
-            let mut topics = iter
-                .map(|(name, topic)| Topic::from_inner(name.clone(), topic.clone()))
-                .collect::<Vec<Topic>>();
-            topics.sort_by_key(|topic| topic.name.clone());
-            topics
-        });
+    async fn _list(&self, request: Request<ListRequest>) -> Result<Response<TopicStream>, Status> {
+        let namespace = match request.get_ref().namespace.as_str() {
+            "" => namespace::DEFAULT_NAMESPACE,
+            namespace => namespace,
+        };
+        let stream = TopicStream {
+            names: self.topic_registry.names_in(namespace),
+            topic_registry: self.topic_registry.clone(),
+            schemas: self.schemas.clone(),
+            keyring: self.keyring.clone(),
+        };
+        Ok(Response::new(stream))
+    }
 
-        let stream = TopicStream(topics);
+    async fn _watch(
+        &self,
+        request: Request<WatchRequest>,
+    ) -> Result<Response<WatchStream<TopicEvent>>, Status> {
+        let watched = match request.into_inner().namespace.as_str() {
+            "" => String::from(namespace::DEFAULT_NAMESPACE),
+            watched => watched.to_owned(),
+        };
+        let handler = self.clone();
+        let stream = watch(&self.events, &self.shutdown, move |event| {
+            let kind = match event.kind {
+                Kind::TopicCreated => EventKind::Created,
+                Kind::TopicUpdated => EventKind::Updated,
+                Kind::TopicDeleted => EventKind::Deleted,
+                _ => return None,
+            };
+            if namespace::of(&event.name) != watched {
+                return None;
+            }
+            let topic = handler
+                .topic_registry
+                .get(&event.name)
+                .map(|topic| handler.topic(event.name.clone(), topic));
+            Some(TopicEvent {
+                kind: kind as i32,
+                name: event.name,
+                time: Some(Timestamp::from(event.time)),
+                topic,
+            })
+        });
         Ok(Response::new(stream))
     }
 
-    async fn _update(&self, _request: Request<UpdateRequest>) -> Result<Response<Topic>, Status> {
-        unimplemented!()
+    async fn _update(&self, request: Request<UpdateRequest>) -> Result<Response<Topic>, Status> {
+        if let Some((forward, mut client)) = self.forward(&request, &request.get_ref().name)? {
+            let response = client.update(forward.request(request)).await?;
+            return Ok(forward.response(response));
+        }
+        let request = request.into_inner();
+
+        let topic = match self.topic_registry.get(&request.name) {
+            Some(topic) => topic,
+            None => return topic_not_found(&request.name),
+        };
+        let mut routes = Vec::with_capacity(request.routes.len());
+        for route in request.routes {
+            if route.subscription.is_empty() {
+                return invalid_argument("every route requires a subscription");
+            }
+            let selector = match selector(&route.filter) {
+                Ok(selector) => selector,
+                Err(err) => return invalid_argument(&err.to_string()),
+            };
+            routes.push(Route::new(route.subscription, selector));
+        }
+        let mut forwards = Vec::with_capacity(request.forwards.len());
+        for rule in request.forwards {
+            if rule.topic.is_empty() || rule.topic == request.name {
+                return invalid_argument("every forwarding rule requires another topic");
+            }
+            let reserved = rule.attributes.iter().any(|(from, to)| {
+                from.starts_with(RESERVED_ATTRIBUTE_PREFIX)
+                    || to.starts_with(RESERVED_ATTRIBUTE_PREFIX)
+            });
+            if reserved {
+                return invalid_argument("reserved attributes can not be renamed");
+            }
+            let selector = match selector(&rule.filter) {
+                Ok(selector) => selector,
+                Err(err) => return invalid_argument(&err.to_string()),
+            };
+            forwards.push(ForwardRule::new(rule.topic, selector, rule.attributes));
+        }
+        topic.set_routes(routes);
+        topic.set_forward_rules(forwards);
+        self.events.emit(Kind::TopicUpdated, &request.name);
+        Ok(Response::new(self.topic(request.name, topic)))
     }
 
     async fn _delete(&self, request: Request<DeleteRequest>) -> Result<Response<Topic>, Status> {
+        if let Some((forward, mut client)) = self.forward(&request, &request.get_ref().name)? {
+            let response = client.delete(forward.request(request)).await?;
+            return Ok(forward.response(response));
+        }
         let request = request.into_inner();
 
         match self.topic_registry.delete(&request.name) {
-            Some(topic) => Ok(Response::new(Topic::from_inner(request.name, topic))),
+            Some(topic) => {
+                self.events.emit(Kind::TopicDeleted, &request.name);
+                let res = self.topic(request.name.clone(), topic);
+                self.schemas.unbind(&request.name);
+                if let Some(keyring) = &self.keyring {
+                    keyring.remove(&request.name).map_err(crate::Error::from)?;
+                }
+                Ok(Response::new(res))
+            }
             None => topic_not_found(&request.name),
         }
     }
@@ -95,7 +385,9 @@ impl Default for Handler {
 impl TopicService for Handler {
     #[inline]
     async fn create(&self, request: Request<CreateRequest>) -> Result<Response<Topic>, Status> {
-        self._create(request).await
+        let name = request.get_ref().name.clone();
+        let event = Event::new(&request, Action::Create, Resource::Topic, name);
+        self.auditor.audit(event, self._create(request)).await
     }
 
     #[inline]
@@ -116,14 +408,28 @@ impl TopicService for Handler {
         self._list(request).await
     }
 
+    type WatchStream = WatchStream<TopicEvent>;
+
+    #[inline]
+    async fn watch(
+        &self,
+        request: Request<WatchRequest>,
+    ) -> Result<Response<Self::WatchStream>, Status> {
+        self._watch(request).await
+    }
+
     #[inline]
     async fn update(&self, request: Request<UpdateRequest>) -> Result<Response<Topic>, Status> {
-        self._update(request).await
+        let name = request.get_ref().name.clone();
+        let event = Event::new(&request, Action::Update, Resource::Topic, name);
+        self.auditor.audit(event, self._update(request)).await
     }
 
     #[inline]
     async fn delete(&self, request: Request<DeleteRequest>) -> Result<Response<Topic>, Status> {
-        self._delete(request).await
+        let name = request.get_ref().name.clone();
+        let event = Event::new(&request, Action::Delete, Resource::Topic, name);
+        self.auditor.audit(event, self._delete(request)).await
     }
 }
 
@@ -132,12 +438,80 @@ impl TopicService for Handler {
 mod tests {
     use super::*;
 
+    use crate::grpc::forward::FORWARDED_METADATA_KEY;
+
     macro_rules! aw {
         ($e:expr) => {
             tokio_test::block_on($e)
         };
     }
 
+    #[test]
+    fn test_forward() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let owner = runtime.block_on(crate::testing::Fixture::start()).unwrap();
+        let cfg = crate::cluster::Config {
+            node_id: Some(String::from("one")),
+            advertise_addr: None,
+            join: Vec::new(),
+            secret: None,
+            discovery_interval_ms: 30000,
+            heartbeat_interval_ms: 1000,
+            suspect_timeout_ms: 5000,
+            dead_timeout_ms: 30000,
+            evict_timeout_ms: 300000,
+            vnodes: 64,
+        };
+        let membership = Membership::new(&cfg, &"127.0.0.1:8081".parse().unwrap()).unwrap();
+        membership.merge(vec![crate::cluster::Member::new(
+            String::from("two"),
+            owner.addr.to_string(),
+            1,
+        )]);
+        let handler = Handler::default().with_membership(membership.clone());
+
+        let name = (0..100)
+            .map(|topic| format!("topic-{}", topic))
+            .find(|topic| !membership.is_local(topic))
+            .unwrap();
+        let req = || {
+            Request::new(CreateRequest {
+                name: name.clone(),
+                schema: None,
+                encrypted: false,
+                default_subscription: false,
+            })
+        };
+
+        // Requests for topics owned by another member are handled by that member.
+        let res = runtime.block_on(handler.create(req())).unwrap();
+        assert_eq!(
+            res.metadata()
+                .get(crate::error::OWNER_METADATA_KEY)
+                .unwrap()
+                .to_str()
+                .unwrap(),
+            owner.addr.to_string()
+        );
+        assert!(owner.registry.get(&name).is_some());
+        assert!(handler.topic_registry.get(&name).is_none());
+        let res = runtime.block_on(handler.get(Request::new(GetRequest { name: name.clone() })));
+        assert_eq!(res.unwrap().into_inner().name, name);
+        let res =
+            runtime.block_on(handler.delete(Request::new(DeleteRequest { name: name.clone() })));
+        assert!(res.is_ok());
+        assert!(owner.registry.get(&name).is_none());
+
+        // Requests merely claiming to be forwarded are refused with the owner instead.
+        let mut forwarded = req();
+        forwarded
+            .metadata_mut()
+            .insert(FORWARDED_METADATA_KEY, "two".parse().unwrap());
+        let status = runtime.block_on(handler.create(forwarded)).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+        assert!(handler.topic_registry.get(&name).is_none());
+    }
+
     #[test]
     fn test_happy_path() {
         let handler = Handler::default();
@@ -146,6 +520,9 @@ mod tests {
 
         let create_req = CreateRequest {
             name: topic_name.clone(),
+            schema: None,
+            encrypted: false,
+            default_subscription: false,
         };
         let req = Request::new(create_req);
         let res = aw!(handler.create(req));
@@ -156,6 +533,9 @@ mod tests {
 
         let create_req = CreateRequest {
             name: second_topic_name.clone(),
+            schema: None,
+            encrypted: false,
+            default_subscription: false,
         };
         let req = Request::new(create_req);
         let res = aw!(handler.create(req));
@@ -174,7 +554,9 @@ mod tests {
         let actual = actual.get_ref();
         assert_eq!(topic_name, actual.name);
 
-        let list_req = ListRequest {};
+        let list_req = ListRequest {
+            namespace: String::new(),
+        };
         let req = Request::new(list_req);
         let res = aw!(handler.list(req));
         assert!(res.is_ok());
@@ -217,4 +599,341 @@ mod tests {
         let actual = aw!(handler.delete(req));
         assert!(actual.is_ok());
     }
+
+    #[test]
+    fn test_default_subscription() {
+        let handler = Handler::default().with_default_subscription(String::from("{topic}-sub"));
+        let create_req = |name: &str, default_subscription| CreateRequest {
+            name: String::from(name),
+            schema: None,
+            encrypted: false,
+            default_subscription,
+        };
+
+        assert!(aw!(handler.create(Request::new(create_req("plain", false)))).is_ok());
+        let topic = handler.topic_registry.get("plain").unwrap();
+        assert!(topic.names().is_empty());
+
+        assert!(aw!(handler.create(Request::new(create_req("orders", true)))).is_ok());
+        let topic = handler.topic_registry.get("orders").unwrap();
+        assert_eq!(topic.names(), vec![Arc::from("orders-sub")]);
+
+        // Recreating the topic keeps its existing default subscription.
+        assert!(aw!(handler.create(Request::new(create_req("orders", true)))).is_ok());
+        assert_eq!(topic.names().len(), 1);
+
+        // Default subscriptions are named after the topic within its namespace.
+        let req = create_req("billing/invoices", true);
+        assert!(aw!(handler.create(Request::new(req))).is_ok());
+        let topic = handler.topic_registry.get("billing/invoices").unwrap();
+        assert_eq!(topic.names(), vec![Arc::from("invoices-sub")]);
+    }
+
+    #[test]
+    fn test_namespaces() {
+        let handler = Handler::default();
+        let create_req = |name: &str| CreateRequest {
+            name: String::from(name),
+            schema: None,
+            encrypted: false,
+            default_subscription: false,
+        };
+        for name in [
+            "orders",
+            "billing/orders",
+            "billing/invoices",
+            "shipping/orders",
+        ] {
+            assert!(aw!(handler.create(Request::new(create_req(name)))).is_ok());
+        }
+        let status = aw!(handler.create(Request::new(create_req("default/orders")))).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        let list = |namespace: &str| {
+            let req = ListRequest {
+                namespace: String::from(namespace),
+            };
+            let mut stream = aw!(handler.list(Request::new(req))).unwrap().into_inner();
+            let mut names = Vec::new();
+            while let Some(topic) = aw!(futures::StreamExt::next(&mut stream)) {
+                names.push(topic.unwrap().name);
+            }
+            names
+        };
+        assert_eq!(list(""), vec!["orders"]);
+        assert_eq!(list("default"), vec!["orders"]);
+        assert_eq!(list("billing"), vec!["billing/orders", "billing/invoices"]);
+        assert!(list("nope").is_empty());
+    }
+
+    #[test]
+    fn test_watch() {
+        use futures::StreamExt;
+
+        let handler = Handler::default();
+        let watch_req = |namespace: &str| WatchRequest {
+            namespace: String::from(namespace),
+        };
+        let mut stream = aw!(handler.watch(Request::new(watch_req(""))))
+            .unwrap()
+            .into_inner();
+        let mut billing = aw!(handler.watch(Request::new(watch_req("billing"))))
+            .unwrap()
+            .into_inner();
+
+        for name in ["orders", "billing/invoices"] {
+            let create_req = CreateRequest {
+                name: String::from(name),
+                schema: None,
+                encrypted: false,
+                default_subscription: false,
+            };
+            aw!(handler.create(Request::new(create_req))).unwrap();
+        }
+        let update_req = UpdateRequest {
+            name: String::from("orders"),
+            routes: Vec::new(),
+            forwards: Vec::new(),
+        };
+        aw!(handler.update(Request::new(update_req))).unwrap();
+        let delete_req = DeleteRequest {
+            name: String::from("orders"),
+        };
+        aw!(handler.delete(Request::new(delete_req))).unwrap();
+
+        // The topic was deleted before any event was streamed, so none of them describe it.
+        let mut events = Vec::new();
+        for _ in 0..3 {
+            let event = aw!(stream.next()).unwrap().unwrap();
+            events.push((event.kind(), event.name, event.topic.is_some()));
+        }
+        let orders = String::from("orders");
+        assert_eq!(
+            events,
+            vec![
+                (EventKind::Created, orders.clone(), false),
+                (EventKind::Updated, orders.clone(), false),
+                (EventKind::Deleted, orders, false),
+            ]
+        );
+        let event = aw!(billing.next()).unwrap().unwrap();
+        assert_eq!(event.name, "billing/invoices");
+        assert_eq!(event.topic.unwrap().name, "billing/invoices");
+    }
+
+    #[test]
+    fn test_events() {
+        let registry = Registry::default();
+        let cfg = crate::events::Config {
+            system_events: true,
+        };
+        let emitter = Emitter::new(&cfg, &registry);
+        let events = registry
+            .get(crate::events::SYSTEM_EVENTS_TOPIC)
+            .unwrap()
+            .create(String::from("sub"));
+        let handler = Handler::with_registry(registry).with_events(emitter);
+
+        let create_req = CreateRequest {
+            name: String::from("orders"),
+            schema: None,
+            encrypted: false,
+            default_subscription: true,
+        };
+        assert!(aw!(handler.create(Request::new(create_req.clone()))).is_ok());
+        assert!(aw!(handler.create(Request::new(create_req))).is_ok());
+        let delete_req = DeleteRequest {
+            name: String::from("orders"),
+        };
+        assert!(aw!(handler.delete(Request::new(delete_req))).is_ok());
+
+        // Recreating an existing topic emits nothing.
+        let emitted = std::iter::from_fn(|| events.queue.next())
+            .map(|(_, _, msg)| {
+                (
+                    msg.attributes["event"].clone(),
+                    msg.attributes["name"].clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            emitted,
+            vec![
+                (String::from("topic.created"), String::from("orders")),
+                (
+                    String::from("subscription.created"),
+                    String::from("orders/default")
+                ),
+                (String::from("topic.deleted"), String::from("orders")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_routes() {
+        use super::super::proto::Route;
+
+        let handler = Handler::default();
+        let route = |subscription: &str, filter: &str| Route {
+            subscription: String::from(subscription),
+            filter: String::from(filter),
+        };
+        let update_req = |name: &str, routes| UpdateRequest {
+            name: String::from(name),
+            routes,
+            forwards: Vec::new(),
+        };
+
+        let res = aw!(handler.update(Request::new(update_req("orders", Vec::new()))));
+        assert_eq!(res.unwrap_err().code(), tonic::Code::NotFound);
+
+        let topic = handler.topic_registry.create(String::from("orders"));
+        let eu = topic.create(String::from("eu-workers"));
+        let rest = topic.create(String::from("rest"));
+        for routes in [vec![route("", "")], vec![route("eu-workers", "region ==")]] {
+            let res = aw!(handler.update(Request::new(update_req("orders", routes))));
+            assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+        }
+
+        let routes = vec![
+            route("eu-workers", "attributes.region == 'eu'"),
+            route("rest", ""),
+        ];
+        let res = aw!(handler.update(Request::new(update_req("orders", routes)))).unwrap();
+        assert_eq!(res.get_ref().routes[0].filter, "attributes.region == 'eu'");
+        assert_eq!(res.get_ref().routes.len(), 2);
+
+        let msg = |region: &str| Message {
+            attributes: [(String::from("region"), String::from(region))]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        topic.push(msg("eu")).unwrap();
+        topic.push(msg("us")).unwrap();
+        assert_eq!((eu.queue.len(), rest.queue.len()), (1, 1));
+
+        let res = aw!(handler.update(Request::new(update_req("orders", Vec::new())))).unwrap();
+        assert!(res.get_ref().routes.is_empty());
+    }
+
+    #[test]
+    fn test_forwards() {
+        use super::super::proto::ForwardRule;
+
+        let handler = Handler::default();
+        let rule = |topic: &str, filter: &str, from: &str, to: &str| ForwardRule {
+            topic: String::from(topic),
+            filter: String::from(filter),
+            attributes: [(String::from(from), String::from(to))]
+                .into_iter()
+                .collect(),
+        };
+        let update_req = |forwards| UpdateRequest {
+            name: String::from("orders"),
+            routes: Vec::new(),
+            forwards,
+        };
+
+        let topic = handler.topic_registry.create(String::from("orders"));
+        for forwards in [
+            vec![rule("", "", "region", "origin")],
+            vec![rule("orders", "", "region", "origin")],
+            vec![rule("audit", "region ==", "region", "origin")],
+            vec![rule("audit", "", "x-rift-encryption", "origin")],
+            vec![rule("audit", "", "region", "x-rift-claim-check")],
+        ] {
+            let res = aw!(handler.update(Request::new(update_req(forwards))));
+            assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+        }
+        assert!(topic.forward_rules().is_empty());
+
+        let forwards = vec![rule("audit", "attributes.region == 'eu'", "region", "")];
+        let res = aw!(handler.update(Request::new(update_req(forwards.clone())))).unwrap();
+        assert_eq!(res.get_ref().forwards, forwards);
+        assert_eq!(topic.forward_rules()[0].topic(), "audit");
+
+        let res = aw!(handler.update(Request::new(update_req(Vec::new())))).unwrap();
+        assert!(res.get_ref().forwards.is_empty());
+        assert!(topic.forward_rules().is_empty());
+    }
+
+    #[test]
+    fn test_schema() {
+        use super::super::proto::{schema::Kind, JsonSchema};
+
+        let handler = Handler::default();
+        let schema = |schema: &str| {
+            Some(Schema {
+                kind: Some(Kind::Json(JsonSchema {
+                    schema: String::from(schema),
+                })),
+            })
+        };
+
+        let create_req = CreateRequest {
+            name: String::from("invalid"),
+            schema: schema(r#"{"pattern": "^a$"}"#),
+            encrypted: false,
+            default_subscription: false,
+        };
+        let res = aw!(handler.create(Request::new(create_req)));
+        assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+        assert!(handler.topic_registry.get("invalid").is_none());
+
+        let create_req = CreateRequest {
+            name: String::from("json"),
+            schema: schema(r#"{"type": "object"}"#),
+            encrypted: false,
+            default_subscription: false,
+        };
+        assert!(aw!(handler.create(Request::new(create_req))).is_ok());
+
+        let get_req = GetRequest {
+            name: String::from("json"),
+        };
+        let res = aw!(handler.get(Request::new(get_req))).unwrap();
+        assert_eq!(res.get_ref().schema, schema(r#"{"type": "object"}"#));
+        assert!(handler.schemas.validate("json", b"[]").is_err());
+
+        let del_req = DeleteRequest {
+            name: String::from("json"),
+        };
+        assert!(aw!(handler.delete(Request::new(del_req))).is_ok());
+        assert!(handler.schemas.get("json").is_none());
+    }
+
+    #[test]
+    fn test_encrypted() {
+        let create_req = |name: &str| CreateRequest {
+            name: String::from(name),
+            schema: None,
+            encrypted: true,
+            default_subscription: false,
+        };
+        let res = aw!(Handler::default().create(Request::new(create_req("secret"))));
+        assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+
+        let keyring = Keyring::with_master_key(
+            std::sync::Arc::new(crate::encryption::LocalKey::new(&[1; 32]).unwrap()),
+            None,
+        );
+        let handler = Handler::default().with_keyring(keyring.clone());
+        let res = aw!(handler.create(Request::new(create_req("secret")))).unwrap();
+        assert!(res.get_ref().encrypted);
+        assert!(keyring.is_encrypted("secret"));
+        // Re-creating an encrypted topic is a no-op.
+        assert!(aw!(handler.create(Request::new(create_req("secret")))).is_ok());
+
+        handler.topic_registry.create(String::from("plain"));
+        let res = aw!(handler.create(Request::new(create_req("plain"))));
+        assert_eq!(res.unwrap_err().code(), tonic::Code::InvalidArgument);
+        assert!(!keyring.is_encrypted("plain"));
+
+        let del_req = DeleteRequest {
+            name: String::from("secret"),
+        };
+        assert!(aw!(handler.delete(Request::new(del_req))).is_ok());
+        assert!(!keyring.is_encrypted("secret"));
+    }
 }
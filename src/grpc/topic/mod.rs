@@ -2,27 +2,93 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
 mod proto {
+    use std::convert::TryFrom;
+
     use prost_types::Timestamp;
 
     tonic::include_proto!("topic");
 
     impl Topic {
         /// Create a new topic from the supplied topic name and inner topic.
-        pub fn from_inner<T>(name: String, i: crate::pubsub::Topic<T>) -> Self {
+        pub fn from_inner<T>(name: String, i: crate::pubsub::Topic<T>) -> Self
+        where
+            T: Clone,
+        {
+            let routes = i
+                .routes()
+                .iter()
+                .map(|route| Route {
+                    subscription: route.subscription().to_owned(),
+                    filter: route.selector().filter().source().to_owned(),
+                })
+                .collect();
+            let forwards = i
+                .forward_rules()
+                .iter()
+                .map(|rule| ForwardRule {
+                    topic: rule.topic().to_owned(),
+                    filter: rule.selector().filter().source().to_owned(),
+                    attributes: rule.attributes().iter().cloned().collect(),
+                })
+                .collect();
             Self {
                 updated: i.updated.map(Timestamp::from),
                 created: Some(Timestamp::from(i.created)),
                 name,
+                schema: None,
+                encrypted: false,
+                routes,
+                forwards,
             }
         }
     }
+
+    impl TryFrom<Schema> for crate::schema::Schema {
+        type Error = crate::schema::Error;
+
+        fn try_from(schema: Schema) -> crate::schema::Result<Self> {
+            match schema.kind {
+                Some(schema::Kind::Protobuf(protobuf)) => crate::schema::ProtobufSchema::new(
+                    protobuf.descriptor_set,
+                    protobuf.message_type,
+                )
+                .map(crate::schema::Schema::Protobuf),
+                Some(schema::Kind::Json(json)) => {
+                    crate::schema::JsonSchema::new(json.schema).map(crate::schema::Schema::Json)
+                }
+                None => Err(crate::schema::Error::Invalid {
+                    reason: String::from("a schema kind is required"),
+                }),
+            }
+        }
+    }
+
+    impl From<&crate::schema::Schema> for Schema {
+        fn from(schema: &crate::schema::Schema) -> Self {
+            let kind = match schema {
+                crate::schema::Schema::Protobuf(protobuf) => {
+                    schema::Kind::Protobuf(ProtobufSchema {
+                        descriptor_set: protobuf.descriptor_set.clone(),
+                        message_type: protobuf.message_type.clone(),
+                    })
+                }
+                crate::schema::Schema::Json(json) => schema::Kind::Json(JsonSchema {
+                    schema: json.schema.clone(),
+                }),
+            };
+            Self { kind: Some(kind) }
+        }
+    }
 }
 mod handler;
 
 pub(crate) const FILE_DESCRIPTOR_SET: &[u8] =
     tonic::include_file_descriptor_set!("topic_descriptor");
 
-pub use handler::Handler;
+pub use handler::{Handler, DEFAULT_SUBSCRIPTION_TEMPLATE};
 pub use proto::topic_service_client::TopicServiceClient;
-pub use proto::topic_service_server::TopicServiceServer;
-pub use proto::{CreateRequest, DeleteRequest, GetRequest, ListRequest, Topic, UpdateRequest};
+pub use proto::topic_service_server::{TopicService, TopicServiceServer};
+pub use proto::{
+    CreateRequest, DeleteRequest, EventKind, ForwardRule, GetRequest, JsonSchema, ListRequest,
+    ProtobufSchema, Route, Schema, Topic, TopicEvent, UpdateRequest, WatchRequest,
+};
@@ -0,0 +1,96 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0
+
+use std::future::Future;
+use std::pin::Pin;
+
+use futures::future::{self, Either};
+use futures::Stream;
+use tokio::sync::broadcast::error::RecvError;
+use tonic::Status;
+
+use crate::events::{Emitter, Event};
+use crate::shutdown::Shutdown;
+
+/// Streams the changes made to resources, as reported by a watch RPC.
+pub type WatchStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+type Draining = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Stream every event emitted by the supplied emitter from now on, converted by the supplied
+/// function, skipping those it converts to [None]. The stream ends once shutdown begins, and
+/// fails with ABORTED if it falls so far behind that events were missed, in which case the
+/// watcher should list the resources again before watching them again.
+pub fn watch<T, F>(events: &Emitter, shutdown: &Shutdown, convert: F) -> WatchStream<T>
+where
+    T: Send + 'static,
+    F: Fn(Event) -> Option<T> + Send + 'static,
+{
+    let draining: Draining = Box::pin(shutdown.wait());
+    let state = Some((events.watch(), draining, convert));
+    let stream = futures::stream::unfold(state, |state| async move {
+        let (mut receiver, mut draining, convert) = state?;
+        loop {
+            // Draining is polled first, so that no further event is streamed once it began.
+            let received = {
+                let recv = Box::pin(receiver.recv());
+                match future::select(&mut draining, recv).await {
+                    Either::Left(_) => return None,
+                    Either::Right((received, _)) => received,
+                }
+            };
+            let item = match received {
+                Ok(event) => match convert(event) {
+                    Some(item) => Ok(item),
+                    None => continue,
+                },
+                Err(RecvError::Lagged(missed)) => {
+                    let status = Status::aborted(format!(
+                        "the watch fell {} events behind, list and watch again",
+                        missed
+                    ));
+                    return Some((Err(status), None));
+                }
+                Err(RecvError::Closed) => return None,
+            };
+            return Some((item, Some((receiver, draining, convert))));
+        }
+    });
+    Box::pin(stream)
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    use futures::StreamExt;
+
+    use crate::events::Kind;
+
+    #[test]
+    fn test_watch() {
+        let events = Emitter::default();
+        let shutdown = Shutdown::new();
+        let mut stream = watch(&events, &shutdown, |event| match event.kind {
+            Kind::TopicCreated => Some(event.name),
+            _ => None,
+        });
+        events.emit(Kind::SubscriptionCreated, "orders/audit");
+        events.emit(Kind::TopicCreated, "orders");
+        let next = tokio_test::block_on(stream.next()).unwrap();
+        assert_eq!(next.unwrap(), "orders");
+
+        for _ in 0..=crate::events::WATCH_CAPACITY {
+            events.emit(Kind::TopicCreated, "billing");
+        }
+        let next = tokio_test::block_on(stream.next()).unwrap();
+        assert_eq!(next.unwrap_err().code(), tonic::Code::Aborted);
+        assert!(tokio_test::block_on(stream.next()).is_none());
+
+        let mut stream = watch(&events, &shutdown, |event| Some(event.name));
+        shutdown.trigger();
+        events.emit(Kind::TopicCreated, "orders");
+        assert!(tokio_test::block_on(stream.next()).is_none());
+    }
+}
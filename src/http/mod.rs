@@ -2,6 +2,8 @@
 // SPDX-License-Identifier: GPL-3.0
 
 use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::{Duration, UNIX_EPOCH};
 
 // extern usings
 use hyper::{
@@ -9,10 +11,42 @@ use hyper::{
     StatusCode,
 };
 use prometheus::{Encoder, ProtobufEncoder, TextEncoder, PROTOBUF_FORMAT, TEXT_FORMAT};
+use serde_json::json;
+
+// crate usings
+use crate::auth::{self, Action, Auth};
+use crate::grpc::pubsub::Message;
+use crate::log;
+use crate::metric;
+use crate::pubsub::{Registry, SlotInfo, SlotState};
+use crate::shutdown::Shutdown;
+
+/// The path prefix of the queue diagnostics of a subscription, followed by
+/// `{topic}/{subscription}`.
+const DEBUG_QUEUES_PATH: &str = "/debug/queues/";
+/// The most slots listed by the queue diagnostics of a subscription, oldest first.
+const DEBUG_SLOT_LIMIT: usize = 100;
+
+/// The state shared across every HTTP request.
+#[derive(Clone)]
+struct State {
+    logger: slog::Logger,
+    level: log::Handle,
+    shutdown: Shutdown,
+    registry: Registry<Message>,
+    auth: Auth,
+}
 
 async fn metrics(req: Request<Body>) -> Result<Response<Body>, hyper::http::Error> {
     let mut buffer = vec![];
 
+    let accepts = |format: &str| {
+        req.headers()
+            .get_all("accept")
+            .iter()
+            .filter_map(|header| header.to_str().ok())
+            .any(|header| header.contains(format))
+    };
     let accepts_protobuf = req
         .headers()
         .get_all("accept")
@@ -20,7 +54,11 @@ async fn metrics(req: Request<Body>) -> Result<Response<Body>, hyper::http::Erro
         .any(|header| header == PROTOBUF_FORMAT);
 
     let metric_families = prometheus::gather();
-    let content_type = if accepts_protobuf {
+    let content_type = if accepts("application/openmetrics-text") {
+        // Exemplars are only supported by the OpenMetrics format.
+        buffer = metric::encode_openmetrics(&metric_families).into_bytes();
+        metric::OPENMETRICS_FORMAT
+    } else if accepts_protobuf {
         let encoder = ProtobufEncoder::new();
         if encoder.encode(&metric_families, &mut buffer).is_err() {
             return server_error();
@@ -40,7 +78,10 @@ async fn metrics(req: Request<Body>) -> Result<Response<Body>, hyper::http::Erro
         .body(Body::from(buffer))
 }
 
-async fn ready() -> Result<Response<Body>, hyper::http::Error> {
+async fn ready(state: &State) -> Result<Response<Body>, hyper::http::Error> {
+    if state.shutdown.is_triggered() {
+        return unavailable();
+    }
     no_content()
 }
 
@@ -48,6 +89,102 @@ async fn live() -> Result<Response<Body>, hyper::http::Error> {
     no_content()
 }
 
+async fn get_level(state: &State) -> Result<Response<Body>, hyper::http::Error> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .body(Body::from(state.level.get().to_string()))
+}
+
+async fn set_level(
+    req: Request<Body>,
+    state: &State,
+) -> Result<Response<Body>, hyper::http::Error> {
+    let body = match hyper::body::to_bytes(req.into_body()).await {
+        Ok(body) => body,
+        Err(_) => return server_error(),
+    };
+    let level = match std::str::from_utf8(&body)
+        .ok()
+        .and_then(|level| log::Level::from_str(level.trim()).ok())
+    {
+        Some(level) => level,
+        None => return bad_request(),
+    };
+
+    let prev = state.level.set(&level);
+    info!(&state.logger, "Switched log level."; "from" => prev.to_string(), "to" => level.to_string());
+    no_content()
+}
+
+/// Describe the slots of the queue of a subscription, counting them by state and listing the
+/// oldest messages along with their attempts and lease deadlines, to explain where a backlog
+/// is stuck.
+async fn debug_queue(path: &str, state: &State) -> Result<Response<Body>, hyper::http::Error> {
+    let (topic, name) = match path.rsplit_once('/') {
+        Some((topic, name)) if !topic.is_empty() && !name.is_empty() => (topic, name),
+        _ => return not_found(),
+    };
+    let queue = match state.registry.get(topic).and_then(|topic| topic.get(name)) {
+        Some(sub) => sub.queue,
+        None => return not_found(),
+    };
+
+    let (mut empty, mut filled, mut locked, mut delayed, mut expired) = (0, 0, 0, 0, 0);
+    let mut slots = Vec::new();
+    queue.inspect(|info| {
+        match info.state {
+            SlotState::Empty => empty += 1,
+            SlotState::Filled => filled += 1,
+            SlotState::Locked => locked += 1,
+        }
+        delayed += info.delayed.is_some() as usize;
+        expired += info.is_expired() as usize;
+        if info.state != SlotState::Empty {
+            slots.push(info);
+        }
+    });
+    slots.sort_unstable_by_key(|info| info.sequence);
+    slots.truncate(DEBUG_SLOT_LIMIT);
+
+    let body = json!({
+        "topic": topic,
+        "subscription": name,
+        "outstanding": queue.outstanding(),
+        "max_outstanding": queue.max_outstanding(),
+        "counts": {
+            "empty": empty,
+            "filled": filled,
+            "locked": locked,
+            "delayed": delayed,
+            "expired": expired,
+        },
+        "slots": slots.iter().map(describe).collect::<Vec<_>>(),
+    });
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+}
+
+/// Describe a single slot, with durations and times in whole milliseconds.
+fn describe(info: &SlotInfo) -> serde_json::Value {
+    let millis = |duration: Duration| duration.as_millis() as u64;
+    json!({
+        "index": info.index,
+        "state": info.state.to_string(),
+        "sequence": info.sequence,
+        "age_ms": info.age.map(millis),
+        "attempts": info.attempts,
+        "delayed_ms": info.delayed.map(millis),
+        "lease_deadline_ms": info
+            .lease_deadline
+            .and_then(|deadline| deadline.duration_since(UNIX_EPOCH).ok())
+            .map(millis),
+        "owner": info.owner.as_deref(),
+        "expired": info.is_expired(),
+    })
+}
+
 #[inline]
 fn no_content() -> Result<Response<Body>, hyper::http::Error> {
     Response::builder()
@@ -62,6 +199,31 @@ fn server_error() -> Result<Response<Body>, hyper::http::Error> {
         .body(Body::from("Internal Server Error"))
 }
 
+#[inline]
+fn bad_request() -> Result<Response<Body>, hyper::http::Error> {
+    Response::builder()
+        .status(StatusCode::BAD_REQUEST)
+        .body(Body::from("Bad Request"))
+}
+
+#[inline]
+fn unavailable() -> Result<Response<Body>, hyper::http::Error> {
+    Response::builder()
+        .status(StatusCode::SERVICE_UNAVAILABLE)
+        .body(Body::from("Service Unavailable"))
+}
+
+#[inline]
+fn unauthorized(err: auth::Error) -> Result<Response<Body>, hyper::http::Error> {
+    let status = match err {
+        auth::Error::PermissionDenied { .. } => StatusCode::FORBIDDEN,
+        _ => StatusCode::UNAUTHORIZED,
+    };
+    Response::builder()
+        .status(status)
+        .body(Body::from(err.to_string()))
+}
+
 #[inline]
 fn not_found() -> Result<Response<Body>, hyper::http::Error> {
     Response::builder()
@@ -69,18 +231,50 @@ fn not_found() -> Result<Response<Body>, hyper::http::Error> {
         .body(Body::from("Not Found"))
 }
 
-async fn router(req: Request<Body>) -> Result<Response<Body>, hyper::http::Error> {
+async fn router(req: Request<Body>, state: State) -> Result<Response<Body>, hyper::http::Error> {
+    let headers = req.headers();
+    let checked = state.auth.check(
+        |name| headers.get(name).and_then(|value| value.to_str().ok()),
+        Action::of_http(req.uri().path()),
+    );
+    if let Err(err) = checked {
+        return unauthorized(err);
+    }
     match (req.method(), req.uri().path()) {
         (&Method::GET, "/metrics") => metrics(req).await,
         (&Method::GET, "/live") => live().await,
-        (&Method::GET, "/ready") => ready().await,
+        (&Method::GET, "/ready") => ready(&state).await,
+        (&Method::GET, "/log/level") => get_level(&state).await,
+        (&Method::PUT, "/log/level") => set_level(req, &state).await,
+        (&Method::GET, path) if path.starts_with(DEBUG_QUEUES_PATH) => {
+            debug_queue(&path[DEBUG_QUEUES_PATH.len()..], &state).await
+        }
         _ => not_found(),
     }
 }
 
-/// Listen for HTTP requests.
-pub async fn listen(addr: &SocketAddr) -> Result<(), hyper::Error> {
-    let svc = make_service_fn(|_| async { Ok::<_, hyper::http::Error>(service_fn(router)) });
+/// Listen for HTTP requests, using the supplied handle to serve the runtime log level and the
+/// supplied registry to serve queue diagnostics. Readiness is withdrawn once the supplied
+/// coordinator begins shutting down. Every request is checked by the supplied auth.
+pub async fn listen(
+    addr: &SocketAddr,
+    logger: slog::Logger,
+    level: log::Handle,
+    shutdown: Shutdown,
+    registry: Registry<Message>,
+    auth: Auth,
+) -> Result<(), hyper::Error> {
+    let state = State {
+        logger,
+        level,
+        shutdown,
+        registry,
+        auth,
+    };
+    let svc = make_service_fn(move |_| {
+        let state = state.clone();
+        async move { Ok::<_, hyper::http::Error>(service_fn(move |req| router(req, state.clone()))) }
+    });
     let srv = Server::bind(addr).serve(svc);
     srv.await?;
     Ok(())
@@ -97,6 +291,16 @@ mod tests {
         };
     }
 
+    fn state() -> State {
+        State {
+            logger: slog::Logger::root(slog::Discard, o!()),
+            level: log::Handle::new(&log::Level::Info),
+            shutdown: Shutdown::new(),
+            registry: Registry::default(),
+            auth: Auth::default(),
+        }
+    }
+
     #[test]
     fn test_not_found() {
         let req = Request::builder()
@@ -105,7 +309,7 @@ mod tests {
             .body(Body::empty())
             .expect("failed to generate /nope request");
 
-        let res = aw!(router(req));
+        let res = aw!(router(req, state()));
         assert!(res.is_ok());
         let res = res.unwrap();
         assert_eq!(res.status(), StatusCode::NOT_FOUND);
@@ -135,10 +339,66 @@ mod tests {
             .body(Body::empty())
             .expect("failed to generate /live request");
 
-        let res = aw!(router(req));
+        let state = state();
+        let res = aw!(router(req, state.clone()));
         assert!(res.is_ok());
         let res = res.unwrap();
         assert_eq!(res.status(), StatusCode::NO_CONTENT);
+
+        state.shutdown.trigger();
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri("/ready")
+            .body(Body::empty())
+            .expect("failed to generate /ready request");
+        let res = aw!(router(req, state)).unwrap();
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[test]
+    fn test_debug_queue() {
+        let state = state();
+        let topic = state.registry.create(String::from("topic"));
+        let sub = topic.create(String::from("sub"));
+        let msg = |data: &'static [u8]| Message {
+            topic: String::from("topic"),
+            data: data.into(),
+            ..Default::default()
+        };
+        sub.queue.push(msg(b"first")).unwrap();
+        sub.queue.push(msg(b"second")).unwrap();
+        sub.queue.next().unwrap();
+
+        let get = |uri: &str| {
+            let req = Request::builder()
+                .method(Method::GET)
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap();
+            aw!(router(req, state.clone())).unwrap()
+        };
+        assert_eq!(
+            get("/debug/queues/topic/nope").status(),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(get("/debug/queues/topic").status(), StatusCode::NOT_FOUND);
+
+        let res = get("/debug/queues/topic/sub");
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = aw!(hyper::body::to_bytes(res.into_body())).unwrap();
+        let body = serde_json::from_slice::<serde_json::Value>(&body).unwrap();
+        assert_eq!(body["outstanding"], 1);
+        assert_eq!(
+            body["counts"],
+            json!({"empty": 0, "filled": 1, "locked": 1, "delayed": 0, "expired": 0})
+        );
+        let slots = body["slots"].as_array().unwrap();
+        assert_eq!(slots.len(), 2);
+        assert_eq!(slots[0]["state"], "locked");
+        assert_eq!(slots[0]["attempts"], 1);
+        assert!(slots[0]["lease_deadline_ms"].is_u64());
+        assert_eq!(slots[1]["state"], "filled");
+        assert!(slots[1]["lease_deadline_ms"].is_null());
     }
 
     #[test]
@@ -149,12 +409,45 @@ mod tests {
             .body(Body::empty())
             .expect("failed to generate /live request");
 
-        let res = aw!(router(req));
+        let res = aw!(router(req, state()));
         assert!(res.is_ok());
         let res = res.unwrap();
         assert_eq!(res.status(), StatusCode::NO_CONTENT);
     }
 
+    #[test]
+    fn test_log_level() {
+        let state = state();
+
+        let req = Request::builder()
+            .method(Method::PUT)
+            .uri("/log/level")
+            .body(Body::from("debug"))
+            .expect("failed to generate /log/level request");
+        let res = aw!(router(req, state.clone())).unwrap();
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert_eq!(state.level.get(), log::Level::Debug);
+
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri("/log/level")
+            .body(Body::empty())
+            .expect("failed to generate /log/level request");
+        let res = aw!(router(req, state.clone())).unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = aw!(hyper::body::to_bytes(res.into_body())).unwrap();
+        assert_eq!(&body[..], b"debug");
+
+        let req = Request::builder()
+            .method(Method::PUT)
+            .uri("/log/level")
+            .body(Body::from("nope"))
+            .expect("failed to generate /log/level request");
+        let res = aw!(router(req, state.clone())).unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(state.level.get(), log::Level::Debug);
+    }
+
     #[test]
     fn test_metrics_proto() {
         let req = Request::builder()
@@ -164,13 +457,32 @@ mod tests {
             .body(Body::empty())
             .expect("failed to generate metrics request");
 
-        let res = aw!(router(req));
+        let res = aw!(router(req, state()));
         assert!(res.is_ok());
         let res = res.unwrap();
 
         assert_eq!(res.status(), StatusCode::OK);
     }
 
+    #[test]
+    fn test_metrics_openmetrics() {
+        let req = Request::builder()
+            .header("accept", "application/openmetrics-text; version=1.0.0")
+            .method(Method::GET)
+            .uri("/metrics")
+            .body(Body::empty())
+            .expect("failed to generate metrics request");
+
+        let res = aw!(router(req, state())).unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers().get("content-type").unwrap(),
+            metric::OPENMETRICS_FORMAT
+        );
+        let body = aw!(hyper::body::to_bytes(res.into_body())).unwrap();
+        assert!(body.ends_with(b"# EOF\n"));
+    }
+
     #[test]
     fn test_metrics_text() {
         let req = Request::builder()
@@ -179,10 +491,44 @@ mod tests {
             .body(Body::empty())
             .expect("failed to generate metrics request");
 
-        let res = aw!(router(req));
+        let res = aw!(router(req, state()));
         assert!(res.is_ok());
         let res = res.unwrap();
 
         assert_eq!(res.status(), StatusCode::OK);
     }
+
+    #[test]
+    fn test_auth() {
+        let policy = auth::Policy::parse(
+            br#"{"identities": [
+                {"name": "ops", "tokens": ["t1"], "actions": ["operate"]},
+                {"name": "app", "tokens": ["t2"], "actions": ["publish"]}
+            ]}"#,
+        )
+        .unwrap();
+        let state = State {
+            auth: Auth::with_policy(policy),
+            ..state()
+        };
+        let req = |uri: &str, token: Option<&str>| {
+            let mut req = Request::builder().method(Method::GET).uri(uri);
+            if let Some(token) = token {
+                req = req.header("authorization", format!("Bearer {}", token));
+            }
+            req.body(Body::empty()).unwrap()
+        };
+
+        let cases = [
+            ("/ready", None, StatusCode::NO_CONTENT),
+            ("/log/level", None, StatusCode::FORBIDDEN),
+            ("/log/level", Some("nope"), StatusCode::UNAUTHORIZED),
+            ("/log/level", Some("t2"), StatusCode::FORBIDDEN),
+            ("/log/level", Some("t1"), StatusCode::OK),
+        ];
+        for (uri, token, status) in cases {
+            let res = aw!(router(req(uri, token), state.clone())).unwrap();
+            assert_eq!(res.status(), status, "{} {:?}", uri, token);
+        }
+    }
 }
@@ -8,20 +8,60 @@
 // macro usings
 #[macro_use]
 extern crate slog;
-#[macro_use]
-extern crate prometheus;
 
+/// Audit logging of control-plane operations.
+pub mod audit;
+/// Authentication and authorization shared by the gRPC and HTTP servers.
+pub mod auth;
+/// Claim checks moving oversized message payloads out of queue memory.
+pub mod claimcheck;
+/// High-level clients for publishing to, and subscribing to, rift.
+pub mod client;
+/// Cluster membership and failure detection.
+pub mod cluster;
+/// Envelope encryption of message payloads with per-topic data keys.
+pub mod encryption;
+/// The crate-wide error type and its stable codes.
+pub mod error;
+/// Lifecycle events published for tooling to react to.
+pub mod events;
+/// Attribute filter expressions selecting the messages a subscription receives.
+pub mod filter;
 /// The main gRPC server/client implementations.
 pub mod grpc;
 /// Debugging/Control Plane HTTP handling.
 pub mod http;
 /// General log related functionality, based ontop of the [slog] ecosystem.
 pub mod log;
+/// Declarative topology manifests reconciled by riftd.
+pub mod manifest;
 /// Prometheus metrics logic and handling.
 pub mod metric;
+/// Pid files guarding against running riftd twice.
+pub mod pidfile;
 /// Pubsub implementation.
 pub mod pubsub;
+/// Push delivery of subscription messages to remote endpoints.
+pub mod push;
+/// Reloading of runtime-tunable settings without restarting riftd.
+pub mod reload;
 /// Entrypoint logic for riftctl.
 pub mod riftctl;
 /// Entrypoint logic for riftd.
 pub mod riftd;
+/// Sizing of the async runtime riftd runs on.
+pub mod runtime;
+/// Per-topic schemas that published message data is validated against.
+pub mod schema;
+/// Coordinated shutdown of riftd.
+pub mod shutdown;
+/// Source connectors publishing external data to topics.
+pub mod source;
+/// Per-tenant usage accounting and quotas, where each namespace of topics is a tenant.
+pub mod tenant;
+/// In-process fixtures for integration testing against the rift gRPC services.
+pub mod testing;
+/// Distributed tracing functionality, based ontop of the [opentelemetry] ecosystem.
+pub mod trace;
+
+pub use error::{Error, Result};
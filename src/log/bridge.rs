@@ -0,0 +1,102 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::fmt::{self, Write};
+use std::result;
+
+// extern usings
+use slog::{Drain, KV};
+
+/// The tracing target that all forwarded slog records are emitted under.
+pub const TARGET: &str = "riftdb";
+
+/// Serializes slog key/value pairs into a single `key=value` formatted string, as tracing
+/// events require their field names to be known statically.
+#[derive(Default)]
+struct KvSerializer(String);
+
+impl slog::Serializer for KvSerializer {
+    fn emit_arguments(&mut self, key: slog::Key, val: &fmt::Arguments) -> slog::Result {
+        if !self.0.is_empty() {
+            self.0.push(' ');
+        }
+        write!(self.0, "{}={}", key, val)?;
+        Ok(())
+    }
+}
+
+/// A drain which forwards slog records into the tracing ecosystem as events, so that they
+/// are emitted alongside, and nested within, the spans of any tracing instrumented
+/// middleware. The key/value pairs of each record, including the logger's own values such
+/// as request IDs, are forwarded as a single `kv` field.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TracingDrain;
+
+impl Drain for TracingDrain {
+    type Ok = ();
+    type Err = slog::Never;
+
+    fn log(
+        &self,
+        record: &slog::Record,
+        values: &slog::OwnedKVList,
+    ) -> result::Result<Self::Ok, Self::Err> {
+        let mut kv = KvSerializer::default();
+        let _ = record.kv().serialize(record, &mut kv);
+        let _ = values.serialize(record, &mut kv);
+        let kv = kv.0;
+
+        macro_rules! forward {
+            ($level:expr) => {
+                tracing::event!(
+                    target: TARGET,
+                    $level,
+                    module = record.module(),
+                    kv = %kv,
+                    "{}",
+                    record.msg()
+                )
+            };
+        }
+        match record.level() {
+            slog::Level::Critical | slog::Level::Error => forward!(tracing::Level::ERROR),
+            slog::Level::Warning => forward!(tracing::Level::WARN),
+            slog::Level::Info => forward!(tracing::Level::INFO),
+            slog::Level::Debug => forward!(tracing::Level::DEBUG),
+            slog::Level::Trace => forward!(tracing::Level::TRACE),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kv_serializer() {
+        let logger = slog::Logger::root(slog::Discard, o!("reqID" => "woot"));
+        let serialize = |record: &slog::Record| {
+            let mut kv = KvSerializer::default();
+            record.kv().serialize(record, &mut kv).unwrap();
+            logger.list().serialize(record, &mut kv).unwrap();
+            kv.0
+        };
+        let kv = serialize(&record!(
+            slog::Level::Info,
+            "",
+            &format_args!("msg"),
+            b!("key" => 1)
+        ));
+        assert_eq!(kv, "key=1 reqID=woot");
+    }
+
+    #[test]
+    fn test_tracing_drain() {
+        let logger = slog::Logger::root(TracingDrain.fuse(), o!("reqID" => "woot"));
+        info!(&logger, "Info"; "key" => "value");
+        crit!(&logger, "Crit");
+    }
+}
@@ -33,4 +33,14 @@ pub struct Config {
     )]
     /// Define whether or not to log in json format.
     pub json: bool,
+
+    #[structopt(
+        long = "log-tracing",
+        env = "RIFT_LOG_TRACING",
+        help = "Whether or not to forward logs through the tracing ecosystem.",
+        long_help = "Sets whether or not to forward all application logs as tracing events, so that they are emitted in one stream alongside the spans of the gRPC server.",
+        takes_value = false
+    )]
+    /// Define whether or not to forward logs through the tracing ecosystem.
+    pub tracing: bool,
 }
@@ -20,4 +20,10 @@ pub enum Error {
         /// level represents the level that was configued but unimplemented.
         level: String,
     },
+    /// Handles errors installing the global tracing subscriber.
+    #[error("failed to initialize tracing subscriber: {reason}")]
+    TracingInit {
+        /// reason represents the underlying failure reason.
+        reason: String,
+    },
 }
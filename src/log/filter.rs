@@ -7,11 +7,15 @@ use std::result;
 // extern usings
 use slog::Drain;
 
+// super usings
+use super::Handle;
+
 /// Wraps a standard slog Drain so that we can filter the messages
-/// logged by the defined log handler.
+/// logged by the defined log handler. The threshold is read through a [Handle] so that it
+/// can be switched at runtime.
 pub struct LevelFilter<D> {
     pub drain: D,
-    pub level: slog::Level,
+    pub level: Handle,
 }
 
 impl<D> Drain for LevelFilter<D>
@@ -27,7 +31,7 @@ where
         record: &slog::Record,
         values: &slog::OwnedKVList,
     ) -> result::Result<Self::Ok, Self::Err> {
-        if record.level().is_at_least(self.level) {
+        if self.level.enabled(record.level()) {
             self.drain.log(record, values).map(Some).map_err(Some)
         } else {
             Ok(None)
@@ -43,14 +47,18 @@ mod tests {
     #[test]
     fn test_filter() {
         let drain = slog::Discard {};
+        let level = Handle::new(&crate::log::Level::Info);
         let filter = LevelFilter {
             drain,
-            level: slog::Level::Info,
+            level: level.clone(),
         }
         .fuse();
         let logger = slog::Logger::root(filter, o!());
 
         info!(&logger, "Info");
         debug!(&logger, "Debug");
+
+        level.set(&crate::log::Level::Debug);
+        debug!(&logger, "Debug");
     }
 }
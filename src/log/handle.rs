@@ -0,0 +1,89 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// crate usings
+use super::Level;
+
+/// A handle to the threshold of a running logger, which allows switching the level of logs
+/// emitted without rebuilding the logger or restarting the process.
+#[derive(Debug, Clone)]
+pub struct Handle {
+    level: Arc<AtomicUsize>,
+}
+
+impl Handle {
+    /// Create a new handle with the supplied initial level.
+    pub fn new(level: &Level) -> Self {
+        Self {
+            level: Arc::new(AtomicUsize::new(level.to_slog().as_usize())),
+        }
+    }
+
+    /// Return the current level.
+    pub fn get(&self) -> Level {
+        let level = slog::Level::from_usize(self.level.load(Ordering::Relaxed))
+            .unwrap_or(slog::Level::Info);
+        Level::from_slog(level)
+    }
+
+    /// Switch to the supplied level, returning the previous one.
+    pub fn set(&self, level: &Level) -> Level {
+        let prev = self
+            .level
+            .swap(level.to_slog().as_usize(), Ordering::Relaxed);
+        Level::from_slog(slog::Level::from_usize(prev).unwrap_or(slog::Level::Info))
+    }
+
+    /// Check whether or not a record of the supplied level should be logged.
+    #[inline]
+    pub(super) fn enabled(&self, level: slog::Level) -> bool {
+        level.as_usize() <= self.level.load(Ordering::Relaxed)
+    }
+}
+
+/// Watch for SIGHUP, toggling the supplied handle between the configured level and
+/// [Level::Debug] on each signal received.
+#[cfg(unix)]
+pub async fn watch_sighup(logger: slog::Logger, handle: Handle, configured: Level) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(err) => {
+            error!(&logger, "Failed to register SIGHUP handler."; "error" => err.to_string());
+            return;
+        }
+    };
+    while hangups.recv().await.is_some() {
+        let next = if handle.get() == configured {
+            Level::Debug
+        } else {
+            configured.clone()
+        };
+        let prev = handle.set(&next);
+        info!(&logger, "Switched log level on SIGHUP."; "from" => prev.to_string(), "to" => next.to_string());
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handle() {
+        let handle = Handle::new(&Level::Info);
+        assert_eq!(Level::Info, handle.get());
+        assert!(handle.enabled(slog::Level::Warning));
+        assert!(!handle.enabled(slog::Level::Debug));
+
+        let prev = handle.clone().set(&Level::Debug);
+        assert_eq!(Level::Info, prev);
+        assert_eq!(Level::Debug, handle.get());
+        assert!(handle.enabled(slog::Level::Debug));
+    }
+}
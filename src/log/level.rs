@@ -5,6 +5,7 @@
 use super::error::{Error, Result};
 
 // Standard usings
+use std::fmt;
 use std::str::FromStr;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -48,7 +49,37 @@ impl FromStr for Level {
     }
 }
 
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self {
+            Level::Crit => "critical",
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+        };
+        f.write_str(level)
+    }
+}
+
 impl Level {
+    /// Handles converting the lower level slog representation of log levels to the internal
+    /// log level, folding levels more verbose than debug into [Level::Debug].
+    ///
+    /// ```
+    /// let x = librift::log::Level::from_slog(slog::Level::Warning);
+    /// assert_eq!(x, librift::log::Level::Warn);
+    /// ```
+    pub fn from_slog(level: slog::Level) -> Level {
+        match level {
+            slog::Level::Critical => Level::Crit,
+            slog::Level::Error => Level::Error,
+            slog::Level::Warning => Level::Warn,
+            slog::Level::Info => Level::Info,
+            slog::Level::Debug | slog::Level::Trace => Level::Debug,
+        }
+    }
+
     /// Handles converting the internal  log level to the lower level slog representation
     /// of log levels for consumption.
     ///
@@ -66,6 +97,22 @@ impl Level {
             Level::Debug => slog::Level::Debug,
         }
     }
+
+    /// Handles converting the internal log level to the tracing representation of log levels,
+    /// folding critical into error as tracing has no critical level.
+    ///
+    /// ```
+    /// let x = librift::log::Level::Crit;
+    /// assert_eq!(x.to_tracing(), tracing::Level::ERROR);
+    /// ```
+    pub fn to_tracing(&self) -> tracing::Level {
+        match self {
+            Level::Crit | Level::Error => tracing::Level::ERROR,
+            Level::Warn => tracing::Level::WARN,
+            Level::Info => tracing::Level::INFO,
+            Level::Debug => tracing::Level::DEBUG,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -89,6 +136,14 @@ mod tests {
             Error::InvalidLevel { ref level } => {
                 assert_eq!(&String::from("nope"), level);
             }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        for level in ["critical", "error", "warn", "info", "debug"] {
+            assert_eq!(level, Level::from_str(level).unwrap().to_string());
         }
     }
 
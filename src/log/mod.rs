@@ -7,13 +7,19 @@ use std::io;
 // extern usings
 use slog::Drain;
 
+mod bridge;
 mod config;
 mod error;
 mod filter;
+mod handle;
 mod level;
 
+pub use self::bridge::{TracingDrain, TARGET};
 pub use self::config::Config;
 pub use self::error::{Error, Result};
+#[cfg(unix)]
+pub use self::handle::watch_sighup;
+pub use self::handle::Handle;
 pub use self::level::Level;
 
 /// Return a defualt logger to use for init processing before configuraiton can be
@@ -36,7 +42,7 @@ pub fn default(bin: &'static str, version: &'static str) -> slog::Logger {
 
     let drain = filter::LevelFilter {
         drain,
-        level: slog::Level::Critical,
+        level: Handle::new(&Level::Crit),
     }
     .fuse();
 
@@ -56,6 +62,7 @@ pub fn default(bin: &'static str, version: &'static str) -> slog::Logger {
 ///     &librift::log::Config {
 ///         level: librift::log::Level::Info,
 ///         json: true,
+///         tracing: false,
 ///     },
 ///     "example",
 ///     "0.1.1",
@@ -64,6 +71,48 @@ pub fn default(bin: &'static str, version: &'static str) -> slog::Logger {
 /// info!(logger, "Hello world!"; "woot" => "woot");
 /// ```
 pub fn new(cfg: &config::Config, bin: &'static str, version: &'static str) -> slog::Logger {
+    reloadable(cfg, bin, version).0
+}
+
+/// Return a newly constructed slog::Logger based on the supplied configuration, alongside a
+/// [Handle] which can be used to switch the logger's level at runtime.
+///
+/// # Example
+/// ```
+/// use slog::{debug, info};
+///
+/// let (logger, handle) = librift::log::reloadable(
+///     &librift::log::Config {
+///         level: librift::log::Level::Info,
+///         json: true,
+///         tracing: false,
+///     },
+///     "example",
+///     "0.1.1",
+/// );
+///
+/// info!(logger, "Hello world!"; "woot" => "woot");
+/// handle.set(&librift::log::Level::Debug);
+/// debug!(logger, "Now logging debug messages.");
+/// ```
+pub fn reloadable(
+    cfg: &config::Config,
+    bin: &'static str,
+    version: &'static str,
+) -> (slog::Logger, Handle) {
+    let handle = Handle::new(&cfg.level);
+    if cfg.tracing {
+        // Records are forwarded synchronously so that they are emitted within the tracing
+        // span that is current on the logging thread.
+        let drain = filter::LevelFilter {
+            drain: TracingDrain,
+            level: handle.clone(),
+        }
+        .fuse();
+        let logger = slog::Logger::root(drain, o!("binary" => bin, "version" => version));
+        return (logger, handle);
+    }
+
     let drain: Box<dyn Drain<Ok = (), Err = slog::Never> + Send> = if cfg.json {
         Box::new(
             slog_json::Json::new(io::stdout())
@@ -83,12 +132,38 @@ pub fn new(cfg: &config::Config, bin: &'static str, version: &'static str) -> sl
 
     let drain = filter::LevelFilter {
         drain,
-        level: cfg.level.to_slog(),
+        level: handle.clone(),
     }
     .fuse();
 
     let drain = slog_async::Async::new(drain).build().fuse();
-    slog::Logger::root(drain, o!("binary" => bin, "version" => version))
+    let logger = slog::Logger::root(drain, o!("binary" => bin, "version" => version));
+    (logger, handle)
+}
+
+/// Install a global tracing subscriber which writes all tracing spans and events, including
+/// those forwarded by a logger configured with [Config::tracing], to stdout. Forwarded logs
+/// are already filtered by their logger so are always passed through, whereas all other
+/// targets are filtered by the configured level.
+pub fn init_tracing(cfg: &config::Config) -> Result<()> {
+    use tracing_subscriber::filter::{LevelFilter, Targets};
+    use tracing_subscriber::prelude::*;
+
+    let targets = Targets::new()
+        .with_target(TARGET, LevelFilter::TRACE)
+        .with_default(cfg.level.to_tracing());
+    let res = if cfg.json {
+        tracing_subscriber::fmt()
+            .json()
+            .finish()
+            .with(targets)
+            .try_init()
+    } else {
+        tracing_subscriber::fmt().finish().with(targets).try_init()
+    };
+    res.map_err(|err| Error::TracingInit {
+        reason: err.to_string(),
+    })
 }
 
 #[cfg(test)]
@@ -106,12 +181,31 @@ mod tests {
         let cfg = Config {
             json: true,
             level: Level::Debug,
+            tracing: false,
         };
         new(&cfg, "test", "alpha");
         let cfg = Config {
             json: false,
             level: Level::Debug,
+            tracing: false,
         };
         new(&cfg, "test", "alpha");
+        let cfg = Config {
+            json: false,
+            level: Level::Debug,
+            tracing: true,
+        };
+        let _logger = new(&cfg, "test", "alpha");
+    }
+
+    #[test]
+    fn test_init_tracing() {
+        let cfg = Config {
+            json: true,
+            level: Level::Info,
+            tracing: true,
+        };
+        assert!(init_tracing(&cfg).is_ok());
+        assert!(init_tracing(&cfg).is_err());
     }
 }
@@ -0,0 +1,52 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::path::PathBuf;
+
+// extern usings
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, StructOpt)]
+/// Rift declarative topology configuration.
+pub struct Config {
+    #[structopt(
+        long = "topology-file",
+        env = "RIFT_TOPOLOGY_FILE",
+        help = "The topology manifest to reconcile topics and subscriptions with.",
+        long_help = "Sets the JSON manifest declaring the topics, their schemas, and their subscriptions that riftd creates at startup. Topics and subscriptions which drift from the manifest are updated to match.",
+        takes_value = true
+    )]
+    /// Define the topology manifest to reconcile with, if any.
+    pub file: Option<PathBuf>,
+
+    #[structopt(
+        long = "topology-watch-interval-ms",
+        env = "RIFT_TOPOLOGY_WATCH_INTERVAL_MS",
+        help = "How often the topology manifest is checked for changes.",
+        long_help = "Sets the interval in milliseconds between checks of the topology manifest for changes, reconciling again whenever it is modified. The manifest is only reconciled at startup when set to 0.",
+        default_value = "0",
+        takes_value = true
+    )]
+    /// Define how often the topology manifest is checked for changes.
+    pub watch_interval_ms: u64,
+
+    #[structopt(
+        long = "topology-prune",
+        env = "RIFT_TOPOLOGY_PRUNE",
+        help = "Delete topics and subscriptions missing from the topology manifest.",
+        long_help = "Enables deleting every topic and subscription which is not declared in the topology manifest when reconciling, including any messages they hold. Internal topics, whose names start with `__`, are never deleted."
+    )]
+    /// Define whether to delete topics and subscriptions missing from the manifest.
+    pub prune: bool,
+
+    #[structopt(
+        long = "bootstrap-file",
+        env = "RIFT_BOOTSTRAP_FILE",
+        help = "A topology manifest of topics and subscriptions to create at startup.",
+        long_help = "Sets a JSON manifest, in the same format as the topology manifest, declaring topics and subscriptions that riftd creates before it starts serving. Unlike the topology manifest it is applied once, only creating what is missing and never updating or deleting anything.",
+        takes_value = true
+    )]
+    /// Define the manifest of topics and subscriptions to create at startup, if any.
+    pub bootstrap_file: Option<PathBuf>,
+}
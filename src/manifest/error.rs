@@ -0,0 +1,55 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::io;
+use std::result;
+
+// crate usings
+use crate::{push, schema};
+
+// extern usings
+use thiserror::Error;
+
+/// Custom Result wrapper to simplify usage.
+pub type Result<T> = result::Result<T, Error>;
+
+/// Represents errors loading a topology manifest.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// Handles failures reading the manifest, or files it references.
+    #[error("failed to read '{path}': {source}")]
+    Io {
+        /// The path that failed to be read.
+        path: String,
+        /// The initial error cause.
+        source: io::Error,
+    },
+    /// Handles manifests which are not valid JSON.
+    #[error("failed to parse the topology manifest: {0}")]
+    Parse(#[from] serde_json::Error),
+    /// Handles manifests which do not describe a valid topology.
+    #[error("invalid topology manifest at '{location}': {reason}")]
+    Invalid {
+        /// The location of the invalid entry within the manifest.
+        location: String,
+        /// The reason the entry is invalid.
+        reason: String,
+    },
+    /// Handles invalid topic schemas.
+    #[error("invalid topology manifest schema for topic '{topic}': {source}")]
+    Schema {
+        /// The topic the schema is defined for.
+        topic: String,
+        /// The initial error cause.
+        source: schema::Error,
+    },
+    /// Handles invalid subscription push endpoints.
+    #[error("invalid topology manifest push endpoint for subscription '{subscription}': {source}")]
+    Push {
+        /// The subscription the endpoint is defined for.
+        subscription: String,
+        /// The initial error cause.
+        source: push::Error,
+    },
+}
@@ -0,0 +1,12 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+mod config;
+mod error;
+mod reconciler;
+mod spec;
+
+pub use config::Config;
+pub use error::{Error, Result};
+pub use reconciler::{Reconciler, Report};
+pub use spec::{Manifest, SubscriptionSpec, TopicSpec};
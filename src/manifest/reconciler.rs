@@ -0,0 +1,367 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::path::PathBuf;
+use std::time::Duration;
+
+// crate usings
+use super::{Config, Manifest, Result};
+use crate::cluster::Membership;
+use crate::grpc::pubsub::Message;
+use crate::pubsub::{namespace, Registry};
+use crate::push::Pusher;
+use crate::schema;
+
+/// The count of changes made by a single reconciliation.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Report {
+    /// The count of topics and subscriptions created.
+    pub created: usize,
+    /// The count of topics and subscriptions updated to match the manifest.
+    pub updated: usize,
+    /// The count of topics and subscriptions deleted as they are missing from the manifest.
+    pub pruned: usize,
+    /// The count of topics and subscriptions not created as they would exceed a resource
+    /// limit.
+    pub refused: usize,
+}
+
+/// Reconciles the topics and subscriptions of a registry with a topology manifest.
+#[derive(Debug, Clone)]
+pub struct Reconciler {
+    registry: Registry<Message>,
+    schemas: schema::Registry,
+    pusher: Pusher,
+    membership: Option<Membership>,
+    prune: bool,
+}
+
+impl Reconciler {
+    /// Create a new reconciler managing the supplied registries, spawning the push deliveries
+    /// of push subscriptions with the supplied pusher.
+    pub fn new(
+        registry: Registry<Message>,
+        schemas: schema::Registry,
+        pusher: Pusher,
+        prune: bool,
+    ) -> Self {
+        Self {
+            registry,
+            schemas,
+            pusher,
+            membership: None,
+            prune,
+        }
+    }
+
+    /// Only reconcile topics owned by the local member of the supplied membership.
+    pub fn with_membership(mut self, membership: Membership) -> Self {
+        self.membership = Some(membership);
+        self
+    }
+
+    fn is_local(&self, topic: &str) -> bool {
+        self.membership
+            .as_ref()
+            .map(|membership| membership.is_local(topic))
+            .unwrap_or(true)
+    }
+
+    /// Create every topic and subscription missing from the registry, update those which have
+    /// drifted from the manifest, and if pruning delete those missing from the manifest.
+    pub fn reconcile(&self, manifest: &Manifest) -> Report {
+        self.apply(manifest, false)
+    }
+
+    /// Create every topic and subscription missing from the registry, leaving those which
+    /// already exist untouched and never deleting anything.
+    pub fn bootstrap(&self, manifest: &Manifest) -> Report {
+        self.apply(manifest, true)
+    }
+
+    fn apply(&self, manifest: &Manifest, bootstrap: bool) -> Report {
+        let mut report = Report::default();
+        for spec in manifest
+            .topics
+            .iter()
+            .filter(|spec| self.is_local(&spec.name))
+        {
+            let (topic, created) = match self.registry.get(&spec.name) {
+                Some(topic) => (topic, false),
+                None => match self.registry.try_create(spec.name.clone()) {
+                    Ok(topic) => {
+                        report.created += 1;
+                        (topic, true)
+                    }
+                    Err(_) => {
+                        report.refused += 1;
+                        continue;
+                    }
+                },
+            };
+
+            let bound = self.schemas.get(&spec.name);
+            match (&spec.schema, bound) {
+                _ if bootstrap && !created => {}
+                (Some(schema), Some(bound)) if bound.as_ref() == schema => {}
+                (Some(schema), _) => {
+                    report.updated += 1;
+                    self.schemas.bind(spec.name.clone(), schema.clone());
+                }
+                (None, Some(_)) => {
+                    report.updated += 1;
+                    self.schemas.unbind(&spec.name);
+                }
+                (None, None) => {}
+            }
+
+            for sub_spec in &spec.subscriptions {
+                let push = sub_spec.push.as_ref().map(ToString::to_string);
+                let quota = sub_spec.quota.unwrap_or_default();
+                let (mut sub, created) =
+                    match topic.create_with_push(sub_spec.name.clone(), push.clone()) {
+                        Ok(res) => res,
+                        Err(_) => {
+                            report.refused += 1;
+                            continue;
+                        }
+                    };
+                if created {
+                    report.created += 1;
+                    sub.throttle.set(quota);
+                } else if bootstrap {
+                    continue;
+                } else if sub.push != push {
+                    report.updated += 1;
+                    topic.set_quota(&sub_spec.name, quota);
+                    match topic.set_push(&sub_spec.name, push) {
+                        Some(updated) => sub = updated,
+                        None => continue,
+                    }
+                } else {
+                    if sub.throttle.quota() != quota {
+                        report.updated += 1;
+                        topic.set_quota(&sub_spec.name, quota);
+                    }
+                    continue;
+                }
+                if let Some(endpoint) = &sub_spec.push {
+                    self.pusher.spawn(
+                        self.registry.clone(),
+                        spec.name.clone(),
+                        sub_spec.name.clone(),
+                        sub,
+                        endpoint.clone(),
+                    );
+                }
+            }
+
+            if self.prune && !bootstrap {
+                let extra = topic.iter(|subs| {
+                    subs.map(|(name, _)| name.clone())
+                        .filter(|name| !spec.subscriptions.iter().any(|sub| *sub.name == **name))
+                        .collect::<Vec<_>>()
+                });
+                for name in extra {
+                    report.pruned += 1;
+                    topic.remove(&name);
+                }
+            }
+        }
+
+        if self.prune && !bootstrap {
+            let extra = self.registry.iter(|topics| {
+                topics
+                    .map(|(name, _)| name.clone())
+                    // Topics rift creates for itself are never pruned.
+                    .filter(|name| !namespace::is_internal(name))
+                    .filter(|name| !manifest.topics.iter().any(|topic| *topic.name == **name))
+                    .collect::<Vec<_>>()
+            });
+            for name in extra {
+                report.pruned += 1;
+                self.registry.delete(&name);
+                self.schemas.unbind(&name);
+            }
+        }
+        report
+    }
+
+    /// Reconcile with the manifest at the supplied path whenever it is modified, checking for
+    /// modifications on every interval, forever.
+    pub async fn watch(self, path: PathBuf, interval: Duration, logger: slog::Logger) {
+        let modified = |path: &PathBuf| {
+            std::fs::metadata(path)
+                .and_then(|meta| meta.modified())
+                .ok()
+        };
+        let mut last = modified(&path);
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            let current = modified(&path);
+            if current == last {
+                continue;
+            }
+            last = current;
+            match Manifest::load(&path) {
+                Ok(manifest) => self.log(&logger, self.reconcile(&manifest)),
+                Err(err) => {
+                    warn!(&logger, "Failed to reload the topology manifest."; "error" => err.to_string())
+                }
+            }
+        }
+    }
+
+    fn log(&self, logger: &slog::Logger, report: Report) {
+        info!(logger, "Reconciled the topology manifest.";
+            "created" => report.created,
+            "updated" => report.updated,
+            "pruned" => report.pruned,
+            "refused" => report.refused,
+        );
+    }
+
+    /// Bootstrap the manifest configured by the supplied configuration, if any, then reconcile
+    /// with the configured topology manifest, if any, spawning a task watching it for changes
+    /// if configured to.
+    pub fn start(self, cfg: &Config, logger: slog::Logger) -> Result<()> {
+        if let Some(path) = &cfg.bootstrap_file {
+            let report = self.bootstrap(&Manifest::load(path)?);
+            info!(&logger, "Bootstrapped the topology.";
+                "created" => report.created,
+                "refused" => report.refused,
+            );
+        }
+        let path = match &cfg.file {
+            Some(path) => path.clone(),
+            None => return Ok(()),
+        };
+        let manifest = Manifest::load(&path)?;
+        self.log(&logger, self.reconcile(&manifest));
+        if cfg.watch_interval_ms > 0 {
+            let interval = Duration::from_millis(cfg.watch_interval_ms);
+            tokio::spawn(self.watch(path, interval, logger));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    use std::path::Path;
+
+    #[test]
+    fn test_reconcile() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+
+        let registry = Registry::default();
+        let schemas = schema::Registry::default();
+        let reconciler =
+            Reconciler::new(registry.clone(), schemas.clone(), Pusher::default(), true);
+
+        registry.create(String::from("extra"));
+        registry.create(String::from(crate::audit::AUDIT_TOPIC));
+        let orders = registry.create(String::from("orders"));
+        orders.create(String::from("stale"));
+        orders.create(String::from("billing"));
+
+        let manifest = Manifest::parse(
+            br#"{"topics": [{
+                "name": "orders",
+                "schema": {"json": {"type": "object"}},
+                "subscriptions": [
+                    {"name": "billing", "push": "http://localhost:8080/push"},
+                    {"name": "audit"}
+                ]
+            }]}"#,
+            Path::new("."),
+        )
+        .unwrap();
+        let report = reconciler.reconcile(&manifest);
+        assert_eq!(
+            report,
+            Report {
+                created: 1,
+                updated: 2,
+                pruned: 2,
+                refused: 0,
+            }
+        );
+        assert!(registry.get("extra").is_none());
+        assert!(registry.get(crate::audit::AUDIT_TOPIC).is_some());
+        assert!(orders.get("stale").is_none());
+        assert!(orders.get("audit").is_some());
+        assert_eq!(
+            orders.get("billing").unwrap().push,
+            Some(String::from("http://localhost:8080/push"))
+        );
+        assert!(schemas.validate("orders", b"[]").is_err());
+
+        // Reconciling an unchanged manifest is a no-op.
+        assert_eq!(reconciler.reconcile(&manifest), Report::default());
+    }
+
+    #[test]
+    fn test_bootstrap() {
+        let registry = Registry::default();
+        let schemas = schema::Registry::default();
+        let reconciler =
+            Reconciler::new(registry.clone(), schemas.clone(), Pusher::default(), true);
+
+        registry.create(String::from("extra"));
+        let orders = registry.create(String::from("orders"));
+        orders.create(String::from("billing"));
+
+        let manifest = Manifest::parse(
+            br#"{"topics": [
+                {
+                    "name": "orders",
+                    "schema": {"json": {"type": "object"}},
+                    "subscriptions": [
+                        {"name": "billing", "quota": {"messages_per_sec": 10}},
+                        {"name": "audit", "quota": {"messages_per_sec": 10}}
+                    ]
+                },
+                {"name": "events", "subscriptions": [{"name": "archive"}]}
+            ]}"#,
+            Path::new("."),
+        )
+        .unwrap();
+        let report = reconciler.bootstrap(&manifest);
+        assert_eq!(
+            report,
+            Report {
+                created: 3,
+                ..Report::default()
+            }
+        );
+
+        // Existing topics and subscriptions are left untouched, and nothing is pruned.
+        assert!(registry.get("extra").is_some());
+        assert!(schemas.validate("orders", b"[]").is_ok());
+        assert!(orders
+            .get("billing")
+            .unwrap()
+            .throttle
+            .quota()
+            .is_unlimited());
+        assert_eq!(
+            orders
+                .get("audit")
+                .unwrap()
+                .throttle
+                .quota()
+                .messages_per_sec,
+            10
+        );
+        assert!(registry.get("events").unwrap().get("archive").is_some());
+
+        assert_eq!(reconciler.bootstrap(&manifest), Report::default());
+    }
+}
@@ -0,0 +1,274 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::collections::HashSet;
+use std::path::Path;
+use std::str::FromStr;
+
+// crate usings
+use super::{Error, Result};
+use crate::pubsub::Quota;
+use crate::push::Endpoint;
+use crate::schema::{JsonSchema, ProtobufSchema, Schema};
+
+// extern usings
+use serde_json::{Map, Value};
+
+/// The declared state of a subscription.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubscriptionSpec {
+    /// The name of the subscription.
+    pub name: String,
+    /// The endpoint the subscription pushes its messages to, if any.
+    pub push: Option<Endpoint>,
+    /// The quota limiting the rate the subscription delivers messages, if any.
+    pub quota: Option<Quota>,
+}
+
+/// The declared state of a topic and its subscriptions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopicSpec {
+    /// The name of the topic.
+    pub name: String,
+    /// The schema bound to the topic, if any.
+    pub schema: Option<Schema>,
+    /// The subscriptions of the topic.
+    pub subscriptions: Vec<SubscriptionSpec>,
+}
+
+/// A declarative topology manifest, describing every topic and subscription that should exist.
+///
+/// Manifests are JSON documents of the form:
+///
+/// ```json
+/// {
+///   "topics": [
+///     {
+///       "name": "orders",
+///       "schema": {"json": {"type": "object"}},
+///       "subscriptions": [
+///         {"name": "billing", "quota": {"messages_per_sec": 100, "bytes_per_sec": 65536}},
+///         {"name": "archive", "push": "file:///var/lib/rift/archive"}
+///       ]
+///     }
+///   ]
+/// }
+/// ```
+///
+/// A protobuf schema is declared as `{"protobuf": {"descriptor_set": "path", "message_type":
+/// "package.Message"}}`, where a relative descriptor set path is resolved against the directory
+/// of the manifest. Either rate of a quota may be omitted, leaving it unlimited.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Manifest {
+    /// The declared topics.
+    pub topics: Vec<TopicSpec>,
+}
+
+impl Manifest {
+    /// Load the manifest at the supplied path.
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path).map_err(|source| Error::Io {
+            path: path.display().to_string(),
+            source,
+        })?;
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        Self::parse(&data, dir)
+    }
+
+    /// Parse the supplied manifest, resolving relative paths against the supplied directory.
+    pub fn parse(data: &[u8], dir: &Path) -> Result<Self> {
+        let root: Value = serde_json::from_slice(data)?;
+        let root = object(&root, "")?;
+        let mut names = HashSet::new();
+        let mut topics = Vec::new();
+        for (idx, topic) in array(root, "topics", "")?.iter().enumerate() {
+            let location = format!("topics[{}]", idx);
+            let topic = TopicSpec::parse(object(topic, &location)?, dir, &location)?;
+            if !names.insert(topic.name.clone()) {
+                return Err(invalid(&location, "duplicate topic"));
+            }
+            topics.push(topic);
+        }
+        Ok(Self { topics })
+    }
+}
+
+impl TopicSpec {
+    fn parse(topic: &Map<String, Value>, dir: &Path, location: &str) -> Result<Self> {
+        let name = string(topic, "name", location)?;
+        let schema = match topic.get("schema") {
+            Some(schema) => Some(parse_schema(&name, schema, dir, location)?),
+            None => None,
+        };
+
+        let mut names = HashSet::new();
+        let mut subscriptions = Vec::new();
+        for (idx, sub) in array(topic, "subscriptions", location)?.iter().enumerate() {
+            let location = format!("{}.subscriptions[{}]", location, idx);
+            let sub = object(sub, &location)?;
+            let name = string(sub, "name", &location)?;
+            let push = match sub.get("push") {
+                Some(Value::String(push)) => {
+                    Some(Endpoint::from_str(push).map_err(|source| Error::Push {
+                        subscription: name.clone(),
+                        source,
+                    })?)
+                }
+                Some(_) => return Err(invalid(&location, "'push' must be a string")),
+                None => None,
+            };
+            let quota = match sub.get("quota") {
+                Some(quota) => Some(parse_quota(quota, &location)?),
+                None => None,
+            };
+            if !names.insert(name.clone()) {
+                return Err(invalid(&location, "duplicate subscription"));
+            }
+            subscriptions.push(SubscriptionSpec { name, push, quota });
+        }
+        Ok(Self {
+            name,
+            schema,
+            subscriptions,
+        })
+    }
+}
+
+fn parse_schema(topic: &str, schema: &Value, dir: &Path, location: &str) -> Result<Schema> {
+    let location = format!("{}.schema", location);
+    let schema = object(schema, &location)?;
+    let wrap = |source| Error::Schema {
+        topic: topic.to_owned(),
+        source,
+    };
+    match (schema.get("json"), schema.get("protobuf")) {
+        (Some(json), None) => JsonSchema::new(json.to_string())
+            .map(Schema::Json)
+            .map_err(wrap),
+        (None, Some(protobuf)) => {
+            let location = format!("{}.protobuf", location);
+            let protobuf = object(protobuf, &location)?;
+            let path = dir.join(string(protobuf, "descriptor_set", &location)?);
+            let descriptor_set = std::fs::read(&path).map_err(|source| Error::Io {
+                path: path.display().to_string(),
+                source,
+            })?;
+            let message_type = string(protobuf, "message_type", &location)?;
+            ProtobufSchema::new(descriptor_set, message_type)
+                .map(Schema::Protobuf)
+                .map_err(wrap)
+        }
+        _ => Err(invalid(
+            &location,
+            "exactly one of 'json' or 'protobuf' is required",
+        )),
+    }
+}
+
+fn parse_quota(quota: &Value, location: &str) -> Result<Quota> {
+    let location = format!("{}.quota", location);
+    let quota = object(quota, &location)?;
+    let rate = |key: &str| match quota.get(key) {
+        Some(rate) => rate.as_u64().ok_or_else(|| {
+            invalid(
+                &location,
+                &format!("'{}' must be a non-negative integer", key),
+            )
+        }),
+        None => Ok(0),
+    };
+    Ok(Quota {
+        messages_per_sec: rate("messages_per_sec")?,
+        bytes_per_sec: rate("bytes_per_sec")?,
+    })
+}
+
+fn invalid(location: &str, reason: &str) -> Error {
+    Error::Invalid {
+        location: location.to_owned(),
+        reason: reason.to_owned(),
+    }
+}
+
+fn object<'a>(value: &'a Value, location: &str) -> Result<&'a Map<String, Value>> {
+    value
+        .as_object()
+        .ok_or_else(|| invalid(location, "expected an object"))
+}
+
+/// Return the array under the supplied key, treating a missing key as an empty array.
+fn array<'a>(value: &'a Map<String, Value>, key: &str, location: &str) -> Result<&'a [Value]> {
+    match value.get(key) {
+        Some(Value::Array(values)) => Ok(values),
+        Some(_) => Err(invalid(location, &format!("'{}' must be an array", key))),
+        None => Ok(&[]),
+    }
+}
+
+fn string(value: &Map<String, Value>, key: &str, location: &str) -> Result<String> {
+    match value.get(key) {
+        Some(Value::String(string)) if !string.is_empty() => Ok(string.clone()),
+        _ => Err(invalid(
+            location,
+            &format!("'{}' must be a non-empty string", key),
+        )),
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let data = br#"{
+            "topics": [
+                {
+                    "name": "orders",
+                    "schema": {"json": {"type": "object"}},
+                    "subscriptions": [
+                        {"name": "billing", "quota": {"messages_per_sec": 100}},
+                        {"name": "archive", "push": "file:///var/lib/rift/archive"}
+                    ]
+                },
+                {"name": "events"}
+            ]
+        }"#;
+        let manifest = Manifest::parse(data, Path::new(".")).unwrap();
+        assert_eq!(manifest.topics.len(), 2);
+        let orders = &manifest.topics[0];
+        assert_eq!(orders.name, "orders");
+        assert!(matches!(orders.schema, Some(Schema::Json(_))));
+        assert_eq!(orders.subscriptions[0].name, "billing");
+        assert_eq!(orders.subscriptions[0].push, None);
+        assert_eq!(
+            orders.subscriptions[0].quota,
+            Some(Quota {
+                messages_per_sec: 100,
+                bytes_per_sec: 0,
+            })
+        );
+        assert_eq!(orders.subscriptions[1].quota, None);
+        assert_eq!(
+            orders.subscriptions[1].push.as_ref().unwrap().to_string(),
+            "file:///var/lib/rift/archive"
+        );
+        assert!(manifest.topics[1].subscriptions.is_empty());
+
+        let cases: [&[u8]; 8] = [
+            b"[]",
+            br#"{"topics": {}}"#,
+            br#"{"topics": [{}]}"#,
+            br#"{"topics": [{"name": "a"}, {"name": "a"}]}"#,
+            br#"{"topics": [{"name": "a", "schema": {}}]}"#,
+            br#"{"topics": [{"name": "a", "subscriptions": [{"name": "b", "push": "ftp://c"}]}]}"#,
+            br#"{"topics": [{"name": "a", "subscriptions": [{"name": "b"}, {"name": "b"}]}]}"#,
+            br#"{"topics": [{"name": "a", "subscriptions": [{"name": "b", "quota": {"bytes_per_sec": -1}}]}]}"#,
+        ];
+        for data in cases {
+            assert!(Manifest::parse(data, Path::new(".")).is_err());
+        }
+    }
+}
@@ -0,0 +1,107 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::{Error, Result};
+
+/// The default buckets of request response time histograms in seconds, spanning half a
+/// millisecond through ten seconds.
+pub const RESPONSE_TIME_BUCKETS: &[f64] = &[
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// The default buckets of end-to-end message latency histograms in seconds, spanning a
+/// millisecond through an hour, as messages may wait on a slow or offline subscriber for far
+/// longer than any request takes.
+pub const LATENCY_BUCKETS: &[f64] = &[
+    0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0, 600.0, 1800.0, 3600.0,
+];
+
+/// A validated set of histogram bucket upper bounds, which are finite and strictly increasing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Buckets(Vec<f64>);
+
+impl Buckets {
+    /// Validate the supplied bucket upper bounds.
+    pub fn new(buckets: Vec<f64>) -> Result<Self> {
+        let invalid = |reason: &str| Error::InvalidBuckets {
+            buckets: format_buckets(&buckets),
+            reason: reason.to_owned(),
+        };
+        if buckets.is_empty() {
+            return Err(invalid("at least one bucket is required"));
+        }
+        if buckets.iter().any(|bucket| !bucket.is_finite()) {
+            return Err(invalid("buckets must be finite"));
+        }
+        if buckets.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(invalid("buckets must be strictly increasing"));
+        }
+        Ok(Self(buckets))
+    }
+
+    /// Return the bucket upper bounds.
+    pub fn as_slice(&self) -> &[f64] {
+        &self.0
+    }
+}
+
+impl FromStr for Buckets {
+    type Err = Error;
+
+    /// Parse a comma separated list of bucket upper bounds.
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// let buckets = librift::metric::Buckets::from_str("0.1, 1, 10").unwrap();
+    /// assert_eq!(buckets.as_slice(), &[0.1, 1.0, 10.0]);
+    /// ```
+    fn from_str(src: &str) -> Result<Buckets> {
+        let buckets = src
+            .split(',')
+            .map(|bucket| bucket.trim().parse::<f64>())
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|err| Error::InvalidBuckets {
+                buckets: src.to_owned(),
+                reason: err.to_string(),
+            })?;
+        Buckets::new(buckets)
+    }
+}
+
+impl fmt::Display for Buckets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&format_buckets(&self.0))
+    }
+}
+
+/// Format the supplied buckets as a comma separated list.
+fn format_buckets(buckets: &[f64]) -> String {
+    let buckets: Vec<_> = buckets.iter().map(f64::to_string).collect();
+    buckets.join(",")
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buckets() {
+        for defaults in [RESPONSE_TIME_BUCKETS, LATENCY_BUCKETS] {
+            let buckets = Buckets::new(defaults.to_vec()).unwrap();
+            assert_eq!(Buckets::from_str(&buckets.to_string()).unwrap(), buckets);
+        }
+
+        let invalid = ["", "1,nope", "1,inf", "1,1", "2,1"];
+        for src in invalid {
+            assert!(
+                matches!(Buckets::from_str(src), Err(Error::InvalidBuckets { .. })),
+                "{}",
+                src
+            );
+        }
+    }
+}
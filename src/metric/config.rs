@@ -0,0 +1,150 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::str::FromStr;
+
+// crate usings
+use super::{Buckets, Error, Result};
+
+// extern usings
+use structopt::StructOpt;
+
+/// The protocol used to push metrics to a remote endpoint.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PushMode {
+    /// Push the text exposition format to a Prometheus Pushgateway.
+    Pushgateway,
+    /// Push snappy compressed protobuf samples to a Prometheus remote-write endpoint.
+    RemoteWrite,
+}
+
+impl FromStr for PushMode {
+    type Err = Error;
+
+    /// Handles converting the supplied &str to a PushMode.
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// let x = librift::metric::PushMode::from_str("remote-write");
+    /// assert_eq!(x.unwrap(), librift::metric::PushMode::RemoteWrite);
+    /// ```
+    fn from_str(t: &str) -> Result<PushMode> {
+        match t {
+            "pushgateway" => Ok(PushMode::Pushgateway),
+            "remote-write" => Ok(PushMode::RemoteWrite),
+            _ => Err(Error::InvalidPushMode { mode: t.to_owned() }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, StructOpt)]
+/// Rift metric configuration.
+pub struct Config {
+    #[structopt(
+        long = "metrics-push-url",
+        env = "RIFT_METRICS_PUSH_URL",
+        help = "The endpoint to periodically push metrics to.",
+        long_help = "Sets the Pushgateway or remote-write endpoint, including the scheme, to periodically push metrics to. Pushing is disabled when no endpoint is supplied.",
+        takes_value = true
+    )]
+    /// Define the endpoint to push metrics to, if any.
+    pub url: Option<String>,
+
+    #[structopt(
+        long = "metrics-push-mode",
+        env = "RIFT_METRICS_PUSH_MODE",
+        help = "The protocol to push metrics with.",
+        long_help = "Selects whether metrics are pushed to a Prometheus Pushgateway or a Prometheus remote-write endpoint.",
+        default_value = "pushgateway",
+        possible_values = &["pushgateway", "remote-write"],
+        takes_value = true
+    )]
+    /// Define the protocol to push metrics with.
+    pub mode: PushMode,
+
+    #[structopt(
+        long = "metrics-push-interval",
+        env = "RIFT_METRICS_PUSH_INTERVAL",
+        help = "The number of seconds between metric pushes.",
+        long_help = "Sets the number of seconds to wait between each push of the gathered metrics, which must be at least one. Each push must complete within the interval.",
+        default_value = "15",
+        takes_value = true
+    )]
+    /// Define the number of seconds between metric pushes.
+    pub interval: u64,
+
+    #[structopt(
+        long = "metrics-push-instance",
+        env = "RIFT_METRICS_PUSH_INSTANCE",
+        help = "The instance label to attach to pushed metrics.",
+        long_help = "Sets the instance label attached to pushed metrics, which must be unique per riftd instance. Defaults to the host name of the machine.",
+        takes_value = true
+    )]
+    /// Define the instance label to attach to pushed metrics.
+    pub instance: Option<String>,
+
+    #[structopt(
+        long = "metrics-identity-limit",
+        env = "RIFT_METRICS_IDENTITY_LIMIT",
+        help = "The maximum number of caller identities to label metrics with.",
+        long_help = "Sets the maximum number of distinct authenticated caller identities that per-identity metrics are labeled with, any further identities are grouped under the `other` label.",
+        default_value = "100",
+        takes_value = true
+    )]
+    /// Define the maximum number of caller identities to label metrics with.
+    pub identity_limit: usize,
+
+    #[structopt(
+        long = "metrics-response-time-buckets",
+        env = "RIFT_METRICS_RESPONSE_TIME_BUCKETS",
+        help = "The histogram buckets of request response times in seconds.",
+        long_help = "Sets the comma separated, strictly increasing upper bounds in seconds of the histogram buckets that gRPC request response times are observed into.",
+        default_value = "0.0005,0.001,0.0025,0.005,0.01,0.025,0.05,0.1,0.25,0.5,1,2.5,5,10",
+        takes_value = true
+    )]
+    /// Define the histogram buckets of request response times in seconds.
+    pub response_time_buckets: Buckets,
+
+    #[structopt(
+        long = "metrics-latency-buckets",
+        env = "RIFT_METRICS_LATENCY_BUCKETS",
+        help = "The histogram buckets of end-to-end message latencies in seconds.",
+        long_help = "Sets the comma separated, strictly increasing upper bounds in seconds of the histogram buckets that the time between a message being published and it being delivered or acked is observed into.",
+        default_value = "0.001,0.005,0.01,0.05,0.1,0.5,1,5,10,30,60,300,600,1800,3600",
+        takes_value = true
+    )]
+    /// Define the histogram buckets of end-to-end message latencies in seconds.
+    pub latency_buckets: Buckets,
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_mode() {
+        assert_eq!(
+            PushMode::from_str("pushgateway").unwrap(),
+            PushMode::Pushgateway
+        );
+        assert!(matches!(
+            PushMode::from_str("nope"),
+            Err(Error::InvalidPushMode { mode }) if mode == "nope"
+        ));
+    }
+
+    #[test]
+    fn test_default_buckets() {
+        let cfg = Config::from_iter_safe(&["riftd"]).unwrap();
+        assert_eq!(
+            cfg.response_time_buckets.as_slice(),
+            super::super::RESPONSE_TIME_BUCKETS
+        );
+        assert_eq!(
+            cfg.latency_buckets.as_slice(),
+            super::super::LATENCY_BUCKETS
+        );
+    }
+}
@@ -20,6 +20,12 @@ pub enum Error {
         /// The duplicate metric name used.
         name: String,
     },
+    /// Handles the case where a metric is unregistered without having been registered.
+    #[error("the provided metric is not registered: {name}")]
+    NotRegistered {
+        /// The name of the metric.
+        name: String,
+    },
     /// Handles the case where the number of labels during write differs from the
     /// registered number of labels.
     #[error("the provided label count is incorrect for metric '{name}': got '{got}' but expected '{expected}'")]
@@ -31,6 +37,37 @@ pub enum Error {
         /// The actual number of labels received during write to this metric.
         got: usize,
     },
+    /// Handles the case where an unknown metric push mode is supplied.
+    #[error("the provided metric push mode is invalid: {mode}")]
+    InvalidPushMode {
+        /// The invalid push mode supplied.
+        mode: String,
+    },
+    /// Handles the case where the metric push endpoint is not a valid URL.
+    #[error("the provided metric push url '{url}' is invalid: {reason}")]
+    InvalidPushUrl {
+        /// The invalid url supplied.
+        url: String,
+        /// The reason the url is invalid.
+        reason: String,
+    },
+    /// Handles the case where metrics are to be pushed without any delay between pushes.
+    #[error("the provided metric push interval must be at least one second")]
+    InvalidPushInterval,
+    /// Handles the case where invalid histogram buckets are supplied.
+    #[error("the provided histogram buckets '{buckets}' are invalid: {reason}")]
+    InvalidBuckets {
+        /// The invalid buckets supplied.
+        buckets: String,
+        /// The reason the buckets are invalid.
+        reason: String,
+    },
+    /// Handles failures to push metrics to the configured endpoint.
+    #[error("failed to push metrics: {reason}")]
+    Push {
+        /// The reason the push failed.
+        reason: String,
+    },
     /// Handles unknown error cases.
     #[error("an internal prometheus error occured when handling metric '{name}': {source}")]
     Unknown {
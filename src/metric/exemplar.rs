@@ -0,0 +1,177 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use lazy_static::lazy_static;
+use opentelemetry::trace::TraceContextExt;
+use prometheus::core::{Collector, Metric};
+use prometheus::proto::LabelPair;
+use prometheus::Histogram;
+
+/// The maximum count of bucket series of a single histogram an exemplar is held for. The
+/// oldest exemplar of a histogram is evicted to make room for that of a new series, so that
+/// histograms with many label values do not grow the exemplars held without bound.
+pub const MAX_SERIES_PER_HISTOGRAM: usize = 1024;
+
+lazy_static! {
+    /// The most recent exemplar of every histogram bucket, keyed by the name of the histogram
+    /// and then by [key].
+    static ref EXEMPLARS: Mutex<HashMap<String, HashMap<String, Exemplar>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// An exemplar links a single histogram observation to the trace it was made within.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exemplar {
+    /// The ID of the trace the observation was made within.
+    pub trace_id: String,
+    /// The observed value.
+    pub value: f64,
+    /// The unix timestamp in seconds of when the observation was made.
+    pub timestamp: f64,
+}
+
+/// Build the key identifying a single bucket series of a histogram.
+pub(super) fn key(labels: &[LabelPair], upper_bound: f64) -> String {
+    let labels = labels
+        .iter()
+        .map(|label| format!("{}={}", label.get_name(), label.get_value()))
+        .collect::<Vec<String>>()
+        .join(",");
+    format!("{}|{}", labels, upper_bound)
+}
+
+/// Return the most recent exemplar recorded for the supplied bucket key of the named
+/// histogram, if any.
+pub(super) fn get(name: &str, key: &str) -> Option<Exemplar> {
+    EXEMPLARS.lock().unwrap().get(name)?.get(key).cloned()
+}
+
+/// Record the supplied exemplar for the supplied bucket key of the named histogram, evicting
+/// the oldest exemplar of the histogram if it already holds the maximum count of series.
+fn record(name: String, key: String, exemplar: Exemplar) {
+    let mut exemplars = EXEMPLARS.lock().unwrap();
+    let series = exemplars.entry(name).or_default();
+    if !series.contains_key(&key) && series.len() >= MAX_SERIES_PER_HISTOGRAM {
+        let oldest = series
+            .iter()
+            .min_by(|(_, a), (_, b)| {
+                a.timestamp
+                    .partial_cmp(&b.timestamp)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .map(|(key, _)| key.clone());
+        if let Some(oldest) = oldest {
+            series.remove(&oldest);
+        }
+    }
+    series.insert(key, exemplar);
+}
+
+/// Observe the supplied value, recording it as the exemplar of its bucket if it was made
+/// within a sampled trace. Exemplars are only exposed when metrics are scraped in the
+/// OpenMetrics format.
+pub fn observe(histogram: &Histogram, value: f64) {
+    histogram.observe(value);
+
+    let cx = opentelemetry::Context::current();
+    let span = cx.span();
+    let span_context = span.span_context();
+    if !span_context.is_valid() || !span_context.is_sampled() {
+        return;
+    }
+
+    let name = match histogram.desc().first() {
+        Some(desc) => desc.fq_name.clone(),
+        None => return,
+    };
+    let metric = histogram.metric();
+    let upper_bound = metric
+        .get_histogram()
+        .get_bucket()
+        .iter()
+        .map(|bucket| bucket.get_upper_bound())
+        .find(|upper_bound| value <= *upper_bound)
+        .unwrap_or(f64::INFINITY);
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|ts| ts.as_secs_f64())
+        .unwrap_or_default();
+    let exemplar = Exemplar {
+        trace_id: span_context.trace_id().to_string(),
+        value,
+        timestamp,
+    };
+    record(name, key(metric.get_label(), upper_bound), exemplar);
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    use opentelemetry::trace::{SpanContext, SpanId, TraceFlags, TraceId, TraceState};
+
+    #[test]
+    fn test_observe() {
+        let histogram = Histogram::with_opts(prometheus::HistogramOpts::new(
+            "exemplar_histogram",
+            "A test histogram.",
+        ))
+        .unwrap();
+
+        observe(&histogram, 0.2);
+        assert!(get("exemplar_histogram", &key(&[], 0.25)).is_none());
+
+        let span_context = SpanContext::new(
+            TraceId::from_bytes([1; 16]),
+            SpanId::from_bytes([1; 8]),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        );
+        let cx = opentelemetry::Context::current().with_remote_span_context(span_context);
+        let _guard = cx.attach();
+        observe(&histogram, 0.2);
+        observe(&histogram, 100.0);
+
+        let exemplar = get("exemplar_histogram", &key(&[], 0.25)).unwrap();
+        assert_eq!(exemplar.trace_id, TraceId::from_bytes([1; 16]).to_string());
+        assert_eq!(exemplar.value, 0.2);
+        assert!(get("exemplar_histogram", &key(&[], f64::INFINITY)).is_some());
+        assert_eq!(histogram.get_sample_count(), 3);
+    }
+
+    #[test]
+    fn test_record() {
+        let exemplar = |timestamp: usize| Exemplar {
+            trace_id: String::new(),
+            value: 1.0,
+            timestamp: timestamp as f64,
+        };
+        for series in 0..=MAX_SERIES_PER_HISTOGRAM {
+            let key = format!("series{}", series);
+            record(String::from("bounded_histogram"), key, exemplar(series));
+        }
+
+        // The oldest series is evicted, while other histograms are unaffected.
+        let exemplars = EXEMPLARS.lock().unwrap();
+        let series = &exemplars["bounded_histogram"];
+        assert_eq!(series.len(), MAX_SERIES_PER_HISTOGRAM);
+        assert!(!series.contains_key("series0"));
+        assert!(series.contains_key(&format!("series{}", MAX_SERIES_PER_HISTOGRAM)));
+        drop(exemplars);
+
+        record(
+            String::from("other_histogram"),
+            String::from("series0"),
+            exemplar(0),
+        );
+        assert!(get("bounded_histogram", "series1").is_some());
+        assert!(get("other_histogram", "series0").is_some());
+    }
+}
@@ -0,0 +1,160 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+// crate usings
+use super::{Manager, Opt, Result};
+
+// extern usings
+use prometheus::IntCounterVec;
+
+/// The identity label used once the number of distinct identities exceeds the configured
+/// limit.
+pub const OTHER_IDENTITY: &str = "other";
+
+/// IdentityMetrics tracks request counts and publish/consume message and byte throughput per
+/// caller identity.
+/// The first `limit` identities seen are labeled as-is, while any further identities are
+/// grouped under [OTHER_IDENTITY] to bound the cardinality of the exposed series.
+#[derive(Debug, Clone)]
+pub struct IdentityMetrics {
+    limit: usize,
+    seen: Arc<Mutex<HashSet<String>>>,
+    requests: IntCounterVec,
+    published_messages: IntCounterVec,
+    published_bytes: IntCounterVec,
+    consumed_messages: IntCounterVec,
+    consumed_bytes: IntCounterVec,
+}
+
+impl IdentityMetrics {
+    /// Create and register a new set of identity metrics, tracking at most `limit` distinct
+    /// identities.
+    pub fn new(mm: &Manager, limit: usize) -> Result<Self> {
+        let labels = |labels: &[&str]| {
+            Some(vec![Opt::Labels(
+                labels.iter().map(|label| label.to_string()).collect(),
+            )])
+        };
+        Ok(Self {
+            limit,
+            seen: Arc::default(),
+            requests: mm.register_int_counter_vec(
+                "identity_requests_total",
+                "The total count of gRPC requests by caller identity and method.",
+                labels(&["identity", "method"]),
+            )?,
+            published_messages: mm.register_int_counter_vec(
+                "identity_published_messages_total",
+                "The total count of messages published by caller identity.",
+                labels(&["identity"]),
+            )?,
+            published_bytes: mm.register_int_counter_vec(
+                "identity_published_bytes_total",
+                "The total count of message bytes published by caller identity.",
+                labels(&["identity"]),
+            )?,
+            consumed_messages: mm.register_int_counter_vec(
+                "identity_consumed_messages_total",
+                "The total count of messages delivered by caller identity.",
+                labels(&["identity"]),
+            )?,
+            consumed_bytes: mm.register_int_counter_vec(
+                "identity_consumed_bytes_total",
+                "The total count of message bytes delivered by caller identity.",
+                labels(&["identity"]),
+            )?,
+        })
+    }
+
+    /// Return the label to record the supplied identity under.
+    pub fn label(&self, identity: &str) -> String {
+        let mut seen = self.seen.lock().unwrap();
+        if seen.contains(identity) {
+            return identity.to_string();
+        }
+        if seen.len() < self.limit {
+            seen.insert(identity.to_string());
+            return identity.to_string();
+        }
+        String::from(OTHER_IDENTITY)
+    }
+
+    /// Record a single request made by the supplied identity.
+    pub fn request(&self, identity: &str, method: &str) {
+        self.requests
+            .with_label_values(&[&self.label(identity), method])
+            .inc();
+    }
+
+    /// Record a single message of the supplied number of bytes published by the supplied
+    /// identity.
+    pub fn published(&self, identity: &str, bytes: usize) {
+        let label = self.label(identity);
+        self.published_messages.with_label_values(&[&label]).inc();
+        self.published_bytes
+            .with_label_values(&[&label])
+            .inc_by(bytes as u64);
+    }
+
+    /// Record a single message of the supplied number of bytes delivered to the supplied
+    /// identity.
+    pub fn consumed(&self, identity: &str, bytes: usize) {
+        let label = self.label(identity);
+        self.consumed_messages.with_label_values(&[&label]).inc();
+        self.consumed_bytes
+            .with_label_values(&[&label])
+            .inc_by(bytes as u64);
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identity_metrics() {
+        let mm = Manager::new(
+            String::from("test"),
+            String::from("identity"),
+            String::from("test"),
+        );
+        let metrics = IdentityMetrics::new(&mm, 2).unwrap();
+        assert_eq!(metrics.label("alice"), "alice");
+        assert_eq!(metrics.label("bob"), "bob");
+        assert_eq!(metrics.label("carol"), OTHER_IDENTITY);
+        assert_eq!(metrics.label("alice"), "alice");
+
+        metrics.request("alice", "/pubsub.PubSubService/Publish");
+        metrics.published("alice", 10);
+        metrics.published("carol", 5);
+        metrics.published("dave", 5);
+        metrics.consumed("bob", 3);
+        assert_eq!(
+            metrics
+                .published_bytes
+                .with_label_values(&[OTHER_IDENTITY])
+                .get(),
+            10
+        );
+        assert_eq!(metrics.consumed_bytes.with_label_values(&["bob"]).get(), 3);
+        assert_eq!(
+            metrics
+                .published_messages
+                .with_label_values(&[OTHER_IDENTITY])
+                .get(),
+            2
+        );
+        assert_eq!(
+            metrics
+                .requests
+                .with_label_values(&["alice", "/pubsub.PubSubService/Publish"])
+                .get(),
+            1
+        );
+    }
+}
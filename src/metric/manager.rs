@@ -1,14 +1,15 @@
 // (c) Copyright 2021-2022 Christian Saide
 // SPDX-License-Identifier: GPL-3.0-only
 
+use prometheus::core::Collector;
 use prometheus::{
     Counter, CounterVec, Gauge, GaugeVec, Histogram, HistogramVec, IntCounter, IntCounterVec,
-    IntGauge, IntGaugeVec,
+    IntGauge, IntGaugeVec, Registry,
 };
 
 use super::{
     opt::{to_common_opts, to_histogram_opts},
-    Error, Opt, Result,
+    Error, Opt, Result, Timed, Timer,
 };
 
 /// A Manager handles creating and returning fully qualified metric collectors based on the supplied const labels.
@@ -20,6 +21,7 @@ pub struct Manager {
     pub subsystem: String,
     /// version represents the specific version of the binary the metrics are from.
     pub version: String,
+    registry: Registry,
 }
 
 impl Manager {
@@ -55,15 +57,57 @@ impl Manager {
         opts
     }
 
-    /// Create a new metrics manager instance, based on the supplied naming information.
+    /// Register the supplied collector, returning it so that it can be used.
+    fn register<C>(&self, name: &str, collector: prometheus::Result<C>) -> Result<C>
+    where
+        C: Collector + Clone + 'static,
+    {
+        let collector = collector.map_err(|err| Error::from(name.to_owned(), err))?;
+        self.registry
+            .register(Box::new(collector.clone()))
+            .map_err(|err| Error::from(name.to_owned(), err))?;
+        Ok(collector)
+    }
+
+    /// Create a new metrics manager instance, based on the supplied naming information, which
+    /// registers metrics with the global prometheus registry.
     pub fn new(namespace: String, subsystem: String, version: String) -> Manager {
         Manager {
             namespace,
             subsystem,
             version,
+            registry: prometheus::default_registry().clone(),
         }
     }
 
+    /// Register metrics with the supplied registry rather than the global one, so that
+    /// embedded servers and tests can register the same metric names independently.
+    pub fn with_registry(mut self, registry: Registry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// Return the registry this manager registers metrics with.
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Unregister the supplied metric, previously registered by a manager sharing this
+    /// manager's registry, so that its name can be registered again.
+    pub fn unregister<C>(&self, collector: &C) -> Result<()>
+    where
+        C: Collector + Clone + 'static,
+    {
+        let name = collector
+            .desc()
+            .first()
+            .map(|desc| desc.fq_name.clone())
+            .unwrap_or_default();
+        self.registry
+            .unregister(Box::new(collector.clone()))
+            .map_err(|_| Error::NotRegistered { name })
+    }
+
     /// Register a new generic atomic f64 based counter. This is best used when you need
     /// to track fractional increments as opposed to whole number increments which you should use
     /// an IntCounter for.
@@ -74,7 +118,7 @@ impl Manager {
         user_opts: Option<Vec<Opt>>,
     ) -> Result<Counter> {
         let opts = self.opts(name, help, user_opts);
-        register_counter!(opts).map_err(|err| Error::from(name.to_owned(), err))
+        self.register(name, Counter::with_opts(opts))
     }
 
     /// Register a new generic atomic f64 counter vec. This is best used when you need to track fractional
@@ -88,8 +132,7 @@ impl Manager {
         let opts = self.opts(name, help, user_opts);
         let labels = opts.variable_labels.clone();
         let labels = labels.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
-        register_counter_vec!(opts, labels.as_ref())
-            .map_err(|err| Error::from(name.to_owned(), err))
+        self.register(name, CounterVec::new(opts, &labels))
     }
 
     /// Register a new atomic u64 based counter. This is best used when you need to track whole number
@@ -101,7 +144,7 @@ impl Manager {
         user_opts: Option<Vec<Opt>>,
     ) -> Result<IntCounter> {
         let opts = self.opts(name, help, user_opts);
-        register_int_counter!(opts).map_err(|err| Error::from(name.to_owned(), err))
+        self.register(name, IntCounter::with_opts(opts))
     }
 
     /// Register a new atomic u64 counter vec. This is best used when you need to track  whole number
@@ -115,8 +158,7 @@ impl Manager {
         let opts = self.opts(name, help, user_opts);
         let labels = opts.variable_labels.clone();
         let labels = labels.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
-        register_int_counter_vec!(opts, labels.as_ref())
-            .map_err(|err| Error::from(name.to_owned(), err))
+        self.register(name, IntCounterVec::new(opts, &labels))
     }
 
     /// Register a new generic atomic f64 based gauge. This is best used when you need to track
@@ -129,7 +171,7 @@ impl Manager {
         user_opts: Option<Vec<Opt>>,
     ) -> Result<Gauge> {
         let opts = self.opts(name, help, user_opts);
-        register_gauge!(opts).map_err(|err| Error::from(name.to_owned(), err))
+        self.register(name, Gauge::with_opts(opts))
     }
 
     /// Register a new generic atomic f64 gauge vec. This is best used when you need to track fractional
@@ -144,7 +186,7 @@ impl Manager {
         let opts = self.opts(name, help, user_opts);
         let labels = opts.variable_labels.clone();
         let labels = labels.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
-        register_gauge_vec!(opts, labels.as_ref()).map_err(|err| Error::from(name.to_owned(), err))
+        self.register(name, GaugeVec::new(opts, &labels))
     }
 
     /// Register a new atomic u64 based gauge. This is best used when you need to track whole increments
@@ -157,7 +199,7 @@ impl Manager {
         user_opts: Option<Vec<Opt>>,
     ) -> Result<IntGauge> {
         let opts = self.opts(name, help, user_opts);
-        register_int_gauge!(opts).map_err(|err| Error::from(name.to_owned(), err))
+        self.register(name, IntGauge::with_opts(opts))
     }
 
     /// Register a new atomic u64 gauge vec. This is best used when you need to track whole
@@ -172,8 +214,7 @@ impl Manager {
         let opts = self.opts(name, help, user_opts);
         let labels = opts.variable_labels.clone();
         let labels = labels.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
-        register_int_gauge_vec!(opts, labels.as_ref())
-            .map_err(|err| Error::from(name.to_owned(), err))
+        self.register(name, IntGaugeVec::new(opts, &labels))
     }
 
     /// Register a new generic atmoic f64 based bucketed histogram. This is best when you need to track
@@ -185,7 +226,50 @@ impl Manager {
         user_opts: Option<Vec<Opt>>,
     ) -> Result<Histogram> {
         let opts = self.histogram_opts(name, help, user_opts);
-        register_histogram!(opts).map_err(|err| Error::from(name.to_owned(), err))
+        self.register(name, Histogram::with_opts(opts))
+    }
+
+    /// Register the `rift_build_info` gauge, which is always set to 1 and labeled with the
+    /// version, git SHA, rustc version, and cargo profile this binary was built with.
+    pub fn register_build_info(&self) -> Result<IntGauge> {
+        let name = "rift_build_info";
+        let opts = prometheus::Opts::new(
+            name,
+            "A constant gauge labeled with the build information of this binary.",
+        )
+        .const_label("version", self.version.clone())
+        .const_label("git_sha", env!("RIFT_GIT_SHA"))
+        .const_label("rustc_version", env!("RIFT_RUSTC_VERSION"))
+        .const_label("profile", env!("RIFT_BUILD_PROFILE"));
+        let gauge = self.register(name, IntGauge::with_opts(opts))?;
+        gauge.set(1);
+        Ok(gauge)
+    }
+
+    /// Register a collector exposing the CPU time, resident memory, open file descriptors,
+    /// thread count, and start time of the current process. The collected metrics use the
+    /// standard `process_` prefix within this manager's namespace.
+    #[cfg(target_os = "linux")]
+    pub fn register_process_collector(&self) -> Result<()> {
+        let collector = prometheus::process_collector::ProcessCollector::new(
+            std::process::id() as i32,
+            self.namespace.clone(),
+        );
+        self.registry
+            .register(Box::new(collector))
+            .map_err(|err| Error::from(String::from("process"), err))
+    }
+
+    /// Start a timer which observes the seconds elapsed on the supplied histogram once it is
+    /// dropped.
+    pub fn timer(histogram: &Histogram) -> Timer {
+        Timer::new(histogram)
+    }
+
+    /// Time the supplied future, observing the seconds it takes to complete on the supplied
+    /// histogram. Futures dropped before they complete are not observed.
+    pub fn time<F>(histogram: &Histogram, future: F) -> Timed<F> {
+        Timed::new(histogram, future)
     }
 
     /// Register a new generic atmoic f64 based bucketed histogram vec. This is best when you need to
@@ -199,8 +283,7 @@ impl Manager {
         let opts = self.histogram_opts(name, help, user_opts);
         let labels = opts.common_opts.variable_labels.clone();
         let labels = labels.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
-        register_histogram_vec!(opts, labels.as_ref())
-            .map_err(|err| Error::from(name.to_owned(), err))
+        self.register(name, HistogramVec::new(opts, &labels))
     }
 }
 
@@ -218,6 +301,67 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_build_info() {
+        let mm = manager();
+        let gauge = mm.register_build_info().unwrap();
+        assert_eq!(1, gauge.get());
+        assert!(matches!(
+            mm.register_build_info(),
+            Err(Error::AlreadyRegistered { .. })
+        ));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_process_collector() {
+        let mm = manager();
+        assert!(mm.register_process_collector().is_ok());
+        assert!(matches!(
+            mm.register_process_collector(),
+            Err(Error::AlreadyRegistered { .. })
+        ));
+
+        let families = prometheus::gather();
+        assert!(families
+            .iter()
+            .any(|family| family.get_name() == "testing_process_open_fds"));
+    }
+
+    #[test]
+    fn test_registry() {
+        let registries = [Registry::new(), Registry::new()];
+        let counters = registries
+            .iter()
+            .map(|registry| {
+                let mm = manager().with_registry(registry.clone());
+                mm.register_int_counter("isolated", "A test counter!", None)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        counters[0].inc();
+        assert_eq!(registries[0].gather().len(), 1);
+        assert_eq!(counters[1].get(), 0);
+        assert!(prometheus::gather()
+            .iter()
+            .all(|family| family.get_name() != "testing_test_isolated"));
+
+        let mm = manager().with_registry(registries[0].clone());
+        assert!(matches!(
+            mm.register_int_counter("isolated", "A test counter!", None),
+            Err(Error::AlreadyRegistered { .. })
+        ));
+        mm.unregister(&counters[0]).unwrap();
+        assert!(mm.registry().gather().is_empty());
+        assert!(matches!(
+            mm.unregister(&counters[0]),
+            Err(Error::NotRegistered { name }) if name == "testing_test_isolated"
+        ));
+        assert!(mm
+            .register_int_counter("isolated", "A test counter!", None)
+            .is_ok());
+    }
+
     #[test]
     fn test_counter() {
         let mm = manager();
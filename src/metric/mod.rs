@@ -1,10 +1,28 @@
 // (c) Copyright 2021-2022 Christian Saide
 // SPDX-License-Identifier: GPL-3.0-only
 
+mod buckets;
+mod config;
 mod error;
+/// Trace exemplars attached to histogram observations.
+pub mod exemplar;
+mod identity;
 mod manager;
+mod openmetrics;
 mod opt;
+mod push;
+#[cfg(tokio_unstable)]
+mod runtime;
+mod timer;
 
+pub use buckets::{Buckets, LATENCY_BUCKETS, RESPONSE_TIME_BUCKETS};
+pub use config::{Config, PushMode};
 pub use error::{Error, Result};
+pub use identity::{IdentityMetrics, OTHER_IDENTITY};
 pub use manager::Manager;
+pub use openmetrics::{encode as encode_openmetrics, OPENMETRICS_FORMAT};
 pub use opt::Opt;
+pub use push::Pusher;
+#[cfg(tokio_unstable)]
+pub use runtime::RuntimeSampler;
+pub use timer::{Timed, Timer};
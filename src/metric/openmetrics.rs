@@ -0,0 +1,221 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::fmt::Write;
+
+use prometheus::proto::{LabelPair, MetricFamily, MetricType};
+
+use super::exemplar;
+
+/// The content type of the OpenMetrics text exposition format.
+pub const OPENMETRICS_FORMAT: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn float(value: f64) -> String {
+    if value == f64::INFINITY {
+        String::from("+Inf")
+    } else if value == f64::NEG_INFINITY {
+        String::from("-Inf")
+    } else {
+        value.to_string()
+    }
+}
+
+fn labels(labels: &[LabelPair], extra: Option<(&str, String)>) -> String {
+    let mut pairs = labels
+        .iter()
+        .map(|label| format!("{}=\"{}\"", label.get_name(), escape(label.get_value())))
+        .collect::<Vec<String>>();
+    if let Some((name, value)) = extra {
+        pairs.push(format!("{}=\"{}\"", name, escape(&value)));
+    }
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+/// Encode the supplied metric families in the OpenMetrics text exposition format, attaching
+/// any recorded trace exemplars to histogram buckets.
+pub fn encode(families: &[MetricFamily]) -> String {
+    let mut out = String::new();
+    for family in families {
+        // Counter samples are suffixed with _total, which prometheus counters are usually
+        // already named with, while the family itself is named without it.
+        let name = match family.get_field_type() {
+            MetricType::COUNTER => {
+                let name = family.get_name();
+                name.strip_suffix("_total").unwrap_or(name)
+            }
+            _ => family.get_name(),
+        };
+        let kind = match family.get_field_type() {
+            MetricType::COUNTER => "counter",
+            MetricType::GAUGE => "gauge",
+            MetricType::HISTOGRAM => "histogram",
+            MetricType::SUMMARY => "summary",
+            MetricType::UNTYPED => "unknown",
+        };
+        let _ = writeln!(out, "# HELP {} {}", name, escape(family.get_help()));
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+
+        for metric in family.get_metric() {
+            let label_pairs = metric.get_label();
+            match family.get_field_type() {
+                MetricType::COUNTER => {
+                    let _ = writeln!(
+                        out,
+                        "{}_total{} {}",
+                        name,
+                        labels(label_pairs, None),
+                        float(metric.get_counter().get_value())
+                    );
+                }
+                MetricType::GAUGE => {
+                    let _ = writeln!(
+                        out,
+                        "{}{} {}",
+                        name,
+                        labels(label_pairs, None),
+                        float(metric.get_gauge().get_value())
+                    );
+                }
+                MetricType::HISTOGRAM => {
+                    let histogram = metric.get_histogram();
+                    let mut buckets = histogram
+                        .get_bucket()
+                        .iter()
+                        .map(|bucket| (bucket.get_upper_bound(), bucket.get_cumulative_count()))
+                        .collect::<Vec<(f64, u64)>>();
+                    if buckets.last().map(|(ub, _)| *ub) != Some(f64::INFINITY) {
+                        buckets.push((f64::INFINITY, histogram.get_sample_count()));
+                    }
+                    for (upper_bound, count) in buckets {
+                        let _ = write!(
+                            out,
+                            "{}_bucket{} {}",
+                            name,
+                            labels(label_pairs, Some(("le", float(upper_bound)))),
+                            count
+                        );
+                        if let Some(exemplar) =
+                            exemplar::get(name, &exemplar::key(label_pairs, upper_bound))
+                        {
+                            let _ = write!(
+                                out,
+                                " # {{trace_id=\"{}\"}} {} {}",
+                                exemplar.trace_id,
+                                float(exemplar.value),
+                                exemplar.timestamp
+                            );
+                        }
+                        out.push('\n');
+                    }
+                    let _ = writeln!(
+                        out,
+                        "{}_count{} {}",
+                        name,
+                        labels(label_pairs, None),
+                        histogram.get_sample_count()
+                    );
+                    let _ = writeln!(
+                        out,
+                        "{}_sum{} {}",
+                        name,
+                        labels(label_pairs, None),
+                        float(histogram.get_sample_sum())
+                    );
+                }
+                MetricType::SUMMARY => {
+                    let summary = metric.get_summary();
+                    for quantile in summary.get_quantile() {
+                        let _ = writeln!(
+                            out,
+                            "{}{} {}",
+                            name,
+                            labels(
+                                label_pairs,
+                                Some(("quantile", float(quantile.get_quantile())))
+                            ),
+                            float(quantile.get_value())
+                        );
+                    }
+                    let _ = writeln!(
+                        out,
+                        "{}_count{} {}",
+                        name,
+                        labels(label_pairs, None),
+                        summary.get_sample_count()
+                    );
+                    let _ = writeln!(
+                        out,
+                        "{}_sum{} {}",
+                        name,
+                        labels(label_pairs, None),
+                        float(summary.get_sample_sum())
+                    );
+                }
+                MetricType::UNTYPED => {
+                    let _ = writeln!(
+                        out,
+                        "{}{} {}",
+                        name,
+                        labels(label_pairs, None),
+                        float(metric.get_untyped().get_value())
+                    );
+                }
+            }
+        }
+    }
+    out.push_str("# EOF\n");
+    out
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    use prometheus::core::Collector;
+    use prometheus::{HistogramOpts, HistogramVec, IntCounter};
+
+    #[test]
+    fn test_encode() {
+        let counter = IntCounter::new("om_counter", "A \"test\" counter.").unwrap();
+        counter.inc();
+        let suffixed = IntCounter::new("om_requests_total", "A suffixed counter.").unwrap();
+        let histogram = HistogramVec::new(
+            HistogramOpts::new("om_histogram", "A test histogram.").buckets(vec![1.0]),
+            &["method"],
+        )
+        .unwrap();
+        histogram.with_label_values(&["get"]).observe(0.5);
+
+        let mut families = counter.collect();
+        families.extend(suffixed.collect());
+        families.extend(histogram.collect());
+        let actual = encode(&families);
+        let expected = "# HELP om_counter A \\\"test\\\" counter.
+# TYPE om_counter counter
+om_counter_total 1
+# HELP om_requests A suffixed counter.
+# TYPE om_requests counter
+om_requests_total 0
+# HELP om_histogram A test histogram.
+# TYPE om_histogram histogram
+om_histogram_bucket{method=\"get\",le=\"1\"} 1
+om_histogram_bucket{method=\"get\",le=\"+Inf\"} 1
+om_histogram_count{method=\"get\"} 1
+om_histogram_sum{method=\"get\"} 0.5
+# EOF
+";
+        assert_eq!(actual, expected);
+    }
+}
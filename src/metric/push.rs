@@ -0,0 +1,422 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// crate usings
+use super::{Config, Error, PushMode, Result};
+
+// extern usings
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Method, Request, Uri};
+use prometheus::proto::{LabelPair, MetricFamily, MetricType};
+use prometheus::{Encoder, TextEncoder};
+use prost::Message;
+
+/// The remote-write protocol version implemented by the [Pusher].
+const REMOTE_WRITE_VERSION: &str = "0.1.0";
+
+#[derive(Clone, PartialEq, Message)]
+struct WriteRequest {
+    #[prost(message, repeated, tag = "1")]
+    timeseries: Vec<TimeSeries>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct TimeSeries {
+    #[prost(message, repeated, tag = "1")]
+    labels: Vec<Label>,
+    #[prost(message, repeated, tag = "2")]
+    samples: Vec<Sample>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Label {
+    #[prost(string, tag = "1")]
+    name: String,
+    #[prost(string, tag = "2")]
+    value: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Sample {
+    #[prost(double, tag = "1")]
+    value: f64,
+    #[prost(int64, tag = "2")]
+    timestamp: i64,
+}
+
+/// Return the host name of the running machine, or "unknown" if it can't be determined.
+fn hostname() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| String::from("unknown"))
+}
+
+/// Build the Pushgateway path segments grouping pushed metrics under the supplied label and
+/// value. Values which are not plain URL path characters, including empty values, are base64
+/// encoded as the Pushgateway expects, as it does not accept percent encoded slashes.
+fn grouping(label: &str, value: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | '~');
+    if !value.is_empty() && value.chars().all(plain) {
+        return format!("{}/{}", label, value);
+    }
+    // Empty values are encoded as a lone padding character, as an empty segment is invalid.
+    let encoded = match value {
+        "" => String::from("="),
+        value => base64::encode_config(value, base64::URL_SAFE),
+    };
+    format!("{}@base64/{}", label, encoded)
+}
+
+/// Build a single remote-write series, sorting its labels by name as the protocol requires.
+fn series(
+    name: String,
+    labels: &[LabelPair],
+    extra: &[(&str, String)],
+    value: f64,
+    timestamp: i64,
+) -> TimeSeries {
+    let mut labels = labels
+        .iter()
+        .map(|label| Label {
+            name: label.get_name().to_string(),
+            value: label.get_value().to_string(),
+        })
+        .chain(extra.iter().map(|(name, value)| Label {
+            name: name.to_string(),
+            value: value.clone(),
+        }))
+        .chain(std::iter::once(Label {
+            name: String::from("__name__"),
+            value: name,
+        }))
+        .collect::<Vec<Label>>();
+    labels.sort_by(|a, b| a.name.cmp(&b.name));
+    TimeSeries {
+        labels,
+        samples: vec![Sample { value, timestamp }],
+    }
+}
+
+/// Convert the supplied metric families into a remote-write request, attaching the supplied
+/// labels to every series.
+fn write_request(families: &[MetricFamily], extra: &[(&str, String)]) -> WriteRequest {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|ts| ts.as_millis() as i64)
+        .unwrap_or_default();
+
+    let mut timeseries = Vec::new();
+    for family in families {
+        let name = family.get_name();
+        for metric in family.get_metric() {
+            let labels = metric.get_label();
+            match family.get_field_type() {
+                MetricType::COUNTER => timeseries.push(series(
+                    name.to_string(),
+                    labels,
+                    extra,
+                    metric.get_counter().get_value(),
+                    timestamp,
+                )),
+                MetricType::GAUGE => timeseries.push(series(
+                    name.to_string(),
+                    labels,
+                    extra,
+                    metric.get_gauge().get_value(),
+                    timestamp,
+                )),
+                MetricType::UNTYPED => timeseries.push(series(
+                    name.to_string(),
+                    labels,
+                    extra,
+                    metric.get_untyped().get_value(),
+                    timestamp,
+                )),
+                MetricType::HISTOGRAM => {
+                    let histogram = metric.get_histogram();
+                    for bucket in histogram.get_bucket() {
+                        let mut extra = extra.to_vec();
+                        extra.push(("le", bucket.get_upper_bound().to_string()));
+                        timeseries.push(series(
+                            format!("{}_bucket", name),
+                            labels,
+                            &extra,
+                            bucket.get_cumulative_count() as f64,
+                            timestamp,
+                        ));
+                    }
+                    let mut inf = extra.to_vec();
+                    inf.push(("le", String::from("+Inf")));
+                    timeseries.push(series(
+                        format!("{}_bucket", name),
+                        labels,
+                        &inf,
+                        histogram.get_sample_count() as f64,
+                        timestamp,
+                    ));
+                    timeseries.push(series(
+                        format!("{}_sum", name),
+                        labels,
+                        extra,
+                        histogram.get_sample_sum(),
+                        timestamp,
+                    ));
+                    timeseries.push(series(
+                        format!("{}_count", name),
+                        labels,
+                        extra,
+                        histogram.get_sample_count() as f64,
+                        timestamp,
+                    ));
+                }
+                MetricType::SUMMARY => {
+                    let summary = metric.get_summary();
+                    for quantile in summary.get_quantile() {
+                        let mut extra = extra.to_vec();
+                        extra.push(("quantile", quantile.get_quantile().to_string()));
+                        timeseries.push(series(
+                            name.to_string(),
+                            labels,
+                            &extra,
+                            quantile.get_value(),
+                            timestamp,
+                        ));
+                    }
+                    timeseries.push(series(
+                        format!("{}_sum", name),
+                        labels,
+                        extra,
+                        summary.get_sample_sum(),
+                        timestamp,
+                    ));
+                    timeseries.push(series(
+                        format!("{}_count", name),
+                        labels,
+                        extra,
+                        summary.get_sample_count() as f64,
+                        timestamp,
+                    ));
+                }
+            }
+        }
+    }
+    WriteRequest { timeseries }
+}
+
+/// A Pusher periodically pushes the gathered metrics to a Prometheus Pushgateway or
+/// remote-write endpoint, for instances which can't be scraped.
+#[derive(Debug, Clone)]
+pub struct Pusher {
+    client: Client<HttpConnector>,
+    uri: Uri,
+    mode: PushMode,
+    interval: Duration,
+    job: String,
+    instance: String,
+}
+
+impl Pusher {
+    /// Create a new pusher based on the supplied configuration, returning [None] if no push
+    /// endpoint is configured.
+    pub fn new(cfg: &Config, job: &str) -> Result<Option<Pusher>> {
+        let url = match &cfg.url {
+            Some(url) => url,
+            None => return Ok(None),
+        };
+        let instance = cfg.instance.clone().unwrap_or_else(hostname);
+        if cfg.interval == 0 {
+            return Err(Error::InvalidPushInterval);
+        }
+
+        let target = match cfg.mode {
+            PushMode::Pushgateway => format!(
+                "{}/metrics/{}/{}",
+                url.trim_end_matches('/'),
+                grouping("job", job),
+                grouping("instance", &instance)
+            ),
+            PushMode::RemoteWrite => url.clone(),
+        };
+        let uri = target.parse::<Uri>().map_err(|err| Error::InvalidPushUrl {
+            url: url.clone(),
+            reason: err.to_string(),
+        })?;
+
+        Ok(Some(Pusher {
+            client: Client::new(),
+            uri,
+            mode: cfg.mode,
+            interval: Duration::from_secs(cfg.interval),
+            job: job.to_string(),
+            instance,
+        }))
+    }
+
+    fn request(&self, families: &[MetricFamily]) -> Result<Request<Body>> {
+        let builder = Request::builder().uri(self.uri.clone());
+        let request = match self.mode {
+            PushMode::Pushgateway => {
+                let encoder = TextEncoder::new();
+                let mut buffer = Vec::new();
+                encoder
+                    .encode(families, &mut buffer)
+                    .map_err(|err| Error::Push {
+                        reason: err.to_string(),
+                    })?;
+                builder
+                    .method(Method::PUT)
+                    .header("content-type", encoder.format_type())
+                    .body(Body::from(buffer))
+            }
+            PushMode::RemoteWrite => {
+                let extra = [
+                    ("job", self.job.clone()),
+                    ("instance", self.instance.clone()),
+                ];
+                let body = write_request(families, &extra).encode_to_vec();
+                let body = snap::raw::Encoder::new()
+                    .compress_vec(&body)
+                    .map_err(|err| Error::Push {
+                        reason: err.to_string(),
+                    })?;
+                builder
+                    .method(Method::POST)
+                    .header("content-type", "application/x-protobuf")
+                    .header("content-encoding", "snappy")
+                    .header("x-prometheus-remote-write-version", REMOTE_WRITE_VERSION)
+                    .body(Body::from(body))
+            }
+        };
+        request.map_err(|err| Error::Push {
+            reason: err.to_string(),
+        })
+    }
+
+    /// Push the currently gathered metrics to the configured endpoint, failing if the
+    /// endpoint does not respond before the next push is due.
+    pub async fn push(&self) -> Result<()> {
+        let request = self.request(&prometheus::gather())?;
+        let response = tokio::time::timeout(self.interval, self.client.request(request))
+            .await
+            .map_err(|_| Error::Push {
+                reason: format!("endpoint did not respond within {:?}", self.interval),
+            })?
+            .map_err(|err| Error::Push {
+                reason: err.to_string(),
+            })?;
+        if !response.status().is_success() {
+            return Err(Error::Push {
+                reason: format!("endpoint responded with status {}", response.status()),
+            });
+        }
+        Ok(())
+    }
+
+    /// Push metrics on every configured interval, forever, logging any failed pushes.
+    pub async fn run(self, logger: slog::Logger) {
+        let mut interval = tokio::time::interval(self.interval);
+        loop {
+            interval.tick().await;
+            if let Err(err) = self.push().await {
+                warn!(logger, "Failed to push metrics."; "error" => err.to_string(), "endpoint" => self.uri.to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    use prometheus::core::Collector;
+    use prometheus::{HistogramOpts, HistogramVec};
+
+    use crate::metric::{Buckets, LATENCY_BUCKETS, RESPONSE_TIME_BUCKETS};
+
+    fn config(mode: PushMode) -> Config {
+        Config {
+            url: Some(String::from("http://localhost:9091/")),
+            mode,
+            interval: 15,
+            instance: Some(String::from("edge-1")),
+            identity_limit: 100,
+            response_time_buckets: Buckets::new(RESPONSE_TIME_BUCKETS.to_vec()).unwrap(),
+            latency_buckets: Buckets::new(LATENCY_BUCKETS.to_vec()).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_new() {
+        let mut cfg = config(PushMode::Pushgateway);
+        let pusher = Pusher::new(&cfg, "riftd").unwrap().unwrap();
+        assert_eq!(
+            pusher.uri.to_string(),
+            "http://localhost:9091/metrics/job/riftd/instance/edge-1"
+        );
+
+        cfg.instance = Some(String::from("edge/1"));
+        let pusher = Pusher::new(&cfg, "riftd").unwrap().unwrap();
+        assert_eq!(
+            pusher.uri.to_string(),
+            "http://localhost:9091/metrics/job/riftd/instance@base64/ZWRnZS8x"
+        );
+        cfg.instance = Some(String::new());
+        let pusher = Pusher::new(&cfg, "riftd").unwrap().unwrap();
+        assert!(pusher.uri.to_string().ends_with("/instance@base64/="));
+
+        cfg.interval = 0;
+        assert!(matches!(
+            Pusher::new(&cfg, "riftd"),
+            Err(Error::InvalidPushInterval)
+        ));
+        cfg.interval = 15;
+
+        cfg.url = Some(String::from("not a url"));
+        assert!(matches!(
+            Pusher::new(&cfg, "riftd"),
+            Err(Error::InvalidPushUrl { .. })
+        ));
+
+        cfg.url = None;
+        assert!(Pusher::new(&cfg, "riftd").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_write_request() {
+        let histogram = HistogramVec::new(
+            HistogramOpts::new("push_histogram", "A test histogram.").buckets(vec![1.0]),
+            &["method"],
+        )
+        .unwrap();
+        histogram.with_label_values(&["get"]).observe(0.5);
+
+        let extra = [("job", String::from("riftd"))];
+        let req = write_request(&histogram.collect(), &extra);
+        // One finite bucket, the +Inf bucket, the sum and the count.
+        assert_eq!(req.timeseries.len(), 4);
+
+        let first = &req.timeseries[0];
+        let names = first
+            .labels
+            .iter()
+            .map(|label| label.name.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(names, vec!["__name__", "job", "le", "method"]);
+        assert_eq!(first.labels[0].value, "push_histogram_bucket");
+        assert_eq!(first.samples[0].value, 1.0);
+
+        let pusher = Pusher::new(&config(PushMode::RemoteWrite), "riftd")
+            .unwrap()
+            .unwrap();
+        let req = pusher.request(&histogram.collect()).unwrap();
+        assert_eq!(req.method(), Method::POST);
+        assert_eq!(req.headers().get("content-encoding").unwrap(), "snappy");
+    }
+}
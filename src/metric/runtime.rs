@@ -0,0 +1,159 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::time::Duration;
+
+use prometheus::{CounterVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec};
+use tokio::runtime::Handle;
+
+use super::{Manager, Opt, Result};
+
+/// The RuntimeSampler periodically samples the statistics of a tokio runtime, exposing them as
+/// prometheus metrics. Note that tokio only exposes these statistics when built with
+/// `--cfg tokio_unstable`, and does not track the count of alive tasks, so task activity is
+/// instead exposed as the number of task polls per worker.
+#[derive(Debug, Clone)]
+pub struct RuntimeSampler {
+    workers: IntGauge,
+    injection_queue_depth: IntGauge,
+    remote_schedules: IntCounter,
+    local_queue_depth: IntGaugeVec,
+    busy_seconds: CounterVec,
+    polls: IntCounterVec,
+    steals: IntCounterVec,
+    parks: IntCounterVec,
+}
+
+impl RuntimeSampler {
+    /// Register the runtime metrics using the supplied manager.
+    pub fn new(mm: &Manager) -> Result<Self> {
+        let labels = || Some(vec![Opt::Labels(vec![String::from("worker")])]);
+        Ok(Self {
+            workers: mm.register_int_gauge(
+                "workers",
+                "The number of worker threads used by the runtime.",
+                None,
+            )?,
+            injection_queue_depth: mm.register_int_gauge(
+                "injection_queue_depth",
+                "The number of tasks currently scheduled in the runtime's global queue.",
+                None,
+            )?,
+            remote_schedules: mm.register_int_counter(
+                "remote_schedules_total",
+                "The total count of tasks scheduled from outside of the runtime.",
+                None,
+            )?,
+            local_queue_depth: mm.register_int_gauge_vec(
+                "worker_local_queue_depth",
+                "The number of tasks currently scheduled in each worker's local queue.",
+                labels(),
+            )?,
+            busy_seconds: mm.register_counter_vec(
+                "worker_busy_seconds_total",
+                "The total time in seconds each worker has spent executing tasks.",
+                labels(),
+            )?,
+            polls: mm.register_int_counter_vec(
+                "worker_polls_total",
+                "The total count of tasks polled by each worker.",
+                labels(),
+            )?,
+            steals: mm.register_int_counter_vec(
+                "worker_steals_total",
+                "The total count of tasks each worker has stolen from other workers.",
+                labels(),
+            )?,
+            parks: mm.register_int_counter_vec(
+                "worker_parks_total",
+                "The total count of times each worker has parked waiting for work.",
+                labels(),
+            )?,
+        })
+    }
+
+    /// Sample the runtime behind the supplied handle once, updating all metrics.
+    pub fn sample(&self, handle: &Handle) {
+        let metrics = handle.metrics();
+        self.workers.set(metrics.num_workers() as i64);
+        self.injection_queue_depth
+            .set(metrics.injection_queue_depth() as i64);
+        advance(&self.remote_schedules, metrics.remote_schedule_count());
+
+        for worker in 0..metrics.num_workers() {
+            let label = worker.to_string();
+            let labels = &[label.as_str()];
+            self.local_queue_depth
+                .with_label_values(labels)
+                .set(metrics.worker_local_queue_depth(worker) as i64);
+            advance(
+                &self.polls.with_label_values(labels),
+                metrics.worker_poll_count(worker),
+            );
+            advance(
+                &self.steals.with_label_values(labels),
+                metrics.worker_steal_count(worker),
+            );
+            advance(
+                &self.parks.with_label_values(labels),
+                metrics.worker_park_count(worker),
+            );
+
+            let busy = self.busy_seconds.with_label_values(labels);
+            let total = metrics.worker_total_busy_duration(worker).as_secs_f64();
+            if total > busy.get() {
+                busy.inc_by(total - busy.get());
+            }
+        }
+    }
+
+    /// Sample the runtime behind the supplied handle on every interval, forever.
+    pub async fn run(self, handle: Handle, interval: Duration) {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            self.sample(&handle);
+        }
+    }
+}
+
+/// Advance the supplied counter to the cumulative total reported by the runtime.
+#[inline]
+fn advance(counter: &IntCounter, total: u64) {
+    if total > counter.get() {
+        counter.inc_by(total - counter.get());
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runtime_sampler() {
+        let mm = Manager::new(
+            String::from("testing"),
+            String::from("runtime"),
+            String::from("0.1.0"),
+        );
+        let sampler = RuntimeSampler::new(&mm).unwrap();
+        assert!(RuntimeSampler::new(&mm).is_err());
+
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .build()
+            .unwrap();
+        rt.block_on(async { tokio::spawn(async {}).await }).unwrap();
+        sampler.sample(rt.handle());
+
+        assert_eq!(2, sampler.workers.get());
+        assert_eq!(0, sampler.injection_queue_depth.get());
+
+        // Worker statistics are only flushed as workers park, so just ensure resampling keeps
+        // the counters monotonic.
+        let polls = sampler.polls.with_label_values(&["0"]).get();
+        sampler.sample(rt.handle());
+        assert!(sampler.polls.with_label_values(&["0"]).get() >= polls);
+    }
+}
@@ -0,0 +1,160 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use prometheus::Histogram;
+
+use super::exemplar;
+
+/// A Timer observes the seconds elapsed since it was started on its histogram once it is
+/// dropped, or explicitly observed, whichever comes first.
+///
+/// ```
+/// use librift::metric::Manager;
+///
+/// let histogram = prometheus::Histogram::with_opts(prometheus::HistogramOpts::new(
+///     "doc_timer",
+///     "A histogram timed by the docs.",
+/// ))
+/// .unwrap();
+/// {
+///     let _timer = Manager::timer(&histogram);
+/// }
+/// assert_eq!(histogram.get_sample_count(), 1);
+/// ```
+#[derive(Debug)]
+#[must_use = "the timer observes its histogram as soon as it is dropped"]
+pub struct Timer {
+    histogram: Histogram,
+    start: Instant,
+    done: bool,
+}
+
+impl Timer {
+    /// Start a new timer observing the supplied histogram.
+    pub fn new(histogram: &Histogram) -> Self {
+        Self {
+            histogram: histogram.clone(),
+            start: Instant::now(),
+            done: false,
+        }
+    }
+
+    /// Return the time elapsed since this timer was started.
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Observe the time elapsed so far, returning it.
+    pub fn observe(mut self) -> Duration {
+        self.record()
+    }
+
+    /// Stop this timer without observing anything.
+    pub fn discard(mut self) {
+        self.done = true;
+    }
+
+    fn record(&mut self) -> Duration {
+        let elapsed = self.start.elapsed();
+        exemplar::observe(&self.histogram, elapsed.as_secs_f64());
+        self.done = true;
+        elapsed
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        if !self.done {
+            self.record();
+        }
+    }
+}
+
+/// A future observing the seconds taken for its inner future to complete. Unlike holding a
+/// [Timer] across an await, futures dropped before completing, such as cancelled requests,
+/// are not observed.
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct Timed<F> {
+    future: Pin<Box<F>>,
+    timer: Option<Timer>,
+}
+
+impl<F> Timed<F> {
+    /// Time the supplied future on the supplied histogram, starting immediately.
+    pub fn new(histogram: &Histogram, future: F) -> Self {
+        Self {
+            future: Box::pin(future),
+            timer: Some(Timer::new(histogram)),
+        }
+    }
+}
+
+impl<F: Future> Future for Timed<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let output = match self.future.as_mut().poll(cx) {
+            Poll::Ready(output) => output,
+            Poll::Pending => return Poll::Pending,
+        };
+        if let Some(timer) = self.timer.take() {
+            timer.observe();
+        }
+        Poll::Ready(output)
+    }
+}
+
+impl<F> Drop for Timed<F> {
+    fn drop(&mut self) {
+        if let Some(timer) = self.timer.take() {
+            timer.discard();
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    use prometheus::HistogramOpts;
+
+    fn histogram() -> Histogram {
+        Histogram::with_opts(HistogramOpts::new("timer", "A test timer.")).unwrap()
+    }
+
+    #[test]
+    fn test_timer() {
+        let histogram = histogram();
+        drop(Timer::new(&histogram));
+        assert_eq!(histogram.get_sample_count(), 1);
+
+        let timer = Timer::new(&histogram);
+        std::thread::sleep(Duration::from_millis(5));
+        let elapsed = timer.observe();
+        assert!(elapsed >= Duration::from_millis(5));
+        assert_eq!(histogram.get_sample_count(), 2);
+        assert!(histogram.get_sample_sum() >= elapsed.as_secs_f64());
+
+        Timer::new(&histogram).discard();
+        assert_eq!(histogram.get_sample_count(), 2);
+    }
+
+    #[test]
+    fn test_timed() {
+        let histogram = histogram();
+        let value = tokio_test::block_on(Timed::new(&histogram, async { 1 }));
+        assert_eq!(value, 1);
+        assert_eq!(histogram.get_sample_count(), 1);
+
+        // Cancelled futures are not observed.
+        drop(Timed::new(&histogram, futures::future::pending::<()>()));
+        assert_eq!(histogram.get_sample_count(), 1);
+    }
+}
@@ -0,0 +1,22 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::path::PathBuf;
+
+// extern usings
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, StructOpt)]
+/// Rift pid file configuration.
+pub struct Config {
+    #[structopt(
+        long = "pid-file",
+        env = "RIFT_PID_FILE",
+        help = "The file to write and lock the riftd process ID in.",
+        long_help = "Sets the file that the process ID of riftd is written to and locked for as long as it runs, and removed from on shutdown. riftd refuses to start while another running instance holds the file, guarding against double starts against the same data.",
+        takes_value = true
+    )]
+    /// Define the pid file to hold, if any.
+    pub pid_file: Option<PathBuf>,
+}
@@ -0,0 +1,33 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::io;
+use std::result;
+
+// extern usings
+use thiserror::Error;
+
+/// Custom Result wrapper to simplify usage.
+pub type Result<T> = result::Result<T, Error>;
+
+/// Represents failures acquiring a pid file.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// Handles pid files locked by another running instance.
+    #[error("the pid file '{path}' is held by the running process '{pid}'")]
+    Held {
+        /// The path of the pid file.
+        path: String,
+        /// The process ID recorded within the pid file.
+        pid: String,
+    },
+    /// Handles failures reading, writing, or locking the pid file.
+    #[error("failed to access the pid file '{path}': {source}")]
+    Io {
+        /// The path of the pid file.
+        path: String,
+        /// The initial error cause.
+        source: io::Error,
+    },
+}
@@ -0,0 +1,112 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+// crate usings
+use super::{Error, Result};
+
+// extern usings
+use fs2::FileExt;
+
+/// A pid file holding the ID of the current process, locked until dropped, at which point
+/// it is removed.
+///
+/// The lock is released by the operating system if the process dies, so a pid file left
+/// behind by a crashed instance does not prevent a restart.
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+    file: File,
+}
+
+impl PidFile {
+    /// Lock the supplied pid file and write the ID of the current process to it, failing if
+    /// another running process holds it.
+    pub fn acquire(path: &Path) -> Result<Self> {
+        let io_err = |source: io::Error| Error::Io {
+            path: path.display().to_string(),
+            source,
+        };
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            // Truncated only once locked, as the file may belong to a running instance.
+            .truncate(false)
+            .open(path)
+            .map_err(io_err)?;
+        if file.try_lock_exclusive().is_err() {
+            let mut pid = String::new();
+            file.read_to_string(&mut pid).map_err(io_err)?;
+            return Err(Error::Held {
+                path: path.display().to_string(),
+                pid: pid.trim().to_owned(),
+            });
+        }
+
+        file.set_len(0).map_err(io_err)?;
+        file.seek(SeekFrom::Start(0)).map_err(io_err)?;
+        writeln!(file, "{}", std::process::id()).map_err(io_err)?;
+        file.sync_all().map_err(io_err)?;
+        Ok(Self {
+            path: path.to_owned(),
+            file,
+        })
+    }
+
+    /// Return the path of the pid file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        // Removed before unlocking, so the file is never left unlocked holding our ID.
+        let _ = std::fs::remove_file(&self.path);
+        let _ = self.file.unlock();
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pid_file() {
+        let dir = std::env::temp_dir().join(format!("rift-pid-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("riftd.pid");
+
+        let pid_file = PidFile::acquire(&path).unwrap();
+        assert_eq!(pid_file.path(), path);
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(contents, format!("{}\n", std::process::id()));
+
+        match PidFile::acquire(&path) {
+            Err(Error::Held { pid, .. }) => assert_eq!(pid, std::process::id().to_string()),
+            other => panic!("expected the pid file to be held, got {:?}", other),
+        }
+
+        drop(pid_file);
+        assert!(!path.exists());
+
+        // A pid file left behind by a dead process is taken over.
+        std::fs::write(&path, "999999999\n").unwrap();
+        let pid_file = PidFile::acquire(&path).unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(contents, format!("{}\n", std::process::id()));
+        drop(pid_file);
+
+        assert!(matches!(
+            PidFile::acquire(&dir.join("missing").join("riftd.pid")),
+            Err(Error::Io { .. })
+        ));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,10 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+mod config;
+mod error;
+mod lock;
+
+pub use config::Config;
+pub use error::{Error, Result};
+pub use lock::PidFile;
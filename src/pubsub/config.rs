@@ -0,0 +1,239 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// extern usings
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, StructOpt)]
+/// Rift pubsub queueing and resource limit configuration.
+pub struct Config {
+    #[structopt(
+        long = "max-topics",
+        env = "RIFT_MAX_TOPICS",
+        help = "The maximum count of topics.",
+        long_help = "Sets the maximum count of topics, rejecting the creation of further topics with RESOURCE_EXHAUSTED. Zero disables the limit.",
+        default_value = "0",
+        takes_value = true
+    )]
+    /// Define the maximum count of topics.
+    pub max_topics: usize,
+
+    #[structopt(
+        long = "max-topics-per-namespace",
+        env = "RIFT_MAX_TOPICS_PER_NAMESPACE",
+        help = "The maximum count of topics per namespace.",
+        long_help = "Sets the maximum count of topics within each namespace, including the default namespace of unqualified topic names, rejecting the creation of further topics within it with RESOURCE_EXHAUSTED. This keeps one team from exhausting the topic limit shared by every namespace. Zero disables the limit.",
+        default_value = "0",
+        takes_value = true
+    )]
+    /// Define the maximum count of topics per namespace.
+    pub max_topics_per_namespace: usize,
+
+    #[structopt(
+        long = "max-subscriptions-per-topic",
+        env = "RIFT_MAX_SUBSCRIPTIONS_PER_TOPIC",
+        help = "The maximum count of subscriptions per topic.",
+        long_help = "Sets the maximum count of subscriptions per topic, rejecting the creation of further subscriptions with RESOURCE_EXHAUSTED. Zero disables the limit.",
+        default_value = "0",
+        takes_value = true
+    )]
+    /// Define the maximum count of subscriptions per topic.
+    pub max_subscriptions_per_topic: usize,
+
+    #[structopt(
+        long = "max-pending-per-subscription",
+        env = "RIFT_MAX_PENDING_PER_SUBSCRIPTION",
+        help = "The maximum count of unacked messages per subscription.",
+        long_help = "Sets the maximum count of unacked messages, either pending or leased, held by each subscription, rejecting further publishes to its topic with RESOURCE_EXHAUSTED. Zero disables the limit.",
+        default_value = "0",
+        takes_value = true
+    )]
+    /// Define the maximum count of unacked messages per subscription.
+    pub max_pending_per_subscription: usize,
+
+    #[structopt(
+        long = "max-queued-bytes",
+        env = "RIFT_MAX_QUEUED_BYTES",
+        help = "The maximum count of message bytes queued across every subscription.",
+        long_help = "Sets the maximum count of message bytes, counting data and attributes, queued across every subscription, rejecting further publishes with RESOURCE_EXHAUSTED. Messages are counted once per subscription they are queued on. Zero disables the limit.",
+        default_value = "0",
+        takes_value = true
+    )]
+    /// Define the maximum count of message bytes queued across every subscription.
+    pub max_queued_bytes: usize,
+
+    #[structopt(
+        long = "max-message-bytes",
+        env = "RIFT_MAX_MESSAGE_BYTES",
+        help = "The maximum count of bytes of a single published message.",
+        long_help = "Sets the maximum count of bytes, counting data and attributes, of a single published message, rejecting larger messages with INVALID_ARGUMENT. Zero disables the limit.",
+        default_value = "0",
+        takes_value = true
+    )]
+    /// Define the maximum count of bytes of a single published message.
+    pub max_message_bytes: usize,
+
+    #[structopt(
+        long = "max-attributes",
+        env = "RIFT_MAX_ATTRIBUTES",
+        help = "The maximum count of attributes of a single published message.",
+        long_help = "Sets the maximum count of attributes of a single published message, rejecting messages with more with INVALID_ARGUMENT. The attributes rift reserves for chunks, idempotency keys and claim checks are not counted, and are held to fixed limits of their own. Zero disables the limit.",
+        default_value = "0",
+        takes_value = true
+    )]
+    /// Define the maximum count of attributes of a single published message.
+    pub max_attributes: usize,
+
+    #[structopt(
+        long = "max-attribute-key-bytes",
+        env = "RIFT_MAX_ATTRIBUTE_KEY_BYTES",
+        help = "The maximum count of bytes of a single attribute key.",
+        long_help = "Sets the maximum count of bytes of each attribute key of a published message, rejecting messages with longer keys with INVALID_ARGUMENT. Zero disables the limit.",
+        default_value = "0",
+        takes_value = true
+    )]
+    /// Define the maximum count of bytes of a single attribute key.
+    pub max_attribute_key_bytes: usize,
+
+    #[structopt(
+        long = "max-attribute-value-bytes",
+        env = "RIFT_MAX_ATTRIBUTE_VALUE_BYTES",
+        help = "The maximum count of bytes of a single attribute value.",
+        long_help = "Sets the maximum count of bytes of each attribute value of a published message, rejecting messages with larger values with INVALID_ARGUMENT. Zero disables the limit.",
+        default_value = "0",
+        takes_value = true
+    )]
+    /// Define the maximum count of bytes of a single attribute value.
+    pub max_attribute_value_bytes: usize,
+
+    #[structopt(
+        long = "max-chunk-bytes",
+        env = "RIFT_MAX_CHUNK_BYTES",
+        help = "The maximum count of bytes of chunks held for incomplete chunked messages.",
+        long_help = "Sets the maximum count of bytes of chunks held across every caller while waiting for the rest of their chunked messages, rejecting further chunks with RESOURCE_EXHAUSTED. Zero disables the limit.",
+        default_value = "268435456",
+        takes_value = true
+    )]
+    /// Define the maximum count of bytes of chunks held for incomplete chunked messages.
+    pub max_chunk_bytes: usize,
+
+    #[structopt(
+        long = "max-chunk-bytes-per-caller",
+        env = "RIFT_MAX_CHUNK_BYTES_PER_CALLER",
+        help = "The maximum count of bytes of chunks held for the incomplete chunked messages of each caller.",
+        long_help = "Sets the maximum count of bytes of chunks held for each caller, by authenticated identity or IP, while waiting for the rest of their chunked messages, rejecting further chunks from the caller with RESOURCE_EXHAUSTED. Zero disables the limit.",
+        default_value = "67108864",
+        takes_value = true
+    )]
+    /// Define the maximum count of bytes of chunks held for the incomplete chunked messages of each caller.
+    pub max_chunk_bytes_per_caller: usize,
+
+    #[structopt(
+        long = "queue-shards",
+        env = "RIFT_QUEUE_SHARDS",
+        help = "The count of shards each subscription's queue is split across.",
+        long_help = "Sets the count of shards the messages of each subscription are split across, each behind its own lock, reducing contention between concurrent publishers and consumers on busy subscriptions. Messages are delivered further out of publish order as the count grows.",
+        default_value = "1",
+        takes_value = true
+    )]
+    /// Define the count of shards each subscription's queue is split across.
+    pub queue_shards: usize,
+
+    #[structopt(
+        long = "queue-message-capacity",
+        env = "RIFT_QUEUE_MESSAGE_CAPACITY",
+        help = "The count of messages each subscription's queue is pre-allocated for.",
+        long_help = "Sets the count of messages each subscription's queue pre-allocates room for as it is created, avoiding repeated reallocation as busy subscriptions ramp up, at the cost of memory held by idle ones. The capacity never exceeds --max-pending-per-subscription when that limit is set.",
+        default_value = "0",
+        takes_value = true
+    )]
+    /// Define the count of messages each subscription's queue is pre-allocated for.
+    pub queue_message_capacity: usize,
+
+    #[structopt(
+        long = "queue-waker-capacity",
+        env = "RIFT_QUEUE_WAKER_CAPACITY",
+        help = "The count of waiting streams each subscription's queue is pre-allocated for.",
+        long_help = "Sets the count of subscription streams waiting on messages that each subscription's queue pre-allocates room for as it is created, avoiding repeated reallocation on subscriptions with many concurrent consumers.",
+        default_value = "0",
+        takes_value = true
+    )]
+    /// Define the count of waiting streams each subscription's queue is pre-allocated for.
+    pub queue_waker_capacity: usize,
+
+    #[structopt(
+        long = "fanout-chunk-size",
+        env = "RIFT_FANOUT_CHUNK_SIZE",
+        help = "The count of subscriptions a published message is enqueued into per task.",
+        long_help = "Sets the count of subscriptions each task enqueues a published message into, so that topics with more subscriptions than this fan messages out across concurrent tasks rather than serializing the publish. Zero fans every message out on the publishing task alone.",
+        default_value = "32",
+        takes_value = true
+    )]
+    /// Define the count of subscriptions a published message is enqueued into per task.
+    pub fanout_chunk_size: usize,
+
+    #[structopt(
+        long = "subscription-idle-expiry-ms",
+        env = "RIFT_SUBSCRIPTION_IDLE_EXPIRY_MS",
+        help = "How long in whole milliseconds a subscription may go unconsumed before it is deleted.",
+        long_help = "Sets how long in whole milliseconds a subscription may go without any connected stream and without any acked message before it is deleted, along with its backlog, recording the deletion as an audit event. Zero never deletes idle subscriptions.",
+        default_value = "0",
+        takes_value = true
+    )]
+    /// Define how long in whole milliseconds a subscription may go unconsumed before it is deleted.
+    pub subscription_idle_expiry_ms: u64,
+
+    #[structopt(
+        long = "auto-create-topics",
+        env = "RIFT_AUTO_CREATE_TOPICS",
+        help = "Whether or not to create missing topics as messages are published to them.",
+        long_help = "Sets whether or not publishing to a missing topic creates it with the default options, rather than failing with NOT_FOUND. Created topics are created along with their default subscription, named by --default-subscription-template, which the message creating them is delivered to. Created topics still count against --max-topics.",
+        takes_value = false
+    )]
+    /// Define whether or not to create missing topics as messages are published to them.
+    pub auto_create_topics: bool,
+
+    #[structopt(
+        long = "default-subscription-template",
+        env = "RIFT_DEFAULT_SUBSCRIPTION_TEMPLATE",
+        help = "The name of the default subscription created alongside topics that request one.",
+        long_help = "Sets the name of the subscription created alongside topics whose create request asks for a default subscription, or which are created as messages are published to them, where every `{topic}` is replaced by the name of the topic within its namespace.",
+        default_value = "default",
+        takes_value = true
+    )]
+    /// Define the name of the default subscription created alongside topics that request one.
+    pub default_subscription_template: String,
+
+    #[structopt(
+        long = "dead-letter-topic-template",
+        env = "RIFT_DEAD_LETTER_TOPIC_TEMPLATE",
+        help = "The name of the topic messages nacked as permanent or malformed are moved to.",
+        long_help = "Sets the name of the topic messages nacked as permanent or malformed are moved to, where every `{topic}` is replaced by the name of the topic they were nacked from. Such messages are redelivered like any other nacked message when the topic does not exist, or the template is empty.",
+        default_value = "{topic}.dead-letter",
+        takes_value = true
+    )]
+    /// Define the name of the topic messages nacked as permanent or malformed are moved to.
+    pub dead_letter_topic_template: String,
+
+    #[structopt(
+        long = "replay-messages-per-sec",
+        env = "RIFT_REPLAY_MESSAGES_PER_SEC",
+        help = "The maximum count of messages a redrive moves per second.",
+        long_help = "Sets the maximum count of messages a redrive moves per second, so that redriving a large backlog does not flood the destination and its consumers at once. Redrives of more messages move the first second's worth immediately and replay the rest in the background, reporting their progress through the subscription stats. Zero moves every message immediately.",
+        default_value = "0",
+        takes_value = true
+    )]
+    /// Define the maximum count of messages a redrive moves per second.
+    pub replay_messages_per_sec: u64,
+
+    #[structopt(
+        long = "quarantine-after-expiries",
+        env = "RIFT_QUARANTINE_AFTER_EXPIRIES",
+        help = "The count of different streams a message's leases may expire under before it is quarantined.",
+        long_help = "Sets the count of different streams whose leases of a message may expire, or which disconnect while holding a lease of it, rather than it being acked or nacked, before the message is quarantined out of its subscription, such as one which crashes or hangs every consumer handling it. Quarantined messages are no longer delivered, and are inspected and released through the pubsub service. Zero never quarantines messages, redelivering them however often their leases expire.",
+        default_value = "3",
+        takes_value = true
+    )]
+    /// Define the count of different streams a message's leases may expire under before it is quarantined.
+    pub quarantine_after_expiries: usize,
+}
@@ -33,4 +33,42 @@ pub enum Error {
     /// An error which occrus when a lease index is out of range when attempting to ack/nack a messge.
     #[error("the supplied slot index is out of range.")]
     IndexOutOfRange,
+    /// An error which occurs when publishing to a topic without any subscriptions.
+    #[error("the topic has no subscriptions to deliver the message to")]
+    NoSubscriptions,
+    /// An error which occurs when publishing a message larger than the configured limit.
+    #[error("the message of {size} bytes exceeds the limit of {limit} bytes")]
+    MessageTooLarge {
+        /// The size of the message in bytes.
+        size: usize,
+        /// The configured limit.
+        limit: usize,
+    },
+    /// An error which occurs when creating a topic with an invalid name.
+    #[error("the topic name '{name}' is invalid: {reason}")]
+    InvalidName {
+        /// The invalid name.
+        name: String,
+        /// Why the name is invalid.
+        reason: String,
+    },
+    /// An error which occurs when an operation would exceed a configured resource limit.
+    #[error("the limit of {limit} {resource} has been reached")]
+    LimitExceeded {
+        /// The resource which is limited.
+        resource: &'static str,
+        /// The configured limit.
+        limit: usize,
+    },
+    /// An error which occurs when replaying the messages of a subscription which is already
+    /// being replayed.
+    #[error("the subscription is already being replayed")]
+    ReplayInProgress,
+    /// An error which occurs when a task enqueuing a published message into subscriptions
+    /// fails to complete.
+    #[error("failed to fan out the message: {reason}")]
+    FanOut {
+        /// Why the task failed to complete.
+        reason: String,
+    },
 }
@@ -0,0 +1,106 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use super::Selector;
+
+/// A forwarding rule of a topic, also enqueueing the messages its selector matches into
+/// another topic, optionally renaming their attributes along the way.
+pub struct ForwardRule<T> {
+    topic: Arc<str>,
+    selector: Selector<T>,
+    attributes: Arc<[(String, String)]>,
+}
+
+impl<T> ForwardRule<T> {
+    /// Create a new rule forwarding the messages the supplied selector matches to the named
+    /// topic, renaming each attribute keyed by the supplied map to its value. Attributes
+    /// renamed to an empty key are removed.
+    pub fn new(topic: String, selector: Selector<T>, attributes: HashMap<String, String>) -> Self {
+        let mut attributes: Vec<_> = attributes.into_iter().collect();
+        attributes.sort_unstable();
+        Self {
+            topic: Arc::from(topic),
+            selector,
+            attributes: Arc::from(attributes),
+        }
+    }
+
+    /// Return the name of the topic matching messages are forwarded to.
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    /// Return the selector messages are matched against.
+    pub fn selector(&self) -> &Selector<T> {
+        &self.selector
+    }
+
+    /// Return the attribute renames of this rule, ordered by the attribute they rename.
+    pub fn attributes(&self) -> &[(String, String)] {
+        &self.attributes
+    }
+
+    /// Rename the supplied attributes of a forwarded message. Every rename applies to the
+    /// attributes as they were, so that attributes may be swapped.
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    ///
+    /// use librift::filter::Filter;
+    /// use librift::pubsub::{ForwardRule, Selector};
+    ///
+    /// fn attributes(msg: &HashMap<String, String>) -> &HashMap<String, String> {
+    ///     msg
+    /// }
+    ///
+    /// let selector = Selector::new(Filter::parse("true").unwrap(), attributes);
+    /// let renames = HashMap::from([
+    ///     (String::from("region"), String::from("origin")),
+    ///     (String::from("trace"), String::new()),
+    /// ]);
+    /// let rule = ForwardRule::new(String::from("audit"), selector, renames);
+    ///
+    /// let mut attributes = HashMap::from([
+    ///     (String::from("region"), String::from("eu")),
+    ///     (String::from("trace"), String::from("abc")),
+    /// ]);
+    /// rule.rename(&mut attributes);
+    /// assert_eq!(attributes, HashMap::from([(String::from("origin"), String::from("eu"))]));
+    /// ```
+    pub fn rename(&self, attributes: &mut HashMap<String, String>) {
+        let renamed: Vec<_> = self
+            .attributes
+            .iter()
+            .filter_map(|(from, to)| attributes.remove(from).map(|value| (to, value)))
+            .collect();
+        for (to, value) in renamed {
+            if !to.is_empty() {
+                attributes.insert(to.clone(), value);
+            }
+        }
+    }
+}
+
+impl<T> Clone for ForwardRule<T> {
+    fn clone(&self) -> Self {
+        Self {
+            topic: self.topic.clone(),
+            selector: self.selector.clone(),
+            attributes: self.attributes.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for ForwardRule<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ForwardRule")
+            .field("topic", &self.topic)
+            .field("selector", &self.selector)
+            .field("attributes", &self.attributes)
+            .finish()
+    }
+}
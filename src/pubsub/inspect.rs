@@ -0,0 +1,96 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use super::Slot;
+
+/// The state of a single queue slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SlotState {
+    /// The slot holds no message, and is free to be filled.
+    Empty,
+    /// The slot holds a message waiting to be leased.
+    Filled,
+    /// The slot holds a leased message waiting to be acked or nacked.
+    Locked,
+}
+
+impl fmt::Display for SlotState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SlotState::Empty => "empty",
+            SlotState::Filled => "filled",
+            SlotState::Locked => "locked",
+        })
+    }
+}
+
+/// A snapshot of the state of a single queue slot, taken without copying its message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlotInfo {
+    /// The index of the message within its queue.
+    pub index: usize,
+    /// The state of the slot.
+    pub state: SlotState,
+    /// How long ago the message was published, if the slot holds one.
+    pub age: Option<Duration>,
+    /// The position the message was pushed to its queue at, if the slot holds one.
+    pub sequence: Option<u64>,
+    /// The count of times the message has been leased.
+    pub attempts: u32,
+    /// How long remains until a message nacked with a delay may be leased again, if any.
+    pub delayed: Option<Duration>,
+    /// When the lease of a locked slot expires, if it was not renewed.
+    pub lease_deadline: Option<SystemTime>,
+    /// The ID of the lease of a locked slot.
+    pub lease_id: Option<u64>,
+    /// Who holds the lease of a locked slot, if known.
+    pub owner: Option<Arc<str>>,
+}
+
+impl SlotInfo {
+    /// Take a snapshot of the supplied slot, holding the message with the supplied index.
+    pub(crate) fn new<T>(index: usize, slot: &Slot<T>) -> Self
+    where
+        T: Clone,
+    {
+        let entry = slot.entry();
+        let state = match slot {
+            Slot::Empty => SlotState::Empty,
+            Slot::Filled(_) => SlotState::Filled,
+            Slot::Locked(_) => SlotState::Locked,
+        };
+        let delayed = entry
+            .and_then(|entry| entry.not_before)
+            .map(|not_before| not_before.saturating_duration_since(Instant::now()))
+            .filter(|delay| !delay.is_zero());
+        Self {
+            index,
+            state,
+            age: entry.map(|entry| entry.published.elapsed().unwrap_or_default()),
+            sequence: entry.map(|entry| entry.sequence),
+            attempts: entry.map_or(0, |entry| entry.attempts),
+            delayed,
+            lease_deadline: match slot {
+                Slot::Locked(lease) => Some(lease.deadline()),
+                _ => None,
+            },
+            lease_id: match slot {
+                Slot::Locked(lease) => Some(lease.id()),
+                _ => None,
+            },
+            owner: match slot {
+                Slot::Locked(_) => entry.and_then(|entry| entry.owner.clone()),
+                _ => None,
+            },
+        }
+    }
+
+    /// Return whether the lease of a locked slot has passed its deadline.
+    pub fn is_expired(&self) -> bool {
+        matches!(self.lease_deadline, Some(deadline) if deadline <= SystemTime::now())
+    }
+}
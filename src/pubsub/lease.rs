@@ -25,6 +25,7 @@ pub struct LeaseTag {
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Hash)]
 pub struct Lease<T> {
     ttl: Duration,
+    leased: SystemTime,
     leased_at: Instant,
     id: u64,
     inner: T,
@@ -45,6 +46,7 @@ impl<T> Lease<T> {
             },
             Self {
                 ttl,
+                leased: now,
                 leased_at: leased_at_instant,
                 id,
                 inner,
@@ -57,16 +59,41 @@ impl<T> Lease<T> {
         self.id
     }
 
+    /// Return a reference to the inner type of this lease.
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
     /// Unwrap this lease into its inner type.
     pub fn into_inner(self) -> T {
         self.inner
     }
 
+    /// Return when this lease expires, unless it is renewed first.
+    pub fn deadline(&self) -> SystemTime {
+        let now = SystemTime::now();
+        now.checked_sub(self.leased_at.elapsed())
+            .unwrap_or(now)
+            .add(self.ttl)
+    }
+
     /// Check to see if this lease is expired.
     pub fn expired(&self) -> bool {
         self.leased_at.elapsed().ge(&self.ttl)
     }
 
+    /// Renew this lease, restarting its ttl from now. The returned tag keeps the original lease
+    /// time, with a deadline one ttl from now.
+    pub fn renew(&mut self) -> LeaseTag {
+        self.leased_at = Instant::now();
+        LeaseTag {
+            id: self.id,
+            ttl: self.ttl,
+            leased_at: self.leased,
+            deadline: SystemTime::now().add(self.ttl),
+        }
+    }
+
     /// Checks wether or not the supplied id matches this leases'.
     pub fn valid(&self, o: u64) -> bool {
         self.id == o
@@ -90,4 +117,18 @@ mod tests {
         std::thread::sleep(ttl);
         assert!(lease.expired());
     }
+
+    #[test]
+    fn test_lease_renew() {
+        let ttl = Duration::from_millis(10);
+        let (tag, mut lease) = Lease::new(ttl, ());
+        std::thread::sleep(ttl);
+        assert!(lease.expired());
+
+        let renewed = lease.renew();
+        assert!(!lease.expired());
+        assert_eq!(renewed.id, tag.id);
+        assert_eq!(renewed.leased_at, tag.leased_at);
+        assert!(renewed.deadline > tag.deadline);
+    }
 }
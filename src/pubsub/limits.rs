@@ -0,0 +1,241 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use prometheus::IntGauge;
+
+use super::{Config, Error, Result};
+
+/// The resource label of the topic count limit.
+pub const TOPICS: &str = "topics";
+/// The resource label of the per namespace topic count limit.
+pub const TOPICS_PER_NAMESPACE: &str = "topics_per_namespace";
+/// The resource label of the per topic subscription count limit.
+pub const SUBSCRIPTIONS_PER_TOPIC: &str = "subscriptions_per_topic";
+/// The resource label of the per subscription pending message count limit.
+pub const PENDING_PER_SUBSCRIPTION: &str = "pending_per_subscription";
+/// The resource label of the total queued bytes limit.
+pub const QUEUED_BYTES: &str = "queued_bytes";
+/// The resource label of the per message bytes limit.
+pub const MESSAGE_BYTES: &str = "message_bytes";
+
+/// Caps on the resources held by a [super::Registry], where zero is unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// The maximum count of topics.
+    pub topics: usize,
+    /// The maximum count of topics per namespace.
+    pub topics_per_namespace: usize,
+    /// The maximum count of subscriptions per topic.
+    pub subscriptions_per_topic: usize,
+    /// The maximum count of unacked messages, either pending or leased, per subscription.
+    pub pending_per_subscription: usize,
+    /// The maximum count of message bytes queued across every subscription.
+    pub queued_bytes: usize,
+    /// The maximum count of bytes of a single message.
+    pub message_bytes: usize,
+}
+
+impl From<&Config> for Limits {
+    fn from(cfg: &Config) -> Self {
+        Self {
+            topics: cfg.max_topics,
+            topics_per_namespace: cfg.max_topics_per_namespace,
+            subscriptions_per_topic: cfg.max_subscriptions_per_topic,
+            pending_per_subscription: cfg.max_pending_per_subscription,
+            queued_bytes: cfg.max_queued_bytes,
+            message_bytes: cfg.max_message_bytes,
+        }
+    }
+}
+
+impl Limits {
+    /// Return each limit alongside its resource label.
+    pub fn iter(&self) -> [(&'static str, usize); 6] {
+        [
+            (TOPICS, self.topics),
+            (TOPICS_PER_NAMESPACE, self.topics_per_namespace),
+            (SUBSCRIPTIONS_PER_TOPIC, self.subscriptions_per_topic),
+            (PENDING_PER_SUBSCRIPTION, self.pending_per_subscription),
+            (QUEUED_BYTES, self.queued_bytes),
+            (MESSAGE_BYTES, self.message_bytes),
+        ]
+    }
+
+    /// Check that the supplied usage of the supplied resource may grow by one, failing if it
+    /// is already at the supplied limit.
+    pub(crate) fn check(resource: &'static str, limit: usize, usage: usize) -> Result<()> {
+        if limit > 0 && usage >= limit {
+            return Err(Error::LimitExceeded { resource, limit });
+        }
+        Ok(())
+    }
+}
+
+/// Returns zero, weighing nothing against the queued bytes limit.
+fn weightless<T>(_: &T) -> usize {
+    0
+}
+
+/// The limits of a registry shared by its topics and queues, along with the bytes queued
+/// across all of them.
+pub(crate) struct Budget<T> {
+    pub(crate) limits: Limits,
+    weigh: fn(&T) -> usize,
+    queued: Arc<AtomicUsize>,
+    gauge: Option<IntGauge>,
+}
+
+impl<T> Budget<T> {
+    /// Create a new budget enforcing the supplied limits, weighing messages with the supplied
+    /// function.
+    pub(crate) fn new(limits: Limits, weigh: fn(&T) -> usize) -> Self {
+        Self {
+            limits,
+            weigh,
+            queued: Arc::default(),
+            gauge: None,
+        }
+    }
+
+    /// Report the bytes queued to the supplied gauge.
+    pub(crate) fn with_gauge(mut self, gauge: IntGauge) -> Self {
+        self.gauge = Some(gauge);
+        self
+    }
+
+    /// Return the weight of the supplied message.
+    pub(crate) fn weigh(&self, msg: &T) -> usize {
+        (self.weigh)(msg)
+    }
+
+    /// Check that the supplied message fits within the per message bytes limit.
+    pub(crate) fn check_message(&self, msg: &T) -> Result<()> {
+        let limit = self.limits.message_bytes;
+        let size = self.weigh(msg);
+        if limit > 0 && size > limit {
+            return Err(Error::MessageTooLarge { size, limit });
+        }
+        Ok(())
+    }
+
+    /// Return the count of bytes currently queued.
+    pub(crate) fn queued(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    /// Reserve the supplied count of bytes, failing if that would exceed the limit.
+    pub(crate) fn reserve(&self, bytes: usize) -> Result<()> {
+        let limit = self.limits.queued_bytes;
+        let mut queued = self.queued.load(Ordering::Relaxed);
+        loop {
+            let next = queued + bytes;
+            if limit > 0 && next > limit {
+                return Err(Error::LimitExceeded {
+                    resource: QUEUED_BYTES,
+                    limit,
+                });
+            }
+            match self.queued.compare_exchange_weak(
+                queued,
+                next,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => {
+                    self.report(next);
+                    return Ok(());
+                }
+                Err(actual) => queued = actual,
+            }
+        }
+    }
+
+    /// Release the supplied count of previously reserved bytes.
+    pub(crate) fn release(&self, bytes: usize) {
+        if bytes == 0 {
+            return;
+        }
+        let prev = self.queued.fetch_sub(bytes, Ordering::Relaxed);
+        self.report(prev - bytes);
+    }
+
+    fn report(&self, queued: usize) {
+        if let Some(gauge) = &self.gauge {
+            gauge.set(queued as i64);
+        }
+    }
+}
+
+impl<T> Default for Budget<T> {
+    fn default() -> Self {
+        Self::new(Limits::default(), weightless)
+    }
+}
+
+impl<T> Clone for Budget<T> {
+    fn clone(&self) -> Self {
+        Self {
+            limits: self.limits,
+            weigh: self.weigh,
+            queued: self.queued.clone(),
+            gauge: self.gauge.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Budget<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Budget")
+            .field("limits", &self.limits)
+            .field("queued", &self.queued())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget() {
+        let limits = Limits {
+            queued_bytes: 10,
+            message_bytes: 6,
+            ..Limits::default()
+        };
+        let budget = Budget::new(limits, |msg: &Vec<u8>| msg.len());
+        assert_eq!(budget.weigh(&vec![0; 4]), 4);
+        assert!(budget.check_message(&vec![0; 6]).is_ok());
+        assert!(matches!(
+            budget.check_message(&vec![0; 7]),
+            Err(Error::MessageTooLarge { size: 7, limit: 6 })
+        ));
+
+        let clone = budget.clone();
+        budget.reserve(6).unwrap();
+        clone.reserve(4).unwrap();
+        assert!(matches!(
+            budget.reserve(1),
+            Err(Error::LimitExceeded {
+                resource: QUEUED_BYTES,
+                limit: 10
+            })
+        ));
+        budget.release(6);
+        assert_eq!(clone.queued(), 4);
+        budget.reserve(6).unwrap();
+
+        let unlimited = Budget::<Vec<u8>>::default();
+        assert_eq!(unlimited.weigh(&vec![0; 4]), 0);
+        unlimited.reserve(usize::MAX).unwrap();
+
+        assert!(Limits::check(TOPICS, 0, 100).is_ok());
+        assert!(Limits::check(TOPICS, 2, 1).is_ok());
+        assert!(Limits::check(TOPICS, 2, 2).is_err());
+    }
+}
@@ -0,0 +1,310 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+use prometheus::{
+    Gauge, GaugeVec, Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+};
+
+use super::Rejection;
+use crate::metric::{self, Manager, Opt};
+
+const TOPIC_LABEL: &str = "topic";
+const SUBSCRIPTION_LABEL: &str = "subscription";
+const RESULT_LABEL: &str = "result";
+const RESOURCE_LABEL: &str = "resource";
+const ACK_VALUE: &str = "ack";
+const NACK_VALUE: &str = "nack";
+const PERMANENT_VALUE: &str = "nack_permanent";
+const MALFORMED_VALUE: &str = "nack_malformed";
+const EXPIRED_VALUE: &str = "expired";
+const QUARANTINED_VALUE: &str = "quarantined";
+
+/// The set of labeled metrics tracking the state of every topic and subscription within a
+/// [super::Registry].
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    received: IntCounterVec,
+    results: IntCounterVec,
+    pending: IntGaugeVec,
+    outstanding: IntGaugeVec,
+    delivery_latency: HistogramVec,
+    ack_latency: HistogramVec,
+    backlog: IntGaugeVec,
+    oldest_pending_age: GaugeVec,
+    oldest_unacked_age: GaugeVec,
+    resource_limit: IntGaugeVec,
+    resource_usage: IntGaugeVec,
+}
+
+impl Metrics {
+    /// Register the pubsub metrics using the supplied manager, observing end-to-end latencies
+    /// into the supplied histogram buckets.
+    pub fn new(mm: &Manager, latency_buckets: &[f64]) -> metric::Result<Self> {
+        let labels = || {
+            vec![Opt::Labels(vec![
+                String::from(TOPIC_LABEL),
+                String::from(SUBSCRIPTION_LABEL),
+            ])]
+        };
+        let latency = || {
+            let mut opts = labels();
+            opts.push(Opt::Buckets(latency_buckets.to_vec()));
+            opts
+        };
+        Ok(Self {
+            received: mm.register_int_counter_vec(
+                "total_messages_received",
+                "The total count of messages received by a subscription.",
+                Some(labels()),
+            )?,
+            results: mm.register_int_counter_vec(
+                "message_results",
+                "The total count of leased messages by their result, either ack, nack, a nack rejecting the message as permanent or malformed, an expired lease, or the message being quarantined as its leases kept expiring.",
+                Some(vec![Opt::Labels(vec![
+                    String::from(TOPIC_LABEL),
+                    String::from(SUBSCRIPTION_LABEL),
+                    String::from(RESULT_LABEL),
+                ])]),
+            )?,
+            pending: mm.register_int_gauge_vec(
+                "messages_pending",
+                "The current count of messages awaiting delivery on a subscription.",
+                Some(labels()),
+            )?,
+            outstanding: mm.register_int_gauge_vec(
+                "messages_outstanding",
+                "The current count of leased messages awaiting an ack or nack on a subscription.",
+                Some(labels()),
+            )?,
+            delivery_latency: mm.register_histogram_vec(
+                "delivery_latency_seconds",
+                "The time in seconds between a message being published and its first delivery.",
+                Some(latency()),
+            )?,
+            ack_latency: mm.register_histogram_vec(
+                "ack_latency_seconds",
+                "The time in seconds between a message being published and it being acked.",
+                Some(latency()),
+            )?,
+            backlog: mm.register_int_gauge_vec(
+                "backlog",
+                "The current count of unacked messages, either pending or leased, on a subscription.",
+                Some(labels()),
+            )?,
+            oldest_pending_age: mm.register_gauge_vec(
+                "oldest_pending_age_seconds",
+                "The age in seconds of the oldest message awaiting delivery on a subscription.",
+                Some(labels()),
+            )?,
+            oldest_unacked_age: mm.register_gauge_vec(
+                "oldest_unacked_age_seconds",
+                "The age in seconds of the oldest unacked message, either pending or leased, on a subscription.",
+                Some(labels()),
+            )?,
+            resource_limit: mm.register_int_gauge_vec(
+                "resource_limit",
+                "The configured limit of a resource, where zero is unlimited.",
+                Some(vec![Opt::Labels(vec![String::from(RESOURCE_LABEL)])]),
+            )?,
+            resource_usage: mm.register_int_gauge_vec(
+                "resource_usage",
+                "The current usage of a limited resource, taken from the busiest topic or subscription for per topic and per subscription limits.",
+                Some(vec![Opt::Labels(vec![String::from(RESOURCE_LABEL)])]),
+            )?,
+        })
+    }
+
+    /// Return the gauge tracking the limit of the supplied resource.
+    pub fn resource_limit(&self, resource: &str) -> IntGauge {
+        self.resource_limit.with_label_values(&[resource])
+    }
+
+    /// Return the gauge tracking the usage of the supplied resource.
+    pub fn resource_usage(&self, resource: &str) -> IntGauge {
+        self.resource_usage.with_label_values(&[resource])
+    }
+
+    /// Scope these metrics to the supplied topic.
+    pub fn topic(&self, topic: String) -> TopicMetrics {
+        TopicMetrics {
+            topic,
+            metrics: self.clone(),
+        }
+    }
+}
+
+/// The set of pubsub metrics scoped to a single topic.
+#[derive(Debug, Clone)]
+pub struct TopicMetrics {
+    topic: String,
+    metrics: Metrics,
+}
+
+impl TopicMetrics {
+    /// Return the metrics for the supplied subscription within this topic.
+    pub fn queue(&self, subscription: &str) -> QueueMetrics {
+        let labels = &[self.topic.as_str(), subscription];
+        let metrics = &self.metrics;
+        QueueMetrics {
+            received: metrics.received.with_label_values(labels),
+            acked: metrics.results.with_label_values(&[
+                self.topic.as_str(),
+                subscription,
+                ACK_VALUE,
+            ]),
+            nacked: metrics.results.with_label_values(&[
+                self.topic.as_str(),
+                subscription,
+                NACK_VALUE,
+            ]),
+            permanent: metrics.results.with_label_values(&[
+                self.topic.as_str(),
+                subscription,
+                PERMANENT_VALUE,
+            ]),
+            malformed: metrics.results.with_label_values(&[
+                self.topic.as_str(),
+                subscription,
+                MALFORMED_VALUE,
+            ]),
+            expired: metrics.results.with_label_values(&[
+                self.topic.as_str(),
+                subscription,
+                EXPIRED_VALUE,
+            ]),
+            quarantined: metrics.results.with_label_values(&[
+                self.topic.as_str(),
+                subscription,
+                QUARANTINED_VALUE,
+            ]),
+            pending: metrics.pending.with_label_values(labels),
+            outstanding: metrics.outstanding.with_label_values(labels),
+            delivery_latency: metrics.delivery_latency.with_label_values(labels),
+            ack_latency: metrics.ack_latency.with_label_values(labels),
+            backlog: metrics.backlog.with_label_values(labels),
+            oldest_pending_age: metrics.oldest_pending_age.with_label_values(labels),
+            oldest_unacked_age: metrics.oldest_unacked_age.with_label_values(labels),
+        }
+    }
+
+    /// Remove the series of the supplied subscription within this topic, so that deleted
+    /// subscriptions do not continue to report stale values.
+    pub fn remove(&self, subscription: &str) {
+        let labels = &[self.topic.as_str(), subscription];
+        let metrics = &self.metrics;
+        let _ = metrics.received.remove_label_values(labels);
+        let _ = metrics.pending.remove_label_values(labels);
+        let _ = metrics.outstanding.remove_label_values(labels);
+        let _ = metrics.delivery_latency.remove_label_values(labels);
+        let _ = metrics.ack_latency.remove_label_values(labels);
+        let _ = metrics.backlog.remove_label_values(labels);
+        let _ = metrics.oldest_pending_age.remove_label_values(labels);
+        let _ = metrics.oldest_unacked_age.remove_label_values(labels);
+        for result in [
+            ACK_VALUE,
+            NACK_VALUE,
+            PERMANENT_VALUE,
+            MALFORMED_VALUE,
+            EXPIRED_VALUE,
+            QUARANTINED_VALUE,
+        ] {
+            let _ =
+                metrics
+                    .results
+                    .remove_label_values(&[self.topic.as_str(), subscription, result]);
+        }
+    }
+}
+
+/// The set of pubsub metrics scoped to a single subscription's queue.
+#[derive(Debug, Clone)]
+pub struct QueueMetrics {
+    /// The total count of messages received by the queue.
+    pub received: IntCounter,
+    /// The total count of acked messages.
+    pub acked: IntCounter,
+    /// The total count of nacked messages.
+    pub nacked: IntCounter,
+    /// The total count of messages rejected as permanently failing.
+    pub permanent: IntCounter,
+    /// The total count of messages rejected as malformed.
+    pub malformed: IntCounter,
+    /// The total count of leases which expired before being acked or nacked.
+    pub expired: IntCounter,
+    /// The total count of messages quarantined as their leases kept expiring.
+    pub quarantined: IntCounter,
+    /// The current count of messages awaiting delivery.
+    pub pending: IntGauge,
+    /// The current count of leased messages awaiting an ack or nack.
+    pub outstanding: IntGauge,
+    /// The latency between a message being published and its first delivery.
+    pub delivery_latency: Histogram,
+    /// The latency between a message being published and it being acked.
+    pub ack_latency: Histogram,
+    /// The current count of unacked messages, sampled periodically.
+    pub backlog: IntGauge,
+    /// The age in seconds of the oldest pending message, sampled periodically.
+    pub oldest_pending_age: Gauge,
+    /// The age in seconds of the oldest unacked message, sampled periodically.
+    pub oldest_unacked_age: Gauge,
+}
+
+impl QueueMetrics {
+    /// Return the counter of messages rejected for the supplied reason.
+    pub fn rejected(&self, rejection: Rejection) -> &IntCounter {
+        match rejection {
+            Rejection::Permanent => &self.permanent,
+            Rejection::Malformed => &self.malformed,
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    use prometheus::core::Metric;
+
+    #[test]
+    fn test_metrics() {
+        let mm = Manager::new(
+            String::from("testing"),
+            String::from("pubsub_metrics"),
+            String::from("0.1.0"),
+        );
+        let metrics = Metrics::new(&mm, metric::LATENCY_BUCKETS).unwrap();
+        assert!(Metrics::new(&mm, metric::LATENCY_BUCKETS).is_err());
+
+        let topic = metrics.topic(String::from("topic"));
+        let queue = topic.queue("sub");
+        queue.received.inc();
+        queue.pending.inc();
+        assert_eq!(
+            1,
+            metrics.received.with_label_values(&["topic", "sub"]).get()
+        );
+        assert_eq!(1, topic.queue("sub").pending.get());
+        queue.delivery_latency.observe(90.0);
+        let buckets = queue
+            .delivery_latency
+            .metric()
+            .get_histogram()
+            .get_bucket()
+            .to_vec();
+        assert_eq!(buckets.len(), metric::LATENCY_BUCKETS.len());
+        assert_eq!(buckets[10].get_cumulative_count(), 0);
+        assert_eq!(buckets[11].get_cumulative_count(), 1);
+        queue.rejected(Rejection::Malformed).inc();
+        assert_eq!(
+            1,
+            metrics
+                .results
+                .with_label_values(&["topic", "sub", MALFORMED_VALUE])
+                .get()
+        );
+
+        topic.remove("sub");
+        assert_eq!(0, topic.queue("sub").pending.get());
+    }
+}
@@ -1,22 +1,48 @@
 // (c) Copyright 2021-2022 Christian Saide
 // SPDX-License-Identifier: GPL-3.0
 
+mod config;
 mod error;
+mod forward;
+mod inspect;
 mod lease;
+mod limits;
+mod metrics;
+/// Namespaces qualifying topic names, isolating the topics of different teams.
+pub mod namespace;
+mod quarantine;
 mod queue;
 mod registry;
+mod replay;
+mod route;
+mod selector;
 mod slot;
 mod stream;
 mod sub;
+mod throttle;
 mod topic;
 mod waker;
 
+pub use config::Config;
 pub use error::{Error, Result};
+pub use forward::ForwardRule;
+pub use inspect::{SlotInfo, SlotState};
 pub use lease::{Lease, LeaseTag};
-pub use queue::{Queue, QueueBuilder};
+pub(crate) use limits::Budget;
+pub use limits::{
+    Limits, MESSAGE_BYTES, PENDING_PER_SUBSCRIPTION, QUEUED_BYTES, SUBSCRIPTIONS_PER_TOPIC, TOPICS,
+    TOPICS_PER_NAMESPACE,
+};
+pub use metrics::{Metrics, QueueMetrics, TopicMetrics};
+pub use quarantine::{Quarantine, Quarantined};
+pub use queue::{Queue, QueueBuilder, Rejection};
 pub use registry::Registry;
-pub use slot::Slot;
+pub use replay::{Progress, Replay};
+pub use route::Route;
+pub use selector::Selector;
+pub use slot::{Entry, Slot};
 pub use stream::Stream;
 pub use sub::Sub;
+pub use throttle::{Quota, Throttle};
 pub use topic::Topic;
 pub use waker::Waker;
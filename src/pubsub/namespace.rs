@@ -0,0 +1,108 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+use super::{Error, Result};
+
+/// The separator between the namespace of a topic and its name within that namespace.
+pub const NAMESPACE_SEPARATOR: char = '/';
+/// The namespace of topics whose names are not qualified with one.
+pub const DEFAULT_NAMESPACE: &str = "default";
+/// The prefix of the names of topics rift creates for itself on every member, such as the
+/// audit and system events topics, which is reserved so that no other topic carries it.
+pub const INTERNAL_TOPIC_PREFIX: &str = "__";
+
+/// Split the supplied topic name into its namespace and its name within that namespace.
+/// Unqualified names belong to the [DEFAULT_NAMESPACE].
+///
+/// ```
+/// use librift::pubsub::namespace;
+///
+/// assert_eq!(namespace::split("billing/invoices"), ("billing", "invoices"));
+/// assert_eq!(namespace::split("invoices"), ("default", "invoices"));
+/// ```
+pub fn split(topic: &str) -> (&str, &str) {
+    topic
+        .split_once(NAMESPACE_SEPARATOR)
+        .unwrap_or((DEFAULT_NAMESPACE, topic))
+}
+
+/// Return whether the supplied topic name is that of a topic rift creates for itself.
+pub fn is_internal(topic: &str) -> bool {
+    topic.starts_with(INTERNAL_TOPIC_PREFIX)
+}
+
+/// Return the namespace of the supplied topic name.
+pub fn of(topic: &str) -> &str {
+    split(topic).0
+}
+
+/// Qualify the supplied topic name with the supplied namespace. Names within the
+/// [DEFAULT_NAMESPACE], or an empty one, are left unqualified.
+pub fn qualify(namespace: &str, topic: &str) -> String {
+    if namespace.is_empty() || namespace == DEFAULT_NAMESPACE {
+        return topic.to_owned();
+    }
+    format!("{}{}{}", namespace, NAMESPACE_SEPARATOR, topic)
+}
+
+/// Check that the supplied topic name is either unqualified, or qualified with a single
+/// namespace other than the [DEFAULT_NAMESPACE], which is only ever implied so that every
+/// topic has exactly one name. Names starting with the [INTERNAL_TOPIC_PREFIX] are reserved.
+pub fn validate(topic: &str) -> Result<()> {
+    let invalid = |reason: &str| Error::InvalidName {
+        name: topic.to_owned(),
+        reason: reason.to_owned(),
+    };
+    if is_internal(topic) {
+        return Err(invalid(
+            "names starting with '__' are reserved for topics rift creates for itself",
+        ));
+    }
+    let (namespace, name) = match topic.split_once(NAMESPACE_SEPARATOR) {
+        Some((namespace, name)) => (Some(namespace), name),
+        None => (None, topic),
+    };
+    if name.is_empty() {
+        return Err(invalid("the name must not be empty"));
+    }
+    if name.contains(NAMESPACE_SEPARATOR) {
+        return Err(invalid(
+            "the name may only be qualified with a single namespace",
+        ));
+    }
+    match namespace {
+        Some("") => Err(invalid("the namespace must not be empty")),
+        Some(DEFAULT_NAMESPACE) => Err(invalid(
+            "names within the default namespace must not be qualified",
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        for valid in ["orders", "billing/orders"] {
+            assert!(validate(valid).is_ok(), "{}", valid);
+            assert_eq!(qualify(of(valid), split(valid).1), valid);
+        }
+        for invalid in [
+            "",
+            "billing/",
+            "/orders",
+            "a/b/c",
+            "default/orders",
+            "__orders",
+        ] {
+            assert!(
+                matches!(validate(invalid), Err(Error::InvalidName { .. })),
+                "{}",
+                invalid
+            );
+        }
+    }
+}
@@ -0,0 +1,152 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use super::Entry;
+
+/// A message quarantined out of a subscription, as leases of it kept expiring under different
+/// owners rather than it being acked or nacked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Quarantined<T> {
+    /// The message itself.
+    pub value: T,
+    /// The number of times the message was leased before it was quarantined.
+    pub attempts: u32,
+    /// The distinct owners whose leases of the message expired, in the order they expired.
+    pub expired_by: Vec<Arc<str>>,
+    /// When the message was published to its subscription.
+    pub published: SystemTime,
+    /// When the message was quarantined.
+    pub quarantined: SystemTime,
+}
+
+impl<T> From<Entry<T>> for Quarantined<T> {
+    fn from(entry: Entry<T>) -> Self {
+        Self {
+            value: entry.value,
+            attempts: entry.attempts,
+            expired_by: entry.expired_by,
+            published: entry.published,
+            quarantined: SystemTime::now(),
+        }
+    }
+}
+
+impl<T> From<Quarantined<T>> for Entry<T> {
+    /// Return a quarantined message to an entry, keeping its attempts and the owners whose
+    /// leases of it expired, so that it is quarantined again as soon as it would have been.
+    fn from(quarantined: Quarantined<T>) -> Self {
+        Self {
+            attempts: quarantined.attempts,
+            published: quarantined.published,
+            expired_by: quarantined.expired_by,
+            ..Entry::new(quarantined.value)
+        }
+    }
+}
+
+/// Holds the messages quarantined out of a subscription, oldest first, until they are
+/// released back to it or discarded. Quarantined messages are never delivered, so that they
+/// no longer hold up every consumer of the subscription. Clones share the same messages.
+#[derive(Debug, Clone)]
+pub struct Quarantine<T> {
+    held: Arc<Mutex<VecDeque<Quarantined<T>>>>,
+}
+
+impl<T> Quarantine<T> {
+    /// Quarantine the supplied entry.
+    pub fn hold(&self, entry: Entry<T>) {
+        self.held
+            .lock()
+            .unwrap()
+            .push_back(Quarantined::from(entry));
+    }
+
+    /// Return the count of quarantined messages.
+    pub fn len(&self) -> usize {
+        self.held.lock().unwrap().len()
+    }
+
+    /// Return whether no message is quarantined.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove and return up to the supplied count of the oldest quarantined messages, where
+    /// zero removes every message.
+    pub fn release(&self, max: usize) -> Vec<Quarantined<T>> {
+        let mut held = self.held.lock().unwrap();
+        let count = match max {
+            0 => held.len(),
+            max => max.min(held.len()),
+        };
+        held.drain(..count).collect()
+    }
+
+    /// Return the supplied released messages to the front of the quarantine, in order, such
+    /// as when they could not be returned to their subscription.
+    pub fn restore(&self, released: Vec<Quarantined<T>>) {
+        let mut held = self.held.lock().unwrap();
+        for quarantined in released.into_iter().rev() {
+            held.push_front(quarantined);
+        }
+    }
+}
+
+impl<T> Quarantine<T>
+where
+    T: Clone,
+{
+    /// Return a copy of up to the supplied count of the oldest quarantined messages, where
+    /// zero returns every message.
+    pub fn list(&self, max: usize) -> Vec<Quarantined<T>> {
+        let held = self.held.lock().unwrap();
+        let count = match max {
+            0 => held.len(),
+            max => max,
+        };
+        held.iter().take(count).cloned().collect()
+    }
+}
+
+impl<T> Default for Quarantine<T> {
+    fn default() -> Self {
+        Self {
+            held: Arc::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quarantine() {
+        let quarantine = Quarantine::default();
+        assert!(quarantine.is_empty());
+        for value in 0..3u32 {
+            let mut entry = Entry::new(value);
+            entry.expired_by.push(Arc::from("stream"));
+            quarantine.hold(entry);
+        }
+
+        let shared = quarantine.clone();
+        let listed: Vec<_> = shared.list(2).into_iter().map(|q| q.value).collect();
+        assert_eq!(listed, vec![0, 1]);
+        assert_eq!(quarantine.list(0).len(), 3);
+        assert_eq!(quarantine.list(0)[0].expired_by, vec![Arc::from("stream")]);
+
+        let released = quarantine.release(2);
+        quarantine.restore(released[1..].to_vec());
+        let released: Vec<_> = released.into_iter().map(|q| q.value).collect();
+        assert_eq!(released, vec![0, 1]);
+        assert_eq!(shared.len(), 2);
+        assert_eq!(quarantine.release(0).len(), 2);
+        assert!(shared.is_empty());
+    }
+}
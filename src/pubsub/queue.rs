@@ -1,16 +1,75 @@
 // (c) Copyright 2021-2022 Christian Saide
 // SPDX-License-Identifier: GPL-3.0
 
+use std::cmp::Reverse;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BinaryHeap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::task;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 
+use opentelemetry::trace::{Span, Tracer};
+use opentelemetry::KeyValue;
 use uuid::Uuid;
 
-use super::{Error, LeaseTag, Result, Slot, Waker};
+use super::{
+    Budget, Entry, Error, LeaseTag, Limits, QueueMetrics, Result, Slot, SlotInfo, Waker,
+    PENDING_PER_SUBSCRIPTION,
+};
+use crate::metric;
+use crate::trace;
 
 pub const DEFAULT_TTL: Duration = Duration::from_secs(10);
 pub const NO_CAPACITY: usize = 0;
+pub const DEFAULT_SHARDS: usize = 1;
+
+type Shard<T> = Mutex<Vec<Slot<T>>>;
+
+/// The sequence and index of every message pushed to an ordered queue, lowest sequence first.
+/// Entries are left in place as their messages are removed, and skipped once they surface.
+type Order = BinaryHeap<Reverse<(u64, usize)>>;
+
+/// Why a leased message was rejected outright, rather than nacked to be redelivered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// Handling the message will never succeed, however often it is redelivered.
+    Permanent,
+    /// The message could not be parsed by its consumer.
+    Malformed,
+}
+
+#[inline]
+fn elapsed(since: SystemTime) -> f64 {
+    since.elapsed().unwrap_or_default().as_secs_f64()
+}
+
+/// The count of streams consuming a queue, and when it was last consumed from in whole
+/// milliseconds since the queue was created.
+#[derive(Debug)]
+struct Activity {
+    created: Instant,
+    streams: AtomicUsize,
+    active_ms: AtomicU64,
+}
+
+impl Activity {
+    fn touch(&self) {
+        let now = self.created.elapsed().as_millis() as u64;
+        self.active_ms.store(now, Ordering::Relaxed);
+    }
+}
+
+impl Default for Activity {
+    fn default() -> Self {
+        Self {
+            created: Instant::now(),
+            streams: AtomicUsize::default(),
+            active_ms: AtomicU64::default(),
+        }
+    }
+}
 
 /// The queue builder enables simple setting of various configuraiton options
 /// on a [Queue] instance.
@@ -19,6 +78,8 @@ pub struct QueueBuilder {
     message_cap: Option<usize>,
     subscription_cap: Option<usize>,
     ttl: Option<Duration>,
+    shards: Option<usize>,
+    metrics: Option<QueueMetrics>,
 }
 
 impl QueueBuilder {
@@ -28,7 +89,7 @@ impl QueueBuilder {
         self
     }
 
-    /// Set the initial subscription capacity of the [Queue].
+    /// Set the initial capacity of the [Queue] for subscription streams waiting on messages.
     pub fn with_subscription_capacity(mut self, cap: usize) -> Self {
         self.subscription_cap = Some(cap);
         self
@@ -40,6 +101,20 @@ impl QueueBuilder {
         self
     }
 
+    /// Set the count of shards the slots of the [Queue] are split across, each behind its own
+    /// lock. More shards reduce contention between concurrent publishers and consumers, at the
+    /// cost of delivering messages further out of publish order.
+    pub fn with_shards(mut self, shards: usize) -> Self {
+        self.shards = Some(shards);
+        self
+    }
+
+    /// Set the metrics the [Queue] reports its state to.
+    pub fn with_metrics(mut self, metrics: QueueMetrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
     /// Build the resulting [Queue].
     pub fn build<T>(self) -> Queue<T> {
         Queue::build(self)
@@ -47,24 +122,55 @@ impl QueueBuilder {
 }
 
 /// A basic queue implementation.
+///
+/// Slots are split across shards, each behind its own lock. Messages are pushed to a shard
+/// chosen by the publishing thread, and consumers scan the shards round-robin. The index of a
+/// message interleaves the shards, such that `index % shards` is the shard holding it.
 #[derive(Debug, Clone)]
 pub struct Queue<T> {
     ttl: Duration,
-    slots: Arc<Mutex<Vec<Slot<T>>>>,
+    shards: Arc<Vec<Shard<T>>>,
+    cursor: Arc<AtomicUsize>,
+    held: Arc<AtomicUsize>,
+    leased: Arc<AtomicUsize>,
+    max_leased: Arc<AtomicUsize>,
+    ordered: Arc<AtomicBool>,
+    order: Arc<Mutex<Option<Order>>>,
+    pushed: Arc<AtomicU64>,
     pub(crate) waker: Arc<Mutex<Waker>>,
+    waiting: Arc<AtomicUsize>,
+    metrics: Option<QueueMetrics>,
+    budget: Budget<T>,
+    detached: Arc<AtomicBool>,
+    activity: Arc<Activity>,
 }
 
 impl<T> Queue<T> {
     fn build(builder: QueueBuilder) -> Self {
-        let slots = Vec::with_capacity(builder.message_cap.unwrap_or(NO_CAPACITY));
-        let slots = Arc::new(Mutex::new(slots));
+        let shards = builder.shards.unwrap_or(DEFAULT_SHARDS).max(1);
+        let cap = builder.message_cap.unwrap_or(NO_CAPACITY);
+        let shards = (0..shards)
+            .map(|_| Mutex::new(Vec::with_capacity(cap.div_ceil(shards))))
+            .collect();
 
         let waker = Waker::with_capacity(builder.subscription_cap.unwrap_or(NO_CAPACITY));
         let waker = Arc::new(Mutex::new(waker));
         Self {
             ttl: builder.ttl.unwrap_or(DEFAULT_TTL),
-            slots,
+            shards: Arc::new(shards),
+            cursor: Arc::default(),
+            held: Arc::default(),
+            leased: Arc::default(),
+            max_leased: Arc::default(),
+            ordered: Arc::default(),
+            order: Arc::default(),
+            pushed: Arc::default(),
             waker,
+            waiting: Arc::default(),
+            metrics: builder.metrics,
+            budget: Budget::default(),
+            detached: Arc::default(),
+            activity: Arc::default(),
         }
     }
 
@@ -76,15 +182,157 @@ impl<T> Queue<T> {
     /// Create a new unbounded queue with no defined capacity and a default lease TTL of 10s.
     pub fn new() -> Self {
         // Create backing store for messages.
-        let slots = Arc::new(Mutex::new(Vec::new()));
+        let shards = Arc::new(vec![Mutex::new(Vec::new())]);
         let waker = Arc::new(Mutex::new(Waker::default()));
         // Return a new queue.
         Self {
             ttl: DEFAULT_TTL,
-            slots,
+            shards,
+            cursor: Arc::default(),
+            held: Arc::default(),
+            leased: Arc::default(),
+            max_leased: Arc::default(),
+            ordered: Arc::default(),
+            order: Arc::default(),
+            pushed: Arc::default(),
             waker,
+            waiting: Arc::default(),
+            metrics: None,
+            budget: Budget::default(),
+            detached: Arc::default(),
+            activity: Arc::default(),
+        }
+    }
+
+    /// Enforce the limits of the supplied budget, counting the bytes of queued messages
+    /// against it.
+    pub(crate) fn with_budget(mut self, budget: Budget<T>) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    #[inline]
+    fn metrics(&self, func: impl FnOnce(&QueueMetrics)) {
+        if let Some(metrics) = &self.metrics {
+            func(metrics)
+        }
+    }
+
+    /// Record a stream starting to consume this queue.
+    pub(crate) fn attach_stream(&self) {
+        self.activity.streams.fetch_add(1, Ordering::Relaxed);
+        self.activity.touch();
+    }
+
+    /// Record a stream no longer consuming this queue.
+    pub(crate) fn detach_stream(&self) {
+        self.activity.touch();
+        self.activity.streams.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Deregister the waker of the stream with the supplied ID, if it is waiting.
+    pub(crate) fn deregister_task_waker(&self, id: &Uuid) {
+        if self.waiting.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+        let mut wakers = self.waker.lock().unwrap();
+        wakers.deregister(id);
+        self.waiting.store(wakers.len(), Ordering::Relaxed);
+    }
+
+    /// Return how long this queue has gone without any stream consuming it or any message
+    /// being acked, or [None] while a stream is consuming it.
+    pub fn idle(&self) -> Option<Duration> {
+        if self.activity.streams.load(Ordering::Relaxed) > 0 {
+            return None;
+        }
+        let active = Duration::from_millis(self.activity.active_ms.load(Ordering::Relaxed));
+        Some(self.activity.created.elapsed().saturating_sub(active))
+    }
+
+    /// Limit the messages leased from this queue but not yet acked or nacked to the supplied
+    /// count, where zero removes the limit. Once the limit is reached no further messages are
+    /// leased, and waiting streams are only woken as outstanding leases are settled.
+    pub fn set_max_outstanding(&self, max: usize) {
+        self.max_leased.store(max, Ordering::Relaxed);
+    }
+
+    /// Return the ttl messages are leased for unless another is requested.
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Return the limit on the messages leased from this queue at once, where zero is no limit.
+    pub fn max_outstanding(&self) -> usize {
+        self.max_leased.load(Ordering::Relaxed)
+    }
+
+    /// Deliver the messages of this queue strictly in the order they were pushed, leasing at
+    /// most one at a time and only leasing the next once the previous is acked or rejected. A
+    /// nacked message is redelivered before any later message, and one nacked with a delay
+    /// holds back every later message until the delay elapses.
+    pub fn set_ordered(&self, ordered: bool) {
+        // The order is only tracked while ordered, and rebuilt by the next ordered lease.
+        let mut order = self.order.lock().unwrap();
+        self.ordered.store(ordered, Ordering::Relaxed);
+        if !ordered {
+            *order = None;
         }
     }
+
+    /// Return whether the messages of this queue are delivered strictly in order.
+    pub fn is_ordered(&self) -> bool {
+        self.ordered.load(Ordering::Relaxed)
+    }
+
+    /// Return the count of messages leased from this queue but not yet acked or nacked.
+    pub fn outstanding(&self) -> usize {
+        self.leased.load(Ordering::Relaxed)
+    }
+
+    /// Return the limit on outstanding leases currently enforced, which is one while ordered.
+    #[inline]
+    fn lease_limit(&self) -> usize {
+        if self.is_ordered() {
+            return 1;
+        }
+        self.max_leased.load(Ordering::Relaxed)
+    }
+
+    /// Reserve room for another lease, returning false if the outstanding limit is reached.
+    fn reserve_lease(&self) -> bool {
+        let leased = self.leased.fetch_add(1, Ordering::Relaxed);
+        let max = self.lease_limit();
+        if max > 0 && leased >= max {
+            self.leased.fetch_sub(1, Ordering::Relaxed);
+            return false;
+        }
+        true
+    }
+
+    /// Return the shard holding the supplied message index, and the index within that shard.
+    #[inline]
+    fn locate(&self, index: usize) -> (&Shard<T>, usize) {
+        let shards = self.shards.len();
+        (&self.shards[index % shards], index / shards)
+    }
+
+    /// Return the message index of the supplied index within the supplied shard.
+    #[inline]
+    fn index(&self, shard: usize, local: usize) -> usize {
+        local * self.shards.len() + shard
+    }
+
+    /// Return the shard the current thread pushes to, so that concurrent publishers on
+    /// separate threads contend on separate locks.
+    fn push_shard(&self) -> usize {
+        if self.shards.len() == 1 {
+            return 0;
+        }
+        let mut hasher = DefaultHasher::new();
+        std::thread::current().id().hash(&mut hasher);
+        hasher.finish() as usize % self.shards.len()
+    }
 }
 
 impl<T> Queue<T>
@@ -93,80 +341,821 @@ where
 {
     #[doc(hidden)]
     pub fn register_task_waker(&self, id: Uuid, waker: task::Waker) {
-        self.waker.lock().unwrap().register(id, waker)
+        let mut wakers = self.waker.lock().unwrap();
+        wakers.register(id, waker);
+        self.waiting.store(wakers.len(), Ordering::Relaxed);
+    }
+
+    /// Return whether the message at the supplied index is still held under the supplied
+    /// lease.
+    pub(crate) fn is_leased(&self, lease_id: u64, index: usize) -> bool {
+        let (shard, index) = self.locate(index);
+        let slots = shard.lock().unwrap();
+        matches!(slots.get(index), Some(slot) if slot.is_leased_by(lease_id))
+    }
+
+    /// Wake up to `count` waiting streams in one pass, skipping the waker lock entirely when
+    /// no stream is waiting.
+    fn wake(&self, count: usize) {
+        if count == 0 || self.waiting.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+        let mut wakers = self.waker.lock().unwrap();
+        wakers.wake_many(count);
+        self.waiting.store(wakers.len(), Ordering::Relaxed);
+    }
+
+    /// Release the lease of a settled message, waking a waiting stream if the queue was at its
+    /// outstanding limit, as nothing else would wake it.
+    fn settle(&self) {
+        let leased = self.leased.fetch_sub(1, Ordering::Relaxed);
+        let max = self.lease_limit();
+        if max > 0 && leased >= max {
+            self.wake(1);
+        }
     }
 
     /// Ack the given message index.
     pub fn ack(&self, lease_id: u64, index: usize) -> Result<()> {
-        let mut slots = self.slots.lock().unwrap();
+        self.ack_with(lease_id, index, |_| ())
+    }
+
+    /// Ack the given message index, returning the result of the supplied function applied to
+    /// the acked message.
+    pub fn ack_with<R>(
+        &self,
+        lease_id: u64,
+        index: usize,
+        func: impl FnOnce(&T) -> R,
+    ) -> Result<R> {
+        let mut span = trace::tracer().start("queue.ack");
+        span.set_attribute(KeyValue::new("queue.index", index as i64));
+
+        let (shard, index) = self.locate(index);
+        let mut slots = shard.lock().unwrap();
         if index >= slots.len() {
             return Err(Error::IndexOutOfRange);
         }
+        let published = slots[index].entry().map(|entry| entry.published);
+        let weight = slots[index]
+            .entry()
+            .map_or(0, |entry| self.budget.weigh(&entry.value));
+        let inspected = slots[index].entry().map(|entry| func(&entry.value));
         let res = slots[index].ack(lease_id);
         if res.is_ok() {
-            // MESSAGE_RESULTS.with_label_values(&[ACK_VALUE]).inc();
-            // MESSAGES_OUTSTANDING.dec();
+            self.activity.touch();
+            self.held.fetch_sub(1, Ordering::Relaxed);
+            self.settle();
+            self.release(weight);
+            self.metrics(|metrics| {
+                metrics.acked.inc();
+                metrics.outstanding.dec();
+                if let Some(published) = published {
+                    metric::exemplar::observe(&metrics.ack_latency, elapsed(published));
+                }
+            });
+        }
+        res.and(inspected.ok_or(Error::MustBeLocked))
+    }
+
+    /// Return a copy of the message at the supplied index, which must still be held under the
+    /// supplied lease, without settling it.
+    pub fn leased(&self, lease_id: u64, index: usize) -> Result<T> {
+        let (shard, index) = self.locate(index);
+        let slots = shard.lock().unwrap();
+        match slots.get(index) {
+            Some(slot) if slot.is_leased_by(lease_id) => slot
+                .entry()
+                .map(|entry| entry.value.clone())
+                .ok_or(Error::MustBeLocked),
+            Some(_) => Err(Error::InvalidOrExpiredLease),
+            None => Err(Error::IndexOutOfRange),
+        }
+    }
+
+    /// Reject the given message index, removing it from the queue and returning it so that
+    /// it may be dead-lettered. Rejections are counted by the supplied reason.
+    pub fn reject(&self, lease_id: u64, index: usize, rejection: Rejection) -> Result<T> {
+        let mut span = trace::tracer().start("queue.reject");
+        span.set_attribute(KeyValue::new("queue.index", index as i64));
+
+        let (shard, index) = self.locate(index);
+        let mut slots = shard.lock().unwrap();
+        if index >= slots.len() {
+            return Err(Error::IndexOutOfRange);
+        }
+        let value = match slots[index].entry() {
+            Some(entry) => entry.value.clone(),
+            None => return Err(Error::MustBeLocked),
+        };
+        slots[index].ack(lease_id)?;
+        self.activity.touch();
+        self.held.fetch_sub(1, Ordering::Relaxed);
+        self.settle();
+        self.release(self.budget.weigh(&value));
+        self.metrics(|metrics| {
+            metrics.rejected(rejection).inc();
+            metrics.outstanding.dec();
+        });
+        Ok(value)
+    }
+
+    /// Return every leased message whose lease expired to pending, so that it is redelivered,
+    /// recording the owner of each expired lease. Pending messages whose leases expired, or
+    /// were abandoned, under at least the supplied count of distinct owners are instead removed
+    /// and returned, so that they can be quarantined, where zero never removes a message.
+    pub fn expire(&self, quarantine_after: usize) -> Vec<Entry<T>> {
+        let mut span = trace::tracer().start("queue.expire");
+
+        let (mut expired, mut requeued, mut quarantined) = (0, 0, Vec::new());
+        for shard in self.shards.iter() {
+            let mut slots = shard.lock().unwrap();
+            for slot in slots.iter_mut() {
+                let expiring = slot.is_expired() && matches!(slot.expired(), Ok(true));
+                if expiring {
+                    expired += 1;
+                    self.settle();
+                    self.metrics(|metrics| {
+                        metrics.expired.inc();
+                        metrics.pending.inc();
+                        metrics.outstanding.dec();
+                    });
+                }
+                if !slot.is_filled() {
+                    continue;
+                }
+                let poisoned = matches!(
+                    slot.entry(),
+                    Some(entry) if quarantine_after > 0 && entry.expired_by.len() >= quarantine_after
+                );
+                if !poisoned {
+                    requeued += expiring as usize;
+                    continue;
+                }
+                let entry = match std::mem::take(slot) {
+                    Slot::Filled(entry) => entry,
+                    _ => unreachable!(),
+                };
+                self.held.fetch_sub(1, Ordering::Relaxed);
+                self.release(self.budget.weigh(&entry.value));
+                self.metrics(|metrics| {
+                    metrics.quarantined.inc();
+                    metrics.pending.dec();
+                });
+                quarantined.push(entry);
+            }
+        }
+        span.set_attribute(KeyValue::new("queue.expired", expired as i64));
+        // The expired messages left pending are redelivered, so wake a waiting stream for each.
+        self.wake(requeued);
+        quarantined
+    }
+
+    /// Return every leased message to pending at once, as though each of their leases was
+    /// nacked, so that they are redelivered without waiting for the leases to expire. Returns
+    /// the count of leases released.
+    pub fn release_leases(&self) -> usize {
+        let mut span = trace::tracer().start("queue.release_leases");
+
+        let mut released = 0;
+        for shard in self.shards.iter() {
+            let mut slots = shard.lock().unwrap();
+            for slot in slots.iter_mut() {
+                let lease_id = match slot {
+                    Slot::Locked(lease) => lease.id(),
+                    _ => continue,
+                };
+                if slot.nack(lease_id).is_err() {
+                    continue;
+                }
+                released += 1;
+                self.settle();
+                self.metrics(|metrics| {
+                    metrics.nacked.inc();
+                    metrics.pending.inc();
+                    metrics.outstanding.dec();
+                });
+            }
+        }
+        span.set_attribute(KeyValue::new("queue.released", released as i64));
+        self.wake(released);
+        released
+    }
+
+    /// Nack the given message index on behalf of a lease holder which went away without
+    /// settling it, counting the lease as expired under its owner so that a message which keeps
+    /// taking down the streams it is delivered on is eventually quarantined by [Queue::expire].
+    pub fn abandon(&self, lease_id: u64, index: usize) -> Result<()> {
+        let mut span = trace::tracer().start("queue.abandon");
+        span.set_attribute(KeyValue::new("queue.index", index as i64));
+
+        let (shard, index) = self.locate(index);
+        let mut slots = shard.lock().unwrap();
+        if index >= slots.len() {
+            return Err(Error::IndexOutOfRange);
+        }
+        let res = slots[index].abandon(lease_id);
+        drop(slots);
+        if res.is_ok() {
+            self.settle();
+            self.metrics(|metrics| {
+                metrics.nacked.inc();
+                metrics.pending.inc();
+                metrics.outstanding.dec();
+            });
+            self.wake(1);
         }
         res
     }
 
     /// Nack the given message index.
     pub fn nack(&self, lease_id: u64, index: usize) -> Result<()> {
-        let mut slots = self.slots.lock().unwrap();
+        self.requeue(lease_id, index, Duration::ZERO)?;
+        // The nacked message is pending again, so wake a waiting stream to redeliver it.
+        self.wake(1);
+        Ok(())
+    }
+
+    /// Nack the given message index, holding it back from redelivery until the supplied delay
+    /// elapses. A waiting stream is woken once the delay elapses when called on a runtime,
+    /// otherwise the message is only noticed by the next poll of a stream.
+    pub fn nack_with_delay(&self, lease_id: u64, index: usize, delay: Duration) -> Result<()>
+    where
+        T: Send + 'static,
+    {
+        if delay.is_zero() {
+            return self.nack(lease_id, index);
+        }
+        self.requeue(lease_id, index, delay)?;
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            let queue = self.clone();
+            runtime.spawn(async move {
+                tokio::time::sleep(delay).await;
+                queue.wake(1);
+            });
+        }
+        Ok(())
+    }
+
+    fn requeue(&self, lease_id: u64, index: usize, delay: Duration) -> Result<()> {
+        let mut span = trace::tracer().start("queue.nack");
+        span.set_attribute(KeyValue::new("queue.index", index as i64));
+
+        let (shard, index) = self.locate(index);
+        let mut slots = shard.lock().unwrap();
         if index >= slots.len() {
             return Err(Error::IndexOutOfRange);
         }
-        let res = slots[index].nack(lease_id);
+        let res = slots[index].nack_with_delay(lease_id, delay);
         if res.is_ok() {
-            // MESSAGE_RESULTS.with_label_values(&[NACK_VALUE]).inc();
-            // MESSAGES_PENDING.inc();
-            // MESSAGES_OUTSTANDING.dec();
+            self.settle();
+            self.metrics(|metrics| {
+                metrics.nacked.inc();
+                metrics.pending.inc();
+                metrics.outstanding.dec();
+            });
         }
         res
     }
 
+    /// Renew the lease on the given message index, returning the renewed lease.
+    pub fn renew(&self, lease_id: u64, index: usize) -> Result<LeaseTag> {
+        let mut span = trace::tracer().start("queue.renew");
+        span.set_attribute(KeyValue::new("queue.index", index as i64));
+
+        let (shard, index) = self.locate(index);
+        let mut slots = shard.lock().unwrap();
+        if index >= slots.len() {
+            return Err(Error::IndexOutOfRange);
+        }
+        slots[index].renew(lease_id)
+    }
+
     /// Push a new message into the queue.
     pub fn push(&self, msg: T) -> Result<()> {
-        let mut slots = self.slots.lock().unwrap();
-        let empty = match slots.iter_mut().find(|slot| slot.is_empty()) {
-            Some(empty) => empty,
+        let _span = trace::tracer().start("queue.push");
+
+        let shard = self.push_shard();
+        self.fill(
+            shard,
+            &mut self.shards[shard].lock().unwrap(),
+            Entry::new(msg),
+        )?;
+        // Lets wake the oldest waker, if it exists, so that it can consume
+        // this new message on the next poll.
+        self.wake(1);
+        Ok(())
+    }
+
+    /// Push the supplied messages into the queue under a single lock, waking up to one
+    /// waiting stream per message pushed once they are all in place. Pushing stops at the
+    /// first failure, so the count of messages pushed is returned along with that failure.
+    pub fn push_batch(&self, msgs: impl IntoIterator<Item = T>) -> (usize, Result<()>) {
+        self.push_entries(msgs.into_iter().map(Entry::new))
+    }
+
+    /// Push the supplied entries into the queue as [Queue::push_batch] does, keeping their
+    /// attempts, lease owner history and publish time, such as when returning quarantined
+    /// messages. Each entry is still pushed behind every message already held.
+    pub fn push_entries(&self, entries: impl IntoIterator<Item = Entry<T>>) -> (usize, Result<()>) {
+        let mut span = trace::tracer().start("queue.push_batch");
+
+        let mut pushed = 0;
+        let res = {
+            let shard = self.push_shard();
+            let mut slots = self.shards[shard].lock().unwrap();
+            entries.into_iter().try_for_each(|entry| {
+                self.fill(shard, &mut slots, entry)?;
+                pushed += 1;
+                Ok(())
+            })
+        };
+        span.set_attribute(KeyValue::new("queue.pushed", pushed as i64));
+        self.wake(pushed);
+        (pushed, res)
+    }
+
+    /// Push a message into the queue for which room was already reserved by [Queue::reserve],
+    /// such as while delivering a message to many queues at once, returning the reservation
+    /// should it fail.
+    pub(crate) fn push_reserved(&self, msg: T, weight: usize) -> Result<()> {
+        let shard = self.push_shard();
+        self.place(
+            shard,
+            &mut self.shards[shard].lock().unwrap(),
+            Entry::new(msg),
+            weight,
+        )?;
+        self.wake(1);
+        Ok(())
+    }
+
+    /// Reserve room for the supplied message within the limits of this queue's budget, without
+    /// pushing it, returning the weight reserved. The reservation must be either pushed with
+    /// [Queue::push_reserved] or returned with [Queue::unreserve].
+    pub(crate) fn reserve(&self, msg: &T) -> Result<usize> {
+        let held = self.held.fetch_add(1, Ordering::Relaxed);
+        let limit = self.budget.limits.pending_per_subscription;
+        if let Err(err) = Limits::check(PENDING_PER_SUBSCRIPTION, limit, held) {
+            self.held.fetch_sub(1, Ordering::Relaxed);
+            return Err(err);
+        }
+
+        // Detached queues are no longer counted against the budget.
+        let weight = if self.detached.load(Ordering::Relaxed) {
+            0
+        } else {
+            self.budget.weigh(msg)
+        };
+        if let Err(err) = self.budget.reserve(weight) {
+            self.held.fetch_sub(1, Ordering::Relaxed);
+            return Err(err);
+        }
+        Ok(weight)
+    }
+
+    /// Return room reserved by [Queue::reserve] without pushing a message into it.
+    pub(crate) fn unreserve(&self, weight: usize) {
+        self.held.fetch_sub(1, Ordering::Relaxed);
+        self.budget.release(weight);
+    }
+
+    /// Fill an empty slot of the supplied shard, whose slots are supplied locked, with the
+    /// supplied entry, enforcing the limits of this queue's budget.
+    fn fill(&self, shard: usize, slots: &mut Vec<Slot<T>>, entry: Entry<T>) -> Result<()> {
+        let weight = self.reserve(&entry.value)?;
+        self.place(shard, slots, entry, weight)
+    }
+
+    /// Fill an empty slot of the supplied shard, whose slots are supplied locked, with the
+    /// supplied entry of the supplied reserved weight, behind every entry already held.
+    fn place(
+        &self,
+        shard: usize,
+        slots: &mut Vec<Slot<T>>,
+        mut entry: Entry<T>,
+        mut weight: usize,
+    ) -> Result<()> {
+        // The queue may have been detached since the room was reserved, releasing the bytes it
+        // held while this message was not yet among them.
+        if weight > 0 && self.detached.load(Ordering::Relaxed) {
+            self.budget.release(weight);
+            weight = 0;
+        }
+
+        let local = match slots.iter().position(Slot::is_empty) {
+            Some(local) => local,
             None => {
                 slots.push(Slot::Empty);
-                slots.last_mut().unwrap()
+                slots.len() - 1
             }
         };
 
-        let res = empty.fill(msg);
+        let sequence = self.pushed.fetch_add(1, Ordering::Relaxed);
+        entry.sequence = sequence;
+        let res = slots[local].fill_entry(entry);
         if res.is_ok() {
-            // TOTAL_MESSAGES_RECEIVED.inc();
-            // MESSAGES_PENDING.inc();
-
-            // Lets wake the oldest waker, if it exists, so that it can consume
-            // this new message on the next poll.
-            self.waker.lock().unwrap().wake();
+            if self.is_ordered() {
+                if let Some(order) = self.order.lock().unwrap().as_mut() {
+                    order.push(Reverse((sequence, self.index(shard, local))));
+                }
+            }
+            self.metrics(|metrics| {
+                metrics.received.inc();
+                metrics.pending.inc();
+            });
+        } else {
+            self.unreserve(weight);
         }
         res
     }
 
-    /// Get the next available message from the front of the queue.
+    /// Get the next available message from the front of the queue, scanning the shards
+    /// round-robin.
     pub fn next(&self) -> Option<(LeaseTag, usize, T)> {
-        let mut slots = self.slots.lock().unwrap();
-        let (idx, next) = match slots
-            .iter_mut()
-            .enumerate()
-            .find(|(_, slot)| slot.is_filled())
-        {
-            Some(res) => res,
-            _ => return None,
+        self.next_with_ttl(self.ttl)
+    }
+
+    /// Get the next available message from the front of the queue, leasing it for the
+    /// supplied ttl rather than the ttl of the queue.
+    pub fn next_with_ttl(&self, ttl: Duration) -> Option<(LeaseTag, usize, T)> {
+        self.next_for(ttl, None)
+    }
+
+    /// Get the next available message from the front of the queue, leasing it for the
+    /// supplied ttl to the supplied owner, which is reported when the queue is inspected.
+    pub fn next_for(
+        &self,
+        ttl: Duration,
+        owner: Option<&Arc<str>>,
+    ) -> Option<(LeaseTag, usize, T)> {
+        self.next_where(ttl, owner, None)
+    }
+
+    /// Get the next available message from the front of the queue as [Queue::next_for] does,
+    /// only leasing messages the supplied filter, if any, accepts. Messages it refuses are left
+    /// untouched for other consumers, and while ordered nothing is leased until they are gone.
+    pub fn next_where(
+        &self,
+        ttl: Duration,
+        owner: Option<&Arc<str>>,
+        filter: Option<&dyn Fn(&T) -> bool>,
+    ) -> Option<(LeaseTag, usize, T)> {
+        if !self.reserve_lease() {
+            return None;
+        }
+        let next = self.lease(ttl, owner, filter);
+        if next.is_none() {
+            self.leased.fetch_sub(1, Ordering::Relaxed);
+        }
+        next
+    }
+
+    /// Lease up to the supplied count of the messages available from the queue, oldest first,
+    /// for moving them elsewhere. Unlike [Queue::next], this neither waits for earlier messages
+    /// while ordered nor honours the limit on outstanding leases, though the leases still count
+    /// towards it until they are settled.
+    pub fn lease_pending(&self, max: usize) -> Vec<(LeaseTag, usize, T)> {
+        let mut due = Vec::new();
+        for (shard, slots) in self.shards.iter().enumerate() {
+            let slots = slots.lock().unwrap();
+            due.extend(slots.iter().enumerate().filter_map(|(local, slot)| {
+                slot.entry()
+                    .filter(|_| slot.is_due())
+                    .map(|entry| (entry.sequence, self.index(shard, local)))
+            }));
+        }
+        due.sort_unstable();
+
+        let mut leased = Vec::with_capacity(max.min(due.len()));
+        for (sequence, idx) in due {
+            if leased.len() == max {
+                break;
+            }
+            let (shard, local) = self.locate(idx);
+            let mut slots = shard.lock().unwrap();
+            // The message may have been leased or replaced since the scan.
+            let next = match slots.get_mut(local) {
+                Some(next)
+                    if next.is_due() && next.entry().map(|e| e.sequence) == Some(sequence) =>
+                {
+                    next
+                }
+                _ => continue,
+            };
+            if let Some(lease) = self.lock_slot(SystemTime::now(), idx, next, self.ttl, None) {
+                self.leased.fetch_add(1, Ordering::Relaxed);
+                leased.push(lease);
+            }
+        }
+        leased
+    }
+
+    fn lease(
+        &self,
+        ttl: Duration,
+        owner: Option<&Arc<str>>,
+        filter: Option<&dyn Fn(&T) -> bool>,
+    ) -> Option<(LeaseTag, usize, T)> {
+        if self.is_ordered() {
+            return self.lease_oldest(ttl, owner, filter);
+        }
+        let accepts = |slot: &Slot<T>| match (slot.entry(), filter) {
+            (Some(entry), Some(filter)) => filter(&entry.value),
+            _ => true,
         };
+        let start = SystemTime::now();
+        let shards = self.shards.len();
+        let first = self.cursor.fetch_add(1, Ordering::Relaxed);
+        for shard in (0..shards).map(|offset| (first + offset) % shards) {
+            let mut slots = self.shards[shard].lock().unwrap();
+            let found = slots
+                .iter_mut()
+                .enumerate()
+                .find(|(_, slot)| slot.is_due() && accepts(slot));
+            let (idx, next) = match found {
+                Some((local, next)) => (self.index(shard, local), next),
+                _ => continue,
+            };
+
+            return self.lock_slot(start, idx, next, ttl, owner);
+        }
+        None
+    }
 
-        let res = next.lock(self.ttl).ok().map(|(tag, val)| (tag, idx, val));
+    /// Lease the message pushed to this queue before every other still held, unless it is
+    /// leased already or was nacked with a delay which has not elapsed, so that messages are
+    /// delivered strictly in order. Every shard is locked, in order, and the message found from
+    /// the order tracked as messages are pushed, which is only built from every slot by the
+    /// first lease once ordered.
+    fn lease_oldest(
+        &self,
+        ttl: Duration,
+        owner: Option<&Arc<str>>,
+        filter: Option<&dyn Fn(&T) -> bool>,
+    ) -> Option<(LeaseTag, usize, T)> {
+        let start = SystemTime::now();
+        let mut shards: Vec<_> = self
+            .shards
+            .iter()
+            .map(|shard| shard.lock().unwrap())
+            .collect();
+        let mut order = self.order.lock().unwrap();
+        let order = order.get_or_insert_with(|| {
+            shards
+                .iter()
+                .enumerate()
+                .flat_map(|(shard, slots)| {
+                    slots.iter().enumerate().filter_map(move |(local, slot)| {
+                        slot.entry()
+                            .map(|entry| Reverse((entry.sequence, self.index(shard, local))))
+                    })
+                })
+                .collect()
+        });
+        // Skip past messages removed since they were pushed, whose slots may have been reused.
+        let (shard, local) = loop {
+            let Reverse((sequence, idx)) = *order.peek()?;
+            let (shard, local) = (idx % shards.len(), idx / shards.len());
+            match shards[shard].get(local).and_then(Slot::entry) {
+                Some(entry) if entry.sequence == sequence => break (shard, local),
+                _ => {
+                    order.pop();
+                }
+            }
+        };
+        let next = &mut shards[shard][local];
+        if !next.is_due() {
+            return None;
+        }
+        match (next.entry(), filter) {
+            (Some(entry), Some(filter)) if !filter(&entry.value) => return None,
+            _ => {}
+        }
+        self.lock_slot(start, self.index(shard, local), next, ttl, owner)
+    }
+
+    /// Lock the supplied slot, holding the message with the supplied index, recording the
+    /// lease if it succeeds.
+    fn lock_slot(
+        &self,
+        start: SystemTime,
+        idx: usize,
+        next: &mut Slot<T>,
+        ttl: Duration,
+        owner: Option<&Arc<str>>,
+    ) -> Option<(LeaseTag, usize, T)> {
+        let res = next
+            .lock_for(ttl, owner.cloned())
+            .ok()
+            .map(|(tag, val)| (tag, idx, val));
         if res.is_some() {
-            // MESSAGES_PENDING.dec();
-            // MESSAGES_OUTSTANDING.inc();
+            // Only leases are recorded, as empty polls from idle streams are not interesting.
+            let tracer = trace::tracer();
+            tracer
+                .span_builder("queue.next")
+                .with_start_time(start)
+                .with_attributes(vec![KeyValue::new("queue.index", idx as i64)])
+                .start(&tracer);
+            self.metrics(|metrics| {
+                metrics.pending.dec();
+                metrics.outstanding.inc();
+                match next.entry() {
+                    Some(entry) if entry.attempts == 1 => metric::exemplar::observe(
+                        &metrics.delivery_latency,
+                        elapsed(entry.published),
+                    ),
+                    _ => {}
+                }
+            });
         }
         res
     }
+
+    /// Sample the backlog of this queue, and the age of its oldest pending and unacked
+    /// messages, into its metrics. This walks every slot so is meant to be called
+    /// periodically rather than on every operation.
+    pub fn sample(&self) {
+        let metrics = match &self.metrics {
+            Some(metrics) => metrics,
+            None => return,
+        };
+
+        let mut backlog = 0;
+        let mut oldest_pending: Option<SystemTime> = None;
+        let mut oldest_unacked: Option<SystemTime> = None;
+        for shard in self.shards.iter() {
+            let slots = shard.lock().unwrap();
+            for slot in slots.iter() {
+                let entry = match slot.entry() {
+                    Some(entry) => entry,
+                    None => continue,
+                };
+                backlog += 1;
+                let published = entry.published;
+                oldest_unacked =
+                    Some(oldest_unacked.map_or(published, |oldest| oldest.min(published)));
+                if slot.is_filled() {
+                    oldest_pending =
+                        Some(oldest_pending.map_or(published, |oldest| oldest.min(published)));
+                }
+            }
+        }
+
+        metrics.backlog.set(backlog);
+        metrics
+            .oldest_pending_age
+            .set(oldest_pending.map_or(0.0, elapsed));
+        metrics
+            .oldest_unacked_age
+            .set(oldest_unacked.map_or(0.0, elapsed));
+    }
+
+    /// Return the count of unacked messages held by this queue alongside their bytes, as
+    /// weighed by its budget. Like [Queue::sample] this walks every slot.
+    pub fn footprint(&self) -> (usize, usize) {
+        let (mut messages, mut bytes) = (0, 0);
+        for shard in self.shards.iter() {
+            let slots = shard.lock().unwrap();
+            for entry in slots.iter().filter_map(Slot::entry) {
+                messages += 1;
+                bytes += self.budget.weigh(&entry.value);
+            }
+        }
+        (messages, bytes)
+    }
+
+    /// Return the count of unacked messages, either pending or leased, within this queue.
+    pub fn len(&self) -> usize {
+        self.held.load(Ordering::Relaxed)
+    }
+
+    /// Return whether this queue holds no unacked messages.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Release the supplied count of bytes from the budget of this queue, unless it was
+    /// detached. Must be called while holding the lock of the shard the bytes were held in.
+    fn release(&self, bytes: usize) {
+        if !self.detached.load(Ordering::Relaxed) {
+            self.budget.release(bytes);
+        }
+    }
+
+    /// Release the bytes of every message held by this queue from its budget, generally as
+    /// its subscription is removed. Messages acked through remaining handles to this queue
+    /// are no longer counted.
+    pub(crate) fn detach(&self) {
+        // Every shard is locked, always in the same order, so that no message is acked or
+        // pushed while the held bytes are counted.
+        let shards = self
+            .shards
+            .iter()
+            .map(|shard| shard.lock().unwrap())
+            .collect::<Vec<_>>();
+        if self.detached.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        let weight = shards
+            .iter()
+            .flat_map(|slots| slots.iter())
+            .filter_map(Slot::entry)
+            .map(|entry| self.budget.weigh(&entry.value))
+            .sum();
+        self.budget.release(weight);
+    }
+
+    /// Call the supplied function with a snapshot of every slot of this queue, without cloning
+    /// the messages they hold, to explain where a backlog is stuck. Slots are visited a shard
+    /// at a time while holding its lock, so the function must not call back into this queue.
+    pub fn inspect(&self, mut func: impl FnMut(SlotInfo)) {
+        for (shard, slots) in self.shards.iter().enumerate() {
+            let slots = slots.lock().unwrap();
+            for (local, slot) in slots.iter().enumerate() {
+                func(SlotInfo::new(self.index(shard, local), slot));
+            }
+        }
+    }
+
+    /// Peek at up to `max` pending messages from the front of the queue without leasing them,
+    /// oldest first as they are delivered, returning each message's slot index alongside its
+    /// entry. Slots are reused, so the front of each shard is found by sequence rather than
+    /// by slot.
+    pub fn peek(&self, max: usize) -> Vec<(usize, Entry<T>)> {
+        let mut pending = Vec::new();
+        for (shard, slots) in self.shards.iter().enumerate() {
+            let slots = slots.lock().unwrap();
+            let mut filled = slots
+                .iter()
+                .enumerate()
+                .filter_map(|(local, slot)| match slot {
+                    Slot::Filled(entry) => Some((self.index(shard, local), entry)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            filled.sort_unstable_by_key(|(_, entry)| entry.sequence);
+            pending.extend(
+                filled
+                    .into_iter()
+                    .take(max)
+                    .map(|(idx, entry)| (idx, entry.clone())),
+            );
+        }
+        pending.sort_unstable_by_key(|(_, entry)| entry.sequence);
+        pending.truncate(max);
+        pending
+    }
+
+    /// Return a copy of up to the supplied count of unacked messages, either pending or leased,
+    /// pushed after the message of the supplied sequence, if any, along with their sequences
+    /// in the order they were pushed. Copying a queue a chunk at a time bounds the copies held
+    /// at once, at the cost of the chunks reflecting different points in time.
+    pub fn snapshot_after(&self, after: Option<u64>, max: usize) -> Vec<(u64, T)>
+    where
+        T: Clone,
+    {
+        let shards = self
+            .shards
+            .iter()
+            .map(|shard| shard.lock().unwrap())
+            .collect::<Vec<_>>();
+        let mut entries = shards
+            .iter()
+            .flat_map(|slots| slots.iter())
+            .filter_map(Slot::entry)
+            .filter(|entry| after < Some(entry.sequence))
+            .collect::<Vec<_>>();
+        entries.sort_unstable_by_key(|entry| entry.sequence);
+        entries
+            .into_iter()
+            .take(max)
+            .map(|entry| (entry.sequence, entry.value.clone()))
+            .collect()
+    }
+
+    /// Return a copy of every unacked message, either pending or leased, in the order they
+    /// were pushed. Every shard is locked while copying, so that the copy reflects a single
+    /// point in time.
+    pub fn snapshot(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        let shards = self
+            .shards
+            .iter()
+            .map(|shard| shard.lock().unwrap())
+            .collect::<Vec<_>>();
+        let mut entries = shards
+            .iter()
+            .flat_map(|slots| slots.iter())
+            .filter_map(Slot::entry)
+            .map(|entry| (entry.sequence, entry.value.clone()))
+            .collect::<Vec<_>>();
+        drop(shards);
+        entries.sort_unstable_by_key(|(sequence, _)| *sequence);
+        entries.into_iter().map(|(_, value)| value).collect()
+    }
 }
 
 impl<T> Default for Queue<T> {
@@ -215,4 +1204,362 @@ mod tests {
         let actual = queue.next();
         assert!(actual.is_none());
     }
+
+    #[test]
+    fn test_ordered() {
+        let queue: Queue<i32> = QueueBuilder::default().with_shards(4).build();
+        queue.set_ordered(true);
+        assert!(queue.is_ordered());
+        // Each thread pushes to its own shard, spreading the messages across them.
+        for msg in 0..6 {
+            let queue = queue.clone();
+            std::thread::spawn(move || queue.push(msg).unwrap())
+                .join()
+                .unwrap();
+        }
+
+        let (tag, idx, msg) = queue.next().unwrap();
+        assert_eq!(msg, 0);
+        assert!(queue.next().is_none());
+        queue.nack(tag.id, idx).unwrap();
+        let (tag, idx, msg) = queue.next().unwrap();
+        assert_eq!(msg, 0);
+        queue.ack(tag.id, idx).unwrap();
+
+        // The slot freed by the ack is reused, but its new message is delivered last.
+        queue.push(6).unwrap();
+        for expected in 1..7 {
+            let (tag, idx, msg) = queue.next().unwrap();
+            assert_eq!(msg, expected);
+            assert!(queue.next().is_none());
+            queue.ack(tag.id, idx).unwrap();
+        }
+        assert!(queue.next().is_none());
+
+        // A message nacked with a delay holds back every later message.
+        queue.push(7).unwrap();
+        queue.push(8).unwrap();
+        let (tag, idx, _) = queue.next().unwrap();
+        queue
+            .nack_with_delay(tag.id, idx, Duration::from_secs(60))
+            .unwrap();
+        assert!(queue.next().is_none());
+
+        queue.set_ordered(false);
+        assert_eq!(queue.next().unwrap().2, 8);
+    }
+
+    #[test]
+    fn test_lease_pending() {
+        let queue: Queue<i32> = QueueBuilder::default().with_shards(4).build();
+        queue.set_ordered(true);
+        for msg in 0..6 {
+            let queue = queue.clone();
+            std::thread::spawn(move || queue.push(msg).unwrap())
+                .join()
+                .unwrap();
+        }
+        let (tag, idx, _) = queue.next().unwrap();
+
+        // Neither ordering nor the outstanding limit hold back the rest, oldest first.
+        let leased = queue.lease_pending(3);
+        let msgs = leased.iter().map(|(_, _, msg)| *msg).collect::<Vec<_>>();
+        assert_eq!(msgs, vec![1, 2, 3]);
+        assert_eq!(queue.outstanding(), 4);
+
+        queue.ack(tag.id, idx).unwrap();
+        for (tag, idx, _) in leased {
+            queue.nack(tag.id, idx).unwrap();
+        }
+        assert_eq!(queue.outstanding(), 0);
+        assert_eq!(queue.lease_pending(10).len(), 5);
+        assert!(queue.lease_pending(10).is_empty());
+    }
+
+    #[test]
+    fn test_max_outstanding() {
+        let queue = Queue::<usize>::default();
+        queue.set_max_outstanding(2);
+        for msg in 0..4 {
+            queue.push(msg).unwrap();
+        }
+
+        let (first, first_idx, _) = queue.next().unwrap();
+        let (second, second_idx, _) = queue.next().unwrap();
+        assert_eq!(queue.outstanding(), 2);
+        assert!(queue.next().is_none());
+        assert_eq!(queue.outstanding(), 2);
+
+        // Settling a lease, either way, makes room for the next.
+        queue.ack(first.id, first_idx).unwrap();
+        let (third, third_idx, _) = queue.next().unwrap();
+        assert!(queue.next().is_none());
+        queue.nack(second.id, second_idx).unwrap();
+        assert!(queue.next().is_some());
+        assert!(queue.next().is_none());
+
+        // Failed settles do not release anything.
+        assert!(queue.ack(first.id, first_idx).is_err());
+        assert!(queue.next().is_none());
+
+        queue.set_max_outstanding(0);
+        assert!(queue.next().is_some());
+        assert_eq!(queue.outstanding(), 3);
+        queue
+            .reject(third.id, third_idx, Rejection::Permanent)
+            .unwrap();
+        assert_eq!(queue.outstanding(), 2);
+    }
+
+    #[test]
+    fn test_inspect() {
+        use crate::pubsub::SlotState;
+        use std::collections::HashMap;
+
+        let queue = Queue::<usize>::builder().with_shards(2).build::<usize>();
+        for msg in 0..3 {
+            queue.push(msg).unwrap();
+        }
+        let (acked, acked_idx, _) = queue.next().unwrap();
+        queue.ack(acked.id, acked_idx).unwrap();
+        let (leased, leased_idx, _) = queue.next().unwrap();
+        let (delayed, delayed_idx, _) = queue.next().unwrap();
+        queue
+            .nack_with_delay(delayed.id, delayed_idx, Duration::from_secs(60))
+            .unwrap();
+
+        let mut slots = HashMap::new();
+        queue.inspect(|info| {
+            slots.insert(info.index, info);
+        });
+        assert_eq!(slots.len(), 3);
+        assert_eq!(slots[&acked_idx].state, SlotState::Empty);
+        assert_eq!(slots[&acked_idx].age, None);
+
+        let locked = &slots[&leased_idx];
+        assert_eq!(locked.state, SlotState::Locked);
+        assert_eq!(locked.attempts, 1);
+        assert!(locked.age.is_some());
+        assert!(locked.lease_deadline.unwrap() <= leased.deadline + Duration::from_millis(1));
+        assert!(!locked.is_expired());
+        assert_eq!(locked.lease_id, Some(leased.id));
+        assert_eq!(locked.owner, None);
+
+        let filled = &slots[&delayed_idx];
+        assert_eq!(filled.state, SlotState::Filled);
+        assert_eq!(filled.attempts, 1);
+        assert!(filled.delayed.unwrap() > Duration::from_secs(59));
+        assert_eq!(filled.lease_deadline, None);
+    }
+
+    #[test]
+    fn test_queue_metrics() {
+        let mm = crate::metric::Manager::new(
+            String::from("testing"),
+            String::from("queue_metrics"),
+            String::from("0.1.0"),
+        );
+        let metrics = crate::pubsub::Metrics::new(&mm, crate::metric::LATENCY_BUCKETS)
+            .unwrap()
+            .topic(String::from("topic"))
+            .queue("sub");
+        let queue = Queue::<usize>::builder()
+            .with_metrics(metrics.clone())
+            .build::<usize>();
+
+        queue.push(1).unwrap();
+        queue.push(2).unwrap();
+        assert_eq!(metrics.received.get(), 2);
+        assert_eq!(metrics.pending.get(), 2);
+
+        let (tag, idx, _) = queue.next().unwrap();
+        assert_eq!(metrics.pending.get(), 1);
+        assert_eq!(metrics.outstanding.get(), 1);
+        queue.nack(tag.id, idx).unwrap();
+        assert_eq!(metrics.nacked.get(), 1);
+        assert_eq!(metrics.pending.get(), 2);
+        assert_eq!(metrics.outstanding.get(), 0);
+
+        let (tag, idx, _) = queue.next().unwrap();
+        queue.ack(tag.id, idx).unwrap();
+        assert_eq!(metrics.acked.get(), 1);
+        assert_eq!(metrics.delivery_latency.get_sample_count(), 1);
+
+        assert_eq!(metrics.ack_latency.get_sample_count(), 1);
+        assert_eq!(metrics.pending.get(), 1);
+        assert_eq!(metrics.outstanding.get(), 0);
+
+        queue.sample();
+        assert_eq!(metrics.backlog.get(), 1);
+        assert!(metrics.oldest_pending_age.get() > 0.0);
+        assert!(metrics.oldest_unacked_age.get() >= metrics.oldest_pending_age.get());
+    }
+
+    #[test]
+    fn test_shards() {
+        let queue = Queue::<usize>::builder()
+            .with_shards(4)
+            .with_message_capacity(8)
+            .build::<usize>();
+        assert_eq!(queue.shards.len(), 4);
+
+        let publishers = (0..4)
+            .map(|publisher| {
+                let queue = queue.clone();
+                std::thread::spawn(move || {
+                    (0..25).for_each(|msg| queue.push(publisher * 100 + msg).unwrap())
+                })
+            })
+            .collect::<Vec<_>>();
+        publishers
+            .into_iter()
+            .for_each(|publisher| publisher.join().unwrap());
+        assert_eq!(queue.len(), 100);
+        assert_eq!(queue.peek(usize::MAX).len(), 100);
+
+        let mut delivered = Vec::new();
+        while let Some((tag, idx, msg)) = queue.next() {
+            queue.renew(tag.id, idx).unwrap();
+            queue.ack(tag.id, idx).unwrap();
+            delivered.push(msg);
+        }
+        delivered.sort_unstable();
+        let expected = (0..4)
+            .flat_map(|publisher| (0..25).map(move |msg| publisher * 100 + msg))
+            .collect::<Vec<_>>();
+        assert_eq!(delivered, expected);
+        assert!(queue.is_empty());
+        assert!(queue.ack(0, 400).is_err());
+    }
+
+    #[test]
+    fn test_push_batch() {
+        let limits = Limits {
+            pending_per_subscription: 3,
+            ..Limits::default()
+        };
+        let queue = Queue::<usize>::default().with_budget(Budget::new(limits, |_| 0));
+        // Pushing with no waiting streams never touches the waker.
+        assert_eq!(queue.push_batch(vec![]).0, 0);
+        assert_eq!(queue.push_batch(vec![1]).0, 1);
+
+        let woken = Arc::new(AtomicUsize::new(0));
+        for _ in 0..3 {
+            queue.register_task_waker(Uuid::new_v4(), counting_waker(woken.clone()));
+        }
+        let (pushed, res) = queue.push_batch(vec![2, 3, 4]);
+        assert_eq!(pushed, 2);
+        assert!(matches!(res, Err(Error::LimitExceeded { .. })));
+        assert_eq!(woken.load(Ordering::Relaxed), 2);
+        assert_eq!(queue.waiting.load(Ordering::Relaxed), 1);
+        assert_eq!(queue.len(), 3);
+        assert_eq!(queue.footprint(), (3, 0));
+
+        let queue = Queue::<usize>::default().with_budget(Budget::new(Limits::default(), |v| *v));
+        assert_eq!(queue.push_batch(vec![1, 2, 3]).0, 3);
+        assert_eq!(queue.footprint(), (3, 6));
+    }
+
+    #[test]
+    fn test_nack_with_delay() {
+        let queue = Queue::<usize>::default();
+        queue.push(1).unwrap();
+        let (tag, idx, _) = queue.next().unwrap();
+        queue
+            .nack_with_delay(tag.id, idx, Duration::from_millis(50))
+            .unwrap();
+        assert!(queue.next().is_none());
+        std::thread::sleep(Duration::from_millis(50));
+        let (tag, idx, _) = queue.next().unwrap();
+        assert!(queue
+            .nack_with_delay(tag.id + 1, idx, Duration::ZERO)
+            .is_err());
+
+        // On a runtime, a waiting stream is woken once the delay elapses.
+        let woken = Arc::new(AtomicUsize::new(0));
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            queue
+                .nack_with_delay(tag.id, idx, Duration::from_millis(20))
+                .unwrap();
+            queue.register_task_waker(Uuid::new_v4(), counting_waker(woken.clone()));
+            assert_eq!(woken.load(Ordering::Relaxed), 0);
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        });
+        assert_eq!(woken.load(Ordering::Relaxed), 1);
+        assert!(queue.next().is_some());
+    }
+
+    fn counting_waker(woken: Arc<AtomicUsize>) -> task::Waker {
+        struct Counter(Arc<AtomicUsize>);
+        impl futures::task::ArcWake for Counter {
+            fn wake_by_ref(arc_self: &Arc<Self>) {
+                arc_self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        futures::task::waker(Arc::new(Counter(woken)))
+    }
+
+    #[test]
+    fn test_peek() {
+        let queue = Queue::<usize>::default();
+        queue.push(1).unwrap();
+        queue.push(2).unwrap();
+        queue.push(3).unwrap();
+
+        let (tag, idx, _) = queue.next().unwrap();
+        queue.nack(tag.id, idx).unwrap();
+
+        let peeked = queue.peek(2);
+        assert_eq!(peeked.len(), 2);
+        assert_eq!(peeked[0].0, 0);
+        assert_eq!(peeked[0].1.value, 1);
+        assert_eq!(peeked[0].1.attempts, 1);
+        assert_eq!(peeked[1].1.value, 2);
+        assert_eq!(peeked[1].1.attempts, 0);
+
+        // Peeking must not lease anything.
+        assert_eq!(queue.peek(usize::MAX).len(), 3);
+        let (_, _, actual) = queue.next().unwrap();
+        assert_eq!(actual, 1);
+        assert_eq!(queue.peek(usize::MAX).len(), 2);
+
+        // Slots freed by acks are reused, yet messages pushed into them are peeked last.
+        let reused = Queue::<usize>::default();
+        for msg in 0..3 {
+            reused.push(msg).unwrap();
+        }
+        let (tag, idx, _) = reused.next().unwrap();
+        reused.ack(tag.id, idx).unwrap();
+        reused.push(3).unwrap();
+        assert_eq!(
+            reused
+                .peek(2)
+                .iter()
+                .map(|(_, entry)| entry.value)
+                .collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(
+            reused
+                .peek(usize::MAX)
+                .iter()
+                .map(|(_, entry)| entry.value)
+                .collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        // Snapshots hold leased messages too, in the order they were pushed.
+        assert_eq!(queue.snapshot(), vec![1, 2, 3]);
+        let first = queue.snapshot_after(None, 2);
+        assert_eq!(
+            first.iter().map(|(_, value)| *value).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        let rest = queue.snapshot_after(Some(first[1].0), 2);
+        assert_eq!(
+            rest.iter().map(|(_, value)| *value).collect::<Vec<_>>(),
+            vec![3]
+        );
+        assert!(queue.snapshot_after(Some(rest[0].0), 2).is_empty());
+    }
 }
@@ -1,26 +1,105 @@
 // (c) Copyright 2021-2022 Christian Saide
 // SPDX-License-Identifier: GPL-3.0-only
 
-use std::collections::hash_map::Iter;
-use std::{
-    collections::HashMap,
-    sync::{Arc, RwLock},
-};
+use std::fmt;
+use std::slice::Iter;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
-use super::Topic;
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+
+use crate::cluster;
+
+use super::queue::{DEFAULT_SHARDS, NO_CAPACITY};
+use super::topic::DEFAULT_FANOUT_CHUNK;
+use super::TOPICS_PER_NAMESPACE;
+use super::{namespace, Budget, Limits, Metrics, Result, Topic};
+use super::{PENDING_PER_SUBSCRIPTION, QUEUED_BYTES, SUBSCRIPTIONS_PER_TOPIC, TOPICS};
 
 /// Handles managing and tracking the lifecycle of a set of topics.
 #[derive(Debug, Default, Clone)]
 pub struct Registry<T> {
-    topics: Arc<RwLock<HashMap<String, Topic<T>>>>,
+    topics: Arc<DashMap<Arc<str>, Topic<T>>>,
+    count: Arc<AtomicUsize>,
+    namespaces: Arc<DashMap<Arc<str>, usize>>,
+    metrics: Option<Metrics>,
+    budget: Budget<T>,
+    shards: usize,
+    message_cap: usize,
+    waker_cap: usize,
+    fanout_chunk: usize,
 }
 
 impl<T> Registry<T> {
     /// Create a new topic manager with an initial capacity.
     pub fn with_capacity(cap: usize) -> Self {
-        let topics = HashMap::with_capacity(cap);
-        let topics = Arc::new(RwLock::new(topics));
-        Self { topics }
+        let topics = Arc::new(DashMap::with_capacity(cap));
+        Self {
+            topics,
+            count: Arc::default(),
+            namespaces: Arc::default(),
+            metrics: None,
+            budget: Budget::default(),
+            shards: DEFAULT_SHARDS,
+            message_cap: NO_CAPACITY,
+            waker_cap: NO_CAPACITY,
+            fanout_chunk: DEFAULT_FANOUT_CHUNK,
+        }
+    }
+
+    /// Create a new topic manager whose topics and subscriptions report their state to the
+    /// supplied metrics.
+    pub fn with_metrics(metrics: Metrics) -> Self {
+        Self {
+            topics: Arc::default(),
+            count: Arc::default(),
+            namespaces: Arc::default(),
+            metrics: Some(metrics),
+            budget: Budget::default(),
+            shards: DEFAULT_SHARDS,
+            message_cap: NO_CAPACITY,
+            waker_cap: NO_CAPACITY,
+            fanout_chunk: DEFAULT_FANOUT_CHUNK,
+        }
+    }
+
+    /// Split the slots of every subscription's queue across the supplied count of shards, each
+    /// behind its own lock.
+    pub fn with_queue_shards(mut self, shards: usize) -> Self {
+        self.shards = shards;
+        self
+    }
+
+    /// Pre-allocate every subscription's queue with room for the supplied counts of messages
+    /// and waiting streams, avoiding repeated reallocation as busy subscriptions ramp up.
+    pub fn with_queue_capacity(mut self, messages: usize, wakers: usize) -> Self {
+        self.message_cap = messages;
+        self.waker_cap = wakers;
+        self
+    }
+
+    /// Fan published messages out to the supplied count of subscriptions per task, so that
+    /// topics with many subscriptions enqueue into them concurrently. Zero fans out on the
+    /// publishing task alone.
+    pub fn with_fanout_chunk(mut self, chunk: usize) -> Self {
+        self.fanout_chunk = chunk;
+        self
+    }
+
+    /// Enforce the supplied limits on the topics, subscriptions and messages held by this
+    /// registry, weighing messages against the queued bytes limit with the supplied function.
+    pub fn with_limits(mut self, limits: Limits, weigh: fn(&T) -> usize) -> Self {
+        let mut budget = Budget::new(limits, weigh);
+        if let Some(metrics) = &self.metrics {
+            for (resource, limit) in limits.iter() {
+                metrics.resource_limit(resource).set(limit as i64);
+            }
+            budget = budget.with_gauge(metrics.resource_usage(QUEUED_BYTES));
+        }
+        self.budget = budget;
+        self
     }
 }
 
@@ -28,37 +107,272 @@ impl<T> Registry<T>
 where
     T: Clone,
 {
-    /// Create a new topic, store it, and return it for use.
+    /// Create a new topic, store it, and return it for use. The limit on the count of topics
+    /// is not enforced, so this is reserved for topics riftd creates itself.
     pub fn create(&self, name: String) -> Topic<T> {
-        let mut topics = self.topics.write().unwrap();
+        self.insert(name, false)
+            .expect("unlimited topic creation can not fail")
+    }
 
-        if let Some(topic) = topics.get(&name).cloned() {
-            return topic;
+    /// Create a new topic, store it, and return it for use, failing if its name is invalid or
+    /// this registry already holds the maximum count of topics, either overall or within the
+    /// namespace of the topic. An existing topic is returned unchanged.
+    pub fn try_create(&self, name: String) -> Result<Topic<T>> {
+        namespace::validate(&name)?;
+        self.insert(name, true)
+    }
+
+    fn insert(&self, name: String, limited: bool) -> Result<Topic<T>> {
+        let entry = match self.topics.entry(Arc::from(name)) {
+            Entry::Occupied(entry) => return Ok(entry.get().clone()),
+            Entry::Vacant(entry) => entry,
+        };
+        // The count is tracked separately, as counting the map itself would deadlock on the
+        // shard locked by the entry.
+        let count = self.count.fetch_add(1, Ordering::Relaxed);
+        if limited {
+            if let Err(err) = Limits::check(TOPICS, self.budget.limits.topics, count) {
+                self.count.fetch_sub(1, Ordering::Relaxed);
+                return Err(err);
+            }
+        }
+        let mut namespaced = self
+            .namespaces
+            .entry(Arc::from(namespace::of(entry.key())))
+            .or_default();
+        if limited {
+            let limit = self.budget.limits.topics_per_namespace;
+            if let Err(err) = Limits::check(TOPICS_PER_NAMESPACE, limit, *namespaced) {
+                self.count.fetch_sub(1, Ordering::Relaxed);
+                return Err(err);
+            }
         }
+        *namespaced += 1;
+        drop(namespaced);
 
-        let topic = Topic::with_capacity(0);
-        topics.insert(name, topic.clone());
-        topic
+        let topic = match &self.metrics {
+            Some(metrics) => Topic::with_metrics(metrics.topic(entry.key().to_string())),
+            None => Topic::with_capacity(0),
+        };
+        let topic = topic
+            .with_budget(self.budget.clone())
+            .with_queue_shards(self.shards)
+            .with_queue_capacity(self.message_cap, self.waker_cap)
+            .with_fanout_chunk(self.fanout_chunk);
+        entry.insert(topic.clone());
+        Ok(topic)
     }
 
-    /// Delete the specified topic if it exists.
+    /// Delete the specified topic if it exists, releasing its messages from the queued bytes
+    /// limit.
     pub fn delete(&self, name: &str) -> Option<Topic<T>> {
-        let mut topics = self.topics.write().unwrap();
-        topics.remove(name)
+        let topic = self.topics.remove(name).map(|(_, topic)| topic);
+        if let Some(topic) = &topic {
+            self.count.fetch_sub(1, Ordering::Relaxed);
+            if let Some(mut namespaced) = self.namespaces.get_mut(namespace::of(name)) {
+                *namespaced -= 1;
+            }
+            topic.detach();
+            topic.remove_metrics();
+        }
+        topic
     }
 
     /// Retrieve the specified topic if it exists, otherwise returning
     /// [None].
     pub fn get(&self, name: &str) -> Option<Topic<T>> {
-        let topics = self.topics.read().unwrap();
-        topics.get(name).cloned()
+        self.topics.get(name).map(|topic| topic.value().clone())
+    }
+
+    /// Return a sorted snapshot of the names of the topics contained in this registry, without
+    /// cloning the topics themselves.
+    pub fn names(&self) -> Vec<Arc<str>> {
+        let mut names = self
+            .topics
+            .iter()
+            .map(|topic| topic.key().clone())
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names
     }
 
-    /// Iterate over the topics contained in this registry. The supplied FnOnce is used to ensure
-    /// the inner state is not mutated while iterating.
-    pub fn iter<R>(&self, func: impl FnOnce(Iter<'_, String, Topic<T>>) -> R) -> R {
-        let guard = self.topics.read().unwrap();
-        func(guard.iter())
+    /// Return a sorted snapshot of the names of the topics within the supplied namespace, as
+    /// [Registry::names] does.
+    pub fn names_in(&self, namespace: &str) -> Vec<Arc<str>> {
+        let mut names = self.names();
+        names.retain(|name| namespace::of(name) == namespace);
+        names
+    }
+
+    /// Iterate over the topics contained in this registry. The supplied FnOnce iterates a
+    /// snapshot of the topics, so that topics may be created or deleted while iterating
+    /// without blocking on it. Names are shared with the registry rather than copied.
+    pub fn iter<R>(&self, func: impl FnOnce(Iter<'_, (Arc<str>, Topic<T>)>) -> R) -> R {
+        let topics = self
+            .topics
+            .iter()
+            .map(|topic| (topic.key().clone(), topic.value().clone()))
+            .collect::<Vec<_>>();
+        func(topics.iter())
+    }
+
+    /// Sample the backlog and oldest message ages of every subscription in this registry
+    /// into their metrics, along with the usage of each limited resource.
+    pub fn sample(&self) {
+        let (mut subscriptions, mut pending) = (0, 0);
+        let topics = self.iter(|topics| {
+            topics
+                .map(|(_, topic)| {
+                    let count = topic.iter(|subs| {
+                        subs.map(|(_, sub)| {
+                            sub.queue.sample();
+                            pending = pending.max(sub.queue.len());
+                        })
+                        .count()
+                    });
+                    subscriptions = subscriptions.max(count);
+                })
+                .count()
+        });
+        if let Some(metrics) = &self.metrics {
+            metrics.resource_usage(TOPICS).set(topics as i64);
+            let namespaced = self.namespaces.iter().map(|count| *count.value()).max();
+            metrics
+                .resource_usage(TOPICS_PER_NAMESPACE)
+                .set(namespaced.unwrap_or_default() as i64);
+            metrics
+                .resource_usage(SUBSCRIPTIONS_PER_TOPIC)
+                .set(subscriptions as i64);
+            metrics
+                .resource_usage(PENDING_PER_SUBSCRIPTION)
+                .set(pending as i64);
+            metrics
+                .resource_usage(QUEUED_BYTES)
+                .set(self.budget.queued() as i64);
+        }
+    }
+
+    /// Remove every subscription which has gone without any stream consuming it or any
+    /// message being acked for at least the supplied duration, returning the topic and name
+    /// of each subscription removed.
+    pub fn expire_idle(&self, idle: Duration) -> Vec<(Arc<str>, Arc<str>)> {
+        let mut expired = Vec::new();
+        self.iter(|topics| {
+            for (topic_name, topic) in topics {
+                let idle = topic.iter(|subs| {
+                    subs.filter(|(_, sub)| matches!(sub.queue.idle(), Some(since) if since >= idle))
+                        .map(|(name, _)| name.clone())
+                        .collect::<Vec<_>>()
+                });
+                for name in idle {
+                    if topic.remove(&name).is_some() {
+                        expired.push((topic_name.clone(), name));
+                    }
+                }
+            }
+        });
+        expired
+    }
+
+    /// Expire the subscriptions idle for at least the supplied duration on every interval,
+    /// forever, calling the supplied function with the topic and name of each one removed.
+    pub async fn run_expiry(
+        self,
+        idle: Duration,
+        interval: Duration,
+        on_expired: impl Fn(&str, &str),
+    ) {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            for (topic, name) in self.expire_idle(idle) {
+                on_expired(&topic, &name);
+            }
+        }
+    }
+
+    /// Return the messages of every subscription in this registry whose leases expired to
+    /// pending, quarantining those whose leases expired under at least the supplied count of
+    /// distinct owners, where zero never quarantines. Returns the topic and name of each
+    /// subscription messages were quarantined out of, alongside their count.
+    pub fn expire_leases(&self, quarantine_after: usize) -> Vec<(Arc<str>, Arc<str>, usize)> {
+        let mut quarantined = Vec::new();
+        self.iter(|topics| {
+            for (topic_name, topic) in topics {
+                topic.iter(|subs| {
+                    for (name, sub) in subs {
+                        let poisoned = sub.queue.expire(quarantine_after);
+                        if poisoned.is_empty() {
+                            continue;
+                        }
+                        quarantined.push((topic_name.clone(), name.clone(), poisoned.len()));
+                        for entry in poisoned {
+                            sub.quarantine.hold(entry);
+                        }
+                    }
+                });
+            }
+        });
+        quarantined
+    }
+
+    /// Return the leased messages of every subscription in this registry to pending, such as
+    /// when shutting down, returning the count of leases released.
+    pub fn release_leases(&self) -> usize {
+        let mut released = 0;
+        self.iter(|topics| {
+            for (_, topic) in topics {
+                topic.iter(|subs| {
+                    for (_, sub) in subs {
+                        released += sub.queue.release_leases();
+                    }
+                });
+            }
+        });
+        released
+    }
+
+    /// Expire the leases of every subscription on every interval, forever, calling the
+    /// supplied function with the topic and name of each subscription messages were
+    /// quarantined out of, alongside their count.
+    pub async fn run_lease_expiry(
+        self,
+        quarantine_after: usize,
+        interval: Duration,
+        on_quarantined: impl Fn(&str, &str, usize),
+    ) {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            for (topic, name, count) in self.expire_leases(quarantine_after) {
+                on_quarantined(&topic, &name, count);
+            }
+        }
+    }
+
+    /// Sample this registry on every interval, forever.
+    pub async fn run_sampler(self, interval: Duration) {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            self.sample();
+        }
+    }
+}
+
+impl<T> cluster::Topics for Registry<T>
+where
+    T: Clone + fmt::Debug + Send + Sync + 'static,
+{
+    // Internal topics exist on every member, so holding one does not make a member its owner.
+    fn contains(&self, topic: &str) -> bool {
+        !namespace::is_internal(topic) && self.topics.contains_key(topic)
+    }
+
+    fn names(&self) -> Vec<Arc<str>> {
+        let mut names = Registry::names(self);
+        names.retain(|name| !namespace::is_internal(name));
+        names
     }
 }
 
@@ -97,5 +411,182 @@ mod tests {
 
         let count = reg.iter(|iter| iter.count());
         assert_eq!(count, 1);
+
+        // Iterating a snapshot leaves the registry free to change.
+        let count = reg.iter(|iter| {
+            reg.create(new_topic_name.clone());
+            iter.count()
+        });
+        assert_eq!(count, 1);
+        assert!(reg.get(&new_topic_name).is_some());
+    }
+
+    #[test]
+    fn test_registry_limits() {
+        let limits = Limits {
+            topics: 1,
+            topics_per_namespace: 0,
+            subscriptions_per_topic: 1,
+            pending_per_subscription: 2,
+            queued_bytes: 8,
+            message_bytes: 6,
+        };
+        let reg = Registry::<Vec<u8>>::with_capacity(1).with_limits(limits, |msg| msg.len());
+
+        let topic = reg.try_create(String::from("first")).unwrap();
+        assert!(reg.try_create(String::from("first")).is_ok());
+        assert!(matches!(
+            reg.try_create(String::from("second")),
+            Err(super::super::Error::LimitExceeded {
+                resource: TOPICS,
+                limit: 1
+            })
+        ));
+        reg.create(String::from("audit"));
+
+        let (sub, created) = topic.create_with_push(String::from("sub"), None).unwrap();
+        assert!(created);
+        assert!(topic.create_with_push(String::from("other"), None).is_err());
+
+        assert!(topic.push(vec![0; 4]).is_ok());
+        assert!(topic.push(vec![0; 6]).is_err());
+        assert!(matches!(
+            topic.push(vec![0; 7]),
+            Err(super::super::Error::MessageTooLarge { size: 7, limit: 6 })
+        ));
+        assert!(topic.push(vec![0; 4]).is_ok());
+        assert!(topic.push(vec![]).is_err());
+        assert_eq!(reg.budget.queued(), 8);
+        assert_eq!(sub.queue.len(), 2);
+
+        // Deleting a topic releases the bytes held by its subscriptions.
+        reg.delete("first");
+        assert_eq!(reg.budget.queued(), 0);
+        assert!(reg.try_create(String::from("second")).is_err());
+        reg.delete("audit");
+        assert!(reg.try_create(String::from("second")).is_ok());
+    }
+
+    #[test]
+    fn test_namespaces() {
+        let limits = Limits {
+            topics_per_namespace: 2,
+            ..Limits::default()
+        };
+        let reg = Registry::<Vec<u8>>::with_capacity(1).with_limits(limits, |msg| msg.len());
+
+        for name in ["a/first", "a/second", "first", "b/first"] {
+            reg.try_create(String::from(name)).unwrap();
+        }
+        assert!(matches!(
+            reg.try_create(String::from("a/third")),
+            Err(super::super::Error::LimitExceeded {
+                resource: TOPICS_PER_NAMESPACE,
+                limit: 2
+            })
+        ));
+        assert!(matches!(
+            reg.try_create(String::from("default/second")),
+            Err(super::super::Error::InvalidName { .. })
+        ));
+        // The internal topic prefix is reserved, as the cluster never treats the local member
+        // as the owner of internal topics it holds.
+        assert!(matches!(
+            reg.try_create(String::from("__orders")),
+            Err(super::super::Error::InvalidName { .. })
+        ));
+        assert!(reg.get("__orders").is_none());
+        assert!(reg.try_create(String::from("second")).is_ok());
+
+        assert_eq!(
+            reg.names_in("a"),
+            vec![Arc::from("a/first"), Arc::from("a/second")]
+        );
+        assert_eq!(
+            reg.names_in(namespace::DEFAULT_NAMESPACE),
+            vec![Arc::from("first"), Arc::from("second")]
+        );
+        assert!(reg.names_in("c").is_empty());
+
+        // Deleting a topic frees room within its namespace.
+        reg.delete("a/first");
+        assert!(reg.try_create(String::from("a/third")).is_ok());
+    }
+
+    #[test]
+    fn test_expire_idle() {
+        let reg = Registry::<u32>::default();
+        let topic = reg.create(String::from("topic"));
+        let idle = topic.create(String::from("idle"));
+        let consumed = topic.create(String::from("consumed"));
+        let _stream = super::super::Stream::from(consumed.queue.clone());
+
+        assert!(reg.expire_idle(Duration::from_secs(60)).is_empty());
+        assert!(idle.queue.idle().is_some());
+        assert!(consumed.queue.idle().is_none());
+
+        let expired = reg.expire_idle(Duration::ZERO);
+        assert_eq!(expired, vec![(Arc::from("topic"), Arc::from("idle"))]);
+        assert!(topic.get("idle").is_none());
+        assert!(topic.get("consumed").is_some());
+    }
+
+    #[test]
+    fn test_expire_leases() {
+        let reg = Registry::<u32>::default();
+        let topic = reg.create(String::from("topic"));
+        let sub = topic.create(String::from("sub"));
+        sub.queue.push(7).unwrap();
+        let lease = |owner: &str| {
+            let owner = Arc::from(owner);
+            sub.queue.next_for(Duration::ZERO, Some(&owner)).unwrap()
+        };
+
+        // Leases expiring under the same owner are redelivered without being quarantined.
+        for _ in 0..2 {
+            lease("a");
+            assert!(reg.expire_leases(2).is_empty());
+            assert_eq!(sub.queue.outstanding(), 0);
+            assert_eq!(sub.queue.peek(1)[0].1.value, 7);
+        }
+        lease("b");
+        let quarantined = reg.expire_leases(2);
+        assert_eq!(quarantined, vec![(Arc::from("topic"), Arc::from("sub"), 1)]);
+        assert!(sub.queue.is_empty());
+        assert!(sub.queue.next().is_none());
+        let held = sub.quarantine.list(0);
+        assert_eq!((held[0].value, held[0].attempts), (7, 3));
+        assert_eq!(held[0].expired_by, vec![Arc::from("a"), Arc::from("b")]);
+    }
+
+    #[test]
+    fn test_release_leases() {
+        let reg = Registry::<u32>::default();
+        let (orders, audit) = (
+            reg.create(String::from("orders")),
+            reg.create(String::from("audit")),
+        );
+        let subs = [
+            orders.create(String::from("billing")),
+            orders.create(String::from("shipping")),
+            audit.create(String::from("archive")),
+        ];
+        for (value, sub) in subs.iter().enumerate() {
+            sub.queue.push(value as u32).unwrap();
+            sub.queue.push(value as u32 + 10).unwrap();
+            sub.queue.next().unwrap();
+        }
+        let (tag, index, _) = subs[0].queue.next().unwrap();
+        subs[0].queue.ack(tag.id, index).unwrap();
+
+        // Acked messages stay acked, while every other leased message is pending again.
+        assert_eq!(reg.release_leases(), 3);
+        assert_eq!(reg.release_leases(), 0);
+        for (value, sub) in subs.iter().enumerate() {
+            assert_eq!(sub.queue.outstanding(), 0);
+            assert_eq!(sub.queue.peek(1)[0].1.value, value as u32);
+        }
+        assert_eq!(subs[0].queue.len(), 1);
+        assert_eq!(subs[1].queue.len(), 2);
     }
 }
@@ -0,0 +1,127 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0
+
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// The progress of a replay, moving the pending messages of a subscription to another
+/// subscription or topic at a throttled rate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Progress {
+    /// The topic messages are moved to.
+    pub destination_topic: String,
+    /// The subscription messages are moved to, or empty when moved to every subscription of
+    /// the destination topic.
+    pub destination_subscription: String,
+    /// The count of messages the replay set out to move.
+    pub total: u64,
+    /// The count of messages moved so far.
+    pub moved: u64,
+    /// The count of messages left pending so far, as the destination refused them.
+    pub failed: u64,
+    /// The maximum count of messages moved per second.
+    pub messages_per_sec: u64,
+    /// When the replay started.
+    pub started: SystemTime,
+    /// When the replay finished, or [None] while it is still moving messages.
+    pub finished: Option<SystemTime>,
+}
+
+impl Progress {
+    /// Create the progress of a new replay of the supplied count of messages.
+    pub fn new(
+        destination_topic: String,
+        destination_subscription: String,
+        total: u64,
+        messages_per_sec: u64,
+    ) -> Self {
+        Self {
+            destination_topic,
+            destination_subscription,
+            total,
+            moved: 0,
+            failed: 0,
+            messages_per_sec,
+            started: SystemTime::now(),
+            finished: None,
+        }
+    }
+
+    /// Return whether the replay is still moving messages.
+    pub fn is_active(&self) -> bool {
+        self.finished.is_none()
+    }
+}
+
+/// Tracks the latest replay out of a subscription, which remains visible once it finishes
+/// until the next one starts. Clones share the same progress.
+#[derive(Debug, Clone, Default)]
+pub struct Replay {
+    progress: Arc<Mutex<Option<Progress>>>,
+}
+
+impl Replay {
+    /// Start tracking the supplied replay, returning false without doing so if another replay
+    /// is still active.
+    pub fn start(&self, progress: Progress) -> bool {
+        let mut current = self.progress.lock().unwrap();
+        if matches!(&*current, Some(active) if active.is_active()) {
+            return false;
+        }
+        *current = Some(progress);
+        true
+    }
+
+    /// Return whether a replay is still moving messages.
+    pub fn is_active(&self) -> bool {
+        matches!(&*self.progress.lock().unwrap(), Some(progress) if progress.is_active())
+    }
+
+    /// Record the supplied counts of messages moved and left pending by the active replay.
+    pub fn record(&self, moved: usize, failed: usize) {
+        if let Some(progress) = self.progress.lock().unwrap().as_mut() {
+            progress.moved += moved as u64;
+            progress.failed += failed as u64;
+        }
+    }
+
+    /// Mark the active replay as finished.
+    pub fn finish(&self) {
+        if let Some(progress) = self.progress.lock().unwrap().as_mut() {
+            progress.finished.get_or_insert_with(SystemTime::now);
+        }
+    }
+
+    /// Return the progress of the latest replay, if any.
+    pub fn progress(&self) -> Option<Progress> {
+        self.progress.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay() {
+        let replay = Replay::default();
+        assert_eq!(replay.progress(), None);
+
+        let progress = Progress::new(String::from("orders"), String::new(), 10, 2);
+        assert!(replay.start(progress.clone()));
+        assert!(!replay.start(progress));
+
+        let shared = replay.clone();
+        shared.record(3, 1);
+        let progress = replay.progress().unwrap();
+        assert_eq!((progress.moved, progress.failed), (3, 1));
+        assert!(progress.is_active());
+
+        replay.finish();
+        let progress = shared.progress().unwrap();
+        assert!(!progress.is_active());
+        assert!(replay.start(Progress::new(String::from("orders"), String::new(), 1, 2)));
+        assert_eq!(replay.progress().unwrap().moved, 0);
+    }
+}
@@ -0,0 +1,99 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0
+
+use std::fmt;
+use std::sync::Arc;
+
+use super::Selector;
+
+/// A routing rule of a topic, directing the messages its selector matches to a single
+/// subscription.
+pub struct Route<T> {
+    subscription: Arc<str>,
+    selector: Selector<T>,
+}
+
+impl<T> Route<T> {
+    /// Create a new route directing the messages the supplied selector matches to the named
+    /// subscription.
+    pub fn new(subscription: String, selector: Selector<T>) -> Self {
+        Self {
+            subscription: Arc::from(subscription),
+            selector,
+        }
+    }
+
+    /// Return the name of the subscription matching messages are routed to.
+    pub fn subscription(&self) -> &str {
+        &self.subscription
+    }
+
+    /// Return the selector messages are matched against.
+    pub fn selector(&self) -> &Selector<T> {
+        &self.selector
+    }
+}
+
+impl<T> Clone for Route<T> {
+    fn clone(&self) -> Self {
+        Self {
+            subscription: self.subscription.clone(),
+            selector: self.selector.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Route<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Route")
+            .field("subscription", &self.subscription)
+            .field("selector", &self.selector)
+            .finish()
+    }
+}
+
+/// The ordered routing rules of a topic. Each message is routed to the subscription of the
+/// first rule it matches, and dropped if it matches none. A topic without rules broadcasts
+/// every message to all of its subscriptions.
+pub(crate) struct Routes<T>(Arc<[Route<T>]>);
+
+impl<T> Routes<T> {
+    /// Create a new set of routing rules, evaluated in the supplied order.
+    pub(crate) fn new(routes: Vec<Route<T>>) -> Self {
+        Self(Arc::from(routes))
+    }
+
+    /// Return the routing rules in the order they are evaluated.
+    pub(crate) fn as_slice(&self) -> &[Route<T>] {
+        &self.0
+    }
+
+    /// Return whether the supplied message is routed to the named subscription.
+    pub(crate) fn admits(&self, name: &str, msg: &T) -> bool {
+        if self.0.is_empty() {
+            return true;
+        }
+        match self.0.iter().find(|route| route.selector.matches(msg)) {
+            Some(route) => route.subscription.as_ref() == name,
+            None => false,
+        }
+    }
+}
+
+impl<T> Clone for Routes<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Default for Routes<T> {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl<T> fmt::Debug for Routes<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.0.iter()).finish()
+    }
+}
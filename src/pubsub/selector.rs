@@ -0,0 +1,52 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::filter::Filter;
+
+/// Selects the messages enqueued into a subscription with a [Filter], reading the attributes
+/// of each message with the supplied function.
+pub struct Selector<T> {
+    filter: Arc<Filter>,
+    attributes: fn(&T) -> &HashMap<String, String>,
+}
+
+impl<T> Selector<T> {
+    /// Create a new selector matching messages against the supplied filter.
+    pub fn new(filter: Filter, attributes: fn(&T) -> &HashMap<String, String>) -> Self {
+        Self {
+            filter: Arc::new(filter),
+            attributes,
+        }
+    }
+
+    /// Return the filter messages are matched against.
+    pub fn filter(&self) -> &Filter {
+        &self.filter
+    }
+
+    /// Return whether the supplied message matches the filter.
+    pub fn matches(&self, msg: &T) -> bool {
+        self.filter.matches((self.attributes)(msg))
+    }
+}
+
+impl<T> Clone for Selector<T> {
+    fn clone(&self) -> Self {
+        Self {
+            filter: self.filter.clone(),
+            attributes: self.attributes,
+        }
+    }
+}
+
+impl<T> fmt::Debug for Selector<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Selector")
+            .field(&self.filter.source())
+            .finish()
+    }
+}
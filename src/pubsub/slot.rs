@@ -1,19 +1,65 @@
 // (c) Copyright 2021-2022 Christian Saide
 // SPDX-License-Identifier: GPL-3.0
 
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
 use super::{lease::LeaseTag, Error, Lease, Result};
 
+/// An entry wraps a message stored within a [Slot] alongside its delivery metadata.
+#[derive(Clone, PartialEq, PartialOrd, Eq, Ord, Debug, Hash)]
+pub struct Entry<T> {
+    /// The number of times this entry has been leased to a subscriber.
+    pub attempts: u32,
+    /// The position this entry was pushed to its queue at, which is lower than that of every
+    /// entry pushed after it.
+    pub sequence: u64,
+    /// When this entry was published to its queue.
+    pub published: SystemTime,
+    /// When this entry may next be leased, if it was nacked with a delay.
+    pub not_before: Option<Instant>,
+    /// Who holds the most recent lease of this entry, such as the stream it was delivered on,
+    /// if known.
+    pub owner: Option<Arc<str>>,
+    /// The distinct owners whose leases of this entry expired without it being acked or
+    /// nacked, in the order they expired. Owners which are not known count as one.
+    pub expired_by: Vec<Arc<str>>,
+    /// The message itself.
+    pub value: T,
+}
+
+impl<T> Entry<T> {
+    /// Create a new entry which has never been leased.
+    pub fn new(value: T) -> Self {
+        Self {
+            attempts: 0,
+            sequence: 0,
+            published: SystemTime::now(),
+            not_before: None,
+            owner: None,
+            expired_by: Vec::new(),
+            value,
+        }
+    }
+
+    /// Check to see if this entry may be leased, as any delay it was nacked with has elapsed.
+    pub fn is_due(&self) -> bool {
+        match self.not_before {
+            Some(not_before) => not_before <= Instant::now(),
+            None => true,
+        }
+    }
+}
+
 /// A queue slot implementation.
 #[derive(Clone, PartialEq, PartialOrd, Eq, Ord, Debug, Hash)]
 pub enum Slot<T> {
     /// An empty slot is available for writing a message to.
     Empty,
     /// A filled slot represents a slot that has a pending message available to be read.
-    Filled(T),
+    Filled(Entry<T>),
     /// A locked slot represents a slot that has a message that is awaiting an Ack or Nack.
-    Locked(Lease<T>),
+    Locked(Lease<Entry<T>>),
 }
 
 impl<T> Default for Slot<T> {
@@ -27,11 +73,20 @@ impl<T> Slot<T>
 where
     T: Clone,
 {
-    fn unwrap(self) -> T {
+    fn unwrap(self) -> Entry<T> {
         match self {
             Self::Empty => panic!("called `Slot::unwrap()` on a `Empty` value"),
-            Self::Filled(value) => value,
-            Self::Locked(.., value) => value.into_inner(),
+            Self::Filled(entry) => entry,
+            Self::Locked(.., lease) => lease.into_inner(),
+        }
+    }
+
+    /// Return a reference to the entry held by this slot, if it is not [Slot::Empty].
+    pub fn entry(&self) -> Option<&Entry<T>> {
+        match self {
+            Self::Empty => None,
+            Self::Filled(entry) => Some(entry),
+            Self::Locked(lease) => Some(lease.inner()),
         }
     }
 
@@ -47,12 +102,24 @@ where
         matches!(self, Self::Filled(..))
     }
 
+    /// Check to see if this slot is currently filled with an entry which may be leased now.
+    #[inline]
+    pub fn is_due(&self) -> bool {
+        matches!(self, Self::Filled(entry) if entry.is_due())
+    }
+
     /// Check to see if this slot is currently locked and waiting for an ack/nack/expiration.
     #[inline]
     pub fn is_locked(&self) -> bool {
         matches!(self, Self::Locked(..))
     }
 
+    /// Check to see if this slot is currently locked under the supplied lease.
+    #[inline]
+    pub fn is_leased_by(&self, id: u64) -> bool {
+        matches!(self, Self::Locked(lease) if lease.valid(id))
+    }
+
     /// Check to see if this slot is currently locked and also has an expired lease.
     #[inline]
     pub fn is_expired(&self) -> bool {
@@ -88,7 +155,7 @@ where
 
     /// Checks whether or not the given locked slot is actually expired, if it is
     /// expired this function will transmute self into a Filled slot ready for a
-    /// subscription to read.
+    /// subscription to read, recording the owner of the expired lease.
     pub fn expired(&mut self) -> Result<bool> {
         self.check_locked()?;
 
@@ -96,30 +163,68 @@ where
             Slot::Locked(lease) => lease,
             _ => unreachable!(),
         };
-        if lease.expired() {
-            let id = lease.id();
-            self.nack(id).map(|_| true)
-        } else {
-            Ok(false)
+        if !lease.expired() {
+            return Ok(false);
         }
+        let id = lease.id();
+        self.abandon(id)?;
+        Ok(true)
+    }
+
+    /// Nack this slot like [Slot::nack], counting its lease as expired under its owner, such as
+    /// when the stream holding it went away without acking or nacking it.
+    pub fn abandon(&mut self, id: u64) -> Result<()> {
+        self.nack(id)?;
+        if let Slot::Filled(entry) = self {
+            let owner = entry.owner.clone().unwrap_or_else(|| Arc::from(""));
+            if !entry.expired_by.contains(&owner) {
+                entry.expired_by.push(owner);
+            }
+        }
+        Ok(())
     }
 
     /// Fill this slot with the supplied value, returning an error if the current slot
     /// is not a [Slot::Empty] variant.
     pub fn fill(&mut self, value: T) -> Result<()> {
+        self.fill_at(value, 0)
+    }
+
+    /// Fill this slot with the supplied value as [Slot::fill] does, recording the supplied
+    /// position it was pushed to its queue at.
+    pub fn fill_at(&mut self, value: T, sequence: u64) -> Result<()> {
+        self.fill_entry(Entry {
+            sequence,
+            ..Entry::new(value)
+        })
+    }
+
+    /// Fill this slot with the supplied entry, keeping its delivery history, returning an error
+    /// if the current slot is not a [Slot::Empty] variant.
+    pub fn fill_entry(&mut self, entry: Entry<T>) -> Result<()> {
         self.check_empty()?;
 
-        *self = Self::Filled(value);
+        *self = Self::Filled(entry);
         Ok(())
     }
 
     /// Lock this slots internal value, while setting a sane TTL to wait for an ack/nack. Returns
     /// an error if the slot is not currently a [Slot::Filled] variant.
     pub fn lock(&mut self, ttl: Duration) -> Result<(LeaseTag, T)> {
+        self.lock_for(ttl, None)
+    }
+
+    /// Lock this slots internal value as [Slot::lock] does, recording the supplied owner of the
+    /// lease so it can be reported while the lease is held.
+    pub fn lock_for(&mut self, ttl: Duration, owner: Option<Arc<str>>) -> Result<(LeaseTag, T)> {
         self.check_filled()?;
 
-        let value = std::mem::take(self).unwrap();
-        let (lease_id, lease) = Lease::new(ttl, value.clone());
+        let mut entry = std::mem::take(self).unwrap();
+        entry.attempts += 1;
+        entry.owner = owner;
+
+        let value = entry.value.clone();
+        let (lease_id, lease) = Lease::new(ttl, entry);
         *self = Slot::Locked(lease);
         Ok((lease_id, value))
     }
@@ -142,9 +247,31 @@ where
         Ok(())
     }
 
+    /// Renew the lease on this slot, extending the time allowed to ack/nack it. Returns an error
+    /// if this slot is not currently a [Slot::Locked] variant, or its lease has expired.
+    pub fn renew(&mut self, id: u64) -> Result<LeaseTag> {
+        self.check_locked()?;
+
+        let lease = match self {
+            Slot::Locked(lease, ..) => lease,
+            _ => unreachable!(),
+        };
+
+        if !lease.valid(id) || lease.expired() {
+            return Err(Error::InvalidOrExpiredLease);
+        }
+        Ok(lease.renew())
+    }
+
     /// Nack this slot which will reset this slot back to [Slot::Filled] with the existing
     /// value. Returns an error if this slot is not currently a [Slot::Locked] variant.
     pub fn nack(&mut self, id: u64) -> Result<()> {
+        self.nack_with_delay(id, Duration::ZERO)
+    }
+
+    /// Nack this slot, like [Slot::nack], holding its value back from being leased again until
+    /// the supplied delay elapses.
+    pub fn nack_with_delay(&mut self, id: u64, delay: Duration) -> Result<()> {
         self.check_locked()?;
 
         let lease = match self {
@@ -156,8 +283,12 @@ where
             return Err(Error::InvalidOrExpiredLease);
         }
 
-        let value = std::mem::take(self).unwrap();
-        *self = Slot::Filled(value);
+        let mut entry = std::mem::take(self).unwrap();
+        entry.not_before = match delay.is_zero() {
+            true => None,
+            false => Instant::now().checked_add(delay),
+        };
+        *self = Slot::Filled(entry);
         Ok(())
     }
 }
@@ -220,10 +351,47 @@ mod tests {
         let (new_lease_tag, actual) = res.unwrap();
         assert_eq!(val, actual);
         assert_ne!(orig_lease_tag, new_lease_tag);
+        assert!(matches!(&slot, Slot::Locked(lease) if lease.inner().attempts == 2));
+        assert_eq!(slot.entry().map(|entry| entry.value), Some(val));
+
+        // Renewing keeps the lease, but only with its current id.
+        assert!(slot.renew(orig_lease_tag.id).is_err());
+        let renewed = slot.renew(new_lease_tag.id).unwrap();
+        assert_eq!(renewed.id, new_lease_tag.id);
 
         // Now ack the slot which should mean we have a empty slot.
         let res = slot.ack(new_lease_tag.id);
         assert!(res.is_ok());
         assert!(slot.is_empty());
     }
+
+    #[test]
+    fn test_expired() {
+        let mut slot = Slot::<usize>::Empty;
+        assert!(slot.expired().is_err());
+        slot.fill(0).unwrap();
+
+        let (tag, _) = slot.lock(Duration::from_secs(10)).unwrap();
+        assert!(!slot.expired().unwrap());
+        slot.nack(tag.id).unwrap();
+
+        let (tag, _) = slot.lock(Duration::ZERO).unwrap();
+        assert!(slot.is_expired());
+        assert!(slot.expired().unwrap());
+        assert!(slot.is_filled());
+        assert!(slot.ack(tag.id).is_err());
+
+        // Each owner is only recorded once, however often its leases expire.
+        for owner in ["a", "b", "a"] {
+            slot.lock_for(Duration::ZERO, Some(Arc::from(owner)))
+                .unwrap();
+            assert!(slot.expired().unwrap());
+        }
+        let expired_by: Vec<_> = slot.entry().unwrap().expired_by.to_vec();
+        assert_eq!(
+            expired_by,
+            vec![Arc::from(""), Arc::from("a"), Arc::from("b")]
+        );
+        assert_eq!(slot.entry().unwrap().attempts, 5);
+    }
 }
@@ -2,16 +2,114 @@
 // SPDX-License-Identifier: GPL-3.0
 
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Duration;
 
 use uuid::Uuid;
 
 use super::{LeaseTag, Queue};
 
+/// The count of leases a stream tracks before it first prunes those no longer held.
+const PRUNE_THRESHOLD: usize = 64;
+
+/// Decides whether a stream leases a message, leaving those it refuses for other streams.
+type Filter<T> = Box<dyn Fn(&T) -> bool + Send + Sync>;
+
 /// A wrapper around [Queue] implementing [futures_core::Stream].
+///
+/// The stream tracks the leases it hands out, so that dropping it promptly nacks those still
+/// outstanding rather than leaving them to expire, and deregisters its waker from the queue.
+/// Leases released by dropping the stream count towards quarantining their messages as
+/// though they expired, while those released by [Stream::release] do not.
 pub struct Stream<T> {
     id: Uuid,
+    owner: Arc<str>,
     queue: Queue<T>,
+    ttl: Option<Duration>,
+    filter: Option<Filter<T>>,
+    leases: Vec<(u64, usize)>,
+    prune_at: usize,
+    nack: fn(&Queue<T>, u64, usize),
+    abandon: fn(&Queue<T>, u64, usize),
+}
+
+impl<T> Stream<T> {
+    /// Nack every lease handed out by this stream which is still outstanding, so that its
+    /// messages are redelivered to other streams straight away.
+    pub fn release(&mut self) {
+        self.release_with(self.nack)
+    }
+
+    fn release_with(&mut self, settle: fn(&Queue<T>, u64, usize)) {
+        for (lease_id, index) in self.leases.drain(..) {
+            settle(&self.queue, lease_id, index);
+        }
+    }
+}
+
+impl<T> Stream<T>
+where
+    T: Clone,
+{
+    /// Lease messages for the supplied ttl, rather than the ttl of the queue.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Only lease the messages the supplied filter accepts, leaving the rest of the queue
+    /// untouched for other streams. See [Queue::next_where].
+    pub fn with_filter(mut self, filter: impl Fn(&T) -> bool + Send + Sync + 'static) -> Self {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Report the supplied owner as holding the leases of this stream when its queue is
+    /// inspected, rather than the ID of the stream.
+    pub fn with_owner(mut self, owner: String) -> Self {
+        self.owner = Arc::from(owner);
+        self
+    }
+
+    /// Return the unique ID of this stream.
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn next(&mut self) -> Option<(LeaseTag, usize, T)> {
+        let ttl = self.ttl.unwrap_or_else(|| self.queue.ttl());
+        let filter = self
+            .filter
+            .as_deref()
+            .map(|filter| filter as &dyn Fn(&T) -> bool);
+        let next = self.queue.next_where(ttl, Some(&self.owner), filter)?;
+        self.track(next.0.id, next.1);
+        Some(next)
+    }
+
+    /// Track the supplied lease, pruning those which were acked, nacked or expired once the
+    /// tracked count doubles, so long lived streams do not grow without bound.
+    fn track(&mut self, lease_id: u64, index: usize) {
+        self.leases.push((lease_id, index));
+        if self.leases.len() < self.prune_at {
+            return;
+        }
+        let queue = &self.queue;
+        self.leases
+            .retain(|(lease_id, index)| queue.is_leased(*lease_id, *index));
+        self.prune_at = (self.leases.len() * 2).max(PRUNE_THRESHOLD);
+    }
+
+    /// Return the count of leases handed out by this stream which may still be outstanding.
+    pub fn outstanding(&self) -> usize {
+        self.leases.len()
+    }
+
+    /// Return the queue this stream leases messages from.
+    pub fn queue(&self) -> &Queue<T> {
+        &self.queue
+    }
 }
 
 impl<T> futures::Stream for Stream<T>
@@ -20,13 +118,25 @@ where
 {
     type Item = (LeaseTag, usize, T);
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let next = self.queue.next();
-        if next.is_none() {
-            self.queue.register_task_waker(self.id, cx.waker().clone());
-            Poll::Pending
-        } else {
-            Poll::Ready(next)
+        let this = self.get_mut();
+        if let Some(next) = this.next() {
+            return Poll::Ready(Some(next));
         }
+        this.queue.register_task_waker(this.id, cx.waker().clone());
+        // Check again now that the waker is registered, as pushes skip waking when no stream
+        // is waiting, so a message pushed in between would otherwise go unnoticed.
+        match this.next() {
+            Some(next) => Poll::Ready(Some(next)),
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl<T> Drop for Stream<T> {
+    fn drop(&mut self) {
+        self.queue.deregister_task_waker(&self.id);
+        self.release_with(self.abandon);
+        self.queue.detach_stream();
     }
 }
 
@@ -35,9 +145,23 @@ where
     T: Clone,
 {
     fn from(queue: Queue<T>) -> Self {
+        queue.attach_stream();
+        let id = Uuid::new_v4();
         Self {
-            id: Uuid::new_v4(),
+            id,
+            owner: Arc::from(id.to_string()),
             queue,
+            ttl: None,
+            filter: None,
+            leases: Vec::new(),
+            prune_at: PRUNE_THRESHOLD,
+            // Leases which were already acked, nacked or expired are refused, and skipped.
+            nack: |queue, lease_id, index| {
+                let _ = queue.nack(lease_id, index);
+            },
+            abandon: |queue, lease_id, index| {
+                let _ = queue.abandon(lease_id, index);
+            },
         }
     }
 }
@@ -92,4 +216,121 @@ mod tests {
             _ => unimplemented!(),
         };
     }
+
+    #[test]
+    fn test_stream_drop() {
+        let queue = Queue::default();
+        queue.push(0).expect("failed to push message");
+        queue.push(1).expect("failed to push message");
+
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let mut stream = Stream::from(queue.clone());
+        let (tag, index, _) = match Pin::new(&mut stream).poll_next(&mut cx) {
+            Poll::Ready(Some(first)) => first,
+            _ => unimplemented!(),
+        };
+        assert!(matches!(
+            Pin::new(&mut stream).poll_next(&mut cx),
+            Poll::Ready(Some(_))
+        ));
+        queue.ack(tag.id, index).unwrap();
+        assert!(Pin::new(&mut stream).poll_next(&mut cx).is_pending());
+        assert_eq!(stream.outstanding(), 2);
+        assert_eq!(queue.waker.lock().unwrap().len(), 1);
+
+        // Dropping the stream deregisters its waker, and redelivers its unacked lease.
+        drop(stream);
+        assert!(queue.waker.lock().unwrap().is_empty());
+        assert_eq!(queue.len(), 1);
+        let mut stream = Stream::from(queue);
+        assert!(matches!(
+            Pin::new(&mut stream).poll_next(&mut cx),
+            Poll::Ready(Some((_, _, 1)))
+        ));
+    }
+
+    #[test]
+    fn test_stream_abandon() {
+        let queue = Queue::default();
+        queue.push(0).expect("failed to push message");
+
+        // Releasing leases, such as while shutting down, does not count towards quarantine.
+        let mut stream = Stream::from(queue.clone());
+        stream.next().unwrap();
+        stream.release();
+        drop(stream);
+        assert!(queue.expire(1).is_empty());
+
+        // Each distinct stream dropped while holding the lease counts once.
+        for _ in 0..2 {
+            let mut stream = Stream::from(queue.clone());
+            stream.next().unwrap();
+            drop(stream);
+            assert_eq!(queue.len(), 1);
+        }
+        assert!(queue.expire(3).is_empty());
+        let quarantined = queue.expire(2);
+        assert_eq!(quarantined.len(), 1);
+        assert_eq!(quarantined[0].expired_by.len(), 2);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_stream_filter() {
+        let queue = Queue::default();
+        for msg in 0..4 {
+            queue.push(msg).expect("failed to push message");
+        }
+
+        // Refused messages are left pending for other streams.
+        let mut stream = Stream::from(queue.clone()).with_filter(|msg| msg % 2 == 1);
+        assert_eq!(stream.next().unwrap().2, 1);
+        assert_eq!(stream.next().unwrap().2, 3);
+        assert!(stream.next().is_none());
+        let (tag, index, msg) = queue.next().unwrap();
+        assert_eq!(msg, 0);
+        queue.nack(tag.id, index).unwrap();
+
+        // Ordered queues lease nothing until the oldest message is accepted.
+        stream.release();
+        queue.set_ordered(true);
+        assert!(stream.next().is_none());
+        assert_eq!(queue.next().unwrap().2, 0);
+    }
+
+    #[test]
+    fn test_stream_prune() {
+        let queue = Queue::default();
+        let mut stream = Stream::from(queue.clone());
+        for msg in 0..PRUNE_THRESHOLD * 2 {
+            queue.push(msg).expect("failed to push message");
+            let (tag, index, _) = stream.next().unwrap();
+            queue.ack(tag.id, index).unwrap();
+        }
+        assert!(stream.outstanding() < PRUNE_THRESHOLD);
+    }
+
+    #[test]
+    fn test_stream_owner() {
+        let queue = Queue::default();
+        queue.push(0).expect("failed to push message");
+        queue.push(1).expect("failed to push message");
+
+        let mut stream = Stream::from(queue.clone());
+        let (first, _, _) = stream.next().unwrap();
+        let mut owned = Stream::from(queue.clone()).with_owner(String::from("alice"));
+        let (second, _, _) = owned.next().unwrap();
+
+        let mut owners = std::collections::HashMap::new();
+        queue.inspect(|info| {
+            if let (Some(lease_id), Some(owner)) = (info.lease_id, info.owner) {
+                owners.insert(lease_id, owner.to_string());
+            }
+        });
+        assert_eq!(owners.len(), 2);
+        assert_eq!(owners[&first.id], stream.id().to_string());
+        assert_eq!(owners[&second.id], "alice");
+    }
 }
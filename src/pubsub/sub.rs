@@ -3,7 +3,7 @@
 
 use std::time::SystemTime;
 
-use super::Queue;
+use super::{Quarantine, Queue, Replay, Selector, Throttle};
 
 /// A subscription represents a single consumer of a given topic.
 #[derive(Debug, Clone)]
@@ -14,6 +14,17 @@ pub struct Sub<T> {
     pub created: SystemTime,
     /// The backing persistent queue for this subscription.
     pub queue: Queue<T>,
+    /// The endpoint messages are pushed to, if this subscription uses push delivery.
+    pub push: Option<String>,
+    /// The throttle limiting the rate messages are delivered from this subscription.
+    pub throttle: Throttle,
+    /// The selector choosing which published messages are enqueued, if this subscription
+    /// only receives a subset of its topic.
+    pub filter: Option<Selector<T>>,
+    /// The latest replay moving the pending messages of this subscription elsewhere.
+    pub replay: Replay,
+    /// The messages quarantined out of this subscription, as their leases kept expiring.
+    pub quarantine: Quarantine<T>,
 }
 
 impl<T> Sub<T> {
@@ -23,6 +34,20 @@ impl<T> Sub<T> {
             updated: None,
             created: SystemTime::now(),
             queue,
+            push: None,
+            throttle: Throttle::default(),
+            filter: None,
+            replay: Replay::default(),
+            quarantine: Quarantine::default(),
+        }
+    }
+
+    /// Return whether the supplied message is enqueued into this subscription, as it matches
+    /// its filter if any.
+    pub fn accepts(&self, msg: &T) -> bool {
+        match &self.filter {
+            Some(filter) => filter.matches(msg),
+            None => true,
         }
     }
 }
@@ -33,6 +58,11 @@ impl<T> Default for Sub<T> {
             updated: None,
             created: SystemTime::now(),
             queue: Queue::default(),
+            push: None,
+            throttle: Throttle::default(),
+            filter: None,
+            replay: Replay::default(),
+            quarantine: Quarantine::default(),
         }
     }
 }
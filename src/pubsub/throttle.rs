@@ -0,0 +1,158 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A limit on the rate messages are delivered from a subscription, where a rate of zero is
+/// unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Quota {
+    /// The maximum count of messages delivered per second.
+    pub messages_per_sec: u64,
+    /// The maximum count of message data bytes delivered per second.
+    pub bytes_per_sec: u64,
+}
+
+impl Quota {
+    /// Check whether this quota places no limit on delivery.
+    pub fn is_unlimited(&self) -> bool {
+        self.messages_per_sec == 0 && self.bytes_per_sec == 0
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    quota: Quota,
+    messages: f64,
+    bytes: f64,
+    refilled: Instant,
+}
+
+impl State {
+    /// Refill the buckets based on the time elapsed since the last refill, holding at most one
+    /// second's worth of deliveries.
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled).as_secs_f64();
+        self.refilled = now;
+        let messages = self.quota.messages_per_sec as f64;
+        let bytes = self.quota.bytes_per_sec as f64;
+        self.messages = (self.messages + elapsed * messages).min(messages);
+        self.bytes = (self.bytes + elapsed * bytes).min(bytes);
+    }
+}
+
+/// A token bucket enforcing a [Quota] across every delivery path of a subscription. Clones
+/// share the same buckets, so updating the quota applies to every clone.
+///
+/// The byte bucket may go into debt, so that a single message larger than the per second byte
+/// limit is still delivered, followed by a proportionally longer pause.
+#[derive(Debug, Clone)]
+pub struct Throttle {
+    state: Arc<Mutex<State>>,
+}
+
+impl Throttle {
+    /// Replace the quota enforced by this throttle, refilling its buckets.
+    pub fn set(&self, quota: Quota) {
+        let mut state = self.state.lock().unwrap();
+        *state = State {
+            quota,
+            messages: quota.messages_per_sec as f64,
+            bytes: quota.bytes_per_sec as f64,
+            refilled: Instant::now(),
+        };
+    }
+
+    /// Return the quota enforced by this throttle.
+    pub fn quota(&self) -> Quota {
+        self.state.lock().unwrap().quota
+    }
+
+    /// Return how long to wait before the next message can be delivered, or [None] if it can
+    /// be delivered now.
+    pub fn delay(&self) -> Option<Duration> {
+        let mut state = self.state.lock().unwrap();
+        if state.quota.is_unlimited() {
+            return None;
+        }
+        state.refill();
+
+        let mut wait: f64 = 0.0;
+        if state.quota.messages_per_sec > 0 && state.messages < 1.0 {
+            wait = wait.max((1.0 - state.messages) / state.quota.messages_per_sec as f64);
+        }
+        if state.quota.bytes_per_sec > 0 && state.bytes < 1.0 {
+            wait = wait.max((1.0 - state.bytes) / state.quota.bytes_per_sec as f64);
+        }
+        match wait {
+            wait if wait > 0.0 => Some(Duration::from_secs_f64(wait)),
+            _ => None,
+        }
+    }
+
+    /// Record the delivery of a message with the supplied data size.
+    pub fn consume(&self, bytes: usize) {
+        let mut state = self.state.lock().unwrap();
+        if state.quota.is_unlimited() {
+            return;
+        }
+        state.refill();
+        if state.quota.messages_per_sec > 0 {
+            state.messages -= 1.0;
+        }
+        if state.quota.bytes_per_sec > 0 {
+            state.bytes -= bytes as f64;
+        }
+    }
+}
+
+impl Default for Throttle {
+    fn default() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                quota: Quota::default(),
+                messages: 0.0,
+                bytes: 0.0,
+                refilled: Instant::now(),
+            })),
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throttle() {
+        let throttle = Throttle::default();
+        throttle.consume(1024);
+        assert_eq!(throttle.delay(), None);
+
+        let shared = throttle.clone();
+        shared.set(Quota {
+            messages_per_sec: 2,
+            bytes_per_sec: 0,
+        });
+        assert_eq!(throttle.quota().messages_per_sec, 2);
+        for _ in 0..2 {
+            assert_eq!(throttle.delay(), None);
+            throttle.consume(1024);
+        }
+        let delay = throttle.delay().unwrap();
+        assert!(delay <= Duration::from_millis(500));
+
+        // An oversized message is delivered, then pauses delivery until the debt is repaid.
+        throttle.set(Quota {
+            messages_per_sec: 0,
+            bytes_per_sec: 100,
+        });
+        assert_eq!(throttle.delay(), None);
+        throttle.consume(300);
+        let delay = throttle.delay().unwrap();
+        assert!(delay > Duration::from_millis(1900) && delay <= Duration::from_millis(2010));
+    }
+}
@@ -1,14 +1,28 @@
 // (c) Copyright 2021-2022 Christian Saide
 // SPDX-License-Identifier: GPL-3.0
 
-use std::collections::hash_map::Iter;
-use std::{
-    collections::HashMap,
-    sync::{Arc, RwLock},
-    time::SystemTime,
+use std::slice::Iter;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use futures::future;
+
+use super::queue::{DEFAULT_SHARDS, NO_CAPACITY};
+use super::route::Routes;
+use super::{
+    Budget, Error, ForwardRule, Limits, Queue, Quota, Result, Route, Selector, Sub, TopicMetrics,
+    SUBSCRIPTIONS_PER_TOPIC,
 };
 
-use super::{Queue, Sub};
+/// The default count of subscriptions a message is enqueued into per task as it is fanned
+/// out.
+pub const DEFAULT_FANOUT_CHUNK: usize = 32;
+
+/// A subscription of a topic alongside its name.
+type NamedSub<T> = (Arc<str>, Sub<T>);
 
 /// A topic represents a configured data flow through the rift system.
 #[derive(Debug, Clone)]
@@ -17,7 +31,16 @@ pub struct Topic<T> {
     pub updated: Option<SystemTime>,
     /// The datetime when this Topic was created.
     pub created: SystemTime,
-    subscriptions: Arc<RwLock<HashMap<String, Sub<T>>>>,
+    subscriptions: Arc<DashMap<Arc<str>, Sub<T>>>,
+    routes: Arc<RwLock<Routes<T>>>,
+    forward_rules: Arc<RwLock<Vec<ForwardRule<T>>>>,
+    count: Arc<AtomicUsize>,
+    metrics: Option<TopicMetrics>,
+    budget: Budget<T>,
+    shards: usize,
+    message_cap: usize,
+    waker_cap: usize,
+    fanout_chunk: usize,
 }
 
 impl<T> Topic<T>
@@ -26,68 +49,426 @@ where
 {
     /// Create a new default topic.
     pub fn new() -> Self {
-        let subscriptions = Arc::new(RwLock::new(HashMap::new()));
+        let subscriptions = Arc::new(DashMap::new());
         Self {
             updated: None,
             created: SystemTime::now(),
             subscriptions,
+            routes: Arc::default(),
+            forward_rules: Arc::default(),
+            count: Arc::default(),
+            metrics: None,
+            budget: Budget::default(),
+            shards: DEFAULT_SHARDS,
+            message_cap: NO_CAPACITY,
+            waker_cap: NO_CAPACITY,
+            fanout_chunk: DEFAULT_FANOUT_CHUNK,
         }
     }
 
     /// Create a new topic with a predefined capacity for subscriber subscriptions.
     pub fn with_capacity(cap: usize) -> Self {
-        let subscriptions = HashMap::with_capacity(cap);
-        let subscriptions = Arc::new(RwLock::new(subscriptions));
+        let subscriptions = Arc::new(DashMap::with_capacity(cap));
         Self {
             updated: None,
             created: SystemTime::now(),
             subscriptions,
+            routes: Arc::default(),
+            forward_rules: Arc::default(),
+            count: Arc::default(),
+            metrics: None,
+            budget: Budget::default(),
+            shards: DEFAULT_SHARDS,
+            message_cap: NO_CAPACITY,
+            waker_cap: NO_CAPACITY,
+            fanout_chunk: DEFAULT_FANOUT_CHUNK,
+        }
+    }
+
+    /// Create a new topic whose subscriptions report their state to the supplied metrics.
+    pub fn with_metrics(metrics: TopicMetrics) -> Self {
+        Self {
+            metrics: Some(metrics),
+            ..Self::new()
         }
     }
 
-    /// Create a new subscription within this topic.
+    /// Split the slots of the queues of subscriptions created within this topic across the
+    /// supplied count of shards.
+    pub(crate) fn with_queue_shards(mut self, shards: usize) -> Self {
+        self.shards = shards;
+        self
+    }
+
+    /// Pre-allocate the queues of subscriptions created within this topic with room for the
+    /// supplied counts of messages and waiting streams.
+    pub(crate) fn with_queue_capacity(mut self, messages: usize, wakers: usize) -> Self {
+        self.message_cap = messages;
+        self.waker_cap = wakers;
+        self
+    }
+
+    /// Fan published messages out to the supplied count of subscriptions per task, where zero
+    /// fans out on the publishing task alone.
+    pub(crate) fn with_fanout_chunk(mut self, chunk: usize) -> Self {
+        self.fanout_chunk = chunk;
+        self
+    }
+
+    /// Enforce the limits of the supplied budget on this topic and its subscriptions.
+    pub(crate) fn with_budget(mut self, budget: Budget<T>) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Create a new subscription within this topic, regardless of the limit on the count of
+    /// subscriptions.
     pub fn create(&self, name: String) -> Sub<T> {
-        let mut subs = self.subscriptions.write().unwrap();
+        self.insert(name, None, None, false)
+            .expect("unlimited subscription creation can not fail")
+            .0
+    }
+
+    /// Create a new subscription within this topic, pushing its messages to the supplied
+    /// endpoint if any. The subscription is returned along with whether it was newly created,
+    /// as an existing subscription is returned unchanged. New subscriptions are refused once
+    /// the topic holds the maximum count of subscriptions.
+    pub fn create_with_push(&self, name: String, push: Option<String>) -> Result<(Sub<T>, bool)> {
+        self.insert(name, push, None, true)
+    }
 
-        if let Some(sub) = subs.get(&name) {
-            return sub.clone();
+    /// Create a new subscription within this topic like [Topic::create_with_push], only
+    /// enqueuing the messages selected by the supplied filter if any. The filter of an
+    /// existing subscription is left unchanged.
+    pub fn create_with_filter(
+        &self,
+        name: String,
+        push: Option<String>,
+        filter: Option<Selector<T>>,
+    ) -> Result<(Sub<T>, bool)> {
+        self.insert(name, push, filter, true)
+    }
+
+    fn insert(
+        &self,
+        name: String,
+        push: Option<String>,
+        filter: Option<Selector<T>>,
+        limited: bool,
+    ) -> Result<(Sub<T>, bool)> {
+        let entry = match self.subscriptions.entry(Arc::from(name)) {
+            Entry::Occupied(entry) => return Ok((entry.get().clone(), false)),
+            Entry::Vacant(entry) => entry,
+        };
+        // The count is tracked separately, as counting the map itself would deadlock on the
+        // shard locked by the entry.
+        let count = self.count.fetch_add(1, Ordering::Relaxed);
+        if limited {
+            let limit = self.budget.limits.subscriptions_per_topic;
+            if let Err(err) = Limits::check(SUBSCRIPTIONS_PER_TOPIC, limit, count) {
+                self.count.fetch_sub(1, Ordering::Relaxed);
+                return Err(err);
+            }
         }
 
-        let queue = Queue::<T>::builder().build();
-        let sub = Sub::with_queue(queue);
-        subs.insert(name, sub.clone());
-        sub
+        // Room beyond the pending limit could never be used, so it is not allocated.
+        let mut message_cap = self.message_cap;
+        if self.budget.limits.pending_per_subscription > 0 {
+            message_cap = message_cap.min(self.budget.limits.pending_per_subscription);
+        }
+        let mut builder = Queue::<T>::builder()
+            .with_shards(self.shards)
+            .with_message_capacity(message_cap)
+            .with_subscription_capacity(self.waker_cap);
+        if let Some(metrics) = &self.metrics {
+            builder = builder.with_metrics(metrics.queue(entry.key()));
+        }
+        let mut sub = Sub::with_queue(builder.build().with_budget(self.budget.clone()));
+        sub.push = push;
+        sub.filter = filter;
+        entry.insert(sub.clone());
+        Ok((sub, true))
+    }
+
+    /// Replace the push endpoint of the supplied subscription, returning the updated
+    /// subscription if it exists.
+    pub fn set_push(&self, name: &str, push: Option<String>) -> Option<Sub<T>> {
+        let mut sub = self.subscriptions.get_mut(name)?;
+        sub.push = push;
+        sub.updated = Some(SystemTime::now());
+        Some(sub.clone())
+    }
+
+    /// Replace the delivery quota of the supplied subscription, returning the updated
+    /// subscription if it exists.
+    pub fn set_quota(&self, name: &str, quota: Quota) -> Option<Sub<T>> {
+        let mut sub = self.subscriptions.get_mut(name)?;
+        sub.throttle.set(quota);
+        sub.updated = Some(SystemTime::now());
+        Some(sub.clone())
+    }
+
+    /// Replace the routing rules of this topic, evaluated in the supplied order. Each message
+    /// is then only delivered to the subscription named by the first rule it matches, and is
+    /// dropped if it matches none or that subscription does not exist. Without any rules,
+    /// messages are broadcast to every subscription.
+    pub fn set_routes(&self, routes: Vec<Route<T>>) {
+        *self.routes.write().unwrap() = Routes::new(routes);
+    }
+
+    /// Return the routing rules of this topic in the order they are evaluated.
+    pub fn routes(&self) -> Vec<Route<T>> {
+        self.routes.read().unwrap().as_slice().to_vec()
+    }
+
+    /// Replace the forwarding rules of this topic. Every rule a message published to this
+    /// topic matches also enqueues it into the topic of that rule.
+    pub fn set_forward_rules(&self, rules: Vec<ForwardRule<T>>) {
+        *self.forward_rules.write().unwrap() = rules;
+    }
+
+    /// Return the forwarding rules of this topic.
+    pub fn forward_rules(&self) -> Vec<ForwardRule<T>> {
+        self.forward_rules.read().unwrap().clone()
     }
 
     /// Remove the supplied subscription if it exists.
     pub fn remove(&self, name: &str) -> Option<Sub<T>> {
-        let mut subs = self.subscriptions.write().unwrap();
-        subs.remove(name)
+        let sub = self.subscriptions.remove(name).map(|(_, sub)| sub);
+        if let Some(sub) = &sub {
+            self.count.fetch_sub(1, Ordering::Relaxed);
+            sub.queue.detach();
+        }
+        if let (Some(_), Some(metrics)) = (&sub, &self.metrics) {
+            metrics.remove(name);
+        }
+        sub
+    }
+
+    /// Release the messages held by every subscription within this topic from its budget,
+    /// generally used when the topic itself is deleted.
+    pub(crate) fn detach(&self) {
+        self.subscriptions
+            .iter()
+            .for_each(|sub| sub.value().queue.detach());
+    }
+
+    /// Remove the metric series of every subscription within this topic, generally used
+    /// when the topic itself is deleted.
+    pub(crate) fn remove_metrics(&self) {
+        if let Some(metrics) = &self.metrics {
+            self.subscriptions
+                .iter()
+                .for_each(|sub| metrics.remove(sub.key()));
+        }
     }
 
     /// Retrieve the specified subscription if it exists, otherwise returning
     /// [None].
     pub fn get(&self, name: &str) -> Option<Sub<T>> {
-        let subs = self.subscriptions.read().unwrap();
-        subs.get(name).cloned()
+        self.subscriptions.get(name).map(|sub| sub.value().clone())
+    }
+
+    /// Return a snapshot of the subscriptions of this topic by name, along with its routing
+    /// rules, failing if there are no subscriptions to deliver messages to.
+    fn subs(&self) -> Result<(Vec<NamedSub<T>>, Routes<T>)> {
+        let subs = self
+            .subscriptions
+            .iter()
+            .map(|sub| (sub.key().clone(), sub.value().clone()))
+            .collect::<Vec<_>>();
+        if subs.is_empty() {
+            return Err(Error::NoSubscriptions);
+        }
+        Ok((subs, self.routes.read().unwrap().clone()))
+    }
+
+    /// Enqueue the supplied message into each of the supplied subscriptions it is routed to
+    /// and whose filter it matches. Room is reserved in every one of them first, so should any
+    /// refuse it the message is enqueued into none of them and the first refusal is returned.
+    /// The supplied function is called with the count of reservations before any is filled.
+    fn fan_out(
+        subs: &[NamedSub<T>],
+        routes: &Routes<T>,
+        msg: &T,
+        counted: impl FnOnce(usize),
+    ) -> Result<()> {
+        let reserved = Self::reserve(subs, routes, msg)?;
+        counted(reserved.len());
+        Self::deliver(reserved, msg)
+    }
+
+    /// Reserve room for the supplied message in each of the supplied subscriptions it is
+    /// routed to and whose filter it matches, returning their queues alongside the weight
+    /// reserved in each. Should any subscription refuse it every reservation is returned.
+    fn reserve(
+        subs: &[NamedSub<T>],
+        routes: &Routes<T>,
+        msg: &T,
+    ) -> Result<Vec<(Queue<T>, usize)>> {
+        let mut reserved = Vec::new();
+        for (_, sub) in subs
+            .iter()
+            .filter(|(name, sub)| routes.admits(name, msg) && sub.accepts(msg))
+        {
+            match sub.queue.reserve(msg) {
+                Ok(weight) => reserved.push((sub.queue.clone(), weight)),
+                Err(err) => {
+                    for (queue, weight) in reserved {
+                        queue.unreserve(weight);
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        Ok(reserved)
+    }
+
+    /// Enqueue the supplied message into each of the supplied queues in the room reserved by
+    /// [Topic::reserve].
+    fn deliver(reserved: Vec<(Queue<T>, usize)>, msg: &T) -> Result<()> {
+        reserved
+            .into_iter()
+            .map(|(queue, weight)| queue.push_reserved(msg.clone(), weight))
+            .fold(Ok(()), Result::and)
     }
 
-    /// Handle the supplied message.
-    pub fn push(&self, msg: T) -> Result<(), String> {
-        let subs = self.subscriptions.read().unwrap();
-        let (_, sub) = match subs.iter().next() {
-            Some(sub) => sub,
-            None => return Err(String::from("no subscriptions....")),
+    /// Handle the supplied message, delivering it to every subscription of this topic on the
+    /// calling thread. Messages larger than the configured limit are refused outright.
+    pub fn push(&self, msg: T) -> Result<()> {
+        self.push_with(msg, |_| ())
+    }
+
+    /// Handle the supplied message as [Topic::push] does, calling the supplied function with
+    /// the count of subscriptions it is about to be enqueued into, before any of them can
+    /// deliver it.
+    pub fn push_with(&self, msg: T, counted: impl FnOnce(usize)) -> Result<()> {
+        self.budget.check_message(&msg)?;
+        let (subs, routes) = self.subs()?;
+        Self::fan_out(&subs, &routes, &msg, counted)
+    }
+
+    /// Handle the supplied messages as one batch, delivering them to every subscription of
+    /// this topic. The count of messages handled by every subscription is returned, which
+    /// stops at the first failure, along with that failure if any. Only the messages prior to
+    /// the first message larger than the configured limit are handled.
+    pub fn push_batch(&self, msgs: impl IntoIterator<Item = T>) -> (usize, Result<()>) {
+        let (subs, routes) = match self.subs() {
+            Ok(subs) => subs,
+            Err(err) => return (0, Err(err)),
         };
+        let mut msgs = msgs.into_iter().collect::<Vec<_>>();
+        let oversized = msgs
+            .iter()
+            .map(|msg| self.budget.check_message(msg))
+            .enumerate()
+            .find(|(_, res)| res.is_err());
+        let checked = match oversized {
+            Some((idx, res)) => {
+                msgs.truncate(idx);
+                res
+            }
+            None => Ok(()),
+        };
+        let (count, res) = subs
+            .iter()
+            .map(|(name, sub)| Self::push_selected(name, sub, &routes, &msgs))
+            .fold(
+                (msgs.len(), Ok(())),
+                |(count, res), (pushed, pushed_res)| (count.min(pushed), res.and(pushed_res)),
+            );
+        (count, res.and(checked))
+    }
+
+    /// Enqueue the supplied messages which are routed to the supplied subscription and match
+    /// its filter, returning the count of the supplied messages handled, counting those
+    /// skipped up until the first refused message.
+    fn push_selected(
+        name: &str,
+        sub: &Sub<T>,
+        routes: &Routes<T>,
+        msgs: &[T],
+    ) -> (usize, Result<()>) {
+        if sub.filter.is_none() && routes.as_slice().is_empty() {
+            return sub.queue.push_batch(msgs.iter().cloned());
+        }
+        let selected = msgs
+            .iter()
+            .enumerate()
+            .filter(|(_, msg)| routes.admits(name, msg) && sub.accepts(msg))
+            .map(|(idx, _)| idx)
+            .collect::<Vec<_>>();
+        let (pushed, res) = sub
+            .queue
+            .push_batch(selected.iter().map(|idx| msgs[*idx].clone()));
+        (selected.get(pushed).copied().unwrap_or(msgs.len()), res)
+    }
 
-        sub.queue.push(msg).map_err(|err| err.to_string())
+    /// Return a sorted snapshot of the names of the subscriptions contained in this topic,
+    /// without cloning the subscriptions themselves.
+    pub fn names(&self) -> Vec<Arc<str>> {
+        let mut names = self
+            .subscriptions
+            .iter()
+            .map(|sub| sub.key().clone())
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names
     }
 
-    /// Iterate over the topics contained in this registry. The supplied FnOnce is used to ensure
-    /// the inner state is not mutated while iterating.
-    pub fn iter<R>(&self, func: impl FnOnce(Iter<'_, String, Sub<T>>) -> R) -> R {
-        let guard = self.subscriptions.read().unwrap();
-        func(guard.iter())
+    /// Iterate over the subscriptions contained in this topic. The supplied FnOnce iterates a
+    /// snapshot of the subscriptions, so that subscriptions may be created or removed while
+    /// iterating without blocking on it. Names are shared with the topic rather than copied.
+    pub fn iter<R>(&self, func: impl FnOnce(Iter<'_, (Arc<str>, Sub<T>)>) -> R) -> R {
+        let subs = self
+            .subscriptions
+            .iter()
+            .map(|sub| (sub.key().clone(), sub.value().clone()))
+            .collect::<Vec<_>>();
+        func(subs.iter())
+    }
+}
+
+impl<T> Topic<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    /// Handle the supplied message, delivering it to every subscription of this topic. Room is
+    /// reserved in every subscription up front, so a refused message is enqueued into none of
+    /// them. Topics with more subscriptions than fit in a single fan-out chunk then enqueue
+    /// each chunk on its own task concurrently, failing once every chunk completes.
+    pub async fn publish(&self, msg: T) -> Result<()> {
+        self.publish_with(msg, |_| ()).await
+    }
+
+    /// Handle the supplied message as [Topic::publish] does, calling the supplied function
+    /// with the count of subscriptions it is about to be enqueued into, before any of them can
+    /// deliver it.
+    pub async fn publish_with(&self, msg: T, counted: impl FnOnce(usize)) -> Result<()> {
+        self.budget.check_message(&msg)?;
+        let (subs, routes) = self.subs()?;
+        if self.fanout_chunk == 0 || subs.len() <= self.fanout_chunk {
+            return Self::fan_out(&subs, &routes, &msg, counted);
+        }
+        let mut reserved = Self::reserve(&subs, &routes, &msg)?;
+        counted(reserved.len());
+        let msg = Arc::new(msg);
+        let mut tasks = Vec::new();
+        while !reserved.is_empty() {
+            let chunk = reserved.split_off(reserved.len().saturating_sub(self.fanout_chunk));
+            let msg = msg.clone();
+            tasks.push(tokio::spawn(async move { Self::deliver(chunk, &msg) }));
+        }
+        future::join_all(tasks)
+            .await
+            .into_iter()
+            .map(|res| {
+                res.map_err(|err| Error::FanOut {
+                    reason: err.to_string(),
+                })
+                .and_then(|res| res)
+            })
+            .fold(Ok(()), Result::and)
     }
 }
 
@@ -106,6 +487,8 @@ where
 mod tests {
     use super::*;
 
+    use std::collections::HashMap;
+
     #[test]
     fn test_topic() {
         let default_topic = Topic::<u32>::default();
@@ -150,4 +533,163 @@ mod tests {
 
         assert!(topic.push(0).is_err());
     }
+
+    #[test]
+    fn test_fan_out() {
+        let topic = Topic::<u32>::new().with_fanout_chunk(2);
+        assert!(matches!(
+            tokio_test::block_on(topic.publish(0)),
+            Err(Error::NoSubscriptions)
+        ));
+        let subs = (0..5)
+            .map(|sub| topic.create(sub.to_string()))
+            .collect::<Vec<_>>();
+
+        topic.push(1).unwrap();
+        assert_eq!(topic.push_batch(vec![2, 3]).0, 2);
+        // Fanning out across more than a single chunk enqueues on concurrent tasks.
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(topic.publish(4)).unwrap();
+        for sub in &subs {
+            assert_eq!(sub.queue.len(), 4);
+        }
+        // The count of subscriptions is known before the message is enqueued into any of them.
+        let mut count = 0;
+        runtime
+            .block_on(topic.publish_with(5, |reserved| {
+                count = reserved;
+                assert_eq!(subs[0].queue.snapshot().len(), 4);
+            }))
+            .unwrap();
+        assert_eq!(count, 5);
+        topic.push_with(6, |reserved| count += reserved).unwrap();
+        assert_eq!(count, 10);
+    }
+
+    #[test]
+    fn test_fan_out_refused() {
+        let limits = Limits {
+            pending_per_subscription: 1,
+            ..Limits::default()
+        };
+        let topic = Topic::new().with_budget(Budget::new(limits, |_| 0));
+        let first = topic.create(String::from("first"));
+        let second = topic.create(String::from("second"));
+        second.queue.push(0).unwrap();
+
+        // A message refused by any subscription is enqueued into none of them.
+        assert!(matches!(topic.push(1), Err(Error::LimitExceeded { .. })));
+        assert_eq!((first.queue.len(), second.queue.len()), (0, 1));
+        // Likewise when fanning out across concurrent tasks.
+        let fanned = topic.clone().with_fanout_chunk(1);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        assert!(matches!(
+            runtime.block_on(fanned.publish(1)),
+            Err(Error::LimitExceeded { .. })
+        ));
+        assert_eq!((first.queue.len(), second.queue.len()), (0, 1));
+
+        // Once room is made the message is enqueued into every subscription again.
+        let (lease, index, _) = second.queue.next().unwrap();
+        second.queue.ack(lease.id, index).unwrap();
+        runtime.block_on(fanned.publish(1)).unwrap();
+        assert_eq!((first.queue.len(), second.queue.len()), (1, 1));
+    }
+
+    #[test]
+    fn test_filter() {
+        let msg = |even: bool| {
+            [(String::from("even"), even.to_string())]
+                .into_iter()
+                .collect::<HashMap<_, _>>()
+        };
+        let selector = || {
+            let filter = crate::filter::Filter::parse("attributes.even == 'true'").unwrap();
+            Selector::new(filter, |msg: &HashMap<String, String>| msg)
+        };
+
+        let topic = Topic::new();
+        let all = topic.create(String::from("all"));
+        let (even, _) = topic
+            .create_with_filter(String::from("even"), None, Some(selector()))
+            .unwrap();
+        topic.push(msg(false)).unwrap();
+        topic.push(msg(true)).unwrap();
+        assert_eq!((all.queue.len(), even.queue.len()), (2, 1));
+
+        // Messages skipped by a filter count as handled, up until the first refused message.
+        let limits = Limits {
+            pending_per_subscription: 2,
+            ..Limits::default()
+        };
+        let topic = Topic::new().with_budget(Budget::new(limits, |_| 0));
+        let (even, _) = topic
+            .create_with_filter(String::from("even"), None, Some(selector()))
+            .unwrap();
+        let msgs = [true, false, true, false, true].map(msg);
+        let (count, res) = topic.push_batch(msgs);
+        assert_eq!(count, 4);
+        assert!(matches!(res, Err(Error::LimitExceeded { .. })));
+        assert_eq!(even.queue.len(), 2);
+    }
+
+    #[test]
+    fn test_routes() {
+        let msg = |region: &str| {
+            [(String::from("region"), String::from(region))]
+                .into_iter()
+                .collect::<HashMap<_, _>>()
+        };
+        let route = |sub: &str, filter: &str| {
+            let filter = crate::filter::Filter::parse(filter).unwrap();
+            let selector = Selector::new(filter, |msg: &HashMap<String, String>| msg);
+            Route::new(String::from(sub), selector)
+        };
+
+        let topic = Topic::new().with_fanout_chunk(1);
+        let eu = topic.create(String::from("eu"));
+        let us = topic.create(String::from("us"));
+        let rest = topic.create(String::from("rest"));
+        topic.set_routes(vec![
+            route("eu", "hasprefix(attributes.region, 'eu-')"),
+            route("us", "attributes.region == 'us'"),
+            route("rest", "attributes.region != 'eu-west'"),
+        ]);
+        assert_eq!(topic.routes().len(), 3);
+
+        // Messages are routed by the first rule they match, and dropped if they match none.
+        topic.push(msg("eu-west")).unwrap();
+        assert_eq!(topic.push_batch([msg("us"), msg("ap")]).0, 2);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(topic.publish(msg("eu-north"))).unwrap();
+        topic.push(HashMap::new()).unwrap();
+        let lens = [&eu, &us, &rest].map(|sub| sub.queue.len());
+        assert_eq!(lens, [2, 1, 1]);
+
+        // Clearing the rules broadcasts to every subscription again.
+        topic.set_routes(Vec::new());
+        topic.push(msg("us")).unwrap();
+        let lens = [&eu, &us, &rest].map(|sub| sub.queue.len());
+        assert_eq!(lens, [3, 2, 2]);
+    }
+
+    #[test]
+    fn test_message_too_large() {
+        let limits = Limits {
+            message_bytes: 2,
+            ..Limits::default()
+        };
+        let topic = Topic::<Vec<u8>>::new().with_budget(Budget::new(limits, |msg| msg.len()));
+        let sub = topic.create(String::from("sub"));
+
+        assert!(matches!(
+            tokio_test::block_on(topic.publish(vec![0; 3])),
+            Err(Error::MessageTooLarge { size: 3, limit: 2 })
+        ));
+        // Only the messages prior to the oversized message are handled.
+        let (count, res) = topic.push_batch(vec![vec![0], vec![0; 3], vec![0]]);
+        assert_eq!(count, 1);
+        assert!(matches!(res, Err(Error::MessageTooLarge { .. })));
+        assert_eq!(sub.queue.len(), 1);
+    }
 }
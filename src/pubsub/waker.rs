@@ -34,6 +34,16 @@ impl Waker {
         }
     }
 
+    /// Deregister the waker of the given ID, returning whether it was registered. Streams
+    /// which go away deregister so that their wakers are not left behind.
+    pub fn deregister(&mut self, id: &Uuid) -> bool {
+        if self.wakers.remove(id).is_none() {
+            return false;
+        }
+        self.ids.retain(|known| known != id);
+        true
+    }
+
     /// Wake the oldest known waker in this instance, if no wakers are registered
     /// this is effectively a no-op.
     pub fn wake(&mut self) -> bool {
@@ -49,6 +59,21 @@ impl Waker {
         }
         unreachable!()
     }
+
+    /// Wake up to `count` of the oldest known wakers in one pass, returning the count woken.
+    pub fn wake_many(&mut self, count: usize) -> usize {
+        (0..count).take_while(|_| self.wake()).count()
+    }
+
+    /// Return the count of wakers currently registered.
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Return whether no wakers are currently registered.
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
 }
 
 #[cfg(test)]
@@ -71,5 +96,19 @@ mod tests {
         assert!(waker.wake());
         assert!(waker.wake());
         assert!(!waker.wake());
+
+        (0..3).for_each(|_| waker.register(Uuid::new_v4(), futures::task::noop_waker()));
+        assert_eq!(waker.len(), 3);
+        assert_eq!(waker.wake_many(2), 2);
+        assert_eq!(waker.wake_many(2), 1);
+        assert!(waker.is_empty());
+
+        waker.register(first, futures::task::noop_waker());
+        waker.register(second, futures::task::noop_waker());
+        assert!(waker.deregister(&first));
+        assert!(!waker.deregister(&first));
+        assert_eq!(waker.len(), 1);
+        assert!(waker.wake());
+        assert!(!waker.wake());
     }
 }
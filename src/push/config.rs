@@ -0,0 +1,64 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// extern usings
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, StructOpt)]
+/// Rift push subscription configuration.
+pub struct Config {
+    #[structopt(
+        long = "push-timeout-ms",
+        env = "RIFT_PUSH_TIMEOUT_MS",
+        help = "The time allowed to deliver a message to a push endpoint.",
+        long_help = "Sets the time in milliseconds a push endpoint has to accept a message, after which the delivery is considered failed and the message is nacked.",
+        default_value = "10000",
+        takes_value = true
+    )]
+    /// Define the time allowed to deliver a message to a push endpoint.
+    pub timeout_ms: u64,
+
+    #[structopt(
+        long = "push-backoff-min-ms",
+        env = "RIFT_PUSH_BACKOFF_MIN_MS",
+        help = "The initial delay after a failed push delivery.",
+        long_help = "Sets the delay in milliseconds after the first failed delivery to a push endpoint. The delay doubles on each consecutive failure, and resets once a delivery succeeds.",
+        default_value = "100",
+        takes_value = true
+    )]
+    /// Define the initial delay after a failed push delivery.
+    pub backoff_min_ms: u64,
+
+    #[structopt(
+        long = "push-backoff-max-ms",
+        env = "RIFT_PUSH_BACKOFF_MAX_MS",
+        help = "The maximum delay between failed push deliveries.",
+        long_help = "Sets the maximum delay in milliseconds between consecutive failed deliveries to a push endpoint.",
+        default_value = "30000",
+        takes_value = true
+    )]
+    /// Define the maximum delay between failed push deliveries.
+    pub backoff_max_ms: u64,
+
+    #[structopt(
+        long = "push-sink-max-bytes",
+        env = "RIFT_PUSH_SINK_MAX_BYTES",
+        help = "The size after which file sinks rotate to a new file.",
+        long_help = "Sets the size in bytes after which subscriptions draining into a `file://` endpoint rotate to a new file.",
+        default_value = "67108864",
+        takes_value = true
+    )]
+    /// Define the size after which file sinks rotate to a new file.
+    pub sink_max_bytes: u64,
+
+    #[structopt(
+        long = "push-sink-max-age-ms",
+        env = "RIFT_PUSH_SINK_MAX_AGE_MS",
+        help = "The age after which file sinks rotate to a new file.",
+        long_help = "Sets the age in milliseconds after which subscriptions draining into a `file://` endpoint rotate to a new file. Rotation happens when the next message is written.",
+        default_value = "3600000",
+        takes_value = true
+    )]
+    /// Define the age after which file sinks rotate to a new file.
+    pub sink_max_age_ms: u64,
+}
@@ -0,0 +1,106 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+// crate usings
+use super::{Error, Result};
+
+// extern usings
+use hyper::Uri;
+
+/// An endpoint that a push subscription delivers its messages to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Endpoint {
+    /// An HTTP or HTTPS endpoint that each message is POSTed to.
+    Webhook(Uri),
+    /// The address, without a scheme, of a gRPC server implementing the deliver service.
+    Grpc(String),
+    /// A local directory that messages are drained into as newline-delimited JSON files.
+    File(PathBuf),
+}
+
+impl FromStr for Endpoint {
+    type Err = Error;
+
+    /// Handles converting the supplied &str to an Endpoint.
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// let x = librift::push::Endpoint::from_str("http://localhost:8080/push");
+    /// assert!(x.is_ok());
+    /// ```
+    fn from_str(endpoint: &str) -> Result<Endpoint> {
+        let invalid = |reason: &str| Error::InvalidEndpoint {
+            endpoint: endpoint.to_owned(),
+            reason: reason.to_owned(),
+        };
+        if let Some(path) = endpoint.strip_prefix("file://") {
+            let path = PathBuf::from(path);
+            if !path.is_absolute() {
+                return Err(invalid("the path must be absolute"));
+            }
+            return Ok(Endpoint::File(path));
+        }
+        let uri = Uri::from_str(endpoint).map_err(|err| invalid(&err.to_string()))?;
+        if uri.host().is_none() {
+            return Err(invalid("a host is required"));
+        }
+        match uri.scheme_str() {
+            Some("http") | Some("https") => Ok(Endpoint::Webhook(uri)),
+            Some("grpc") if uri.port().is_none() => Err(invalid("a port is required")),
+            Some("grpc") if uri.path() != "/" => Err(invalid("a path is not supported")),
+            Some("grpc") => Ok(Endpoint::Grpc(uri.authority().unwrap().to_string())),
+            _ => Err(invalid(
+                "the scheme must be one of 'http', 'https', 'grpc', or 'file'",
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Endpoint::Webhook(uri) => write!(f, "{}", uri),
+            Endpoint::Grpc(addr) => write!(f, "grpc://{}", addr),
+            Endpoint::File(path) => write!(f, "file://{}", path.display()),
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoint() {
+        let endpoint = Endpoint::from_str("http://localhost:8080/push").unwrap();
+        assert_eq!(endpoint.to_string(), "http://localhost:8080/push");
+        assert!(matches!(endpoint, Endpoint::Webhook(_)));
+        let endpoint = Endpoint::from_str("https://localhost:8443/push").unwrap();
+        assert_eq!(endpoint.to_string(), "https://localhost:8443/push");
+        assert!(matches!(endpoint, Endpoint::Webhook(_)));
+
+        let endpoint = Endpoint::from_str("grpc://localhost:8081").unwrap();
+        assert_eq!(endpoint.to_string(), "grpc://localhost:8081");
+        assert_eq!(endpoint, Endpoint::Grpc(String::from("localhost:8081")));
+
+        let endpoint = Endpoint::from_str("file:///var/lib/rift/archive").unwrap();
+        assert_eq!(endpoint.to_string(), "file:///var/lib/rift/archive");
+        assert_eq!(
+            endpoint,
+            Endpoint::File(PathBuf::from("/var/lib/rift/archive"))
+        );
+
+        assert!(Endpoint::from_str("file://archive").is_err());
+        assert!(Endpoint::from_str("grpc://localhost").is_err());
+        assert!(Endpoint::from_str("grpc://localhost:8081/push").is_err());
+        assert!(Endpoint::from_str("ftp://localhost/push").is_err());
+        assert!(Endpoint::from_str("/push").is_err());
+        assert!(Endpoint::from_str("not a uri").is_err());
+    }
+}
@@ -0,0 +1,52 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::io;
+use std::result;
+
+// crate usings
+use crate::{claimcheck, encryption};
+
+// extern usings
+use thiserror::Error;
+
+/// Custom Result wrapper to simplify usage.
+pub type Result<T> = result::Result<T, Error>;
+
+/// Represents push subscription errors based on user configuration or failed deliveries.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// Handles push endpoints that can not be delivered to.
+    #[error("the supplied push endpoint '{endpoint}' is invalid: {reason}")]
+    InvalidEndpoint {
+        /// The invalid endpoint.
+        endpoint: String,
+        /// The reason the endpoint is invalid.
+        reason: String,
+    },
+    /// Handles failures sending a message to its push endpoint.
+    #[error("failed to deliver message: {0}")]
+    Request(#[from] hyper::Error),
+    /// Handles gRPC push endpoints which refused or failed to handle a message.
+    #[error("the push endpoint failed to handle the message: {0}")]
+    Status(#[from] tonic::Status),
+    /// Handles failures writing messages to a file sink.
+    #[error("failed to write message: {0}")]
+    Io(#[from] io::Error),
+    /// Handles messages which failed to be decrypted for delivery.
+    #[error("failed to decrypt message: {0}")]
+    Encryption(#[from] encryption::Error),
+    /// Handles claim checked payloads which failed to be claimed for delivery.
+    #[error("failed to claim message payload: {0}")]
+    ClaimCheck(#[from] claimcheck::Error),
+    /// Handles deliveries which did not complete in time.
+    #[error("timed out delivering message")]
+    Timeout,
+    /// Handles deliveries which the push endpoint refused.
+    #[error("the push endpoint rejected the message with status {status}")]
+    Rejected {
+        /// The status the push endpoint responded with.
+        status: u16,
+    },
+}
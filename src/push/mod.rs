@@ -0,0 +1,14 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+mod config;
+mod endpoint;
+mod error;
+mod pusher;
+mod sink;
+
+pub use config::Config;
+pub use endpoint::Endpoint;
+pub use error::{Error, Result};
+pub use pusher::Pusher;
+pub use sink::FileSink;
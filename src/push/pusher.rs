@@ -0,0 +1,508 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::sync::Arc;
+use std::time::Duration;
+
+// crate usings
+use super::{Config, Endpoint, Error, FileSink, Result};
+use crate::claimcheck::{self, CLAIM_CHECK_ATTRIBUTE};
+use crate::cluster::Pool;
+use crate::encryption::Keyring;
+use crate::grpc::deliver::{DeliverRequest, DeliverServiceClient};
+use crate::grpc::pubsub::Message;
+use crate::pubsub::{Registry, Stream, Sub};
+
+// extern usings
+use futures::StreamExt;
+use hyper::client::HttpConnector;
+use hyper::header::{HeaderName, HeaderValue, CONTENT_TYPE};
+use hyper::{Body, Client, Method, Request};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use tokio::task::JoinHandle;
+
+/// The header carrying the topic of a pushed message.
+pub const TOPIC_HEADER: &str = "x-rift-topic";
+/// The header carrying the subscription of a pushed message.
+pub const SUBSCRIPTION_HEADER: &str = "x-rift-subscription";
+/// The prefix of the headers carrying the attributes of a pushed message.
+pub const ATTRIBUTE_HEADER_PREFIX: &str = "x-rift-attribute-";
+
+/// How long a push task waits for a message before checking whether its subscription still
+/// exists.
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Delivers the messages of push subscriptions to their endpoints, acking each message the
+/// endpoint accepts and nacking the rest.
+#[derive(Debug, Clone)]
+pub struct Pusher {
+    client: Client<HttpsConnector<HttpConnector>>,
+    pool: Pool,
+    timeout: Duration,
+    backoff_min: Duration,
+    backoff_max: Duration,
+    sink_max_bytes: u64,
+    sink_max_age: Duration,
+    keyring: Option<Keyring>,
+    claim_checks: Option<claimcheck::Store>,
+    logger: slog::Logger,
+}
+
+impl Pusher {
+    /// Create a new pusher based on the supplied configuration.
+    pub fn new(cfg: &Config, logger: slog::Logger) -> Self {
+        // Webhooks served over TLS are verified against the Mozilla root certificates.
+        let connector = HttpsConnectorBuilder::new()
+            .with_webpki_roots()
+            .https_or_http()
+            .enable_http1()
+            .build();
+        Self {
+            client: Client::builder().build(connector),
+            pool: Pool::new(Duration::from_millis(cfg.timeout_ms)),
+            timeout: Duration::from_millis(cfg.timeout_ms),
+            backoff_min: Duration::from_millis(cfg.backoff_min_ms),
+            backoff_max: Duration::from_millis(cfg.backoff_max_ms),
+            sink_max_bytes: cfg.sink_max_bytes,
+            sink_max_age: Duration::from_millis(cfg.sink_max_age_ms),
+            keyring: None,
+            claim_checks: None,
+            logger,
+        }
+    }
+
+    /// Decrypt the data of messages from encrypted topics with their data keys within the
+    /// supplied keyring before pushing them.
+    pub fn with_keyring(mut self, keyring: Keyring) -> Self {
+        self.keyring = Some(keyring);
+        self
+    }
+
+    /// Claim the payloads of claim checked messages from the supplied store before pushing
+    /// them, deleting each payload once the last message holding it is pushed.
+    pub fn with_claim_checks(mut self, claim_checks: claimcheck::Store) -> Self {
+        self.claim_checks = Some(claim_checks);
+        self
+    }
+
+    /// Spawn a task delivering the messages of the supplied subscription to its endpoint, until
+    /// the subscription is removed from the registry.
+    pub fn spawn(
+        &self,
+        registry: Registry<Message>,
+        topic: String,
+        name: String,
+        sub: Sub<Message>,
+        endpoint: Endpoint,
+    ) -> JoinHandle<()> {
+        let pusher = self.clone();
+        let (topic, name) = (Arc::from(topic), Arc::from(name));
+        tokio::spawn(async move { pusher.run(registry, topic, name, sub, endpoint).await })
+    }
+
+    async fn run(
+        self,
+        registry: Registry<Message>,
+        topic: Arc<str>,
+        name: Arc<str>,
+        sub: Sub<Message>,
+        endpoint: Endpoint,
+    ) {
+        let logger = self.logger.new(o!(
+            "topic" => topic.to_string(),
+            "subscription" => name.to_string(),
+            "endpoint" => endpoint.to_string(),
+        ));
+        let mut sink = match &endpoint {
+            Endpoint::File(dir) => Some(FileSink::new(
+                dir.clone(),
+                &topic,
+                &name,
+                self.sink_max_bytes,
+                self.sink_max_age,
+            )),
+            _ => None,
+        };
+        let mut stream = Stream::from(sub.queue.clone()).with_owner(format!("push {}", endpoint));
+        let mut backoff = self.backoff_min;
+        loop {
+            while let Some(delay) = sub.throttle.delay() {
+                tokio::time::sleep(delay).await;
+            }
+            let next = tokio::time::timeout(IDLE_CHECK_INTERVAL, stream.next()).await;
+            // Stop once the subscription is removed, or replaced by one with another endpoint,
+            // leaving any leased message to be redelivered by its replacement.
+            let current = registry.get(&topic).and_then(|topic| topic.get(&name));
+            let current = current.map(|current| (current.created, current.push));
+            if current != Some((sub.created, sub.push.clone())) {
+                if let Ok(Some((tag, index, _))) = next {
+                    let _ = sub.queue.nack(tag.id, index);
+                }
+                debug!(
+                    &logger,
+                    "Subscription removed or changed, stopping push delivery."
+                );
+                return;
+            }
+            let (tag, index, mut msg) = match next {
+                Ok(Some(next)) => next,
+                Ok(None) => return,
+                Err(_) => continue,
+            };
+            sub.throttle.consume(msg.data.len());
+
+            let opened = match &self.keyring {
+                Some(keyring) => keyring.open(&mut msg).map_err(Error::from),
+                None => Ok(()),
+            };
+            let claimed = match opened {
+                Ok(()) => self.claim(&mut msg).await,
+                Err(err) => Err(err),
+            };
+            let (claimed, res) = match claimed {
+                Err(err) => (None, Err(err)),
+                Ok(claimed) => (claimed, Ok(())),
+            };
+            let res = match res {
+                Err(err) => Err(err),
+                Ok(()) => match sink.take() {
+                    Some(mut file) => {
+                        let subscription = name.clone();
+                        let (file, res) = tokio::task::spawn_blocking(move || {
+                            let res = file.write(&subscription, &msg);
+                            (file, res)
+                        })
+                        .await
+                        .expect("file sink write panicked");
+                        sink = Some(file);
+                        res
+                    }
+                    None => self.deliver(&endpoint, &name, &msg).await,
+                },
+            };
+            match res {
+                Ok(()) => {
+                    backoff = self.backoff_min;
+                    if let Err(err) = sub.queue.ack(tag.id, index) {
+                        warn!(&logger, "Failed to ack pushed message."; "error" => err.to_string());
+                    } else if let Some((store, key)) = self.claim_checks.clone().zip(claimed) {
+                        // A payload which fails to be deleted is left to the retention.
+                        let _ = tokio::task::spawn_blocking(move || store.release(&key)).await;
+                    }
+                }
+                Err(err) => {
+                    warn!(&logger, "Failed to push message, retrying."; "error" => err.to_string(), "backoff_ms" => backoff.as_millis() as u64);
+                    if let Err(err) = sub.queue.nack(tag.id, index) {
+                        warn!(&logger, "Failed to nack pushed message."; "error" => err.to_string());
+                    }
+                    tokio::time::sleep(backoff).await;
+                    backoff = std::cmp::min(backoff * 2, self.backoff_max);
+                }
+            }
+        }
+    }
+
+    /// Replace the data of the supplied message, if claim checked, with its payload, decrypted
+    /// when its topic is encrypted, so that endpoints receive the payload rather than its key.
+    /// Returns the key of the claimed payload, if any.
+    async fn claim(&self, msg: &mut Message) -> Result<Option<String>> {
+        let key = msg.attributes.get(CLAIM_CHECK_ATTRIBUTE).cloned();
+        let (store, key) = match self.claim_checks.clone().zip(key) {
+            Some(claim_check) => claim_check,
+            None => return Ok(None),
+        };
+        let claimed = key.clone();
+        let mut data = tokio::task::spawn_blocking(move || store.get(&claimed))
+            .await
+            .expect("claim check read panicked")?;
+        // Only new topics can be encrypted, so every payload of an encrypted topic is sealed.
+        if let Some(keyring) = self
+            .keyring
+            .as_ref()
+            .filter(|keyring| keyring.is_encrypted(&msg.topic))
+        {
+            data = keyring.decrypt(&msg.topic, &data)?;
+        }
+        msg.data = data.into();
+        msg.attributes.remove(CLAIM_CHECK_ATTRIBUTE);
+        Ok(Some(key))
+    }
+
+    /// Deliver the supplied message to the supplied remote endpoint, succeeding only if the
+    /// endpoint accepted it. File endpoints are written by a [FileSink] instead.
+    pub async fn deliver(
+        &self,
+        endpoint: &Endpoint,
+        subscription: &str,
+        msg: &Message,
+    ) -> Result<()> {
+        match endpoint {
+            Endpoint::Webhook(uri) => {
+                let mut req = Request::builder()
+                    .method(Method::POST)
+                    .uri(uri.clone())
+                    .header(CONTENT_TYPE, "application/octet-stream")
+                    .header(TOPIC_HEADER, msg.topic.as_str())
+                    .header(SUBSCRIPTION_HEADER, subscription)
+                    .body(Body::from(msg.data.clone()))
+                    .unwrap();
+                let headers = req.headers_mut();
+                for (key, value) in &msg.attributes {
+                    let name = format!("{}{}", ATTRIBUTE_HEADER_PREFIX, key.to_lowercase());
+                    // Attributes which are not valid header names or values are not forwarded.
+                    if let (Ok(name), Ok(value)) =
+                        (HeaderName::try_from(name), HeaderValue::try_from(value))
+                    {
+                        headers.insert(name, value);
+                    }
+                }
+
+                let res = tokio::time::timeout(self.timeout, self.client.request(req))
+                    .await
+                    .map_err(|_| Error::Timeout)??;
+                if res.status().is_success() {
+                    Ok(())
+                } else {
+                    Err(Error::Rejected {
+                        status: res.status().as_u16(),
+                    })
+                }
+            }
+            Endpoint::Grpc(addr) => {
+                let channel = self
+                    .pool
+                    .channel(addr)
+                    .map_err(|err| Error::InvalidEndpoint {
+                        endpoint: endpoint.to_string(),
+                        reason: err.to_string(),
+                    })?;
+                let req = DeliverRequest::from_message(subscription.to_owned(), msg);
+                let mut client = DeliverServiceClient::new(channel);
+                tokio::time::timeout(self.timeout, client.deliver(req))
+                    .await
+                    .map_err(|_| Error::Timeout)??;
+                Ok(())
+            }
+            Endpoint::File(_) => Err(Error::InvalidEndpoint {
+                endpoint: endpoint.to_string(),
+                reason: String::from("file endpoints are written by a sink"),
+            }),
+        }
+    }
+}
+
+impl Default for Pusher {
+    fn default() -> Self {
+        let cfg = Config {
+            timeout_ms: 10000,
+            backoff_min_ms: 100,
+            backoff_max_ms: 30000,
+            sink_max_bytes: 64 * 1024 * 1024,
+            sink_max_age_ms: 3600000,
+        };
+        Self::new(&cfg, slog::Logger::root(slog::Discard, o!()))
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    use std::convert::Infallible;
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use hyper::service::{make_service_fn, service_fn};
+    use tonic::{Response as GrpcResponse, Status};
+
+    use crate::grpc::deliver::{DeliverResponse, DeliverService, DeliverServiceServer};
+    use hyper::{Response, Server, StatusCode};
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn test_pusher() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            // Reject the first delivery to exercise the nack and retry path.
+            let attempts = Arc::new(AtomicUsize::new(0));
+            let (tx, mut rx) = mpsc::unbounded_channel();
+            let make_svc = make_service_fn(move |_| {
+                let attempts = attempts.clone();
+                let tx = tx.clone();
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                        let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+                        let tx = tx.clone();
+                        async move {
+                            let topic = req.headers()[TOPIC_HEADER].to_str().unwrap().to_owned();
+                            let attribute = req.headers()["x-rift-attribute-key"]
+                                .to_str()
+                                .unwrap()
+                                .to_owned();
+                            let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+                            tx.send((attempt, topic, attribute, body)).unwrap();
+                            let status = if attempt == 0 {
+                                StatusCode::INTERNAL_SERVER_ERROR
+                            } else {
+                                StatusCode::OK
+                            };
+                            let mut res = Response::new(Body::empty());
+                            *res.status_mut() = status;
+                            Ok::<_, Infallible>(res)
+                        }
+                    }))
+                }
+            });
+            let server = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_svc);
+            let addr = server.local_addr();
+            tokio::spawn(server);
+
+            let registry = Registry::default();
+            let topic = registry.create(String::from("topic"));
+            let sub = topic.create(String::from("sub"));
+            let mut msg = Message {
+                topic: String::from("topic"),
+                data: vec![1, 2, 3].into(),
+                ..Default::default()
+            };
+            msg.attributes
+                .insert(String::from("Key"), String::from("value"));
+            topic.push(msg).unwrap();
+
+            let endpoint = Endpoint::from_str(&format!("http://{}/push", addr)).unwrap();
+            let handle = Pusher::default().spawn(
+                registry,
+                String::from("topic"),
+                String::from("sub"),
+                sub,
+                endpoint,
+            );
+
+            for expected in 0..2 {
+                let (attempt, topic, attribute, body) = rx.recv().await.unwrap();
+                assert_eq!(attempt, expected);
+                assert_eq!(topic, "topic");
+                assert_eq!(attribute, "value");
+                assert_eq!(&body[..], &[1, 2, 3]);
+            }
+            handle.abort();
+        });
+    }
+
+    struct Subscriber(mpsc::UnboundedSender<DeliverRequest>);
+
+    #[tonic::async_trait]
+    impl DeliverService for Subscriber {
+        async fn deliver(
+            &self,
+            request: tonic::Request<DeliverRequest>,
+        ) -> std::result::Result<GrpcResponse<DeliverResponse>, Status> {
+            self.0.send(request.into_inner()).unwrap();
+            Ok(GrpcResponse::new(DeliverResponse {}))
+        }
+    }
+
+    #[test]
+    fn test_pusher_grpc() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let addr = std::net::TcpListener::bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap();
+            let (tx, mut rx) = mpsc::unbounded_channel();
+            tokio::spawn(
+                tonic::transport::Server::builder()
+                    .add_service(DeliverServiceServer::new(Subscriber(tx)))
+                    .serve(addr),
+            );
+
+            let registry = Registry::default();
+            let topic = registry.create(String::from("topic"));
+            let sub = topic.create(String::from("sub"));
+            topic
+                .push(Message {
+                    topic: String::from("topic"),
+                    data: vec![1, 2, 3].into(),
+                    ..Default::default()
+                })
+                .unwrap();
+
+            let endpoint = Endpoint::from_str(&format!("grpc://{}", addr)).unwrap();
+            let handle = Pusher::default().spawn(
+                registry,
+                String::from("topic"),
+                String::from("sub"),
+                sub.clone(),
+                endpoint,
+            );
+
+            let req = rx.recv().await.unwrap();
+            assert_eq!(req.topic, "topic");
+            assert_eq!(req.subscription, "sub");
+            assert_eq!(req.data, vec![1, 2, 3]);
+            handle.abort();
+        });
+    }
+
+    #[test]
+    fn test_pusher_claim_check() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let addr = std::net::TcpListener::bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap();
+            let (tx, mut rx) = mpsc::unbounded_channel();
+            tokio::spawn(
+                tonic::transport::Server::builder()
+                    .add_service(DeliverServiceServer::new(Subscriber(tx)))
+                    .serve(addr),
+            );
+
+            let dir = std::env::temp_dir().join(format!("rift-push-{}", uuid::Uuid::new_v4()));
+            let cfg = claimcheck::Config {
+                dir: Some(dir.clone()),
+                threshold_bytes: 0,
+                retention_ms: 60000,
+            };
+            let store = claimcheck::Store::new(&cfg).unwrap().unwrap();
+            let key = store.put(&[1, 2, 3]).unwrap();
+            store.track(&key, 1);
+
+            let registry = Registry::default();
+            let topic = registry.create(String::from("topic"));
+            let sub = topic.create(String::from("sub"));
+            let mut msg = Message {
+                topic: String::from("topic"),
+                data: key.clone().into_bytes().into(),
+                ..Default::default()
+            };
+            msg.attributes
+                .insert(String::from(CLAIM_CHECK_ATTRIBUTE), key.clone());
+            topic.push(msg).unwrap();
+
+            // Endpoints receive the claimed payload, which is deleted once pushed.
+            let endpoint = Endpoint::from_str(&format!("grpc://{}", addr)).unwrap();
+            let handle = Pusher::default().with_claim_checks(store.clone()).spawn(
+                registry,
+                String::from("topic"),
+                String::from("sub"),
+                sub.clone(),
+                endpoint,
+            );
+
+            let req = rx.recv().await.unwrap();
+            assert_eq!(req.data, vec![1, 2, 3]);
+            assert!(!req.attributes.contains_key(CLAIM_CHECK_ATTRIBUTE));
+            while store.get(&key).is_ok() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            handle.abort();
+            std::fs::remove_dir_all(dir).unwrap();
+        });
+    }
+}
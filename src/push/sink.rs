@@ -0,0 +1,167 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// crate usings
+use super::Result;
+use crate::grpc::pubsub::Message;
+
+// extern usings
+use serde_json::json;
+
+/// The file currently being written by a [FileSink].
+#[derive(Debug)]
+struct Current {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    opened: Instant,
+}
+
+/// Drains the messages of a subscription into newline-delimited JSON files within a directory,
+/// rotating to a new file once the current one exceeds a size or age.
+///
+/// Each line is a JSON object holding the topic, subscription, attributes, publish time in
+/// milliseconds since the unix epoch, and base64 encoded data of a single message.
+#[derive(Debug)]
+pub struct FileSink {
+    dir: PathBuf,
+    prefix: String,
+    max_bytes: u64,
+    max_age: Duration,
+    current: Option<Current>,
+}
+
+impl FileSink {
+    /// Create a new sink writing files within the supplied directory for the supplied
+    /// subscription, rotating files after `max_bytes` bytes or `max_age` has elapsed.
+    pub fn new(
+        dir: PathBuf,
+        topic: &str,
+        subscription: &str,
+        max_bytes: u64,
+        max_age: Duration,
+    ) -> Self {
+        let prefix = format!("{}.{}", topic, subscription).replace('/', "_");
+        Self {
+            dir,
+            prefix,
+            max_bytes,
+            max_age,
+            current: None,
+        }
+    }
+
+    /// Return the path of the file currently being written, if any.
+    pub fn path(&self) -> Option<&PathBuf> {
+        self.current.as_ref().map(|current| &current.path)
+    }
+
+    /// Append the supplied message to the current file, rotating first if it is due, and
+    /// syncing it to disk before returning so that the message can be safely acked.
+    pub fn write(&mut self, subscription: &str, msg: &Message) -> Result<()> {
+        let published = msg
+            .published
+            .as_ref()
+            .map(|ts| ts.seconds * 1000 + i64::from(ts.nanos) / 1_000_000);
+        let mut line = json!({
+            "topic": msg.topic,
+            "subscription": subscription,
+            "attributes": msg.attributes,
+            "published": published,
+            "data": base64::encode(&msg.data),
+        })
+        .to_string();
+        line.push('\n');
+
+        let due = match &self.current {
+            Some(current) => {
+                current.written + line.len() as u64 > self.max_bytes && current.written > 0
+                    || current.opened.elapsed() >= self.max_age
+            }
+            None => true,
+        };
+        if due {
+            self.rotate()?;
+        }
+
+        let current = self.current.as_mut().unwrap();
+        current.file.write_all(line.as_bytes())?;
+        current.file.sync_data()?;
+        current.written += line.len() as u64;
+        Ok(())
+    }
+
+    /// Close the current file, if any, and open a new one.
+    fn rotate(&mut self) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        // Suffix the file name to avoid clobbering files rotated within the same millisecond.
+        let mut attempt = 0;
+        let (path, file) = loop {
+            let path = self
+                .dir
+                .join(format!("{}.{}.{}.ndjson", self.prefix, millis, attempt));
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => break (path, file),
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => attempt += 1,
+                Err(err) => return Err(err.into()),
+            }
+        };
+        self.current = Some(Current {
+            path,
+            file,
+            written: 0,
+            opened: Instant::now(),
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    use bytes::Bytes;
+
+    #[test]
+    fn test_file_sink() {
+        let dir = std::env::temp_dir().join(format!("rift-sink-{}", uuid::Uuid::new_v4()));
+        let mut sink = FileSink::new(dir.clone(), "topic", "sub", 200, Duration::from_secs(3600));
+        assert!(sink.path().is_none());
+
+        let msg = Message {
+            topic: String::from("topic"),
+            data: Bytes::from_static(b"hello"),
+            ..Default::default()
+        };
+        sink.write("sub", &msg).unwrap();
+        let first = sink.path().unwrap().clone();
+        sink.write("sub", &msg).unwrap();
+        assert_eq!(sink.path().unwrap(), &first);
+
+        let contents = std::fs::read_to_string(&first).unwrap();
+        let lines = contents.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 2);
+        let line: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(line["topic"], "topic");
+        assert_eq!(line["subscription"], "sub");
+        assert_eq!(line["data"], base64::encode(b"hello"));
+
+        // The third line exceeds the maximum size, so is written to a new file.
+        sink.write("sub", &msg).unwrap();
+        assert_ne!(sink.path().unwrap(), &first);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
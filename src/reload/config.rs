@@ -0,0 +1,33 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::path::PathBuf;
+
+// extern usings
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, StructOpt)]
+/// Rift runtime configuration reloading.
+pub struct Config {
+    #[structopt(
+        long = "reload-file",
+        env = "RIFT_RELOAD_FILE",
+        help = "The file of settings to apply without restarting.",
+        long_help = "Sets the JSON file of runtime-tunable settings, keyed by their flag names, which riftd applies at startup and again on SIGHUP or whenever the file is modified. Only log-level, max-connections-per-ip, max-streams-per-caller, and claim-check-retention-ms can be reloaded. SIGHUP no longer toggles debug logging when supplied.",
+        takes_value = true
+    )]
+    /// Define the file of reloadable settings, if any.
+    pub reload_file: Option<PathBuf>,
+
+    #[structopt(
+        long = "reload-watch-interval-ms",
+        env = "RIFT_RELOAD_WATCH_INTERVAL_MS",
+        help = "How often the reload file is checked for changes.",
+        long_help = "Sets the interval in milliseconds between checks of the reload file for changes, applying it again whenever it is modified. The file is only reloaded on SIGHUP when set to 0.",
+        default_value = "5000",
+        takes_value = true
+    )]
+    /// Define how often the reload file is checked for changes.
+    pub reload_watch_interval_ms: u64,
+}
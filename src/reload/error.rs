@@ -0,0 +1,43 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::io;
+use std::result;
+
+// extern usings
+use thiserror::Error;
+
+/// Custom Result wrapper to simplify usage.
+pub type Result<T> = result::Result<T, Error>;
+
+/// Represents errors reloading runtime settings. A reload which fails applies none of its
+/// settings.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// Handles failures reading the reload file.
+    #[error("failed to read '{path}': {source}")]
+    Io {
+        /// The path that failed to be read.
+        path: String,
+        /// The initial error cause.
+        source: io::Error,
+    },
+    /// Handles reload files which are not a JSON object.
+    #[error("failed to parse the reload file: {0}")]
+    Parse(#[from] serde_json::Error),
+    /// Handles settings which can only be changed by restarting riftd.
+    #[error("the setting '{setting}' can not be reloaded, change it and restart riftd instead")]
+    NotReloadable {
+        /// The setting which can not be reloaded.
+        setting: String,
+    },
+    /// Handles reloadable settings with invalid values.
+    #[error("invalid value for the setting '{setting}': {reason}")]
+    Invalid {
+        /// The setting with an invalid value.
+        setting: String,
+        /// The reason the value is invalid.
+        reason: String,
+    },
+}
@@ -0,0 +1,330 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+// crate usings
+use super::{Config, Error, Result};
+use crate::claimcheck;
+use crate::grpc::limit::Limiter;
+use crate::log::{Handle, Level};
+
+// extern usings
+use serde_json::{Map, Value};
+
+const LOG_LEVEL: &str = "log-level";
+const MAX_CONNECTIONS_PER_IP: &str = "max-connections-per-ip";
+const MAX_STREAMS_PER_CALLER: &str = "max-streams-per-caller";
+const CLAIM_CHECK_RETENTION_MS: &str = "claim-check-retention-ms";
+
+/// A setting whose value was changed by a reload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    /// The name of the setting, matching its flag.
+    pub setting: String,
+    /// The value prior to the reload.
+    pub from: String,
+    /// The value after the reload.
+    pub to: String,
+}
+
+/// A validated value for a reloadable setting, along with what it applies to.
+enum Update<'a> {
+    LogLevel(Level),
+    Limit(&'a Limiter, usize),
+    Retention(&'a claimcheck::Store, Duration),
+}
+
+/// Applies files of runtime-tunable settings to a running riftd, without a restart.
+///
+/// Settings are keyed by their flag names, and only those present in the file are applied.
+/// A file containing any setting which can not be reloaded, or any invalid value, is
+/// rejected as a whole.
+#[derive(Debug, Clone)]
+pub struct Reloader {
+    log_level: Handle,
+    connections: Option<Limiter>,
+    streams: Option<Limiter>,
+    claim_checks: Option<claimcheck::Store>,
+}
+
+impl Reloader {
+    /// Create a new reloader switching the level of logs through the supplied handle.
+    pub fn new(log_level: Handle) -> Self {
+        Self {
+            log_level,
+            connections: None,
+            streams: None,
+            claim_checks: None,
+        }
+    }
+
+    /// Reload the maximum of the supplied per-IP connection limiter.
+    pub fn with_connection_limiter(mut self, connections: Limiter) -> Self {
+        self.connections = Some(connections);
+        self
+    }
+
+    /// Reload the maximum of the supplied per-caller stream limiter.
+    pub fn with_stream_limiter(mut self, streams: Limiter) -> Self {
+        self.streams = Some(streams);
+        self
+    }
+
+    /// Reload the retention of the supplied claim check store.
+    pub fn with_claim_checks(mut self, claim_checks: claimcheck::Store) -> Self {
+        self.claim_checks = Some(claim_checks);
+        self
+    }
+
+    fn parse<'a>(&'a self, setting: &str, value: &Value) -> Result<Update<'a>> {
+        let invalid = |reason: String| Error::Invalid {
+            setting: setting.to_owned(),
+            reason,
+        };
+        let count = || {
+            value
+                .as_u64()
+                .ok_or_else(|| invalid(String::from("expected a non-negative integer")))
+        };
+        match (
+            setting,
+            &self.connections,
+            &self.streams,
+            &self.claim_checks,
+        ) {
+            (LOG_LEVEL, ..) => {
+                let level = value
+                    .as_str()
+                    .ok_or_else(|| invalid(String::from("expected a string")))?;
+                Level::from_str(level)
+                    .map(Update::LogLevel)
+                    .map_err(|err| invalid(err.to_string()))
+            }
+            (MAX_CONNECTIONS_PER_IP, Some(connections), ..) => {
+                Ok(Update::Limit(connections, count()? as usize))
+            }
+            (MAX_STREAMS_PER_CALLER, _, Some(streams), _) => {
+                Ok(Update::Limit(streams, count()? as usize))
+            }
+            (CLAIM_CHECK_RETENTION_MS, .., Some(store)) => {
+                Ok(Update::Retention(store, Duration::from_millis(count()?)))
+            }
+            (MAX_CONNECTIONS_PER_IP | MAX_STREAMS_PER_CALLER | CLAIM_CHECK_RETENTION_MS, ..) => {
+                Err(invalid(String::from("the feature it tunes is not enabled")))
+            }
+            _ => Err(Error::NotReloadable {
+                setting: setting.to_owned(),
+            }),
+        }
+    }
+
+    fn update(&self, setting: &str, update: Update) -> Option<Change> {
+        let (from, to) = match update {
+            Update::LogLevel(level) => (self.log_level.set(&level).to_string(), level.to_string()),
+            Update::Limit(limiter, max) => (limiter.set_max(max).to_string(), max.to_string()),
+            Update::Retention(store, retention) => (
+                store.set_retention(retention).as_millis().to_string(),
+                retention.as_millis().to_string(),
+            ),
+        };
+        (from != to).then(|| Change {
+            setting: setting.to_owned(),
+            from,
+            to,
+        })
+    }
+
+    /// Apply the supplied JSON object of settings, returning those which changed. Nothing is
+    /// applied if any setting can not be reloaded or has an invalid value.
+    pub fn apply(&self, data: &str) -> Result<Vec<Change>> {
+        let settings: Map<String, Value> = serde_json::from_str(data)?;
+        let updates = settings
+            .iter()
+            .map(|(setting, value)| Ok((setting, self.parse(setting, value)?)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(updates
+            .into_iter()
+            .filter_map(|(setting, update)| self.update(setting, update))
+            .collect())
+    }
+
+    /// Apply the settings in the supplied file, returning those which changed.
+    pub fn reload(&self, path: &Path) -> Result<Vec<Change>> {
+        let data = std::fs::read_to_string(path).map_err(|source| Error::Io {
+            path: path.display().to_string(),
+            source,
+        })?;
+        self.apply(&data)
+    }
+
+    fn log(&self, logger: &slog::Logger, changes: Result<Vec<Change>>) {
+        match changes {
+            Ok(changes) if changes.is_empty() => {
+                info!(logger, "Reloaded settings, nothing changed.")
+            }
+            Ok(changes) => {
+                for change in changes {
+                    info!(logger, "Reloaded setting.";
+                        "setting" => change.setting,
+                        "from" => change.from,
+                        "to" => change.to,
+                    );
+                }
+            }
+            Err(err) => {
+                error!(logger, "Rejected reloaded settings, none were applied."; "error" => err.to_string())
+            }
+        }
+    }
+
+    /// Reload the supplied file on SIGHUP, and whenever it is modified if the supplied
+    /// interval is non-zero, forever.
+    #[cfg(unix)]
+    pub async fn watch(self, path: PathBuf, interval: Duration, logger: slog::Logger) {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut hangups = match signal(SignalKind::hangup()) {
+            Ok(hangups) => hangups,
+            Err(err) => {
+                error!(&logger, "Failed to register SIGHUP handler."; "error" => err.to_string());
+                return;
+            }
+        };
+        let modified = |path: &PathBuf| {
+            std::fs::metadata(path)
+                .and_then(|meta| meta.modified())
+                .ok()
+        };
+        let mut last = modified(&path);
+        let watching = !interval.is_zero();
+        let mut ticks = tokio::time::interval(interval.max(Duration::from_millis(1)));
+        loop {
+            tokio::select! {
+                _ = ticks.tick(), if watching => {
+                    let current = modified(&path);
+                    if current == last {
+                        continue;
+                    }
+                    last = current;
+                }
+                hangup = hangups.recv() => {
+                    if hangup.is_none() {
+                        return;
+                    }
+                    last = modified(&path);
+                }
+            }
+            self.log(&logger, self.reload(&path));
+        }
+    }
+
+    /// Apply the file configured by the supplied configuration, if any, then spawn a task
+    /// reloading it as configured.
+    #[cfg(unix)]
+    pub fn start(self, cfg: &Config, logger: slog::Logger) -> Result<()> {
+        let path = match &cfg.reload_file {
+            Some(path) => path.clone(),
+            None => return Ok(()),
+        };
+        self.log(&logger, Ok(self.reload(&path)?));
+        let interval = Duration::from_millis(cfg.reload_watch_interval_ms);
+        tokio::spawn(self.watch(path, interval, logger));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        let log_level = Handle::new(&Level::Info);
+        let connections = Limiter::new(0);
+        let streams = Limiter::new(4);
+        let reloader = Reloader::new(log_level.clone())
+            .with_connection_limiter(connections.clone())
+            .with_stream_limiter(streams.clone());
+
+        let changes = reloader
+            .apply(r#"{"log-level": "debug", "max-connections-per-ip": 8, "max-streams-per-caller": 4}"#)
+            .unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                Change {
+                    setting: String::from(LOG_LEVEL),
+                    from: String::from("info"),
+                    to: String::from("debug"),
+                },
+                Change {
+                    setting: String::from(MAX_CONNECTIONS_PER_IP),
+                    from: String::from("0"),
+                    to: String::from("8"),
+                },
+            ]
+        );
+        assert_eq!(log_level.get(), Level::Debug);
+        assert_eq!(connections.max(), 8);
+        assert!(reloader.apply("{}").unwrap().is_empty());
+
+        // Files with any rejected setting apply none of their settings.
+        let rejected = [
+            (
+                r#"{"max-streams-per-caller": 1, "grpc-addr": "[::]:9000"}"#,
+                "grpc-addr",
+            ),
+            (
+                r#"{"max-streams-per-caller": 1, "log-level": "loud"}"#,
+                "log-level",
+            ),
+            (
+                r#"{"max-streams-per-caller": -1}"#,
+                "max-streams-per-caller",
+            ),
+            (
+                r#"{"claim-check-retention-ms": 1}"#,
+                "claim-check-retention-ms",
+            ),
+        ];
+        for (data, setting) in rejected {
+            let err = reloader.apply(data).unwrap_err();
+            assert!(err.to_string().contains(setting), "{}", err);
+        }
+        assert!(matches!(
+            reloader.apply(r#"{"grpc-addr": "[::]:9000"}"#),
+            Err(Error::NotReloadable { .. })
+        ));
+        assert!(matches!(reloader.apply("[]"), Err(Error::Parse(_))));
+        assert_eq!(streams.max(), 4);
+    }
+
+    #[test]
+    fn test_reload() {
+        let dir = std::env::temp_dir().join(format!("rift-reload-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let store = claimcheck::Store::new(&claimcheck::Config {
+            dir: Some(dir.join("claims")),
+            threshold_bytes: 4,
+            retention_ms: 60000,
+        })
+        .unwrap()
+        .unwrap();
+        let reloader = Reloader::new(Handle::new(&Level::Info)).with_claim_checks(store.clone());
+
+        let path = dir.join("reload.json");
+        assert!(matches!(reloader.reload(&path), Err(Error::Io { .. })));
+        std::fs::write(&path, r#"{"claim-check-retention-ms": 1000}"#).unwrap();
+        let changes = reloader.reload(&path).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].from, "60000");
+        assert_eq!(store.retention(), Duration::from_secs(1));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
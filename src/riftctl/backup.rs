@@ -0,0 +1,203 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+
+use exitcode::ExitCode;
+use prost::Message;
+use structopt::StructOpt;
+
+use crate::grpc::backup::{BackupRecord, BackupRequest, BackupServiceClient};
+
+use super::client;
+
+/// The path standing in for stdin or stdout.
+const STDIO: &str = "-";
+
+/// Back up every topic, subscription and unacked message of a riftd instance.
+#[derive(Debug, Clone, StructOpt)]
+pub struct Backup {
+    #[structopt(
+        long = "output",
+        short = "o",
+        help = "The file to write the backup to, or '-' for stdout.",
+        long_help = "Sets the file to write the backup to. A value of '-' writes the backup to stdout, so that it can be piped to an object store client or compressed on the fly.",
+        default_value = "-",
+        takes_value = true
+    )]
+    output: PathBuf,
+    #[structopt(
+        long = "metadata-only",
+        help = "Only back up topics and subscriptions.",
+        long_help = "Only back up topics and subscriptions, omitting the unacked messages of every subscription.",
+        takes_value = false
+    )]
+    metadata_only: bool,
+}
+
+impl Backup {
+    /// Execute the backup command using the supplied client configuration.
+    pub async fn run(self, logger: &slog::Logger, cfg: &client::Config) -> ExitCode {
+        let channel = match cfg.channel() {
+            Ok(channel) => channel,
+            Err(err) => {
+                crit!(logger, "Invalid gRPC endpoint supplied."; "endpoint" => &cfg.endpoint, "error" => err.to_string());
+                return exitcode::CONFIG;
+            }
+        };
+        let client = BackupServiceClient::new(channel);
+
+        let mut output: Box<dyn Write> = if self.output.as_os_str() == STDIO {
+            Box::new(io::stdout())
+        } else {
+            match File::create(&self.output) {
+                Ok(file) => Box::new(io::BufWriter::new(file)),
+                Err(err) => {
+                    crit!(logger, "Failed to create the backup file."; "path" => self.output.display().to_string(), "error" => err.to_string());
+                    return exitcode::CANTCREAT;
+                }
+            }
+        };
+
+        let req = BackupRequest {
+            metadata_only: self.metadata_only,
+        };
+        let res = cfg
+            .call(logger, || {
+                let mut client = client.clone();
+                let req = req.clone();
+                async move { client.backup(req).await }
+            })
+            .await;
+        let mut stream = match res {
+            Ok(res) => res.into_inner(),
+            Err(status) => return client::report(logger, "Failed to start the backup.", &status),
+        };
+
+        let mut records = 0u64;
+        loop {
+            let record = match stream.message().await {
+                Ok(Some(record)) => record,
+                Ok(None) => break,
+                Err(status) => {
+                    return client::report(logger, "Failed to read the backup.", &status)
+                }
+            };
+            if let Err(err) = output.write_all(&record.encode_length_delimited_to_vec()) {
+                crit!(logger, "Failed to write the backup."; "error" => err.to_string());
+                return exitcode::IOERR;
+            }
+            records += 1;
+        }
+        if let Err(err) = output.flush() {
+            crit!(logger, "Failed to write the backup."; "error" => err.to_string());
+            return exitcode::IOERR;
+        }
+        info!(logger, "Backup complete."; "records" => records);
+        exitcode::OK
+    }
+}
+
+/// Restore a backup into a riftd instance.
+#[derive(Debug, Clone, StructOpt)]
+pub struct Restore {
+    #[structopt(
+        long = "input",
+        short = "i",
+        help = "The file to read the backup from, or '-' for stdin.",
+        long_help = "Sets the file to read the backup from. A value of '-' reads the backup from stdin, so that it can be piped from an object store client.",
+        default_value = "-",
+        takes_value = true
+    )]
+    input: PathBuf,
+}
+
+impl Restore {
+    /// Execute the restore command using the supplied client configuration.
+    pub async fn run(self, logger: &slog::Logger, cfg: &client::Config) -> ExitCode {
+        let channel = match cfg.channel() {
+            Ok(channel) => channel,
+            Err(err) => {
+                crit!(logger, "Invalid gRPC endpoint supplied."; "endpoint" => &cfg.endpoint, "error" => err.to_string());
+                return exitcode::CONFIG;
+            }
+        };
+        let mut client = BackupServiceClient::new(channel);
+
+        let mut data = Vec::new();
+        let read = if self.input.as_os_str() == STDIO {
+            io::stdin().read_to_end(&mut data)
+        } else {
+            File::open(&self.input).and_then(|mut file| file.read_to_end(&mut data))
+        };
+        if let Err(err) = read {
+            crit!(logger, "Failed to read the backup file."; "path" => self.input.display().to_string(), "error" => err.to_string());
+            return exitcode::NOINPUT;
+        }
+        // Decode the whole backup up front, so that a corrupt backup is never partially restored.
+        let records = match decode(&data) {
+            Ok(records) => records,
+            Err(err) => {
+                crit!(logger, "Failed to decode the backup."; "error" => err.to_string());
+                return exitcode::DATAERR;
+            }
+        };
+
+        // The records are consumed as they are streamed, so the restore can't be retried and
+        // runs without the per attempt timeout.
+        let restored = match client.restore(futures::stream::iter(records)).await {
+            Ok(res) => res.into_inner(),
+            Err(status) => return client::report(logger, "Failed to restore the backup.", &status),
+        };
+        info!(logger, "Restore complete.";
+            "topics" => restored.topics,
+            "subscriptions" => restored.subscriptions,
+            "messages" => restored.messages,
+        );
+        exitcode::OK
+    }
+}
+
+/// Decode the length delimited records of the supplied backup.
+fn decode(mut data: &[u8]) -> Result<Vec<BackupRecord>, prost::DecodeError> {
+    let mut records = Vec::new();
+    while !data.is_empty() {
+        records.push(BackupRecord::decode_length_delimited(&mut data)?);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+    use crate::grpc::backup::{BackupMessage, Record};
+    use crate::grpc::topic::Topic;
+
+    #[test]
+    fn test_decode() {
+        let records = vec![
+            BackupRecord {
+                record: Some(Record::Topic(Topic {
+                    name: String::from("orders"),
+                    ..Default::default()
+                })),
+            },
+            BackupRecord {
+                record: Some(Record::Message(BackupMessage {
+                    subscription: String::from("audit"),
+                    message: None,
+                })),
+            },
+        ];
+        let data: Vec<u8> = records
+            .iter()
+            .flat_map(|record| record.encode_length_delimited_to_vec())
+            .collect();
+        assert_eq!(decode(&data).unwrap(), records);
+        assert!(decode(&[]).unwrap().is_empty());
+        assert!(decode(&data[..data.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,264 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0
+
+use std::future::Future;
+use std::time::Duration;
+
+use exitcode::ExitCode;
+use rand::Rng;
+use structopt::StructOpt;
+use tonic::codegen::http::uri::InvalidUri;
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Code, Status};
+
+use crate::pubsub::namespace;
+
+/// Client configuration shared by all riftctl commands, which controls how gRPC calls are
+/// made against riftd.
+#[derive(Debug, Clone, StructOpt)]
+pub struct Config {
+    #[structopt(
+        long = "grpc-endpoint",
+        short = "g",
+        env = "RIFT_GRPC_ENDPOINT",
+        help = "The gRPC endpoint of the riftd instance to manage.",
+        long_help = "This sets the endpoint, including the scheme, of the riftd instance to send gRPC requests to.",
+        default_value = "http://127.0.0.1:8081",
+        takes_value = true
+    )]
+    /// The endpoint of the riftd instance to send requests to.
+    pub endpoint: String,
+    #[structopt(
+        long = "timeout-ms",
+        env = "RIFT_TIMEOUT_MS",
+        help = "The timeout in milliseconds for each individual gRPC call attempt.",
+        long_help = "Sets the timeout in milliseconds that each individual gRPC call attempt, including connecting, is allowed to take before failing with DEADLINE_EXCEEDED.",
+        default_value = "5000",
+        takes_value = true
+    )]
+    /// The timeout in milliseconds for each individual call attempt.
+    pub timeout_ms: u64,
+    #[structopt(
+        long = "retries",
+        env = "RIFT_RETRIES",
+        help = "The number of times to retry a gRPC call that failed with UNAVAILABLE.",
+        long_help = "Sets the number of times to retry a gRPC call that failed with UNAVAILABLE, a value of zero disables retries.",
+        default_value = "3",
+        takes_value = true
+    )]
+    /// The number of times to retry calls that failed with UNAVAILABLE.
+    pub retries: u32,
+    #[structopt(
+        long = "backoff-ms",
+        env = "RIFT_BACKOFF_MS",
+        help = "The base backoff in milliseconds between retries.",
+        long_help = "Sets the base backoff in milliseconds between retries, which is doubled on each subsequent retry and jittered.",
+        default_value = "100",
+        takes_value = true
+    )]
+    /// The base backoff in milliseconds between retries.
+    pub backoff_ms: u64,
+    #[structopt(
+        long = "max-backoff-ms",
+        env = "RIFT_MAX_BACKOFF_MS",
+        help = "The maximum backoff in milliseconds between retries.",
+        default_value = "5000",
+        takes_value = true
+    )]
+    /// The maximum backoff in milliseconds between retries.
+    pub max_backoff_ms: u64,
+    #[structopt(
+        long = "namespace",
+        short = "N",
+        env = "RIFT_NAMESPACE",
+        help = "The namespace topic names refer to.",
+        long_help = "Sets the namespace that the topic names supplied to every command refer to, unless they are already qualified as `namespace/name`.",
+        default_value = "default",
+        takes_value = true
+    )]
+    /// The namespace unqualified topic names refer to.
+    pub namespace: String,
+}
+
+impl Config {
+    /// Create a lazily connected channel to the configured endpoint. Connection failures are
+    /// surfaced as UNAVAILABLE statuses on the first call, which allows them to be retried.
+    pub fn channel(&self) -> Result<Channel, InvalidUri> {
+        let endpoint = Endpoint::from_shared(self.endpoint.clone())?
+            .connect_timeout(Duration::from_millis(self.timeout_ms));
+        Ok(endpoint.connect_lazy())
+    }
+
+    /// Qualify the supplied topic name with the configured namespace, unless it is already
+    /// qualified.
+    pub fn qualify(&self, topic: &str) -> String {
+        if topic.contains(namespace::NAMESPACE_SEPARATOR) {
+            return topic.to_owned();
+        }
+        namespace::qualify(&self.namespace, topic)
+    }
+
+    /// Compute the jittered backoff to wait before the supplied retry attempt, starting at zero.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let ceiling = self
+            .backoff_ms
+            .saturating_mul(1u64.checked_shl(attempt).unwrap_or(u64::MAX))
+            .min(self.max_backoff_ms);
+        Duration::from_millis(rand::thread_rng().gen_range(0..=ceiling))
+    }
+
+    /// Execute the supplied gRPC call, applying the configured per attempt timeout and retrying
+    /// calls that fail with UNAVAILABLE using an exponential backoff with jitter.
+    pub async fn call<T, F, Fut>(&self, logger: &slog::Logger, mut func: F) -> Result<T, Status>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, Status>>,
+    {
+        let timeout = Duration::from_millis(self.timeout_ms);
+        let mut attempt = 0;
+        loop {
+            let status = match tokio::time::timeout(timeout, func()).await {
+                Ok(Ok(res)) => return Ok(res),
+                Ok(Err(status)) => status,
+                Err(_) => Status::deadline_exceeded(format!(
+                    "the call did not complete within {}ms",
+                    self.timeout_ms
+                )),
+            };
+            if status.code() != Code::Unavailable || attempt >= self.retries {
+                return Err(status);
+            }
+
+            let backoff = self.backoff(attempt);
+            attempt += 1;
+            warn!(logger, "Call failed with a transient error, retrying.";
+                "attempt" => attempt,
+                "backoff_ms" => backoff.as_millis() as u64,
+                "error" => status.message(),
+            );
+            tokio::time::sleep(backoff).await;
+        }
+    }
+}
+
+/// Report the supplied failed gRPC call in a human readable form, and return the exit code
+/// which best represents the failure.
+pub fn report(logger: &slog::Logger, msg: &str, status: &Status) -> ExitCode {
+    crit!(logger, "{}", msg; "code" => format!("{:?}", status.code()), "error" => status.message());
+    exit_code(status)
+}
+
+/// Map the supplied gRPC status to the exit code which best represents it.
+pub fn exit_code(status: &Status) -> ExitCode {
+    match status.code() {
+        Code::Ok => exitcode::OK,
+        Code::Unavailable => exitcode::UNAVAILABLE,
+        Code::DeadlineExceeded | Code::ResourceExhausted | Code::Aborted => exitcode::TEMPFAIL,
+        Code::InvalidArgument | Code::NotFound | Code::AlreadyExists | Code::OutOfRange => {
+            exitcode::DATAERR
+        }
+        Code::PermissionDenied | Code::Unauthenticated => exitcode::NOPERM,
+        _ => exitcode::SOFTWARE,
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    fn config() -> Config {
+        Config {
+            endpoint: String::from("http://127.0.0.1:8081"),
+            timeout_ms: 50,
+            retries: 2,
+            backoff_ms: 1,
+            max_backoff_ms: 4,
+            namespace: String::from("billing"),
+        }
+    }
+
+    #[test]
+    fn test_qualify() {
+        let mut cfg = config();
+        assert_eq!(cfg.qualify("orders"), "billing/orders");
+        assert_eq!(cfg.qualify("shipping/orders"), "shipping/orders");
+        cfg.namespace = String::from("default");
+        assert_eq!(cfg.qualify("orders"), "orders");
+    }
+
+    #[test]
+    fn test_backoff() {
+        let cfg = config();
+        for attempt in 0..64 {
+            assert!(cfg.backoff(attempt) <= Duration::from_millis(cfg.max_backoff_ms));
+        }
+    }
+
+    #[test]
+    fn test_call_retries_unavailable() {
+        let logger = slog::Logger::root(slog::Discard {}, o!());
+        let cfg = config();
+
+        let calls = AtomicU32::new(0);
+        let res: Result<(), Status> = aw!(cfg.call(&logger, || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async { Err(Status::unavailable("nope")) }
+        }));
+        assert_eq!(res.unwrap_err().code(), Code::Unavailable);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        let calls = AtomicU32::new(0);
+        let res = aw!(cfg.call(&logger, || {
+            let attempt = calls.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if attempt == 0 {
+                    Err(Status::unavailable("nope"))
+                } else {
+                    Ok(attempt)
+                }
+            }
+        }));
+        assert_eq!(res.unwrap(), 1);
+    }
+
+    #[test]
+    fn test_call_fails_fast() {
+        let logger = slog::Logger::root(slog::Discard {}, o!());
+        let cfg = config();
+
+        let calls = AtomicU32::new(0);
+        let res: Result<(), Status> = aw!(cfg.call(&logger, || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async { Err(Status::not_found("nope")) }
+        }));
+        assert_eq!(res.unwrap_err().code(), Code::NotFound);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let res: Result<(), Status> = aw!(cfg.call(&logger, || async {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok(())
+        }));
+        assert_eq!(res.unwrap_err().code(), Code::DeadlineExceeded);
+    }
+
+    #[test]
+    fn test_exit_code() {
+        assert_eq!(exit_code(&Status::unavailable("")), exitcode::UNAVAILABLE);
+        assert_eq!(
+            exit_code(&Status::deadline_exceeded("")),
+            exitcode::TEMPFAIL
+        );
+        assert_eq!(exit_code(&Status::not_found("")), exitcode::DATAERR);
+        assert_eq!(exit_code(&Status::permission_denied("")), exitcode::NOPERM);
+        assert_eq!(exit_code(&Status::internal("")), exitcode::SOFTWARE);
+    }
+}
@@ -0,0 +1,74 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0
+
+use crate::log;
+
+use exitcode::ExitCode;
+use structopt::clap::{self, crate_version, ErrorKind};
+use structopt::StructOpt;
+
+mod backup;
+mod client;
+mod peek;
+mod topics;
+mod topology;
+
+const RIFTCTL: &str = "riftctl";
+
+/// The set of commands riftctl exposes.
+#[derive(Debug, Clone, StructOpt)]
+enum Command {
+    #[structopt(about = "Display the pending messages of a subscription without leasing them.")]
+    Peek(peek::Peek),
+    #[structopt(about = "Display the cluster members and which members own which topics.")]
+    Topology(topology::GetTopology),
+    #[structopt(about = "List the topics of a namespace.")]
+    Topics(topics::ListTopics),
+    #[structopt(about = "Back up every topic, subscription and unacked message.")]
+    Backup(backup::Backup),
+    #[structopt(about = "Restore a backup, seeding a new instance.")]
+    Restore(backup::Restore),
+}
+
+/// Overall riftd binary configuration.
+#[derive(Debug, Clone, StructOpt)]
+#[structopt(
+    global_settings = &[clap::AppSettings::DeriveDisplayOrder],
+    author = "Christian Saide <me@csaide.dev>",
+    about = "Manage a riftd instance or cluster."
+)]
+struct RiftctlConfig {
+    #[structopt(flatten)]
+    log_config: log::Config,
+    #[structopt(flatten)]
+    client_config: client::Config,
+    #[structopt(subcommand)]
+    cmd: Command,
+}
+
+/// Execute riftctl.
+pub async fn run() -> ExitCode {
+    let setup_logger = log::default(RIFTCTL, crate_version!());
+    let cfg = match RiftctlConfig::from_args_safe() {
+        Ok(cfg) => cfg,
+        Err(err)
+            if err.kind == ErrorKind::HelpDisplayed || err.kind == ErrorKind::VersionDisplayed =>
+        {
+            println!("{}", err.message);
+            return exitcode::USAGE;
+        }
+        Err(err) => {
+            crit!(setup_logger, "Failed to parse provided configuration."; "error" => err.to_string());
+            return exitcode::CONFIG;
+        }
+    };
+
+    let root_logger = log::new(&cfg.log_config, RIFTCTL, crate_version!());
+    match cfg.cmd {
+        Command::Peek(peek) => peek.run(&root_logger, &cfg.client_config).await,
+        Command::Topology(topology) => topology.run(&root_logger, &cfg.client_config).await,
+        Command::Topics(topics) => topics.run(&root_logger, &cfg.client_config).await,
+        Command::Backup(backup) => backup.run(&root_logger, &cfg.client_config).await,
+        Command::Restore(restore) => restore.run(&root_logger, &cfg.client_config).await,
+    }
+}
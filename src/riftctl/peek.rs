@@ -0,0 +1,151 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0
+
+use std::convert::TryFrom;
+use std::time::SystemTime;
+
+use exitcode::ExitCode;
+use structopt::StructOpt;
+
+use crate::grpc::pubsub::{PeekRequest, PeekedMessage, PubSubServiceClient};
+
+use super::client;
+
+/// Display the pending messages of a subscription without leasing them.
+#[derive(Debug, Clone, StructOpt)]
+pub struct Peek {
+    #[structopt(help = "The topic the subscription belongs to.")]
+    topic: String,
+    #[structopt(help = "The subscription to peek at.")]
+    subscription: String,
+    #[structopt(
+        long = "max",
+        short = "n",
+        help = "The maximum number of pending messages to display.",
+        long_help = "Sets the maximum number of pending messages to display, a value of zero displays every pending message.",
+        default_value = "10",
+        takes_value = true
+    )]
+    max: u64,
+}
+
+impl Peek {
+    /// Execute the peek command using the supplied client configuration.
+    pub async fn run(self, logger: &slog::Logger, cfg: &client::Config) -> ExitCode {
+        let channel = match cfg.channel() {
+            Ok(channel) => channel,
+            Err(err) => {
+                crit!(logger, "Invalid gRPC endpoint supplied."; "endpoint" => &cfg.endpoint, "error" => err.to_string());
+                return exitcode::CONFIG;
+            }
+        };
+        let client = PubSubServiceClient::new(channel);
+
+        let req = PeekRequest {
+            topic: cfg.qualify(&self.topic),
+            subscription: self.subscription,
+            max: self.max,
+        };
+        let res = cfg
+            .call(logger, || {
+                let mut client = client.clone();
+                let req = req.clone();
+                async move { client.peek(req).await }
+            })
+            .await;
+        let mut stream = match res {
+            Ok(res) => res.into_inner(),
+            Err(status) => {
+                return client::report(logger, "Failed to peek at subscription.", &status)
+            }
+        };
+
+        println!(
+            "{:<8} {:<8} {:<10} {:<12} ATTRIBUTES",
+            "INDEX", "ATTEMPTS", "SIZE", "AGE"
+        );
+        loop {
+            match stream.message().await {
+                Ok(Some(peeked)) => println!("{}", format_peeked(&peeked, SystemTime::now())),
+                Ok(None) => return exitcode::OK,
+                Err(status) => {
+                    return client::report(logger, "Failed to read peeked messages.", &status)
+                }
+            }
+        }
+    }
+}
+
+fn format_peeked(peeked: &PeekedMessage, now: SystemTime) -> String {
+    let (size, age, attributes) = match &peeked.message {
+        Some(msg) => {
+            let age = msg
+                .published
+                .clone()
+                .and_then(|published| SystemTime::try_from(published).ok())
+                .and_then(|published| now.duration_since(published).ok())
+                .map(|age| format!("{:.3}s", age.as_secs_f64()))
+                .unwrap_or_else(|| String::from("-"));
+
+            let mut attributes = msg
+                .attributes
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect::<Vec<String>>();
+            attributes.sort();
+            (msg.data.len(), age, attributes.join(","))
+        }
+        None => (0, String::from("-"), String::new()),
+    };
+
+    format!(
+        "{:<8} {:<8} {:<10} {:<12} {}",
+        peeked.index, peeked.attempts, size, age, attributes
+    )
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use prost_types::Timestamp;
+
+    use super::*;
+    use crate::grpc::pubsub::Message;
+
+    #[test]
+    fn test_format_peeked() {
+        let now = SystemTime::now();
+        let mut attributes = HashMap::new();
+        attributes.insert(String::from("b"), String::from("2"));
+        attributes.insert(String::from("a"), String::from("1"));
+
+        let peeked = PeekedMessage {
+            index: 3,
+            attempts: 2,
+            message: Some(Message {
+                topic: String::from("topic"),
+                attributes,
+                published: Some(Timestamp::from(now - Duration::from_millis(1500))),
+                data: vec![0x01, 0x02].into(),
+                checksum: None,
+                content_type: String::new(),
+            }),
+        };
+        let actual = format_peeked(&peeked, now);
+        assert_eq!(
+            actual,
+            format!("{:<8} {:<8} {:<10} {:<12} a=1,b=2", 3, 2, 2, "1.500s")
+        );
+
+        let peeked = PeekedMessage {
+            index: 0,
+            attempts: 0,
+            message: None,
+        };
+        let actual = format_peeked(&peeked, now);
+        assert_eq!(actual, format!("{:<8} {:<8} {:<10} {:<12} ", 0, 0, 0, "-"));
+    }
+}
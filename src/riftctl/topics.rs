@@ -0,0 +1,79 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0
+
+use exitcode::ExitCode;
+use structopt::StructOpt;
+
+use crate::grpc::topic::{ListRequest, Topic, TopicServiceClient};
+
+use super::client;
+
+/// List the topics of the configured namespace.
+#[derive(Debug, Clone, StructOpt)]
+pub struct ListTopics {}
+
+impl ListTopics {
+    /// Execute the topics command using the supplied client configuration.
+    pub async fn run(self, logger: &slog::Logger, cfg: &client::Config) -> ExitCode {
+        let channel = match cfg.channel() {
+            Ok(channel) => channel,
+            Err(err) => {
+                crit!(logger, "Invalid gRPC endpoint supplied."; "endpoint" => &cfg.endpoint, "error" => err.to_string());
+                return exitcode::CONFIG;
+            }
+        };
+        let client = TopicServiceClient::new(channel);
+
+        let req = ListRequest {
+            namespace: cfg.namespace.clone(),
+        };
+        let res = cfg
+            .call(logger, || {
+                let mut client = client.clone();
+                let req = req.clone();
+                async move { client.list(req).await }
+            })
+            .await;
+        let mut stream = match res {
+            Ok(res) => res.into_inner(),
+            Err(status) => return client::report(logger, "Failed to list topics.", &status),
+        };
+
+        println!("{:<32} {:<9} ROUTES", "TOPIC", "ENCRYPTED");
+        loop {
+            match stream.message().await {
+                Ok(Some(topic)) => println!("{}", format_topic(&topic)),
+                Ok(None) => return exitcode::OK,
+                Err(status) => return client::report(logger, "Failed to read topics.", &status),
+            }
+        }
+    }
+}
+
+fn format_topic(topic: &Topic) -> String {
+    format!(
+        "{:<32} {:<9} {}",
+        topic.name,
+        topic.encrypted,
+        topic.routes.len()
+    )
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_topic() {
+        let topic = Topic {
+            name: String::from("billing/orders"),
+            encrypted: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_topic(&topic),
+            format!("{:<32} {:<9} {}", "billing/orders", true, 0)
+        );
+    }
+}
@@ -0,0 +1,110 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0
+
+use exitcode::ExitCode;
+use structopt::StructOpt;
+
+use crate::grpc::cluster::{ClusterServiceClient, GetTopologyRequest, MemberState, Topology};
+
+use super::client;
+
+/// Display the cluster members and the members owning the supplied topics.
+#[derive(Debug, Clone, StructOpt)]
+pub struct GetTopology {
+    #[structopt(help = "The topics to display the owners of.")]
+    topics: Vec<String>,
+}
+
+impl GetTopology {
+    /// Execute the topology command using the supplied client configuration.
+    pub async fn run(self, logger: &slog::Logger, cfg: &client::Config) -> ExitCode {
+        let channel = match cfg.channel() {
+            Ok(channel) => channel,
+            Err(err) => {
+                crit!(logger, "Invalid gRPC endpoint supplied."; "endpoint" => &cfg.endpoint, "error" => err.to_string());
+                return exitcode::CONFIG;
+            }
+        };
+        let client = ClusterServiceClient::new(channel);
+
+        let req = GetTopologyRequest {
+            topics: self.topics.iter().map(|topic| cfg.qualify(topic)).collect(),
+        };
+        let res = cfg
+            .call(logger, || {
+                let mut client = client.clone();
+                let req = req.clone();
+                async move { client.get_topology(req).await }
+            })
+            .await;
+        match res {
+            Ok(res) => {
+                print!("{}", format_topology(&res.into_inner()));
+                exitcode::OK
+            }
+            Err(status) => client::report(logger, "Failed to get cluster topology.", &status),
+        }
+    }
+}
+
+fn format_topology(topology: &Topology) -> String {
+    let mut out = format!(
+        "VERSION {} VNODES {}\n\n{:<24} {:<24} STATE\n",
+        topology.version, topology.vnodes, "MEMBER", "ADDR"
+    );
+    for member in &topology.members {
+        let state = match MemberState::from_i32(member.state) {
+            Some(MemberState::Alive) => "alive",
+            Some(MemberState::Suspect) => "suspect",
+            Some(MemberState::Dead) => "dead",
+            None => "unknown",
+        };
+        out.push_str(&format!(
+            "{:<24} {:<24} {}\n",
+            member.id, member.addr, state
+        ));
+    }
+    if !topology.topics.is_empty() {
+        out.push_str(&format!("\n{:<24} {:<24} ADDR\n", "TOPIC", "OWNER"));
+        for topic in &topology.topics {
+            let (id, addr) = match &topic.owner {
+                Some(owner) => (owner.id.as_str(), owner.addr.as_str()),
+                None => ("-", "-"),
+            };
+            out.push_str(&format!("{:<24} {:<24} {}\n", topic.topic, id, addr));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+    use crate::grpc::cluster::{Member, TopicOwner};
+
+    #[test]
+    fn test_format_topology() {
+        let member = Member {
+            id: String::from("one"),
+            addr: String::from("10.0.0.1:8081"),
+            heartbeat: 3,
+            state: MemberState::Alive as i32,
+            incarnation: 1,
+        };
+        let topology = Topology {
+            version: 2,
+            vnodes: 64,
+            members: vec![member.clone()],
+            topics: vec![TopicOwner {
+                topic: String::from("woot"),
+                owner: Some(member),
+            }],
+        };
+        let expected = format!(
+            "VERSION 2 VNODES 64\n\n{:<24} {:<24} STATE\n{:<24} {:<24} alive\n\n{:<24} {:<24} ADDR\n{:<24} {:<24} 10.0.0.1:8081\n",
+            "MEMBER", "ADDR", "one", "10.0.0.1:8081", "TOPIC", "OWNER", "woot", "one"
+        );
+        assert_eq!(format_topology(&topology), expected);
+    }
+}
@@ -3,13 +3,34 @@
 
 use std::net::SocketAddr;
 
+use crate::audit;
+use crate::auth;
+use crate::claimcheck;
+use crate::cluster;
+use crate::encryption;
+use crate::events;
+use crate::grpc::backup;
+use crate::grpc::cluster as cluster_grpc;
+use crate::grpc::layer::{AuthLayer, MetricsLayer, PanicLayer, TraceLayer};
+use crate::grpc::limit;
 use crate::grpc::pubsub;
 use crate::grpc::subscription;
+use crate::grpc::tenant as tenant_grpc;
 use crate::grpc::topic;
 use crate::http;
 use crate::log;
+use crate::manifest;
 use crate::metric;
-use crate::pubsub::Registry;
+use crate::pidfile;
+use crate::pubsub::{Config as PubsubConfig, Limits, Metrics as PubsubMetrics, Registry};
+use crate::push;
+use crate::reload;
+use crate::runtime;
+use crate::schema;
+use crate::shutdown;
+use crate::source;
+use crate::tenant;
+use crate::trace;
 
 use exitcode::ExitCode;
 use structopt::clap::{self, crate_version, ErrorKind};
@@ -17,6 +38,11 @@ use structopt::StructOpt;
 use tonic::transport::Server;
 
 const RIFTD: &str = "riftd";
+const PUBSUB_SAMPLE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+const CHUNK_PRUNE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+const LEASE_EXPIRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+#[cfg(tokio_unstable)]
+const RUNTIME_SAMPLE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
 
 /// Overall riftd binary configuration.
 #[derive(Debug, Clone, StructOpt)]
@@ -29,6 +55,42 @@ const RIFTD: &str = "riftd";
 struct RiftdConfig {
     #[structopt(flatten)]
     log_config: log::Config,
+    #[structopt(flatten)]
+    trace_config: trace::Config,
+    #[structopt(flatten)]
+    audit_config: audit::Config,
+    #[structopt(flatten)]
+    auth_config: auth::Config,
+    #[structopt(flatten)]
+    events_config: events::Config,
+    #[structopt(flatten)]
+    metric_config: metric::Config,
+    #[structopt(flatten)]
+    cluster_config: cluster::Config,
+    #[structopt(flatten)]
+    limit_config: limit::Config,
+    #[structopt(flatten)]
+    pubsub_config: PubsubConfig,
+    #[structopt(flatten)]
+    tenant_config: tenant::Config,
+    #[structopt(flatten)]
+    push_config: push::Config,
+    #[structopt(flatten)]
+    claim_check_config: claimcheck::Config,
+    #[structopt(flatten)]
+    encryption_config: encryption::Config,
+    #[structopt(flatten)]
+    source_config: source::Config,
+    #[structopt(flatten)]
+    manifest_config: manifest::Config,
+    #[structopt(flatten)]
+    reload_config: reload::Config,
+    #[structopt(flatten)]
+    shutdown_config: shutdown::Config,
+    #[structopt(flatten)]
+    pid_config: pidfile::Config,
+    #[structopt(flatten)]
+    runtime_config: runtime::Config,
     #[structopt(
         long = "grpc-addr",
         short = "g",
@@ -49,10 +111,49 @@ struct RiftdConfig {
         takes_value = true
     )]
     http_addr: SocketAddr,
+    #[structopt(
+        long = "disable-pubsub",
+        env = "RIFT_DISABLE_PUBSUB",
+        help = "Do not serve the topic, subscription, pubsub, tenant and backup gRPC services.",
+        long_help = "Disables the topic, subscription, pubsub, tenant and backup gRPC services, so that riftd only serves the remaining services. Topologies loaded from a manifest and tailed sources are still maintained. Refused alongside cluster peers, as every member of a cluster owns a share of its topics.",
+        takes_value = false
+    )]
+    disable_pubsub: bool,
+    #[structopt(
+        long = "disable-cluster",
+        env = "RIFT_DISABLE_CLUSTER",
+        help = "Do not serve the cluster topology gRPC service.",
+        long_help = "Disables the gRPC service exposing the cluster topology to cluster-aware clients. Membership is still tracked when cluster peers are configured.",
+        takes_value = false
+    )]
+    disable_cluster: bool,
+    #[structopt(
+        long = "disable-reflection",
+        env = "RIFT_DISABLE_REFLECTION",
+        help = "Do not serve the gRPC reflection service.",
+        long_help = "Disables the gRPC reflection service, so that clients must already know the schema of the served services. When enabled it only describes the services being served.",
+        takes_value = false
+    )]
+    disable_reflection: bool,
+}
+
+/// Create the tracing span wrapping each gRPC request, which any logs emitted while handling
+/// the request are nested within when logs are forwarded through the tracing ecosystem.
+fn grpc_span(req: &tonic::codegen::http::Request<()>) -> tracing::Span {
+    let req_id = req
+        .headers()
+        .get("x-request-id")
+        .and_then(|req_id| req_id.to_str().ok());
+    tracing::info_span!(
+        target: log::TARGET,
+        "grpc",
+        method = req.uri().path(),
+        reqID = req_id,
+    )
 }
 
-/// Execute riftd.
-pub async fn run() -> ExitCode {
+/// Execute riftd, on an async runtime sized as configured.
+pub fn run() -> ExitCode {
     let setup_logger = log::default(RIFTD, crate_version!());
     let cfg = match RiftdConfig::from_args_safe() {
         Ok(cfg) => cfg,
@@ -68,7 +169,62 @@ pub async fn run() -> ExitCode {
         }
     };
 
-    let root_logger = log::new(&cfg.log_config, RIFTD, crate_version!());
+    let runtime = match runtime::build(&cfg.runtime_config) {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            crit!(setup_logger, "Failed to build the async runtime."; "error" => err.to_string());
+            return exitcode::OSERR;
+        }
+    };
+    runtime.block_on(serve(cfg, setup_logger))
+}
+
+async fn serve(cfg: RiftdConfig, setup_logger: slog::Logger) -> ExitCode {
+    if cfg.log_config.tracing {
+        if let Err(err) = log::init_tracing(&cfg.log_config) {
+            crit!(setup_logger, "Failed to initialize tracing subscriber."; "error" => err.to_string());
+            return exitcode::CONFIG;
+        }
+    }
+    let (root_logger, log_level) = log::reloadable(&cfg.log_config, RIFTD, crate_version!());
+    // SIGHUP reloads settings instead when a reload file is supplied.
+    if cfg.reload_config.reload_file.is_none() {
+        tokio::spawn(log::watch_sighup(
+            root_logger.new(o!("mod" => "log")),
+            log_level.clone(),
+            cfg.log_config.level.clone(),
+        ));
+    }
+
+    // Held until riftd exits, removing the pid file on the way out.
+    let _pid_file = match &cfg.pid_config.pid_file {
+        Some(path) => match pidfile::PidFile::acquire(path) {
+            Ok(pid_file) => Some(pid_file),
+            Err(err) => {
+                crit!(&root_logger, "Failed to acquire the pid file."; "error" => err.to_string());
+                return exitcode::CANTCREAT;
+            }
+        },
+        None => None,
+    };
+
+    let shutdown = shutdown::Shutdown::new();
+    tokio::spawn(
+        shutdown
+            .clone()
+            .watch_signals(root_logger.new(o!("mod" => "shutdown"))),
+    );
+
+    match trace::init(&cfg.trace_config, RIFTD, crate_version!()) {
+        Ok(true) => {
+            info!(&root_logger, "Exporting traces."; "endpoint" => cfg.trace_config.endpoint.as_ref())
+        }
+        Ok(false) => {}
+        Err(err) => {
+            crit!(&root_logger, "Failed to initialize tracing."; "error" => err.to_string());
+            return exitcode::CONFIG;
+        }
+    }
 
     let mm = metric::Manager::new(
         "riftd".to_string(),
@@ -76,68 +232,481 @@ pub async fn run() -> ExitCode {
         crate_version!().to_string(),
     );
 
-    let registry = Registry::default();
-    let pubsub_impl = pubsub::Handler::with_registry(registry.clone());
-    let topic_impl = topic::Handler::with_registry(registry.clone());
-    let sub_impl = subscription::Handler::with_registry(registry.clone());
+    if let Err(err) = mm.register_build_info() {
+        crit!(&root_logger, "Failed to register build info metric."; "error" => err.to_string());
+        return exitcode::SOFTWARE;
+    }
+
+    #[cfg(target_os = "linux")]
+    if let Err(err) = mm.register_process_collector() {
+        crit!(&root_logger, "Failed to register process metrics."; "error" => err.to_string());
+        return exitcode::SOFTWARE;
+    }
+
+    match metric::Pusher::new(&cfg.metric_config, RIFTD) {
+        Ok(Some(pusher)) => {
+            info!(&root_logger, "Pushing metrics."; "endpoint" => cfg.metric_config.url.as_ref());
+            tokio::spawn(pusher.run(root_logger.new(o!("mod" => "metric"))));
+        }
+        Ok(None) => {}
+        Err(err) => {
+            crit!(&root_logger, "Failed to initialize metric pushing."; "error" => err.to_string());
+            return exitcode::CONFIG;
+        }
+    }
+
+    #[cfg(tokio_unstable)]
+    {
+        let runtime_mm = metric::Manager::new(
+            "riftd".to_string(),
+            "runtime".to_string(),
+            crate_version!().to_string(),
+        );
+        match metric::RuntimeSampler::new(&runtime_mm) {
+            Ok(sampler) => {
+                let handle = tokio::runtime::Handle::current();
+                tokio::spawn(sampler.run(handle, RUNTIME_SAMPLE_INTERVAL));
+            }
+            Err(err) => {
+                crit!(&root_logger, "Failed to register runtime metrics."; "error" => err.to_string());
+                return exitcode::SOFTWARE;
+            }
+        }
+    }
+
+    let metrics_layer =
+        match MetricsLayer::new(&mm, cfg.metric_config.response_time_buckets.as_slice()) {
+            Ok(layer) => layer,
+            Err(err) => {
+                crit!(&root_logger, "Failed to register gRPC metrics."; "error" => err.to_string());
+                return exitcode::SOFTWARE;
+            }
+        };
+
+    let pubsub_mm = metric::Manager::new(
+        "riftd".to_string(),
+        "pubsub".to_string(),
+        crate_version!().to_string(),
+    );
+    let registry = match PubsubMetrics::new(
+        &pubsub_mm,
+        cfg.metric_config.latency_buckets.as_slice(),
+    ) {
+        Ok(metrics) => Registry::with_metrics(metrics)
+            .with_queue_shards(cfg.pubsub_config.queue_shards)
+            .with_queue_capacity(
+                cfg.pubsub_config.queue_message_capacity,
+                cfg.pubsub_config.queue_waker_capacity,
+            )
+            .with_fanout_chunk(cfg.pubsub_config.fanout_chunk_size)
+            .with_limits(Limits::from(&cfg.pubsub_config), |msg: &pubsub::Message| {
+                let attributes = msg.attributes.iter().map(|(k, v)| k.len() + v.len());
+                msg.data.len() + attributes.sum::<usize>()
+            }),
+        Err(err) => {
+            crit!(&root_logger, "Failed to register pubsub metrics."; "error" => err.to_string());
+            return exitcode::SOFTWARE;
+        }
+    };
+    tokio::spawn(registry.clone().run_sampler(PUBSUB_SAMPLE_INTERVAL));
+
+    let tenant_mm = metric::Manager::new(
+        "riftd".to_string(),
+        "tenant".to_string(),
+        crate_version!().to_string(),
+    );
+    let tenants = match tenant::Tenants::new(&cfg.tenant_config) {
+        Ok(tenants) => tenants,
+        Err(err) => {
+            crit!(&root_logger, "Failed to load the tenant quotas."; "error" => err.to_string());
+            return exitcode::CONFIG;
+        }
+    };
+    let tenants = match tenants.with_metrics(&tenant_mm) {
+        Ok(tenants) => tenants,
+        Err(err) => {
+            crit!(&root_logger, "Failed to register tenant metrics."; "error" => err.to_string());
+            return exitcode::SOFTWARE;
+        }
+    };
+    tokio::spawn(
+        tenants
+            .clone()
+            .run_sampler(registry.clone(), PUBSUB_SAMPLE_INTERVAL),
+    );
+
+    let auth = match auth::Auth::new(&cfg.auth_config) {
+        Ok(auth) => auth,
+        Err(err) => {
+            crit!(&root_logger, "Failed to load the auth policy."; "error" => err.to_string());
+            return exitcode::CONFIG;
+        }
+    };
+    if auth.is_enabled() {
+        info!(&root_logger, "Enforcing the auth policy."; "file" => cfg.auth_config.auth_file.as_ref().map(|file| file.display().to_string()));
+    }
+
+    let auditor = match audit::Auditor::new(&cfg.audit_config, &registry) {
+        Ok(auditor) => auditor,
+        Err(err) => {
+            crit!(&root_logger, "Failed to initialize audit logging."; "error" => err.to_string());
+            return exitcode::CONFIG;
+        }
+    };
+    let quarantine_logger = root_logger.new(o!("mod" => "quarantine"));
+    let on_quarantined = move |topic: &str, name: &str, count: usize| {
+        warn!(&quarantine_logger, "Quarantined messages whose leases kept expiring.";
+            "topic" => topic,
+            "subscription" => name,
+            "messages" => count,
+        );
+    };
+    tokio::spawn(registry.clone().run_lease_expiry(
+        cfg.pubsub_config.quarantine_after_expiries,
+        LEASE_EXPIRY_INTERVAL,
+        on_quarantined,
+    ));
+    let identity_metrics = match metric::IdentityMetrics::new(
+        &pubsub_mm,
+        cfg.metric_config.identity_limit,
+    ) {
+        Ok(metrics) => metrics,
+        Err(err) => {
+            crit!(&root_logger, "Failed to register identity metrics."; "error" => err.to_string());
+            return exitcode::SOFTWARE;
+        }
+    };
+
+    let cluster_mm = metric::Manager::new(
+        "riftd".to_string(),
+        "cluster".to_string(),
+        crate_version!().to_string(),
+    );
+    // Every member of a cluster owns a share of its topics, so peers would forward requests
+    // to a member which does not serve them.
+    if cfg.disable_pubsub && !cfg.cluster_config.join.is_empty() {
+        crit!(&root_logger, "Serving pubsub can not be disabled on a cluster member."; "join" => cfg.cluster_config.join.join(","));
+        return exitcode::CONFIG;
+    }
+    let membership = match cluster::Membership::new(&cfg.cluster_config, &cfg.grpc_addr) {
+        Ok(membership) => membership.with_topics(registry.clone()),
+        Err(err) => {
+            crit!(&root_logger, "Failed to parse cluster configuration."; "error" => err.to_string());
+            return exitcode::CONFIG;
+        }
+    };
+    let membership = match membership.with_metrics(&cluster_mm) {
+        Ok(membership) => membership,
+        Err(err) => {
+            crit!(&root_logger, "Failed to register cluster metrics."; "error" => err.to_string());
+            return exitcode::SOFTWARE;
+        }
+    };
+    let cluster_logger = root_logger.new(o!("mod" => "cluster"));
+    tokio::spawn(membership.discovery().run(cluster_logger.clone()));
+    tokio::spawn(membership.clone().run(cluster_logger));
+    let events =
+        events::Emitter::new(&cfg.events_config, &registry).with_membership(membership.clone());
+    if cfg.pubsub_config.subscription_idle_expiry_ms > 0 {
+        let idle = std::time::Duration::from_millis(cfg.pubsub_config.subscription_idle_expiry_ms);
+        let expiry_logger = root_logger.new(o!("mod" => "expiry"));
+        let (auditor, events) = (auditor.clone(), events.clone());
+        let on_expired = move |topic: &str, name: &str| {
+            info!(&expiry_logger, "Deleted idle subscription.";
+                "topic" => topic,
+                "subscription" => name,
+            );
+            let name = format!("{}/{}", topic, name);
+            let event =
+                audit::Event::system(audit::Action::Delete, audit::Resource::Subscription, name);
+            auditor.record(&event, None);
+            events.emit(events::Kind::SubscriptionDeleted, &event.name);
+        };
+        tokio::spawn(
+            registry
+                .clone()
+                .run_expiry(idle, PUBSUB_SAMPLE_INTERVAL, on_expired),
+        );
+    }
+    tokio::spawn(events.clone().watch_membership(membership.clone()));
+
+    let keyring = match encryption::Keyring::new(&cfg.encryption_config) {
+        Ok(keyring) => keyring,
+        Err(err) => {
+            crit!(&root_logger, "Failed to initialize payload encryption."; "error" => err.to_string());
+            return exitcode::CONFIG;
+        }
+    };
+
+    let streams = limit::Limiter::new(cfg.limit_config.max_streams_per_caller);
+    let connections = limit::Limiter::new(cfg.limit_config.max_connections_per_ip);
+    let mut reloader = reload::Reloader::new(log_level.clone())
+        .with_stream_limiter(streams.clone())
+        .with_connection_limiter(connections.clone());
+
+    let schemas = schema::Registry::default();
+    let assembler = pubsub::Assembler::from(&cfg.pubsub_config);
+    tokio::spawn(assembler.clone().run(CHUNK_PRUNE_INTERVAL));
+    let mut pubsub_impl = pubsub::Handler::with_registry(registry.clone())
+        .with_identity_metrics(identity_metrics)
+        .with_tenants(tenants.clone())
+        .with_membership(membership.clone())
+        .with_schemas(schemas.clone())
+        .with_stream_limiter(streams)
+        .with_attribute_limits(pubsub::AttributeLimits::from(&cfg.pubsub_config))
+        .with_assembler(assembler)
+        .with_auto_create_topics(cfg.pubsub_config.auto_create_topics)
+        .with_default_subscription(cfg.pubsub_config.default_subscription_template.clone())
+        .with_dead_letter_topic(cfg.pubsub_config.dead_letter_topic_template.clone())
+        .with_replay_rate(cfg.pubsub_config.replay_messages_per_sec)
+        .with_events(events.clone())
+        .with_shutdown(shutdown.clone());
+    let mut pusher = push::Pusher::new(&cfg.push_config, root_logger.new(o!("mod" => "push")));
+    let claim_check_logger = root_logger.new(o!("mod" => "claimcheck"));
+    match claimcheck::Store::new(&cfg.claim_check_config) {
+        Ok(Some(store)) => {
+            let store = match pubsub_mm.register_int_counter(
+                "corrupted_payloads",
+                "The total count of claim checked payloads which failed checksum verification as they were read.",
+                None,
+            ) {
+                Ok(corrupted) => store.with_corruption_counter(corrupted),
+                Err(err) => {
+                    crit!(&claim_check_logger, "Failed to register claim check metrics."; "error" => err.to_string());
+                    return exitcode::SOFTWARE;
+                }
+            };
+            pubsub_impl = pubsub_impl.with_claim_checks(store.clone());
+            pusher = pusher.with_claim_checks(store.clone());
+            reloader = reloader.with_claim_checks(store.clone());
+            tokio::spawn(store.run(claim_check_logger));
+        }
+        Ok(None) => {}
+        Err(err) => {
+            crit!(&claim_check_logger, "Failed to initialize the claim check store."; "error" => err.to_string());
+            return exitcode::CONFIG;
+        }
+    }
+    let mut topic_impl = topic::Handler::with_registry(registry.clone())
+        .with_auditor(auditor.clone())
+        .with_default_subscription(cfg.pubsub_config.default_subscription_template.clone())
+        .with_events(events.clone())
+        .with_membership(membership.clone())
+        .with_schemas(schemas.clone())
+        .with_shutdown(shutdown.clone());
+    if let Some(keyring) = keyring {
+        pubsub_impl = pubsub_impl.with_keyring(keyring.clone());
+        topic_impl = topic_impl.with_keyring(keyring.clone());
+        pusher = pusher.with_keyring(keyring);
+    }
+    let sub_impl = subscription::Handler::with_registry(registry.clone())
+        .with_auditor(auditor.clone())
+        .with_events(events.clone())
+        .with_membership(membership.clone())
+        .with_pusher(pusher.clone())
+        .with_shutdown(shutdown.clone());
+    let tenant_impl = tenant_grpc::Handler::with_tenants(tenants);
+    let backup_impl = backup::Handler::new(registry.clone(), topic_impl.clone(), sub_impl.clone());
+
+    let manifest_logger = root_logger.new(o!("mod" => "manifest"));
+    let reconciler =
+        manifest::Reconciler::new(registry.clone(), schemas, pusher, cfg.manifest_config.prune)
+            .with_membership(membership.clone());
+    if let Err(err) = reconciler.start(&cfg.manifest_config, manifest_logger.clone()) {
+        crit!(&manifest_logger, "Failed to load the topology manifest."; "error" => err.to_string());
+        return exitcode::CONFIG;
+    }
+
+    let reload_logger = root_logger.new(o!("mod" => "reload"));
+    if let Err(err) = reloader.start(&cfg.reload_config, reload_logger.clone()) {
+        crit!(&reload_logger, "Failed to load the reload file."; "error" => err.to_string());
+        return exitcode::CONFIG;
+    }
+
+    let cluster_impl = cluster_grpc::Handler::with_membership(membership).with_events(events);
+
+    let source_logger = root_logger.new(o!("mod" => "source"));
+    match source::Tailer::new(&cfg.source_config, registry.clone(), source_logger.clone()) {
+        Ok(Some(tailer)) => {
+            info!(&source_logger, "Tailing files."; "tails" => cfg.source_config.tails.join(","));
+            tokio::spawn(tailer.run());
+        }
+        Ok(None) => {}
+        Err(err) => {
+            crit!(&source_logger, "Failed to initialize file tailing."; "error" => err.to_string());
+            return exitcode::CONFIG;
+        }
+    }
 
     let (mut health_reporter, health_service) = tonic_health::server::health_reporter();
     health_reporter
         .set_service_status("", tonic_health::ServingStatus::Serving)
         .await;
-    health_reporter
-        .set_service_status("pubsub", tonic_health::ServingStatus::Serving)
-        .await;
+    if !cfg.disable_pubsub {
+        health_reporter
+            .set_service_status("pubsub", tonic_health::ServingStatus::Serving)
+            .await;
+    }
+    let draining = shutdown.wait();
+    let disable_pubsub = cfg.disable_pubsub;
+    tokio::spawn(async move {
+        draining.await;
+        health_reporter
+            .set_service_status("", tonic_health::ServingStatus::NotServing)
+            .await;
+        if !disable_pubsub {
+            health_reporter
+                .set_service_status("pubsub", tonic_health::ServingStatus::NotServing)
+                .await;
+        }
+    });
 
     let grpc_logger = root_logger.new(o!("mod" => "grpc"));
+    let panic_layer = match PanicLayer::new(&grpc_logger, &mm) {
+        Ok(layer) => layer,
+        Err(err) => {
+            crit!(&grpc_logger, "Failed to register panic metrics."; "error" => err.to_string());
+            return exitcode::SOFTWARE;
+        }
+    };
+    let grpc_auth_layer = AuthLayer::new(auth.clone());
+    let grpc_shutdown = shutdown.clone();
     let grpc_handle = async move {
-        let reflection = tonic_reflection::server::Builder::configure()
-            .register_encoded_file_descriptor_set(topic::FILE_DESCRIPTOR_SET)
-            .register_encoded_file_descriptor_set(pubsub::FILE_DESCRIPTOR_SET)
-            .register_encoded_file_descriptor_set(subscription::FILE_DESCRIPTOR_SET)
+        let mut reflection = tonic_reflection::server::Builder::configure()
             .register_encoded_file_descriptor_set(
                 tonic_health::proto::GRPC_HEALTH_V1_FILE_DESCRIPTOR_SET,
-            )
-            .build()
-            .unwrap();
+            );
+        if !cfg.disable_pubsub {
+            reflection = reflection
+                .register_encoded_file_descriptor_set(topic::FILE_DESCRIPTOR_SET)
+                .register_encoded_file_descriptor_set(pubsub::FILE_DESCRIPTOR_SET)
+                .register_encoded_file_descriptor_set(subscription::FILE_DESCRIPTOR_SET)
+                .register_encoded_file_descriptor_set(tenant_grpc::FILE_DESCRIPTOR_SET)
+                .register_encoded_file_descriptor_set(backup::FILE_DESCRIPTOR_SET);
+        }
+        if !cfg.disable_cluster {
+            reflection =
+                reflection.register_encoded_file_descriptor_set(cluster_grpc::FILE_DESCRIPTOR_SET);
+        }
+        let reflection = (!cfg.disable_reflection).then(|| reflection.build().unwrap());
         let interceptor = crate::grpc::interceptor::RiftInterceptor::new(&grpc_logger, mm);
+        let pubsub_enabled = !cfg.disable_pubsub;
+        let topic_service = pubsub_enabled
+            .then(|| topic::TopicServiceServer::with_interceptor(topic_impl, interceptor.clone()));
+        let pubsub_service = pubsub_enabled.then(|| {
+            pubsub::PubSubServiceServer::with_interceptor(pubsub_impl, interceptor.clone())
+        });
+        let sub_service = pubsub_enabled.then(|| {
+            subscription::SubscriptionServiceServer::with_interceptor(sub_impl, interceptor.clone())
+        });
+        let tenant_service = pubsub_enabled.then(|| {
+            tenant_grpc::TenantServiceServer::with_interceptor(tenant_impl, interceptor.clone())
+        });
+        let backup_service = pubsub_enabled.then(|| {
+            backup::BackupServiceServer::with_interceptor(backup_impl, interceptor.clone())
+        });
+        let cluster_service = (!cfg.disable_cluster).then(|| {
+            cluster_grpc::ClusterServiceServer::with_interceptor(cluster_impl, interceptor.clone())
+        });
+
+        let listener = match tokio::net::TcpListener::bind(cfg.grpc_addr).await {
+            Ok(listener) => listener,
+            Err(err) => {
+                crit!(&grpc_logger, "Failed to listen for gRPC requests."; "error" => err.to_string());
+                return false;
+            }
+        };
+        let incoming = limit::incoming(listener, connections, grpc_logger.clone());
 
         info!(&grpc_logger, "Listening for gRPC requests."; "addr" => cfg.grpc_addr.to_string());
         if let Err(err) = Server::builder()
-            .add_service(topic::TopicServiceServer::with_interceptor(
-                topic_impl,
-                interceptor.clone(),
-            ))
-            .add_service(pubsub::PubSubServiceServer::with_interceptor(
-                pubsub_impl,
-                interceptor.clone(),
-            ))
-            .add_service(subscription::SubscriptionServiceServer::with_interceptor(
-                sub_impl,
-                interceptor.clone(),
-            ))
-            .add_service(reflection)
+            .trace_fn(grpc_span)
+            .layer(TraceLayer)
+            .layer(metrics_layer)
+            .layer(panic_layer)
+            .layer(grpc_auth_layer)
+            .add_optional_service(topic_service)
+            .add_optional_service(pubsub_service)
+            .add_optional_service(sub_service)
+            .add_optional_service(tenant_service)
+            .add_optional_service(backup_service)
+            .add_optional_service(cluster_service)
+            .add_optional_service(reflection)
             .add_service(health_service)
-            .serve(cfg.grpc_addr)
+            .serve_with_incoming_shutdown(incoming, grpc_shutdown.wait())
             .await
         {
             crit!(&grpc_logger, "Failed to listen and serve gRPC."; "error" => err.to_string());
+            return false;
         }
+        info!(&grpc_logger, "Drained in-flight gRPC requests.");
+        true
     };
 
     let http_logger = root_logger.new(o!("mod" => "http"));
+    let http_shutdown = shutdown.clone();
+    let http_registry = registry.clone();
     let http_handle = async move {
         info!(&http_logger, "Listening for HTTP requests."; "addr" => cfg.http_addr.to_string());
-        if let Err(err) = http::listen(&cfg.http_addr).await {
+        if let Err(err) = http::listen(
+            &cfg.http_addr,
+            http_logger.clone(),
+            log_level,
+            http_shutdown,
+            http_registry,
+            auth,
+        )
+        .await
+        {
             crit!(&http_logger, "Failed to listen and serve HTTP."; "error" => err.to_string());
         }
     };
 
-    info!(&root_logger, "Fully initialized and listening!");
-    tokio::select! {
-        _ = grpc_handle => {},
-        _ = http_handle => {},
+    // Either listener failing shuts the other down too. Once shutdown begins new gRPC
+    // connections are refused, and riftd exits as soon as in-flight requests finish and
+    // subscribe streams end, or once the grace period elapses. HTTP keeps serving throughout
+    // so that probes observe the node withdrawing its readiness.
+    let grace = std::time::Duration::from_millis(cfg.shutdown_config.grace_ms);
+    let forced = {
+        let draining = shutdown.wait();
+        async move {
+            draining.await;
+            tokio::time::sleep(grace).await;
+        }
     };
+    tokio::pin!(grpc_handle, http_handle, forced);
+
+    info!(&root_logger, "Fully initialized and listening!");
+    let mut code = exitcode::OK;
+    let mut http_done = false;
+    loop {
+        tokio::select! {
+            served = &mut grpc_handle => {
+                if !served {
+                    code = exitcode::IOERR;
+                }
+                break;
+            }
+            _ = &mut http_handle, if !http_done => {
+                http_done = true;
+                code = exitcode::IOERR;
+                shutdown.trigger();
+            }
+            _ = &mut forced => {
+                warn!(&root_logger, "Timed out draining in-flight requests, exiting anyway."; "grace_ms" => cfg.shutdown_config.grace_ms);
+                code = exitcode::TEMPFAIL;
+                break;
+            }
+        }
+    }
 
-    exitcode::IOERR
+    // Subscribe streams release their own leases as they end, while leases held by anything
+    // else, such as pushers or clients which never acked, are released here, so that their
+    // messages are not left waiting for the leases to expire.
+    let released = registry.release_leases();
+    info!(&root_logger, "Released outstanding leases."; "leases" => released);
+    auditor.flush();
+    info!(&root_logger, "Shut down."; "code" => code);
+    trace::shutdown();
+    code
 }
@@ -0,0 +1,40 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::io;
+
+// crate usings
+use super::Config;
+
+// extern usings
+use tokio::runtime::{Builder, Runtime};
+
+/// Build the multi-threaded runtime riftd runs on, as sized by the supplied configuration.
+pub fn build(cfg: &Config) -> io::Result<Runtime> {
+    let mut builder = Builder::new_multi_thread();
+    builder.enable_all().thread_name("riftd-worker");
+    if cfg.worker_threads > 0 {
+        builder.worker_threads(cfg.worker_threads);
+    }
+    if cfg.max_blocking_threads > 0 {
+        builder.max_blocking_threads(cfg.max_blocking_threads);
+    }
+    builder.build()
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build() {
+        let cfg = Config {
+            worker_threads: 2,
+            max_blocking_threads: 0,
+        };
+        let runtime = build(&cfg).unwrap();
+        assert_eq!(runtime.block_on(async { 1 + 1 }), 2);
+    }
+}
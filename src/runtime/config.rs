@@ -0,0 +1,31 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// extern usings
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, StructOpt)]
+/// Rift async runtime configuration.
+pub struct Config {
+    #[structopt(
+        long = "worker-threads",
+        env = "RIFT_WORKER_THREADS",
+        help = "The count of threads driving riftd's async tasks.",
+        long_help = "Sets the count of worker threads the async runtime drives requests, subscribe streams and background tasks on, pinning riftd's CPU usage on shared hosts. Zero starts one worker thread per CPU core.",
+        default_value = "0",
+        takes_value = true
+    )]
+    /// Define the count of runtime worker threads, where zero is one per CPU core.
+    pub worker_threads: usize,
+
+    #[structopt(
+        long = "max-blocking-threads",
+        env = "RIFT_MAX_BLOCKING_THREADS",
+        help = "The maximum count of threads running blocking work such as file IO.",
+        long_help = "Sets the maximum count of threads the async runtime spawns for blocking work, such as claim check and key file IO, on top of its worker threads. Blocking work queues once every thread is busy. Zero uses the runtime default of 512.",
+        default_value = "512",
+        takes_value = true
+    )]
+    /// Define the maximum count of blocking threads, where zero is the runtime default.
+    pub max_blocking_threads: usize,
+}
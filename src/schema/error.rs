@@ -0,0 +1,48 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::result;
+
+// extern usings
+use thiserror::Error;
+
+/// Custom Result wrapper to simplify usage.
+pub type Result<T> = result::Result<T, Error>;
+
+/// Represents invalid schemas, and message data which does not conform to its schema.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// Handles schema definitions which can not be used to validate messages.
+    #[error("the supplied schema is invalid: {reason}")]
+    Invalid {
+        /// The reason the schema is invalid.
+        reason: String,
+    },
+    /// Handles message data which does not conform to its topic's schema.
+    #[error("the message does not conform to the topic schema at '{path}': {reason}")]
+    Violation {
+        /// The path to the offending field, with the root message being an empty path.
+        path: String,
+        /// The reason the field does not conform to the schema.
+        reason: String,
+    },
+    /// Handles message data which can not be transcoded between content types.
+    #[error("message data can not be transcoded from '{from}' to '{to}'")]
+    Unsupported {
+        /// The content type of the data.
+        from: String,
+        /// The content type the data was to be transcoded to.
+        to: String,
+    },
+}
+
+impl Error {
+    /// Create a violation at the supplied path.
+    pub(super) fn violation(path: &str, reason: impl Into<String>) -> Self {
+        Error::Violation {
+            path: path.to_owned(),
+            reason: reason.into(),
+        }
+    }
+}
@@ -0,0 +1,400 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::sync::Arc;
+
+// crate usings
+use super::{Error, Result};
+
+// extern usings
+use serde_json::{Map, Value};
+
+/// The keywords which carry no validation semantics, and are accepted but ignored.
+const ANNOTATIONS: &[&str] = &[
+    "$schema",
+    "$id",
+    "$comment",
+    "title",
+    "description",
+    "default",
+    "examples",
+    "deprecated",
+    "readOnly",
+    "writeOnly",
+];
+
+/// The validation keywords supported by [JsonSchema].
+const KEYWORDS: &[&str] = &[
+    "type",
+    "enum",
+    "const",
+    "properties",
+    "required",
+    "additionalProperties",
+    "items",
+    "minItems",
+    "maxItems",
+    "minLength",
+    "maxLength",
+    "minimum",
+    "maximum",
+    "exclusiveMinimum",
+    "exclusiveMaximum",
+    "allOf",
+    "anyOf",
+    "oneOf",
+    "not",
+];
+
+/// A schema requiring message data to be a JSON document conforming to a JSON Schema.
+///
+/// Only a subset of the JSON Schema validation keywords is supported, and schemas using any
+/// other keyword are rejected rather than silently ignoring it. Violations are reported with
+/// a JSON pointer to the offending value.
+#[derive(Debug, Clone)]
+pub struct JsonSchema {
+    /// The JSON Schema document, as supplied.
+    pub schema: String,
+    root: Arc<Value>,
+}
+
+impl JsonSchema {
+    /// Create a new schema from the supplied JSON Schema document.
+    pub fn new(schema: String) -> Result<Self> {
+        let root: Value = serde_json::from_str(&schema).map_err(|err| Error::Invalid {
+            reason: format!("failed to parse the JSON schema: {}", err),
+        })?;
+        check(&root, "#")?;
+        Ok(Self {
+            schema,
+            root: Arc::new(root),
+        })
+    }
+
+    /// Validate the supplied data is a JSON document conforming to this schema.
+    pub fn validate(&self, data: &[u8]) -> Result<()> {
+        let value: Value = serde_json::from_slice(data)
+            .map_err(|err| Error::violation("", format!("invalid JSON: {}", err)))?;
+        validate(&self.root, &value, "")
+    }
+}
+
+impl PartialEq for JsonSchema {
+    /// Schemas are equal if their documents are, regardless of formatting.
+    fn eq(&self, other: &Self) -> bool {
+        self.root == other.root
+    }
+}
+
+/// Check the supplied schema only uses supported keywords, recursing into its subschemas.
+fn check(schema: &Value, location: &str) -> Result<()> {
+    let invalid = |reason: String| Error::Invalid {
+        reason: format!("{} at '{}'", reason, location),
+    };
+    let schema = match schema {
+        Value::Bool(_) => return Ok(()),
+        Value::Object(schema) => schema,
+        _ => {
+            return Err(invalid(String::from(
+                "a schema must be an object or boolean",
+            )))
+        }
+    };
+
+    for (keyword, value) in schema {
+        if ANNOTATIONS.contains(&keyword.as_str()) {
+            continue;
+        }
+        if !KEYWORDS.contains(&keyword.as_str()) {
+            return Err(invalid(format!(
+                "the keyword '{}' is not supported",
+                keyword
+            )));
+        }
+        let location = format!("{}/{}", location, keyword);
+        match (keyword.as_str(), value) {
+            ("properties", Value::Object(properties)) => {
+                for (name, property) in properties {
+                    check(property, &format!("{}/{}", location, name))?;
+                }
+            }
+            ("additionalProperties" | "items" | "not", subschema) => check(subschema, &location)?,
+            ("allOf" | "anyOf" | "oneOf", Value::Array(subschemas)) if !subschemas.is_empty() => {
+                for (idx, subschema) in subschemas.iter().enumerate() {
+                    check(subschema, &format!("{}/{}", location, idx))?;
+                }
+            }
+            ("type", Value::String(_) | Value::Array(_)) => {}
+            ("enum" | "required", Value::Array(_)) => {}
+            ("const", _) => {}
+            ("minItems" | "maxItems" | "minLength" | "maxLength", Value::Number(number))
+                if number.is_u64() => {}
+            ("minimum" | "maximum" | "exclusiveMinimum" | "exclusiveMaximum", Value::Number(_)) => {
+            }
+            (keyword, _) => {
+                return Err(invalid(format!(
+                    "the keyword '{}' has an invalid value",
+                    keyword
+                )))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Return whether the supplied value is an instance of the supplied JSON Schema type.
+fn is_type(value: &Value, ty: &str) -> bool {
+    match ty {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => match value {
+            Value::Number(number) => {
+                number.is_i64() || number.is_u64() || number.as_f64().unwrap().fract() == 0.0
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Validate the supplied value, found at the supplied JSON pointer, against the supplied
+/// schema.
+fn validate(schema: &Value, value: &Value, path: &str) -> Result<()> {
+    let schema = match schema {
+        Value::Bool(true) => return Ok(()),
+        Value::Bool(false) => return Err(Error::violation(path, "no value is allowed")),
+        Value::Object(schema) => schema,
+        _ => return Ok(()),
+    };
+
+    if let Some(ty) = schema.get("type") {
+        let types: Vec<&str> = match ty {
+            Value::String(ty) => vec![ty.as_str()],
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !types.iter().any(|ty| is_type(value, ty)) {
+            return Err(Error::violation(
+                path,
+                format!("expected type {}", types.join(" or ")),
+            ));
+        }
+    }
+    if let Some(Value::Array(allowed)) = schema.get("enum") {
+        if !allowed.contains(value) {
+            return Err(Error::violation(
+                path,
+                "value is not one of the allowed values",
+            ));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            return Err(Error::violation(path, format!("expected {}", expected)));
+        }
+    }
+
+    match value {
+        Value::Object(object) => validate_object(schema, object, path)?,
+        Value::Array(items) => {
+            if let Some(min) = bound(schema, "minItems") {
+                if (items.len() as u64) < min {
+                    return Err(Error::violation(
+                        path,
+                        format!("expected at least {} items", min),
+                    ));
+                }
+            }
+            if let Some(max) = bound(schema, "maxItems") {
+                if items.len() as u64 > max {
+                    return Err(Error::violation(
+                        path,
+                        format!("expected at most {} items", max),
+                    ));
+                }
+            }
+            if let Some(subschema) = schema.get("items") {
+                for (idx, item) in items.iter().enumerate() {
+                    validate(subschema, item, &format!("{}/{}", path, idx))?;
+                }
+            }
+        }
+        Value::String(string) => {
+            let len = string.chars().count() as u64;
+            if let Some(min) = bound(schema, "minLength") {
+                if len < min {
+                    return Err(Error::violation(
+                        path,
+                        format!("expected at least {} characters", min),
+                    ));
+                }
+            }
+            if let Some(max) = bound(schema, "maxLength") {
+                if len > max {
+                    return Err(Error::violation(
+                        path,
+                        format!("expected at most {} characters", max),
+                    ));
+                }
+            }
+        }
+        Value::Number(number) => {
+            let number = number.as_f64().unwrap();
+            let limit = |keyword| schema.get(keyword).and_then(Value::as_f64);
+            if let Some(min) = limit("minimum").filter(|min| number < *min) {
+                return Err(Error::violation(path, format!("expected at least {}", min)));
+            }
+            if let Some(max) = limit("maximum").filter(|max| number > *max) {
+                return Err(Error::violation(path, format!("expected at most {}", max)));
+            }
+            if let Some(min) = limit("exclusiveMinimum").filter(|min| number <= *min) {
+                return Err(Error::violation(
+                    path,
+                    format!("expected more than {}", min),
+                ));
+            }
+            if let Some(max) = limit("exclusiveMaximum").filter(|max| number >= *max) {
+                return Err(Error::violation(
+                    path,
+                    format!("expected less than {}", max),
+                ));
+            }
+        }
+        _ => {}
+    }
+
+    if let Some(Value::Array(subschemas)) = schema.get("allOf") {
+        for subschema in subschemas {
+            validate(subschema, value, path)?;
+        }
+    }
+    if let Some(Value::Array(subschemas)) = schema.get("anyOf") {
+        if !subschemas
+            .iter()
+            .any(|subschema| validate(subschema, value, path).is_ok())
+        {
+            return Err(Error::violation(
+                path,
+                "value matches none of the 'anyOf' schemas",
+            ));
+        }
+    }
+    if let Some(Value::Array(subschemas)) = schema.get("oneOf") {
+        let matched = subschemas
+            .iter()
+            .filter(|subschema| validate(subschema, value, path).is_ok())
+            .count();
+        if matched != 1 {
+            return Err(Error::violation(
+                path,
+                format!(
+                    "value matches {} of the 'oneOf' schemas, expected 1",
+                    matched
+                ),
+            ));
+        }
+    }
+    if let Some(subschema) = schema.get("not") {
+        if validate(subschema, value, path).is_ok() {
+            return Err(Error::violation(path, "value matches the 'not' schema"));
+        }
+    }
+    Ok(())
+}
+
+/// Validate the supplied object against the object keywords of the supplied schema.
+fn validate_object(
+    schema: &Map<String, Value>,
+    object: &Map<String, Value>,
+    path: &str,
+) -> Result<()> {
+    if let Some(Value::Array(required)) = schema.get("required") {
+        for name in required.iter().filter_map(Value::as_str) {
+            if !object.contains_key(name) {
+                return Err(Error::violation(
+                    &format!("{}/{}", path, name),
+                    "required property is missing",
+                ));
+            }
+        }
+    }
+
+    let properties = schema.get("properties").and_then(Value::as_object);
+    for (name, property) in object {
+        let property_path = format!("{}/{}", path, name);
+        match properties.and_then(|properties| properties.get(name)) {
+            Some(subschema) => validate(subschema, property, &property_path)?,
+            None => {
+                if let Some(additional) = schema.get("additionalProperties") {
+                    validate(additional, property, &property_path)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Return the non-negative integer value of the supplied keyword, if set.
+fn bound(schema: &Map<String, Value>, keyword: &str) -> Option<u64> {
+    schema.get(keyword).and_then(Value::as_u64)
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = r#"{
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "type": "object",
+        "required": ["id", "tags"],
+        "additionalProperties": false,
+        "properties": {
+            "id": {"type": "integer", "minimum": 1},
+            "name": {"type": "string", "maxLength": 4},
+            "kind": {"enum": ["a", "b"]},
+            "tags": {"type": "array", "items": {"type": "string"}, "minItems": 1}
+        }
+    }"#;
+
+    #[test]
+    fn test_new() {
+        assert!(JsonSchema::new(String::from(SCHEMA)).is_ok());
+        assert!(JsonSchema::new(String::from("true")).is_ok());
+        assert!(JsonSchema::new(String::from("{")).is_err());
+        assert!(JsonSchema::new(String::from("1")).is_err());
+        assert!(JsonSchema::new(String::from(r#"{"pattern": "^a$"}"#)).is_err());
+        assert!(JsonSchema::new(String::from(r#"{"minLength": -1}"#)).is_err());
+        assert!(JsonSchema::new(String::from(r#"{"properties": {"a": 1}}"#)).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let schema = JsonSchema::new(String::from(SCHEMA)).unwrap();
+        assert!(schema
+            .validate(br#"{"id": 1, "name": "abc", "kind": "a", "tags": ["x"]}"#)
+            .is_ok());
+
+        let cases: [(&[u8], &str); 8] = [
+            (b"{", ""),
+            (b"[]", ""),
+            (br#"{"tags": ["x"]}"#, "/id"),
+            (br#"{"id": 0, "tags": ["x"]}"#, "/id"),
+            (br#"{"id": 1.5, "tags": ["x"]}"#, "/id"),
+            (br#"{"id": 1, "tags": ["x"], "name": "abcde"}"#, "/name"),
+            (br#"{"id": 1, "tags": ["x", 2]}"#, "/tags/1"),
+            (br#"{"id": 1, "tags": ["x"], "other": true}"#, "/other"),
+        ];
+        for (data, expected) in cases {
+            match schema.validate(data) {
+                Err(Error::Violation { path, .. }) => assert_eq!(path, expected),
+                other => panic!("expected a violation, got {:?}", other),
+            }
+        }
+    }
+}
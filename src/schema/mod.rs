@@ -0,0 +1,104 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+mod error;
+mod json;
+mod protobuf;
+mod registry;
+mod transcode;
+
+// stdlib usings
+use std::borrow::Cow;
+
+pub use error::{Error, Result};
+pub use json::JsonSchema;
+pub use protobuf::ProtobufSchema;
+pub use registry::Registry;
+
+/// The content type of message data holding a JSON document.
+pub const JSON_CONTENT_TYPE: &str = "application/json";
+/// The content type of message data holding a serialized protobuf message.
+pub const PROTOBUF_CONTENT_TYPE: &str = "application/x-protobuf";
+
+/// A schema that the data of every message published to a topic must conform to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schema {
+    /// Message data must be a serialized protobuf message of a given type.
+    Protobuf(ProtobufSchema),
+    /// Message data must be a JSON document conforming to a JSON Schema.
+    Json(JsonSchema),
+}
+
+impl Schema {
+    /// Validate the supplied message data against this schema.
+    pub fn validate(&self, data: &[u8]) -> Result<()> {
+        match self {
+            Schema::Protobuf(schema) => schema.validate(data),
+            Schema::Json(schema) => schema.validate(data),
+        }
+    }
+
+    /// Return the content type of message data in the native format of this schema.
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Schema::Protobuf(_) => PROTOBUF_CONTENT_TYPE,
+            Schema::Json(_) => JSON_CONTENT_TYPE,
+        }
+    }
+
+    /// Return the supplied content type, or the native content type of this schema if it is
+    /// empty.
+    fn resolve<'a>(&self, content_type: &'a str) -> &'a str {
+        match content_type {
+            "" => self.content_type(),
+            content_type => content_type,
+        }
+    }
+
+    /// Return whether message data can be transcoded into the supplied content type, where
+    /// an empty content type is the native content type of this schema.
+    pub fn transcodes_to(&self, content_type: &str) -> bool {
+        let content_type = self.resolve(content_type);
+        match self {
+            Schema::Protobuf(_) => {
+                content_type == PROTOBUF_CONTENT_TYPE || content_type == JSON_CONTENT_TYPE
+            }
+            Schema::Json(_) => content_type == JSON_CONTENT_TYPE,
+        }
+    }
+
+    /// Validate the supplied message data of the supplied content type against this schema.
+    /// Protobuf schemas also accept JSON documents which transcode into a valid message.
+    pub fn validate_as(&self, content_type: &str, data: &[u8]) -> Result<()> {
+        match (self, self.resolve(content_type)) {
+            (schema, content_type) if content_type == schema.content_type() => {
+                schema.validate(data)
+            }
+            (Schema::Protobuf(schema), JSON_CONTENT_TYPE) => schema.from_json(data).map(|_| ()),
+            (schema, content_type) => Err(Error::Unsupported {
+                from: content_type.to_owned(),
+                to: schema.content_type().to_owned(),
+            }),
+        }
+    }
+
+    /// Transcode the supplied message data from one content type to another, where an empty
+    /// content type is the native content type of this schema. Data already of the requested
+    /// content type is returned as is.
+    pub fn transcode<'a>(&self, data: &'a [u8], from: &str, to: &str) -> Result<Cow<'a, [u8]>> {
+        let (from, to) = (self.resolve(from), self.resolve(to));
+        match (self, from, to) {
+            (_, from, to) if from == to => Ok(Cow::Borrowed(data)),
+            (Schema::Protobuf(schema), PROTOBUF_CONTENT_TYPE, JSON_CONTENT_TYPE) => {
+                schema.to_json(data).map(Cow::Owned)
+            }
+            (Schema::Protobuf(schema), JSON_CONTENT_TYPE, PROTOBUF_CONTENT_TYPE) => {
+                schema.from_json(data).map(Cow::Owned)
+            }
+            (_, from, to) => Err(Error::Unsupported {
+                from: from.to_owned(),
+                to: to.to_owned(),
+            }),
+        }
+    }
+}
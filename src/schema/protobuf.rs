@@ -0,0 +1,345 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// crate usings
+use super::{Error, Result};
+
+// extern usings
+use prost::encoding::{decode_varint, WireType};
+use prost::Message;
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{DescriptorProto, EnumDescriptorProto, FileDescriptorSet};
+
+/// The maximum depth of nested messages validated, guarding against deeply nested or
+/// recursive data exhausting the stack.
+pub(super) const MAX_DEPTH: usize = 64;
+
+/// A schema requiring message data to be a serialized protobuf message of a given type.
+///
+/// Validation walks the wire format of the data against the message descriptor, ensuring
+/// every field is declared with a matching wire type, strings are valid UTF-8, and nested
+/// messages are themselves valid.
+#[derive(Debug, Clone)]
+pub struct ProtobufSchema {
+    /// The serialized `google.protobuf.FileDescriptorSet` defining the message type.
+    pub descriptor_set: Vec<u8>,
+    /// The fully qualified name of the message type, without a leading dot.
+    pub message_type: String,
+    messages: Arc<HashMap<String, DescriptorProto>>,
+    enums: Arc<HashMap<String, EnumDescriptorProto>>,
+}
+
+impl ProtobufSchema {
+    /// Create a new schema from a serialized `google.protobuf.FileDescriptorSet` and the fully
+    /// qualified name of the message type defined within it.
+    pub fn new(descriptor_set: Vec<u8>, message_type: String) -> Result<Self> {
+        let set =
+            FileDescriptorSet::decode(descriptor_set.as_slice()).map_err(|err| Error::Invalid {
+                reason: format!("failed to decode the descriptor set: {}", err),
+            })?;
+
+        let (mut messages, mut enums) = (HashMap::new(), HashMap::new());
+        for file in set.file {
+            let prefix = match file.package() {
+                "" => String::new(),
+                package => format!(".{}", package),
+            };
+            collect_enums(&prefix, file.enum_type, &mut enums);
+            collect(&prefix, file.message_type, &mut messages, &mut enums);
+        }
+
+        let message_type = message_type.trim_start_matches('.').to_owned();
+        if !messages.contains_key(&format!(".{}", message_type)) {
+            return Err(Error::Invalid {
+                reason: format!(
+                    "the message type '{}' is not defined in the descriptor set",
+                    message_type
+                ),
+            });
+        }
+
+        Ok(Self {
+            descriptor_set,
+            message_type,
+            messages: Arc::new(messages),
+            enums: Arc::new(enums),
+        })
+    }
+
+    /// Return the descriptor of this schema's message type.
+    pub(super) fn root(&self) -> &DescriptorProto {
+        &self.messages[&format!(".{}", self.message_type)]
+    }
+
+    /// Return the descriptor of the message type with the supplied fully qualified name,
+    /// including its leading dot.
+    pub(super) fn message(&self, name: &str) -> Option<&DescriptorProto> {
+        self.messages.get(name)
+    }
+
+    /// Return the descriptor of the enum type with the supplied fully qualified name,
+    /// including its leading dot.
+    pub(super) fn enumeration(&self, name: &str) -> Option<&EnumDescriptorProto> {
+        self.enums.get(name)
+    }
+
+    /// Validate the supplied data is a serialized message of this schema's message type.
+    pub fn validate(&self, data: &[u8]) -> Result<()> {
+        self.validate_message(self.root(), data, "", 0)
+    }
+
+    fn validate_message(
+        &self,
+        descriptor: &DescriptorProto,
+        mut buf: &[u8],
+        path: &str,
+        depth: usize,
+    ) -> Result<()> {
+        if depth > MAX_DEPTH {
+            return Err(Error::violation(path, "messages are nested too deeply"));
+        }
+
+        while !buf.is_empty() {
+            let key = decode_varint(&mut buf)
+                .map_err(|_| Error::violation(path, "malformed field key"))?;
+            let number = (key >> 3) as i32;
+            let wire_type = WireType::try_from(key & 0x07)
+                .map_err(|_| Error::violation(path, format!("invalid wire type {}", key & 0x07)))?;
+
+            let field = descriptor
+                .field
+                .iter()
+                .find(|field| field.number() == number)
+                .ok_or_else(|| Error::violation(path, format!("unknown field {}", number)))?;
+            let field_path = match path {
+                "" => field.name().to_owned(),
+                path => format!("{}.{}", path, field.name()),
+            };
+
+            let expected = expected_wire_type(field.r#type());
+            let packed = field.label() == Label::Repeated
+                && wire_type == WireType::LengthDelimited
+                && expected != WireType::LengthDelimited;
+            if wire_type != expected && !packed {
+                return Err(Error::violation(
+                    &field_path,
+                    format!(
+                        "expected wire type {:?} but found {:?}",
+                        expected, wire_type
+                    ),
+                ));
+            }
+
+            match wire_type {
+                WireType::Varint => {
+                    decode_varint(&mut buf)
+                        .map_err(|_| Error::violation(&field_path, "malformed varint"))?;
+                }
+                WireType::SixtyFourBit => skip(&mut buf, 8, &field_path)?,
+                WireType::ThirtyTwoBit => skip(&mut buf, 4, &field_path)?,
+                WireType::LengthDelimited => {
+                    let len = decode_varint(&mut buf)
+                        .map_err(|_| Error::violation(&field_path, "malformed length"))?;
+                    let value = take(&mut buf, len as usize, &field_path)?;
+                    if packed {
+                        validate_packed(expected, value, &field_path)?;
+                        continue;
+                    }
+                    match field.r#type() {
+                        Type::String => {
+                            std::str::from_utf8(value).map_err(|_| {
+                                Error::violation(&field_path, "strings must be valid UTF-8")
+                            })?;
+                        }
+                        Type::Message => {
+                            let nested = self.messages.get(field.type_name()).ok_or_else(|| {
+                                Error::violation(
+                                    &field_path,
+                                    format!("undefined message type '{}'", field.type_name()),
+                                )
+                            })?;
+                            self.validate_message(nested, value, &field_path, depth + 1)?;
+                        }
+                        _ => {}
+                    }
+                }
+                WireType::StartGroup | WireType::EndGroup => {
+                    return Err(Error::violation(&field_path, "groups are not supported"))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PartialEq for ProtobufSchema {
+    fn eq(&self, other: &Self) -> bool {
+        self.message_type == other.message_type && self.descriptor_set == other.descriptor_set
+    }
+}
+
+/// Collect the supplied messages and their nested messages and enums into the supplied maps,
+/// keyed by their fully qualified names with a leading dot, as used by field type names.
+fn collect(
+    prefix: &str,
+    descriptors: Vec<DescriptorProto>,
+    messages: &mut HashMap<String, DescriptorProto>,
+    enums: &mut HashMap<String, EnumDescriptorProto>,
+) {
+    for mut descriptor in descriptors {
+        let name = format!("{}.{}", prefix, descriptor.name());
+        collect_enums(&name, std::mem::take(&mut descriptor.enum_type), enums);
+        collect(
+            &name,
+            std::mem::take(&mut descriptor.nested_type),
+            messages,
+            enums,
+        );
+        messages.insert(name, descriptor);
+    }
+}
+
+/// Collect the supplied enums into the supplied map, keyed by their fully qualified names
+/// with a leading dot.
+fn collect_enums(
+    prefix: &str,
+    descriptors: Vec<EnumDescriptorProto>,
+    enums: &mut HashMap<String, EnumDescriptorProto>,
+) {
+    for descriptor in descriptors {
+        enums.insert(format!("{}.{}", prefix, descriptor.name()), descriptor);
+    }
+}
+
+/// Return the wire type that a non-packed field of the supplied type is encoded with.
+pub(super) fn expected_wire_type(ty: Type) -> WireType {
+    match ty {
+        Type::Double | Type::Fixed64 | Type::Sfixed64 => WireType::SixtyFourBit,
+        Type::Float | Type::Fixed32 | Type::Sfixed32 => WireType::ThirtyTwoBit,
+        Type::String | Type::Bytes | Type::Message => WireType::LengthDelimited,
+        Type::Group => WireType::StartGroup,
+        Type::Int32
+        | Type::Int64
+        | Type::Uint32
+        | Type::Uint64
+        | Type::Sint32
+        | Type::Sint64
+        | Type::Bool
+        | Type::Enum => WireType::Varint,
+    }
+}
+
+/// Validate the supplied packed repeated field only contains whole values of its wire type.
+fn validate_packed(wire_type: WireType, mut buf: &[u8], path: &str) -> Result<()> {
+    while !buf.is_empty() {
+        match wire_type {
+            WireType::Varint => {
+                decode_varint(&mut buf).map_err(|_| Error::violation(path, "malformed varint"))?;
+            }
+            WireType::SixtyFourBit => skip(&mut buf, 8, path)?,
+            WireType::ThirtyTwoBit => skip(&mut buf, 4, path)?,
+            _ => return Err(Error::violation(path, "field can not be packed")),
+        }
+    }
+    Ok(())
+}
+
+/// Take the next `len` bytes of the supplied buffer.
+pub(super) fn take<'a>(buf: &mut &'a [u8], len: usize, path: &str) -> Result<&'a [u8]> {
+    if buf.len() < len {
+        return Err(Error::violation(path, "unexpected end of data"));
+    }
+    let (value, rest) = buf.split_at(len);
+    *buf = rest;
+    Ok(value)
+}
+
+/// Skip the next `len` bytes of the supplied buffer.
+fn skip(buf: &mut &[u8], len: usize, path: &str) -> Result<()> {
+    take(buf, len, path).map(|_| ())
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    use prost_types::{FieldDescriptorProto, FileDescriptorProto};
+
+    fn field(name: &str, number: i32, ty: Type, label: Label) -> FieldDescriptorProto {
+        FieldDescriptorProto {
+            name: Some(name.to_owned()),
+            number: Some(number),
+            r#type: Some(ty as i32),
+            label: Some(label as i32),
+            ..Default::default()
+        }
+    }
+
+    fn schema() -> ProtobufSchema {
+        let mut nested = field("inner", 3, Type::Message, Label::Optional);
+        nested.type_name = Some(String::from(".test.Outer.Inner"));
+        let set = FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some(String::from("test.proto")),
+                package: Some(String::from("test")),
+                message_type: vec![DescriptorProto {
+                    name: Some(String::from("Outer")),
+                    field: vec![
+                        field("name", 1, Type::String, Label::Optional),
+                        field("ids", 2, Type::Int64, Label::Repeated),
+                        nested,
+                    ],
+                    nested_type: vec![DescriptorProto {
+                        name: Some(String::from("Inner")),
+                        field: vec![field("value", 1, Type::Fixed32, Label::Optional)],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+        ProtobufSchema::new(set.encode_to_vec(), String::from("test.Outer")).unwrap()
+    }
+
+    #[test]
+    fn test_new() {
+        assert!(ProtobufSchema::new(vec![0xff], String::from("test.Outer")).is_err());
+        let set = schema().descriptor_set;
+        assert!(ProtobufSchema::new(set.clone(), String::from(".test.Outer")).is_ok());
+        assert!(ProtobufSchema::new(set.clone(), String::from("test.Outer.Inner")).is_ok());
+        assert!(ProtobufSchema::new(set, String::from("test.Missing")).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let schema = schema();
+        // An empty message is valid, as every proto3 field is optional.
+        assert!(schema.validate(&[]).is_ok());
+        // name = "hi", ids = [1, 2] packed, inner.value = 1.
+        let valid = [
+            0x0a, 0x02, b'h', b'i', 0x12, 0x02, 0x01, 0x02, 0x10, 0x03, 0x1a, 0x05, 0x0d, 0x01,
+            0x00, 0x00, 0x00,
+        ];
+        assert!(schema.validate(&valid).is_ok());
+
+        let cases: [(&[u8], &str); 5] = [
+            (&[0x20, 0x01], ""),
+            (&[0x0d, 0x01, 0x00, 0x00, 0x00], "name"),
+            (&[0x0a, 0x02, 0xff, 0xff], "name"),
+            (&[0x0a, 0x05, b'h'], "name"),
+            (&[0x1a, 0x02, 0x08, 0x01], "inner.value"),
+        ];
+        for (data, expected) in cases {
+            match schema.validate(data) {
+                Err(Error::Violation { path, .. }) => assert_eq!(path, expected),
+                other => panic!("expected a violation, got {:?}", other),
+            }
+        }
+    }
+}
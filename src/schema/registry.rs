@@ -0,0 +1,53 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+// crate usings
+use super::{Result, Schema};
+
+/// Tracks the schema bound to each topic, if any.
+#[derive(Debug, Default, Clone)]
+pub struct Registry {
+    schemas: Arc<RwLock<HashMap<String, Arc<Schema>>>>,
+}
+
+impl Registry {
+    /// Bind the supplied schema to the supplied topic, replacing any existing binding.
+    pub fn bind(&self, topic: String, schema: Schema) {
+        self.schemas
+            .write()
+            .unwrap()
+            .insert(topic, Arc::new(schema));
+    }
+
+    /// Remove the schema bound to the supplied topic, if any.
+    pub fn unbind(&self, topic: &str) -> Option<Arc<Schema>> {
+        self.schemas.write().unwrap().remove(topic)
+    }
+
+    /// Retrieve the schema bound to the supplied topic, if any.
+    pub fn get(&self, topic: &str) -> Option<Arc<Schema>> {
+        self.schemas.read().unwrap().get(topic).cloned()
+    }
+
+    /// Validate the supplied message data against the schema bound to the supplied topic.
+    /// Topics without a schema accept any data.
+    pub fn validate(&self, topic: &str, data: &[u8]) -> Result<()> {
+        match self.get(topic) {
+            Some(schema) => schema.validate(data),
+            None => Ok(()),
+        }
+    }
+
+    /// Validate the supplied message data of the supplied content type against the schema
+    /// bound to the supplied topic, like [Registry::validate].
+    pub fn validate_as(&self, topic: &str, content_type: &str, data: &[u8]) -> Result<()> {
+        match self.get(topic) {
+            Some(schema) => schema.validate_as(content_type, data),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,655 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::convert::{TryFrom, TryInto};
+
+// crate usings
+use super::protobuf::{expected_wire_type, take, MAX_DEPTH};
+use super::{Error, ProtobufSchema, Result};
+
+// extern usings
+use prost::encoding::{decode_varint, encode_key, encode_varint, WireType};
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{DescriptorProto, FieldDescriptorProto};
+use serde_json::{Map, Number, Value};
+
+impl ProtobufSchema {
+    /// Transcode the supplied serialized message of this schema's message type into a JSON
+    /// document, following the proto3 JSON mapping of scalar, enum, repeated, map and nested
+    /// message fields. Only fields present in the data are included, and well known types
+    /// are transcoded as regular messages.
+    pub fn to_json(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let value = self.decode(self.root(), data, "", 0)?;
+        Ok(serde_json::to_vec(&value).expect("JSON values always serialize"))
+    }
+
+    /// Transcode the supplied JSON document into a serialized message of this schema's
+    /// message type, accepting both the JSON and original names of fields.
+    pub fn from_json(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let value = serde_json::from_slice::<Value>(data)
+            .map_err(|err| Error::violation("", format!("invalid JSON: {}", err)))?;
+        let mut buf = Vec::with_capacity(data.len());
+        self.encode(self.root(), &value, "", 0, &mut buf)?;
+        Ok(buf)
+    }
+
+    fn decode(
+        &self,
+        descriptor: &DescriptorProto,
+        mut buf: &[u8],
+        path: &str,
+        depth: usize,
+    ) -> Result<Value> {
+        if depth > MAX_DEPTH {
+            return Err(Error::violation(path, "messages are nested too deeply"));
+        }
+
+        let mut object = Map::new();
+        while !buf.is_empty() {
+            let key = decode_varint(&mut buf)
+                .map_err(|_| Error::violation(path, "malformed field key"))?;
+            let number = (key >> 3) as i32;
+            let wire_type = WireType::try_from(key & 0x07)
+                .map_err(|_| Error::violation(path, format!("invalid wire type {}", key & 0x07)))?;
+            let field = descriptor
+                .field
+                .iter()
+                .find(|field| field.number() == number)
+                .ok_or_else(|| Error::violation(path, format!("unknown field {}", number)))?;
+            let field_path = join(path, field.name());
+
+            let expected = expected_wire_type(field.r#type());
+            let mut values = Vec::with_capacity(1);
+            match wire_type {
+                WireType::LengthDelimited => {
+                    let len = decode_varint(&mut buf)
+                        .map_err(|_| Error::violation(&field_path, "malformed length"))?;
+                    let mut value = take(&mut buf, len as usize, &field_path)?;
+                    match field.r#type() {
+                        Type::String => {
+                            let value = std::str::from_utf8(value).map_err(|_| {
+                                Error::violation(&field_path, "strings must be valid UTF-8")
+                            })?;
+                            values.push(Value::from(value));
+                        }
+                        Type::Bytes => values.push(Value::from(base64::encode(value))),
+                        Type::Message => {
+                            let nested = self.nested(field, &field_path)?;
+                            values.push(self.decode(nested, value, &field_path, depth + 1)?);
+                        }
+                        // Packed repeated scalars.
+                        _ if field.label() == Label::Repeated => {
+                            while !value.is_empty() {
+                                values.push(self.scalar(
+                                    field,
+                                    expected,
+                                    &mut value,
+                                    &field_path,
+                                )?);
+                            }
+                        }
+                        _ => return Err(mismatch(&field_path, expected, wire_type)),
+                    }
+                }
+                WireType::StartGroup | WireType::EndGroup => {
+                    return Err(Error::violation(&field_path, "groups are not supported"))
+                }
+                _ => values.push(self.scalar(field, wire_type, &mut buf, &field_path)?),
+            }
+
+            let name = json_name(field);
+            if field.label() != Label::Repeated {
+                if let Some(value) = values.pop() {
+                    object.insert(name, value);
+                }
+                continue;
+            }
+            match self.map_entry(field) {
+                Some(entry) => {
+                    let map = object
+                        .entry(name)
+                        .or_insert_with(|| Value::Object(Map::new()));
+                    for mut value in values {
+                        let (key, value) = (take_entry(&mut value, 1), take_entry(&mut value, 2));
+                        let key = match key {
+                            Some(Value::String(key)) => key,
+                            Some(key) => key.to_string(),
+                            None => default_value(self, &entry.field[0]).to_string(),
+                        };
+                        let value = value.unwrap_or_else(|| default_value(self, &entry.field[1]));
+                        if let Value::Object(map) = map {
+                            map.insert(key, value);
+                        }
+                    }
+                }
+                None => {
+                    let array = object
+                        .entry(name)
+                        .or_insert_with(|| Value::Array(Vec::new()));
+                    if let Value::Array(array) = array {
+                        array.extend(values);
+                    }
+                }
+            }
+        }
+        Ok(Value::Object(object))
+    }
+
+    /// Decode the next scalar value of the supplied field from the supplied buffer.
+    fn scalar(
+        &self,
+        field: &FieldDescriptorProto,
+        wire_type: WireType,
+        buf: &mut &[u8],
+        path: &str,
+    ) -> Result<Value> {
+        let ty = field.r#type();
+        if wire_type != expected_wire_type(ty) {
+            return Err(mismatch(path, expected_wire_type(ty), wire_type));
+        }
+        let value = match wire_type {
+            WireType::Varint => {
+                let value =
+                    decode_varint(buf).map_err(|_| Error::violation(path, "malformed varint"))?;
+                match ty {
+                    Type::Int32 => Value::from(value as i32),
+                    Type::Int64 => Value::from((value as i64).to_string()),
+                    Type::Uint32 => Value::from(value as u32),
+                    Type::Uint64 => Value::from(value.to_string()),
+                    Type::Sint32 => Value::from(unzigzag(value) as i32),
+                    Type::Sint64 => Value::from(unzigzag(value).to_string()),
+                    Type::Bool => Value::from(value != 0),
+                    _ => self.enum_name(field, value as i32),
+                }
+            }
+            WireType::SixtyFourBit => {
+                let bits = u64::from_le_bytes(take(buf, 8, path)?.try_into().unwrap());
+                match ty {
+                    Type::Fixed64 => Value::from(bits.to_string()),
+                    Type::Sfixed64 => Value::from((bits as i64).to_string()),
+                    _ => float(f64::from_bits(bits)),
+                }
+            }
+            _ => {
+                let bits = u32::from_le_bytes(take(buf, 4, path)?.try_into().unwrap());
+                match ty {
+                    Type::Fixed32 => Value::from(bits),
+                    Type::Sfixed32 => Value::from(bits as i32),
+                    _ => float(f32::from_bits(bits) as f64),
+                }
+            }
+        };
+        Ok(value)
+    }
+
+    fn encode(
+        &self,
+        descriptor: &DescriptorProto,
+        value: &Value,
+        path: &str,
+        depth: usize,
+        buf: &mut Vec<u8>,
+    ) -> Result<()> {
+        if depth > MAX_DEPTH {
+            return Err(Error::violation(path, "messages are nested too deeply"));
+        }
+        let object = value
+            .as_object()
+            .ok_or_else(|| Error::violation(path, "expected an object"))?;
+
+        for (name, value) in object {
+            let field = descriptor
+                .field
+                .iter()
+                .find(|field| json_name(field) == *name || field.name() == name)
+                .ok_or_else(|| Error::violation(path, format!("unknown field '{}'", name)))?;
+            let field_path = join(path, field.name());
+            if value.is_null() {
+                continue;
+            }
+            if field.label() != Label::Repeated {
+                self.encode_field(field, value, &field_path, depth, buf)?;
+                continue;
+            }
+            match self.map_entry(field) {
+                Some(entry) => {
+                    let map = value
+                        .as_object()
+                        .ok_or_else(|| Error::violation(&field_path, "expected an object"))?;
+                    let (key_field, value_field) = (&entry.field[0], &entry.field[1]);
+                    for (key, value) in map {
+                        let key = match key_field.r#type() {
+                            Type::Bool => Value::from(key == "true"),
+                            _ => Value::from(key.as_str()),
+                        };
+                        let mut entry_buf = Vec::new();
+                        self.encode_field(key_field, &key, &field_path, depth, &mut entry_buf)?;
+                        self.encode_field(value_field, value, &field_path, depth, &mut entry_buf)?;
+                        delimit(field.number() as u32, &entry_buf, buf);
+                    }
+                }
+                None => {
+                    let array = value
+                        .as_array()
+                        .ok_or_else(|| Error::violation(&field_path, "expected an array"))?;
+                    for value in array {
+                        self.encode_field(field, value, &field_path, depth, buf)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Encode the supplied value of a single occurrence of the supplied field.
+    fn encode_field(
+        &self,
+        field: &FieldDescriptorProto,
+        value: &Value,
+        path: &str,
+        depth: usize,
+        buf: &mut Vec<u8>,
+    ) -> Result<()> {
+        let tag = field.number() as u32;
+        let expected = |kind: &str| Error::violation(path, format!("expected {}", kind));
+        let ty = field.r#type();
+        match ty {
+            Type::String => {
+                let value = value.as_str().ok_or_else(|| expected("a string"))?;
+                delimit(tag, value.as_bytes(), buf);
+            }
+            Type::Bytes => {
+                let value = value.as_str().ok_or_else(|| expected("a base64 string"))?;
+                let value = base64::decode(value)
+                    .or_else(|_| base64::decode_config(value, base64::URL_SAFE))
+                    .map_err(|_| expected("a base64 string"))?;
+                delimit(tag, &value, buf);
+            }
+            Type::Message => {
+                let nested = self.nested(field, path)?;
+                let mut nested_buf = Vec::new();
+                self.encode(nested, value, path, depth + 1, &mut nested_buf)?;
+                delimit(tag, &nested_buf, buf);
+            }
+            Type::Bool => {
+                let value = value.as_bool().ok_or_else(|| expected("a boolean"))?;
+                encode_key(tag, WireType::Varint, buf);
+                encode_varint(value as u64, buf);
+            }
+            Type::Enum => {
+                let number = match value {
+                    Value::String(name) => self.enum_number(field, name),
+                    value => integer(value).and_then(|value| i32::try_from(value).ok()),
+                };
+                let number = number.ok_or_else(|| expected("an enum value"))?;
+                encode_key(tag, WireType::Varint, buf);
+                encode_varint(number as i64 as u64, buf);
+            }
+            Type::Double | Type::Float => {
+                let value = match value {
+                    Value::Number(number) => number.as_f64(),
+                    Value::String(value) => match value.as_str() {
+                        "NaN" => Some(f64::NAN),
+                        "Infinity" => Some(f64::INFINITY),
+                        "-Infinity" => Some(f64::NEG_INFINITY),
+                        value => value.parse().ok(),
+                    },
+                    _ => None,
+                };
+                let value = value.ok_or_else(|| expected("a number"))?;
+                if ty == Type::Double {
+                    encode_key(tag, WireType::SixtyFourBit, buf);
+                    buf.extend_from_slice(&value.to_le_bytes());
+                } else {
+                    encode_key(tag, WireType::ThirtyTwoBit, buf);
+                    buf.extend_from_slice(&(value as f32).to_le_bytes());
+                }
+            }
+            Type::Group => return Err(Error::violation(path, "groups are not supported")),
+            _ => {
+                let value = integer(value).ok_or_else(|| expected("an integer"))?;
+                let out_of_range = || expected("an integer within range");
+                encode_key(tag, expected_wire_type(ty), buf);
+                match ty {
+                    Type::Int32 => {
+                        let value = i32::try_from(value).map_err(|_| out_of_range())?;
+                        encode_varint(value as i64 as u64, buf);
+                    }
+                    Type::Int64 => {
+                        let value = i64::try_from(value).map_err(|_| out_of_range())?;
+                        encode_varint(value as u64, buf);
+                    }
+                    Type::Uint32 | Type::Uint64 => {
+                        let value = match ty {
+                            Type::Uint32 => u32::try_from(value).map(u64::from),
+                            _ => u64::try_from(value),
+                        };
+                        encode_varint(value.map_err(|_| out_of_range())?, buf);
+                    }
+                    Type::Sint32 => {
+                        let value = i32::try_from(value).map_err(|_| out_of_range())?;
+                        encode_varint(((value << 1) ^ (value >> 31)) as u32 as u64, buf);
+                    }
+                    Type::Sint64 => {
+                        let value = i64::try_from(value).map_err(|_| out_of_range())?;
+                        encode_varint(((value << 1) ^ (value >> 63)) as u64, buf);
+                    }
+                    Type::Fixed32 => {
+                        let value = u32::try_from(value).map_err(|_| out_of_range())?;
+                        buf.extend_from_slice(&value.to_le_bytes());
+                    }
+                    Type::Sfixed32 => {
+                        let value = i32::try_from(value).map_err(|_| out_of_range())?;
+                        buf.extend_from_slice(&value.to_le_bytes());
+                    }
+                    Type::Fixed64 => {
+                        let value = u64::try_from(value).map_err(|_| out_of_range())?;
+                        buf.extend_from_slice(&value.to_le_bytes());
+                    }
+                    _ => {
+                        let value = i64::try_from(value).map_err(|_| out_of_range())?;
+                        buf.extend_from_slice(&value.to_le_bytes());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Return the descriptor of the message type of the supplied field.
+    fn nested(&self, field: &FieldDescriptorProto, path: &str) -> Result<&DescriptorProto> {
+        self.message(field.type_name()).ok_or_else(|| {
+            Error::violation(
+                path,
+                format!("undefined message type '{}'", field.type_name()),
+            )
+        })
+    }
+
+    /// Return the descriptor of the entries of the supplied field if it is a map.
+    fn map_entry(&self, field: &FieldDescriptorProto) -> Option<&DescriptorProto> {
+        if field.r#type() != Type::Message {
+            return None;
+        }
+        self.message(field.type_name())
+            .filter(|entry| matches!(&entry.options, Some(options) if options.map_entry()))
+            .filter(|entry| entry.field.len() == 2)
+    }
+
+    /// Return the name of the supplied value of the enum type of the supplied field, or the
+    /// number itself if it is not a known value.
+    fn enum_name(&self, field: &FieldDescriptorProto, number: i32) -> Value {
+        self.enumeration(field.type_name())
+            .and_then(|descriptor| {
+                descriptor
+                    .value
+                    .iter()
+                    .find(|value| value.number() == number)
+            })
+            .map(|value| Value::from(value.name()))
+            .unwrap_or_else(|| Value::from(number))
+    }
+
+    /// Return the number of the named value of the enum type of the supplied field.
+    fn enum_number(&self, field: &FieldDescriptorProto, name: &str) -> Option<i32> {
+        self.enumeration(field.type_name())?
+            .value
+            .iter()
+            .find(|value| value.name() == name)
+            .map(|value| value.number())
+    }
+}
+
+/// Return the JSON name of the supplied field, which is its name in lower camel case unless
+/// the descriptor says otherwise.
+fn json_name(field: &FieldDescriptorProto) -> String {
+    if let Some(name) = field.json_name.as_ref().filter(|name| !name.is_empty()) {
+        return name.clone();
+    }
+    let mut name = String::with_capacity(field.name().len());
+    let mut upper = false;
+    for c in field.name().chars() {
+        match c {
+            '_' => upper = true,
+            c if upper => {
+                name.extend(c.to_uppercase());
+                upper = false;
+            }
+            c => name.push(c),
+        }
+    }
+    name
+}
+
+/// Return the default value of the supplied field, used for map entries which omit it.
+fn default_value(schema: &ProtobufSchema, field: &FieldDescriptorProto) -> Value {
+    match field.r#type() {
+        Type::String | Type::Bytes => Value::from(""),
+        Type::Bool => Value::from(false),
+        Type::Message => Value::Object(Map::new()),
+        Type::Enum => schema.enum_name(field, 0),
+        Type::Int64 | Type::Uint64 | Type::Sint64 | Type::Fixed64 | Type::Sfixed64 => {
+            Value::from("0")
+        }
+        _ => Value::from(0),
+    }
+}
+
+/// Remove the value of the field with the supplied number from a decoded map entry.
+fn take_entry(entry: &mut Value, number: usize) -> Option<Value> {
+    let name = if number == 1 { "key" } else { "value" };
+    entry.as_object_mut()?.remove(name)
+}
+
+/// Return the supplied integer, either a JSON number or a string holding one.
+fn integer(value: &Value) -> Option<i128> {
+    match value {
+        Value::Number(number) => match (number.as_i64(), number.as_u64(), number.as_f64()) {
+            (Some(value), _, _) => Some(value as i128),
+            (_, Some(value), _) => Some(value as i128),
+            (_, _, Some(value)) if value.fract() == 0.0 && value.abs() < 1e38 => {
+                Some(value as i128)
+            }
+            _ => None,
+        },
+        Value::String(value) => value.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Return the JSON form of the supplied float, where values which are not finite are strings.
+fn float(value: f64) -> Value {
+    match Number::from_f64(value) {
+        Some(number) => Value::Number(number),
+        None if value.is_nan() => Value::from("NaN"),
+        None if value > 0.0 => Value::from("Infinity"),
+        None => Value::from("-Infinity"),
+    }
+}
+
+/// Decode the supplied zigzag encoded varint.
+fn unzigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+/// Encode the supplied length delimited value of the field with the supplied number.
+fn delimit(tag: u32, value: &[u8], buf: &mut Vec<u8>) {
+    encode_key(tag, WireType::LengthDelimited, buf);
+    encode_varint(value.len() as u64, buf);
+    buf.extend_from_slice(value);
+}
+
+/// Return the path of the named field within the message at the supplied path.
+fn join(path: &str, name: &str) -> String {
+    match path {
+        "" => name.to_owned(),
+        path => format!("{}.{}", path, name),
+    }
+}
+
+/// Create a violation for a field encoded with an unexpected wire type.
+fn mismatch(path: &str, expected: WireType, actual: WireType) -> Error {
+    Error::violation(
+        path,
+        format!("expected wire type {:?} but found {:?}", expected, actual),
+    )
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    use prost::Message;
+    use prost_types::{
+        EnumDescriptorProto, EnumValueDescriptorProto, FileDescriptorProto, FileDescriptorSet,
+        MessageOptions,
+    };
+    use serde_json::json;
+
+    fn field(name: &str, number: i32, ty: Type, label: Label) -> FieldDescriptorProto {
+        FieldDescriptorProto {
+            name: Some(name.to_owned()),
+            number: Some(number),
+            r#type: Some(ty as i32),
+            label: Some(label as i32),
+            ..Default::default()
+        }
+    }
+
+    fn typed(mut field: FieldDescriptorProto, type_name: &str) -> FieldDescriptorProto {
+        field.type_name = Some(type_name.to_owned());
+        field
+    }
+
+    fn schema() -> ProtobufSchema {
+        let set = FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some(String::from("test.proto")),
+                package: Some(String::from("test")),
+                message_type: vec![DescriptorProto {
+                    name: Some(String::from("Order")),
+                    field: vec![
+                        field("order_id", 1, Type::String, Label::Optional),
+                        field("total", 2, Type::Int64, Label::Optional),
+                        field("quantities", 3, Type::Sint32, Label::Repeated),
+                        typed(
+                            field("state", 4, Type::Enum, Label::Optional),
+                            ".test.Order.State",
+                        ),
+                        typed(
+                            field("labels", 5, Type::Message, Label::Repeated),
+                            ".test.Order.LabelsEntry",
+                        ),
+                        typed(
+                            field("line", 6, Type::Message, Label::Optional),
+                            ".test.Order.Line",
+                        ),
+                        field("price", 7, Type::Double, Label::Optional),
+                        field("digest", 8, Type::Bytes, Label::Optional),
+                    ],
+                    nested_type: vec![
+                        DescriptorProto {
+                            name: Some(String::from("LabelsEntry")),
+                            field: vec![
+                                field("key", 1, Type::String, Label::Optional),
+                                field("value", 2, Type::Uint32, Label::Optional),
+                            ],
+                            options: Some(MessageOptions {
+                                map_entry: Some(true),
+                                ..Default::default()
+                            }),
+                            ..Default::default()
+                        },
+                        DescriptorProto {
+                            name: Some(String::from("Line")),
+                            field: vec![
+                                field("sku", 1, Type::String, Label::Optional),
+                                field("fragile", 2, Type::Bool, Label::Optional),
+                            ],
+                            ..Default::default()
+                        },
+                    ],
+                    enum_type: vec![EnumDescriptorProto {
+                        name: Some(String::from("State")),
+                        value: ["PENDING", "SHIPPED"]
+                            .iter()
+                            .zip(0..)
+                            .map(|(name, number)| EnumValueDescriptorProto {
+                                name: Some(String::from(*name)),
+                                number: Some(number),
+                                ..Default::default()
+                            })
+                            .collect(),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+        ProtobufSchema::new(set.encode_to_vec(), String::from("test.Order")).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let schema = schema();
+        let document = json!({
+            "orderId": "o-1",
+            "total": "-42",
+            "quantities": [1, -2, 3],
+            "state": "SHIPPED",
+            "labels": {"a": 1, "b": 2},
+            "line": {"sku": "x-1", "fragile": true},
+            "price": 9.5,
+            "digest": base64::encode(b"digest"),
+        });
+        let data = schema
+            .from_json(&serde_json::to_vec(&document).unwrap())
+            .unwrap();
+        assert!(schema.validate(&data).is_ok());
+        let transcoded = schema.to_json(&data).unwrap();
+        assert_eq!(
+            serde_json::from_slice::<Value>(&transcoded).unwrap(),
+            document
+        );
+
+        // Original field names, numeric enums and integers as numbers are also accepted.
+        let data = schema
+            .from_json(br#"{"order_id": "o-2", "state": 0, "total": 7}"#)
+            .unwrap();
+        assert_eq!(
+            serde_json::from_slice::<Value>(&schema.to_json(&data).unwrap()).unwrap(),
+            json!({"orderId": "o-2", "state": "PENDING", "total": "7"})
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        let schema = schema();
+        let cases = [
+            (&br#"not json"#[..], ""),
+            (br#"[]"#, ""),
+            (br#"{"missing": 1}"#, ""),
+            (br#"{"orderId": 1}"#, "order_id"),
+            (br#"{"quantities": 1}"#, "quantities"),
+            (br#"{"state": "UNKNOWN"}"#, "state"),
+            (br#"{"labels": {"a": -1}}"#, "labels"),
+            (br#"{"line": {"fragile": "yes"}}"#, "line.fragile"),
+            (br#"{"digest": "!"}"#, "digest"),
+        ];
+        for (data, expected) in cases {
+            match schema.from_json(data) {
+                Err(Error::Violation { path, .. }) => assert_eq!(path, expected),
+                other => panic!("expected a violation, got {:?}", other),
+            }
+        }
+
+        // A name field encoded as fixed32.
+        match schema.to_json(&[0x0d, 0x01, 0x00, 0x00, 0x00]) {
+            Err(Error::Violation { path, .. }) => assert_eq!(path, "order_id"),
+            other => panic!("expected a violation, got {:?}", other),
+        }
+    }
+}
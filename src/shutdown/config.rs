@@ -0,0 +1,20 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// extern usings
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, StructOpt)]
+/// Rift shutdown configuration.
+pub struct Config {
+    #[structopt(
+        long = "shutdown-grace-ms",
+        env = "RIFT_SHUTDOWN_GRACE_MS",
+        help = "How long to wait for in-flight requests to finish when shutting down.",
+        long_help = "Sets the time in milliseconds that riftd waits on SIGTERM or SIGINT for in-flight requests to finish and subscribe streams to drain, after which it exits regardless.",
+        default_value = "30000",
+        takes_value = true
+    )]
+    /// Define how long to wait for in-flight requests when shutting down.
+    pub grace_ms: u64,
+}
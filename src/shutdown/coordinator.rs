@@ -0,0 +1,109 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::future::Future;
+use std::sync::Arc;
+
+// extern usings
+use tokio::sync::watch;
+
+/// Coordinates shutting down, notifying every clone once shutdown begins so that each
+/// component can stop taking on new work and finish what it has in flight.
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    tx: Arc<watch::Sender<bool>>,
+    rx: watch::Receiver<bool>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shutdown {
+    /// Create a new coordinator which has not begun shutting down.
+    pub fn new() -> Self {
+        let (tx, rx) = watch::channel(false);
+        Self {
+            tx: Arc::new(tx),
+            rx,
+        }
+    }
+
+    /// Begin shutting down, returning whether shutdown had already begun.
+    pub fn trigger(&self) -> bool {
+        let triggered = self.is_triggered();
+        // A receiver is held by self, so sending can not fail.
+        let _ = self.tx.send(true);
+        triggered
+    }
+
+    /// Return whether shutdown has begun.
+    pub fn is_triggered(&self) -> bool {
+        *self.rx.borrow()
+    }
+
+    /// Return a future which completes once shutdown begins.
+    pub fn wait(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut rx = self.rx.clone();
+        async move {
+            while !*rx.borrow() {
+                if rx.changed().await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Begin shutting down on the first SIGTERM or SIGINT received.
+    #[cfg(unix)]
+    pub async fn watch_signals(self, logger: slog::Logger) {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let signals = signal(SignalKind::terminate())
+            .and_then(|terms| Ok((terms, signal(SignalKind::interrupt())?)));
+        let (mut terms, mut ints) = match signals {
+            Ok(signals) => signals,
+            Err(err) => {
+                error!(&logger, "Failed to register shutdown signal handlers."; "error" => err.to_string());
+                return;
+            }
+        };
+        let received = tokio::select! {
+            _ = terms.recv() => "SIGTERM",
+            _ = ints.recv() => "SIGINT",
+        };
+        info!(&logger, "Received signal, shutting down."; "signal" => received);
+        self.trigger();
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    #[test]
+    fn test_shutdown() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let shutdown = Shutdown::new();
+        let waiting = runtime.spawn(shutdown.wait());
+        assert!(!shutdown.is_triggered());
+        let pending = runtime.block_on(async {
+            tokio::time::timeout(Duration::from_millis(10), shutdown.wait()).await
+        });
+        assert!(pending.is_err());
+
+        let clone = shutdown.clone();
+        assert!(!clone.trigger());
+        assert!(shutdown.is_triggered());
+        assert!(shutdown.trigger());
+        runtime.block_on(waiting).unwrap();
+        // Waiting after shutdown has begun completes immediately.
+        runtime.block_on(shutdown.wait());
+    }
+}
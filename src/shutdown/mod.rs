@@ -0,0 +1,8 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+mod config;
+mod coordinator;
+
+pub use config::Config;
+pub use coordinator::Shutdown;
@@ -0,0 +1,90 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::path::PathBuf;
+use std::str::FromStr;
+
+// crate usings
+use super::{Error, Result};
+
+// extern usings
+use structopt::StructOpt;
+
+/// How records are delimited within tailed files.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// Each newline terminated line is a record.
+    Line,
+    /// Each record is prefixed by its length as a big-endian 32 bit integer.
+    LengthPrefixed,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    /// Handles converting the supplied &str to a Format.
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// let x = librift::source::Format::from_str("length-prefixed");
+    /// assert_eq!(x.unwrap(), librift::source::Format::LengthPrefixed);
+    /// ```
+    fn from_str(format: &str) -> Result<Format> {
+        match format {
+            "line" => Ok(Format::Line),
+            "length-prefixed" => Ok(Format::LengthPrefixed),
+            _ => Err(Error::InvalidFormat {
+                format: format.to_owned(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, StructOpt)]
+/// Rift source connector configuration.
+pub struct Config {
+    #[structopt(
+        long = "tail",
+        env = "RIFT_TAIL",
+        help = "The files to tail and publish to a topic.",
+        long_help = "Sets the comma separated list of files to tail, each as `path=topic`. The file name of the path may contain `*` and `?` wildcards to tail every matching file within its directory. Every record appended to a tailed file is published to the topic.",
+        use_delimiter = true,
+        takes_value = true
+    )]
+    /// Define the files to tail and the topics to publish them to.
+    pub tails: Vec<String>,
+
+    #[structopt(
+        long = "tail-format",
+        env = "RIFT_TAIL_FORMAT",
+        help = "How records are delimited within tailed files.",
+        long_help = "Selects whether each line of a tailed file is a record, or each record is prefixed by its length as a big-endian 32 bit integer.",
+        default_value = "line",
+        possible_values = &["line", "length-prefixed"],
+        takes_value = true
+    )]
+    /// Define how records are delimited within tailed files.
+    pub format: Format,
+
+    #[structopt(
+        long = "tail-checkpoint",
+        env = "RIFT_TAIL_CHECKPOINT",
+        help = "The file to checkpoint tailed positions to.",
+        long_help = "Sets the file that the position of every tailed file is checkpointed to after each poll, so that tailing resumes where it left off after a restart. Positions are only kept in memory when no checkpoint is supplied.",
+        takes_value = true
+    )]
+    /// Define the file to checkpoint tailed positions to, if any.
+    pub checkpoint: Option<PathBuf>,
+
+    #[structopt(
+        long = "tail-interval-ms",
+        env = "RIFT_TAIL_INTERVAL_MS",
+        help = "How often tailed files are polled for new records.",
+        long_help = "Sets the interval in milliseconds between polls of the tailed files for newly appended records.",
+        default_value = "1000",
+        takes_value = true
+    )]
+    /// Define how often tailed files are polled for new records.
+    pub interval_ms: u64,
+}
@@ -0,0 +1,37 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::io;
+use std::result;
+
+// extern usings
+use thiserror::Error;
+
+/// Custom Result wrapper to simplify usage.
+pub type Result<T> = result::Result<T, Error>;
+
+/// Represents source connector errors based on user configuration or failures reading sources.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// Handles invalid `--tail` entries.
+    #[error("the supplied tail '{tail}' is invalid: {reason}")]
+    InvalidTail {
+        /// The invalid tail entry.
+        tail: String,
+        /// The reason the entry is invalid.
+        reason: String,
+    },
+    /// Handles invalid `--tail-format` values.
+    #[error("the supplied tail format '{format}' is invalid")]
+    InvalidFormat {
+        /// The invalid format.
+        format: String,
+    },
+    /// Handles failures reading tailed files, or reading and writing checkpoints.
+    #[error("failed to read source: {0}")]
+    Io(#[from] io::Error),
+    /// Handles malformed checkpoints.
+    #[error("failed to parse checkpoint: {0}")]
+    Checkpoint(#[from] serde_json::Error),
+}
@@ -0,0 +1,483 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, Metadata};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+// crate usings
+use super::{Config, Error, Format, Result};
+use crate::grpc::pubsub::Message;
+use crate::pubsub::Registry;
+
+// extern usings
+use bytes::Bytes;
+use prost_types::Timestamp;
+
+/// The attribute carrying the path of the file a tailed record was read from.
+pub const PATH_ATTRIBUTE: &str = "path";
+
+/// The count of bytes read from a tailed file at a time, unless a single record is larger.
+const READ_CHUNK: u64 = 1024 * 1024;
+
+/// Return the identity of the supplied file, which it keeps as it is renamed, such as when it
+/// is rotated, and which a file replacing it does not share.
+#[cfg(unix)]
+fn identity(meta: &Metadata) -> u64 {
+    std::os::unix::fs::MetadataExt::ino(meta)
+}
+
+/// Return the identity of the supplied file, which it keeps as it is renamed, such as when it
+/// is rotated, and which a file replacing it does not share.
+#[cfg(not(unix))]
+fn identity(meta: &Metadata) -> u64 {
+    meta.created()
+        .ok()
+        .and_then(|created| created.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0, |created| created.as_nanos() as u64)
+}
+
+/// A set of files to tail, and the topic to publish their records to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tail {
+    /// The directory containing the tailed files.
+    pub dir: PathBuf,
+    /// The file name, or wildcard pattern matching the file names, to tail.
+    pub pattern: String,
+    /// The topic to publish records to.
+    pub topic: String,
+}
+
+impl FromStr for Tail {
+    type Err = Error;
+
+    /// Handles converting the supplied `path=topic` &str to a Tail.
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// let x = librift::source::Tail::from_str("/var/log/app/*.log=logs").unwrap();
+    /// assert_eq!(x.pattern, "*.log");
+    /// assert_eq!(x.topic, "logs");
+    /// ```
+    fn from_str(tail: &str) -> Result<Tail> {
+        let invalid = |reason: &str| Error::InvalidTail {
+            tail: tail.to_owned(),
+            reason: reason.to_owned(),
+        };
+        let (path, topic) = tail
+            .rsplit_once('=')
+            .ok_or_else(|| invalid("expected `path=topic`"))?;
+        if topic.is_empty() {
+            return Err(invalid("a topic is required"));
+        }
+        let path = Path::new(path);
+        let pattern = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| invalid("a file name is required"))?;
+        let dir = match path.parent() {
+            Some(dir) if dir.as_os_str().is_empty() => PathBuf::from("."),
+            Some(dir) => dir.to_path_buf(),
+            None => PathBuf::from("."),
+        };
+        Ok(Tail {
+            dir,
+            pattern: pattern.to_owned(),
+            topic: topic.to_owned(),
+        })
+    }
+}
+
+impl Tail {
+    /// List the files currently matching this tail, sorted by path.
+    pub fn paths(&self) -> Result<Vec<PathBuf>> {
+        if !self.pattern.contains(&['*', '?'][..]) {
+            let path = self.dir.join(&self.pattern);
+            return Ok(if path.is_file() {
+                vec![path]
+            } else {
+                Vec::new()
+            });
+        }
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let matched = name
+                .to_str()
+                .map(|name| matches(self.pattern.as_bytes(), name.as_bytes()))
+                .unwrap_or(false);
+            if matched && entry.file_type()?.is_file() {
+                paths.push(entry.path());
+            }
+        }
+        paths.sort();
+        Ok(paths)
+    }
+}
+
+/// Return whether the supplied name matches the supplied pattern, where `*` matches any run of
+/// characters and `?` matches any single character.
+fn matches(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            matches(&pattern[1..], name) || (!name.is_empty() && matches(pattern, &name[1..]))
+        }
+        (Some(b'?'), Some(_)) => matches(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p == n => matches(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+/// Split the complete records off the front of the supplied data, each along with the offset
+/// just past its end. Any trailing partial record is left to a later poll.
+fn split(format: Format, data: &[u8]) -> Vec<(&[u8], usize)> {
+    let mut records = Vec::new();
+    let mut consumed = 0;
+    match format {
+        Format::Line => {
+            while let Some(end) = data[consumed..].iter().position(|b| *b == b'\n') {
+                let line = &data[consumed..consumed + end];
+                let line = line.strip_suffix(b"\r").unwrap_or(line);
+                consumed += end + 1;
+                if !line.is_empty() {
+                    records.push((line, consumed));
+                }
+            }
+        }
+        Format::LengthPrefixed => {
+            while data.len() - consumed >= 4 {
+                let mut len = [0; 4];
+                len.copy_from_slice(&data[consumed..consumed + 4]);
+                let len = u32::from_be_bytes(len) as usize;
+                if data.len() - consumed - 4 < len {
+                    break;
+                }
+                records.push((&data[consumed + 4..consumed + 4 + len], consumed + 4 + len));
+                consumed += 4 + len;
+            }
+        }
+    }
+    records
+}
+
+/// Parse the positions of a checkpoint. Checkpoints keyed by the path of each file, as written
+/// before positions were kept by identity, are keyed by the identity of the file currently at
+/// each path instead.
+fn load(checkpoint: &[u8]) -> Result<HashMap<u64, u64>> {
+    if let Ok(positions) = serde_json::from_slice(checkpoint) {
+        return Ok(positions);
+    }
+    let positions: HashMap<PathBuf, u64> = serde_json::from_slice(checkpoint)?;
+    Ok(positions
+        .into_iter()
+        .filter_map(|(path, position)| {
+            let meta = std::fs::metadata(path).ok()?;
+            Some((identity(&meta), position))
+        })
+        .collect())
+}
+
+/// Tails a set of files, publishing every record appended to them to a topic and
+/// checkpointing how far into each file has been published. Positions are kept by the identity
+/// of each file rather than its path, so that a file rotated into place of another is read
+/// from its start, and one rotated away keeps its position.
+#[derive(Debug)]
+pub struct Tailer {
+    tails: Vec<Tail>,
+    format: Format,
+    interval: Duration,
+    checkpoint: Option<PathBuf>,
+    positions: HashMap<u64, u64>,
+    registry: Registry<Message>,
+    logger: slog::Logger,
+}
+
+impl Tailer {
+    /// Create a new tailer based on the supplied configuration, resuming from its checkpoint if
+    /// one exists. [None] is returned if no files are configured to be tailed.
+    pub fn new(
+        cfg: &Config,
+        registry: Registry<Message>,
+        logger: slog::Logger,
+    ) -> Result<Option<Self>> {
+        if cfg.tails.is_empty() {
+            return Ok(None);
+        }
+        let tails = cfg
+            .tails
+            .iter()
+            .map(|tail| Tail::from_str(tail))
+            .collect::<Result<Vec<Tail>>>()?;
+        let positions = match &cfg.checkpoint {
+            Some(checkpoint) if checkpoint.exists() => load(&std::fs::read(checkpoint)?)?,
+            _ => HashMap::new(),
+        };
+        Ok(Some(Self {
+            tails,
+            format: cfg.format,
+            interval: Duration::from_millis(cfg.interval_ms),
+            checkpoint: cfg.checkpoint.clone(),
+            positions,
+            registry,
+            logger,
+        }))
+    }
+
+    /// Publish every complete record appended to the tailed files since the last poll, and
+    /// checkpoint the new positions. The count of published records is returned.
+    pub fn poll(&mut self) -> Result<usize> {
+        let (mut published, mut seen) = (0, HashSet::new());
+        for tail in self.tails.clone() {
+            for path in tail.paths()? {
+                match self.poll_file(&tail.topic, &path, &mut seen) {
+                    Ok(count) => published += count,
+                    // Files removed since they were listed, such as by rotation, are skipped.
+                    Err(Error::Io(err)) if err.kind() == io::ErrorKind::NotFound => {
+                        debug!(&self.logger, "Tailed file vanished, skipping."; "path" => path.display().to_string());
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+        // Positions of files which no longer exist are dropped, so that a file later reusing
+        // their identity is read from its start.
+        let before = self.positions.len();
+        self.positions.retain(|identity, _| seen.contains(identity));
+        if published > 0 || self.positions.len() != before {
+            self.save()?;
+        }
+        Ok(published)
+    }
+
+    fn poll_file(
+        &mut self,
+        topic_name: &str,
+        path: &Path,
+        seen: &mut HashSet<u64>,
+    ) -> Result<usize> {
+        let mut file = File::open(path)?;
+        let meta = file.metadata()?;
+        let (identity, len) = (identity(&meta), meta.len());
+        seen.insert(identity);
+        let mut position = self.positions.get(&identity).copied().unwrap_or(0);
+        if len < position {
+            info!(&self.logger, "Tailed file was truncated, reading from the start."; "path" => path.display().to_string());
+            position = 0;
+        }
+        if len == position {
+            return Ok(0);
+        }
+
+        let topic = match self.registry.get(topic_name) {
+            Some(topic) => topic,
+            None => {
+                warn!(&self.logger, "Tailed topic does not exist, retrying."; "topic" => topic_name);
+                return Ok(0);
+            }
+        };
+
+        // The file is read a chunk at a time, so that a large backlog is not read into memory
+        // at once. A chunk is only grown when it does not hold a single complete record.
+        let (mut total, mut chunk) = (0, READ_CHUNK);
+        while position < len {
+            let size = (len - position).min(chunk);
+            let mut data = Vec::with_capacity(size as usize);
+            file.seek(SeekFrom::Start(position))?;
+            (&mut file).take(size).read_to_end(&mut data)?;
+
+            let data = Bytes::from(data);
+            let records = split(self.format, &data);
+            if records.is_empty() {
+                // Any trailing partial record is left to a later poll.
+                if position + size >= len {
+                    break;
+                }
+                chunk *= 2;
+                continue;
+            }
+            let msgs = records
+                .iter()
+                .map(|(record, _)| {
+                    let mut msg = Message {
+                        topic: topic_name.to_owned(),
+                        published: Some(Timestamp::from(SystemTime::now())),
+                        data: data.slice_ref(record),
+                        ..Default::default()
+                    };
+                    msg.attributes
+                        .insert(String::from(PATH_ATTRIBUTE), path.display().to_string());
+                    msg
+                })
+                .collect::<Vec<_>>();
+            // Stop at the first failure without advancing past it, so the rest are retried.
+            let (published, res) = topic.push_batch(msgs);
+            let advanced = published.checked_sub(1).map_or(0, |last| records[last].1);
+            position += advanced as u64;
+            total += published;
+            self.positions.insert(identity, position);
+            if let Err(err) = res {
+                warn!(&self.logger, "Failed to publish tailed record, retrying."; "topic" => topic_name, "error" => err.to_string());
+                break;
+            }
+            chunk = READ_CHUNK;
+        }
+        Ok(total)
+    }
+
+    /// Write the current positions to the checkpoint file, if any, atomically replacing the
+    /// previous checkpoint.
+    fn save(&self) -> Result<()> {
+        let checkpoint = match &self.checkpoint {
+            Some(checkpoint) => checkpoint,
+            None => return Ok(()),
+        };
+        let tmp = checkpoint.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec(&self.positions)?)?;
+        std::fs::rename(tmp, checkpoint)?;
+        Ok(())
+    }
+
+    /// Poll the tailed files on every interval, forever.
+    pub async fn run(mut self) {
+        let mut interval = tokio::time::interval(self.interval);
+        loop {
+            interval.tick().await;
+            // Reading files blocks, so each poll is moved off of the async runtime.
+            self = tokio::task::spawn_blocking(move || {
+                if let Err(err) = self.poll() {
+                    warn!(&self.logger, "Failed to poll tailed files."; "error" => err.to_string());
+                }
+                self
+            })
+            .await
+            .expect("tail poll panicked");
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    use std::io::Write;
+
+    #[test]
+    fn test_tail() {
+        let tail = Tail::from_str("/var/log/*.log=logs").unwrap();
+        assert_eq!(tail.dir, PathBuf::from("/var/log"));
+        assert_eq!(tail.pattern, "*.log");
+        assert_eq!(tail.topic, "logs");
+
+        assert!(Tail::from_str("/var/log/app.log").is_err());
+        assert!(Tail::from_str("/var/log/app.log=").is_err());
+        assert!(Tail::from_str("/=logs").is_err());
+
+        assert!(matches(b"*.log", b"app.log"));
+        assert!(matches(b"app-?.log", b"app-1.log"));
+        assert!(!matches(b"*.log", b"app.log.1"));
+        assert!(!matches(b"app-?.log", b"app-10.log"));
+    }
+
+    #[test]
+    fn test_split() {
+        let records = split(Format::Line, b"one\r\n\ntwo\nthr");
+        assert_eq!(records, vec![(&b"one"[..], 5), (&b"two"[..], 10)]);
+
+        let records = split(
+            Format::LengthPrefixed,
+            &[0, 0, 0, 2, b'h', b'i', 0, 0, 0, 3, b'x'],
+        );
+        assert_eq!(records, vec![(&b"hi"[..], 6)]);
+    }
+
+    #[test]
+    fn test_tailer() {
+        let dir = std::env::temp_dir().join(format!("rift-tail-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let checkpoint = dir.join("checkpoint.json");
+        let cfg = Config {
+            tails: vec![format!("{}/*.log=logs", dir.display())],
+            format: Format::Line,
+            checkpoint: Some(checkpoint.clone()),
+            interval_ms: 1000,
+        };
+        let registry = Registry::default();
+        let logger = slog::Logger::root(slog::Discard, o!());
+        let mut tailer = Tailer::new(&cfg, registry.clone(), logger.clone())
+            .unwrap()
+            .unwrap();
+
+        let path = dir.join("app.log");
+        let mut file = File::create(&path).unwrap();
+        file.write_all(b"one\ntw").unwrap();
+
+        // Records are not consumed until the topic exists.
+        assert_eq!(tailer.poll().unwrap(), 0);
+        let topic = registry.create(String::from("logs"));
+        let sub = topic.create(String::from("sub"));
+        assert_eq!(tailer.poll().unwrap(), 1);
+
+        file.write_all(b"o\n").unwrap();
+        assert_eq!(tailer.poll().unwrap(), 1);
+        assert_eq!(tailer.poll().unwrap(), 0);
+
+        let (_, _, first) = sub.queue.next().unwrap();
+        assert_eq!(first.data, &b"one"[..]);
+        assert_eq!(first.attributes[PATH_ATTRIBUTE], path.display().to_string());
+        let (_, _, second) = sub.queue.next().unwrap();
+        assert_eq!(second.data, &b"two"[..]);
+
+        // A new tailer resumes from the checkpoint.
+        let mut tailer = Tailer::new(&cfg, registry, logger).unwrap().unwrap();
+        assert_eq!(tailer.poll().unwrap(), 0);
+
+        // A file rotated into place of another is read from its start, even though it is no
+        // shorter than the position reached in the file it replaced.
+        drop(file);
+        std::fs::rename(&path, dir.join("app.log.1")).unwrap();
+        std::fs::write(&path, b"three\n").unwrap();
+        assert_eq!(tailer.poll().unwrap(), 1);
+        let (_, _, third) = sub.queue.next().unwrap();
+        assert_eq!(third.data, &b"three"[..]);
+
+        // Records larger than a single read are still read whole.
+        let mut large = vec![b'x'; READ_CHUNK as usize + 1];
+        large.extend_from_slice(b"\nfour\n");
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(&large)
+            .unwrap();
+        assert_eq!(tailer.poll().unwrap(), 2);
+        let (_, _, fourth) = sub.queue.next().unwrap();
+        assert_eq!(fourth.data.len(), READ_CHUNK as usize + 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_load() {
+        let path = std::env::temp_dir().join(format!("rift-tail-{}.log", uuid::Uuid::new_v4()));
+        std::fs::write(&path, b"one\n").unwrap();
+        let id = identity(&std::fs::metadata(&path).unwrap());
+
+        let positions = load(format!("{{\"{}\": 4}}", id).as_bytes()).unwrap();
+        assert_eq!(positions, HashMap::from([(id, 4)]));
+
+        // Checkpoints keyed by path are keyed by identity instead, skipping vanished files.
+        let legacy = serde_json::json!({path.display().to_string(): 4, "/nonexistent.log": 2});
+        let positions = load(legacy.to_string().as_bytes()).unwrap();
+        assert_eq!(positions, HashMap::from([(id, 4)]));
+        assert!(load(b"[]").is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,22 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::path::PathBuf;
+
+// extern usings
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, StructOpt)]
+/// Rift per-tenant quota configuration.
+pub struct Config {
+    #[structopt(
+        long = "tenant-quota-file",
+        env = "RIFT_TENANT_QUOTA_FILE",
+        help = "The file of the quotas enforced per tenant.",
+        long_help = "Sets the JSON file declaring the publish rates and stored messages and bytes each tenant, that is each topic namespace, is allowed. Publishes exceeding a quota are rejected with RESOURCE_EXHAUSTED. Usage is tracked per tenant regardless, and no quotas are enforced when no file is supplied.",
+        takes_value = true
+    )]
+    /// Define the file of the quotas enforced per tenant, if any.
+    pub quota_file: Option<PathBuf>,
+}
@@ -0,0 +1,46 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::io;
+use std::result;
+
+// extern usings
+use thiserror::Error;
+
+/// Custom Result wrapper to simplify usage.
+pub type Result<T> = result::Result<T, Error>;
+
+/// Represents errors loading tenant quotas, and enforcing them.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// Handles failures reading the quota file.
+    #[error("failed to read '{path}': {source}")]
+    Io {
+        /// The path that failed to be read.
+        path: String,
+        /// The initial error cause.
+        source: io::Error,
+    },
+    /// Handles quota files which are not valid JSON.
+    #[error("failed to parse the tenant quotas: {0}")]
+    Parse(#[from] serde_json::Error),
+    /// Handles quota files which do not describe a valid set of quotas.
+    #[error("invalid tenant quotas at '{location}': {reason}")]
+    Invalid {
+        /// The location of the invalid entry within the quota file.
+        location: String,
+        /// The reason the entry is invalid.
+        reason: String,
+    },
+    /// Handles publishes which would exceed a quota of their tenant.
+    #[error("the tenant '{tenant}' has reached its quota of {limit} {quota}")]
+    QuotaExceeded {
+        /// The tenant whose quota was reached.
+        tenant: String,
+        /// The quota which was reached.
+        quota: &'static str,
+        /// The configured quota.
+        limit: u64,
+    },
+}
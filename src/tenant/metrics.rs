@@ -0,0 +1,70 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+use prometheus::{IntCounterVec, IntGaugeVec};
+
+use crate::metric::{self, Manager, Opt};
+
+const TENANT_LABEL: &str = "tenant";
+const QUOTA_LABEL: &str = "quota";
+
+/// The set of labeled metrics tracking the usage of every tenant. Tenants are namespaces,
+/// whose count is bounded by the topic limits, so no cardinality limit applies.
+#[derive(Debug, Clone)]
+pub(super) struct Metrics {
+    pub(super) published_messages: IntCounterVec,
+    pub(super) published_bytes: IntCounterVec,
+    pub(super) consumed_messages: IntCounterVec,
+    pub(super) consumed_bytes: IntCounterVec,
+    pub(super) stored_messages: IntGaugeVec,
+    pub(super) stored_bytes: IntGaugeVec,
+    pub(super) rejections: IntCounterVec,
+}
+
+impl Metrics {
+    /// Register the tenant metrics using the supplied manager.
+    pub(super) fn new(mm: &Manager) -> metric::Result<Self> {
+        let labels = |labels: &[&str]| {
+            Some(vec![Opt::Labels(
+                labels.iter().map(|label| label.to_string()).collect(),
+            )])
+        };
+        Ok(Self {
+            published_messages: mm.register_int_counter_vec(
+                "published_messages_total",
+                "The total count of messages published by tenant.",
+                labels(&[TENANT_LABEL]),
+            )?,
+            published_bytes: mm.register_int_counter_vec(
+                "published_bytes_total",
+                "The total count of message bytes published by tenant.",
+                labels(&[TENANT_LABEL]),
+            )?,
+            consumed_messages: mm.register_int_counter_vec(
+                "consumed_messages_total",
+                "The total count of messages delivered by tenant.",
+                labels(&[TENANT_LABEL]),
+            )?,
+            consumed_bytes: mm.register_int_counter_vec(
+                "consumed_bytes_total",
+                "The total count of message bytes delivered by tenant.",
+                labels(&[TENANT_LABEL]),
+            )?,
+            stored_messages: mm.register_int_gauge_vec(
+                "stored_messages",
+                "The current count of unacked messages held across every subscription by tenant.",
+                labels(&[TENANT_LABEL]),
+            )?,
+            stored_bytes: mm.register_int_gauge_vec(
+                "stored_bytes",
+                "The current count of message bytes held across every subscription by tenant.",
+                labels(&[TENANT_LABEL]),
+            )?,
+            rejections: mm.register_int_counter_vec(
+                "quota_rejections_total",
+                "The total count of publishes rejected by tenant and the quota they exceeded.",
+                labels(&[TENANT_LABEL, QUOTA_LABEL]),
+            )?,
+        })
+    }
+}
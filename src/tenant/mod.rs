@@ -0,0 +1,394 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+mod config;
+mod error;
+mod metrics;
+mod quota;
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use dashmap::DashMap;
+
+use crate::metric::{self, Manager};
+use crate::pubsub::{self, namespace, Registry, Throttle};
+
+pub use config::Config;
+pub use error::{Error, Result};
+use metrics::Metrics;
+pub use quota::{
+    Quota, Quotas, PUBLISHED_BYTES_PER_SEC, PUBLISHED_MESSAGES_PER_SEC, STORED_BYTES,
+    STORED_MESSAGES,
+};
+
+/// A snapshot of the usage of a single tenant, counted since riftd started.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Usage {
+    /// The name of the tenant, which is the namespace of its topics.
+    pub tenant: String,
+    /// The quota the tenant is held to.
+    pub quota: Quota,
+    /// The count of messages published.
+    pub published_messages: u64,
+    /// The count of message data bytes published.
+    pub published_bytes: u64,
+    /// The count of messages delivered to subscribers.
+    pub consumed_messages: u64,
+    /// The count of message data bytes delivered to subscribers.
+    pub consumed_bytes: u64,
+    /// The count of unacked messages held across every subscription, as last sampled.
+    pub stored_messages: u64,
+    /// The count of message bytes held across every subscription, as last sampled.
+    pub stored_bytes: u64,
+    /// The count of publishes rejected for exceeding a quota.
+    pub rejected: u64,
+}
+
+#[derive(Debug)]
+struct Tenant {
+    quota: Quota,
+    messages: Throttle,
+    bytes: Throttle,
+    published_messages: AtomicU64,
+    published_bytes: AtomicU64,
+    consumed_messages: AtomicU64,
+    consumed_bytes: AtomicU64,
+    stored_messages: AtomicU64,
+    stored_bytes: AtomicU64,
+    rejected: AtomicU64,
+}
+
+impl Tenant {
+    fn new(quota: Quota) -> Self {
+        // Each rate is throttled separately, so that rejections name the one exceeded.
+        let (messages, bytes) = (Throttle::default(), Throttle::default());
+        messages.set(pubsub::Quota {
+            messages_per_sec: quota.published_messages_per_sec,
+            bytes_per_sec: 0,
+        });
+        bytes.set(pubsub::Quota {
+            messages_per_sec: 0,
+            bytes_per_sec: quota.published_bytes_per_sec,
+        });
+        Self {
+            quota,
+            messages,
+            bytes,
+            published_messages: AtomicU64::default(),
+            published_bytes: AtomicU64::default(),
+            consumed_messages: AtomicU64::default(),
+            consumed_bytes: AtomicU64::default(),
+            stored_messages: AtomicU64::default(),
+            stored_bytes: AtomicU64::default(),
+            rejected: AtomicU64::default(),
+        }
+    }
+
+    /// Return the first quota publishing a message of the supplied size would exceed.
+    fn exceeded(&self, bytes: usize) -> Option<(&'static str, u64)> {
+        let stored_messages = self.stored_messages.load(Ordering::Relaxed);
+        let stored_bytes = self.stored_bytes.load(Ordering::Relaxed) + bytes as u64;
+        let quota = &self.quota;
+        if quota.stored_messages > 0 && stored_messages >= quota.stored_messages {
+            return Some((STORED_MESSAGES, quota.stored_messages));
+        }
+        if quota.stored_bytes > 0 && stored_bytes > quota.stored_bytes {
+            return Some((STORED_BYTES, quota.stored_bytes));
+        }
+        if self.messages.delay().is_some() {
+            return Some((PUBLISHED_MESSAGES_PER_SEC, quota.published_messages_per_sec));
+        }
+        if self.bytes.delay().is_some() {
+            return Some((PUBLISHED_BYTES_PER_SEC, quota.published_bytes_per_sec));
+        }
+        None
+    }
+
+    fn usage(&self, tenant: &str) -> Usage {
+        Usage {
+            tenant: tenant.to_owned(),
+            quota: self.quota,
+            published_messages: self.published_messages.load(Ordering::Relaxed),
+            published_bytes: self.published_bytes.load(Ordering::Relaxed),
+            consumed_messages: self.consumed_messages.load(Ordering::Relaxed),
+            consumed_bytes: self.consumed_bytes.load(Ordering::Relaxed),
+            stored_messages: self.stored_messages.load(Ordering::Relaxed),
+            stored_bytes: self.stored_bytes.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Tenants accounts the usage of every tenant, that is every namespace of topics, and
+/// enforces their quotas on publish. Without [Quotas] usage is still accounted, but nothing
+/// is enforced. Clones share the same usage.
+///
+/// Stored messages and bytes are sampled periodically from the subscriptions of each tenant,
+/// so the storage quotas may be briefly overshot between samples.
+#[derive(Debug, Clone, Default)]
+pub struct Tenants {
+    quotas: Arc<Quotas>,
+    tenants: Arc<DashMap<Arc<str>, Arc<Tenant>>>,
+    metrics: Option<Metrics>,
+}
+
+impl Tenants {
+    /// Create new tenants enforcing the quota file of the supplied configuration, if any.
+    pub fn new(cfg: &Config) -> Result<Self> {
+        let quotas = match &cfg.quota_file {
+            Some(path) => Quotas::load(path)?,
+            None => Quotas::default(),
+        };
+        Ok(Self::with_quotas(quotas))
+    }
+
+    /// Create new tenants enforcing the supplied quotas.
+    pub fn with_quotas(quotas: Quotas) -> Self {
+        Self {
+            quotas: Arc::new(quotas),
+            ..Default::default()
+        }
+    }
+
+    /// Register and record the usage of every tenant into metrics using the supplied manager.
+    pub fn with_metrics(mut self, mm: &Manager) -> metric::Result<Self> {
+        self.metrics = Some(Metrics::new(mm)?);
+        Ok(self)
+    }
+
+    /// Return the tenant owning the supplied topic, along with its name.
+    fn tenant(&self, topic: &str) -> (Arc<str>, Arc<Tenant>) {
+        let name = namespace::of(topic);
+        if let Some(tenant) = self.tenants.get(name) {
+            return (tenant.key().clone(), tenant.value().clone());
+        }
+        let tenant = self
+            .tenants
+            .entry(Arc::from(name))
+            .or_insert_with(|| Arc::new(Tenant::new(self.quotas.get(name))));
+        (tenant.key().clone(), tenant.value().clone())
+    }
+
+    /// Admit a message of the supplied size for publishing to the supplied topic, failing if
+    /// it would exceed a quota of the topic's tenant. Admitted messages are counted against
+    /// the publish rates, but are only accounted once [Tenants::published].
+    pub fn admit(&self, topic: &str, bytes: usize) -> Result<()> {
+        let (name, tenant) = self.tenant(topic);
+        if let Some((quota, limit)) = tenant.exceeded(bytes) {
+            tenant.rejected.fetch_add(1, Ordering::Relaxed);
+            if let Some(metrics) = &self.metrics {
+                metrics.rejections.with_label_values(&[&name, quota]).inc();
+            }
+            return Err(Error::QuotaExceeded {
+                tenant: name.to_string(),
+                quota,
+                limit,
+            });
+        }
+        tenant.messages.consume(bytes);
+        tenant.bytes.consume(bytes);
+        Ok(())
+    }
+
+    /// Account a message of the supplied size published to the supplied topic.
+    pub fn published(&self, topic: &str, bytes: usize) {
+        let (name, tenant) = self.tenant(topic);
+        tenant.published_messages.fetch_add(1, Ordering::Relaxed);
+        tenant
+            .published_bytes
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+        if let Some(metrics) = &self.metrics {
+            metrics.published_messages.with_label_values(&[&name]).inc();
+            metrics
+                .published_bytes
+                .with_label_values(&[&name])
+                .inc_by(bytes as u64);
+        }
+    }
+
+    /// Account a message of the supplied size delivered from the supplied topic.
+    pub fn consumed(&self, topic: &str, bytes: usize) {
+        let (name, tenant) = self.tenant(topic);
+        tenant.consumed_messages.fetch_add(1, Ordering::Relaxed);
+        tenant
+            .consumed_bytes
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+        if let Some(metrics) = &self.metrics {
+            metrics.consumed_messages.with_label_values(&[&name]).inc();
+            metrics
+                .consumed_bytes
+                .with_label_values(&[&name])
+                .inc_by(bytes as u64);
+        }
+    }
+
+    /// Sample the messages and bytes held by the subscriptions of every tenant within the
+    /// supplied registry. Tenants without any topics left hold nothing.
+    pub fn sample<T: Clone>(&self, registry: &Registry<T>) {
+        let mut stored: HashMap<Arc<str>, (usize, usize)> = HashMap::new();
+        registry.iter(|topics| {
+            for (name, topic) in topics {
+                let (name, _) = self.tenant(name);
+                let footprint = stored.entry(name).or_default();
+                topic.iter(|subs| {
+                    for (_, sub) in subs {
+                        let (messages, bytes) = sub.queue.footprint();
+                        footprint.0 += messages;
+                        footprint.1 += bytes;
+                    }
+                });
+            }
+        });
+        let tenants = self
+            .tenants
+            .iter()
+            .map(|tenant| (tenant.key().clone(), tenant.value().clone()))
+            .collect::<Vec<_>>();
+        for (name, tenant) in tenants {
+            let (messages, bytes) = stored.get(&name).copied().unwrap_or_default();
+            tenant
+                .stored_messages
+                .store(messages as u64, Ordering::Relaxed);
+            tenant.stored_bytes.store(bytes as u64, Ordering::Relaxed);
+            if let Some(metrics) = &self.metrics {
+                metrics
+                    .stored_messages
+                    .with_label_values(&[&name])
+                    .set(messages as i64);
+                metrics
+                    .stored_bytes
+                    .with_label_values(&[&name])
+                    .set(bytes as i64);
+            }
+        }
+    }
+
+    /// Sample the supplied registry on every interval, forever.
+    pub async fn run_sampler<T: Clone>(self, registry: Registry<T>, interval: Duration) {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            self.sample(&registry);
+        }
+    }
+
+    /// Return the usage of the supplied tenant, which is empty if it has not yet published,
+    /// consumed or held any messages.
+    pub fn usage(&self, tenant: &str) -> Usage {
+        match self.tenants.get(tenant) {
+            Some(entry) => entry.value().usage(tenant),
+            None => Usage {
+                tenant: tenant.to_owned(),
+                quota: self.quotas.get(tenant),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Return the usage of every tenant, sorted by name.
+    pub fn list(&self) -> Vec<Usage> {
+        let mut usage = self
+            .tenants
+            .iter()
+            .map(|entry| entry.value().usage(entry.key()))
+            .collect::<Vec<_>>();
+        usage.sort_unstable_by(|a, b| a.tenant.cmp(&b.tenant));
+        usage
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage() {
+        let mm = Manager::new(
+            String::from("test"),
+            String::from("tenant"),
+            String::from("test"),
+        );
+        let tenants = Tenants::default().with_metrics(&mm).unwrap();
+        let registry: Registry<usize> = Registry::default();
+        registry
+            .create(String::from("billing/invoices"))
+            .create(String::from("a"));
+        registry.create(String::from("orders"));
+
+        for topic in ["billing/invoices", "billing/invoices", "orders"] {
+            assert!(tenants.admit(topic, 10).is_ok());
+            tenants.published(topic, 10);
+        }
+        registry.get("billing/invoices").unwrap().push(1).unwrap();
+        tenants.consumed("billing/invoices", 4);
+        tenants.sample(&registry);
+
+        let billing = tenants.usage("billing");
+        assert_eq!(billing.published_messages, 2);
+        assert_eq!(billing.published_bytes, 20);
+        assert_eq!(billing.consumed_messages, 1);
+        assert_eq!(billing.consumed_bytes, 4);
+        assert_eq!(billing.stored_messages, 1);
+        assert_eq!(tenants.usage("default").published_messages, 1);
+        assert_eq!(tenants.usage("nope").published_messages, 0);
+        let names: Vec<_> = tenants.list().into_iter().map(|u| u.tenant).collect();
+        assert_eq!(names, vec!["billing", "default"]);
+        assert_eq!(
+            tenants
+                .metrics
+                .as_ref()
+                .unwrap()
+                .published_bytes
+                .with_label_values(&["billing"])
+                .get(),
+            20
+        );
+
+        registry.delete("billing/invoices");
+        tenants.sample(&registry);
+        assert_eq!(tenants.usage("billing").stored_messages, 0);
+    }
+
+    #[test]
+    fn test_quotas() {
+        let quotas = Quotas::parse(
+            br#"{"namespaces": {
+                "billing": {"published_messages_per_sec": 2},
+                "audit": {"stored_messages": 1}
+            }}"#,
+        )
+        .unwrap();
+        let tenants = Tenants::with_quotas(quotas);
+        let registry: Registry<usize> = Registry::default();
+        registry
+            .create(String::from("audit/log"))
+            .create(String::from("a"));
+
+        assert!(tenants.admit("billing/invoices", 1).is_ok());
+        assert!(tenants.admit("billing/invoices", 1).is_ok());
+        assert!(matches!(
+            tenants.admit("billing/invoices", 1),
+            Err(Error::QuotaExceeded { tenant, quota: PUBLISHED_MESSAGES_PER_SEC, limit: 2 }) if tenant == "billing"
+        ));
+        assert_eq!(tenants.usage("billing").rejected, 1);
+        // Other tenants are unaffected.
+        for _ in 0..3 {
+            assert!(tenants.admit("orders", 1).is_ok());
+        }
+
+        assert!(tenants.admit("audit/log", 1).is_ok());
+        registry.get("audit/log").unwrap().push(1).unwrap();
+        tenants.sample(&registry);
+        assert!(matches!(
+            tenants.admit("audit/log", 1),
+            Err(Error::QuotaExceeded {
+                quota: STORED_MESSAGES,
+                ..
+            })
+        ));
+    }
+}
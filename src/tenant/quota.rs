@@ -0,0 +1,196 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::collections::HashMap;
+use std::path::Path;
+
+// crate usings
+use super::{Error, Result};
+
+// extern usings
+use serde_json::Value;
+
+/// The quota label of the published message rate quota.
+pub const PUBLISHED_MESSAGES_PER_SEC: &str = "published_messages_per_sec";
+/// The quota label of the published byte rate quota.
+pub const PUBLISHED_BYTES_PER_SEC: &str = "published_bytes_per_sec";
+/// The quota label of the stored message count quota.
+pub const STORED_MESSAGES: &str = "stored_messages";
+/// The quota label of the stored byte count quota.
+pub const STORED_BYTES: &str = "stored_bytes";
+
+/// The quotas of a single tenant, where zero is unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Quota {
+    /// The maximum count of messages published per second.
+    pub published_messages_per_sec: u64,
+    /// The maximum count of message data bytes published per second.
+    pub published_bytes_per_sec: u64,
+    /// The maximum count of unacked messages held across every subscription.
+    pub stored_messages: u64,
+    /// The maximum count of message bytes held across every subscription.
+    pub stored_bytes: u64,
+}
+
+impl Quota {
+    /// Return each quota alongside its label.
+    pub fn iter(&self) -> [(&'static str, u64); 4] {
+        [
+            (PUBLISHED_MESSAGES_PER_SEC, self.published_messages_per_sec),
+            (PUBLISHED_BYTES_PER_SEC, self.published_bytes_per_sec),
+            (STORED_MESSAGES, self.stored_messages),
+            (STORED_BYTES, self.stored_bytes),
+        ]
+    }
+
+    fn parse(value: &Value, location: &str) -> Result<Self> {
+        let fields = match value {
+            Value::Object(fields) => fields,
+            _ => return Err(invalid(location, "expected an object")),
+        };
+        let mut quota = Quota::default();
+        for (key, value) in fields {
+            let location = format!("{}.{}", location, key);
+            let field = match key.as_str() {
+                PUBLISHED_MESSAGES_PER_SEC => &mut quota.published_messages_per_sec,
+                PUBLISHED_BYTES_PER_SEC => &mut quota.published_bytes_per_sec,
+                STORED_MESSAGES => &mut quota.stored_messages,
+                STORED_BYTES => &mut quota.stored_bytes,
+                _ => return Err(invalid(&location, "unknown quota")),
+            };
+            *field = value
+                .as_u64()
+                .ok_or_else(|| invalid(&location, "expected a non-negative integer"))?;
+        }
+        Ok(quota)
+    }
+}
+
+/// The quotas enforced per tenant, where each namespace of topics is a tenant.
+///
+/// Quotas are JSON documents of the form:
+///
+/// ```json
+/// {
+///   "default": {"published_messages_per_sec": 1000, "stored_bytes": 1073741824},
+///   "namespaces": {
+///     "billing": {"published_bytes_per_sec": 1048576}
+///   }
+/// }
+/// ```
+///
+/// Namespaces listed under `namespaces` are held to their own quotas in place of the
+/// `default` ones, which every other namespace is held to. Omitted quotas are unlimited.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Quotas {
+    default: Quota,
+    namespaces: HashMap<String, Quota>,
+}
+
+impl Quotas {
+    /// Load the quotas at the supplied path.
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path).map_err(|source| Error::Io {
+            path: path.display().to_string(),
+            source,
+        })?;
+        Self::parse(&data)
+    }
+
+    /// Parse the supplied quotas.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let root: Value = serde_json::from_slice(data)?;
+        let root = match root {
+            Value::Object(root) => root,
+            _ => return Err(invalid("", "expected an object")),
+        };
+
+        let mut quotas = Quotas::default();
+        if let Some(default) = root.get("default") {
+            quotas.default = Quota::parse(default, "default")?;
+        }
+        let namespaces: Vec<_> = match root.get("namespaces") {
+            Some(Value::Object(namespaces)) => namespaces.iter().collect(),
+            Some(_) => return Err(invalid("", "'namespaces' must be an object")),
+            None => Vec::new(),
+        };
+        for (namespace, quota) in namespaces {
+            let location = format!("namespaces.{}", namespace);
+            quotas
+                .namespaces
+                .insert(namespace.clone(), Quota::parse(quota, &location)?);
+        }
+        Ok(quotas)
+    }
+
+    /// Return the quota the supplied namespace is held to.
+    pub fn get(&self, namespace: &str) -> Quota {
+        self.namespaces
+            .get(namespace)
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+fn invalid(location: &str, reason: &str) -> Error {
+    Error::Invalid {
+        location: location.to_owned(),
+        reason: reason.to_owned(),
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let quotas = Quotas::parse(
+            br#"{
+                "default": {"published_messages_per_sec": 10, "stored_bytes": 1024},
+                "namespaces": {"billing": {"stored_messages": 5}}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            quotas.get("default"),
+            Quota {
+                published_messages_per_sec: 10,
+                stored_bytes: 1024,
+                ..Quota::default()
+            }
+        );
+        assert_eq!(quotas.get("orders"), quotas.get("default"));
+        assert_eq!(
+            quotas.get("billing"),
+            Quota {
+                stored_messages: 5,
+                ..Quota::default()
+            }
+        );
+        assert_eq!(Quotas::parse(b"{}").unwrap(), Quotas::default());
+
+        let invalid = [
+            (r#"[]"#, ""),
+            (r#"{"default": 1}"#, "default"),
+            (r#"{"default": {"nope": 1}}"#, "default.nope"),
+            (
+                r#"{"default": {"stored_bytes": -1}}"#,
+                "default.stored_bytes",
+            ),
+            (r#"{"namespaces": []}"#, ""),
+            (
+                r#"{"namespaces": {"billing": {"stored_bytes": "1"}}}"#,
+                "namespaces.billing.stored_bytes",
+            ),
+        ];
+        for (src, expected) in invalid {
+            match Quotas::parse(src.as_bytes()) {
+                Err(Error::Invalid { location, .. }) => assert_eq!(location, expected, "{}", src),
+                res => panic!("expected '{}' to be invalid, got {:?}", src, res),
+            }
+        }
+    }
+}
@@ -0,0 +1,174 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::net::SocketAddr;
+
+// crate usings
+use crate::grpc::pubsub::{self, Message, PubSubServiceClient};
+use crate::grpc::subscription::{self, SubscriptionServiceClient};
+use crate::grpc::topic::{self, TopicServiceClient};
+use crate::pubsub::Registry;
+use crate::schema;
+
+// extern usings
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tonic::transport::{Channel, Server};
+
+/// A set of rift gRPC services running in-process on an ephemeral local port, backed by fresh
+/// registries, for use in integration tests.
+///
+/// The services are shut down when the fixture is dropped.
+///
+/// ```
+/// # tokio_test::block_on(async {
+/// use librift::grpc::topic::CreateRequest;
+///
+/// let fixture = librift::testing::Fixture::start().await.unwrap();
+/// let mut topics = fixture.topics().await.unwrap();
+/// let req = CreateRequest {
+///     name: String::from("orders"),
+///     schema: None,
+///     encrypted: false,
+///     default_subscription: false,
+/// };
+/// topics.create(req).await.unwrap();
+/// assert!(fixture.registry.get("orders").is_some());
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct Fixture {
+    /// The address the services are listening on.
+    pub addr: SocketAddr,
+    /// The registry backing the services, for inspecting or seeding topics directly.
+    pub registry: Registry<Message>,
+    /// The schema registry backing the services.
+    pub schemas: schema::Registry,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl Fixture {
+    /// Start the topic, subscription, and pub/sub services on an ephemeral local port.
+    pub async fn start() -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let registry = Registry::default();
+        let schemas = schema::Registry::default();
+
+        let topic_impl =
+            topic::Handler::with_registry(registry.clone()).with_schemas(schemas.clone());
+        let sub_impl = subscription::Handler::with_registry(registry.clone());
+        let pubsub_impl =
+            pubsub::Handler::with_registry(registry.clone()).with_schemas(schemas.clone());
+
+        let incoming = futures::stream::unfold(listener, |listener| async move {
+            let conn = listener.accept().await.map(|(stream, _)| stream);
+            Some((conn, listener))
+        });
+        let (shutdown, signal) = oneshot::channel::<()>();
+        tokio::spawn(
+            Server::builder()
+                .add_service(topic::TopicServiceServer::new(topic_impl))
+                .add_service(subscription::SubscriptionServiceServer::new(sub_impl))
+                .add_service(pubsub::PubSubServiceServer::new(pubsub_impl))
+                .serve_with_incoming_shutdown(incoming, async {
+                    let _ = signal.await;
+                }),
+        );
+
+        Ok(Self {
+            addr,
+            registry,
+            schemas,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    /// Connect a new channel to the services.
+    pub async fn channel(&self) -> Result<Channel, tonic::transport::Error> {
+        Channel::from_shared(format!("http://{}", self.addr))
+            .unwrap()
+            .connect()
+            .await
+    }
+
+    /// Connect a new topic service client.
+    pub async fn topics(&self) -> Result<TopicServiceClient<Channel>, tonic::transport::Error> {
+        self.channel().await.map(TopicServiceClient::new)
+    }
+
+    /// Connect a new subscription service client.
+    pub async fn subscriptions(
+        &self,
+    ) -> Result<SubscriptionServiceClient<Channel>, tonic::transport::Error> {
+        self.channel().await.map(SubscriptionServiceClient::new)
+    }
+
+    /// Connect a new pub/sub service client.
+    pub async fn pubsub(&self) -> Result<PubSubServiceClient<Channel>, tonic::transport::Error> {
+        self.channel().await.map(PubSubServiceClient::new)
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    use crate::grpc::subscription::CreateRequest as CreateSubscriptionRequest;
+    use crate::grpc::topic::CreateRequest as CreateTopicRequest;
+
+    #[test]
+    fn test_fixture() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let fixture = Fixture::start().await.unwrap();
+            let mut topics = fixture.topics().await.unwrap();
+            let mut subscriptions = fixture.subscriptions().await.unwrap();
+            let mut pubsub = fixture.pubsub().await.unwrap();
+
+            topics
+                .create(CreateTopicRequest {
+                    name: String::from("topic"),
+                    schema: None,
+                    encrypted: false,
+                    default_subscription: false,
+                })
+                .await
+                .unwrap();
+            subscriptions
+                .create(CreateSubscriptionRequest {
+                    name: String::from("sub"),
+                    topic: String::from("topic"),
+                    push: None,
+                    quota: None,
+                    filter: String::new(),
+                    max_outstanding_messages: 0,
+                    ordered: false,
+                })
+                .await
+                .unwrap();
+            pubsub
+                .publish(Message {
+                    topic: String::from("topic"),
+                    data: vec![1, 2, 3].into(),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+
+            let sub = fixture.registry.get("topic").unwrap().get("sub").unwrap();
+            let (_, _, msg) = sub.queue.next().unwrap();
+            assert_eq!(msg.data, vec![1, 2, 3]);
+        });
+    }
+}
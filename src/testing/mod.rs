@@ -0,0 +1,6 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+mod fixture;
+
+pub use fixture::Fixture;
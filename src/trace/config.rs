@@ -0,0 +1,30 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// extern usings
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, StructOpt)]
+/// Rift tracing configuration.
+pub struct Config {
+    #[structopt(
+        long = "otlp-endpoint",
+        env = "RIFT_OTLP_ENDPOINT",
+        help = "The OTLP gRPC endpoint to export traces to.",
+        long_help = "Sets the OTLP gRPC endpoint, including the scheme, to export traces to. Tracing is disabled when no endpoint is supplied.",
+        takes_value = true
+    )]
+    /// Define the OTLP endpoint to export traces to, if any.
+    pub endpoint: Option<String>,
+
+    #[structopt(
+        long = "trace-sample-rate",
+        env = "RIFT_TRACE_SAMPLE_RATE",
+        help = "The ratio of root traces to sample.",
+        long_help = "Sets the ratio, between 0.0 and 1.0, of root traces to sample. Traces with a sampled remote parent are always sampled.",
+        default_value = "1.0",
+        takes_value = true
+    )]
+    /// Define the ratio of root traces to sample.
+    pub sample_rate: f64,
+}
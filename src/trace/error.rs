@@ -0,0 +1,31 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// stdlib usings
+use std::result;
+
+// extern usings
+use opentelemetry::trace::TraceError;
+use thiserror::Error;
+
+/// Custom Result wrapper to simplify usage.
+pub type Result<T> = result::Result<T, Error>;
+
+/// Represents tracing errors based on user configuration or failures while
+/// installing the trace pipeline.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// Handles sample rates outside of the valid range.
+    #[error("invalid sample rate specified, must be between 0.0 and 1.0: {rate}")]
+    InvalidSampleRate {
+        /// rate represents the sample rate that was configured.
+        rate: f64,
+    },
+    /// Handles failures while installing the OTLP trace pipeline.
+    #[error("failed to install the OTLP trace pipeline: {source}")]
+    Pipeline {
+        /// The initial error cause.
+        #[from]
+        source: TraceError,
+    },
+}
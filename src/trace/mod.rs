@@ -0,0 +1,95 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// extern usings
+use opentelemetry::global::{self, BoxedTracer};
+use opentelemetry::sdk::propagation::TraceContextPropagator;
+use opentelemetry::sdk::trace::{self as sdktrace, Sampler};
+use opentelemetry::sdk::Resource;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+
+mod config;
+mod error;
+mod propagation;
+
+pub use self::config::Config;
+pub use self::error::{Error, Result};
+pub use self::propagation::{MetadataExtractor, MetadataInjector};
+
+/// The instrumentation name used for all spans created by riftdb.
+pub const INSTRUMENTATION_NAME: &str = "riftdb";
+
+/// Install the global W3C trace context propagator and the global OTLP trace pipeline based
+/// on the supplied configuration, returning whether or not exporting was enabled. Exporting is
+/// disabled when no endpoint is configured, in which case all local spans are no-ops but trace
+/// context is still propagated.
+pub fn init(cfg: &Config, bin: &'static str, version: &'static str) -> Result<bool> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let endpoint = match &cfg.endpoint {
+        Some(endpoint) => endpoint.clone(),
+        None => return Ok(false),
+    };
+    if !(0.0..=1.0).contains(&cfg.sample_rate) {
+        return Err(Error::InvalidSampleRate {
+            rate: cfg.sample_rate,
+        });
+    }
+
+    let sampler = Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(cfg.sample_rate)));
+    let resource = Resource::new(vec![
+        KeyValue::new("service.name", bin),
+        KeyValue::new("service.version", version),
+    ]);
+    opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint),
+        )
+        .with_trace_config(
+            sdktrace::config()
+                .with_sampler(sampler)
+                .with_resource(resource),
+        )
+        .install_batch(opentelemetry::runtime::Tokio)?;
+    Ok(true)
+}
+
+/// Flush any pending spans and shutdown the global trace pipeline.
+pub fn shutdown() {
+    global::shutdown_tracer_provider();
+}
+
+/// Return the riftdb tracer from the global trace provider.
+pub fn tracer() -> BoxedTracer {
+    global::tracer(INSTRUMENTATION_NAME)
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_init_disabled() {
+        let cfg = Config {
+            endpoint: None,
+            sample_rate: 1.0,
+        };
+        let res = init(&cfg, "test", "alpha");
+        assert!(matches!(res, Ok(false)));
+    }
+
+    #[test]
+    fn test_init_invalid_sample_rate() {
+        let cfg = Config {
+            endpoint: Some(String::from("http://127.0.0.1:4317")),
+            sample_rate: 1.5,
+        };
+        let res = init(&cfg, "test", "alpha");
+        assert!(matches!(res, Err(Error::InvalidSampleRate { .. })));
+    }
+}
@@ -0,0 +1,59 @@
+// (c) Copyright 2021-2022 Christian Saide
+// SPDX-License-Identifier: GPL-3.0-only
+
+// extern usings
+use opentelemetry::propagation::{Extractor, Injector};
+use tonic::metadata::{MetadataKey, MetadataMap, MetadataValue};
+
+/// Extracts propagated trace context, i.e. the W3C `traceparent` and `tracestate` keys, from
+/// a set of gRPC metadata.
+pub struct MetadataExtractor<'a>(pub &'a MetadataMap);
+
+impl<'a> Extractor for MetadataExtractor<'a> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0
+            .keys()
+            .filter_map(|key| match key {
+                tonic::metadata::KeyRef::Ascii(key) => Some(key.as_str()),
+                tonic::metadata::KeyRef::Binary(_) => None,
+            })
+            .collect()
+    }
+}
+
+/// Injects trace context into a set of gRPC metadata, for use with outbound gRPC calls.
+pub struct MetadataInjector<'a>(pub &'a mut MetadataMap);
+
+impl<'a> Injector for MetadataInjector<'a> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(key), Ok(value)) = (
+            MetadataKey::from_bytes(key.as_bytes()),
+            MetadataValue::from_str(&value),
+        ) {
+            self.0.insert(key, value);
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(not(tarpaulin_include))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut metadata = MetadataMap::new();
+        let mut injector = MetadataInjector(&mut metadata);
+        injector.set("traceparent", String::from("woot"));
+        injector.set("invalid key", String::from("nope"));
+
+        let extractor = MetadataExtractor(&metadata);
+        assert_eq!(extractor.get("traceparent"), Some("woot"));
+        assert_eq!(extractor.get("invalid key"), None);
+        assert_eq!(extractor.keys(), vec!["traceparent"]);
+    }
+}